@@ -0,0 +1,145 @@
+// Copyright 2019 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `alacritty bench` subcommand
+//!
+//! The results are printed to stdout as a JSON array with an object for every workload, so
+//! they can be tracked by CI.
+use std::io::{self, Write};
+use std::process;
+
+use serde_json::{json, Value};
+
+use alacritty_terminal::benchmark::{self, Report, Workload, WORKLOADS};
+
+use crate::cli::Benchmark;
+
+/// Run the selected workloads and print their results
+pub fn run(options: &Benchmark) -> ! {
+    let workloads = match workloads(options) {
+        Ok(workloads) => workloads,
+        Err(err) => {
+            let _ = writeln!(io::stderr(), "{}", err);
+            process::exit(1);
+        },
+    };
+
+    let reports: Vec<Value> = workloads
+        .iter()
+        .map(|workload| to_json(&benchmark::run(workload, options.iterations)))
+        .collect();
+
+    let _ = writeln!(io::stdout(), "{}", Value::Array(reports));
+    process::exit(0);
+}
+
+/// Load the workloads selected on the command line
+///
+/// All bundled workloads are run, unless workloads or recordings have been selected.
+fn workloads(options: &Benchmark) -> Result<Vec<Workload>, String> {
+    let mut names: Vec<&str> = options.workloads.iter().map(String::as_str).collect();
+    if names.is_empty() && options.recordings.is_empty() {
+        names = WORKLOADS.to_vec();
+    }
+
+    let mut workloads = Vec::new();
+    for name in names {
+        let workload =
+            Workload::bundled(name).ok_or_else(|| format!("Unknown workload {}", name))?;
+        workloads.push(workload);
+    }
+
+    for path in &options.recordings {
+        let workload = Workload::from_recording(path)
+            .map_err(|err| format!("Unable to read recording {:?}: {}", path, err))?;
+        workloads.push(workload);
+    }
+
+    Ok(workloads)
+}
+
+fn to_json(report: &Report) -> Value {
+    json!({
+        "name": report.name,
+        "bytes": report.bytes,
+        "cells": report.cells,
+        "iterations": report.iterations,
+        "seconds": report.seconds(),
+        "bytes_per_second": report.bytes_per_second(),
+        "cells_per_second": report.cells_per_second(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use alacritty_terminal::benchmark::{Report, WORKLOADS};
+
+    use super::{to_json, workloads};
+    use crate::cli::Benchmark;
+
+    fn benchmark(workloads: &[&str], recordings: &[&str]) -> Benchmark {
+        Benchmark {
+            workloads: workloads.iter().map(|&name| name.to_owned()).collect(),
+            recordings: recordings.iter().map(PathBuf::from).collect(),
+            iterations: 1,
+        }
+    }
+
+    fn names(options: &Benchmark) -> Vec<String> {
+        workloads(options).unwrap().into_iter().map(|workload| workload.name).collect()
+    }
+
+    #[test]
+    fn all_bundled_workloads_by_default() {
+        assert_eq!(names(&benchmark(&[], &[])), WORKLOADS);
+    }
+
+    #[test]
+    fn selected_workloads() {
+        let recording = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../alacritty_terminal/tests/ref/vttest_scroll/alacritty.recording"
+        );
+
+        assert_eq!(names(&benchmark(&["utf8"], &[])), vec!["utf8"]);
+        assert_eq!(names(&benchmark(&[], &[recording])), vec!["vttest_scroll"]);
+        assert_eq!(names(&benchmark(&["sgr"], &[recording])), vec!["sgr", "vttest_scroll"]);
+    }
+
+    #[test]
+    fn missing_recording() {
+        assert!(workloads(&benchmark(&[], &["/nonexistent.recording"])).is_err());
+    }
+
+    #[test]
+    fn report_json() {
+        let report = Report {
+            name: "ascii".into(),
+            bytes: 4096,
+            cells: 1024,
+            iterations: 2,
+            duration: Duration::from_millis(500),
+        };
+
+        let json = to_json(&report);
+        assert_eq!(json["name"], "ascii");
+        assert_eq!(json["iterations"], 2);
+        assert_eq!(json["seconds"], 0.5);
+        assert_eq!(json["bytes_per_second"], 8192.);
+        assert_eq!(json["cells_per_second"], 2048.);
+    }
+}
@@ -14,16 +14,24 @@
 
 use std::borrow::Cow;
 use std::cmp::max;
+use std::env;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 
-use clap::{crate_authors, crate_description, crate_name, crate_version, App, Arg};
+use clap::{crate_authors, crate_description, crate_name, crate_version, App, Arg, SubCommand};
+#[cfg(unix)]
+use clap::{AppSettings, ArgMatches};
 use log::{self, LevelFilter};
 
-use alacritty_terminal::config::{Config, Delta, Dimensions, Shell};
+use alacritty_terminal::benchmark::WORKLOADS;
+use alacritty_terminal::config::{Config, ConfigOverride, Delta, Dimensions, Shell, DEFAULT_NAME};
 use alacritty_terminal::index::{Column, Line};
-use alacritty_terminal::window::DEFAULT_NAME;
+
+#[cfg(unix)]
+use crate::ipc::Message;
 
 /// Options specified on the command line
+#[derive(Clone)]
 pub struct Options {
     pub live_config_reload: Option<bool>,
     pub print_events: bool,
@@ -34,9 +42,28 @@ pub struct Options {
     pub class: Option<String>,
     pub log_level: LevelFilter,
     pub command: Option<Shell<'static>>,
+    pub hold: bool,
     pub working_dir: Option<PathBuf>,
     pub config: Option<PathBuf>,
     pub persistent_logging: bool,
+    pub config_options: Vec<ConfigOverride>,
+
+    /// X11 window which the first window is embedded into
+    pub embed: Option<u64>,
+
+    /// Benchmark the parser instead of starting a terminal
+    pub benchmark: Option<Benchmark>,
+
+    /// Migrate the config file instead of starting a terminal
+    pub migrate_config: bool,
+
+    /// Message for a running instance, sent instead of starting a new one
+    #[cfg(unix)]
+    pub message: Option<Message>,
+
+    /// Socket of the instance receiving the message
+    #[cfg(unix)]
+    pub socket: Option<PathBuf>,
 }
 
 impl Default for Options {
@@ -51,9 +78,18 @@ impl Default for Options {
             class: None,
             log_level: LevelFilter::Warn,
             command: None,
+            hold: false,
             working_dir: None,
             config: None,
             persistent_logging: false,
+            config_options: Vec::new(),
+            embed: None,
+            benchmark: None,
+            migrate_config: false,
+            #[cfg(unix)]
+            message: None,
+            #[cfg(unix)]
+            socket: None,
         }
     }
 }
@@ -61,6 +97,13 @@ impl Default for Options {
 impl Options {
     /// Build `Options` from command line arguments.
     pub fn new() -> Self {
+        Self::from_args(env::args_os())
+    }
+
+    /// Build `Options` from a list of arguments, starting with the program name
+    fn from_args<I: IntoIterator<Item = OsString>>(args: I) -> Self {
+        let (args, command) = split_command(args.into_iter().collect());
+
         let mut version = crate_version!().to_owned();
         let commit_hash = env!("GIT_HASH");
         if !commit_hash.is_empty() {
@@ -69,7 +112,7 @@ impl Options {
 
         let mut options = Options::default();
 
-        let matches = App::new(crate_name!())
+        let app = App::new(crate_name!())
             .version(version.as_str())
             .author(crate_authors!("\n"))
             .about(crate_description!())
@@ -126,7 +169,20 @@ impl Options {
                 Arg::with_name("class")
                     .long("class")
                     .takes_value(true)
-                    .help(&format!("Defines window class on Linux [default: {}]", DEFAULT_NAME)),
+                    .value_name("instance[,general]")
+                    .help(&format!(
+                        "Defines window class and Wayland app_id on Linux, also used as title \
+                         when no title is set [default: {}]",
+                        DEFAULT_NAME
+                    )),
+            )
+            .arg(
+                Arg::with_name("embed")
+                    .long("embed")
+                    .takes_value(true)
+                    .value_name("window-id")
+                    .validator(|id| parse_window_id(&id).map(|_| ()))
+                    .help("Embed the window into the X11 window with this ID"),
             )
             .arg(
                 Arg::with_name("q")
@@ -152,6 +208,27 @@ impl Options {
                 "Specify alternative configuration file [default: \
                  $XDG_CONFIG_HOME/alacritty/alacritty.yml]",
             ))
+            .arg(
+                Arg::with_name("option")
+                    .long("option")
+                    .short("o")
+                    .multiple(true)
+                    .takes_value(true)
+                    .number_of_values(1)
+                    .value_name("path=value")
+                    .validator(|option| {
+                        option
+                            .parse::<ConfigOverride>()
+                            .map(|_| ())
+                            .map_err(|err| format!("`{}`: {}", option, err))
+                    })
+                    .help("Override a config file option, like `font.size=14`"),
+            )
+            .arg(
+                Arg::with_name("hold")
+                    .long("hold")
+                    .help("Remain open after the command exits, requires closing the window"),
+            )
             .arg(
                 Arg::with_name("command")
                     .long("command")
@@ -161,8 +238,19 @@ impl Options {
                     .min_values(1)
                     .allow_hyphen_values(true)
                     .help("Command and args to execute (must be last argument)"),
-            )
-            .get_matches();
+            );
+
+        let app = app.subcommand(bench_subcommand()).subcommand(
+            SubCommand::with_name("migrate-config").about(
+                "Write the config file with renamed options updated next to it, as \
+                 <name>.migrated.yml",
+            ),
+        );
+
+        #[cfg(unix)]
+        let app = app.subcommand(msg_subcommand());
+
+        let matches = app.get_matches_from(args);
 
         if matches.is_present("ref-test") {
             options.ref_test = true;
@@ -182,6 +270,8 @@ impl Options {
             options.persistent_logging = true;
         }
 
+        options.hold = matches.is_present("hold");
+
         if let Some(mut dimensions) = matches.values_of("dimensions") {
             let width = dimensions.next().map(|w| w.parse().map(Column));
             let height = dimensions.next().map(|h| h.parse().map(Line));
@@ -200,6 +290,7 @@ impl Options {
 
         options.class = matches.value_of("class").map(ToOwned::to_owned);
         options.title = matches.value_of("title").map(ToOwned::to_owned);
+        options.embed = matches.value_of("embed").and_then(|id| parse_window_id(id).ok());
 
         match matches.occurrences_of("q") {
             0 => {},
@@ -222,13 +313,39 @@ impl Options {
             options.config = Some(PathBuf::from(path.to_string()));
         }
 
-        if let Some(mut args) = matches.values_of("command") {
-            // The following unwrap is guaranteed to succeed.
-            // If 'command' exists it must also have a first item since
-            // Arg::min_values(1) is set.
-            let command = String::from(args.next().unwrap());
-            let args = args.map(String::from).collect();
-            options.command = Some(Shell::new_with_args(command, args));
+        if let Some(config_options) = matches.values_of("option") {
+            options.config_options =
+                config_options.filter_map(|option| option.parse().ok()).collect();
+        }
+
+        if let Some(mut args) = command {
+            let program = args.remove(0);
+            let mut command = Shell::new_with_args(program, args);
+
+            // Commands are never login shells, even where the configured shell is
+            command.set_login(false);
+
+            options.command = Some(command);
+        }
+
+        if let Some(matches) = matches.subcommand_matches("bench") {
+            let recordings = matches.values_of_os("recording").into_iter().flatten();
+            let iterations = matches.value_of("iterations").and_then(|n| n.parse().ok());
+            options.benchmark = Some(Benchmark {
+                workloads: matches.values_of_lossy("workload").unwrap_or_default(),
+                recordings: recordings.map(PathBuf::from).collect(),
+                iterations: iterations.unwrap_or(1),
+            });
+        }
+
+        options.migrate_config = matches.subcommand_matches("migrate-config").is_some();
+
+        #[cfg(unix)]
+        {
+            if let Some(matches) = matches.subcommand_matches("msg") {
+                options.socket = matches.value_of("socket").map(PathBuf::from);
+                options.message = message_from_matches(matches);
+            }
         }
 
         options
@@ -246,10 +363,14 @@ impl Options {
             self.working_dir.or_else(|| config.working_directory().to_owned()),
         );
         config.shell = self.command.or(config.shell);
+        config.hold = self.hold;
 
-        config.window.dimensions = self.dimensions.unwrap_or(config.window.dimensions);
+        config.window.cli_dimensions = self.dimensions;
         config.window.position = self.position.or(config.window.position);
         config.window.title = self.title.or(config.window.title);
+        config.window.embed = self.embed;
+
+        config.set_dynamic_title(config.dynamic_title() && config.window.title.is_none());
 
         if let Some(class) = self.class {
             let parts: Vec<_> = class.split(',').collect();
@@ -257,9 +378,12 @@ impl Options {
             if let Some(&general) = parts.get(1) {
                 config.window.class.general = general.into();
             }
-        }
 
-        config.set_dynamic_title(config.dynamic_title() && config.window.title.is_none());
+            // Use the class as initial title, without disabling dynamic titles
+            if config.window.title.is_none() {
+                config.window.title = Some(config.window.class.instance.clone());
+            }
+        }
 
         config.debug.print_events = self.print_events || config.debug.print_events;
         config.debug.log_level = max(config.debug.log_level, self.log_level);
@@ -273,11 +397,177 @@ impl Options {
     }
 }
 
+/// Split the arguments of `-e`/`--command` off the command line
+///
+/// Everything after the first `-e` or `--command` is passed to the command verbatim, even when
+/// it looks like an option of Alacritty or is `--`. Without any arguments following it, the
+/// flag is left on the command line for the parser to report the missing command.
+fn split_command(mut args: Vec<OsString>) -> (Vec<OsString>, Option<Vec<String>>) {
+    let index = args.iter().skip(1).position(|arg| arg == "-e" || arg == "--command");
+    match index.map(|index| index + 1) {
+        Some(index) if index + 1 < args.len() => {
+            let command = args.split_off(index + 1);
+            args.truncate(index);
+            let command = command.iter().map(|arg| arg.to_string_lossy().into_owned()).collect();
+            (args, Some(command))
+        },
+        _ => (args, None),
+    }
+}
+
+/// Parse the ID of the X11 window passed to `--embed`
+///
+/// IDs are accepted in decimal and in hexadecimal with a `0x` prefix, the way
+/// `xwininfo` prints them.
+fn parse_window_id(id: &str) -> Result<u64, String> {
+    if cfg!(any(target_os = "macos", windows)) {
+        return Err("embedding is only supported on X11".into());
+    }
+
+    let parsed = if id.starts_with("0x") || id.starts_with("0X") {
+        u64::from_str_radix(&id[2..], 16)
+    } else {
+        id.parse()
+    };
+
+    match parsed {
+        Ok(0) | Err(_) => Err(format!("`{}` is not a valid window ID", id)),
+        Ok(id) => Ok(id),
+    }
+}
+
+/// Workloads selected by the `bench` subcommand
+#[derive(Clone, Debug, PartialEq)]
+pub struct Benchmark {
+    /// Names of bundled workloads
+    pub workloads: Vec<String>,
+
+    /// Recordings of program output which are replayed as workloads
+    pub recordings: Vec<PathBuf>,
+
+    /// Number of times each workload is parsed
+    pub iterations: usize,
+}
+
+/// Subcommand for measuring the throughput of the parser and terminal
+fn bench_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("bench")
+        .about("Measure the throughput of the parser, printing the results as JSON")
+        .arg(
+            Arg::with_name("workload")
+                .long("workload")
+                .short("w")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .possible_values(WORKLOADS)
+                .help("Bundled workload to run [default: all unless recordings are given]"),
+        )
+        .arg(
+            Arg::with_name("recording")
+                .long("recording")
+                .short("r")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_name("file")
+                .help("Replay a recording like the ones of `--ref-test`"),
+        )
+        .arg(
+            Arg::with_name("iterations")
+                .long("iterations")
+                .short("n")
+                .takes_value(true)
+                .default_value("10")
+                .validator(|n| match n.parse::<usize>() {
+                    Ok(n) if n > 0 => Ok(()),
+                    _ => Err(format!("`{}` is not a positive number", n)),
+                })
+                .help("Number of times each workload is parsed"),
+        )
+}
+
+/// Subcommand for sending a message to a running instance
+#[cfg(unix)]
+fn msg_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("msg")
+        .about("Send a message to a running Alacritty instance")
+        .arg(
+            Arg::with_name("socket")
+                .long("socket")
+                .short("s")
+                .takes_value(true)
+                .help("Socket of the instance [default: $ALACRITTY_SOCKET]"),
+        )
+        .subcommand(SubCommand::with_name("create-window").about("Open a new window"))
+        .subcommand(
+            SubCommand::with_name("config").about("Override config options").arg(
+                Arg::with_name("options")
+                    .required(true)
+                    .multiple(true)
+                    .value_name("path=value")
+                    .validator(|option| {
+                        option
+                            .parse::<ConfigOverride>()
+                            .map(|_| ())
+                            .map_err(|err| format!("`{}`: {}", option, err))
+                    })
+                    .help("Config options, like `font.size=14`"),
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("font-size").about("Change the font size of all windows").arg(
+                Arg::with_name("delta")
+                    .required(true)
+                    .allow_hyphen_values(true)
+                    .validator(|delta| {
+                        if delta == "reset" || delta.parse::<f32>().is_ok() {
+                            Ok(())
+                        } else {
+                            Err(format!("`{}` is neither a number nor `reset`", delta))
+                        }
+                    })
+                    .help("Change in points, like `+2` or `-1`, or `reset`"),
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("color-scheme")
+                .about("Select a color scheme in all windows")
+                .arg(Arg::with_name("name").required(true).help("Name of the color scheme")),
+        )
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+}
+
+/// Message selected by the arguments of the `msg` subcommand
+#[cfg(unix)]
+fn message_from_matches(matches: &ArgMatches<'_>) -> Option<Message> {
+    match matches.subcommand() {
+        ("create-window", _) => Some(Message::CreateWindow),
+        ("config", Some(matches)) => {
+            let options = matches.values_of("options")?;
+            Some(Message::Config(options.filter_map(|option| option.parse().ok()).collect()))
+        },
+        ("font-size", Some(matches)) => match matches.value_of("delta")? {
+            "reset" => Some(Message::ResetFontSize),
+            delta => delta.parse().ok().map(Message::ChangeFontSize),
+        },
+        ("color-scheme", Some(matches)) => {
+            matches.value_of("name").map(|name| Message::SetColorScheme(name.to_owned()))
+        },
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use alacritty_terminal::config::{Config, DEFAULT_ALACRITTY_CONFIG};
+    use std::path::PathBuf;
+
+    use alacritty_terminal::config::{Config, Dimensions, DEFAULT_ALACRITTY_CONFIG};
+    use alacritty_terminal::index::{Column, Line};
 
-    use crate::cli::Options;
+    use std::ffi::OsString;
+
+    use crate::cli::{parse_window_id, Options};
 
     #[test]
     fn dynamic_title_ignoring_options_by_default() {
@@ -312,4 +602,250 @@ mod test {
 
         assert!(!config.dynamic_title());
     }
+
+    #[test]
+    fn class_overrides_config() {
+        let mut config: Config =
+            ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
+        config.window.class.instance = "config_instance".into();
+        config.window.class.general = "config_general".into();
+
+        let mut options = Options::default();
+        options.class = Some("foo,bar".to_owned());
+        let config = options.into_config(config);
+
+        assert_eq!(config.window.class.instance, "foo");
+        assert_eq!(config.window.class.general, "bar");
+    }
+
+    #[test]
+    fn class_instance_keeps_general_from_config() {
+        let mut config: Config =
+            ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
+        config.window.class.general = "config_general".into();
+
+        let mut options = Options::default();
+        options.class = Some("foo".to_owned());
+        let config = options.into_config(config);
+
+        assert_eq!(config.window.class.instance, "foo");
+        assert_eq!(config.window.class.general, "config_general");
+    }
+
+    #[test]
+    fn class_sets_default_title() {
+        let config: Config =
+            ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
+        let old_dynamic_title = config.dynamic_title();
+
+        let mut options = Options::default();
+        options.class = Some("foo,bar".to_owned());
+        let config = options.into_config(config);
+
+        assert_eq!(config.window.title, Some("foo".to_owned()));
+        assert_eq!(config.dynamic_title(), old_dynamic_title);
+    }
+
+    #[test]
+    fn class_keeps_explicit_title() {
+        let mut config: Config =
+            ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
+        config.window.title = Some("config".to_owned());
+
+        let mut options = Options::default();
+        options.class = Some("foo".to_owned());
+        let config = options.into_config(config);
+
+        assert_eq!(config.window.title, Some("config".to_owned()));
+
+        let mut options = Options::default();
+        options.class = Some("foo".to_owned());
+        options.title = Some("cli".to_owned());
+        let config = options.into_config(config);
+
+        assert_eq!(config.window.title, Some("cli".to_owned()));
+    }
+
+    #[test]
+    fn working_dir_overrides_config() {
+        let mut config: Config =
+            ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
+        config.set_working_directory(Some(PathBuf::from("/config")));
+
+        let mut options = Options::default();
+        options.working_dir = Some(PathBuf::from("/cli"));
+        let config = options.into_config(config);
+
+        assert_eq!(config.working_directory(), &Some(PathBuf::from("/cli")));
+    }
+
+    #[test]
+    fn working_dir_falls_back_to_config() {
+        let mut config: Config =
+            ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
+        config.set_working_directory(Some(PathBuf::from("/config")));
+
+        let config = Options::default().into_config(config);
+
+        assert_eq!(config.working_directory(), &Some(PathBuf::from("/config")));
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn window_id_decimal() {
+        assert_eq!(parse_window_id("29360130"), Ok(29_360_130));
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn window_id_hexadecimal() {
+        assert_eq!(parse_window_id("0x1c00002"), Ok(0x1c0_0002));
+        assert_eq!(parse_window_id("0X1C00002"), Ok(0x1c0_0002));
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn window_id_invalid() {
+        assert!(parse_window_id("").is_err());
+        assert!(parse_window_id("0x").is_err());
+        assert!(parse_window_id("0").is_err());
+        assert!(parse_window_id("-1").is_err());
+        assert!(parse_window_id("1c00002").is_err());
+        assert!(parse_window_id("0x1c00002z").is_err());
+    }
+
+    #[test]
+    #[cfg(any(target_os = "macos", windows))]
+    fn window_id_unsupported() {
+        assert_eq!(
+            parse_window_id("0x1c00002"),
+            Err("embedding is only supported on X11".to_owned())
+        );
+    }
+
+    #[test]
+    fn embed_sets_window_config() {
+        let config: Config =
+            ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
+        assert_eq!(config.window.embed, None);
+
+        let mut options = Options::default();
+        options.embed = Some(0x1c0_0002);
+        let config = options.into_config(config);
+
+        assert_eq!(config.window.embed, Some(0x1c0_0002));
+    }
+
+    fn options(args: &[&str]) -> Options {
+        let args = ["alacritty"].iter().chain(args).map(OsString::from);
+        Options::from_args(args.collect::<Vec<_>>())
+    }
+
+    fn command_args(options: &Options) -> (String, Vec<String>) {
+        let command = options.command.as_ref().expect("command");
+        (command.program.to_string(), command.args.clone())
+    }
+
+    #[test]
+    fn command_keeps_hyphenated_args() {
+        let options = options(&["-e", "rg", "--hidden", "-t", "rust", "foo"]);
+
+        let (program, args) = command_args(&options);
+        assert_eq!(program, "rg");
+        assert_eq!(args, vec!["--hidden", "-t", "rust", "foo"]);
+        assert_eq!(options.title, None);
+    }
+
+    #[test]
+    fn command_keeps_double_dash() {
+        let options = options(&["--command", "git", "log", "--", "-e", "msg"]);
+
+        let (program, args) = command_args(&options);
+        assert_eq!(program, "git");
+        assert_eq!(args, vec!["log", "--", "-e", "msg"]);
+    }
+
+    #[test]
+    fn command_after_options() {
+        let options = options(&["--hold", "-t", "foo", "-e", "vim", "-t", "bar"]);
+
+        let (program, args) = command_args(&options);
+        assert_eq!(program, "vim");
+        assert_eq!(args, vec!["-t", "bar"]);
+        assert_eq!(options.title, Some("foo".to_owned()));
+        assert!(options.hold);
+    }
+
+    #[test]
+    fn command_is_not_a_login_shell() {
+        let options = options(&["-e", "sh"]);
+
+        let command = options.command.expect("command");
+        assert!(!command.login());
+        assert!(command.args.is_empty());
+    }
+
+    #[test]
+    fn no_command() {
+        let options = options(&["--hold"]);
+
+        assert!(options.command.is_none());
+        assert!(options.hold);
+    }
+
+    #[test]
+    fn bench_defaults() {
+        let benchmark = options(&["bench"]).benchmark.expect("benchmark");
+
+        assert!(benchmark.workloads.is_empty());
+        assert!(benchmark.recordings.is_empty());
+        assert_eq!(benchmark.iterations, 10);
+    }
+
+    #[test]
+    fn bench_selects_workloads() {
+        let args = ["bench", "-w", "sgr", "--workload", "utf8", "-r", "ll.recording", "-n", "3"];
+        let benchmark = options(&args).benchmark.expect("benchmark");
+
+        assert_eq!(benchmark.workloads, vec!["sgr", "utf8"]);
+        assert_eq!(benchmark.recordings, vec![PathBuf::from("ll.recording")]);
+        assert_eq!(benchmark.iterations, 3);
+    }
+
+    #[test]
+    fn migrate_config() {
+        let migrate = options(&["--config-file", "old.yml", "migrate-config"]);
+        assert!(migrate.migrate_config);
+        assert_eq!(migrate.config, Some(PathBuf::from("old.yml")));
+        assert!(!options(&[]).migrate_config);
+    }
+
+    #[test]
+    fn no_bench() {
+        assert_eq!(options(&["--hold"]).benchmark, None);
+    }
+
+    #[test]
+    fn dimensions_override_config() {
+        let config: Config =
+            ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
+
+        let config = options(&["--dimensions", "100", "30"]).into_config(config);
+
+        assert_eq!(config.window.cli_dimensions, Some(Dimensions::new(Column(100), Line(30))));
+        assert_eq!(config.window.dimensions(), Dimensions::new(Column(100), Line(30)));
+    }
+
+    #[test]
+    fn hold_sets_config() {
+        let config: Config =
+            ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
+        assert!(!config.hold);
+
+        let mut options = Options::default();
+        options.hold = true;
+        let config = options.into_config(config);
+
+        assert!(config.hold);
+    }
 }
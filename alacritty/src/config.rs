@@ -3,18 +3,33 @@ use std::env;
 use std::fs::File;
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::ptr;
 
 #[cfg(windows)]
 use dirs;
 use log::{error, warn};
-use serde_yaml;
+use serde_yaml::{self, Mapping, Value};
 #[cfg(not(windows))]
 use xdg;
 
-use alacritty_terminal::config::{Config, DEFAULT_ALACRITTY_CONFIG};
+use alacritty_terminal::config::{
+    apply_overrides, collect_unknown_fields, merge_platform, migrate, rejected_overrides, Config,
+    ConfigOverride, Migrated, DEFAULT_ALACRITTY_CONFIG,
+};
+use alacritty_terminal::message_bar::Message;
+use alacritty_terminal::term::color::{self, Rgb};
 
 pub const SOURCE_FILE_PATH: &str = file!();
 
+/// Log target for problems with the config file
+///
+/// These are put in the message bar by the config loader, so the logger doesn't show them again.
+pub const LOG_TARGET: &str = "alacritty_config";
+
+const OPEN_CONFIG_HINT: &str = "Click this message to open the config file";
+
+const MIGRATE_CONFIG_HINT: &str = "Run `alacritty migrate-config` to update the config file";
+
 /// Result from config loading
 pub type Result<T> = ::std::result::Result<T, Error>;
 
@@ -159,23 +174,75 @@ pub fn write_defaults() -> io::Result<Cow<'static, Path>> {
     Ok(path.into())
 }
 
-pub fn load_from(path: PathBuf) -> Config {
-    let mut config = reload_from(&path).unwrap_or_else(|_| Config::default());
+pub fn load_from(
+    path: PathBuf,
+    overrides: &[ConfigOverride],
+    messages: &mut Vec<Message>,
+) -> Config {
+    let mut config =
+        reload_from(&path, overrides, messages).unwrap_or_else(|_| load_default(overrides));
     config.config_path = Some(path);
     config
 }
 
-pub fn reload_from(path: &PathBuf) -> Result<Config> {
-    match read_config(path) {
-        Ok(config) => Ok(config),
-        Err(err) => {
-            error!("Unable to load config {:?}: {}", path, err);
-            Err(err)
-        },
+/// Default config with the command line overrides applied
+pub fn load_default(overrides: &[ConfigOverride]) -> Config {
+    deserialize(Value::Mapping(Mapping::new()), overrides).unwrap_or_else(|_| Config::default())
+}
+
+/// Check that all `new` overrides can be applied on top of the config file and `overrides`
+///
+/// Problems of the config file itself are not reported here, they show up once it's reloaded.
+pub fn check_overrides(
+    path: Option<&Path>,
+    overrides: &[ConfigOverride],
+    new: &[ConfigOverride],
+) -> ::std::result::Result<(), String> {
+    let tree = path
+        .and_then(|path| read_config(path).ok())
+        .filter(|contents| !contents.is_empty())
+        .and_then(|contents| serde_yaml::from_str(&contents).ok())
+        .unwrap_or_else(|| Value::Mapping(Mapping::new()));
+
+    let mut tree = merge_platform(tree);
+    migrate(&mut tree);
+
+    let all: Vec<ConfigOverride> = overrides.iter().chain(new).cloned().collect();
+    let rejected = match rejected_overrides(tree, &all) {
+        Ok(rejected) => rejected,
+        Err(_) => return Ok(()),
+    };
+
+    // Earlier overrides have already been accepted
+    let all_new = &all[overrides.len()..];
+    match rejected.iter().find(|(option, _)| all_new.iter().any(|new| ptr::eq(new, *option))) {
+        Some((option, err)) => Err(format!("`{}`: {}", option, err)),
+        None => Ok(()),
     }
 }
 
-fn read_config(path: &PathBuf) -> Result<Config> {
+/// Load the config file, problems with it are added to `messages` for the message bar
+pub fn reload_from(
+    path: &PathBuf,
+    overrides: &[ConfigOverride],
+    messages: &mut Vec<Message>,
+) -> Result<Config> {
+    let result =
+        read_config(path).and_then(|contents| parse_config(&contents, path, overrides, messages));
+
+    if let Err(err) = &result {
+        let text = match err {
+            Error::Yaml(err) => yaml_error_message(path, err),
+            err => format!("Unable to load config {:?}: {}", path, err),
+        };
+        error!(target: LOG_TARGET, "{}", text);
+        messages.push(config_message(path, text, color::RED));
+    }
+
+    result
+}
+
+fn read_config(path: &Path) -> Result<String> {
     let mut contents = String::new();
     File::open(path)?.read_to_string(&mut contents)?;
 
@@ -184,32 +251,279 @@ fn read_config(path: &PathBuf) -> Result<Config> {
         contents = contents.split_off(3);
     }
 
+    Ok(contents)
+}
+
+fn parse_config(
+    contents: &str,
+    path: &Path,
+    overrides: &[ConfigOverride],
+    messages: &mut Vec<Message>,
+) -> Result<Config> {
     // Prevent parsing error with empty string
-    if contents.is_empty() {
-        return Ok(Config::default());
+    let tree = if contents.is_empty() {
+        Value::Mapping(Mapping::new())
+    } else {
+        serde_yaml::from_str(contents)?
+    };
+
+    // Platform sections are merged first, so options of other platforms are never validated
+    let mut tree = merge_platform(tree);
+
+    // Renamed options are moved before they could be reported as unknown
+    let migrated = migrate(&mut tree);
+
+    let (config, unknown) = collect_unknown_fields(|| deserialize(tree, overrides));
+    let config = config?;
+
+    if !migrated.is_empty() {
+        let mut lines: Vec<_> =
+            migrated.iter().map(|migrated| migration_warning(contents, path, migrated)).collect();
+        lines.push(MIGRATE_CONFIG_HINT.into());
+        let text = lines.join("\n");
+        warn!(target: LOG_TARGET, "{}", text);
+        messages.push(config_message(path, text, color::YELLOW));
+    }
+
+    // All unknown fields are reported at once, to not flood the message bar with typos
+    if !unknown.is_empty() {
+        let name = file_name(path);
+        let text = unknown
+            .iter()
+            .map(|field| match field_line(contents, field) {
+                Some(line) => {
+                    format!("config warning at {}:{}: unknown field `{}`", name, line, field)
+                },
+                None => format!("config warning in {}: unknown field `{}`", name, field),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        warn!(target: LOG_TARGET, "{}", text);
+        messages.push(config_message(path, text, color::YELLOW));
+    }
+
+    Ok(config)
+}
+
+/// Migrate the config file at `path`, writing the result to a new file next to it
+///
+/// The new file is only written if any options had to be migrated, it doesn't keep the comments
+/// of the original file.
+pub fn migrate_file(path: &Path) -> Result<(PathBuf, Vec<Migrated>)> {
+    let contents = read_config(path)?;
+    let mut tree = if contents.is_empty() {
+        Value::Mapping(Mapping::new())
+    } else {
+        serde_yaml::from_str(&contents)?
+    };
+
+    let migrated = migrate(&mut tree);
+    let target = path.with_extension("migrated.yml");
+    if !migrated.is_empty() {
+        File::create(&target)?.write_all(serde_yaml::to_string(&tree)?.as_bytes())?;
     }
 
-    let config = serde_yaml::from_str(&contents)?;
+    Ok((target, migrated))
+}
+
+/// Deserialize the config with the command line overrides applied on top
+fn deserialize(tree: Value, overrides: &[ConfigOverride]) -> Result<Config> {
+    let (config, rejected) = apply_overrides(tree, overrides)?;
 
-    print_deprecation_warnings(&config);
+    for (option, err) in rejected {
+        error!("Ignoring option `-o {}`: {}", option, err);
+    }
 
     Ok(config)
 }
 
-fn print_deprecation_warnings(config: &Config) {
-    if config.window.start_maximized.is_some() {
-        warn!(
-            "Config window.start_maximized is deprecated; please use window.startup_mode instead"
-        );
+/// Describe a migrated option with the line it has been found in
+fn migration_warning(contents: &str, path: &Path, migrated: &Migrated) -> String {
+    let name = file_name(path);
+    match field_line(contents, migrated.field()) {
+        Some(line) => format!("config warning at {}:{}: {}", name, line, migrated),
+        None => format!("config warning in {}: {}", name, migrated),
     }
+}
+
+/// Message bar entry about a problem in the config file, clicking it opens the file
+fn config_message(path: &Path, text: String, color: Rgb) -> Message {
+    let mut message = Message::new(format!("{}\n{}", text, OPEN_CONFIG_HINT), color);
+    message.set_topic(SOURCE_FILE_PATH.into());
+    message.set_target(path.to_owned());
+    message
+}
+
+/// Describe a YAML error with the line it occurred in
+fn yaml_error_message(path: &Path, err: &serde_yaml::Error) -> String {
+    let mut text = err.to_string();
 
-    if config.render_timer.is_some() {
-        warn!("Config render_timer is deprecated; please use debug.render_timer instead");
+    match err.location() {
+        Some(location) => {
+            // Only keep the line, which is moved in front of the message
+            if let Some(index) = text.rfind(" at line ") {
+                text.truncate(index);
+            }
+            format!("config error at {}:{}: {}", file_name(path), location.line(), text)
+        },
+        None => format!("config error in {}: {}", file_name(path), text),
     }
+}
 
-    if config.persistent_logging.is_some() {
-        warn!(
-            "Config persistent_logging is deprecated; please use debug.persistent_logging instead"
+/// Find the line of a field like `font.size` by searching for each of its keys in order
+///
+/// This doesn't parse the YAML, so it's only a best effort for block mappings.
+fn field_line(contents: &str, field: &str) -> Option<usize> {
+    let mut lines = contents.lines().enumerate();
+    let mut line = None;
+
+    for key in field.split('.') {
+        let prefix = format!("{}:", key);
+        let (index, _) = lines.find(|(_, text)| text.trim_start().starts_with(&prefix))?;
+        line = Some(index + 1);
+    }
+
+    line
+}
+
+fn file_name(path: &Path) -> Cow<'_, str> {
+    path.file_name().unwrap_or_else(|| path.as_os_str()).to_string_lossy()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use alacritty_terminal::config::ConfigOverride;
+    use alacritty_terminal::message_bar::Message;
+    use alacritty_terminal::term::color::{self, Rgb};
+
+    use super::{
+        config_message, migrate_file, parse_config, reload_from, yaml_error_message, Error,
+    };
+
+    /// Messages produced by loading `contents` as `alacritty.yml`
+    fn messages(contents: &str) -> Vec<Message> {
+        let mut messages = Vec::new();
+        let _ = parse_config(contents, Path::new("alacritty.yml"), &[], &mut messages);
+        messages
+    }
+
+    fn message(text: &str, color: Rgb) -> Message {
+        config_message(Path::new("alacritty.yml"), text.into(), color)
+    }
+
+    /// Description of the error which prevents loading `contents` as `alacritty.yml`
+    fn yaml_error(contents: &str) -> String {
+        let path = Path::new("alacritty.yml");
+        match parse_config(contents, path, &[], &mut Vec::new()) {
+            Err(Error::Yaml(err)) => yaml_error_message(path, &err),
+            _ => panic!("config should be rejected"),
+        }
+    }
+
+    #[test]
+    fn overrides_survive_reload() {
+        let path = env::temp_dir().join(format!("alacritty-overrides-{}.yml", std::process::id()));
+        let overrides: Vec<ConfigOverride> = vec!["font.size=14".parse().unwrap()];
+        let mut messages = Vec::new();
+
+        fs::write(&path, "font:\n  size: 10.0\ntabspaces: 2\n").unwrap();
+        let config = reload_from(&path, &overrides, &mut messages).unwrap();
+        assert_eq!(config.font.size.as_f32_pts(), 14.);
+        assert_eq!(config.tabspaces(), 2);
+
+        // The file's changes are picked up, while the override still wins
+        fs::write(&path, "font:\n  size: 12.0\ntabspaces: 4\n").unwrap();
+        let config = reload_from(&path, &overrides, &mut messages).unwrap();
+        assert_eq!(config.font.size.as_f32_pts(), 14.);
+        assert_eq!(config.tabspaces(), 4);
+
+        assert!(messages.is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn syntax_error_with_line() {
+        let contents = "font:\n  normal:\n    family: \"Monaco\n";
+        assert_eq!(
+            yaml_error(contents),
+            "config error at alacritty.yml:3: while scanning a quoted scalar, found unexpected end \
+             of stream"
+        );
+    }
+
+    #[test]
+    fn type_error_without_line() {
+        assert_eq!(
+            yaml_error("42\n"),
+            "config error in alacritty.yml: invalid type: integer `42`, expected struct Config"
         );
     }
+
+    #[test]
+    fn unknown_fields_in_one_message() {
+        let contents = "colour: true\nfont:\n  size: 12.0\n  sise: 14.0\n";
+
+        assert_eq!(messages(contents), vec![message(
+            "config warning at alacritty.yml:1: unknown field `colour`\nconfig warning at \
+             alacritty.yml:4: unknown field `font.sise`",
+            color::YELLOW
+        )]);
+    }
+
+    #[test]
+    fn valid_config_has_no_messages() {
+        assert!(messages("font:\n  size: 12.0\n").is_empty());
+        assert!(messages("").is_empty());
+    }
+
+    #[test]
+    fn renamed_options_in_one_message() {
+        let contents =
+            "font:\n  size: 12.0\nrender_timer: true\nwindow:\n  start_maximized: true\n";
+
+        assert_eq!(messages(contents), vec![message(
+            "config warning at alacritty.yml:3: `render_timer` has been renamed to \
+             `debug.render_timer`\nconfig warning at alacritty.yml:5: `window.start_maximized` \
+             has been renamed to `window.startup_mode`\nRun `alacritty migrate-config` to update \
+             the config file",
+            color::YELLOW
+        )]);
+    }
+
+    #[test]
+    fn migrated_file_next_to_config() {
+        let dir = env::temp_dir().join(format!("alacritty-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("alacritty.yml");
+
+        fs::write(&path, "# Comment\npersistent_logging: true\n").unwrap();
+        let (target, migrated) = migrate_file(&path).unwrap();
+        assert_eq!(target, dir.join("alacritty.migrated.yml"));
+        assert_eq!(migrated.len(), 1);
+
+        let config = reload_from(&target, &[], &mut Vec::new()).unwrap();
+        assert!(config.persistent_logging());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# Comment\npersistent_logging: true\n");
+
+        // Nothing is written for configs which are up to date
+        fs::remove_file(&target).unwrap();
+        fs::write(&path, "debug:\n  persistent_logging: true\n").unwrap();
+        let (target, migrated) = migrate_file(&path).unwrap();
+        assert!(migrated.is_empty());
+        assert!(!target.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn other_platforms_are_not_validated() {
+        let contents =
+            "platform:\n  plan9:\n    rio: true\n  macos:\n    font:\n      size: 14.0\n";
+        assert!(messages(contents).is_empty());
+    }
 }
@@ -0,0 +1,622 @@
+// Copyright 2019 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Control of a running instance through a unix domain socket
+//!
+//! Every message is a JSON object, prefixed with its length as a big-endian `u32`. The
+//! instance answers each message with a reply in the same format once it has been applied,
+//! either `{"ok":true}` or `{"error":"..."}`.
+use std::env;
+use std::fs::{self, DirBuilder, Permissions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use log::warn;
+use serde_json::{self as json, json, Value};
+use xdg;
+
+use alacritty_terminal::config::ConfigOverride;
+use alacritty_terminal::display;
+use alacritty_terminal::util::thread;
+
+/// Environment variable with the socket of the instance running a shell
+pub const SOCKET_ENV: &str = "ALACRITTY_SOCKET";
+
+/// Length of the longest message, anything longer is rejected without reading it
+const MAX_MESSAGE_LEN: u32 = 64 * 1024;
+
+/// Time a client has to send its message before it's disconnected
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time the instance has to apply a message before the client is told it failed
+const APPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Command sent to a running instance
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Open a new window
+    CreateWindow,
+
+    /// Override config options, like `-o` on the command line
+    Config(Vec<ConfigOverride>),
+
+    /// Change the font size of all windows by a number of points
+    ChangeFontSize(f32),
+
+    /// Reset the font size of all windows
+    ResetFontSize,
+
+    /// Select a color scheme in all windows
+    SetColorScheme(String),
+}
+
+impl Message {
+    pub fn to_json(&self) -> Value {
+        match self {
+            Message::CreateWindow => json!({ "command": "create_window" }),
+            Message::Config(options) => {
+                let options: Vec<String> = options.iter().map(ToString::to_string).collect();
+                json!({ "command": "config", "options": options })
+            },
+            Message::ChangeFontSize(delta) => {
+                json!({ "command": "change_font_size", "delta": delta })
+            },
+            Message::ResetFontSize => json!({ "command": "reset_font_size" }),
+            Message::SetColorScheme(name) => json!({ "command": "set_color_scheme", "name": name }),
+        }
+    }
+
+    pub fn from_json(value: &Value) -> Result<Message, Error> {
+        let field = |name: &str| {
+            value.get(name).ok_or_else(|| Error::Invalid(format!("missing field `{}`", name)))
+        };
+
+        let command = field("command")?
+            .as_str()
+            .ok_or_else(|| Error::Invalid("`command` is not a string".into()))?;
+
+        match command {
+            "create_window" => Ok(Message::CreateWindow),
+            "config" => {
+                let options = field("options")?
+                    .as_array()
+                    .ok_or_else(|| Error::Invalid("`options` is not a list".into()))?;
+
+                let options = options
+                    .iter()
+                    .map(|option| {
+                        let option = option
+                            .as_str()
+                            .ok_or_else(|| Error::Invalid("option is not a string".into()))?;
+                        option
+                            .parse()
+                            .map_err(|err| Error::Invalid(format!("`{}`: {}", option, err)))
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Message::Config(options))
+            },
+            "change_font_size" => match field("delta")?.as_f64() {
+                Some(delta) => Ok(Message::ChangeFontSize(delta as f32)),
+                None => Err(Error::Invalid("`delta` is not a number".into())),
+            },
+            "reset_font_size" => Ok(Message::ResetFontSize),
+            "set_color_scheme" => match field("name")?.as_str() {
+                Some(name) => Ok(Message::SetColorScheme(name.to_owned())),
+                None => Err(Error::Invalid("`name` is not a string".into())),
+            },
+            command => Err(Error::Invalid(format!("unknown command `{}`", command))),
+        }
+    }
+}
+
+/// Errors of sending or receiving messages
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the socket failed
+    Io(io::Error),
+
+    /// The length of the message exceeds the limit
+    TooLong(u32),
+
+    /// The message is not valid JSON
+    Json(json::Error),
+
+    /// The message is not a known command
+    Invalid(String),
+
+    /// The instance didn't accept the message
+    Rejected(String),
+}
+
+impl ::std::error::Error for Error {
+    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::Json(ref err) => Some(err),
+            Error::TooLong(_) | Error::Invalid(_) | Error::Rejected(_) => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref err) => err.description(),
+            Error::TooLong(_) => "Message is too long",
+            Error::Json(ref err) => err.description(),
+            Error::Invalid(_) => "Invalid message",
+            Error::Rejected(_) => "Message was rejected",
+        }
+    }
+}
+
+impl ::std::fmt::Display for Error {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "Socket error: {}", err),
+            Error::TooLong(len) => {
+                write!(f, "Message of {} bytes exceeds the limit of {}", len, MAX_MESSAGE_LEN)
+            },
+            Error::Json(ref err) => write!(f, "Message is not valid JSON: {}", err),
+            Error::Invalid(ref err) => write!(f, "Invalid message: {}", err),
+            Error::Rejected(ref err) => write!(f, "Message was rejected: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(val: io::Error) -> Error {
+        Error::Io(val)
+    }
+}
+
+impl From<json::Error> for Error {
+    fn from(val: json::Error) -> Error {
+        Error::Json(val)
+    }
+}
+
+/// Write a length-prefixed JSON value
+pub fn write_frame<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
+    let payload = value.to_string().into_bytes();
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(&payload)?;
+    writer.flush()
+}
+
+/// Read a length-prefixed JSON value
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Value, Error> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+
+    let len = u32::from_be_bytes(len);
+    if len > MAX_MESSAGE_LEN {
+        return Err(Error::TooLong(len));
+    }
+
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+
+    Ok(json::from_slice(&payload)?)
+}
+
+/// Reply of the instance to a received message
+fn reply<E: ::std::fmt::Display>(result: &Result<(), E>) -> Value {
+    match result {
+        Ok(()) => json!({ "ok": true }),
+        Err(err) => json!({ "error": err.to_string() }),
+    }
+}
+
+/// Send a message to the instance listening on `socket`
+pub fn send(socket: &Path, message: &Message) -> Result<(), Error> {
+    let mut stream = UnixStream::connect(socket)?;
+    write_frame(&mut stream, &message.to_json())?;
+
+    let reply = read_frame(&mut stream)?;
+    match reply.get("error") {
+        Some(err) => Err(Error::Rejected(err.as_str().unwrap_or_default().to_owned())),
+        None => Ok(()),
+    }
+}
+
+/// Path of the socket of this instance
+///
+/// The socket is placed in `$XDG_RUNTIME_DIR` if possible, otherwise in a directory of the user
+/// in the temporary directory.
+pub fn socket_path() -> PathBuf {
+    let name = format!("Alacritty-{}.sock", process::id());
+    xdg::BaseDirectories::with_prefix("alacritty")
+        .ok()
+        .filter(|xdg| xdg.has_runtime_directory())
+        .and_then(|xdg| xdg.place_runtime_file(&name).ok())
+        .unwrap_or_else(|| {
+            let uid = unsafe { libc::getuid() };
+            env::temp_dir().join(format!("alacritty-{}", uid)).join(name)
+        })
+}
+
+/// Create a directory which only the current user can access
+///
+/// An existing directory of the user is made private, one of somebody else is rejected.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+        result => result?,
+    }
+
+    // Links are not followed, they could point to a directory of somebody else
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::getuid() } {
+        let err = format!("{} is not a directory of the user", dir.display());
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, err));
+    }
+
+    if metadata.mode() & 0o077 != 0 {
+        fs::set_permissions(dir, Permissions::from_mode(0o700))?;
+    }
+
+    Ok(())
+}
+
+pub trait OnMessage {
+    fn on_message(&mut self);
+}
+
+impl OnMessage for display::Notifier {
+    fn on_message(&mut self) {
+        self.notify();
+    }
+}
+
+/// Socket of this instance which is not listening yet
+pub struct Socket {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Socket {
+    /// Create the socket, it can only be used by the current user
+    ///
+    /// The directory of the socket is made private first, so nobody else can connect in the
+    /// time between creating the socket and restricting its permissions.
+    pub fn bind(path: PathBuf) -> io::Result<Socket> {
+        if let Some(dir) = path.parent() {
+            create_private_dir(dir)?;
+        }
+
+        // A socket left behind by a previous instance with the same process ID
+        let _ = fs::remove_file(&path);
+
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, Permissions::from_mode(0o600))?;
+
+        Ok(Socket { listener, path })
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Accept messages on a separate thread, notifying `handler` about each of them
+    pub fn listen<H>(self, mut handler: H) -> Listener
+    where
+        H: OnMessage + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let listener = self.listener;
+
+        Listener {
+            _thread: thread::spawn_named("ipc listener", move || {
+                for stream in listener.incoming() {
+                    let mut stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            warn!("Unable to accept IPC connection: {}", err);
+                            continue;
+                        },
+                    };
+
+                    // A client which doesn't send anything must not block the others
+                    let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+
+                    let message =
+                        read_frame(&mut stream).and_then(|value| Message::from_json(&value));
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(err) => {
+                            warn!("Rejected IPC message: {}", err);
+                            let _ = write_frame(&mut stream, &reply(&Err(err)));
+                            continue;
+                        },
+                    };
+
+                    // The client is answered once the message has been applied
+                    let (reply_tx, reply_rx) = mpsc::channel();
+                    if tx.send(Request { message, reply: reply_tx }).is_err() {
+                        break;
+                    }
+                    handler.on_message();
+
+                    let result = reply_rx
+                        .recv_timeout(APPLY_TIMEOUT)
+                        .unwrap_or_else(|_| Err(String::from("message was not applied")));
+                    if let Err(err) = &result {
+                        warn!("Rejected IPC message: {}", err);
+                    }
+                    let _ = write_frame(&mut stream, &reply(&result));
+                }
+            }),
+            path: self.path,
+            rx,
+        }
+    }
+}
+
+/// Message received by the instance, waiting for the result of applying it
+pub struct Request {
+    pub message: Message,
+    reply: mpsc::Sender<Result<(), String>>,
+}
+
+impl Request {
+    /// Answer the client with the result of applying the message
+    pub fn reply(self, result: Result<(), String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+/// Receiver of the messages sent to this instance
+pub struct Listener {
+    _thread: thread::JoinHandle<()>,
+    path: PathBuf,
+    rx: mpsc::Receiver<Request>,
+}
+
+impl Listener {
+    /// Get all messages received since the last call
+    pub fn pending(&self) -> Vec<Request> {
+        self.rx.try_iter().collect()
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs::{self, Permissions};
+    use std::io::{Cursor, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use alacritty_terminal::config::Config;
+
+    use crate::cli::Options;
+    use crate::config;
+    use crate::handle_message;
+
+    use super::{
+        read_frame, send, write_frame, Error, Listener, Message, OnMessage, Socket, MAX_MESSAGE_LEN,
+    };
+
+    fn round_trip(message: &Message) -> Message {
+        let mut frame = Vec::new();
+        write_frame(&mut frame, &message.to_json()).unwrap();
+
+        let value = read_frame(&mut Cursor::new(frame)).unwrap();
+        Message::from_json(&value).unwrap()
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = vec![
+            Message::CreateWindow,
+            Message::Config(vec![
+                "font.size=14".parse().unwrap(),
+                "colors.x=[1, 2]".parse().unwrap(),
+            ]),
+            Message::ChangeFontSize(-1.5),
+            Message::ResetFontSize,
+            Message::SetColorScheme("dark".into()),
+        ];
+
+        for message in &messages {
+            assert_eq!(&round_trip(message), message);
+        }
+    }
+
+    #[test]
+    fn frame_is_length_prefixed() {
+        let mut frame = Vec::new();
+        write_frame(&mut frame, &Message::CreateWindow.to_json()).unwrap();
+
+        let payload = br#"{"command":"create_window"}"#;
+        assert_eq!(&frame[..4], &(payload.len() as u32).to_be_bytes());
+        assert_eq!(&frame[4..], &payload[..]);
+    }
+
+    #[test]
+    fn too_long_frame_is_rejected() {
+        let frame = (MAX_MESSAGE_LEN + 1).to_be_bytes().to_vec();
+        match read_frame(&mut Cursor::new(frame)) {
+            Err(Error::TooLong(len)) => assert_eq!(len, MAX_MESSAGE_LEN + 1),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn truncated_frame_is_rejected() {
+        let mut frame = Vec::new();
+        write_frame(&mut frame, &Message::CreateWindow.to_json()).unwrap();
+        frame.truncate(frame.len() - 1);
+
+        assert!(read_frame(&mut Cursor::new(frame)).is_err());
+    }
+
+    #[test]
+    fn invalid_messages_are_rejected() {
+        let invalid = vec![
+            json!("create_window"),
+            json!({}),
+            json!({ "command": 1 }),
+            json!({ "command": "quit" }),
+            json!({ "command": "config", "options": "font.size=14" }),
+            json!({ "command": "config", "options": ["font.size"] }),
+            json!({ "command": "change_font_size", "delta": "1" }),
+            json!({ "command": "set_color_scheme" }),
+        ];
+
+        for value in &invalid {
+            assert!(Message::from_json(value).is_err(), "{}", value);
+        }
+    }
+
+    impl OnMessage for mpsc::Sender<()> {
+        fn on_message(&mut self) {
+            let _ = self.send(());
+        }
+    }
+
+    fn socket_path(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("alacritty-test-{}", process::id()));
+        dir.join(format!("{}.sock", name))
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn socket_is_private() {
+        let path = socket_path("private");
+        let socket = Socket::bind(path.clone()).unwrap();
+
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+
+        let (tx, _rx) = mpsc::channel();
+        drop(socket.listen(tx));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn socket_dir_is_made_private() {
+        let dir = env::temp_dir().join(format!("alacritty-test-{}-shared", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::set_permissions(&dir, Permissions::from_mode(0o755)).unwrap();
+
+        let socket = Socket::bind(dir.join("shared.sock")).unwrap();
+        assert_eq!(mode(&dir), 0o700);
+
+        let (tx, _rx) = mpsc::channel();
+        drop(socket.listen(tx));
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    /// Send `message` from another client and apply it like the main loop does
+    fn apply(
+        listener: &Listener,
+        wakeups: &mpsc::Receiver<()>,
+        config: &mut Config,
+        options: &mut Options,
+        message: Message,
+    ) -> Result<(), Error> {
+        let socket = listener.path.clone();
+        let client = thread::spawn(move || send(&socket, &message));
+
+        wakeups.recv_timeout(Duration::from_secs(5)).expect("wakeup after message");
+        for request in listener.pending() {
+            // There are no windows to update
+            let message = request.message.clone();
+            let result = handle_message(&mut HashMap::new(), config, options, message);
+            assert_ne!(result, Ok(true));
+            request.reply(result.map(|_| ()));
+        }
+
+        client.join().unwrap()
+    }
+
+    #[test]
+    fn config_change_through_socket() {
+        let path = socket_path("config");
+        let (tx, rx) = mpsc::channel();
+        let listener = Socket::bind(path.clone()).unwrap().listen(tx);
+
+        // Malformed messages are answered with an error and don't stop the listener
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(&[0, 0, 0, 3, b'{', b'{', b'{']).unwrap();
+        let reply = read_frame(&mut stream).unwrap();
+        assert!(reply.get("error").is_some());
+
+        let mut options = Options::default();
+        let mut config = options.clone().into_config(config::load_default(&[]));
+
+        let message = Message::Config(vec!["font.size=20".parse().unwrap()]);
+        match apply(&listener, &rx, &mut config, &mut options, message) {
+            Ok(()) => (),
+            Err(err) => panic!("message not accepted: {}", err),
+        }
+
+        assert_eq!(config.font.size.as_f32_pts(), 20.);
+
+        // The override is kept for later reloads of the config file
+        assert_eq!(options.config_options.len(), 1);
+    }
+
+    #[test]
+    fn invalid_changes_are_rejected() {
+        let path = socket_path("invalid");
+        let (tx, rx) = mpsc::channel();
+        let listener = Socket::bind(path).unwrap().listen(tx);
+
+        let mut options = Options::default();
+        let mut config = options.clone().into_config(config::load_default(&[]));
+        let font_size = config.font.size;
+
+        let message =
+            Message::Config(vec!["font.size=20".parse().unwrap(), "font.sise=3".parse().unwrap()]);
+        match apply(&listener, &rx, &mut config, &mut options, message) {
+            Err(Error::Rejected(err)) => assert!(err.contains("font.sise"), "{}", err),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        let message = Message::SetColorScheme("missing".into());
+        match apply(&listener, &rx, &mut config, &mut options, message) {
+            Err(Error::Rejected(err)) => assert!(err.contains("missing"), "{}", err),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        // Nothing of a rejected message is applied or kept
+        assert_eq!(config.font.size, font_size);
+        assert!(options.config_options.is_empty());
+    }
+}
@@ -33,6 +33,7 @@ use alacritty_terminal::message_bar::Message;
 use alacritty_terminal::term::color;
 
 use crate::cli::Options;
+use crate::config;
 
 const ALACRITTY_LOG_ENV: &str = "ALACRITTY_LOG";
 
@@ -55,6 +56,11 @@ pub fn initialize(
     }
 }
 
+/// Path of the file panics are written to, next to the log file
+pub fn crash_file_path() -> PathBuf {
+    env::temp_dir().join(format!("Alacritty-{}-crash.txt", process::id()))
+}
+
 pub struct Logger {
     logfile: Mutex<OnDemandLogFile>,
     stdout: Mutex<LineWriter<Stdout>>,
@@ -85,7 +91,10 @@ impl log::Log for Logger {
 
     fn log(&self, record: &log::Record<'_>) {
         if self.enabled(record.metadata()) && record.target().starts_with("alacritty") {
-            let now = time::strftime("%F %R", &time::now()).unwrap();
+            // Millisecond precision makes it possible to correlate printed events
+            let now = time::now();
+            let millis = now.tm_nsec / 1_000_000;
+            let now = format!("{}.{:03}", time::strftime("%F %T", &now).unwrap(), millis);
 
             let msg = if record.level() >= Level::Trace {
                 format!(
@@ -103,7 +112,8 @@ impl log::Log for Logger {
             if let Ok(ref mut logfile) = self.logfile.lock() {
                 let _ = logfile.write_all(msg.as_ref());
 
-                if record.level() <= Level::Warn {
+                // Config errors are put in the message bar by the config loader itself
+                if record.level() <= Level::Warn && record.target() != config::LOG_TARGET {
                     #[cfg(not(windows))]
                     let env_var = format!("${}", ALACRITTY_LOG_ENV);
                     #[cfg(windows)]
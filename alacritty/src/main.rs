@@ -22,40 +22,49 @@
 // See https://msdn.microsoft.com/en-us/library/4cc7ya5b.aspx for more details.
 #![windows_subsystem = "windows"]
 
-#[cfg(target_os = "macos")]
+use std::collections::HashMap;
+#[cfg(unix)]
 use std::env;
 use std::error::Error;
 use std::fs::{self, File};
 use std::io::{self, Write};
-#[cfg(not(windows))]
-use std::os::unix::io::AsRawFd;
-use std::sync::Arc;
+use std::mem;
+use std::path::PathBuf;
+use std::process::{self, ExitStatus};
 
 #[cfg(target_os = "macos")]
 use dirs;
+use glutin::{ControlFlow, Event, EventsLoop, WindowId};
+#[cfg(unix)]
+use log::warn;
 use log::{error, info};
 use serde_json as json;
 #[cfg(windows)]
 use winapi::um::wincon::{AttachConsole, FreeConsole, ATTACH_PARENT_PROCESS};
 
-use alacritty_terminal::clipboard::Clipboard;
-use alacritty_terminal::config::{Config, Monitor};
-use alacritty_terminal::display::Display;
-use alacritty_terminal::event_loop::{self, EventLoop, Msg};
+use alacritty_terminal::config::{Config, ConfigChanges, Monitor};
+use alacritty_terminal::die;
 #[cfg(target_os = "macos")]
 use alacritty_terminal::locale;
-use alacritty_terminal::message_bar::MessageBuffer;
+use alacritty_terminal::message_bar::{Message, MessageBuffer};
 use alacritty_terminal::panic;
-use alacritty_terminal::sync::FairMutex;
+use alacritty_terminal::scheduler::Scheduler;
 use alacritty_terminal::term::{cell::Cell, Term};
 use alacritty_terminal::tty;
-use alacritty_terminal::{die, event};
 
+mod benchmark;
 mod cli;
 mod config;
+#[cfg(unix)]
+mod ipc;
 mod logging;
+#[cfg(feature = "session")]
+mod session;
+mod window_context;
+mod window_size;
 
 use crate::cli::Options;
+use crate::window_context::WindowContext;
 
 fn main() {
     panic::attach_handler();
@@ -71,6 +80,24 @@ fn main() {
     // Load command line options
     let options = Options::new();
 
+    // Measure the parser throughput instead of starting a terminal
+    if let Some(options) = &options.benchmark {
+        benchmark::run(options);
+    }
+
+    // Migrate the config file instead of starting a terminal
+    if options.migrate_config {
+        migrate_config(&options);
+    }
+
+    // Send a message to a running instance instead of starting a new one
+    #[cfg(unix)]
+    {
+        if let Some(message) = &options.message {
+            send_message(&options, message);
+        }
+    }
+
     // Setup storage for message UI
     let message_buffer = MessageBuffer::new();
 
@@ -85,13 +112,25 @@ fn main() {
         .or_else(config::installed_config)
         .or_else(|| config::write_defaults().ok())
         .map(|path| path.to_path_buf());
+    let mut config_messages = Vec::new();
     let config = if let Some(path) = config_path {
-        config::load_from(path)
+        config::load_from(path, &options.config_options, &mut config_messages)
     } else {
         error!("Unable to write the default config");
-        Config::default()
+        config::load_default(&options.config_options)
     };
-    let config = options.into_config(config);
+    let mut config = options.clone().into_config(config);
+
+    // Start with the size of the last closed window
+    if config.window.remember_size {
+        config.window.remembered_dimensions = window_size::load();
+    }
+
+    // Show problems with the config file in the message bar
+    let message_tx = message_buffer.tx();
+    for message in config_messages {
+        let _ = message_tx.send(message);
+    }
 
     // Update the log level from config
     log::set_max_level(config.debug.log_level);
@@ -106,10 +145,14 @@ fn main() {
     // Store if log file should be deleted before moving config
     let persistent_logging = config.persistent_logging();
 
+    // The exit status of a command passed with `-e` is reported as Alacritty's own
+    let has_command = options.command.is_some();
+
     // Run alacritty
-    if let Err(err) = run(config, message_buffer) {
-        die!("Alacritty encountered an unrecoverable error:\n\n\t{}\n", err);
-    }
+    let exit_status = match run(config, options, message_buffer) {
+        Ok(exit_status) => exit_status,
+        Err(err) => die!("Alacritty encountered an unrecoverable error:\n\n\t{}\n", err),
+    };
 
     // Clean up logfile
     if let Some(log_file) = log_file {
@@ -117,13 +160,24 @@ fn main() {
             let _ = writeln!(io::stdout(), "Deleted log file at {:?}", log_file);
         }
     }
+
+    if let Some(status) = exit_status.filter(|_| has_command) {
+        process::exit(tty::exit_code(status));
+    }
 }
 
 /// Run Alacritty
 ///
-/// Creates a window, the terminal state, pty, I/O event loop, input processor,
-/// config change monitor, and runs the main display loop.
-fn run(config: Config, message_buffer: MessageBuffer) -> Result<(), Box<dyn Error>> {
+/// Creates the first window with its terminal state, pty, I/O event loop and
+/// input processor, the config change monitor, and runs the main display loop
+/// until all windows have been closed.
+///
+/// Returns the exit status of the child process in the last closed window.
+fn run(
+    mut config: Config,
+    options: Options,
+    message_buffer: MessageBuffer,
+) -> Result<Option<ExitStatus>, Box<dyn Error>> {
     info!("Welcome to Alacritty");
     if let Some(config_path) = &config.config_path {
         info!("Configuration loaded from {:?}", config_path.display());
@@ -132,135 +186,160 @@ fn run(config: Config, message_buffer: MessageBuffer) -> Result<(), Box<dyn Erro
     // Set environment variables
     tty::setup_env(&config);
 
-    // Create a display.
-    //
-    // The display manages a window and can draw the terminal
-    let mut display = Display::new(&config)?;
-
-    info!("PTY Dimensions: {:?} x {:?}", display.size().lines(), display.size().cols());
-
-    // Create new native clipboard
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-    let clipboard = Clipboard::new(display.get_wayland_display());
-    #[cfg(any(target_os = "macos", target_os = "windows"))]
-    let clipboard = Clipboard::new();
-
-    // Create the terminal
-    //
-    // This object contains all of the state about what's being displayed. It's
-    // wrapped in a clonable mutex since both the I/O loop and display need to
-    // access it.
-    let terminal = Term::new(&config, display.size().to_owned(), message_buffer, clipboard);
-    let terminal = Arc::new(FairMutex::new(terminal));
+    // Create the socket for `alacritty msg`, the shells find it through the environment
+    #[cfg(unix)]
+    let socket = match ipc::Socket::bind(ipc::socket_path()) {
+        Ok(socket) => {
+            env::set_var(ipc::SOCKET_ENV, socket.path());
+            Some(socket)
+        },
+        Err(err) => {
+            warn!("Unable to create IPC socket: {}", err);
+            None
+        },
+    };
 
-    // Find the window ID for setting $WINDOWID
-    let window_id = display.get_window_id();
+    // The event loop is shared by all windows, its events are routed to the
+    // windows using their window ID
+    let mut event_loop = EventsLoop::new();
 
-    // Create the pty
-    //
-    // The pty forks a process to run the shell on the slave side of the
-    // pseudoterminal. A file descriptor for the master side is retained for
-    // reading/writing to the shell.
-    let pty = tty::new(&config, &display.size(), window_id);
+    // Timers of all windows are handled by a single thread, which wakes up the event loop
+    let proxy = event_loop.create_proxy();
+    let scheduler = Scheduler::new(move || {
+        // The event loop is only gone during shutdown, when no timer matters anymore
+        let _ = proxy.wakeup();
+    });
 
-    // Get a reference to something that we can resize
-    //
-    // This exists because rust doesn't know the interface is thread-safe
-    // and we need to be able to resize the PTY from the main thread while the IO
-    // thread owns the EventedRW object.
-    #[cfg(windows)]
-    let mut resize_handle = pty.resize_handle();
-    #[cfg(not(windows))]
-    let mut resize_handle = pty.fd.as_raw_fd();
+    // Log messages are only shown in the message bar of the first window
+    let message_tx = message_buffer.tx();
+    let window = WindowContext::new(&config, &event_loop, &scheduler, message_buffer)?;
 
-    // Create the pseudoterminal I/O loop
-    //
-    // pty I/O is ran on another thread as to not occupy cycles used by the
-    // renderer and input processing. Note that access to the terminal state is
-    // synchronized since the I/O loop updates the state, and the display
-    // consumes it periodically.
-    let event_loop =
-        EventLoop::new(Arc::clone(&terminal), display.notifier(), pty, config.debug.ref_test);
-
-    // The event loop channel allows write requests from the event processor
-    // to be sent to the loop and ultimately written to the pty.
-    let loop_tx = event_loop.channel();
-
-    // Event processor
-    //
-    // Need the Rc<RefCell<_>> here since a ref is shared in the resize callback
-    let mut processor = event::Processor::new(
-        event_loop::Notifier(event_loop.channel()),
-        display.resize_channel(),
-        &config,
-        display.size().to_owned(),
-    );
+    // Panics of other threads are reported in the message bar as well
+    let notifier = window.notifier();
+    panic::report_crashes(logging::crash_file_path(), message_tx, move || notifier.notify());
 
     // Create a config monitor when config was loaded from path
     //
     // The monitor watches the config file for changes and reloads it. Pending
     // config changes are processed in the main loop.
     let config_monitor = if config.live_config_reload() {
-        config.config_path.as_ref().map(|path| Monitor::new(path, display.notifier()))
+        config.config_path.as_ref().map(|path| Monitor::new(path, window.notifier()))
     } else {
         None
     };
 
-    // Kick off the I/O thread
-    let _io_thread = event_loop.spawn(None);
+    // Messages wake up the event loop like config changes
+    #[cfg(unix)]
+    let ipc_listener = socket.map(|socket| socket.listen(window.notifier()));
+
+    // Only the first window is embedded, additional windows are top-level windows.
+    // Config overrides received as messages are added to the command line options.
+    let mut options = options;
+    options.embed = None;
+    config.window.embed = None;
+
+    // Additional windows use the configured size
+    config.window.remembered_dimensions = None;
+
+    // Ref tests are only written for the first window
+    let ref_test_window = if config.debug.ref_test { Some(window.id()) } else { None };
+
+    let mut windows = HashMap::new();
+    windows.insert(window.id(), window);
+
+    let mut exit_status = None;
 
     info!("Initialisation complete");
 
     // Main display loop
-    loop {
-        // Process input and window events
-        let mut terminal_lock = processor.process_events(&terminal, display.window());
+    while !windows.is_empty() {
+        // Wait for the next event unless there's work left in any of the windows
+        if windows.values().all(WindowContext::is_idle) {
+            event_loop.run_forever(|event| {
+                route_event(&mut windows, event);
+                ControlFlow::Break
+            });
+        }
+
+        // Queue everything else which is available right now
+        event_loop.poll_events(|event| route_event(&mut windows, event));
 
         // Handle config reloads
         if let Some(ref path) = config_monitor.as_ref().and_then(Monitor::pending) {
-            // Clear old config messages from bar
-            terminal_lock.message_buffer_mut().remove_topic(config::SOURCE_FILE_PATH);
+            let mut messages = Vec::new();
+            let reloaded = reload_config(path, &options, &mut messages);
+            update_config(&mut windows, &mut config, reloaded, &messages);
+        }
 
-            if let Ok(config) = config::reload_from(path) {
-                display.update_config(&config);
-                processor.update_config(&config);
-                terminal_lock.update_config(&config);
+        let mut requested = 0;
+
+        // Handle messages of `alacritty msg`
+        #[cfg(unix)]
+        {
+            let pending = ipc_listener.as_ref().map(ipc::Listener::pending).unwrap_or_default();
+            for request in pending {
+                let message = request.message.clone();
+                let result = handle_message(&mut windows, &mut config, &mut options, message);
+                if let Ok(true) = result {
+                    requested += 1;
+                }
+                request.reply(result.map(|_| ()));
             }
-
-            terminal_lock.dirty = true;
         }
 
-        // Begin shutdown if the flag was raised
-        if terminal_lock.should_exit() || tty::process_should_exit() {
-            break;
+        let mut closed = Vec::new();
+        for (id, window) in windows.iter_mut() {
+            let status = window.process(&config);
+
+            if status.exit {
+                closed.push(*id);
+            }
+
+            if status.create_window {
+                requested += 1;
+            }
         }
 
-        // Maybe draw the terminal
-        if terminal_lock.needs_draw() {
-            // Try to update the position of the input method editor
-            #[cfg(not(windows))]
-            display.update_ime_position(&terminal_lock);
+        for id in closed {
+            if let Some(window) = windows.remove(&id) {
+                let mut terminal = window.terminal().lock();
 
-            // Handle pending resize events
-            //
-            // The second argument is a list of types that want to be notified
-            // of display size changes.
-            display.handle_resize(&mut terminal_lock, &config, &mut resize_handle, &mut processor);
+                // Write ref tests to disk
+                if ref_test_window == Some(id) {
+                    write_ref_test_results(&mut terminal);
+                }
 
-            drop(terminal_lock);
+                if config.window.remember_size {
+                    window_size::save(&terminal);
+                }
 
-            // Draw the current state of the terminal
-            display.draw(&terminal, &config);
+                // Keep the content of the last closed window for the next start
+                #[cfg(feature = "session")]
+                {
+                    if config.restore_session() {
+                        session::save(&terminal);
+                    }
+                }
+
+                exit_status = terminal.exit_status().or(exit_status);
+            }
         }
-    }
 
-    // Write ref tests to disk
-    if config.debug.ref_test {
-        write_ref_test_results(&terminal.lock());
+        for _ in 0..requested {
+            // The recording of an additional window would replace the one of the ref test
+            let ref_test = mem::replace(&mut config.debug.ref_test, false);
+            let window = WindowContext::new(&config, &event_loop, &scheduler, MessageBuffer::new());
+            config.debug.ref_test = ref_test;
+
+            match window {
+                Ok(window) => {
+                    windows.insert(window.id(), window);
+                },
+                Err(err) => error!("Unable to create new window: {}", err),
+            }
+        }
     }
 
-    loop_tx.send(Msg::Shutdown).expect("Error sending shutdown to event loop");
-
     // FIXME patch notify library to have a shutdown method
     // config_reloader.join().ok();
 
@@ -272,11 +351,168 @@ fn run(config: Config, message_buffer: MessageBuffer) -> Result<(), Box<dyn Erro
 
     info!("Goodbye");
 
-    Ok(())
+    Ok(exit_status)
+}
+
+/// Apply a reloaded config to all windows
+///
+/// Only what has changed is applied, the old config is kept if the new one can't be loaded.
+fn update_config(
+    windows: &mut HashMap<WindowId, WindowContext>,
+    config: &mut Config,
+    reloaded: Option<Config>,
+    messages: &[Message],
+) {
+    // Replace old config messages in the bar
+    for window in windows.values_mut() {
+        window.replace_config_messages(messages);
+    }
+
+    if let Some(reloaded) = reloaded {
+        let changes = ConfigChanges::between(config, &reloaded);
+        for window in windows.values_mut() {
+            window.update_config(&reloaded, changes);
+        }
+        *config = reloaded;
+    }
+}
+
+/// Apply a message of `alacritty msg` to all windows
+///
+/// Returns `true` if the message requested a new window, messages which can't be applied are
+/// rejected without changing anything.
+#[cfg(unix)]
+fn handle_message(
+    windows: &mut HashMap<WindowId, WindowContext>,
+    config: &mut Config,
+    options: &mut Options,
+    message: ipc::Message,
+) -> Result<bool, String> {
+    match message {
+        ipc::Message::CreateWindow => return Ok(true),
+        ipc::Message::Config(overrides) => {
+            let path = config.config_path.as_ref().map(PathBuf::as_path);
+            config::check_overrides(path, &options.config_options, &overrides)?;
+
+            // Overrides are kept when the config file is reloaded
+            options.config_options.extend(overrides);
+
+            let mut messages = Vec::new();
+            let reloaded = match &config.config_path {
+                Some(path) => reload_config(path, options, &mut messages),
+                None => {
+                    let reloaded = config::load_default(&options.config_options);
+                    Some(options.clone().into_config(reloaded))
+                },
+            };
+            update_config(windows, config, reloaded, &messages);
+        },
+        ipc::Message::ChangeFontSize(delta) => {
+            for window in windows.values() {
+                window.terminal().lock().change_font_size(delta);
+            }
+        },
+        ipc::Message::ResetFontSize => {
+            for window in windows.values() {
+                window.terminal().lock().reset_font_size();
+            }
+        },
+        ipc::Message::SetColorScheme(name) => {
+            if !config.schemes.iter().any(|scheme| scheme.name == name) {
+                return Err(format!("unknown color scheme `{}`", name));
+            }
+
+            for window in windows.values() {
+                window.terminal().lock().set_color_scheme(&name);
+            }
+        },
+    }
+
+    Ok(false)
+}
+
+/// Send a message to the instance of the `--socket` option or `$ALACRITTY_SOCKET` and exit
+#[cfg(unix)]
+fn send_message(options: &Options, message: &ipc::Message) -> ! {
+    let socket = options.socket.clone().or_else(|| env::var_os(ipc::SOCKET_ENV).map(PathBuf::from));
+    let socket = match socket {
+        Some(socket) => socket,
+        None => {
+            let _ = writeln!(io::stderr(), "Missing --socket option and ${}", ipc::SOCKET_ENV);
+            process::exit(1);
+        },
+    };
+
+    match ipc::send(&socket, message) {
+        Ok(()) => process::exit(0),
+        Err(err) => {
+            let _ = writeln!(io::stderr(), "Unable to send message to {:?}: {}", socket, err);
+            process::exit(1);
+        },
+    }
+}
+
+/// Write the migrated config file next to the original and list the migrated options
+fn migrate_config(options: &Options) -> ! {
+    let path = match options.config_path().or_else(config::installed_config) {
+        Some(path) => path,
+        None => {
+            let _ = writeln!(io::stderr(), "Unable to find a config file to migrate");
+            process::exit(1);
+        },
+    };
+
+    let (target, migrated) = match config::migrate_file(&path) {
+        Ok(result) => result,
+        Err(err) => {
+            let _ = writeln!(io::stderr(), "Unable to migrate config {:?}: {}", path, err);
+            process::exit(1);
+        },
+    };
+
+    let mut stdout = io::stdout();
+    if migrated.is_empty() {
+        let _ = writeln!(stdout, "Config {:?} is up to date", path);
+    } else {
+        for migrated in migrated {
+            let _ = writeln!(stdout, "{}", migrated);
+        }
+        let _ = writeln!(stdout, "Migrated config written to {:?} without comments", target);
+    }
+
+    process::exit(0);
+}
+
+/// Load the config file again and apply the command line options to it
+fn reload_config(path: &PathBuf, options: &Options, messages: &mut Vec<Message>) -> Option<Config> {
+    let mut config = config::reload_from(path, &options.config_options, messages).ok()?;
+    config.config_path = Some(path.to_owned());
+    Some(options.clone().into_config(config))
+}
+
+/// Pass an event of the shared event loop to the windows it belongs to
+fn route_event(windows: &mut HashMap<WindowId, WindowContext>, event: Event) {
+    match event {
+        Event::WindowEvent { window_id, .. } => {
+            if let Some(window) = windows.get_mut(&window_id) {
+                window.push_event(event);
+            }
+        },
+        // Wakeups don't carry a window ID, so every window has to check for changes
+        Event::Awakened => {
+            for window in windows.values_mut() {
+                window.push_event(Event::Awakened);
+            }
+        },
+        _ => (),
+    }
 }
 
 // Write the ref test results to the disk
-fn write_ref_test_results(terminal: &Term) {
+fn write_ref_test_results(terminal: &mut Term) {
+    // finish the recording with the state which isn't part of the grid
+    terminal.finish_recording();
+
     // dump grid state
     let mut grid = terminal.grid().clone();
     grid.initialize_all(&Cell::default());
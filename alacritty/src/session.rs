@@ -0,0 +1,92 @@
+// Copyright 2019 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Content of the last closed window, shown again on startup with `restore_session`
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(windows)]
+use dirs;
+use log::{info, warn};
+#[cfg(not(windows))]
+use xdg;
+
+use alacritty_terminal::term::session::Error;
+use alacritty_terminal::term::Term;
+
+/// Only the first window shows the previous session
+static RESTORED: AtomicBool = AtomicBool::new(false);
+
+/// File the session is saved to
+#[cfg(not(windows))]
+fn session_path() -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("alacritty")
+        .ok()
+        .map(|xdg| xdg.get_data_home().join("session"))
+}
+
+#[cfg(windows)]
+fn session_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|path| path.join("alacritty\\session"))
+}
+
+/// Save the content of a closed window for the next start
+pub fn save(terminal: &Term) {
+    let path = match session_path() {
+        Some(path) => path,
+        None => {
+            warn!("Unable to find a directory for saving the session");
+            return;
+        },
+    };
+
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| File::create(&path))
+        .map_err(Error::from)
+        .and_then(|file| terminal.serialize_state(BufWriter::new(file)));
+
+    match result {
+        Ok(()) => info!("Session saved to {:?}", path),
+        Err(err) => warn!("Unable to save session to {:?}: {}", path, err),
+    }
+}
+
+/// Show the saved session as scrollback history of a new terminal
+///
+/// This has to happen before the shell is started, so its output is below the old content.
+pub fn restore(terminal: &mut Term) {
+    if RESTORED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    // Nothing to do before the first session has been saved
+    let path = match session_path() {
+        Some(path) => path,
+        None => return,
+    };
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(_) => return,
+    };
+
+    let size = *terminal.size_info();
+    match terminal.restore_history(BufReader::new(file), &size) {
+        Ok(()) => info!("Session restored from {:?}", path),
+        Err(err) => warn!("Unable to restore session from {:?}: {}", path, err),
+    }
+}
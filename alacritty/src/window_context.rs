@@ -0,0 +1,298 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! State of a single terminal window
+use std::error::Error;
+#[cfg(not(windows))]
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::time::Instant;
+
+use glutin::{Event, EventsLoop, WindowId};
+use log::info;
+
+use alacritty_terminal::clipboard::Clipboard;
+use alacritty_terminal::config::{Config, ConfigChanges};
+use alacritty_terminal::display::{self, Display};
+use alacritty_terminal::event;
+use alacritty_terminal::event_loop::{self, EventLoop, Msg};
+use alacritty_terminal::message_bar::{Message, MessageBuffer};
+use alacritty_terminal::scheduler::Scheduler;
+use alacritty_terminal::sync::FairMutex;
+use alacritty_terminal::term::Term;
+use alacritty_terminal::tty::{self, OnResize};
+use alacritty_terminal::util::thread;
+
+use crate::config;
+#[cfg(feature = "session")]
+use crate::session;
+
+/// Everything that belongs to one window: display, terminal, pty and input processor
+pub struct WindowContext {
+    display: Display,
+    terminal: Arc<FairMutex<Term>>,
+    processor: event::Processor<event_loop::Notifier>,
+    resize_handle: Box<dyn OnResize>,
+
+    /// Channel to the pty I/O loop of this window
+    loop_tx: event_loop::Notifier,
+
+    /// Thread of the pty I/O loop, joined when the window is dropped
+    io_thread: Option<thread::JoinHandle<(EventLoop<Pty, display::Notifier>, event_loop::State)>>,
+}
+
+#[cfg(not(windows))]
+type Pty = tty::Pty;
+#[cfg(windows)]
+type Pty = tty::Pty<'static>;
+
+/// Outcome of processing a window
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct WindowStatus {
+    /// The window should be closed
+    pub exit: bool,
+
+    /// A new window has been requested from this window
+    pub create_window: bool,
+}
+
+impl WindowContext {
+    /// Create a new window running its own shell
+    ///
+    /// The timers of the window are handled by the thread of `scheduler`.
+    pub fn new(
+        config: &Config,
+        event_loop: &EventsLoop,
+        scheduler: &Scheduler,
+        message_buffer: MessageBuffer,
+    ) -> Result<WindowContext, Box<dyn Error>> {
+        // Create a display.
+        //
+        // The display manages a window and can draw the terminal
+        let display = Display::new(config, event_loop)?;
+
+        info!("PTY Dimensions: {:?} x {:?}", display.size().lines(), display.size().cols());
+
+        // Create new native clipboard
+        #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+        let clipboard = Clipboard::new(display.get_wayland_display());
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        let clipboard = Clipboard::new();
+
+        // Create the terminal
+        //
+        // This object contains all of the state about what's being displayed. It's
+        // wrapped in a clonable mutex since both the I/O loop and display need to
+        // access it.
+        #[cfg_attr(not(feature = "session"), allow(unused_mut))]
+        let mut terminal = Term::new(config, display.size().to_owned(), message_buffer, clipboard);
+
+        // Show the content of the last closed window above the new shell
+        #[cfg(feature = "session")]
+        {
+            if config.restore_session() {
+                session::restore(&mut terminal);
+            }
+        }
+
+        let terminal = Arc::new(FairMutex::new(terminal));
+
+        // Find the window ID for setting $WINDOWID
+        let window_id = display.get_window_id();
+
+        // Create the pty
+        //
+        // The pty forks a process to run the shell on the slave side of the
+        // pseudoterminal. A file descriptor for the master side is retained for
+        // reading/writing to the shell.
+        let pty = tty::new(config, &display.size(), window_id);
+
+        // Get a reference to something that we can resize
+        //
+        // This exists because rust doesn't know the interface is thread-safe
+        // and we need to be able to resize the PTY from the main thread while the IO
+        // thread owns the EventedRW object.
+        #[cfg(windows)]
+        let resize_handle = Box::new(pty.resize_handle());
+        #[cfg(not(windows))]
+        let resize_handle = Box::new(pty.fd.as_raw_fd());
+
+        // Handle for finding the working directory of the shell
+        let processes = pty.processes();
+
+        // Create the pseudoterminal I/O loop
+        //
+        // pty I/O is ran on another thread as to not occupy cycles used by the
+        // renderer and input processing. Note that access to the terminal state is
+        // synchronized since the I/O loop updates the state, and the display
+        // consumes it periodically.
+        let event_loop = EventLoop::new(Arc::clone(&terminal), display.notifier(), pty, config);
+
+        // The event loop channel allows write requests from the event processor
+        // to be sent to the loop and ultimately written to the pty.
+        let loop_tx = event_loop::Notifier(event_loop.channel());
+
+        // Event processor
+        let processor = event::Processor::new(
+            event_loop::Notifier(event_loop.channel()),
+            scheduler.clone(),
+            processes,
+            display.resize_channel(),
+            config,
+            display.size().to_owned(),
+        );
+
+        // Kick off the I/O thread
+        let io_thread = Some(event_loop.spawn(None));
+
+        Ok(WindowContext { display, terminal, processor, resize_handle, loop_tx, io_thread })
+    }
+
+    /// Identifier of the window in the shared event loop
+    #[inline]
+    pub fn id(&self) -> WindowId {
+        self.display.window_id()
+    }
+
+    #[inline]
+    pub fn notifier(&self) -> display::Notifier {
+        self.display.notifier()
+    }
+
+    #[inline]
+    pub fn terminal(&self) -> &Arc<FairMutex<Term>> {
+        &self.terminal
+    }
+
+    /// Check if the window has nothing to do until the next event arrives
+    #[inline]
+    pub fn is_idle(&self) -> bool {
+        self.processor.is_idle()
+    }
+
+    /// Queue an event which belongs to this window
+    #[inline]
+    pub fn push_event(&mut self, event: Event) {
+        self.processor.push_event(event);
+    }
+
+    /// Replace messages about errors in the previous configuration file
+    pub fn replace_config_messages(&mut self, messages: &[Message]) {
+        let mut terminal = self.terminal.lock();
+        terminal.message_buffer_mut().remove_topic(config::SOURCE_FILE_PATH);
+
+        let message_tx = terminal.message_buffer().tx();
+        for message in messages {
+            let _ = message_tx.send(message.clone());
+        }
+
+        terminal.dirty = true;
+    }
+
+    /// Apply the parts of a reloaded configuration file which have changed
+    pub fn update_config(&mut self, config: &Config, changes: ConfigChanges) {
+        let mut terminal = self.terminal.lock();
+
+        if changes.contains(ConfigChanges::TERMINAL) {
+            terminal.update_config(config);
+        } else if changes.contains(ConfigChanges::COLORS) {
+            terminal.update_colors(config);
+        }
+
+        if changes.contains(ConfigChanges::FONT) {
+            terminal.update_font(config);
+        }
+
+        if changes.contains(ConfigChanges::BINDINGS) {
+            self.processor.update_bindings(config);
+        }
+
+        if changes.contains(ConfigChanges::INPUT) {
+            self.processor.update_config(config);
+        }
+
+        self.display.update_config(config, changes);
+
+        // Draw the next frame with the new config, even if the terminal didn't change
+        if changes.intersects(ConfigChanges::PADDING | ConfigChanges::DISPLAY) {
+            terminal.request_redraw();
+        }
+    }
+
+    /// Process all queued events and draw the terminal if necessary
+    pub fn process(&mut self, config: &Config) -> WindowStatus {
+        self.display.record_wakeup();
+
+        // Process input and window events
+        let mut terminal_lock =
+            self.processor.process_events(&self.terminal, self.display.window());
+
+        let create_window = self.processor.take_window_request();
+
+        // Begin shutdown if the flag was raised
+        if terminal_lock.should_exit() {
+            return WindowStatus { exit: true, create_window };
+        }
+
+        // Maybe draw the terminal
+        if terminal_lock.needs_draw() {
+            // Try to update the position of the input method editor
+            #[cfg(not(windows))]
+            self.display.update_ime_position(&terminal_lock);
+
+            // Handle pending resize events
+            //
+            // The second argument is a list of types that want to be notified
+            // of display size changes.
+            self.display.handle_resize(
+                &mut terminal_lock,
+                config,
+                &mut *self.resize_handle,
+                &mut self.processor,
+            );
+
+            // Coalesce updates until the frame rate limit allows the next draw, the event loop
+            // keeps handling input in the meantime
+            if let Some(deadline) = self.display.next_frame(&terminal_lock, Instant::now()) {
+                self.processor.postpone_frame(deadline);
+            } else {
+                drop(terminal_lock);
+
+                // Draw the current state of the terminal
+                let animating = self.display.draw(&self.terminal, config);
+                self.processor.frame_drawn(animating);
+            }
+        }
+
+        // Resize the pty once interactive resizing has settled
+        let now = Instant::now();
+        if let Some(deadline) = self.display.update_pty_size(&mut *self.resize_handle, now) {
+            self.processor.schedule_wakeup(deadline);
+        }
+
+        WindowStatus { exit: false, create_window }
+    }
+}
+
+impl Drop for WindowContext {
+    fn drop(&mut self) {
+        // Stop the pty I/O loop, this closes the pty and hangs up the shell
+        let _ = self.loop_tx.0.send(Msg::Shutdown);
+
+        // The pty is only closed once the loop has returned it
+        if let Some(io_thread) = self.io_thread.take() {
+            let _ = io_thread.join();
+        }
+    }
+}
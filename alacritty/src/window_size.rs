@@ -0,0 +1,117 @@
+// Copyright 2019 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Size of the last closed window, used on startup with `window.remember_size`
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+#[cfg(windows)]
+use dirs;
+use log::{debug, warn};
+#[cfg(not(windows))]
+use xdg;
+
+use alacritty_terminal::config::Dimensions;
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::Term;
+
+/// File the window size is saved to
+#[cfg(not(windows))]
+fn state_path() -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("alacritty")
+        .ok()
+        .map(|xdg| xdg.get_data_home().join("window_size"))
+}
+
+#[cfg(windows)]
+fn state_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|path| path.join("alacritty\\window_size"))
+}
+
+/// Save the size of a closed window for the next start
+pub fn save(terminal: &Term) {
+    let path = match state_path() {
+        Some(path) => path,
+        None => {
+            warn!("Unable to find a directory for saving the window size");
+            return;
+        },
+    };
+
+    let dimensions = Dimensions::new(terminal.cols(), terminal.lines());
+
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| File::create(&path))
+        .and_then(|file| write(file, dimensions));
+
+    if let Err(err) = result {
+        warn!("Unable to save window size to {:?}: {}", path, err);
+    }
+}
+
+/// Size of the last closed window
+///
+/// Missing and corrupt files are ignored, the configured size is used instead.
+pub fn load() -> Option<Dimensions> {
+    let path = state_path()?;
+    let dimensions = File::open(&path).ok().and_then(read);
+    debug!("Window size loaded from {:?}: {:?}", path, dimensions);
+    dimensions
+}
+
+fn write<W: Write>(mut writer: W, dimensions: Dimensions) -> io::Result<()> {
+    writeln!(writer, "{}x{}", dimensions.columns_u32(), dimensions.lines_u32())
+}
+
+fn read<R: Read>(mut reader: R) -> Option<Dimensions> {
+    let mut content = String::new();
+    reader.read_to_string(&mut content).ok()?;
+
+    let mut parts = content.trim().splitn(2, 'x');
+    let columns = parts.next()?.parse::<usize>().ok()?;
+    let lines = parts.next()?.parse::<usize>().ok()?;
+    if columns == 0 || lines == 0 {
+        return None;
+    }
+
+    Some(Dimensions::new(Column(columns), Line(lines)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, write};
+
+    use alacritty_terminal::config::Dimensions;
+    use alacritty_terminal::index::{Column, Line};
+
+    #[test]
+    fn round_trip() {
+        let dimensions = Dimensions::new(Column(132), Line(43));
+
+        let mut buf = Vec::new();
+        write(&mut buf, dimensions).unwrap();
+
+        assert_eq!(read(&buf[..]), Some(dimensions));
+    }
+
+    #[test]
+    fn corrupt_state() {
+        for content in &["", "80", "80x", "x24", "80x24x1", "0x24", "80x0", "-1x24", "\u{fffd}"] {
+            assert_eq!(read(content.as_bytes()), None, "{:?}", content);
+        }
+    }
+}
@@ -19,6 +19,11 @@ use std::fs::File;
 use std::path::Path;
 
 fn main() {
+    // The OpenGL bindings are only used by the renderer
+    if env::var_os("CARGO_FEATURE_GUI").is_none() {
+        return;
+    }
+
     let dest = env::var("OUT_DIR").unwrap();
     let mut file = File::create(&Path::new(&dest).join("gl_bindings.rs")).unwrap();
 
@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use alacritty_terminal::testing;
+
+fuzz_target!(|data: &[u8]| {
+    testing::fuzz_parser(data);
+});
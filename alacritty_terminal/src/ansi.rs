@@ -13,17 +13,135 @@
 // limitations under the License.
 //
 //! ANSI Terminal Stream Parsing
-use std::io;
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashMap;
 use std::ops::Range;
-use std::str;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use std::{fmt, io, mem, str};
 
 use crate::index::{Column, Contains, Line};
-use base64;
-use glutin::MouseCursor;
 use vte;
 
 use crate::term::color::Rgb;
 
+/// Maximum number of characters in a window title set by an escape sequence
+pub const MAX_TITLE_LENGTH: usize = 4096;
+
+/// Largest value of a CSI parameter, bigger ones are saturated
+const MAX_CSI_PARAM: i64 = u16::MAX as i64;
+
+/// Default limit for the decoded size of OSC 52 clipboard content, in bytes
+pub const MAX_CLIPBOARD_SIZE: usize = 1024 * 1024;
+
+/// Remove all control characters from a window title and limit its length
+pub(crate) fn sanitize_title(title: &str) -> String {
+    title.chars().filter(|c| !c.is_control()).take(MAX_TITLE_LENGTH).collect()
+}
+
+/// Remove control characters from clipboard content
+///
+/// Tabs and line breaks are kept, since they are part of any multi-line text.
+fn sanitize_clipboard(text: &str) -> String {
+    text.chars().filter(|&c| !c.is_control() || c == '\t' || c == '\n' || c == '\r').collect()
+}
+
+/// Reasons for rejecting the base64 payload of a clipboard escape
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Base64Error {
+    /// Decoded content exceeds the limit
+    TooLarge,
+    /// Byte which is neither part of the standard nor the URL-safe alphabet
+    InvalidByte(u8),
+    /// Last group of characters doesn't contain a whole byte
+    Truncated,
+}
+
+/// Incremental decoder for the base64 payload of a clipboard escape
+///
+/// Both the standard and the URL-safe alphabet are accepted, even mixed, and padding is optional.
+/// Decoding stops at the first invalid byte or once more than `limit` bytes are decoded, so
+/// rejected payloads are never fully allocated.
+struct ClipboardDecoder {
+    decoded: Vec<u8>,
+    limit: usize,
+    /// Number of encoded bytes
+    len: usize,
+    bits: u32,
+    bit_count: u32,
+    padded: bool,
+    error: Option<Base64Error>,
+}
+
+impl ClipboardDecoder {
+    fn new(limit: usize) -> ClipboardDecoder {
+        ClipboardDecoder {
+            decoded: Vec::new(),
+            limit,
+            len: 0,
+            bits: 0,
+            bit_count: 0,
+            padded: false,
+            error: None,
+        }
+    }
+
+    #[inline]
+    fn advance(&mut self, byte: u8) {
+        self.len = self.len.saturating_add(1);
+        if self.error.is_some() {
+            return;
+        }
+
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => {
+                self.padded = true;
+                return;
+            },
+            _ => return self.fail(Base64Error::InvalidByte(byte)),
+        };
+
+        // Nothing but padding may follow the padding
+        if self.padded {
+            return self.fail(Base64Error::InvalidByte(byte));
+        }
+
+        self.bits = (self.bits << 6) | u32::from(value);
+        self.bit_count += 6;
+
+        if self.bit_count >= 8 {
+            if self.decoded.len() == self.limit {
+                return self.fail(Base64Error::TooLarge);
+            }
+
+            self.bit_count -= 8;
+            self.decoded.push((self.bits >> self.bit_count) as u8);
+            self.bits &= (1 << self.bit_count) - 1;
+        }
+    }
+
+    /// Stop decoding and release what was decoded so far
+    fn fail(&mut self, err: Base64Error) {
+        self.error = Some(err);
+        self.decoded = Vec::new();
+    }
+
+    fn finish(self) -> Result<Vec<u8>, Base64Error> {
+        match self.error {
+            Some(err) => Err(err),
+            // A single character in the last group only holds 6 bits
+            None if self.bit_count >= 6 => Err(Base64Error::Truncated),
+            None => Ok(self.decoded),
+        }
+    }
+}
+
 // Parse color arguments
 //
 // Expect that color argument looks like "rgb:xx/xx/xx" or "#xxxxxx"
@@ -83,6 +201,48 @@ fn parse_rgb_color(color: &[u8]) -> Option<Rgb> {
     }
 }
 
+/// Parse the path of a `file://host/path` URL with percent-encoded bytes
+///
+/// The host is ignored, a directory on another machine is only used if it exists locally.
+fn parse_file_url(url: &[u8]) -> Option<PathBuf> {
+    if !url.starts_with(b"file://") {
+        return None;
+    }
+
+    let url = &url[7..];
+    let path = &url[url.iter().position(|&b| b == b'/')?..];
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut iter = path.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [*iter.next()?, *iter.next()?];
+            let hex = str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    let path = String::from_utf8(bytes).ok()?;
+    Some(PathBuf::from(path))
+}
+
+/// Parse the name of a mouse cursor shape set by OSC 22
+///
+/// Both the CSS names and the names of the X11 cursor font used by xterm are accepted. The
+/// default shape isn't included, since it restores the automatic shape instead.
+fn parse_mouse_cursor(name: &[u8]) -> Option<MouseCursorIcon> {
+    match name {
+        b"text" | b"xterm" => Some(MouseCursorIcon::Text),
+        b"pointer" | b"hand" | b"hand1" | b"hand2" => Some(MouseCursorIcon::Hand),
+        b"crosshair" | b"cross" => Some(MouseCursorIcon::Crosshair),
+        b"wait" | b"watch" => Some(MouseCursorIcon::Wait),
+        b"not-allowed" | b"circle" => Some(MouseCursorIcon::NotAllowed),
+        _ => None,
+    }
+}
+
 fn parse_number(input: &[u8]) -> Option<u8> {
     if input.is_empty() {
         return None;
@@ -111,19 +271,362 @@ pub struct Processor {
 /// Internal state for VTE processor
 struct ProcessorState {
     preceding_char: Option<char>,
+    unhandled: Unhandled,
+    osc: OscString,
+    csi: CsiParamCount,
+}
+
+/// Size of the buffer used by the parser for OSC strings
+const MAX_OSC_LENGTH: usize = 1024;
+
+/// Terminator of an OSC or DCS string
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StringTerminator {
+    /// BEL, used by most applications for OSC strings
+    Bel,
+    /// String terminator, `ESC \`
+    St,
+}
+
+impl Default for StringTerminator {
+    fn default() -> StringTerminator {
+        StringTerminator::Bel
+    }
+}
+
+impl StringTerminator {
+    /// Bytes written to the pty to terminate a reply
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StringTerminator::Bel => "\x07",
+            StringTerminator::St => "\x1b\\",
+        }
+    }
+}
+
+/// OSC string which is currently being parsed
+///
+/// The parser silently truncates OSC strings which don't fit into its buffer and dispatches
+/// whatever was kept. Following its state transitions allows discarding these instead of
+/// acting on a partial payload.
+///
+/// Clipboard content is usually much larger than the buffer, so the payload of OSC 52 is
+/// decoded here while it's passed through instead.
+#[derive(Default)]
+struct OscString {
+    state: OscState,
+    /// Number of bytes stored in the parser's buffer
+    len: usize,
+    /// How the last OSC string was terminated, replies to queries use the same terminator
+    terminator: StringTerminator,
+    /// Number of parameters before the current one
+    separators: usize,
+    /// Start of the first parameter, it's checked for `52` when it ends
+    command: [u8; 2],
+    command_len: usize,
+    /// Payload of an OSC 52 string
+    clipboard: Option<ClipboardDecoder>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum OscState {
+    Ground,
+    Escape,
+    String,
+}
+
+impl Default for OscState {
+    fn default() -> OscState {
+        OscState::Ground
+    }
+}
+
+impl OscString {
+    /// Update the state with the next byte, before it is passed to the parser
+    #[inline]
+    fn advance<H: Handler + ?Sized>(&mut self, byte: u8, handler: &H) {
+        match (self.state, byte) {
+            // CAN and SUB abort every sequence, ESC starts a new one
+            (_, 0x18) | (_, 0x1a) => self.state = OscState::Ground,
+            (OscState::String, 0x1b) => {
+                self.state = OscState::Escape;
+                self.terminator = StringTerminator::St;
+            },
+            (_, 0x1b) => self.state = OscState::Escape,
+            (OscState::Escape, b']') => {
+                self.state = OscState::String;
+                self.len = 0;
+                self.separators = 0;
+                self.command_len = 0;
+                self.clipboard = None;
+            },
+            // Control characters are executed without leaving the escape
+            (OscState::Escape, 0x00..=0x1f) | (OscState::Escape, 0x7f) => (),
+            (OscState::Escape, _) => self.state = OscState::Ground,
+            (OscState::String, 0x07) => {
+                self.state = OscState::Ground;
+                self.terminator = StringTerminator::Bel;
+            },
+            // Separators aren't stored in the parser's buffer
+            (OscState::String, b';') => {
+                self.separators = self.separators.saturating_add(1);
+                if self.separators == 2 && self.command_len == 2 && self.command == *b"52" {
+                    self.clipboard = Some(ClipboardDecoder::new(handler.clipboard_size_limit()));
+                }
+            },
+            (OscState::String, 0x20..=0xff) => match self.clipboard {
+                Some(ref mut clipboard) if self.separators == 2 => clipboard.advance(byte),
+                _ => {
+                    if self.separators == 0 {
+                        if let Some(command) = self.command.get_mut(self.command_len) {
+                            *command = byte;
+                        }
+                        self.command_len = self.command_len.saturating_add(1);
+                    }
+                    self.len = self.len.saturating_add(1);
+                },
+            },
+            _ => (),
+        }
+    }
+
+    /// Take the decoded payload of the last OSC 52 string
+    #[inline]
+    fn take_clipboard(&mut self) -> Option<ClipboardDecoder> {
+        self.clipboard.take()
+    }
+
+    /// Whether the last OSC string was truncated by the parser
+    #[inline]
+    fn overflowed(&self) -> bool {
+        self.len > MAX_OSC_LENGTH
+    }
+}
+
+/// Number of CSI parameters kept by the parser
+///
+/// Once the limit is reached, the parser keeps appending the digits of all remaining
+/// parameters to the last one. Only the parameters before it are passed on to the handler.
+const MAX_CSI_PARAMS: usize = 16;
+
+/// Number of parameters of the CSI sequence which is currently being parsed
+#[derive(Default)]
+struct CsiParamCount {
+    state: CsiState,
+    separators: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CsiState {
+    Ground,
+    Escape,
+    Params,
+}
+
+impl Default for CsiState {
+    fn default() -> CsiState {
+        CsiState::Ground
+    }
+}
+
+impl CsiParamCount {
+    /// Update the state with the next byte, before it is passed to the parser
+    #[inline]
+    fn advance(&mut self, byte: u8) {
+        match (self.state, byte) {
+            // CAN and SUB abort every sequence, ESC starts a new one
+            (_, 0x18) | (_, 0x1a) => self.state = CsiState::Ground,
+            (_, 0x1b) => self.state = CsiState::Escape,
+            (CsiState::Escape, b'[') => {
+                self.state = CsiState::Params;
+                self.separators = 0;
+            },
+            // Control characters are executed without leaving the escape
+            (CsiState::Escape, 0x00..=0x1f) | (CsiState::Escape, 0x7f) => (),
+            (CsiState::Escape, _) => self.state = CsiState::Ground,
+            (CsiState::Params, b';') => self.separators = self.separators.saturating_add(1),
+            (CsiState::Params, 0x40..=0x7e) => self.state = CsiState::Ground,
+            _ => (),
+        }
+    }
+
+    /// Whether the last CSI sequence had more parameters than the parser can store
+    #[inline]
+    fn truncated(&self) -> bool {
+        self.separators >= MAX_CSI_PARAMS
+    }
+}
+
+/// Interval between summaries of repeated unhandled sequences in the log
+const UNHANDLED_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Unhandled sequence with its parameters removed, repetitions are aggregated by signature
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Signature {
+    Execute(u8),
+    Csi { intermediates: [u8; 2], action: char },
+    Esc { intermediates: [u8; 2], byte: u8 },
+    Osc(Option<u32>),
+    Dcs { intermediates: [u8; 2] },
+}
+
+impl Signature {
+    fn csi(intermediates: &[u8], action: char) -> Signature {
+        Signature::Csi { intermediates: Self::intermediates(intermediates), action }
+    }
+
+    fn esc(intermediates: &[u8], byte: u8) -> Signature {
+        Signature::Esc { intermediates: Self::intermediates(intermediates), byte }
+    }
+
+    /// OSC sequences are identified by their first parameter
+    fn osc(params: &[&[u8]]) -> Signature {
+        let number = params.get(0).and_then(|param| str::from_utf8(param).ok());
+        Signature::Osc(number.and_then(|number| number.parse().ok()))
+    }
+
+    fn dcs(intermediates: &[u8]) -> Signature {
+        Signature::Dcs { intermediates: Self::intermediates(intermediates) }
+    }
+
+    /// First two intermediates, padded with zeros
+    fn intermediates(intermediates: &[u8]) -> [u8; 2] {
+        let mut normalized = [0; 2];
+        for (normalized, &intermediate) in normalized.iter_mut().zip(intermediates) {
+            *normalized = intermediate;
+        }
+        normalized
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let intermediates = |intermediates: &[u8; 2]| -> String {
+            intermediates.iter().filter(|&&byte| byte != 0).map(|&byte| byte as char).collect()
+        };
+
+        match self {
+            Signature::Execute(byte) => write!(f, "execute {:02x}", byte),
+            Signature::Csi { intermediates: ints, action } => {
+                write!(f, "CSI {}{}", intermediates(ints), action)
+            },
+            Signature::Esc { intermediates: ints, byte } => {
+                write!(f, "ESC {}{}", intermediates(ints), *byte as char)
+            },
+            Signature::Osc(Some(number)) => write!(f, "OSC {}", number),
+            Signature::Osc(None) => write!(f, "OSC"),
+            Signature::Dcs { intermediates: ints } => write!(f, "DCS {}", intermediates(ints)),
+        }
+    }
+}
+
+/// Number of unhandled sequences, shown in the render timer
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct UnhandledStats {
+    pub total: usize,
+
+    /// Number of different signatures
+    pub kinds: usize,
+}
+
+/// What has to be logged for an unhandled sequence
+#[derive(Debug, PartialEq, Eq)]
+pub enum UnhandledLog {
+    /// First occurrence of a signature, logged with all details
+    First,
+
+    /// Repetitions of all signatures since the last summary
+    Summary(Vec<(Signature, usize)>),
+}
+
+/// Aggregation of unhandled sequences, to avoid flooding the log with repetitions
+#[derive(Debug, Default)]
+pub struct Unhandled {
+    /// Occurrences of every signature, in total and since the last summary
+    counts: HashMap<Signature, (usize, usize)>,
+    total: usize,
+    next_summary: Option<Instant>,
+}
+
+impl Unhandled {
+    /// Count an unhandled sequence without logging it
+    #[inline]
+    pub fn count(&mut self, signature: Signature) {
+        self.total += 1;
+        self.counts.entry(signature).or_insert((0, 0)).0 += 1;
+    }
+
+    /// Count an unhandled sequence and decide what has to be logged at `now`
+    ///
+    /// The first occurrence of every signature is logged in detail, repetitions are only
+    /// summarized every `UNHANDLED_SUMMARY_INTERVAL`.
+    pub fn report(&mut self, signature: Signature, now: Instant) -> Option<UnhandledLog> {
+        let deadline = *self.next_summary.get_or_insert(now + UNHANDLED_SUMMARY_INTERVAL);
+
+        self.total += 1;
+        let (total, repeated) = self.counts.entry(signature).or_insert((0, 0));
+        *total += 1;
+        if *total == 1 {
+            return Some(UnhandledLog::First);
+        }
+
+        *repeated += 1;
+        if now < deadline {
+            return None;
+        }
+
+        self.next_summary = Some(now + UNHANDLED_SUMMARY_INTERVAL);
+
+        let mut summary: Vec<_> = self
+            .counts
+            .iter_mut()
+            .filter(|(_, (_, repeated))| *repeated > 0)
+            .map(|(signature, (_, repeated))| (*signature, mem::replace(repeated, 0)))
+            .collect();
+        summary.sort_by(|a, b| b.1.cmp(&a.1));
+        Some(UnhandledLog::Summary(summary))
+    }
+
+    pub fn stats(&self) -> UnhandledStats {
+        UnhandledStats { total: self.total, kinds: self.counts.len() }
+    }
+}
+
+/// Count an unhandled sequence and log it if necessary
+///
+/// The `details` are only formatted when they are actually written to the log.
+#[inline]
+fn report_unhandled<F>(unhandled: &mut Unhandled, signature: Signature, details: F)
+where
+    F: FnOnce() -> String,
+{
+    if !log_enabled!(log::Level::Debug) {
+        unhandled.count(signature);
+        return;
+    }
+
+    match unhandled.report(signature, Instant::now()) {
+        Some(UnhandledLog::First) => debug!("[unhandled] {}", details()),
+        Some(UnhandledLog::Summary(summary)) => {
+            for (signature, count) in summary {
+                debug!("[unhandled] {} repeated {} times", signature, count);
+            }
+        },
+        None => (),
+    }
 }
 
 /// Helper type that implements `vte::Perform`.
 ///
 /// Processor creates a Performer when running advance and passes the Performer
 /// to `vte::Parser`.
-struct Performer<'a, H: Handler + TermInfo, W: io::Write> {
+struct Performer<'a, H: Handler + TermInfo + ?Sized, W: io::Write> {
     _state: &'a mut ProcessorState,
     handler: &'a mut H,
     writer: &'a mut W,
 }
 
-impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
+impl<'a, H: Handler + TermInfo + ?Sized + 'a, W: io::Write> Performer<'a, H, W> {
     /// Create a performer
     #[inline]
     pub fn new<'b>(
@@ -137,7 +640,13 @@ impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
 
 impl Default for Processor {
     fn default() -> Processor {
-        Processor { state: ProcessorState { preceding_char: None }, parser: vte::Parser::new() }
+        let state = ProcessorState {
+            preceding_char: None,
+            unhandled: Unhandled::default(),
+            osc: OscString::default(),
+            csi: CsiParamCount::default(),
+        };
+        Processor { state, parser: vte::Parser::new() }
     }
 }
 
@@ -146,15 +655,41 @@ impl Processor {
         Default::default()
     }
 
+    /// Number of sequences which were not handled
+    #[inline]
+    pub fn unhandled_stats(&self) -> UnhandledStats {
+        self.state.unhandled.stats()
+    }
+
     #[inline]
     pub fn advance<H, W>(&mut self, handler: &mut H, byte: u8, writer: &mut W)
     where
-        H: Handler + TermInfo,
+        H: Handler + TermInfo + ?Sized,
         W: io::Write,
     {
+        self.state.osc.advance(byte, handler);
+        self.state.csi.advance(byte);
         let mut performer = Performer::new(&mut self.state, handler, writer);
         self.parser.advance(&mut performer, byte);
     }
+
+    /// Parse a buffer of pty output
+    ///
+    /// Equivalent to calling `advance` for every byte, without setting up the performer for
+    /// each one of them.
+    #[inline]
+    pub fn advance_bytes<H, W>(&mut self, handler: &mut H, bytes: &[u8], writer: &mut W)
+    where
+        H: Handler + TermInfo + ?Sized,
+        W: io::Write,
+    {
+        let mut performer = Performer::new(&mut self.state, handler, writer);
+        for &byte in bytes {
+            performer._state.osc.advance(byte, performer.handler);
+            performer._state.csi.advance(byte);
+            self.parser.advance(&mut performer, byte);
+        }
+    }
 }
 
 /// Trait that provides properties of terminal
@@ -171,8 +706,8 @@ pub trait Handler {
     /// OSC to set window title
     fn set_title(&mut self, _: &str) {}
 
-    /// Set the window's mouse cursor
-    fn set_mouse_cursor(&mut self, _: MouseCursor) {}
+    /// OSC 22 to set the shape of the mouse cursor, `None` restores the automatic shape
+    fn set_mouse_cursor(&mut self, _: Option<MouseCursorIcon>) {}
 
     /// Set the cursor style
     fn set_cursor_style(&mut self, _: Option<CursorStyle>) {}
@@ -201,10 +736,10 @@ pub trait Handler {
     /// Identify the terminal (should write back to the pty stream)
     ///
     /// TODO this should probably return an io::Result
-    fn identify_terminal<W: io::Write>(&mut self, _: &mut W) {}
+    fn identify_terminal(&mut self, _: &mut dyn io::Write) {}
 
     // Report device status
-    fn device_status<W: io::Write>(&mut self, _: &mut W, _: usize) {}
+    fn device_status(&mut self, _: &mut dyn io::Write, _: usize) {}
 
     /// Move cursor forward `cols`
     fn move_forward(&mut self, _: Column) {}
@@ -333,18 +868,233 @@ pub trait Handler {
     fn set_color(&mut self, _: usize, _: Rgb) {}
 
     /// Write a foreground/background color escape sequence with the current color
-    fn dynamic_color_sequence<W: io::Write>(&mut self, _: &mut W, _: u8, _: usize) {}
+    fn dynamic_color_sequence(
+        &mut self,
+        _: &mut dyn io::Write,
+        _: u8,
+        _: usize,
+        _: StringTerminator,
+    ) {
+    }
+
+    /// Write an OSC 4 escape sequence with the current value of an indexed color
+    fn indexed_color_sequence(&mut self, _: &mut dyn io::Write, _: usize, _: StringTerminator) {}
 
     /// Reset an indexed color to original value
     fn reset_color(&mut self, _: usize) {}
 
-    /// Set the clipboard
-    fn set_clipboard(&mut self, _: &str) {}
+    /// Store text in the clipboard selected by an OSC 52 target character
+    fn set_clipboard(&mut self, _: u8, _: &str) {}
+
+    /// Maximum size of text stored in the clipboard by OSC 52, in bytes
+    fn clipboard_size_limit(&self) -> usize {
+        MAX_CLIPBOARD_SIZE
+    }
+
+    /// Whether invalid UTF-8 in OSC 52 clipboard content is replaced instead of rejected
+    fn clipboard_lossy_utf8(&self) -> bool {
+        false
+    }
+
+    /// OSC to set the opacity of the entire window, `None` restores the configured opacity
+    fn set_window_opacity(&mut self, _: Option<f32>) {}
+
+    /// OSC 7 to report the working directory of the shell
+    fn set_working_directory(&mut self, _: PathBuf) {}
+
+    /// OSC 5522 to report the lines in the scrollback history and how far it is scrolled up
+    fn scroll_status(&mut self, _: &mut dyn io::Write, _: StringTerminator) {}
 
     /// Run the dectest routine
     fn dectest(&mut self) {}
 }
 
+/// Handler which logs every call before forwarding it to another handler
+///
+/// Printable characters are coalesced into runs, which are logged as a single string once
+/// the run is interrupted by another call or the handler is dropped.
+pub struct DebugHandler<'a, H: ?Sized> {
+    handler: &'a mut H,
+    run: String,
+}
+
+impl<'a, H: ?Sized> DebugHandler<'a, H> {
+    pub fn new(handler: &'a mut H) -> DebugHandler<'a, H> {
+        DebugHandler { handler, run: String::new() }
+    }
+
+    /// Log the pending run of printable characters
+    pub fn flush(&mut self) {
+        if !self.run.is_empty() {
+            info!("Handler input: {:?}", self.run);
+            self.run.clear();
+        }
+    }
+}
+
+impl<'a, H: ?Sized> Drop for DebugHandler<'a, H> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl<'a, H: TermInfo + ?Sized> TermInfo for DebugHandler<'a, H> {
+    fn lines(&self) -> Line {
+        self.handler.lines()
+    }
+
+    fn cols(&self) -> Column {
+        self.handler.cols()
+    }
+}
+
+/// Log and forward calls to `Handler` methods without a writer
+macro_rules! debug_forward {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(&mut self, $($arg: $ty),*) {
+                self.flush();
+                let args: &[String] = &[$(format!("{:?}", $arg)),*];
+                info!("Handler call: {}({})", stringify!($method), args.join(", "));
+                self.handler.$method($($arg),*);
+            }
+        )*
+    };
+}
+
+impl<'a, H: Handler + ?Sized> Handler for DebugHandler<'a, H> {
+    debug_forward! {
+        set_title(title: &str);
+        set_mouse_cursor(cursor: Option<MouseCursorIcon>);
+        set_cursor_style(style: Option<CursorStyle>);
+        goto(line: Line, col: Column);
+        goto_line(line: Line);
+        goto_col(col: Column);
+        insert_blank(count: Column);
+        move_up(lines: Line);
+        move_down(lines: Line);
+        move_forward(cols: Column);
+        move_backward(cols: Column);
+        move_down_and_cr(lines: Line);
+        move_up_and_cr(lines: Line);
+        put_tab(count: i64);
+        backspace();
+        carriage_return();
+        linefeed();
+        bell();
+        substitute();
+        newline();
+        set_horizontal_tabstop();
+        scroll_up(lines: Line);
+        scroll_down(lines: Line);
+        insert_blank_lines(count: Line);
+        delete_lines(count: Line);
+        erase_chars(count: Column);
+        delete_chars(count: Column);
+        move_backward_tabs(count: i64);
+        move_forward_tabs(count: i64);
+        save_cursor_position();
+        restore_cursor_position();
+        clear_line(mode: LineClearMode);
+        clear_screen(mode: ClearMode);
+        clear_tabs(mode: TabulationClearMode);
+        reset_state();
+        reverse_index();
+        terminal_attribute(attr: Attr);
+        set_mode(mode: Mode);
+        unset_mode(mode: Mode);
+        set_scrolling_region(region: Range<Line>);
+        set_keypad_application_mode();
+        unset_keypad_application_mode();
+        set_active_charset(index: CharsetIndex);
+        configure_charset(index: CharsetIndex, charset: StandardCharset);
+        set_color(index: usize, color: Rgb);
+        reset_color(index: usize);
+        set_clipboard(clipboard: u8, text: &str);
+        set_window_opacity(opacity: Option<f32>);
+        set_working_directory(path: PathBuf);
+        dectest();
+    }
+
+    fn input(&mut self, c: char) {
+        self.run.push(c);
+        self.handler.input(c);
+    }
+
+    fn clipboard_size_limit(&self) -> usize {
+        self.handler.clipboard_size_limit()
+    }
+
+    fn clipboard_lossy_utf8(&self) -> bool {
+        self.handler.clipboard_lossy_utf8()
+    }
+
+    fn identify_terminal(&mut self, writer: &mut dyn io::Write) {
+        self.flush();
+        info!("Handler call: identify_terminal()");
+        self.handler.identify_terminal(writer);
+    }
+
+    fn device_status(&mut self, writer: &mut dyn io::Write, arg: usize) {
+        self.flush();
+        info!("Handler call: device_status({:?})", arg);
+        self.handler.device_status(writer, arg);
+    }
+
+    fn dynamic_color_sequence(
+        &mut self,
+        writer: &mut dyn io::Write,
+        code: u8,
+        index: usize,
+        terminator: StringTerminator,
+    ) {
+        self.flush();
+        info!("Handler call: dynamic_color_sequence({:?}, {:?}, {:?})", code, index, terminator);
+        self.handler.dynamic_color_sequence(writer, code, index, terminator);
+    }
+
+    fn indexed_color_sequence(
+        &mut self,
+        writer: &mut dyn io::Write,
+        index: usize,
+        terminator: StringTerminator,
+    ) {
+        self.flush();
+        info!("Handler call: indexed_color_sequence({:?}, {:?})", index, terminator);
+        self.handler.indexed_color_sequence(writer, index, terminator);
+    }
+
+    fn scroll_status(&mut self, writer: &mut dyn io::Write, terminator: StringTerminator) {
+        self.flush();
+        info!("Handler call: scroll_status({:?})", terminator);
+        self.handler.scroll_status(writer, terminator);
+    }
+}
+
+/// Icon of the mouse cursor while it is over the terminal
+///
+/// This is converted to the windowing library's cursor type by the window.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum MouseCursorIcon {
+    /// Default arrow
+    Default,
+
+    /// Text selection I-beam
+    Text,
+
+    /// Pointing hand, used for clickable URLs
+    Hand,
+
+    /// Crosshair for precise selection
+    Crosshair,
+
+    /// Busy application
+    Wait,
+
+    /// Action which isn't allowed at the current position
+    NotAllowed,
+}
+
 /// Describes shape of cursor
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Hash, Deserialize)]
 pub enum CursorStyle {
@@ -394,6 +1144,10 @@ pub enum Mode {
     /// * `CSI 4 h` change to insert mode
     /// * `CSI 4 l` reset to replacement mode
     Insert = 4,
+    /// ?5
+    ///
+    /// DECSCNM swaps the foreground and background colors of the whole screen
+    ReverseVideo = 5,
     /// ?6
     Origin = 6,
     /// ?7
@@ -417,6 +1171,10 @@ pub enum Mode {
     ReportFocusInOut = 1004,
     /// ?1006
     SgrMouse = 1006,
+    /// ?1034
+    ///
+    /// Reset makes Alt send plain characters, set restores the `alt_send_esc` config
+    InterpretMetaKey = 1034,
     /// ?1049
     SwapScreenAndSetRestoreCursor = 1049,
     /// ?2004
@@ -432,6 +1190,7 @@ impl Mode {
             Some(match num {
                 1 => Mode::CursorKeys,
                 3 => Mode::DECCOLM,
+                5 => Mode::ReverseVideo,
                 6 => Mode::Origin,
                 7 => Mode::LineWrap,
                 12 => Mode::BlinkingCursor,
@@ -441,6 +1200,7 @@ impl Mode {
                 1003 => Mode::ReportAllMouseMotion,
                 1004 => Mode::ReportFocusInOut,
                 1006 => Mode::SgrMouse,
+                1034 => Mode::InterpretMetaKey,
                 1049 => Mode::SwapScreenAndSetRestoreCursor,
                 2004 => Mode::BracketedPaste,
                 _ => {
@@ -694,7 +1454,7 @@ impl Default for StandardCharset {
 
 impl<'a, H, W> vte::Perform for Performer<'a, H, W>
 where
-    H: Handler + TermInfo + 'a,
+    H: Handler + TermInfo + ?Sized + 'a,
     W: io::Write + 'a,
 {
     #[inline]
@@ -717,34 +1477,32 @@ where
             C1::NEL => self.handler.newline(),
             C1::HTS => self.handler.set_horizontal_tabstop(),
             C1::DECID => self.handler.identify_terminal(self.writer),
-            _ => debug!("[unhandled] execute byte={:02x}", byte),
+            _ => report_unhandled(&mut self._state.unhandled, Signature::Execute(byte), || {
+                format!("execute byte={:02x}", byte)
+            }),
         }
     }
 
     #[inline]
     fn hook(&mut self, params: &[i64], intermediates: &[u8], ignore: bool) {
-        debug!(
-            "[unhandled hook] params={:?}, ints: {:?}, ignore: {:?}",
-            params, intermediates, ignore
-        );
+        report_unhandled(&mut self._state.unhandled, Signature::dcs(intermediates), || {
+            format!("hook params={:?}, ints: {:?}, ignore: {:?}", params, intermediates, ignore)
+        });
     }
 
+    // The data of DCS sequences is accounted for by `hook`
     #[inline]
-    fn put(&mut self, byte: u8) {
-        debug!("[unhandled put] byte={:?}", byte);
-    }
+    fn put(&mut self, _byte: u8) {}
 
     #[inline]
-    fn unhook(&mut self) {
-        debug!("[unhandled unhook]");
-    }
+    fn unhook(&mut self) {}
 
     // TODO replace OSC parsing with parser combinators
     #[inline]
     fn osc_dispatch(&mut self, params: &[&[u8]]) {
         let writer = &mut self.writer;
 
-        fn unhandled(params: &[&[u8]]) {
+        fn details(params: &[&[u8]]) -> String {
             let mut buf = String::new();
             for items in params {
                 buf.push_str("[");
@@ -753,7 +1511,24 @@ where
                 }
                 buf.push_str("],");
             }
-            debug!("[unhandled osc_dispatch]: [{}] at line {}", &buf, line!());
+            format!("osc_dispatch: [{}]", &buf)
+        }
+
+        macro_rules! unhandled {
+            () => {
+                report_unhandled(&mut self._state.unhandled, Signature::osc(params), || {
+                    details(params)
+                })
+            };
+        }
+
+        // Only the start of oversized strings is kept by the parser, which can't be used
+        if self._state.osc.overflowed() {
+            warn!(
+                "Discarding OSC sequence of {} bytes, the limit is {} bytes",
+                self._state.osc.len, MAX_OSC_LENGTH
+            );
+            return;
         }
 
         if params.is_empty() || params[0].is_empty() {
@@ -765,30 +1540,59 @@ where
             b"0" | b"2" => {
                 if params.len() >= 2 {
                     if let Ok(utf8_title) = str::from_utf8(params[1]) {
-                        self.handler.set_title(utf8_title);
+                        self.handler.set_title(&sanitize_title(utf8_title));
                         return;
                     }
                 }
-                unhandled(params);
+                unhandled!();
             },
 
             // Set icon name
             // This is ignored, since alacritty has no concept of tabs
             b"1" => return,
 
+            // Set mouse cursor shape, the default restores the automatic shape
+            b"22" => match params.get(1).map_or(&b""[..], |name| *name) {
+                b"" | b"default" | b"left_ptr" => self.handler.set_mouse_cursor(None),
+                name => match parse_mouse_cursor(name) {
+                    Some(cursor) => self.handler.set_mouse_cursor(Some(cursor)),
+                    None => {
+                        debug!("Ignoring unknown mouse cursor {:?}", String::from_utf8_lossy(name))
+                    },
+                },
+            },
+
+            // Report working directory
+            b"7" => match params.get(1).and_then(|url| parse_file_url(url)) {
+                Some(path) => self.handler.set_working_directory(path),
+                None => unhandled!(),
+            },
+
             // Set color index
             b"4" => {
-                if params.len() > 1 && params.len() % 2 != 0 {
-                    for chunk in params[1..].chunks(2) {
-                        let index = parse_number(chunk[0]);
-                        let color = parse_rgb_color(chunk[1]);
-                        if let (Some(i), Some(c)) = (index, color) {
-                            self.handler.set_color(i as usize, c);
-                            return;
-                        }
+                if params.len() <= 1 || params.len() % 2 == 0 {
+                    return unhandled!();
+                }
+
+                // Palettes are usually changed with several colors per sequence
+                let terminator = self._state.osc.terminator;
+                for chunk in params[1..].chunks(2) {
+                    let index = match parse_number(chunk[0]) {
+                        Some(index) => index as usize,
+                        None => {
+                            unhandled!();
+                            continue;
+                        },
+                    };
+
+                    if let Some(color) = parse_rgb_color(chunk[1]) {
+                        self.handler.set_color(index, color);
+                    } else if chunk[1] == b"?" {
+                        self.handler.indexed_color_sequence(writer, index, terminator);
+                    } else {
+                        unhandled!();
                     }
                 }
-                unhandled(params);
             },
 
             // Get/set Foreground, Background, Cursor colors
@@ -802,23 +1606,29 @@ where
 
                             // End of setting dynamic colors
                             if index > NamedColor::Cursor as usize {
-                                unhandled(params);
+                                unhandled!();
                                 break;
                             }
 
                             if let Some(color) = parse_rgb_color(param) {
                                 self.handler.set_color(index, color);
                             } else if param == b"?" {
-                                self.handler.dynamic_color_sequence(writer, dynamic_code, index);
+                                let terminator = self._state.osc.terminator;
+                                self.handler.dynamic_color_sequence(
+                                    writer,
+                                    dynamic_code,
+                                    index,
+                                    terminator,
+                                );
                             } else {
-                                unhandled(params);
+                                unhandled!();
                             }
                             dynamic_code += 1;
                         }
                         return;
                     }
                 }
-                unhandled(params);
+                unhandled!();
             },
 
             // Set cursor style
@@ -831,28 +1641,53 @@ where
                         '0' => CursorStyle::Block,
                         '1' => CursorStyle::Beam,
                         '2' => CursorStyle::Underline,
-                        _ => return unhandled(params),
+                        _ => return unhandled!(),
                     };
                     self.handler.set_cursor_style(Some(style));
                     return;
                 }
-                unhandled(params);
+                unhandled!();
             },
 
             // Set clipboard
             b"52" => {
                 if params.len() < 3 {
-                    return unhandled(params);
+                    return unhandled!();
                 }
 
-                match params[2] {
-                    b"?" => unhandled(params),
-                    selection => {
-                        if let Ok(string) = base64::decode(selection) {
-                            if let Ok(utf8_string) = str::from_utf8(&string) {
-                                self.handler.set_clipboard(utf8_string);
+                // Without a target, the clipboard is used
+                let clipboard = params[1].get(0).unwrap_or(&b'c');
+                match (params[2], self._state.osc.take_clipboard()) {
+                    (b"?", _) | (_, None) => unhandled!(),
+                    // The payload was decoded while it was parsed, since the parser only keeps
+                    // its start. The limit is enforced before anything large is allocated.
+                    (_, Some(decoder)) => {
+                        let (len, limit) = (decoder.len, decoder.limit);
+                        let decoded = match decoder.finish() {
+                            Ok(decoded) => decoded,
+                            Err(Base64Error::TooLarge) => {
+                                warn!(
+                                    "Ignoring clipboard escape of {} encoded bytes, the limit is \
+                                     {} bytes",
+                                    len, limit
+                                );
+                                return;
+                            },
+                            Err(err) => {
+                                debug!("Ignoring clipboard escape with invalid base64: {:?}", err);
+                                return;
+                            },
+                        };
+
+                        let text = if self.handler.clipboard_lossy_utf8() {
+                            String::from_utf8_lossy(&decoded)
+                        } else {
+                            match str::from_utf8(&decoded) {
+                                Ok(text) => Cow::Borrowed(text),
+                                Err(_) => return unhandled!(),
                             }
-                        }
+                        };
+                        self.handler.set_clipboard(*clipboard, &sanitize_clipboard(&text));
                     },
                 }
             },
@@ -871,11 +1706,32 @@ where
                 for param in &params[1..] {
                     match parse_number(param) {
                         Some(index) => self.handler.reset_color(index as usize),
-                        None => unhandled(params),
+                        None => unhandled!(),
                     }
                 }
             },
 
+            // Set window opacity
+            b"1001" => {
+                let opacity = match params.get(1).filter(|param| !param.is_empty()) {
+                    None => None,
+                    Some(param) => match str::from_utf8(param).ok().and_then(|s| s.parse().ok()) {
+                        Some(opacity) => Some(opacity),
+                        None => return unhandled!(),
+                    },
+                };
+                self.handler.set_window_opacity(opacity);
+            },
+
+            // Query the scrollback status, this is private to Alacritty
+            b"5522" => {
+                if params.len() == 2 && params[1] == b"?" {
+                    self.handler.scroll_status(writer, self._state.osc.terminator);
+                } else {
+                    unhandled!();
+                }
+            },
+
             // Reset foreground color
             b"110" => self.handler.reset_color(NamedColor::Foreground as usize),
 
@@ -885,26 +1741,42 @@ where
             // Reset text cursor color
             b"112" => self.handler.reset_color(NamedColor::Cursor as usize),
 
-            _ => unhandled(params),
+            _ => unhandled!(),
         }
     }
 
     #[inline]
     fn csi_dispatch(&mut self, args: &[i64], intermediates: &[u8], has_ignored_intermediates: bool, action: char) {
+        // The last parameter is garbage when there were too many, everything before it is intact
+        let args = if self._state.csi.truncated() {
+            debug!("Dropping CSI parameters beyond the first {}", MAX_CSI_PARAMS - 1);
+            &args[..cmp::min(args.len(), MAX_CSI_PARAMS - 1)]
+        } else {
+            args
+        };
+
         macro_rules! unhandled {
             () => {{
-                debug!(
-                    "[Unhandled CSI] action={:?}, args={:?}, intermediates={:?}",
-                    action, args, intermediates
+                report_unhandled(
+                    &mut self._state.unhandled,
+                    Signature::csi(intermediates, action),
+                    || {
+                        format!(
+                            "CSI action={:?}, args={:?}, intermediates={:?}",
+                            action, args, intermediates
+                        )
+                    },
                 );
                 return;
             }};
         }
 
+        // Parameters are saturated, so no handler has to deal with absurd counts and positions
         macro_rules! arg_or_default {
             (idx: $idx:expr, default: $default:expr) => {
                 args.get($idx)
-                    .and_then(|v| if *v == 0 { None } else { Some(*v) })
+                    .map(|v| cmp::min(*v, MAX_CSI_PARAM))
+                    .and_then(|v| if v <= 0 { None } else { Some(v) })
                     .unwrap_or($default)
             };
         }
@@ -982,13 +1854,17 @@ where
                     None => false,
                     _ => unhandled!(),
                 };
+                // Unknown modes don't prevent the remaining ones from being applied
+                let mut unknown = false;
                 for arg in args {
-                    let mode = Mode::from_primitive(is_private_mode, *arg);
-                    match mode {
+                    match Mode::from_primitive(is_private_mode, *arg) {
                         Some(mode) => handler.unset_mode(mode),
-                        None => unhandled!(),
+                        None => unknown = true,
                     }
                 }
+                if unknown {
+                    unhandled!();
+                }
             },
             ('M', None) => handler.delete_lines(Line(arg_or_default!(idx: 0, default: 1) as usize)),
             ('X', None) => handler.erase_chars(Column(arg_or_default!(idx: 0, default: 1) as usize)),
@@ -1001,13 +1877,17 @@ where
                     None => false,
                     _ => unhandled!(),
                 };
+                // Unknown modes don't prevent the remaining ones from being applied
+                let mut unknown = false;
                 for arg in args {
-                    let mode = Mode::from_primitive(is_private_mode, *arg);
-                    match mode {
+                    match Mode::from_primitive(is_private_mode, *arg) {
                         Some(mode) => handler.set_mode(mode),
-                        None => unhandled!(),
+                        None => unknown = true,
                     }
                 }
+                if unknown {
+                    unhandled!();
+                }
             },
             ('m', None) => {
                 if args.is_empty() {
@@ -1056,9 +1936,15 @@ where
     fn esc_dispatch(&mut self, params: &[i64], intermediates: &[u8], _ignore: bool, byte: u8) {
         macro_rules! unhandled {
             () => {{
-                debug!(
-                    "[unhandled] esc_dispatch params={:?}, ints={:?}, byte={:?} ({:02x})",
-                    params, intermediates, byte as char, byte
+                report_unhandled(
+                    &mut self._state.unhandled,
+                    Signature::esc(intermediates, byte),
+                    || {
+                        format!(
+                            "esc_dispatch params={:?}, ints={:?}, byte={:?} ({:02x})",
+                            params, intermediates, byte as char, byte
+                        )
+                    },
                 );
                 return;
             }};
@@ -1207,6 +2093,8 @@ fn parse_color(attrs: &[i64], i: &mut usize) -> Option<Color> {
             // RGB color spec
             if attrs.len() < 5 {
                 debug!("Expected RGB color spec; got {:?}", attrs);
+                // Don't interpret the incomplete spec as attributes
+                *i = attrs.len() - 1;
                 return None;
             }
 
@@ -1227,6 +2115,7 @@ fn parse_color(attrs: &[i64], i: &mut usize) -> Option<Color> {
         5 => {
             if attrs.len() < 3 {
                 debug!("Expected color index; got {:?}", attrs);
+                *i = attrs.len() - 1;
                 None
             } else {
                 *i += 2;
@@ -1397,25 +2286,19 @@ pub mod C1 {
 #[cfg(test)]
 mod tests {
     use super::{
-        parse_number, parse_rgb_color, Attr, CharsetIndex, Color, Handler, Processor,
-        StandardCharset, TermInfo,
+        parse_file_url, parse_mouse_cursor, parse_number, parse_rgb_color, sanitize_title, Attr,
+        Base64Error, CharsetIndex, ClearMode, ClipboardDecoder, Color, CursorStyle, DebugHandler,
+        Handler, LineClearMode, Mode, MouseCursorIcon, NamedColor, Processor, Signature,
+        StandardCharset, StringTerminator, TabulationClearMode, TermInfo, Unhandled, UnhandledLog,
+        UnhandledStats, MAX_OSC_LENGTH, MAX_TITLE_LENGTH, UNHANDLED_SUMMARY_INTERVAL,
     };
     use crate::index::{Column, Line};
     use crate::term::color::Rgb;
+    use crate::testing::MockWriter;
     use std::io;
-
-    /// The /dev/null of `io::Write`
-    struct Void;
-
-    impl io::Write for Void {
-        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
-            Ok(bytes.len())
-        }
-
-        fn flush(&mut self) -> io::Result<()> {
-            Ok(())
-        }
-    }
+    use std::ops::Range;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
 
     #[derive(Default)]
     struct AttrHandler {
@@ -1446,7 +2329,7 @@ mod tests {
         let mut handler = AttrHandler::default();
 
         for byte in &BYTES[..] {
-            parser.advance(&mut handler, *byte, &mut Void);
+            parser.advance(&mut handler, *byte, &mut MockWriter::new());
         }
 
         assert_eq!(handler.attr, Some(Attr::Bold));
@@ -1463,7 +2346,7 @@ mod tests {
         let mut handler = AttrHandler::default();
 
         for byte in &BYTES[..] {
-            parser.advance(&mut handler, *byte, &mut Void);
+            parser.advance(&mut handler, *byte, &mut MockWriter::new());
         }
 
         let spec = Rgb { r: 128, g: 66, b: 255 };
@@ -1471,6 +2354,24 @@ mod tests {
         assert_eq!(handler.attr, Some(Attr::Foreground(Color::Spec(spec))));
     }
 
+    #[test]
+    fn parse_split_batches() {
+        static BYTES: &[u8] = b"\x1b[38;2;128;66;255m";
+
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        // Sequences split across reads are continued by the next batch
+        let (first, second) = BYTES.split_at(7);
+        parser.advance_bytes(&mut handler, first, &mut MockWriter::new());
+        assert_eq!(handler.attr, None);
+        parser.advance_bytes(&mut handler, second, &mut MockWriter::new());
+
+        let spec = Rgb { r: 128, g: 66, b: 255 };
+
+        assert_eq!(handler.attr, Some(Attr::Foreground(Color::Spec(spec))));
+    }
+
     /// No exactly a test; useful for debugging
     #[test]
     fn parse_zsh_startup() {
@@ -1494,7 +2395,7 @@ mod tests {
         let mut parser = Processor::new();
 
         for byte in &BYTES[..] {
-            parser.advance(&mut handler, *byte, &mut Void);
+            parser.advance(&mut handler, *byte, &mut MockWriter::new());
         }
     }
 
@@ -1537,7 +2438,7 @@ mod tests {
         let mut handler = CharsetHandler::default();
 
         for byte in &BYTES[..] {
-            parser.advance(&mut handler, *byte, &mut Void);
+            parser.advance(&mut handler, *byte, &mut MockWriter::new());
         }
 
         assert_eq!(handler.index, CharsetIndex::G0);
@@ -1551,18 +2452,292 @@ mod tests {
         let mut handler = CharsetHandler::default();
 
         for byte in &BYTES[..3] {
-            parser.advance(&mut handler, *byte, &mut Void);
+            parser.advance(&mut handler, *byte, &mut MockWriter::new());
         }
 
         assert_eq!(handler.index, CharsetIndex::G1);
         assert_eq!(handler.charset, StandardCharset::SpecialCharacterAndLineDrawing);
 
         let mut handler = CharsetHandler::default();
-        parser.advance(&mut handler, BYTES[3], &mut Void);
+        parser.advance(&mut handler, BYTES[3], &mut MockWriter::new());
 
         assert_eq!(handler.index, CharsetIndex::G1);
     }
 
+    #[derive(Default)]
+    struct OscHandler {
+        titles: Vec<String>,
+        clipboard: Vec<(u8, String)>,
+        input: String,
+        lossy_utf8: bool,
+    }
+
+    impl Handler for OscHandler {
+        fn set_title(&mut self, title: &str) {
+            self.titles.push(title.to_owned());
+        }
+
+        fn set_clipboard(&mut self, clipboard: u8, text: &str) {
+            self.clipboard.push((clipboard, text.to_owned()));
+        }
+
+        fn input(&mut self, c: char) {
+            self.input.push(c);
+        }
+
+        fn clipboard_size_limit(&self) -> usize {
+            4096
+        }
+
+        fn clipboard_lossy_utf8(&self) -> bool {
+            self.lossy_utf8
+        }
+    }
+
+    impl TermInfo for OscHandler {
+        fn lines(&self) -> Line {
+            Line(24)
+        }
+
+        fn cols(&self) -> Column {
+            Column(80)
+        }
+    }
+
+    #[test]
+    fn osc_title_sanitized() {
+        let mut parser = Processor::new();
+        let mut handler = OscHandler::default();
+        let mut writer = MockWriter::new();
+
+        // C1 controls are part of the OSC string, while ESC terminates it
+        parser.advance_bytes(&mut handler, "\x1b]2;a\u{9b}31mb\x07".as_bytes(), &mut writer);
+        parser.advance_bytes(&mut handler, b"\x1b]2;c\x1b[31md\x07", &mut writer);
+        parser.advance_bytes(&mut handler, b"\x1b]2;next\x07x", &mut writer);
+
+        assert_eq!(handler.titles.first().map(String::as_str), Some("a31mb"));
+        assert!(handler.titles.iter().all(|title| !title.chars().any(char::is_control)));
+        assert_eq!(handler.titles.last().map(String::as_str), Some("next"));
+        assert!(handler.input.ends_with('x'));
+    }
+
+    #[test]
+    fn title_sanitized_and_bounded() {
+        assert_eq!(sanitize_title("a\x1b[31mb\u{9b}\n"), "a[31mb");
+
+        let title = "a".repeat(MAX_TITLE_LENGTH + 1);
+        assert_eq!(sanitize_title(&title).len(), MAX_TITLE_LENGTH);
+    }
+
+    #[test]
+    fn osc_clipboard_bounded() {
+        let mut parser = Processor::new();
+        let mut handler = OscHandler::default();
+
+        // Payloads over the limit are ignored
+        let payload = base64::encode(&vec![b'a'; 10 * 1024 * 1024]);
+        parser.advance_bytes(&mut handler, b"\x1b]52;c;", &mut MockWriter::new());
+        parser.advance_bytes(&mut handler, payload.as_bytes(), &mut MockWriter::new());
+        parser.advance_bytes(&mut handler, b"\x07", &mut MockWriter::new());
+        assert!(handler.clipboard.is_empty());
+
+        // Parsing continues normally afterwards, with control characters removed
+        let payload = base64::encode("hi\tthere\n\x1b[31m\u{9b}");
+        let bytes = format!("\x1b]52;c;{}\x07x", payload);
+        parser.advance_bytes(&mut handler, bytes.as_bytes(), &mut MockWriter::new());
+        assert_eq!(handler.clipboard, vec![(b'c', String::from("hi\tthere\n[31m"))]);
+        assert_eq!(handler.input, "x");
+    }
+
+    #[test]
+    fn osc_clipboard_invalid_utf8() {
+        let mut parser = Processor::new();
+        let mut handler = OscHandler::default();
+        let bytes = format!("\x1b]52;c;{}\x07", base64::encode(b"a\xffb"));
+
+        parser.advance_bytes(&mut handler, bytes.as_bytes(), &mut MockWriter::new());
+        assert!(handler.clipboard.is_empty());
+
+        handler.lossy_utf8 = true;
+        parser.advance_bytes(&mut handler, bytes.as_bytes(), &mut MockWriter::new());
+        assert_eq!(handler.clipboard, vec![(b'c', String::from("a\u{fffd}b"))]);
+    }
+
+    #[test]
+    fn osc_clipboard_larger_than_buffer() {
+        let mut parser = Processor::new();
+        let mut handler = OscHandler::default();
+        let mut writer = MockWriter::new();
+
+        // The payload is decoded while it's parsed, up to the handler's limit
+        let text = "a".repeat(4096);
+        let bytes = format!("\x1b]52;c;{}\x07", base64::encode(&text));
+        for chunk in bytes.as_bytes().chunks(100) {
+            parser.advance_bytes(&mut handler, chunk, &mut writer);
+        }
+        assert_eq!(handler.clipboard, vec![(b'c', text)]);
+
+        let bytes = format!("\x1b]52;p;{}\x1b\\", base64::encode(&"a".repeat(4097)));
+        parser.advance_bytes(&mut handler, bytes.as_bytes(), &mut writer);
+        assert_eq!(handler.clipboard.len(), 1);
+    }
+
+    fn decode_clipboard(encoded: &[u8], limit: usize) -> Result<Vec<u8>, Base64Error> {
+        let mut decoder = ClipboardDecoder::new(limit);
+        for &byte in encoded {
+            decoder.advance(byte);
+        }
+        decoder.finish()
+    }
+
+    #[test]
+    fn decode_clipboard_oversized() {
+        let encoded = base64::encode(&[b'a'; 10]);
+        assert_eq!(decode_clipboard(encoded.as_bytes(), 9), Err(Base64Error::TooLarge));
+        assert_eq!(decode_clipboard(encoded.as_bytes(), 10), Ok(vec![b'a'; 10]));
+    }
+
+    #[test]
+    fn decode_clipboard_unpadded() {
+        assert_eq!(decode_clipboard(b"YQ", 16), Ok(b"a".to_vec()));
+        assert_eq!(decode_clipboard(b"YWI", 16), Ok(b"ab".to_vec()));
+        assert_eq!(decode_clipboard(b"YWI=", 16), Ok(b"ab".to_vec()));
+        assert_eq!(decode_clipboard(b"", 16), Ok(Vec::new()));
+        assert_eq!(decode_clipboard(b"YWJjZ", 16), Err(Base64Error::Truncated));
+        assert_eq!(decode_clipboard(b"YQ==YQ", 16), Err(Base64Error::InvalidByte(b'Y')));
+    }
+
+    #[test]
+    fn decode_clipboard_mixed_alphabet() {
+        let bytes = [0xfb, 0xff, 0xbf, 0xfb, 0xff];
+        assert_eq!(decode_clipboard(b"+/+/+/8", 16), Ok(bytes.to_vec()));
+        assert_eq!(decode_clipboard(b"-_-_-_8", 16), Ok(bytes.to_vec()));
+        assert_eq!(decode_clipboard(b"+_-/+_8=", 16), Ok(bytes.to_vec()));
+        assert_eq!(decode_clipboard(b"YW\nJj", 16), Err(Base64Error::InvalidByte(b'\n')));
+    }
+
+    #[test]
+    fn osc_oversized_discarded() {
+        let mut parser = Processor::new();
+        let mut handler = OscHandler::default();
+        let mut writer = MockWriter::new();
+
+        let payload = base64::encode(&vec![b'a'; 1024 * 1024]);
+        let bytes = format!("\x1b]52;c;{}\x07echo ok", payload);
+        parser.advance_bytes(&mut handler, bytes.as_bytes(), &mut writer);
+        assert!(handler.clipboard.is_empty());
+        assert_eq!(handler.input, "echo ok");
+
+        // Terminating with ST and feeding byte by byte behaves the same
+        let bytes = format!("\x1b]2;{}\x1b\\ok", "a".repeat(MAX_OSC_LENGTH));
+        for &byte in bytes.as_bytes() {
+            parser.advance(&mut handler, byte, &mut writer);
+        }
+        assert!(handler.titles.is_empty());
+        assert_eq!(handler.input, "echo okok");
+
+        // Strings which fit into the buffer are still dispatched
+        parser.advance_bytes(&mut handler, b"\x1b]2;title\x07", &mut writer);
+        assert_eq!(handler.titles, vec![String::from("title")]);
+    }
+
+    #[test]
+    fn osc_length_excludes_separators() {
+        let mut parser = Processor::new();
+        let mut handler = OscHandler::default();
+        let mut writer = MockWriter::new();
+
+        // Exactly fills the buffer, without the three separators
+        let title = "a".repeat(MAX_OSC_LENGTH - 3);
+        let bytes = format!("\x1b]2;{};b;c\x07", title);
+        parser.advance_bytes(&mut handler, bytes.as_bytes(), &mut writer);
+        assert_eq!(handler.titles, vec![title.clone()]);
+
+        // A single byte more doesn't fit
+        let bytes = format!("\x1b]2;{};b;cd\x07", title);
+        parser.advance_bytes(&mut handler, bytes.as_bytes(), &mut writer);
+        assert_eq!(handler.titles.len(), 1);
+    }
+
+    #[derive(Default)]
+    struct ColorHandler {
+        colors: Vec<(usize, Rgb)>,
+        queries: Vec<(usize, StringTerminator)>,
+    }
+
+    impl Handler for ColorHandler {
+        fn set_color(&mut self, index: usize, color: Rgb) {
+            self.colors.push((index, color));
+        }
+
+        fn dynamic_color_sequence(
+            &mut self,
+            _: &mut dyn io::Write,
+            _: u8,
+            index: usize,
+            terminator: StringTerminator,
+        ) {
+            self.queries.push((index, terminator));
+        }
+
+        fn indexed_color_sequence(
+            &mut self,
+            _: &mut dyn io::Write,
+            index: usize,
+            terminator: StringTerminator,
+        ) {
+            self.queries.push((index, terminator));
+        }
+    }
+
+    impl TermInfo for ColorHandler {
+        fn lines(&self) -> Line {
+            Line(24)
+        }
+
+        fn cols(&self) -> Column {
+            Column(80)
+        }
+    }
+
+    #[test]
+    fn osc_set_multiple_colors() {
+        let mut parser = Processor::new();
+        let mut handler = ColorHandler::default();
+
+        let bytes = b"\x1b]4;1;#ff0000;2;invalid;3;rgb:00/00/ff\x07";
+        parser.advance_bytes(&mut handler, bytes, &mut MockWriter::new());
+
+        let red = Rgb { r: 0xff, g: 0, b: 0 };
+        let blue = Rgb { r: 0, g: 0, b: 0xff };
+        assert_eq!(handler.colors, vec![(1, red), (3, blue)]);
+    }
+
+    #[test]
+    fn osc_query_terminator() {
+        let mut parser = Processor::new();
+        let mut handler = ColorHandler::default();
+        let mut writer = MockWriter::new();
+
+        parser.advance_bytes(&mut handler, b"\x1b]11;?\x07", &mut writer);
+        parser.advance_bytes(&mut handler, b"\x1b]11;?\x1b\\", &mut writer);
+        for &byte in b"\x1b]4;1;?;2;#ff0000\x1b\\\x1b]4;3;?\x07".iter() {
+            parser.advance(&mut handler, byte, &mut writer);
+        }
+
+        let background = NamedColor::Background as usize;
+        assert_eq!(
+            handler.queries,
+            vec![
+                (background, StringTerminator::Bel),
+                (background, StringTerminator::St),
+                (1, StringTerminator::St),
+                (3, StringTerminator::Bel),
+            ]
+        );
+        assert_eq!(handler.colors, vec![(2, Rgb { r: 0xff, g: 0, b: 0 })]);
+    }
+
     #[test]
     fn parse_valid_rgb_color() {
         assert_eq!(parse_rgb_color(b"rgb:11/aa/ff"), Some(Rgb { r: 0x11, g: 0xaa, b: 0xff }));
@@ -1587,4 +2762,435 @@ mod tests {
     fn parse_number_too_large() {
         assert_eq!(parse_number(b"321"), None);
     }
+
+    #[test]
+    fn parse_valid_file_url() {
+        assert_eq!(parse_file_url(b"file://host/home/user"), Some(PathBuf::from("/home/user")));
+        assert_eq!(parse_file_url(b"file:///tmp"), Some(PathBuf::from("/tmp")));
+        assert_eq!(
+            parse_file_url(b"file://host/home/user/My%20Files%c3%a9"),
+            Some(PathBuf::from("/home/user/My Files\u{e9}"))
+        );
+    }
+
+    #[test]
+    fn parse_invalid_file_url() {
+        assert_eq!(parse_file_url(b"/home/user"), None);
+        assert_eq!(parse_file_url(b"http://host/home/user"), None);
+        assert_eq!(parse_file_url(b"file://host"), None);
+        assert_eq!(parse_file_url(b"file://host/home/%2"), None);
+        assert_eq!(parse_file_url(b"file://host/home/%zz"), None);
+    }
+
+    #[test]
+    fn parse_mouse_cursor_names() {
+        assert_eq!(parse_mouse_cursor(b"text"), Some(MouseCursorIcon::Text));
+        assert_eq!(parse_mouse_cursor(b"pointer"), Some(MouseCursorIcon::Hand));
+        assert_eq!(parse_mouse_cursor(b"hand2"), Some(MouseCursorIcon::Hand));
+        assert_eq!(parse_mouse_cursor(b"crosshair"), Some(MouseCursorIcon::Crosshair));
+        assert_eq!(parse_mouse_cursor(b"watch"), Some(MouseCursorIcon::Wait));
+        assert_eq!(parse_mouse_cursor(b"not-allowed"), Some(MouseCursorIcon::NotAllowed));
+        assert_eq!(parse_mouse_cursor(b"Pointer"), None);
+        assert_eq!(parse_mouse_cursor(b"default"), None);
+    }
+
+    /// Handler which records every call, including the arguments
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<String>,
+    }
+
+    macro_rules! record {
+        ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+            $(
+                fn $method(&mut self, $($arg: $ty),*) {
+                    let args: &[String] = &[$(format!("{:?}", $arg)),*];
+                    self.calls.push(format!("{}({})", stringify!($method), args.join(", ")));
+                }
+            )*
+        };
+    }
+
+    impl Handler for Recorder {
+        record! {
+            set_title(title: &str);
+            set_mouse_cursor(cursor: Option<MouseCursorIcon>);
+            set_cursor_style(style: Option<CursorStyle>);
+            input(c: char);
+            goto(line: Line, col: Column);
+            goto_line(line: Line);
+            goto_col(col: Column);
+            insert_blank(count: Column);
+            move_up(lines: Line);
+            move_down(lines: Line);
+            move_forward(cols: Column);
+            move_backward(cols: Column);
+            move_down_and_cr(lines: Line);
+            move_up_and_cr(lines: Line);
+            put_tab(count: i64);
+            backspace();
+            carriage_return();
+            linefeed();
+            bell();
+            substitute();
+            newline();
+            set_horizontal_tabstop();
+            scroll_up(lines: Line);
+            scroll_down(lines: Line);
+            insert_blank_lines(count: Line);
+            delete_lines(count: Line);
+            erase_chars(count: Column);
+            delete_chars(count: Column);
+            move_backward_tabs(count: i64);
+            move_forward_tabs(count: i64);
+            save_cursor_position();
+            restore_cursor_position();
+            clear_line(mode: LineClearMode);
+            clear_screen(mode: ClearMode);
+            clear_tabs(mode: TabulationClearMode);
+            reset_state();
+            reverse_index();
+            terminal_attribute(attr: Attr);
+            set_mode(mode: Mode);
+            unset_mode(mode: Mode);
+            set_scrolling_region(region: Range<Line>);
+            set_keypad_application_mode();
+            unset_keypad_application_mode();
+            set_active_charset(index: CharsetIndex);
+            configure_charset(index: CharsetIndex, charset: StandardCharset);
+            set_color(index: usize, color: Rgb);
+            reset_color(index: usize);
+            set_clipboard(clipboard: u8, text: &str);
+            set_window_opacity(opacity: Option<f32>);
+            set_working_directory(path: PathBuf);
+            dectest();
+        }
+
+        fn identify_terminal(&mut self, writer: &mut dyn io::Write) {
+            self.calls.push("identify_terminal()".into());
+            let _ = writer.write_all(b"identify");
+        }
+
+        fn device_status(&mut self, writer: &mut dyn io::Write, arg: usize) {
+            self.calls.push(format!("device_status({})", arg));
+            let _ = writer.write_all(b"status");
+        }
+
+        fn dynamic_color_sequence(
+            &mut self,
+            writer: &mut dyn io::Write,
+            code: u8,
+            index: usize,
+            terminator: StringTerminator,
+        ) {
+            self.calls
+                .push(format!("dynamic_color_sequence({}, {}, {:?})", code, index, terminator));
+            let _ = writer.write_all(b"color");
+        }
+
+        fn indexed_color_sequence(
+            &mut self,
+            writer: &mut dyn io::Write,
+            index: usize,
+            terminator: StringTerminator,
+        ) {
+            self.calls.push(format!("indexed_color_sequence({}, {:?})", index, terminator));
+            let _ = writer.write_all(b"indexed");
+        }
+
+        fn scroll_status(&mut self, writer: &mut dyn io::Write, terminator: StringTerminator) {
+            self.calls.push(format!("scroll_status({:?})", terminator));
+            let _ = writer.write_all(b"scroll");
+        }
+    }
+
+    impl TermInfo for Recorder {
+        fn lines(&self) -> Line {
+            Line(24)
+        }
+
+        fn cols(&self) -> Column {
+            Column(80)
+        }
+    }
+
+    /// Call every method of the handler once
+    fn call_all<H: Handler + ?Sized>(handler: &mut H, writer: &mut MockWriter) {
+        handler.set_title("title");
+        handler.set_mouse_cursor(Some(MouseCursorIcon::Text));
+        handler.set_cursor_style(Some(CursorStyle::Beam));
+        handler.input('a');
+        handler.input('\u{1f600}');
+        handler.goto(Line(1), Column(2));
+        handler.goto_line(Line(3));
+        handler.goto_col(Column(4));
+        handler.insert_blank(Column(5));
+        handler.move_up(Line(6));
+        handler.move_down(Line(7));
+        handler.identify_terminal(writer);
+        handler.device_status(writer, 6);
+        handler.move_forward(Column(8));
+        handler.move_backward(Column(9));
+        handler.move_down_and_cr(Line(10));
+        handler.move_up_and_cr(Line(11));
+        handler.put_tab(12);
+        handler.backspace();
+        handler.carriage_return();
+        handler.linefeed();
+        handler.bell();
+        handler.substitute();
+        handler.newline();
+        handler.set_horizontal_tabstop();
+        handler.scroll_up(Line(13));
+        handler.scroll_down(Line(14));
+        handler.insert_blank_lines(Line(15));
+        handler.delete_lines(Line(16));
+        handler.erase_chars(Column(17));
+        handler.delete_chars(Column(18));
+        handler.move_backward_tabs(19);
+        handler.move_forward_tabs(20);
+        handler.save_cursor_position();
+        handler.restore_cursor_position();
+        handler.clear_line(LineClearMode::Left);
+        handler.clear_screen(ClearMode::Saved);
+        handler.clear_tabs(TabulationClearMode::All);
+        handler.reset_state();
+        handler.reverse_index();
+        handler.terminal_attribute(Attr::Foreground(Color::Indexed(21)));
+        handler.set_mode(Mode::BracketedPaste);
+        handler.unset_mode(Mode::Origin);
+        handler.set_scrolling_region(Line(22)..Line(23));
+        handler.set_keypad_application_mode();
+        handler.unset_keypad_application_mode();
+        handler.set_active_charset(CharsetIndex::G2);
+        handler.configure_charset(CharsetIndex::G1, StandardCharset::Ascii);
+        handler.set_color(24, Rgb { r: 25, g: 26, b: 27 });
+        handler.dynamic_color_sequence(writer, 11, 28, StringTerminator::St);
+        handler.indexed_color_sequence(writer, 30, StringTerminator::Bel);
+        handler.scroll_status(writer, StringTerminator::St);
+        handler.reset_color(29);
+        handler.set_clipboard(b'p', "text");
+        handler.set_window_opacity(Some(0.5));
+        handler.set_working_directory(PathBuf::from("/tmp"));
+        handler.dectest();
+    }
+
+    #[test]
+    fn debug_handler_forwards_all_calls() {
+        let mut expected = Recorder::default();
+        let mut expected_writer = MockWriter::new();
+        call_all(&mut expected, &mut expected_writer);
+
+        let mut recorder = Recorder::default();
+        let mut writer = MockWriter::new();
+        call_all(&mut DebugHandler::new(&mut recorder), &mut writer);
+
+        assert_eq!(recorder.calls, expected.calls);
+        assert_eq!(writer, expected_writer);
+        assert_eq!(recorder.calls.len(), 57);
+    }
+
+    /// Handler which can be passed to the parser as a trait object
+    trait Terminal: Handler + TermInfo {}
+
+    impl<T: Handler + TermInfo> Terminal for T {}
+
+    #[test]
+    fn dyn_handler_chain() {
+        let mut expected = Recorder::default();
+        let mut expected_writer = MockWriter::new();
+        call_all(&mut expected, &mut expected_writer);
+
+        let mut recorder = Recorder::default();
+        let mut writer = MockWriter::new();
+        {
+            let inner: &mut dyn Terminal = &mut recorder;
+            let mut handler: Box<dyn Terminal + '_> = Box::new(DebugHandler::new(inner));
+            call_all(&mut *handler, &mut writer);
+        }
+
+        assert_eq!(recorder.calls, expected.calls);
+        assert_eq!(writer, expected_writer);
+    }
+
+    #[test]
+    fn osc_mouse_cursor() {
+        static BYTES: &[u8] =
+            b"\x1b]22;pointer\x07\x1b]22;watch\x1b\\\x1b]22;unknown\x07\x1b]22;default\x07\x1b]22\x07";
+
+        let mut recorder = Recorder::default();
+        Processor::new().advance_bytes(&mut recorder, BYTES, &mut MockWriter::new());
+
+        assert_eq!(
+            recorder.calls,
+            vec![
+                "set_mouse_cursor(Some(Hand))",
+                "set_mouse_cursor(Some(Wait))",
+                "set_mouse_cursor(None)",
+                "set_mouse_cursor(None)",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_through_dyn_handler_chain() {
+        static BYTES: &[u8] = b"\x1b[1ma\x1b]2;title\x07\x1b[6n\x1b[c\x1b]11;?\x07\x1b(0\x0e";
+
+        let mut expected = Recorder::default();
+        let mut expected_writer = MockWriter::new();
+        Processor::new().advance_bytes(&mut expected, BYTES, &mut expected_writer);
+
+        let mut recorder = Recorder::default();
+        let mut writer = MockWriter::new();
+        {
+            let inner: &mut dyn Terminal = &mut recorder;
+            let mut handler: Box<dyn Terminal + '_> = Box::new(DebugHandler::new(inner));
+            Processor::new().advance_bytes(&mut *handler, BYTES, &mut writer);
+        }
+
+        assert_eq!(recorder.calls, expected.calls);
+        assert_eq!(writer, expected_writer);
+        assert_eq!(recorder.calls.len(), 8);
+        assert_eq!(writer.text(), "statusidentifycolor");
+    }
+
+    #[test]
+    fn debug_handler_coalesces_input() {
+        let mut recorder = Recorder::default();
+        let mut parser = Processor::new();
+
+        let mut handler = DebugHandler::new(&mut recorder);
+        parser.advance_bytes(&mut handler, b"ab\x1b[1mc", &mut MockWriter::new());
+        assert_eq!(handler.run, "c");
+        handler.flush();
+        assert!(handler.run.is_empty());
+        drop(handler);
+
+        let expected = ["input('a')", "input('b')", "terminal_attribute(Bold)", "input('c')"];
+        assert_eq!(recorder.calls, expected);
+    }
+
+    #[test]
+    fn unhandled_repetitions_are_summarized() {
+        let signature = Signature::csi(b"", 'y');
+        let start = Instant::now();
+        let mut unhandled = Unhandled::default();
+
+        let mut first = 0;
+        let mut summaries = Vec::new();
+        for i in 0..10_000 {
+            match unhandled.report(signature, start + Duration::from_millis(3 * i)) {
+                Some(UnhandledLog::First) => first += 1,
+                Some(UnhandledLog::Summary(summary)) => summaries.push(summary),
+                None => (),
+            }
+        }
+
+        // Repetitions over 30 seconds are summarized at 10.002 and 20.004 seconds
+        assert_eq!(first, 1);
+        let expected = vec![vec![(signature, 3334)], vec![(signature, 3334)]];
+        assert_eq!(summaries, expected);
+        assert_eq!(unhandled.stats(), UnhandledStats { total: 10_000, kinds: 1 });
+    }
+
+    #[test]
+    fn unhandled_summary_sorted_by_count() {
+        let start = Instant::now();
+        let mut unhandled = Unhandled::default();
+        let (csi, osc) = (Signature::csi(b"?", 'y'), Signature::Osc(Some(1337)));
+
+        assert_eq!(unhandled.report(csi, start), Some(UnhandledLog::First));
+        assert_eq!(unhandled.report(osc, start), Some(UnhandledLog::First));
+        assert_eq!(unhandled.report(osc, start), None);
+        assert_eq!(unhandled.report(csi, start), None);
+
+        let later = start + UNHANDLED_SUMMARY_INTERVAL;
+        let expected = UnhandledLog::Summary(vec![(osc, 2), (csi, 1)]);
+        assert_eq!(unhandled.report(osc, later), Some(expected));
+        assert_eq!(unhandled.report(csi, later), None);
+    }
+
+    #[test]
+    fn unhandled_without_logging_skips_details() {
+        // Tests don't install a logger, so nothing is formatted
+        let mut unhandled = Unhandled::default();
+        for _ in 0..3 {
+            super::report_unhandled(&mut unhandled, Signature::Execute(0x1a), || {
+                panic!("details formatted without logging")
+            });
+        }
+
+        assert_eq!(unhandled.stats(), UnhandledStats { total: 3, kinds: 1 });
+    }
+
+    #[test]
+    fn unhandled_sequences_are_normalized() {
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        let bytes = b"\x1b[1;2y\x1b[3y\x1b[?5y\x1b]1337;a\x07\x1b]1337;b\x07\x1bP1$q\x1b\\";
+        parser.advance_bytes(&mut handler, bytes, &mut MockWriter::new());
+
+        assert_eq!(parser.unhandled_stats(), UnhandledStats { total: 6, kinds: 4 });
+        assert_eq!(Signature::csi(b"?", 'y').to_string(), "CSI ?y");
+        assert_eq!(Signature::osc(&[&b"1337"[..], &b"a"[..]]).to_string(), "OSC 1337");
+        assert_eq!(Signature::dcs(b"$").to_string(), "DCS $");
+    }
+
+    /// Parse a CSI sequence with `count` repetitions of `param` before the given suffix
+    fn csi_calls(prefix: &str, param: &str, count: usize, suffix: &str) -> Vec<String> {
+        let params = vec![param; count].join(";");
+        let bytes = format!("\x1b[{}{};{}", prefix, params, suffix);
+
+        let mut recorder = Recorder::default();
+        Processor::new().advance_bytes(&mut recorder, bytes.as_bytes(), &mut MockWriter::new());
+        recorder.calls
+    }
+
+    #[test]
+    fn csi_sgr_truncated_color_skipped() {
+        // The color spec is cut off after `38;2`, the attributes before it still apply
+        let calls = csi_calls("", "1", 12, "3;38;2;10;20;30m");
+        let mut expected = vec!["terminal_attribute(Bold)".to_string(); 12];
+        expected.push("terminal_attribute(Italic)".into());
+        assert_eq!(calls, expected);
+
+        // Exactly at the cap nothing is lost
+        let calls = csi_calls("", "1", 11, "38;2;10;20;30m");
+        let mut expected = Recorder::default();
+        for _ in 0..11 {
+            expected.terminal_attribute(Attr::Bold);
+        }
+        expected.terminal_attribute(Attr::Foreground(Color::Spec(Rgb { r: 10, g: 20, b: 30 })));
+        assert_eq!(calls, expected.calls);
+    }
+
+    #[test]
+    fn csi_incomplete_color_not_attributes() {
+        let calls = csi_calls("", "1", 1, "38;2;3m");
+        assert_eq!(calls, vec!["terminal_attribute(Bold)".to_string()]);
+    }
+
+    #[test]
+    fn csi_set_mode_beyond_cap() {
+        // Unknown modes are skipped and only the modes which fit are applied
+        let calls = csi_calls("?", "9999", 14, "25;7;1h");
+        assert_eq!(calls, vec!["set_mode(ShowCursor)".to_string()]);
+
+        let calls = csi_calls("?", "9999", 13, "25;7;1l");
+        let mut expected = Recorder::default();
+        expected.unset_mode(Mode::ShowCursor);
+        expected.unset_mode(Mode::LineWrap);
+        expected.unset_mode(Mode::CursorKeys);
+        assert_eq!(calls, expected.calls);
+    }
+
+    #[test]
+    fn csi_cursor_movement_beyond_cap() {
+        let calls = csi_calls("5;10;", "0", 20, "1H");
+        assert_eq!(calls, vec!["goto(Line(4), Column(9))".to_string()]);
+
+        let calls = csi_calls("5;10;", "0", 13, "0H");
+        assert_eq!(calls, vec!["goto(Line(4), Column(9))".to_string()]);
+    }
 }
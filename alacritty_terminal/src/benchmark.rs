@@ -0,0 +1,262 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Throughput of the parser and terminal for recorded and synthetic output
+//!
+//! Workloads are parsed into a terminal with a fixed size, no window or OpenGL context is
+//! required.
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::ansi::{self, Handler, TermInfo};
+use crate::clipboard::Clipboard;
+use crate::config::Config;
+use crate::index::{Column, Line};
+use crate::message_bar::MessageBuffer;
+use crate::recording;
+use crate::term::{SizeInfo, Term};
+
+/// Names of the bundled workloads
+pub const WORKLOADS: &[&str] = &["ascii", "sgr", "scroll", "utf8", "shell"];
+
+/// Number of lines printed by each bundled workload
+const WORKLOAD_LINES: usize = 2_000;
+
+/// Size of the terminal the workloads are parsed into
+const SIZE: SizeInfo = SizeInfo {
+    width: 800.,
+    height: 480.,
+    cell_width: 10.,
+    cell_height: 20.,
+    padding_left: 0.,
+    padding_right: 0.,
+    padding_top: 0.,
+    padding_bottom: 0.,
+    dpr: 1.,
+};
+
+/// Output of a program which is parsed by the benchmark
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workload {
+    pub name: String,
+    pub output: Vec<u8>,
+}
+
+impl Workload {
+    /// Bundled workload with the name `name`, see `WORKLOADS`
+    pub fn bundled(name: &str) -> Option<Workload> {
+        let lines = 0..WORKLOAD_LINES;
+        let output: String = match name {
+            // Plain text filling every line, like `cat` of a log file
+            "ascii" => lines
+                .map(|i| {
+                    let line: String =
+                        (0..79).map(|col| (b'!' + ((i + col) % 94) as u8) as char).collect();
+                    line + "\r\n"
+                })
+                .collect(),
+            // Every word in a different style, like colored `ls` or compiler output
+            "sgr" => lines
+                .map(|i| {
+                    let words: String = (0..15)
+                        .map(|word| match (i + word) % 3 {
+                            0 => format!("\x1b[1;3{}mword\x1b[0m ", word % 8),
+                            1 => format!("\x1b[38;5;{}mword\x1b[0m ", (i * word) % 256),
+                            _ => format!("\x1b[4;38;2;{};{};255mword\x1b[0m ", i % 256, word),
+                        })
+                        .collect();
+                    words + "\r\n"
+                })
+                .collect(),
+            // Output at the bottom of a scrolling region, with scrolling back down in between
+            "scroll" => {
+                let lines = lines.map(|i| {
+                    let reverse_index = if i % 10 == 0 { "\x1b[2H\x1bM" } else { "" };
+                    format!("{}\x1b[23Hscrolling line {:05}\n", reverse_index, i)
+                });
+                Some(String::from("\x1b[2;23r")).into_iter().chain(lines).collect()
+            },
+            // Multi-byte and wide characters
+            "utf8" => lines.map(|_| "Grüße κ\u{3cc}σμε 日本語 ".repeat(4) + "\r\n").collect(),
+            // Mostly short lines of a shell session, which are compacted in the history
+            "shell" => lines
+                .map(|i| match i % 4 {
+                    0 => String::from("\r\n"),
+                    1 => String::from("\x1b[1;32muser@host\x1b[0m:\x1b[1;34m~/src\x1b[0m$ ls\r\n"),
+                    2 => format!("-rw-r--r-- 1 user user {:6} file_{:05}.rs\r\n", i * 37, i),
+                    _ => "-".repeat(79) + "\r\n",
+                })
+                .collect(),
+            _ => return None,
+        };
+
+        Some(Workload { name: name.to_owned(), output: output.into_bytes() })
+    }
+
+    /// Workload replaying a recording, like the ones of the ref tests
+    ///
+    /// Recordings of ref tests are named after their directory. Resizes are not replayed, only
+    /// the output is parsed.
+    pub fn from_recording(path: &Path) -> io::Result<Workload> {
+        let output = recording::output(&recording::parse(&fs::read(path)?)?);
+
+        let name = match path.file_name() {
+            Some(name) if name == "alacritty.recording" => path.parent().and_then(Path::file_name),
+            _ => path.file_stem(),
+        };
+        let name = name.unwrap_or_else(|| path.as_os_str()).to_string_lossy().into_owned();
+
+        Ok(Workload { name, output })
+    }
+
+    /// Number of cells printed to by the workload
+    ///
+    /// Wide characters count as two cells, zero width characters as the cell they are
+    /// combined with.
+    pub fn cells(&self) -> usize {
+        let mut counter = CellCounter::default();
+        let mut parser = ansi::Processor::new();
+        parser.advance_bytes(&mut counter, &self.output, &mut io::sink());
+        counter.cells
+    }
+}
+
+/// Result of benchmarking a workload
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub name: String,
+
+    /// Bytes parsed in all iterations
+    pub bytes: usize,
+
+    /// Cells printed to in all iterations
+    pub cells: usize,
+
+    pub iterations: usize,
+
+    /// Time spent parsing
+    pub duration: Duration,
+}
+
+impl Report {
+    pub fn bytes_per_second(&self) -> f64 {
+        self.bytes as f64 / self.seconds()
+    }
+
+    pub fn cells_per_second(&self) -> f64 {
+        self.cells as f64 / self.seconds()
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.duration.as_secs() as f64 + f64::from(self.duration.subsec_nanos()) / 1e9
+    }
+}
+
+/// Parse the output of a workload `iterations` times into a terminal
+pub fn run(workload: &Workload, iterations: usize) -> Report {
+    let config = Config::default();
+    let mut terminal = Term::new(&config, SIZE, MessageBuffer::new(), Clipboard::new_nop());
+    let mut parser = ansi::Processor::new();
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        parser.advance_bytes(&mut terminal, &workload.output, &mut io::sink());
+    }
+    let duration = start.elapsed();
+
+    Report {
+        name: workload.name.clone(),
+        bytes: workload.output.len() * iterations,
+        cells: workload.cells() * iterations,
+        iterations,
+        duration,
+    }
+}
+
+/// Handler which only counts the cells printed to
+#[derive(Default)]
+struct CellCounter {
+    cells: usize,
+}
+
+impl Handler for CellCounter {
+    fn input(&mut self, c: char) {
+        self.cells += c.width().unwrap_or(1).max(1);
+    }
+}
+
+impl TermInfo for CellCounter {
+    fn lines(&self) -> Line {
+        SIZE.lines()
+    }
+
+    fn cols(&self) -> Column {
+        SIZE.cols()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{run, Workload, WORKLOADS, WORKLOAD_LINES};
+
+    #[test]
+    fn bundled_workloads() {
+        for name in WORKLOADS {
+            let workload = Workload::bundled(name).unwrap();
+            assert_eq!(workload.name, *name);
+            assert!(!workload.output.is_empty());
+        }
+
+        assert_eq!(Workload::bundled("missing"), None);
+    }
+
+    #[test]
+    fn stable_cell_counts() {
+        let cells = |name| Workload::bundled(name).unwrap().cells();
+
+        assert_eq!(cells("ascii"), WORKLOAD_LINES * 79);
+        assert_eq!(cells("sgr"), WORKLOAD_LINES * 15 * 5);
+        assert_eq!(cells("scroll"), WORKLOAD_LINES * 20);
+        assert_eq!(cells("utf8"), WORKLOAD_LINES * 4 * 19);
+        assert_eq!(cells("shell"), WORKLOAD_LINES / 4 * (19 + 43 + 79));
+    }
+
+    #[test]
+    fn report_counts_iterations() {
+        let workload = Workload::bundled("scroll").unwrap();
+
+        let report = run(&workload, 3);
+        assert_eq!(report.name, "scroll");
+        assert_eq!(report.iterations, 3);
+        assert_eq!(report.bytes, workload.output.len() * 3);
+        assert_eq!(report.cells, WORKLOAD_LINES * 20 * 3);
+        assert_eq!(run(&workload, 3).cells, report.cells);
+    }
+
+    #[test]
+    fn ref_test_recording() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ref/ll/alacritty.recording");
+
+        let workload = Workload::from_recording(&path).unwrap();
+        assert_eq!(workload.name, "ll");
+        assert_eq!(workload.cells(), Workload::from_recording(&path).unwrap().cells());
+        assert!(workload.cells() > 0);
+    }
+}
@@ -67,6 +67,31 @@ impl Clipboard {
     pub fn new_nop() -> Self {
         Self { clipboard: Box::new(NopClipboardContext::new().unwrap()), selection: None }
     }
+
+    /// Separate in-memory clipboard and selection buffers
+    #[cfg(test)]
+    pub fn new_memory() -> Self {
+        Self {
+            clipboard: Box::new(MemoryClipboard::default()),
+            selection: Some(Box::new(MemoryClipboard::default())),
+        }
+    }
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct MemoryClipboard(String);
+
+#[cfg(test)]
+impl ClipboardProvider for MemoryClipboard {
+    fn get_contents(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(self.0.clone())
+    }
+
+    fn set_contents(&mut self, contents: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.0 = contents;
+        Ok(())
+    }
 }
 
 impl Default for Clipboard {
@@ -75,7 +100,11 @@ impl Default for Clipboard {
     }
 }
 
-#[derive(Debug)]
+/// Buffer used for storing and loading text
+///
+/// The selection buffer is X11's PRIMARY selection. Platforms without one store nothing there
+/// and load from the clipboard instead.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ClipboardType {
     Clipboard,
     Selection,
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
 
@@ -20,7 +21,7 @@ use serde::de::Error as SerdeError;
 use serde::de::{self, MapAccess, Unexpected, Visitor};
 use serde::{Deserialize, Deserializer};
 
-use crate::input::{Action, Binding, KeyBinding, MouseBinding};
+use crate::input::{Action, Binding, KeyBinding, KeyChord, MouseBinding};
 use crate::term::TermMode;
 
 macro_rules! bindings {
@@ -53,6 +54,7 @@ macro_rules! bindings {
                 mode: _mode,
                 notmode: _notmode,
                 action: $action,
+                prefix: Vec::new(),
             });
         )*
 
@@ -72,8 +74,9 @@ pub fn default_key_bindings() -> Vec<KeyBinding> {
         KeyBinding;
         Key::Paste; Action::Paste;
         Key::Copy; Action::Copy;
-        Key::L, [ctrl: true]; Action::ClearLogNotice;
+        Key::L, [ctrl: true]; Action::ClearNotice;
         Key::L, [ctrl: true]; Action::Esc("\x0c".into());
+        Key::D, [ctrl: true, shift: true]; Action::ClearNotice;
         Key::Home, [alt: true]; Action::Esc("\x1b[1;3H".into());
         Key::Home, +TermMode::APP_CURSOR; Action::Esc("\x1bOH".into());
         Key::Home, ~TermMode::APP_CURSOR; Action::Esc("\x1b[H".into());
@@ -175,7 +178,7 @@ pub fn default_key_bindings() -> Vec<KeyBinding> {
         Key::F10, [logo: true]; Action::Esc("\x1b[21;3~".into());
         Key::F11, [logo: true]; Action::Esc("\x1b[23;3~".into());
         Key::F12, [logo: true]; Action::Esc("\x1b[24;3~".into());
-        Key::NumpadEnter; Action::Esc("\n".into());
+        Key::NumpadEnter, ~TermMode::APP_KEYPAD; Action::Esc("\n".into());
     );
 
     bindings.extend(platform_key_bindings());
@@ -590,7 +593,10 @@ impl<'a> Deserialize<'a> for ModeWrapper {
             type Value = ModeWrapper;
 
             fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.write_str("Combination of AppCursor | AppKeypad, possibly with negation (~)")
+                f.write_str(
+                    "Combination of AppCursor | AppKeypad | Alt | Vi | Search | Hint, possibly \
+                     with negation (~)",
+                )
             }
 
             fn visit_str<E>(self, value: &str) -> ::std::result::Result<ModeWrapper, E>
@@ -607,7 +613,13 @@ impl<'a> Deserialize<'a> for ModeWrapper {
                         "~appkeypad" => res.not_mode |= TermMode::APP_KEYPAD,
                         "~alt" => res.not_mode |= TermMode::ALT_SCREEN,
                         "alt" => res.mode |= TermMode::ALT_SCREEN,
-                        _ => error!("Unknown mode {:?}", modifier),
+                        "vi" => res.mode |= TermMode::VI,
+                        "~vi" => res.not_mode |= TermMode::VI,
+                        "search" => res.mode |= TermMode::SEARCH,
+                        "~search" => res.not_mode |= TermMode::SEARCH,
+                        "hint" => res.mode |= TermMode::HINT,
+                        "~hint" => res.not_mode |= TermMode::HINT,
+                        _ => config_error!("Unknown mode {:?}", modifier),
                     }
                 }
 
@@ -657,9 +669,19 @@ impl<'a> Deserialize<'a> for MouseButtonWrapper {
                     },
                 }
             }
+
+            fn visit_u64<E>(self, value: u64) -> ::std::result::Result<MouseButtonWrapper, E>
+            where
+                E: de::Error,
+            {
+                match u8::try_from(value) {
+                    Ok(index) => Ok(MouseButtonWrapper(MouseButton::Other(index))),
+                    Err(_) => Err(E::invalid_value(Unexpected::Unsigned(value), &self)),
+                }
+            }
         }
 
-        deserializer.deserialize_str(MouseButtonVisitor)
+        deserializer.deserialize_any(MouseButtonVisitor)
     }
 }
 
@@ -668,6 +690,7 @@ impl<'a> Deserialize<'a> for MouseButtonWrapper {
 #[derive(PartialEq, Eq)]
 struct RawBinding {
     key: Option<Key>,
+    prefix: Vec<KeyChord>,
     mouse: Option<MouseButton>,
     mods: ModifiersState,
     mode: TermMode,
@@ -684,6 +707,7 @@ impl RawBinding {
                 action: self.action,
                 mode: self.mode,
                 notmode: self.notmode,
+                prefix: Vec::new(),
             })
         } else {
             Err(self)
@@ -698,6 +722,7 @@ impl RawBinding {
                 action: self.action,
                 mode: self.mode,
                 notmode: self.notmode,
+                prefix: self.prefix,
             })
         } else {
             Err(self)
@@ -712,6 +737,7 @@ impl<'a> Deserialize<'a> for RawBinding {
     {
         enum Field {
             Key,
+            Keys,
             Mods,
             Mode,
             Action,
@@ -728,7 +754,7 @@ impl<'a> Deserialize<'a> for RawBinding {
                 struct FieldVisitor;
 
                 static FIELDS: &[&str] =
-                    &["key", "mods", "mode", "action", "chars", "mouse", "command"];
+                    &["key", "keys", "mods", "mode", "action", "chars", "mouse", "command"];
 
                 impl<'a> Visitor<'a> for FieldVisitor {
                     type Value = Field;
@@ -743,6 +769,7 @@ impl<'a> Deserialize<'a> for RawBinding {
                     {
                         match value {
                             "key" => Ok(Field::Key),
+                            "keys" => Ok(Field::Keys),
                             "mods" => Ok(Field::Mods),
                             "mode" => Ok(Field::Mode),
                             "action" => Ok(Field::Action),
@@ -772,6 +799,7 @@ impl<'a> Deserialize<'a> for RawBinding {
             {
                 let mut mods: Option<ModifiersState> = None;
                 let mut key: Option<Key> = None;
+                let mut keys: Option<Vec<KeyChord>> = None;
                 let mut chars: Option<String> = None;
                 let mut action: Option<crate::input::Action> = None;
                 let mut mode: Option<TermMode> = None;
@@ -789,19 +817,15 @@ impl<'a> Deserialize<'a> for RawBinding {
                             }
 
                             let val = map.next_value::<serde_yaml::Value>()?;
-                            if val.is_u64() {
-                                let scancode = val.as_u64().unwrap();
-                                if scancode > u64::from(::std::u32::MAX) {
-                                    return Err(<V::Error as Error>::custom(format!(
-                                        "Invalid key binding, scancode too big: {}",
-                                        scancode
-                                    )));
-                                }
-                                key = Some(Key::Scancode(scancode as u32));
-                            } else {
-                                let k = Key::deserialize(val).map_err(V::Error::custom)?;
-                                key = Some(k);
+                            key = Some(key_from_value(val)?);
+                        },
+                        Field::Keys => {
+                            if keys.is_some() {
+                                return Err(<V::Error as Error>::duplicate_field("keys"));
                             }
+
+                            let chords = map.next_value::<Vec<KeyChordWrapper>>()?;
+                            keys = Some(chords.into_iter().map(|chord| chord.0).collect());
                         },
                         Field::Mods => {
                             if mods.is_some() {
@@ -869,22 +893,73 @@ impl<'a> Deserialize<'a> for RawBinding {
 
                 let mode = mode.unwrap_or_else(TermMode::empty);
                 let not_mode = not_mode.unwrap_or_else(TermMode::empty);
-                let mods = mods.unwrap_or_else(ModifiersState::default);
+                let mut mods = mods.unwrap_or_else(ModifiersState::default);
+
+                // The last chord of a sequence is the binding's trigger
+                let mut prefix = Vec::new();
+                if let Some(mut keys) = keys {
+                    if key.is_some() || mouse.is_some() || mods != ModifiersState::default() {
+                        return Err(V::Error::custom(
+                            "keys can't be combined with key, mods or mouse",
+                        ));
+                    }
+
+                    let last = keys.pop().ok_or_else(|| V::Error::custom("keys is empty"))?;
+                    key = Some(last.key);
+                    mods = last.mods;
+                    prefix = keys;
+                }
 
                 if mouse.is_none() && key.is_none() {
                     return Err(V::Error::custom("bindings require mouse button or key"));
                 }
 
-                Ok(RawBinding { mode, notmode: not_mode, action, key, mouse, mods })
+                Ok(RawBinding { mode, notmode: not_mode, action, key, prefix, mouse, mods })
             }
         }
 
-        const FIELDS: &[&str] = &["key", "mods", "mode", "action", "chars", "mouse", "command"];
+        const FIELDS: &[&str] =
+            &["key", "keys", "mods", "mode", "action", "chars", "mouse", "command"];
 
         deserializer.deserialize_struct("RawBinding", FIELDS, RawBindingVisitor)
     }
 }
 
+/// Parse a binding's key, which is either a key name or a scancode
+fn key_from_value<E: de::Error>(val: serde_yaml::Value) -> ::std::result::Result<Key, E> {
+    if val.is_u64() {
+        let scancode = val.as_u64().unwrap();
+        if scancode > u64::from(::std::u32::MAX) {
+            return Err(E::custom(format!("Invalid key binding, scancode too big: {}", scancode)));
+        }
+        Ok(Key::Scancode(scancode as u32))
+    } else {
+        Key::deserialize(val).map_err(E::custom)
+    }
+}
+
+/// One entry of a binding's `keys` sequence
+struct KeyChordWrapper(KeyChord);
+
+impl<'a> Deserialize<'a> for KeyChordWrapper {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct RawKeyChord {
+            key: serde_yaml::Value,
+            #[serde(default)]
+            mods: ModsWrapper,
+        }
+
+        let raw = RawKeyChord::deserialize(deserializer)?;
+        let key = key_from_value(raw.key)?;
+        Ok(KeyChordWrapper(KeyChord { key, mods: raw.mods.into_inner() }))
+    }
+}
+
 impl<'a> Deserialize<'a> for MouseBinding {
     fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
     where
@@ -971,7 +1046,7 @@ impl<'a> de::Deserialize<'a> for ModsWrapper {
                         "alt" | "option" => res.alt = true,
                         "control" => res.ctrl = true,
                         "none" => (),
-                        _ => error!("Unknown modifier {:?}", modifier),
+                        _ => config_error!("Unknown modifier {:?}", modifier),
                     }
                 }
 
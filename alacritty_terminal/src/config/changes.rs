@@ -0,0 +1,177 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Differences between two configurations
+use bitflags::bitflags;
+
+use crate::config::Config;
+
+bitflags! {
+    /// Parts of Alacritty which have to be updated after the config has been reloaded
+    ///
+    /// Options which are only read at startup, like the shell or the window class, are not
+    /// tracked since changing them has no effect on running windows.
+    pub struct ConfigChanges: u8 {
+        /// Colors of the terminal, requires a redraw
+        const COLORS   = 0b0000_0001;
+
+        /// Fonts, requires rebuilding the glyph cache and resizing the grid
+        const FONT     = 0b0000_0010;
+
+        /// Window padding, requires resizing the grid
+        const PADDING  = 0b0000_0100;
+
+        /// Key and mouse bindings
+        const BINDINGS = 0b0000_1000;
+
+        /// Terminal behavior like scrollback history and the visual bell
+        const TERMINAL = 0b0001_0000;
+
+        /// Input options other than the bindings
+        const INPUT    = 0b0010_0000;
+
+        /// Options which only change how frames are drawn
+        const DISPLAY  = 0b0100_0000;
+    }
+}
+
+impl ConfigChanges {
+    /// Find all parts which have to be updated when switching from `old` to `new`
+    pub fn between(old: &Config, new: &Config) -> ConfigChanges {
+        let mut changes = ConfigChanges::empty();
+
+        changes.set(ConfigChanges::COLORS, old.colors != new.colors || old.schemes != new.schemes);
+
+        changes.set(
+            ConfigChanges::PADDING,
+            old.window.padding != new.window.padding
+                || old.window.dynamic_padding != new.window.dynamic_padding,
+        );
+
+        changes.set(
+            ConfigChanges::TERMINAL,
+            old.selection != new.selection
+                || old.visual_bell != new.visual_bell
+                || old.cursor.style != new.cursor.style
+                || old.dynamic_title != new.dynamic_title
+                || old.window.opacity_escape != new.window.opacity_escape
+                || old.scrolling != new.scrolling
+                || old.terminal != new.terminal,
+        );
+
+        changes.set(
+            ConfigChanges::INPUT,
+            old.scrolling != new.scrolling
+                || old.selection != new.selection
+                || old.alt_send_esc != new.alt_send_esc
+                || old.window.option_as_alt != new.window.option_as_alt,
+        );
+
+        changes.set(
+            ConfigChanges::DISPLAY,
+            old.debug != new.debug
+                || old.cursor != new.cursor
+                || old.visual_bell != new.visual_bell
+                || old.draw_bold_text_with_bright_colors != new.draw_bold_text_with_bright_colors
+                || old.background_opacity != new.background_opacity
+                || old.window.opacity() != new.window.opacity()
+                || old.window.max_fps != new.window.max_fps
+                || old.window.rulers != new.window.rulers
+                || old.window.bell_badge != new.window.bell_badge,
+        );
+
+        // Fonts, bindings and the mouse only exist for the window
+        #[cfg(feature = "gui")]
+        {
+            changes.set(ConfigChanges::FONT, old.font != new.font);
+
+            changes.set(
+                ConfigChanges::BINDINGS,
+                old.key_bindings != new.key_bindings
+                    || old.mouse_bindings != new.mouse_bindings
+                    || old.key_chords != new.key_chords,
+            );
+
+            if old.mouse != new.mouse {
+                changes.insert(ConfigChanges::INPUT);
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigChanges;
+    use crate::config::Config;
+
+    fn changes(yaml: &str) -> ConfigChanges {
+        let new: Config = serde_yaml::from_str(yaml).unwrap();
+        ConfigChanges::between(&Config::default(), &new)
+    }
+
+    #[test]
+    fn unchanged_config() {
+        assert_eq!(changes("key: val\n"), ConfigChanges::empty());
+    }
+
+    #[test]
+    fn startup_options_are_ignored() {
+        assert_eq!(changes("shell:\n  program: /bin/zsh\n"), ConfigChanges::empty());
+        assert_eq!(changes("window:\n  class:\n    instance: Foo\n"), ConfigChanges::empty());
+    }
+
+    #[test]
+    fn color_change() {
+        let yaml = "colors:\n  primary:\n    background: '0x123456'\n";
+        assert_eq!(changes(yaml), ConfigChanges::COLORS);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn font_change() {
+        assert_eq!(changes("font:\n  size: 42.0\n"), ConfigChanges::FONT);
+    }
+
+    #[test]
+    fn padding_change() {
+        assert_eq!(changes("window:\n  padding:\n    x: 10\n"), ConfigChanges::PADDING);
+        assert_eq!(changes("window:\n  dynamic_padding: true\n"), ConfigChanges::PADDING);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn binding_change() {
+        let yaml = "key_bindings:\n  - { key: F1, action: Copy }\n";
+        assert_eq!(changes(yaml), ConfigChanges::BINDINGS);
+    }
+
+    #[test]
+    fn scrolling_change() {
+        let expected = ConfigChanges::TERMINAL | ConfigChanges::INPUT;
+        assert_eq!(changes("scrolling:\n  history: 42\n"), expected);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn mouse_change() {
+        assert_eq!(changes("mouse:\n  hide_when_typing: true\n"), ConfigChanges::INPUT);
+    }
+
+    #[test]
+    fn render_option_change() {
+        assert_eq!(changes("window:\n  max_fps: 30\n"), ConfigChanges::DISPLAY);
+    }
+}
@@ -1,10 +1,11 @@
 use serde::{Deserialize, Deserializer};
+use serde_yaml::{Mapping, Value};
 
-use crate::config::failure_default;
+use crate::config::{failure_default, unknown};
 use crate::term::color::Rgb;
 
 #[serde(default)]
-#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[derive(Deserialize, Debug, Default, PartialEq)]
 pub struct Colors {
     #[serde(deserialize_with = "failure_default")]
     pub primary: PrimaryColors,
@@ -20,6 +21,14 @@ pub struct Colors {
     pub dim: Option<AnsiColors>,
     #[serde(deserialize_with = "failure_default")]
     pub indexed_colors: Vec<IndexedColor>,
+
+    /// Brightness of dim text without a color in the dim palette
+    #[serde(deserialize_with = "failure_default")]
+    dim_factor: DimFactor,
+
+    /// Name of the color scheme used instead of these colors
+    #[serde(deserialize_with = "failure_default")]
+    pub scheme: Option<String>,
 }
 
 impl Colors {
@@ -30,6 +39,88 @@ impl Colors {
     pub fn bright(&self) -> &AnsiColors {
         &self.bright.0
     }
+
+    /// Factor the brightness of dim text is scaled by
+    #[inline]
+    pub fn dim_factor(&self) -> f32 {
+        self.dim_factor.0
+    }
+
+    /// Cursor foreground color
+    #[inline]
+    pub fn cursor_text_color(&self) -> Option<Rgb> {
+        self.cursor.text
+    }
+}
+
+/// Named set of colors which can be selected at runtime
+#[derive(Debug, PartialEq)]
+pub struct ColorScheme {
+    pub name: String,
+    pub colors: Colors,
+}
+
+/// Deserialize the color schemes, keeping the order of the config file
+pub fn deserialize_color_schemes<'a, D>(
+    deserializer: D,
+) -> ::std::result::Result<Vec<ColorScheme>, D::Error>
+where
+    D: Deserializer<'a>,
+{
+    let mapping = match Mapping::deserialize(deserializer) {
+        Ok(mapping) => mapping,
+        Err(err) => {
+            config_error!("Problem with config: {}; ignoring setting", err);
+            return Ok(Vec::new());
+        },
+    };
+
+    let mut schemes = Vec::new();
+    for (name, colors) in mapping {
+        let name = match name {
+            Value::String(name) => name,
+            name => {
+                config_error!(
+                    "Problem with config: invalid color scheme name {:?}; ignoring scheme",
+                    name
+                );
+                continue;
+            },
+        };
+
+        match unknown::deserialize_field::<Colors>(&name, colors) {
+            Ok(colors) => schemes.push(ColorScheme { name, colors }),
+            Err(err) => {
+                config_error!(
+                    "Problem with config: color scheme {}: {}; ignoring scheme",
+                    name,
+                    err
+                )
+            },
+        }
+    }
+
+    Ok(schemes)
+}
+
+/// Wrapper around f32 that represents a brightness factor between 0.0 and 1.0
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct DimFactor(f32);
+
+impl Default for DimFactor {
+    fn default() -> Self {
+        DimFactor(0.66)
+    }
+}
+
+impl<'a> Deserialize<'a> for DimFactor {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let factor = f32::deserialize(deserializer)?;
+        Ok(DimFactor(factor.max(0.0).min(1.0)))
+    }
 }
 
 #[serde(default)]
@@ -49,7 +140,7 @@ where
     match u8::deserialize(value) {
         Ok(index) => {
             if index < 16 {
-                error!(
+                config_error!(
                     "Problem with config: indexed_color's index is {}, but a value bigger than 15 \
                      was expected; ignoring setting",
                     index
@@ -62,7 +153,7 @@ where
             }
         },
         Err(err) => {
-            error!("Problem with config: {}; ignoring setting", err);
+            config_error!("Problem with config: {}; ignoring setting", err);
 
             // Return value out of range to ignore this color
             Ok(0)
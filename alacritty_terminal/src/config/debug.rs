@@ -1,7 +1,9 @@
+use std::time::Duration;
+
 use log::LevelFilter;
 use serde::Deserializer;
 
-use crate::config::failure_default;
+use crate::config::{failure_default, option_explicit_none, DefaultTrueBool};
 
 /// Debugging options
 #[serde(default)]
@@ -21,9 +23,33 @@ pub struct Debug {
     #[serde(deserialize_with = "failure_default")]
     pub render_timer: bool,
 
+    /// Corner of the window the render timer is drawn in
+    #[serde(deserialize_with = "failure_default")]
+    pub render_timer_position: RenderTimerPosition,
+
+    /// Interval in seconds for logging render timing statistics
+    #[serde(deserialize_with = "failure_default")]
+    draw_times: u16,
+
     /// Record ref test
     #[serde(deserialize_with = "failure_default")]
     pub ref_test: bool,
+
+    /// Synchronize buffer swaps with the display refresh rate
+    #[serde(deserialize_with = "failure_default")]
+    vsync: DefaultTrueBool,
+
+    /// Force a specific OpenGL API instead of picking the best one available
+    #[serde(deserialize_with = "option_explicit_none")]
+    pub renderer: Option<RendererApi>,
+
+    /// Don't warn about OpenGL drivers which render on the CPU
+    #[serde(deserialize_with = "failure_default")]
+    pub allow_software_gl: bool,
+
+    /// Rasterize missing glyphs during the draw instead of on a background thread
+    #[serde(deserialize_with = "failure_default")]
+    pub synchronous_glyph_rasterization: bool,
 }
 
 impl Default for Debug {
@@ -33,9 +59,63 @@ impl Default for Debug {
             print_events: Default::default(),
             persistent_logging: Default::default(),
             render_timer: Default::default(),
+            render_timer_position: Default::default(),
+            draw_times: Default::default(),
             ref_test: Default::default(),
+            vsync: Default::default(),
+            renderer: Default::default(),
+            allow_software_gl: Default::default(),
+            synchronous_glyph_rasterization: Default::default(),
+        }
+    }
+}
+
+impl Debug {
+    /// Interval for logging render timing statistics
+    #[inline]
+    pub fn draw_times(&self) -> Option<Duration> {
+        match self.draw_times {
+            0 => None,
+            secs => Some(Duration::from_secs(u64::from(secs))),
         }
     }
+
+    /// Synchronize buffer swaps with the display refresh rate
+    #[inline]
+    pub fn vsync(&self) -> bool {
+        self.vsync.0
+    }
+}
+
+/// OpenGL API and shading language used by the renderer
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RendererApi {
+    /// Desktop OpenGL 3.3 with dual-source blending for subpixel text
+    Glsl3,
+
+    /// OpenGL ES 3.0 with plain alpha blending for text
+    Gles3,
+}
+
+impl Default for RendererApi {
+    fn default() -> Self {
+        RendererApi::Glsl3
+    }
+}
+
+/// Window corner of the render timer overlay
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderTimerPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Default for RenderTimerPosition {
+    fn default() -> Self {
+        RenderTimerPosition::BottomLeft
+    }
 }
 
 fn default_log_level() -> LevelFilter {
@@ -54,7 +134,7 @@ where
         "debug" => LevelFilter::Debug,
         "trace" => LevelFilter::Trace,
         level => {
-            error!("Problem with config: invalid log level {}; using level Warn", level);
+            config_error!("Problem with config: invalid log level {}; using level Warn", level);
             default_log_level()
         },
     })
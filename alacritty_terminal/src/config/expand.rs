@@ -0,0 +1,209 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Expansion of environment variables in config values
+use std::env;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::config::{failure_default, option_explicit_none};
+
+/// String value with `$VAR` and `${VAR}` replaced by the environment variable's content
+///
+/// Variables are expanded when the config is deserialized, so every reload picks up the
+/// environment at that time. `$$` is used for a literal `$`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Expanded<T>(pub T);
+
+impl<'de, T: From<String>> Deserialize<'de> for Expanded<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        Ok(Expanded(T::from(expand_env(&value))))
+    }
+}
+
+/// Deserialize a value with environment variables expanded
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: From<String>,
+{
+    Expanded::deserialize(deserializer).map(|Expanded(value)| value)
+}
+
+/// Like `failure_default`, with environment variables expanded
+pub fn failure_default_expanded<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: From<String>,
+{
+    let value: Option<Expanded<T>> = failure_default(deserializer)?;
+    Ok(value.map(|Expanded(value)| value))
+}
+
+/// Like `option_explicit_none`, with environment variables expanded
+pub fn option_explicit_none_expanded<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: From<String> + Default,
+{
+    let value: Option<Expanded<T>> = option_explicit_none(deserializer)?;
+    Ok(value.map(|Expanded(value)| value))
+}
+
+/// Replace all environment variables in `input` with their current values
+pub fn expand_env(input: &str) -> String {
+    expand(input, |name| env::var(name).ok())
+}
+
+/// Replace all environment variables in `input` using `lookup`
+///
+/// Variables which are not set and `$` signs that don't start a variable are kept as they are.
+fn expand<F>(input: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(index) = rest.find('$') {
+        output.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+
+        // Escaped dollar sign
+        if rest.starts_with('$') {
+            output.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let (name, len) = if rest.starts_with('{') {
+            match rest.find('}') {
+                Some(end) if is_name(&rest[1..end]) => (&rest[1..end], end + 1),
+                _ => ("", 0),
+            }
+        } else {
+            let end = rest.find(|c: char| !is_name_char(c)).unwrap_or_else(|| rest.len());
+            if rest.starts_with(|c: char| c.is_ascii_digit()) {
+                ("", 0)
+            } else {
+                (&rest[..end], end)
+            }
+        };
+
+        if name.is_empty() {
+            output.push('$');
+            continue;
+        }
+
+        match lookup(name) {
+            Some(value) => output.push_str(&value),
+            None => {
+                warn!("Environment variable {} in config is not set", name);
+                output.push('$');
+                output.push_str(&rest[..len]);
+            },
+        }
+
+        rest = &rest[len..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[inline]
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[inline]
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(is_name_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+
+    use super::expand;
+    use crate::config::Config;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some(String::from("/home/user")),
+            "XDG_DATA_HOME" => Some(String::from("/home/user/.local/share")),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expand_plain_and_braced() {
+        assert_eq!(expand("$HOME/bin", lookup), "/home/user/bin");
+        assert_eq!(
+            expand("${XDG_DATA_HOME}/alacritty", lookup),
+            "/home/user/.local/share/alacritty"
+        );
+        assert_eq!(expand("${HOME}_dir", lookup), "/home/user_dir");
+        assert_eq!(expand("$HOME_dir", lookup), "$HOME_dir");
+        assert_eq!(expand("a${EMPTY}b", lookup), "ab");
+        assert_eq!(expand("no variables", lookup), "no variables");
+    }
+
+    #[test]
+    fn expand_nested_braces() {
+        assert_eq!(expand("${FOO${HOME}}", lookup), "${FOO/home/user}");
+        assert_eq!(expand("{${HOME}}", lookup), "{/home/user}");
+        assert_eq!(expand("${HOME", lookup), "${HOME");
+        assert_eq!(expand("${}", lookup), "${}");
+    }
+
+    #[test]
+    fn expand_unset_variables() {
+        assert_eq!(expand("$UNSET/bin", lookup), "$UNSET/bin");
+        assert_eq!(expand("${UNSET}/bin", lookup), "${UNSET}/bin");
+        assert_eq!(expand("$UNSET$HOME", lookup), "$UNSET/home/user");
+    }
+
+    #[test]
+    fn expand_escaped_dollar() {
+        assert_eq!(expand("$$HOME", lookup), "$HOME");
+        assert_eq!(expand("$$$HOME", lookup), "$/home/user");
+        assert_eq!(expand("cost: 5$", lookup), "cost: 5$");
+        assert_eq!(expand("$1 $-", lookup), "$1 $-");
+    }
+
+    #[test]
+    fn expand_working_directory() {
+        env::set_var("ALACRITTY_EXPAND_TEST_DIR", "/tmp/alacritty");
+
+        let yaml = "working_directory: ${ALACRITTY_EXPAND_TEST_DIR}/work\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.working_directory(), &Some(PathBuf::from("/tmp/alacritty/work")));
+
+        let yaml = "shell:\n  program: $ALACRITTY_EXPAND_TEST_DIR/sh\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.shell.unwrap().program, "/tmp/alacritty/sh");
+
+        let config: Config = serde_yaml::from_str("working_directory: None\n").unwrap();
+        assert_eq!(config.working_directory(), &None);
+    }
+}
@@ -1,12 +1,13 @@
 use std::fmt;
 
 use font::Size;
+use serde::de::Error as SerdeError;
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer};
 
-#[cfg(target_os = "macos")]
 use crate::config::DefaultTrueBool;
 use crate::config::{failure_default, Delta};
+use crate::input::FONT_SIZE_STEP;
 
 /// Font config
 ///
@@ -29,10 +30,18 @@ pub struct Font {
     #[serde(deserialize_with = "failure_default")]
     bold: SecondaryFontDescription,
 
+    /// Families which can be switched to with the `CycleFont` action
+    #[serde(deserialize_with = "failure_default")]
+    families: Vec<String>,
+
     /// Font size in points
     #[serde(deserialize_with = "DeserializeSize::deserialize")]
     pub size: Size,
 
+    /// Change of the font size with the font size actions
+    #[serde(deserialize_with = "failure_default")]
+    size_step: FontSizeStep,
+
     /// Extra spacing per character
     #[serde(deserialize_with = "failure_default")]
     pub offset: Delta<i8>,
@@ -41,22 +50,28 @@ pub struct Font {
     #[serde(deserialize_with = "failure_default")]
     pub glyph_offset: Delta<i8>,
 
-    #[cfg(target_os = "macos")]
+    /// Only used on macOS, but accepted everywhere to allow sharing config files
     #[serde(deserialize_with = "failure_default")]
     use_thin_strokes: DefaultTrueBool,
+
+    /// Blend glyphs in linear color space
+    #[serde(deserialize_with = "failure_default")]
+    pub gamma_correct_blending: bool,
 }
 
 impl Default for Font {
     fn default() -> Font {
         Font {
             size: default_font_size(),
+            size_step: Default::default(),
             normal: Default::default(),
             bold: Default::default(),
             italic: Default::default(),
+            families: Default::default(),
             glyph_offset: Default::default(),
             offset: Default::default(),
-            #[cfg(target_os = "macos")]
             use_thin_strokes: Default::default(),
+            gamma_correct_blending: Default::default(),
         }
     }
 }
@@ -67,6 +82,14 @@ impl Font {
         Font { size, ..self }
     }
 
+    /// Get a font clone which uses `family` for all font faces
+    pub fn with_family(self, family: &str) -> Font {
+        let normal = FontDescription { family: family.to_owned(), ..self.normal };
+        let italic = SecondaryFontDescription { family: None, ..self.italic };
+        let bold = SecondaryFontDescription { family: None, ..self.bold };
+        Font { normal, italic, bold, ..self }
+    }
+
     // Get normal font description
     pub fn normal(&self) -> &FontDescription {
         &self.normal
@@ -82,14 +105,18 @@ impl Font {
         self.bold.desc(&self.normal)
     }
 
-    #[cfg(target_os = "macos")]
     pub fn use_thin_strokes(&self) -> bool {
-        self.use_thin_strokes.0
+        cfg!(target_os = "macos") && self.use_thin_strokes.0
     }
 
-    #[cfg(not(target_os = "macos"))]
-    pub fn use_thin_strokes(&self) -> bool {
-        false
+    /// Families which can be switched to at runtime
+    pub fn families(&self) -> &[String] {
+        &self.families
+    }
+
+    /// Change of the font size with the font size actions
+    pub fn size_step(&self) -> Size {
+        self.size_step.0
     }
 }
 
@@ -97,6 +124,34 @@ fn default_font_size() -> Size {
     Size::new(11.)
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct FontSizeStep(Size);
+
+impl Default for FontSizeStep {
+    fn default() -> Self {
+        FontSizeStep(Size::new(FONT_SIZE_STEP))
+    }
+}
+
+impl<'de> Deserialize<'de> for FontSizeStep {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let step = f32::deserialize(deserializer)?;
+
+        // Sizes are stored in steps of half a point
+        if Size::new(step) <= Size::new(0.) {
+            return Err(D::Error::custom(format!(
+                "font size step {} is smaller than {}",
+                step, FONT_SIZE_STEP
+            )));
+        }
+
+        Ok(FontSizeStep(Size::new(step)))
+    }
+}
+
 /// Description of the normal font
 #[serde(default)]
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
@@ -192,7 +247,7 @@ impl DeserializeSize for Size {
             Ok(size) => Ok(size),
             Err(err) => {
                 let size = default_font_size();
-                error!("Problem with config: {}; using size {}", err, size.as_f32_pts());
+                config_error!("Problem with config: {}; using size {}", err, size.as_f32_pts());
                 Ok(size)
             },
         }
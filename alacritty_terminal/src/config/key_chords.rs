@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use crate::config::{failure_default, DefaultTrueBool};
+
+/// Behavior of key bindings which are triggered by a sequence of key chords
+#[serde(default)]
+#[derive(Deserialize, Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct KeyChords {
+    /// Time in milliseconds to wait for the next chord of a binding
+    #[serde(deserialize_with = "failure_default")]
+    timeout: KeyChordTimeout,
+
+    /// Show the pressed chords in the message bar while waiting for the next one
+    #[serde(deserialize_with = "failure_default")]
+    pub indicator: bool,
+
+    /// Send the keys of an aborted chord to the shell instead of discarding them
+    #[serde(deserialize_with = "failure_default")]
+    forward_aborted: DefaultTrueBool,
+}
+
+impl KeyChords {
+    pub fn timeout(self) -> Duration {
+        Duration::from_millis(self.timeout.0)
+    }
+
+    pub fn forward_aborted(self) -> bool {
+        self.forward_aborted.0
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+struct KeyChordTimeout(u64);
+
+impl Default for KeyChordTimeout {
+    fn default() -> Self {
+        KeyChordTimeout(1000)
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Migration of options which have been renamed or removed
+//!
+//! Configs are migrated before they are deserialized, so options of older releases keep working
+//! instead of silently falling back to their defaults.
+use std::fmt;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::config::platform::{self, PLATFORM_KEY};
+
+/// Change of a single option of the config format
+enum Migration {
+    /// Option moved to a new path, keeping its value
+    Rename(&'static str, &'static str),
+
+    /// Option moved to a new path with a converted value, `None` drops the value
+    Convert(&'static str, &'static str, fn(Value) -> Option<Value>),
+
+    /// Option removed without replacement, with a hint for its users
+    Remove(&'static str, &'static str),
+}
+
+impl Migration {
+    /// Path of the option before the change
+    fn from(&self) -> &'static str {
+        match *self {
+            Migration::Rename(from, _)
+            | Migration::Convert(from, ..)
+            | Migration::Remove(from, _) => from,
+        }
+    }
+}
+
+/// All changes of the config format, oldest first
+const MIGRATIONS: &[Migration] = &[
+    Migration::Rename("dimensions", "window.dimensions"),
+    Migration::Rename("padding", "window.padding"),
+    Migration::Rename("cursor_style", "cursor.style"),
+    Migration::Rename("unfocused_hollow_cursor", "cursor.unfocused_hollow"),
+    Migration::Rename("hide_cursor_when_typing", "mouse.hide_when_typing"),
+    Migration::Rename("faux_scrolling_lines", "scrolling.faux_multiplier"),
+    Migration::Rename("mouse.faux_scrollback_lines", "scrolling.faux_multiplier"),
+    Migration::Rename("render_timer", "debug.render_timer"),
+    Migration::Rename("persistent_logging", "debug.persistent_logging"),
+    Migration::Convert("window.start_maximized", "window.startup_mode", start_maximized),
+    Migration::Remove(
+        "enable_experimental_conpty_backend",
+        "ConPTY is used by default, set `winpty_backend` to use winpty instead",
+    ),
+];
+
+/// Option which has been migrated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Migrated {
+    /// Option has been moved to the path `to`
+    Moved { from: String, to: String },
+
+    /// Option has been removed and has no effect
+    Removed { from: String, hint: &'static str },
+}
+
+impl Migrated {
+    /// Path of the option in the config before the migration
+    pub fn field(&self) -> &str {
+        match self {
+            Migrated::Moved { from, .. } | Migrated::Removed { from, .. } => from,
+        }
+    }
+}
+
+impl fmt::Display for Migrated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Migrated::Moved { from, to } => write!(f, "`{}` has been renamed to `{}`", from, to),
+            Migrated::Removed { from, hint } => write!(f, "`{}` has been removed; {}", from, hint),
+        }
+    }
+}
+
+/// Move all renamed options of `tree` to their current paths and drop removed options
+///
+/// Platform sections are migrated as well. When an option is set at both its old and its new
+/// path, the old value wins, just like it did before the option was renamed.
+pub fn migrate(tree: &mut Value) -> Vec<Migrated> {
+    let mut migrated = migrate_section(tree, "");
+
+    let platforms = tree
+        .as_mapping_mut()
+        .and_then(|mapping| mapping.get_mut(&Value::String(PLATFORM_KEY.into())))
+        .and_then(Value::as_mapping_mut);
+    if let Some(platforms) = platforms {
+        let names: Vec<Value> = platforms.iter().map(|(name, _)| name.clone()).collect();
+        for name in names {
+            if let (Some(section), Value::String(name)) = (platforms.get_mut(&name), &name) {
+                let prefix = format!("{}.{}.", PLATFORM_KEY, name);
+                migrated.extend(migrate_section(section, &prefix));
+            }
+        }
+    }
+
+    migrated
+}
+
+/// Apply all migrations to a section of the config, `prefix` is the path of the section
+fn migrate_section(section: &mut Value, prefix: &str) -> Vec<Migrated> {
+    let mut migrated = Vec::new();
+
+    for migration in MIGRATIONS {
+        let value = match take(section, migration.from()) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        let from = format!("{}{}", prefix, migration.from());
+        match *migration {
+            Migration::Rename(_, to) => {
+                insert(section, to, value);
+                migrated.push(Migrated::Moved { from, to: format!("{}{}", prefix, to) });
+            },
+            Migration::Convert(_, to, convert) => {
+                if let Some(value) = convert(value) {
+                    insert(section, to, value);
+                }
+                migrated.push(Migrated::Moved { from, to: format!("{}{}", prefix, to) });
+            },
+            Migration::Remove(_, hint) => migrated.push(Migrated::Removed { from, hint }),
+        }
+    }
+
+    migrated
+}
+
+/// Remove the option at `path` from `tree`
+fn take(tree: &mut Value, path: &str) -> Option<Value> {
+    let mut keys: Vec<&str> = path.split('.').collect();
+    let last = keys.pop()?;
+
+    let mut section = tree;
+    for key in keys {
+        section = section.as_mapping_mut()?.get_mut(&Value::String(key.into()))?;
+    }
+
+    section.as_mapping_mut()?.remove(&Value::String(last.into()))
+}
+
+/// Set the option at `path` in `tree`, merging it with the sections which already exist
+fn insert(tree: &mut Value, path: &str, value: Value) {
+    let mut overlay = value;
+    for key in path.rsplit('.') {
+        let mut mapping = Mapping::new();
+        mapping.insert(Value::String(key.into()), overlay);
+        overlay = Value::Mapping(mapping);
+    }
+
+    platform::merge(tree, overlay);
+}
+
+/// `start_maximized: true` is the `Maximized` startup mode, otherwise the mode is unchanged
+fn start_maximized(value: Value) -> Option<Value> {
+    match value {
+        Value::Bool(true) => Some(Value::String("Maximized".into())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::Value;
+
+    use super::{migrate, Migrated};
+    #[cfg(feature = "gui")]
+    use crate::ansi::CursorStyle;
+    use crate::config::{collect_unknown_fields, Config, StartupMode, DEFAULT_ALACRITTY_CONFIG};
+    #[cfg(feature = "gui")]
+    use crate::config::{Dimensions, Padding};
+    #[cfg(feature = "gui")]
+    use crate::index::{Column, Line};
+
+    // The old configs contain font and mouse options, which only exist with the window
+    #[cfg(feature = "gui")]
+    static TOP_LEVEL: &str =
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/config/top_level.yml"));
+    #[cfg(feature = "gui")]
+    static DEPRECATED: &str =
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/config/deprecated.yml"));
+
+    /// Migrated tree of a config and the options which have been migrated
+    fn migrated(contents: &str) -> (Value, Vec<Migrated>) {
+        let mut tree: Value = serde_yaml::from_str(contents).unwrap();
+        let migrated = migrate(&mut tree);
+        (tree, migrated)
+    }
+
+    /// Config from a migrated tree, which must not have any unknown fields left
+    fn config(tree: Value) -> Config {
+        let (config, unknown) = collect_unknown_fields(|| Config::from_value(tree));
+        assert_eq!(unknown, Vec::<String>::new());
+        config.unwrap()
+    }
+
+    fn moved(from: &str, to: &str) -> Migrated {
+        Migrated::Moved { from: from.into(), to: to.into() }
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn top_level_options() {
+        let (tree, migrated) = migrated(TOP_LEVEL);
+
+        let expected = vec![
+            moved("dimensions", "window.dimensions"),
+            moved("padding", "window.padding"),
+            moved("cursor_style", "cursor.style"),
+            moved("unfocused_hollow_cursor", "cursor.unfocused_hollow"),
+            moved("hide_cursor_when_typing", "mouse.hide_when_typing"),
+            moved("faux_scrolling_lines", "scrolling.faux_multiplier"),
+        ];
+        assert_eq!(migrated, expected);
+
+        let config = config(tree);
+        assert_eq!(config.window.dimensions, Dimensions::new(Column(100), Line(30)));
+        assert_eq!(config.window.padding, Padding::symmetric(4, 2));
+        assert_eq!(config.cursor.style, CursorStyle::Beam);
+        assert!(!config.cursor.unfocused_hollow());
+        assert!(config.mouse.hide_when_typing);
+        assert_eq!(config.scrolling.faux_multiplier(), 5);
+        assert_eq!(config.font.size.as_f32_pts(), 9.0);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn deprecated_options() {
+        let (tree, migrated) = migrated(DEPRECATED);
+
+        let expected = vec![
+            moved("mouse.faux_scrollback_lines", "scrolling.faux_multiplier"),
+            moved("render_timer", "debug.render_timer"),
+            moved("persistent_logging", "debug.persistent_logging"),
+            moved("window.start_maximized", "window.startup_mode"),
+            Migrated::Removed {
+                from: "enable_experimental_conpty_backend".into(),
+                hint: "ConPTY is used by default, set `winpty_backend` to use winpty instead",
+            },
+            moved("platform.macos.render_timer", "platform.macos.debug.render_timer"),
+        ];
+        assert_eq!(migrated, expected);
+        assert_eq!(tree["platform"]["macos"]["debug"]["render_timer"], Value::Bool(false));
+
+        // Old options take precedence over the new ones, like before they were renamed
+        let config = config(tree);
+        assert!(config.debug.render_timer);
+        assert!(config.debug.persistent_logging);
+        assert_eq!(config.window.startup_mode(), StartupMode::Maximized);
+        assert_eq!(config.scrolling.faux_multiplier(), 2);
+        assert!(!config.mouse.hide_when_typing);
+        assert_eq!(config.window.padding, Padding { left: 1, right: 1, top: 10, bottom: 0 });
+    }
+
+    #[test]
+    fn start_maximized_false_keeps_startup_mode() {
+        let (tree, migrated) =
+            migrated("window:\n  start_maximized: false\n  startup_mode: Fullscreen\n");
+
+        assert_eq!(migrated, vec![moved("window.start_maximized", "window.startup_mode")]);
+        assert_eq!(config(tree).window.startup_mode(), StartupMode::Fullscreen);
+    }
+
+    #[test]
+    fn current_config_is_unchanged() {
+        let (tree, migrated) = migrated(DEFAULT_ALACRITTY_CONFIG);
+
+        assert!(migrated.is_empty());
+        assert_eq!(tree, serde_yaml::from_str::<Value>(DEFAULT_ALACRITTY_CONFIG).unwrap());
+    }
+
+    #[test]
+    fn migration_messages() {
+        assert_eq!(
+            moved("render_timer", "debug.render_timer").to_string(),
+            "`render_timer` has been renamed to `debug.render_timer`"
+        );
+        let removed = Migrated::Removed { from: "custom".into(), hint: "use `other` instead" };
+        assert_eq!(removed.to_string(), "`custom` has been removed; use `other` instead");
+        assert_eq!(removed.field(), "custom");
+    }
+}
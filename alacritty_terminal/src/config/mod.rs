@@ -13,55 +13,100 @@
 // limitations under the License.
 
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use serde::{Deserialize, Deserializer};
 use serde_yaml::Value;
 
+/// Report a config value which couldn't be used
+///
+/// The error is logged, unless errors are being collected with `collect_errors`.
+macro_rules! config_error {
+    ($($arg:tt)*) => {
+        if !$crate::config::collect_error(|| format!($($arg)*)) {
+            error!($($arg)*);
+        }
+    };
+}
+
+#[cfg(feature = "gui")]
 mod bindings;
+mod changes;
 mod colors;
 mod debug;
+mod expand;
+#[cfg(feature = "gui")]
 mod font;
+#[cfg(feature = "gui")]
+mod key_chords;
+mod migrate;
 mod monitor;
+#[cfg(feature = "gui")]
 mod mouse;
+mod overrides;
+mod platform;
 mod scrolling;
+mod terminal;
 #[cfg(test)]
 mod test;
+mod unknown;
 mod visual_bell;
 mod window;
 
-use crate::ansi::{Color, CursorStyle, NamedColor};
+use crate::ansi::{self, CursorStyle};
+use crate::config::unknown::CheckedValue;
+#[cfg(feature = "gui")]
 use crate::input::{Binding, KeyBinding, MouseBinding};
+use crate::util::limit;
 
+#[cfg(feature = "gui")]
 pub use crate::config::bindings::Key;
-pub use crate::config::colors::Colors;
-pub use crate::config::debug::Debug;
+pub use crate::config::changes::ConfigChanges;
+pub use crate::config::colors::{ColorScheme, Colors};
+pub use crate::config::debug::{Debug, RenderTimerPosition, RendererApi};
+#[cfg(feature = "gui")]
 pub use crate::config::font::{Font, FontDescription};
-pub use crate::config::monitor::Monitor;
+#[cfg(feature = "gui")]
+pub use crate::config::key_chords::KeyChords;
+pub use crate::config::migrate::{migrate, Migrated};
+pub use crate::config::monitor::{Monitor, OnConfigReload};
+#[cfg(feature = "gui")]
 pub use crate::config::mouse::{ClickHandler, Mouse};
+pub use crate::config::overrides::{
+    apply_overrides, rejected_overrides, ConfigOverride, OverrideError,
+};
+pub use crate::config::platform::merge_platform;
 pub use crate::config::scrolling::Scrolling;
-pub use crate::config::visual_bell::{VisualBellAnimation, VisualBellConfig};
-pub use crate::config::window::{Decorations, Dimensions, StartupMode, WindowConfig};
-use crate::term::color::Rgb;
+pub use crate::config::terminal::{AmbiguousWidth, ReplyTerminator, TerminalConfig};
+pub use crate::config::unknown::collect_unknown_fields;
+pub use crate::config::visual_bell::{VisualBellAnimation, VisualBellConfig, VisualBellRegion};
+pub use crate::config::window::{
+    Class, Decorations, Dimensions, OptionAsAlt, Padding, Rulers, StartupMode, TitlebarColor,
+    WindowConfig, DEFAULT_NAME,
+};
 
 pub static DEFAULT_ALACRITTY_CONFIG: &str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../alacritty.yml"));
 const MAX_SCROLLBACK_LINES: u32 = 100_000;
 
+thread_local! {
+    /// Config errors of the current thread, only collected while `collect_errors` is running
+    static COLLECTED_ERRORS: RefCell<Option<Vec<String>>> = RefCell::new(None);
+}
+
 /// Top-level config type
 #[derive(Debug, PartialEq, Deserialize)]
 pub struct Config {
-    /// Pixel padding
-    #[serde(default, deserialize_with = "failure_default")]
-    pub padding: Option<Delta<u8>>,
-
     /// TERM env variable
     #[serde(default, deserialize_with = "failure_default")]
     pub env: HashMap<String, String>,
 
     /// Font configuration
+    #[cfg(feature = "gui")]
     #[serde(default, deserialize_with = "failure_default")]
     pub font: Font,
 
@@ -72,6 +117,10 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     pub colors: Colors,
 
+    /// Named color schemes which can replace `colors`
+    #[serde(default, deserialize_with = "colors::deserialize_color_schemes")]
+    pub schemes: Vec<ColorScheme>,
+
     /// Background opacity from 0.0 to 1.0
     #[serde(default, deserialize_with = "failure_default")]
     background_opacity: Alpha,
@@ -81,16 +130,24 @@ pub struct Config {
     pub window: WindowConfig,
 
     /// Keybindings
+    #[cfg(feature = "gui")]
     #[serde(default = "default_key_bindings", deserialize_with = "deserialize_key_bindings")]
     pub key_bindings: Vec<KeyBinding>,
 
+    /// Key bindings which are triggered by a sequence of chords
+    #[cfg(feature = "gui")]
+    #[serde(default, deserialize_with = "failure_default")]
+    pub key_chords: KeyChords,
+
     /// Bindings for the mouse
+    #[cfg(feature = "gui")]
     #[serde(default = "default_mouse_bindings", deserialize_with = "deserialize_mouse_bindings")]
     pub mouse_bindings: Vec<MouseBinding>,
 
     #[serde(default, deserialize_with = "failure_default")]
     pub selection: Selection,
 
+    #[cfg(feature = "gui")]
     #[serde(default, deserialize_with = "failure_default")]
     pub mouse: Mouse,
 
@@ -98,6 +155,18 @@ pub struct Config {
     #[serde(default, deserialize_with = "from_string_or_deserialize")]
     pub shell: Option<Shell<'static>>,
 
+    /// Keep the window open after the shell exited, only set from the command line
+    #[serde(skip)]
+    pub hold: bool,
+
+    /// Milliseconds the window stays open after the shell failed
+    #[serde(default, deserialize_with = "failure_default")]
+    exit_status_delay: ExitStatusDelay,
+
+    /// Bytes read from the pty at once
+    #[serde(default, deserialize_with = "failure_default")]
+    pty_read_buffer_size: PtyReadBufferSize,
+
     /// Path where config was loaded from
     #[serde(default, deserialize_with = "failure_default")]
     pub config_path: Option<PathBuf>,
@@ -114,10 +183,18 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     live_config_reload: DefaultTrueBool,
 
+    /// Show the content of the last closed window on startup
+    #[serde(default, deserialize_with = "failure_default")]
+    restore_session: bool,
+
     /// Number of spaces in one tab
     #[serde(default, deserialize_with = "failure_default")]
     tabspaces: Tabspaces,
 
+    /// Interpretation of the output of applications
+    #[serde(default, deserialize_with = "failure_default")]
+    pub terminal: TerminalConfig,
+
     /// How much scrolling history to keep
     #[serde(default, deserialize_with = "failure_default")]
     pub scrolling: Scrolling,
@@ -126,31 +203,25 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     pub cursor: Cursor,
 
-    /// Enable experimental conpty backend instead of using winpty.
-    /// Will only take effect on Windows 10 Oct 2018 and later.
-    #[cfg(windows)]
+    /// Use the winpty backend even if ConPTY is available, only used on Windows
     #[serde(default, deserialize_with = "failure_default")]
-    pub enable_experimental_conpty_backend: bool,
+    pub winpty_backend: bool,
 
     /// Send escape sequences using the alt key.
     #[serde(default, deserialize_with = "failure_default")]
     alt_send_esc: DefaultTrueBool,
 
     /// Shell startup directory
-    #[serde(default, deserialize_with = "option_explicit_none")]
+    #[serde(default, deserialize_with = "expand::option_explicit_none_expanded")]
     working_directory: Option<PathBuf>,
 
+    /// Directory screenshots are saved to
+    #[serde(default, deserialize_with = "expand::option_explicit_none_expanded")]
+    screenshot_directory: Option<PathBuf>,
+
     /// Debug options
     #[serde(default, deserialize_with = "failure_default")]
     pub debug: Debug,
-
-    // TODO: DEPRECATED
-    #[serde(default, deserialize_with = "failure_default")]
-    pub render_timer: Option<bool>,
-
-    // TODO: DEPRECATED
-    #[serde(default, deserialize_with = "failure_default")]
-    pub persistent_logging: Option<bool>,
 }
 
 impl Default for Config {
@@ -160,10 +231,30 @@ impl Default for Config {
 }
 
 impl Config {
+    /// Deserialize the config from a YAML tree
+    ///
+    /// Unlike `serde_yaml::from_value`, this reports fields which don't belong to any option to
+    /// `collect_unknown_fields`.
+    pub fn from_value(value: Value) -> Result<Config, serde_yaml::Error> {
+        Config::deserialize(CheckedValue(value))
+    }
+
     pub fn tabspaces(&self) -> usize {
         self.tabspaces.0
     }
 
+    /// Time the exit status of a failed shell is shown before closing the window
+    #[inline]
+    pub fn exit_status_delay(&self) -> Duration {
+        Duration::from_millis(self.exit_status_delay.0)
+    }
+
+    /// Size of the buffer for pty output, between 4 KiB and 16 MiB
+    #[inline]
+    pub fn pty_read_buffer_size(&self) -> usize {
+        limit(self.pty_read_buffer_size.0, 0x1000, 0x100_0000)
+    }
+
     #[inline]
     pub fn draw_bold_text_with_bright_colors(&self) -> bool {
         self.draw_bold_text_with_bright_colors.0
@@ -172,7 +263,7 @@ impl Config {
     /// Should show render timer
     #[inline]
     pub fn render_timer(&self) -> bool {
-        self.render_timer.unwrap_or(self.debug.render_timer)
+        self.debug.render_timer
     }
 
     /// Live config reload
@@ -186,21 +277,22 @@ impl Config {
         self.live_config_reload.0 = live_config_reload;
     }
 
+    /// Show the content of the last closed window on startup
     #[inline]
-    pub fn dynamic_title(&self) -> bool {
-        self.dynamic_title.0
+    pub fn restore_session(&self) -> bool {
+        self.restore_session
     }
 
-    /// Cursor foreground color
     #[inline]
-    pub fn cursor_text_color(&self) -> Option<Rgb> {
-        self.colors.cursor.text
+    pub fn dynamic_title(&self) -> bool {
+        self.dynamic_title.0
     }
 
-    /// Cursor background color
-    #[inline]
-    pub fn cursor_cursor_color(&self) -> Option<Color> {
-        self.colors.cursor.cursor.map(|_| Color::Named(NamedColor::Cursor))
+    /// Colors of the scheme `name`, the `colors` section is used without a known scheme
+    pub fn scheme_colors(&self, name: Option<&str>) -> &Colors {
+        name.and_then(|name| self.schemes.iter().find(|scheme| scheme.name == name))
+            .map(|scheme| &scheme.colors)
+            .unwrap_or(&self.colors)
     }
 
     #[inline]
@@ -209,15 +301,28 @@ impl Config {
     }
 
     /// Send escape sequences using the alt key
+    ///
+    /// On macOS Alt is the Option key, which composes characters unless it's used as Alt with
+    /// `window.option_as_alt`.
     #[inline]
     pub fn alt_send_esc(&self) -> bool {
-        self.alt_send_esc.0
+        self.alt_send_esc.0 && !cfg!(target_os = "macos")
+    }
+
+    /// Option keys which act as Alt, only used on macOS
+    #[inline]
+    pub fn option_as_alt(&self) -> OptionAsAlt {
+        if cfg!(target_os = "macos") {
+            self.window.option_as_alt
+        } else {
+            OptionAsAlt::None
+        }
     }
 
     /// Keep the log file after quitting Alacritty
     #[inline]
     pub fn persistent_logging(&self) -> bool {
-        self.persistent_logging.unwrap_or(self.debug.persistent_logging)
+        self.debug.persistent_logging
     }
 
     #[inline]
@@ -234,16 +339,24 @@ impl Config {
     pub fn set_working_directory(&mut self, working_directory: Option<PathBuf>) {
         self.working_directory = working_directory;
     }
+
+    #[inline]
+    pub fn screenshot_directory(&self) -> &Option<PathBuf> {
+        &self.screenshot_directory
+    }
 }
 
+#[cfg(feature = "gui")]
 fn default_key_bindings() -> Vec<KeyBinding> {
     bindings::default_key_bindings()
 }
 
+#[cfg(feature = "gui")]
 fn default_mouse_bindings() -> Vec<MouseBinding> {
     bindings::default_mouse_bindings()
 }
 
+#[cfg(feature = "gui")]
 fn deserialize_key_bindings<'a, D>(deserializer: D) -> Result<Vec<KeyBinding>, D::Error>
 where
     D: Deserializer<'a>,
@@ -251,6 +364,7 @@ where
     deserialize_bindings(deserializer, bindings::default_key_bindings())
 }
 
+#[cfg(feature = "gui")]
 fn deserialize_mouse_bindings<'a, D>(deserializer: D) -> Result<Vec<MouseBinding>, D::Error>
 where
     D: Deserializer<'a>,
@@ -258,6 +372,7 @@ where
     deserialize_bindings(deserializer, bindings::default_mouse_bindings())
 }
 
+#[cfg(feature = "gui")]
 fn deserialize_bindings<'a, D, T>(
     deserializer: D,
     mut default: Vec<Binding<T>>,
@@ -285,12 +400,31 @@ pub struct Selection {
     semantic_escape_chars: EscapeChars,
     #[serde(deserialize_with = "failure_default")]
     pub save_to_clipboard: bool,
+    #[serde(deserialize_with = "failure_default")]
+    max_clipboard_escape_size: ClipboardEscapeSize,
+    #[serde(deserialize_with = "failure_default")]
+    pub lossy_clipboard_escape: bool,
 }
 
 impl Selection {
     pub fn semantic_escape_chars(&self) -> &str {
         &self.semantic_escape_chars.0
     }
+
+    /// Maximum size of text stored in the clipboard by escape sequences, in bytes
+    pub fn max_clipboard_escape_size(&self) -> usize {
+        self.max_clipboard_escape_size.0.saturating_mul(1024)
+    }
+}
+
+/// Limit for OSC 52 clipboard content in kilobytes
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+struct ClipboardEscapeSize(usize);
+
+impl Default for ClipboardEscapeSize {
+    fn default() -> Self {
+        ClipboardEscapeSize(ansi::MAX_CLIPBOARD_SIZE / 1024)
+    }
 }
 
 #[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -323,12 +457,17 @@ impl Cursor {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 pub struct Shell<'a> {
+    #[serde(deserialize_with = "expand::deserialize")]
     pub program: Cow<'a, str>,
 
     #[serde(default, deserialize_with = "failure_default")]
     pub args: Vec<String>,
+
+    /// Start the program as login shell, defaults to `true` on macOS only
+    #[serde(default, deserialize_with = "failure_default")]
+    login: Option<bool>,
 }
 
 impl<'a> Shell<'a> {
@@ -336,20 +475,28 @@ impl<'a> Shell<'a> {
     where
         S: Into<Cow<'a, str>>,
     {
-        Shell { program: program.into(), args: Vec::new() }
+        Shell { program: program.into(), args: Vec::new(), login: None }
     }
 
     pub fn new_with_args<S>(program: S, args: Vec<String>) -> Shell<'a>
     where
         S: Into<Cow<'a, str>>,
     {
-        Shell { program: program.into(), args }
+        Shell { program: program.into(), args, login: None }
+    }
+
+    pub fn login(&self) -> bool {
+        self.login.unwrap_or(cfg!(target_os = "macos"))
+    }
+
+    pub fn set_login(&mut self, login: bool) {
+        self.login = Some(login);
     }
 }
 
 impl FromString for Option<Shell<'_>> {
     fn from(input: String) -> Self {
-        Some(Shell::new(input))
+        Some(Shell::new(expand::expand_env(&input)))
     }
 }
 
@@ -405,7 +552,25 @@ impl Default for Tabspaces {
     }
 }
 
-#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+struct ExitStatusDelay(u64);
+
+impl Default for ExitStatusDelay {
+    fn default() -> Self {
+        ExitStatusDelay(3000)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+struct PtyReadBufferSize(usize);
+
+impl Default for PtyReadBufferSize {
+    fn default() -> Self {
+        PtyReadBufferSize(0x1_0000)
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 struct DefaultTrueBool(bool);
 
 impl Default for DefaultTrueBool {
@@ -419,16 +584,45 @@ where
     T: Default,
     E: Display,
 {
-    error!("Problem with config: {}; using default value", err);
+    config_error!("Problem with config: {}; using default value", err);
     T::default()
 }
 
+/// Run `f` and return all config errors reported during it instead of logging them
+pub(crate) fn collect_errors<T, F>(f: F) -> (T, Vec<String>)
+where
+    F: FnOnce() -> T,
+{
+    let previous = COLLECTED_ERRORS.with(|errors| errors.replace(Some(Vec::new())));
+
+    // Configs deserialized here are not loaded, so their unknown fields are not reported
+    let (result, _) = collect_unknown_fields(f);
+
+    let collected = COLLECTED_ERRORS.with(|errors| errors.replace(previous));
+    (result, collected.unwrap_or_default())
+}
+
+/// Store a config error if errors are being collected, returns `false` if it should be logged
+pub(crate) fn collect_error<F>(message: F) -> bool
+where
+    F: FnOnce() -> String,
+{
+    COLLECTED_ERRORS.with(|errors| match errors.borrow_mut().as_mut() {
+        Some(errors) => {
+            errors.push(message());
+            true
+        },
+        None => false,
+    })
+}
+
 pub fn failure_default<'a, D, T>(deserializer: D) -> Result<T, D::Error>
 where
     D: Deserializer<'a>,
     T: Deserialize<'a> + Default,
 {
-    Ok(T::deserialize(Value::deserialize(deserializer)?).unwrap_or_else(fallback_default))
+    let value = Value::deserialize(deserializer)?;
+    Ok(T::deserialize(CheckedValue(value)).unwrap_or_else(fallback_default))
 }
 
 pub fn option_explicit_none<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
@@ -438,7 +632,7 @@ where
 {
     Ok(match Value::deserialize(deserializer)? {
         Value::String(ref value) if value.to_lowercase() == "none" => None,
-        value => Some(T::deserialize(value).unwrap_or_else(fallback_default)),
+        value => Some(T::deserialize(CheckedValue(value)).unwrap_or_else(fallback_default)),
     })
 }
 
@@ -449,7 +643,7 @@ where
 {
     Ok(match Value::deserialize(deserializer)? {
         Value::String(value) => T::from(value),
-        value => T::deserialize(value).unwrap_or_else(fallback_default),
+        value => T::deserialize(CheckedValue(value)).unwrap_or_else(fallback_default),
     })
 }
 
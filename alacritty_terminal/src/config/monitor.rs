@@ -13,12 +13,6 @@ pub trait OnConfigReload {
     fn on_config_reload(&mut self);
 }
 
-impl OnConfigReload for crate::display::Notifier {
-    fn on_config_reload(&mut self) {
-        self.notify();
-    }
-}
-
 impl Monitor {
     /// Get pending config changes
     pub fn pending(&self) -> Option<PathBuf> {
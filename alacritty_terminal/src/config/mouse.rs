@@ -4,7 +4,7 @@ use glutin::ModifiersState;
 use serde::{Deserialize, Deserializer};
 
 use crate::config::bindings::{CommandWrapper, ModsWrapper};
-use crate::config::failure_default;
+use crate::config::{failure_default, DefaultTrueBool};
 
 #[serde(default)]
 #[derive(Default, Clone, Debug, Deserialize, PartialEq, Eq)]
@@ -16,7 +16,57 @@ pub struct Mouse {
     #[serde(deserialize_with = "failure_default")]
     pub hide_when_typing: bool,
     #[serde(deserialize_with = "failure_default")]
+    hide_delay: u64,
+    #[serde(deserialize_with = "failure_default")]
     pub url: Url,
+    #[serde(deserialize_with = "failure_default")]
+    drop_files: DefaultTrueBool,
+    #[serde(deserialize_with = "failure_default")]
+    bypass_modifiers: BypassModifiers,
+}
+
+impl Mouse {
+    /// Inactivity after which the pointer is hidden, `None` if it is never hidden
+    #[inline]
+    pub fn hide_delay(&self) -> Option<Duration> {
+        Some(self.hide_delay).filter(|&secs| secs > 0).map(Duration::from_secs)
+    }
+
+    /// Insert the paths of files dropped onto the window
+    #[inline]
+    pub fn drop_files(&self) -> bool {
+        self.drop_files.0
+    }
+
+    #[inline]
+    pub fn set_drop_files(&mut self, drop_files: bool) {
+        self.drop_files = DefaultTrueBool(drop_files);
+    }
+
+    /// Modifiers which send mouse input to Alacritty while the application has mouse reporting
+    /// enabled, `None` if mouse reporting can't be bypassed
+    #[inline]
+    pub fn bypass_modifiers(&self) -> Option<ModifiersState> {
+        Some(self.bypass_modifiers.0).filter(|&mods| mods != ModifiersState::default())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct BypassModifiers(ModifiersState);
+
+impl Default for BypassModifiers {
+    fn default() -> Self {
+        BypassModifiers(ModifiersState { shift: true, ..Default::default() })
+    }
+}
+
+impl<'de> Deserialize<'de> for BypassModifiers {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ModsWrapper::deserialize(deserializer).map(|mods| BypassModifiers(mods.into_inner()))
+    }
 }
 
 #[serde(default)]
@@ -56,7 +106,11 @@ where
     match <Option<CommandWrapper>>::deserialize(val) {
         Ok(launcher) => Ok(launcher),
         Err(err) => {
-            error!("Problem with config: {}; using {}", err, default.clone().unwrap().program());
+            config_error!(
+                "Problem with config: {}; using {}",
+                err,
+                default.clone().unwrap().program()
+            );
             Ok(default)
         },
     }
@@ -101,7 +155,7 @@ where
     match u64::deserialize(value) {
         Ok(threshold_ms) => Ok(Duration::from_millis(threshold_ms)),
         Err(err) => {
-            error!("Problem with config: {}; using default value", err);
+            config_error!("Problem with config: {}; using default value", err);
             Ok(default_threshold_ms())
         },
     }
@@ -0,0 +1,438 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Config options set on the command line
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::config::{collect_errors, Config};
+
+/// Part of the path to an overridden option
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Field of a section, like `font` in `font.size`
+    Key(String),
+
+    /// Entry of a list, like `[0]` in `key_bindings[0]`
+    Index(usize),
+}
+
+/// Option set on the command line with `path=value`, like `font.size=14`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOverride {
+    /// Text of the flag, used in error messages
+    flag: String,
+
+    path: Vec<Segment>,
+    value: Value,
+}
+
+/// Reasons for rejecting a config override
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverrideError {
+    /// The flag is not of the form `path=value`
+    MissingValue,
+
+    /// The path is empty or contains an invalid list index
+    InvalidPath,
+
+    /// The value is not valid YAML
+    InvalidYaml(String),
+
+    /// The path goes through a value which is not a section
+    NotASection(String),
+
+    /// The path uses an index on a value which is not a list
+    NotAList(String),
+
+    /// The path uses an index past the end of a list
+    IndexOutOfBounds(String, usize),
+
+    /// The value has the wrong type for the option
+    InvalidValue(String),
+
+    /// No option with this path exists
+    UnknownOption(String),
+}
+
+impl Display for OverrideError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OverrideError::MissingValue => f.write_str("expected an option like `font.size=14`"),
+            OverrideError::InvalidPath => f.write_str("invalid option path"),
+            OverrideError::InvalidYaml(err) => write!(f, "invalid value: {}", err),
+            OverrideError::NotASection(path) => write!(f, "`{}` has no options", path),
+            OverrideError::NotAList(path) => write!(f, "`{}` is not a list", path),
+            OverrideError::IndexOutOfBounds(path, len) => {
+                write!(f, "`{}` is out of bounds, the list has {} entries", path, len)
+            },
+            OverrideError::InvalidValue(err) => f.write_str(err),
+            OverrideError::UnknownOption(path) => write!(f, "unknown option `{}`", path),
+        }
+    }
+}
+
+impl FromStr for ConfigOverride {
+    type Err = OverrideError;
+
+    fn from_str(flag: &str) -> Result<ConfigOverride, OverrideError> {
+        let mut parts = flag.splitn(2, '=');
+        let path = parts.next().unwrap_or_default();
+        let value = parts.next().ok_or(OverrideError::MissingValue)?;
+
+        Ok(ConfigOverride {
+            flag: flag.to_owned(),
+            path: parse_path(path.trim())?,
+            value: parse_value(value)?,
+        })
+    }
+}
+
+impl Display for ConfigOverride {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.flag)
+    }
+}
+
+impl ConfigOverride {
+    /// Write the value into the config's YAML tree, creating missing sections
+    fn apply(&self, tree: &mut Value) -> Result<(), OverrideError> {
+        self.set(tree, self.value.clone())
+    }
+
+    fn set(&self, tree: &mut Value, value: Value) -> Result<(), OverrideError> {
+        let mut current = tree;
+        for (i, segment) in self.path.iter().enumerate() {
+            current = match segment {
+                Segment::Key(key) => {
+                    if current.is_null() {
+                        *current = Value::Mapping(Mapping::new());
+                    }
+
+                    let mapping = match current {
+                        Value::Mapping(mapping) => mapping,
+                        _ => return Err(OverrideError::NotASection(self.path_until(i))),
+                    };
+
+                    let key = Value::String(key.clone());
+                    if !mapping.contains_key(&key) {
+                        mapping.insert(key.clone(), Value::Null);
+                    }
+                    mapping.get_mut(&key).unwrap()
+                },
+                Segment::Index(index) => match current {
+                    Value::Sequence(sequence) => {
+                        let len = sequence.len();
+                        match sequence.get_mut(*index) {
+                            Some(value) => value,
+                            None => {
+                                return Err(OverrideError::IndexOutOfBounds(
+                                    self.path_until(i + 1),
+                                    len,
+                                ))
+                            },
+                        }
+                    },
+                    _ => return Err(OverrideError::NotAList(self.path_until(i))),
+                },
+            };
+        }
+
+        *current = value;
+
+        Ok(())
+    }
+
+    /// Text of the path up to the segment at `end`
+    fn path_until(&self, end: usize) -> String {
+        let mut path = String::new();
+        for segment in &self.path[..end] {
+            match segment {
+                Segment::Key(key) if path.is_empty() => path.push_str(key),
+                Segment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                },
+                Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+
+    /// Check that the override changes the config deserialized from `tree`
+    ///
+    /// Returns the config with the override applied.
+    fn validate(&self, tree: &Value, config: &Config) -> Result<(Value, Config), OverrideError> {
+        let mut overridden = tree.clone();
+        self.apply(&mut overridden)?;
+
+        let (_, base_errors) = collect_errors(|| Config::from_value(tree.clone()));
+
+        let (new_config, errors) = deserialize(&overridden, &base_errors)?;
+        if let Some(err) = errors.into_iter().next() {
+            return Err(OverrideError::InvalidValue(err));
+        }
+
+        if new_config != *config {
+            return Ok((overridden, new_config));
+        }
+
+        // The option might already have this value, replacing it with something invalid
+        // will show if it exists at all
+        for probe in vec![Value::Null, Value::Sequence(Vec::new())] {
+            let mut probed = tree.clone();
+            self.set(&mut probed, probe)?;
+
+            let (probed_config, errors) = deserialize(&probed, &base_errors)?;
+            if !errors.is_empty() || probed_config != *config {
+                return Ok((overridden, new_config));
+            }
+        }
+
+        Err(OverrideError::UnknownOption(self.path_until(self.path.len())))
+    }
+}
+
+/// Deserialize the config from `tree` with all valid overrides applied on top
+///
+/// Overrides which can't be applied are skipped and returned with the reason.
+pub fn apply_overrides(
+    tree: Value,
+    overrides: &[ConfigOverride],
+) -> Result<(Config, Vec<(&ConfigOverride, OverrideError)>), serde_yaml::Error> {
+    let (tree, rejected) = override_tree(tree, overrides)?;
+    Ok((Config::from_value(tree)?, rejected))
+}
+
+/// Overrides which can't be applied to the config from `tree`, with the reason
+///
+/// Unlike `apply_overrides` this doesn't deserialize the final config, so problems of `tree`
+/// itself are not reported.
+pub fn rejected_overrides(
+    tree: Value,
+    overrides: &[ConfigOverride],
+) -> Result<Vec<(&ConfigOverride, OverrideError)>, serde_yaml::Error> {
+    override_tree(tree, overrides).map(|(_, rejected)| rejected)
+}
+
+/// Apply all valid overrides to `tree`, returning the rejected ones
+fn override_tree(
+    mut tree: Value,
+    overrides: &[ConfigOverride],
+) -> Result<(Value, Vec<(&ConfigOverride, OverrideError)>), serde_yaml::Error> {
+    let mut rejected = Vec::new();
+
+    if !overrides.is_empty() {
+        // Errors of the config file itself are reported once the final config is loaded
+        let (config, _) = collect_errors(|| Config::from_value(tree.clone()));
+        let mut config = config?;
+
+        for config_override in overrides {
+            match config_override.validate(&tree, &config) {
+                Ok((overridden, overridden_config)) => {
+                    tree = overridden;
+                    config = overridden_config;
+                },
+                Err(err) => rejected.push((config_override, err)),
+            }
+        }
+    }
+
+    Ok((tree, rejected))
+}
+
+/// Deserialize the config from `tree`, returning all errors which are not in `base_errors`
+fn deserialize(
+    tree: &Value,
+    base_errors: &[String],
+) -> Result<(Config, Vec<String>), OverrideError> {
+    let (config, mut errors) = collect_errors(|| Config::from_value(tree.clone()));
+    let config = config.map_err(|err| OverrideError::InvalidValue(err.to_string()))?;
+
+    errors.retain(|err| !base_errors.contains(err));
+
+    Ok((config, errors))
+}
+
+/// Split a path like `key_bindings[0].mods` into its segments
+fn parse_path(path: &str) -> Result<Vec<Segment>, OverrideError> {
+    let mut segments = Vec::new();
+
+    for part in path.split('.') {
+        let (key, mut indices) = part.split_at(part.find('[').unwrap_or_else(|| part.len()));
+        if key.is_empty() {
+            return Err(OverrideError::InvalidPath);
+        }
+        segments.push(Segment::Key(key.to_owned()));
+
+        while !indices.is_empty() {
+            let end = indices.find(']').filter(|_| indices.starts_with('['));
+            match end.and_then(|end| indices[1..end].parse().ok().map(|index| (end, index))) {
+                Some((end, index)) => {
+                    segments.push(Segment::Index(index));
+                    indices = &indices[end + 1..];
+                },
+                None => return Err(OverrideError::InvalidPath),
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Parse the value of an override as YAML
+fn parse_value(value: &str) -> Result<Value, OverrideError> {
+    let value = value.trim();
+
+    // Colors like `#ff00ff` would be parsed as a comment
+    if value.starts_with('#') {
+        return Ok(Value::String(value.to_owned()));
+    }
+
+    serde_yaml::from_str(value).map_err(|err| OverrideError::InvalidYaml(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::Value;
+
+    use super::{apply_overrides, rejected_overrides, ConfigOverride, OverrideError, Segment};
+    use crate::config::Config;
+    #[cfg(feature = "gui")]
+    use crate::input::Action;
+    #[cfg(feature = "gui")]
+    use crate::term::color::Rgb;
+
+    fn config(yaml: &str, flags: &[&str]) -> (Config, Vec<OverrideError>) {
+        let tree: Value = serde_yaml::from_str(yaml).unwrap();
+        let overrides: Vec<ConfigOverride> =
+            flags.iter().map(|flag| flag.parse().unwrap()).collect();
+        let (config, rejected) = apply_overrides(tree, &overrides).unwrap();
+        (config, rejected.into_iter().map(|(_, err)| err).collect())
+    }
+
+    #[test]
+    fn parse_paths() {
+        let option: ConfigOverride = "font.size=14".parse().unwrap();
+        assert_eq!(option.path, vec![Segment::Key("font".into()), Segment::Key("size".into())]);
+        assert_eq!(option.value, Value::Number(14.into()));
+
+        let option: ConfigOverride = "key_bindings[2][0].mods=Control".parse().unwrap();
+        assert_eq!(option.path, vec![
+            Segment::Key("key_bindings".into()),
+            Segment::Index(2),
+            Segment::Index(0),
+            Segment::Key("mods".into()),
+        ]);
+    }
+
+    #[test]
+    fn parse_invalid_flags() {
+        assert_eq!("font.size".parse::<ConfigOverride>(), Err(OverrideError::MissingValue));
+        assert_eq!("font..size=1".parse::<ConfigOverride>(), Err(OverrideError::InvalidPath));
+        assert_eq!("=1".parse::<ConfigOverride>(), Err(OverrideError::InvalidPath));
+        assert_eq!("list[x]=1".parse::<ConfigOverride>(), Err(OverrideError::InvalidPath));
+        assert_eq!("list[0=1".parse::<ConfigOverride>(), Err(OverrideError::InvalidPath));
+        assert!("font.size=[".parse::<ConfigOverride>().is_err());
+    }
+
+    #[test]
+    fn override_scalar() {
+        let (config, rejected) = config("key: val", &["tabspaces=4", "live_config_reload=false"]);
+        assert!(rejected.is_empty());
+        assert_eq!(config.tabspaces(), 4);
+        assert!(!config.live_config_reload());
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn override_nested() {
+        let yaml = "font:\n  size: 11.0\n";
+        let flags = ["font.size=14", "colors.primary.background=#123456"];
+        let (config, rejected) = config(yaml, &flags);
+
+        assert!(rejected.is_empty());
+        assert_eq!(config.font.size.as_f32_pts(), 14.);
+        assert_eq!(config.colors.primary.background, Rgb { r: 0x12, g: 0x34, b: 0x56 });
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn override_list_entry() {
+        let yaml = "key_bindings:\n  - { key: F1, action: Copy }\n  - { key: F2, action: Copy }\n";
+        let (config, rejected) = config(yaml, &["key_bindings[1].action=Paste"]);
+
+        assert!(rejected.is_empty());
+        assert_eq!(config.key_bindings[0].action, Action::Copy);
+        assert_eq!(config.key_bindings[1].action, Action::Paste);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn override_same_value() {
+        let (_, rejected) = config("font:\n  size: 14.0\n", &["font.size=14"]);
+        assert!(rejected.is_empty());
+    }
+
+    #[test]
+    fn reject_unknown_options() {
+        let (_, rejected) = config("key: val", &["font.sise=14", "foo=bar"]);
+        assert_eq!(rejected, vec![
+            OverrideError::UnknownOption("font.sise".into()),
+            OverrideError::UnknownOption("foo".into()),
+        ]);
+    }
+
+    #[test]
+    fn rejected_overrides_only() {
+        let tree: Value = serde_yaml::from_str("key: val").unwrap();
+        let overrides: Vec<ConfigOverride> =
+            ["scrolling.histroy=10", "scrolling.history=10"].iter().map(|flag| flag.parse().unwrap()).collect();
+
+        let rejected = rejected_overrides(tree, &overrides).unwrap();
+        assert_eq!(rejected, vec![(
+            &overrides[0],
+            OverrideError::UnknownOption("scrolling.histroy".into())
+        )]);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn reject_type_mismatch() {
+        let (config, rejected) = config("font:\n  size: 13.0\n", &["font.size=big"]);
+
+        assert_eq!(rejected.len(), 1);
+        assert!(match &rejected[0] {
+            OverrideError::InvalidValue(_) => true,
+            _ => false,
+        });
+        assert_eq!(config.font.size.as_f32_pts(), 13.);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn reject_invalid_structure() {
+        let yaml = "font:\n  size: 12.0\nkey_bindings:\n  - { key: F1, action: Copy }\n";
+        let flags = ["font.size.x=1", "font[0]=1", "key_bindings[3].action=Paste"];
+        let (_, rejected) = config(yaml, &flags);
+
+        assert_eq!(rejected, vec![
+            OverrideError::NotASection("font.size".into()),
+            OverrideError::NotAList("font".into()),
+            OverrideError::IndexOutOfBounds("key_bindings[3]".into(), 1),
+        ]);
+    }
+}
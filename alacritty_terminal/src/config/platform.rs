@@ -0,0 +1,167 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Config sections which only apply to one platform
+use std::env::consts::OS;
+
+use serde_yaml::Value;
+
+/// Top-level key of the platform sections
+pub(crate) const PLATFORM_KEY: &str = "platform";
+
+/// Merge the section of the current platform into the rest of the config
+///
+/// The sections are keyed by the name of the operating system, like `linux`, `macos` or
+/// `windows`. Sections of other platforms are dropped, so their options are never validated.
+pub fn merge_platform(tree: Value) -> Value {
+    merge_platform_for(tree, OS)
+}
+
+/// Merge the platform section named `platform` into the rest of the config
+fn merge_platform_for(tree: Value, platform: &str) -> Value {
+    let mut mapping = match tree {
+        Value::Mapping(mapping) => mapping,
+        tree => return tree,
+    };
+
+    let platforms = mapping.remove(&Value::String(PLATFORM_KEY.into()));
+    let mut tree = Value::Mapping(mapping);
+
+    match platforms {
+        Some(Value::Mapping(mut platforms)) => {
+            if let Some(section) = platforms.remove(&Value::String(platform.into())) {
+                merge(&mut tree, section);
+            }
+        },
+        Some(Value::Null) | None => (),
+        Some(_) => error!("Problem with config: {} is not a section", PLATFORM_KEY),
+    }
+
+    tree
+}
+
+/// Recursively merge `overlay` into `base`
+///
+/// Mappings are merged key by key, all other values including lists are replaced.
+pub(crate) fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    },
+                }
+            }
+        },
+        (base, overlay) => *base = overlay,
+    }
+}
+
+// The tests merge font sections, which only exist with the window
+#[cfg(all(test, feature = "gui"))]
+mod tests {
+    use serde_yaml::Value;
+
+    use super::{merge_platform, merge_platform_for};
+    use crate::config::Config;
+
+    const CONFIG: &str = "
+font:
+  size: 10.0
+  offset:
+    x: 1
+window:
+  decorations: full
+platform:
+  macos:
+    font:
+      size: 14.0
+    window:
+      decorations: buttonless
+  linux:
+    font:
+      offset:
+        y: 2
+  windows:
+    shell:
+      program: wsl
+  plan9:
+    unknown_field: true
+";
+
+    fn config_for(platform: &str) -> Config {
+        let tree: Value = serde_yaml::from_str(CONFIG).unwrap();
+        Config::from_value(merge_platform_for(tree, platform)).unwrap()
+    }
+
+    #[test]
+    fn platform_section_overrides_base() {
+        let config = config_for("macos");
+        assert_eq!(config.font.size.as_f32_pts(), 14.0);
+        assert_eq!(config.font.offset.x, 1);
+        assert_eq!(config.window.decorations, crate::config::Decorations::Buttonless);
+    }
+
+    #[test]
+    fn platform_section_merges_nested_sections() {
+        let config = config_for("linux");
+        assert_eq!(config.font.size.as_f32_pts(), 10.0);
+        assert_eq!(config.font.offset.x, 1);
+        assert_eq!(config.font.offset.y, 2);
+        assert_eq!(config.window.decorations, crate::config::Decorations::Full);
+    }
+
+    #[test]
+    fn other_platforms_are_ignored() {
+        let config = config_for("freebsd");
+        assert_eq!(config.font.size.as_f32_pts(), 10.0);
+        assert_eq!(config.font.offset.y, 0);
+        assert!(config.shell.is_none());
+    }
+
+    #[test]
+    fn platform_key_is_removed() {
+        let tree: Value = serde_yaml::from_str(CONFIG).unwrap();
+        let merged = merge_platform_for(tree, "plan9");
+        assert!(merged.get("platform").is_none());
+        assert_eq!(merged["unknown_field"], Value::Bool(true));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn current_platform_is_macos() {
+        let tree: Value = serde_yaml::from_str(CONFIG).unwrap();
+        let config = Config::from_value(merge_platform(tree)).unwrap();
+        assert_eq!(config.font.size.as_f32_pts(), 14.0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn current_platform_is_linux() {
+        let tree: Value = serde_yaml::from_str(CONFIG).unwrap();
+        let config = Config::from_value(merge_platform(tree)).unwrap();
+        assert_eq!(config.font.offset.y, 2);
+        assert!(config.shell.is_none());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn current_platform_is_windows() {
+        let tree: Value = serde_yaml::from_str(CONFIG).unwrap();
+        let config = Config::from_value(merge_platform(tree)).unwrap();
+        assert_eq!(config.shell.unwrap().program, "wsl");
+    }
+}
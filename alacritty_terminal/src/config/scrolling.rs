@@ -9,11 +9,15 @@ pub struct Scrolling {
     #[serde(deserialize_with = "failure_default")]
     history: ScrollingHistory,
     #[serde(deserialize_with = "failure_default")]
+    max_memory: u32,
+    #[serde(deserialize_with = "failure_default")]
     multiplier: ScrollingMultiplier,
     #[serde(deserialize_with = "failure_default")]
     faux_multiplier: ScrollingMultiplier,
     #[serde(deserialize_with = "failure_default")]
     pub auto_scroll: bool,
+    #[serde(deserialize_with = "failure_default")]
+    pub status_escape: bool,
 }
 
 impl Scrolling {
@@ -21,6 +25,15 @@ impl Scrolling {
         self.history.0
     }
 
+    /// Maximum memory used by the scrollback history in megabytes
+    pub fn max_memory(self) -> Option<u32> {
+        if self.max_memory == 0 {
+            None
+        } else {
+            Some(self.max_memory)
+        }
+    }
+
     pub fn multiplier(self) -> u8 {
         self.multiplier.0
     }
@@ -62,10 +75,11 @@ impl<'de> Deserialize<'de> for ScrollingHistory {
         match u32::deserialize(value) {
             Ok(lines) => {
                 if lines > MAX_SCROLLBACK_LINES {
-                    error!(
+                    config_error!(
                         "Problem with config: scrollback size is {}, but expected a maximum of \
                          {}; using {1} instead",
-                        lines, MAX_SCROLLBACK_LINES,
+                        lines,
+                        MAX_SCROLLBACK_LINES,
                     );
                     Ok(ScrollingHistory(MAX_SCROLLBACK_LINES))
                 } else {
@@ -73,7 +87,7 @@ impl<'de> Deserialize<'de> for ScrollingHistory {
                 }
             },
             Err(err) => {
-                error!("Problem with config: {}; using default value", err);
+                config_error!("Problem with config: {}; using default value", err);
                 Ok(Default::default())
             },
         }
@@ -0,0 +1,73 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::ansi::StringTerminator;
+use crate::config::failure_default;
+
+/// How output of applications is interpreted
+#[serde(default)]
+#[derive(Deserialize, Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct TerminalConfig {
+    /// Columns occupied by East Asian ambiguous width characters
+    #[serde(deserialize_with = "failure_default")]
+    pub ambiguous_width: AmbiguousWidth,
+
+    /// Terminator of replies to OSC queries
+    #[serde(deserialize_with = "failure_default")]
+    pub reply_terminator: ReplyTerminator,
+}
+
+/// Width of characters like `§` and `±`, which depends on the locale of the application
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AmbiguousWidth {
+    #[serde(rename = "single")]
+    Single,
+    #[serde(rename = "double")]
+    Double,
+}
+
+impl Default for AmbiguousWidth {
+    fn default() -> AmbiguousWidth {
+        AmbiguousWidth::Single
+    }
+}
+
+impl AmbiguousWidth {
+    /// Number of columns occupied by a character, `None` for control characters
+    #[inline]
+    pub fn char_width(self, c: char) -> Option<usize> {
+        match self {
+            AmbiguousWidth::Single => c.width(),
+            AmbiguousWidth::Double => c.width_cjk(),
+        }
+    }
+}
+
+/// Terminator used for replies, which some applications only accept in one style
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReplyTerminator {
+    /// Use the terminator of the query
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "bel")]
+    Bel,
+    #[serde(rename = "st")]
+    St,
+}
+
+impl Default for ReplyTerminator {
+    fn default() -> ReplyTerminator {
+        ReplyTerminator::Auto
+    }
+}
+
+impl ReplyTerminator {
+    /// Terminator of the reply to a query which was terminated by `query`
+    #[inline]
+    pub fn resolve(self, query: StringTerminator) -> StringTerminator {
+        match self {
+            ReplyTerminator::Auto => query,
+            ReplyTerminator::Bel => StringTerminator::Bel,
+            ReplyTerminator::St => StringTerminator::St,
+        }
+    }
+}
@@ -1,6 +1,19 @@
-use crate::config::{Config, DEFAULT_ALACRITTY_CONFIG};
+use std::time::Duration;
+
+#[cfg(feature = "gui")]
+use font::Size;
+#[cfg(feature = "gui")]
+use glutin::ModifiersState;
+
+use crate::config::{AmbiguousWidth, Config, Dimensions, Padding, RendererApi, ReplyTerminator};
+#[cfg(feature = "gui")]
+use crate::config::{Key, DEFAULT_ALACRITTY_CONFIG};
+use crate::index::{Column, Line};
+#[cfg(feature = "gui")]
+use crate::input::{KeyBinding, KeyChord};
 
 #[test]
+#[cfg(feature = "gui")]
 fn parse_config() {
     let config: Config =
         ::serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).expect("deserialize config");
@@ -20,3 +33,183 @@ fn default_match_empty() {
 
     assert_eq!(default, empty);
 }
+
+#[test]
+fn padding_shorthand() {
+    let config: Config = serde_yaml::from_str("window:\n  padding:\n    x: 2\n    y: 3\n").unwrap();
+
+    assert_eq!(config.window.padding, Padding::symmetric(2, 3));
+}
+
+#[test]
+fn padding_per_side() {
+    let yaml = "window:\n  padding:\n    x: 2\n    y: 3\n    top: 20\n    left: 1\n";
+    let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+    assert_eq!(config.window.padding, Padding { left: 1, right: 2, top: 20, bottom: 3 });
+}
+
+#[test]
+fn window_dimensions_precedence() {
+    let yaml = "window:\n  dimensions:\n    columns: 80\n    lines: 24\n  remember_size: true\n";
+    let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(config.window.dimensions(), Dimensions::new(Column(80), Line(24)));
+
+    config.window.remembered_dimensions = Some(Dimensions::new(Column(120), Line(40)));
+    assert_eq!(config.window.dimensions(), Dimensions::new(Column(120), Line(40)));
+
+    config.window.cli_dimensions = Some(Dimensions::new(Column(100), Line(30)));
+    assert_eq!(config.window.dimensions(), Dimensions::new(Column(100), Line(30)));
+
+    // The remembered size is ignored when it's disabled
+    config.window.remember_size = false;
+    config.window.cli_dimensions = None;
+    assert_eq!(config.window.dimensions(), Dimensions::new(Column(80), Line(24)));
+}
+
+#[test]
+fn renderer_override() {
+    let config: Config = serde_yaml::from_str("debug:\n  renderer: Gles3\n").unwrap();
+    assert_eq!(config.debug.renderer, Some(RendererApi::Gles3));
+
+    let config: Config = serde_yaml::from_str("debug:\n  renderer: None\n").unwrap();
+    assert_eq!(config.debug.renderer, None);
+}
+
+#[test]
+fn ambiguous_width() {
+    let config: Config = serde_yaml::from_str("terminal:\n  ambiguous_width: double\n").unwrap();
+    assert_eq!(config.terminal.ambiguous_width, AmbiguousWidth::Double);
+
+    let config: Config = serde_yaml::from_str("terminal:\n  ambiguous_width: wide\n").unwrap();
+    assert_eq!(config.terminal.ambiguous_width, AmbiguousWidth::Single);
+}
+
+#[test]
+fn reply_terminator() {
+    let config: Config = serde_yaml::from_str("terminal:\n  reply_terminator: st\n").unwrap();
+    assert_eq!(config.terminal.reply_terminator, ReplyTerminator::St);
+
+    let config: Config = serde_yaml::from_str("terminal:\n  ambiguous_width: double\n").unwrap();
+    assert_eq!(config.terminal.reply_terminator, ReplyTerminator::Auto);
+}
+
+#[test]
+#[cfg(feature = "gui")]
+fn key_chord_binding() {
+    let yaml = "key_bindings:\n  - { keys: [{ key: A, mods: Control }, { key: V }], chars: x }\n";
+    let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+    let binding = &config.key_bindings[0];
+    assert_eq!(binding.trigger, Key::V);
+    assert_eq!(binding.mods, ModifiersState::default());
+    assert_eq!(binding.prefix, vec![KeyChord {
+        key: Key::A,
+        mods: ModifiersState { ctrl: true, ..Default::default() }
+    }]);
+}
+
+#[test]
+#[cfg(feature = "gui")]
+fn key_chord_binding_rejects_key() {
+    let yaml = "{ keys: [{ key: A }, { key: V }], key: B, chars: x }";
+    assert!(serde_yaml::from_str::<KeyBinding>(yaml).is_err());
+    assert!(serde_yaml::from_str::<KeyBinding>("{ keys: [], chars: x }").is_err());
+}
+
+#[test]
+#[cfg(feature = "gui")]
+fn font_size_step() {
+    let config: Config = serde_yaml::from_str("font:\n  size_step: 2\n").unwrap();
+    assert_eq!(config.font.size_step(), Size::new(2.));
+
+    // Steps which are rounded to zero fall back to the default
+    let config: Config = serde_yaml::from_str("font:\n  size_step: 0.1\n").unwrap();
+    assert_eq!(config.font.size_step(), Size::new(0.5));
+}
+
+#[test]
+#[cfg(feature = "gui")]
+fn font_with_family() {
+    let yaml = "font:\n  bold:\n    family: Bold Family\n    style: Heavy\n  families: [A, B]\n";
+    let config: Config = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(config.font.families(), &["A".to_owned(), "B".to_owned()][..]);
+
+    // The family replaces the families of all faces, the styles are kept
+    let font = config.font.clone().with_family("B");
+    assert_eq!(font.normal().family, "B");
+    assert_eq!(font.bold().family, "B");
+    assert_eq!(font.bold().style, Some("Heavy".into()));
+    assert_eq!(font.italic().family, "B");
+}
+
+#[test]
+#[cfg(feature = "gui")]
+fn mouse_hide_delay() {
+    let config: Config = serde_yaml::from_str("mouse:\n  hide_delay: 3\n").unwrap();
+    assert_eq!(config.mouse.hide_delay(), Some(Duration::from_secs(3)));
+
+    let config: Config = serde_yaml::from_str("mouse:\n  hide_delay: 0\n").unwrap();
+    assert_eq!(config.mouse.hide_delay(), None);
+}
+
+#[test]
+fn exit_status_delay() {
+    let config: Config = serde_yaml::from_str("exit_status_delay: 500\n").unwrap();
+    assert_eq!(config.exit_status_delay(), Duration::from_millis(500));
+
+    let config: Config = serde_yaml::from_str("exit_status_delay: -1\n").unwrap();
+    assert_eq!(config.exit_status_delay(), Duration::from_millis(3000));
+}
+
+#[test]
+fn pty_read_buffer_size() {
+    assert_eq!(Config::default().pty_read_buffer_size(), 0x1_0000);
+
+    let config: Config = serde_yaml::from_str("pty_read_buffer_size: 1048576\n").unwrap();
+    assert_eq!(config.pty_read_buffer_size(), 0x10_0000);
+
+    // Tiny buffers would need a read for every few bytes
+    let config: Config = serde_yaml::from_str("pty_read_buffer_size: 16\n").unwrap();
+    assert_eq!(config.pty_read_buffer_size(), 0x1000);
+}
+
+#[test]
+fn scrolling_max_memory() {
+    assert_eq!(Config::default().scrolling.max_memory(), None);
+
+    let config: Config = serde_yaml::from_str("scrolling:\n  max_memory: 64\n").unwrap();
+    assert_eq!(config.scrolling.max_memory(), Some(64));
+
+    let config: Config = serde_yaml::from_str("scrolling:\n  max_memory: 0\n").unwrap();
+    assert_eq!(config.scrolling.max_memory(), None);
+}
+
+#[test]
+fn max_clipboard_escape_size() {
+    assert_eq!(Config::default().selection.max_clipboard_escape_size(), 1024 * 1024);
+
+    let config: Config =
+        serde_yaml::from_str("selection:\n  max_clipboard_escape_size: 64\n").unwrap();
+    assert_eq!(config.selection.max_clipboard_escape_size(), 64 * 1024);
+}
+
+#[test]
+fn lossy_clipboard_escape() {
+    assert!(!Config::default().selection.lossy_clipboard_escape);
+
+    let config: Config =
+        serde_yaml::from_str("selection:\n  lossy_clipboard_escape: true\n").unwrap();
+    assert!(config.selection.lossy_clipboard_escape);
+}
+
+#[test]
+fn dim_factor() {
+    assert_eq!(Config::default().colors.dim_factor(), 0.66);
+
+    let config: Config = serde_yaml::from_str("colors:\n  dim_factor: 0.5\n").unwrap();
+    assert_eq!(config.colors.dim_factor(), 0.5);
+
+    let config: Config = serde_yaml::from_str("colors:\n  dim_factor: 2.0\n").unwrap();
+    assert_eq!(config.colors.dim_factor(), 1.0);
+}
@@ -0,0 +1,282 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detection of config fields which don't belong to any option
+use std::cell::RefCell;
+use std::vec;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+thread_local! {
+    /// Unknown fields of the current thread, only collected while `collect_unknown_fields` runs
+    static UNKNOWN_FIELDS: RefCell<Option<Vec<String>>> = RefCell::new(None);
+
+    /// Keys leading to the field which is currently deserialized
+    static PATH: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
+/// Window options of the shared config file, which are ignored without the `gui` feature
+#[cfg(not(feature = "gui"))]
+const WINDOW_FIELDS: &[&str] = &["font", "key_bindings", "key_chords", "mouse_bindings", "mouse"];
+
+/// Run `f` and return the paths of all fields which were ignored while deserializing the config
+pub fn collect_unknown_fields<T, F>(f: F) -> (T, Vec<String>)
+where
+    F: FnOnce() -> T,
+{
+    let previous = UNKNOWN_FIELDS.with(|fields| fields.replace(Some(Vec::new())));
+    let result = f();
+    let collected = UNKNOWN_FIELDS.with(|fields| fields.replace(previous));
+    (result, collected.unwrap_or_default())
+}
+
+/// Deserialize the value of the field `name` with unknown fields reported below its path
+pub(crate) fn deserialize_field<'de, T>(name: &str, value: Value) -> Result<T, serde_yaml::Error>
+where
+    T: Deserialize<'de>,
+{
+    PATH.with(|path| path.borrow_mut().push(name.to_owned()));
+    let result = T::deserialize(CheckedValue(value));
+    PATH.with(|path| path.borrow_mut().pop());
+    result
+}
+
+/// Record all keys of `mapping` which are not in `fields`
+fn report_unknown(mapping: &Mapping, fields: &[&str]) {
+    UNKNOWN_FIELDS.with(|unknown| {
+        let mut unknown = unknown.borrow_mut();
+        let unknown = match unknown.as_mut() {
+            Some(unknown) => unknown,
+            None => return,
+        };
+
+        for (key, _) in mapping.iter() {
+            match key {
+                Value::String(key) if !fields.contains(&key.as_str()) && !is_window_field(key) => {
+                    let mut path = PATH.with(|path| path.borrow().join("."));
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                    unknown.push(path);
+                },
+                _ => (),
+            }
+        }
+    });
+}
+
+/// Check if `key` is a top-level window option that isn't part of the config without the window
+#[cfg(not(feature = "gui"))]
+fn is_window_field(key: &str) -> bool {
+    PATH.with(|path| path.borrow().is_empty()) && WINDOW_FIELDS.contains(&key)
+}
+
+#[cfg(feature = "gui")]
+fn is_window_field(_key: &str) -> bool {
+    false
+}
+
+/// YAML value which reports fields that are not part of the struct it is deserialized into
+pub(crate) struct CheckedValue(pub Value);
+
+macro_rules! forward_to_value {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.0.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for CheckedValue {
+    type Error = serde_yaml::Error;
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Mapping(mapping) => {
+                report_unknown(&mapping, fields);
+
+                let entries: Vec<_> = mapping.into_iter().collect();
+                visitor.visit_map(CheckedMap { entries: entries.into_iter(), value: None })
+            },
+            value => value.deserialize_struct(name, fields, visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_value! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+}
+
+/// Fields of a struct, with the path of each field tracked while its value is deserialized
+struct CheckedMap {
+    entries: vec::IntoIter<(Value, Value)>,
+    value: Option<(String, Value)>,
+}
+
+impl<'de> MapAccess<'de> for CheckedMap {
+    type Error = serde_yaml::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            Some((key, value)) => {
+                let name = match &key {
+                    Value::String(name) => name.clone(),
+                    _ => String::from("?"),
+                };
+                self.value = Some((name, value));
+                seed.deserialize(key).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (name, value) =
+            self.value.take().ok_or_else(|| de::Error::custom("value is missing"))?;
+
+        PATH.with(|path| path.borrow_mut().push(name));
+        let result = seed.deserialize(CheckedValue(value));
+        PATH.with(|path| path.borrow_mut().pop());
+
+        result
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::collect_unknown_fields;
+    use crate::config::Config;
+
+    fn unknown_fields(yaml: &str) -> Vec<String> {
+        let value = serde_yaml::from_str(yaml).unwrap();
+        let (config, unknown) = collect_unknown_fields(|| Config::from_value(value));
+        config.unwrap();
+        unknown
+    }
+
+    #[test]
+    fn default_config_has_no_unknown_fields() {
+        let value = serde_yaml::from_str(crate::config::DEFAULT_ALACRITTY_CONFIG).unwrap();
+        let (_, unknown) = collect_unknown_fields(|| Config::from_value(value));
+        assert_eq!(unknown, Vec::<String>::new());
+    }
+
+    #[test]
+    fn unknown_top_level_field() {
+        assert_eq!(unknown_fields("colour: true\n"), vec![String::from("colour")]);
+    }
+
+    #[test]
+    fn unknown_nested_fields() {
+        let yaml = "visual_bell:\n  duraton: 10\nwindow:\n  padding:\n    z: 3\n    x: 2\n";
+        assert_eq!(unknown_fields(yaml), vec![
+            String::from("visual_bell.duraton"),
+            String::from("window.padding.z")
+        ]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "gui"))]
+    fn window_fields_ignored_without_window() {
+        let yaml = "font:\n  size: 12.0\nmouse_bindings: []\ncursor:\n  font: 1\n";
+        assert_eq!(unknown_fields(yaml), vec![String::from("cursor.font")]);
+    }
+
+    #[test]
+    fn unknown_field_in_color_scheme() {
+        let yaml = "schemes:\n  dark:\n    primary:\n      foregroud: '0x000000'\n";
+        assert_eq!(unknown_fields(yaml), vec![String::from("schemes.dark.primary.foregroud")]);
+    }
+
+    #[test]
+    fn unknown_fields_are_not_collected_by_default() {
+        let value = serde_yaml::from_str("colour: true\n").unwrap();
+        Config::from_value(value).unwrap();
+
+        let (_, unknown) = collect_unknown_fields(|| ());
+        assert!(unknown.is_empty());
+    }
+}
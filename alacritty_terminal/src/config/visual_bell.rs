@@ -17,6 +17,10 @@ pub struct VisualBellConfig {
     /// Visual bell flash color
     #[serde(deserialize_with = "failure_default")]
     pub color: Rgb,
+
+    /// Part of the window which flashes
+    #[serde(deserialize_with = "failure_default")]
+    pub region: VisualBellRegion,
 }
 
 impl Default for VisualBellConfig {
@@ -25,6 +29,7 @@ impl Default for VisualBellConfig {
             animation: Default::default(),
             duration: Default::default(),
             color: default_visual_bell_color(),
+            region: Default::default(),
         }
     }
 }
@@ -59,6 +64,25 @@ impl Default for VisualBellAnimation {
     }
 }
 
+/// Part of the window covered by the visual bell flash
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum VisualBellRegion {
+    /// The entire window
+    Full,
+    /// Thin bar at the top of the terminal
+    TopBar,
+    /// Thin bar at the bottom of the terminal
+    BottomBar,
+    /// Small squares in the corners of the terminal
+    Corners,
+}
+
+impl Default for VisualBellRegion {
+    fn default() -> Self {
+        VisualBellRegion::Full
+    }
+}
+
 fn default_visual_bell_color() -> Rgb {
     Rgb { r: 255, g: 255, b: 255 }
 }
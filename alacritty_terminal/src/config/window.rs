@@ -1,32 +1,60 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Deserializer};
+
 use crate::config::{
-    failure_default, from_string_or_deserialize, option_explicit_none, Delta, FromString,
+    expand, failure_default, from_string_or_deserialize, option_explicit_none, Alpha, Delta,
+    FromString,
 };
 use crate::index::{Column, Line};
-use crate::window::DEFAULT_NAME;
+use crate::term::color::Rgb;
+
+/// Default Alacritty name, used for window title and class.
+pub const DEFAULT_NAME: &str = "Alacritty";
 
 #[serde(default)]
-#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct WindowConfig {
     /// Initial dimensions
     #[serde(deserialize_with = "failure_default")]
     pub dimensions: Dimensions,
 
+    /// Start with the size of the last closed window
+    #[serde(deserialize_with = "failure_default")]
+    pub remember_size: bool,
+
+    /// Dimensions passed on the command line, which override all others
+    #[serde(skip)]
+    pub cli_dimensions: Option<Dimensions>,
+
+    /// Size of the last closed window, only used with `remember_size`
+    #[serde(skip)]
+    pub remembered_dimensions: Option<Dimensions>,
+
     /// Initial position
     #[serde(deserialize_with = "failure_default")]
     pub position: Option<Delta<i32>>,
 
     /// Pixel padding
     #[serde(deserialize_with = "failure_default")]
-    pub padding: Delta<u8>,
+    pub padding: Padding,
 
     /// Draw the window with title bar / borders
     #[serde(deserialize_with = "failure_default")]
     pub decorations: Decorations,
 
+    /// Title bar color
+    #[serde(deserialize_with = "failure_default")]
+    pub titlebar_color: TitlebarColor,
+
     /// Spread out additional padding evenly
     #[serde(deserialize_with = "failure_default")]
     pub dynamic_padding: bool,
 
+    /// Fill the padding with the background applications draw at the edges of the screen
+    #[serde(deserialize_with = "failure_default")]
+    pub extend_background: bool,
+
     /// Startup mode
     #[serde(deserialize_with = "failure_default")]
     startup_mode: StartupMode,
@@ -43,18 +71,69 @@ pub struct WindowConfig {
     #[serde(deserialize_with = "option_explicit_none")]
     pub gtk_theme_variant: Option<String>,
 
-    /// TODO: DEPRECATED
+    /// Maximum number of frames drawn per second, `0` is unlimited
     #[serde(deserialize_with = "failure_default")]
-    pub start_maximized: Option<bool>,
+    pub max_fps: u16,
+
+    /// Vertical lines drawn at specific columns
+    #[serde(deserialize_with = "failure_default")]
+    pub rulers: Rulers,
+
+    /// Opacity of the entire window
+    #[serde(deserialize_with = "failure_default")]
+    opacity: Alpha,
+
+    /// Allow changing the window opacity with an escape sequence
+    #[serde(deserialize_with = "failure_default")]
+    pub opacity_escape: bool,
+
+    /// Option keys which are treated as Alt instead of composing characters
+    #[serde(deserialize_with = "failure_default")]
+    pub option_as_alt: OptionAsAlt,
+
+    /// Image used as window icon instead of the Alacritty logo
+    #[serde(deserialize_with = "expand::failure_default_expanded")]
+    pub icon: Option<PathBuf>,
+
+    /// Mark the window icon when a bell rings while the window is unfocused
+    #[serde(deserialize_with = "failure_default")]
+    pub bell_badge: bool,
+
+    /// X11 window which the window is embedded into, only set from the command line
+    #[serde(skip)]
+    pub embed: Option<u64>,
 }
 
 impl WindowConfig {
-    pub fn startup_mode(&self) -> StartupMode {
-        match self.start_maximized {
-            Some(true) => StartupMode::Maximized,
-            _ => self.startup_mode,
+    /// Initial size of the window
+    ///
+    /// Dimensions from the command line take precedence over the remembered size, which takes
+    /// precedence over the configured dimensions.
+    pub fn dimensions(&self) -> Dimensions {
+        let remembered = self.remembered_dimensions.filter(|_| self.remember_size);
+        self.cli_dimensions.or(remembered).unwrap_or(self.dimensions)
+    }
+
+    /// Decorations supported by the current platform
+    ///
+    /// The macOS title bar variants use full decorations everywhere else.
+    pub fn decorations(&self) -> Decorations {
+        match self.decorations {
+            Decorations::Transparent | Decorations::Buttonless if !cfg!(target_os = "macos") => {
+                Decorations::Full
+            },
+            decorations => decorations,
         }
     }
+
+    #[inline]
+    pub fn opacity(&self) -> f32 {
+        self.opacity.0
+    }
+
+    pub fn startup_mode(&self) -> StartupMode {
+        self.startup_mode
+    }
 }
 
 #[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
@@ -72,14 +151,41 @@ impl Default for StartupMode {
     }
 }
 
+/// Option keys which send Alt sequences on macOS
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum OptionAsAlt {
+    None,
+    OnlyLeft,
+    OnlyRight,
+    Both,
+}
+
+impl OptionAsAlt {
+    /// Check if one of the held Option keys should act as Alt
+    pub fn matches(self, left: bool, right: bool) -> bool {
+        match self {
+            OptionAsAlt::None => false,
+            OptionAsAlt::OnlyLeft => left,
+            OptionAsAlt::OnlyRight => right,
+            OptionAsAlt::Both => left || right,
+        }
+    }
+}
+
+impl Default for OptionAsAlt {
+    fn default() -> OptionAsAlt {
+        OptionAsAlt::None
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
 pub enum Decorations {
     #[serde(rename = "full")]
     Full,
-    #[cfg(target_os = "macos")]
+    /// Transparent title bar drawn over the content (macOS only)
     #[serde(rename = "transparent")]
     Transparent,
-    #[cfg(target_os = "macos")]
+    /// Transparent title bar without buttons (macOS only)
     #[serde(rename = "buttonless")]
     Buttonless,
     #[serde(rename = "none")]
@@ -92,6 +198,23 @@ impl Default for Decorations {
     }
 }
 
+/// Color of the window title bar (macOS only)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum TitlebarColor {
+    /// Title bar color of the system theme
+    #[serde(rename = "system")]
+    System,
+    /// Follow the terminal background color, including changes by escape sequences
+    #[serde(rename = "background")]
+    Background,
+}
+
+impl Default for TitlebarColor {
+    fn default() -> TitlebarColor {
+        TitlebarColor::System
+    }
+}
+
 /// Window Dimensions
 ///
 /// Newtype to avoid passing values incorrectly
@@ -125,6 +248,58 @@ impl Dimensions {
     }
 }
 
+/// Window padding in pixels for each edge
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Padding {
+    pub left: u8,
+    pub right: u8,
+    pub top: u8,
+    pub bottom: u8,
+}
+
+impl Padding {
+    /// Same padding for opposing edges
+    pub fn symmetric(x: u8, y: u8) -> Self {
+        Padding { left: x, right: x, top: y, bottom: y }
+    }
+}
+
+impl<'de> Deserialize<'de> for Padding {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        /// Padding with `x` and `y` as shorthand for both opposing edges
+        #[serde(default)]
+        #[derive(Deserialize, Default)]
+        struct RawPadding {
+            #[serde(deserialize_with = "failure_default")]
+            x: Option<u8>,
+            #[serde(deserialize_with = "failure_default")]
+            y: Option<u8>,
+            #[serde(deserialize_with = "failure_default")]
+            left: Option<u8>,
+            #[serde(deserialize_with = "failure_default")]
+            right: Option<u8>,
+            #[serde(deserialize_with = "failure_default")]
+            top: Option<u8>,
+            #[serde(deserialize_with = "failure_default")]
+            bottom: Option<u8>,
+        }
+
+        let raw = RawPadding::deserialize(deserializer)?;
+        let x = raw.x.unwrap_or_default();
+        let y = raw.y.unwrap_or_default();
+
+        Ok(Padding {
+            left: raw.left.unwrap_or(x),
+            right: raw.right.unwrap_or(x),
+            top: raw.top.unwrap_or(y),
+            bottom: raw.bottom.unwrap_or(y),
+        })
+    }
+}
+
 /// Window class hint
 #[serde(default)]
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -144,3 +319,61 @@ impl FromString for Class {
         Class { instance: value, general: DEFAULT_NAME.into() }
     }
 }
+
+/// Column ruler configuration
+#[serde(default)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Rulers {
+    /// Columns which are followed by a ruler
+    #[serde(deserialize_with = "failure_default")]
+    pub columns: Vec<usize>,
+
+    /// Ruler width in physical pixels
+    #[serde(deserialize_with = "failure_default")]
+    thickness: RulerThickness,
+
+    /// Ruler color
+    #[serde(deserialize_with = "failure_default")]
+    pub color: Rgb,
+
+    /// Ruler opacity from 0.0 to 1.0
+    #[serde(deserialize_with = "failure_default")]
+    opacity: Alpha,
+
+    /// Draw rulers while the alternate screen is active
+    #[serde(deserialize_with = "failure_default")]
+    pub alt_screen: bool,
+}
+
+impl Default for Rulers {
+    fn default() -> Self {
+        Rulers {
+            columns: Vec::new(),
+            thickness: RulerThickness::default(),
+            color: Rgb { r: 0x80, g: 0x80, b: 0x80 },
+            opacity: Alpha::new(0.2),
+            alt_screen: false,
+        }
+    }
+}
+
+impl Rulers {
+    #[inline]
+    pub fn thickness(&self) -> u8 {
+        self.thickness.0
+    }
+
+    #[inline]
+    pub fn opacity(&self) -> f32 {
+        self.opacity.0
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+struct RulerThickness(u8);
+
+impl Default for RulerThickness {
+    fn default() -> Self {
+        RulerThickness(1)
+    }
+}
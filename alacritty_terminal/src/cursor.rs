@@ -23,13 +23,6 @@ use crate::ansi::CursorStyle;
 /// Width/Height of the cursor relative to the font width
 pub const CURSOR_WIDTH_PERCENTAGE: i32 = 15;
 
-/// A key for caching cursor glyphs
-#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash, Deserialize)]
-pub struct CursorKey {
-    pub style: CursorStyle,
-    pub is_wide: bool,
-}
-
 pub fn get_cursor_glyph(
     cursor: CursorStyle,
     metrics: Metrics,
@@ -104,3 +97,51 @@ pub fn get_block_cursor_glyph(height: i32, width: i32) -> RasterizedGlyph {
     // Create a custom glyph with the rectangle data attached to it
     RasterizedGlyph { c: ' ', top: height, left: 0, height, width, buf }
 }
+
+#[cfg(test)]
+mod tests {
+    use font::Metrics;
+
+    use super::get_cursor_glyph;
+    use crate::ansi::CursorStyle;
+
+    /// Position and size of a cursor glyph for a cell of 7x16 pixels
+    fn geometry(style: CursorStyle, is_wide: bool) -> (i32, i32, i32, i32) {
+        let metrics = Metrics {
+            average_advance: 7.,
+            line_height: 16.,
+            descent: -4.,
+            underline_position: -2.,
+            underline_thickness: 1.,
+            strikeout_position: 4.,
+            strikeout_thickness: 1.,
+        };
+
+        let glyph = get_cursor_glyph(style, metrics, 0, 0, is_wide);
+        (glyph.left, glyph.top, glyph.width, glyph.height)
+    }
+
+    #[test]
+    fn block_spans_wide_char() {
+        assert_eq!(geometry(CursorStyle::Block, false), (0, 16, 7, 16));
+        assert_eq!(geometry(CursorStyle::Block, true), (0, 16, 14, 16));
+    }
+
+    #[test]
+    fn hollow_block_spans_wide_char() {
+        assert_eq!(geometry(CursorStyle::HollowBlock, false), (0, 16, 7, 16));
+        assert_eq!(geometry(CursorStyle::HollowBlock, true), (0, 16, 14, 16));
+    }
+
+    #[test]
+    fn underline_spans_wide_char() {
+        assert_eq!(geometry(CursorStyle::Underline, false), (0, 1, 7, 1));
+        assert_eq!(geometry(CursorStyle::Underline, true), (0, 1, 14, 1));
+    }
+
+    #[test]
+    fn beam_stays_at_leading_edge() {
+        assert_eq!(geometry(CursorStyle::Beam, false), (0, 16, 1, 16));
+        assert_eq!(geometry(CursorStyle::Beam, true), (0, 16, 1, 16));
+    }
+}
@@ -14,24 +14,40 @@
 
 //! The display subsystem including window management, font rasterization, and
 //! GPU drawing.
+use std::cmp;
 use std::f64;
 #[cfg(not(any(target_os = "macos", target_os = "windows")))]
 use std::ffi::c_void;
+use std::mem;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
 use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
-use glutin::dpi::{PhysicalPosition, PhysicalSize};
-use glutin::EventsLoop;
+use crossbeam_channel::Sender;
+use glutin::dpi::{LogicalPosition, PhysicalPosition, PhysicalSize};
+use glutin::{EventsLoop, WindowId};
 use parking_lot::MutexGuard;
 
-use crate::config::{Config, StartupMode};
-use crate::index::Line;
+use crate::ansi::{CursorStyle, MouseCursorIcon, UnhandledStats};
+use crate::config::{
+    Config, ConfigChanges, Decorations, OnConfigReload, RenderTimerPosition, StartupMode,
+    TitlebarColor,
+};
+use crate::event_loop::OnWakeup;
+use crate::index::{Column, Line, Linear, Point};
 use crate::message_bar::Message;
-use crate::meter::Meter;
+use crate::meter::{Meter, Rate, Statistics};
+use crate::renderer::glyph_worker::GlyphWorker;
 use crate::renderer::rects::{RenderRect, RenderLines};
 use crate::renderer::{self, GlyphCache, QuadRenderer};
+use crate::screenshot::Screenshot;
+use crate::selection::Selection;
 use crate::sync::FairMutex;
-use crate::term::color::Rgb;
-use crate::term::{RenderableCell, SizeInfo, Term};
+use crate::term::color::{self, Rgb};
+use crate::term::mode::TermMode;
+use crate::term::{OpacityChange, RenderableCell, SizeInfo, Term};
+use crate::tty::OnResize;
 use crate::window::{self, Window};
 use font::{self, Rasterize};
 
@@ -99,20 +115,241 @@ pub struct Display {
     renderer: QuadRenderer,
     glyph_cache: GlyphCache,
     render_timer: bool,
-    rx: mpsc::Receiver<PhysicalSize>,
-    tx: mpsc::Sender<PhysicalSize>,
+    draw_times: Option<Duration>,
+    last_draw_times: Instant,
+    resize_queue: ResizeQueue,
     meter: Meter,
+
+    /// Time the terminal lock is held for each frame
+    lock_meter: Meter,
+
+    /// Time from a keypress or pty read to the frame presenting it
+    latency_meter: Meter,
+
+    /// Number of times the event loop has processed this window
+    wakeups: Rate,
+
+    /// Buffer for the renderable cells, reused to avoid allocations while the terminal is locked
+    grid_cells: Vec<RenderableCell>,
+
+    /// Frame which is currently on screen
+    last_frame: LastFrame,
+
     font_size: font::Size,
+
+    /// Font family used instead of the configured one, switched with the `CycleFont` action
+    font_family: Option<String>,
+
+    /// Reloaded config changed the fonts, the glyph cache has to be rebuilt on the next resize
+    font_reloaded: bool,
+
+    /// Reloaded config changed the padding, the grid has to be resized
+    padding_reloaded: bool,
+
     size_info: SizeInfo,
     last_message: Option<Message>,
+    frame_timer: FrameTimer,
+    pty_resize: PtyResize,
+    ime_spot: ImeSpot,
+
+    /// Reduce rendering costs, since OpenGL is rendered on the CPU
+    software_gl: bool,
+}
+
+/// Frame rate limit while OpenGL is rendered on the CPU
+const SOFTWARE_GL_MAX_FPS: u16 = 30;
+
+/// Logical height of the macOS title bar
+const MACOS_TITLEBAR_HEIGHT: f32 = 22.;
+
+/// Logical height of the title bar drawn over the window content
+///
+/// Only the transparent macOS title bar overlaps the content while its buttons
+/// are visible, so the first line has to be moved below it.
+fn titlebar_overlap(config: &Config) -> f32 {
+    match config.window.decorations() {
+        Decorations::Transparent => MACOS_TITLEBAR_HEIGHT,
+        _ => 0.,
+    }
+}
+
+/// Frame rate limit, which is capped when OpenGL is rendered on the CPU
+fn max_fps(config: &Config, software_gl: bool) -> u16 {
+    match config.window.max_fps {
+        max_fps if !software_gl => max_fps,
+        0 => SOFTWARE_GL_MAX_FPS,
+        max_fps => max_fps.min(SOFTWARE_GL_MAX_FPS),
+    }
+}
+
+/// Level used to report software rendering, only warnings are shown in the message bar
+fn software_gl_log_level(config: &Config) -> log::Level {
+    if config.debug.allow_software_gl {
+        log::Level::Info
+    } else {
+        log::Level::Warn
+    }
+}
+
+/// Last position of the input method editor
+#[derive(Debug, Default)]
+struct ImeSpot {
+    last: Option<(PhysicalPosition, f64)>,
+}
+
+impl ImeSpot {
+    /// Get the new editor position, if it has changed since the last update
+    fn update(&mut self, position: PhysicalPosition, dpr: f64) -> Option<LogicalPosition> {
+        if self.last == Some((position, dpr)) {
+            return None;
+        }
+
+        self.last = Some((position, dpr));
+        Some(position.to_logical(dpr))
+    }
+}
+
+/// Size changes of the window which haven't been applied to the display yet
+///
+/// The event processor stops handling events after every resize, so usually only a single size
+/// is queued here before the next draw call. This keeps the grid and viewport in sync with the
+/// window while it is being resized interactively.
+///
+/// On macOS the event loop is blocked until the window is done resizing, so only the final size
+/// is drawn there. Drawing during a resize would have to happen in the resize callback.
+#[derive(Debug)]
+struct ResizeQueue {
+    rx: mpsc::Receiver<DisplayUpdate>,
+    tx: mpsc::Sender<DisplayUpdate>,
+}
+
+impl ResizeQueue {
+    fn new() -> ResizeQueue {
+        let (tx, rx) = mpsc::channel();
+        ResizeQueue { rx, tx }
+    }
+
+    #[inline]
+    fn sender(&self) -> mpsc::Sender<DisplayUpdate> {
+        self.tx.clone()
+    }
+
+    /// Take the most recent size and DPR, older updates are never drawn
+    fn take(&self) -> (Option<PhysicalSize>, Option<f64>) {
+        let mut size = None;
+        let mut dpr = None;
+
+        while let Ok(update) = self.rx.try_recv() {
+            match update {
+                DisplayUpdate::Resize(new_size) => size = Some(new_size),
+                DisplayUpdate::DprChange(new_dpr) => dpr = Some(new_dpr),
+            }
+        }
+
+        (size, dpr)
+    }
+}
+
+/// Time the window size has to be stable before the pty is resized
+const PTY_RESIZE_DELAY: Duration = Duration::from_millis(100);
+
+/// Pty size which is held back while the window is resized interactively
+///
+/// Dragging the window border emits dozens of resize events per second. Passing all of them on
+/// makes full-screen applications redraw over and over, so only the final size is sent once it
+/// has been stable for `PTY_RESIZE_DELAY`.
+#[derive(Debug, Default)]
+struct PtyResize {
+    /// Size which hasn't been sent yet, with the time it may be sent at
+    pending: Option<(SizeInfo, Instant)>,
+}
+
+impl PtyResize {
+    /// Send `size` unless the window is resized again within `PTY_RESIZE_DELAY` after `now`
+    fn debounce(&mut self, size: SizeInfo, now: Instant) {
+        self.pending = Some((size, now + PTY_RESIZE_DELAY));
+    }
+
+    /// Send `size` right away, superseding any pending size
+    fn immediate(&mut self, pty_resize_handle: &mut dyn OnResize, size: &SizeInfo) {
+        self.pending = None;
+        pty_resize_handle.on_resize(size);
+    }
+
+    #[inline]
+    fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Send the pending size once it has been stable long enough
+    ///
+    /// Returns the time this has to be called again at, while a size is still pending.
+    fn update(&mut self, pty_resize_handle: &mut dyn OnResize, now: Instant) -> Option<Instant> {
+        let (size, deadline) = self.pending?;
+        if now < deadline {
+            return Some(deadline);
+        }
+
+        self.pending = None;
+        pty_resize_handle.on_resize(&size);
+        None
+    }
+}
+
+/// Limits how frequently frames are drawn
+#[derive(Debug, Default)]
+struct FrameTimer {
+    /// Minimum time between the start of two frames
+    interval: Option<Duration>,
+
+    /// Time the last frame was started at
+    last_frame: Option<Instant>,
+}
+
+impl FrameTimer {
+    fn new(max_fps: u16) -> FrameTimer {
+        let mut frame_timer = FrameTimer::default();
+        frame_timer.set_max_fps(max_fps);
+        frame_timer
+    }
+
+    fn set_max_fps(&mut self, max_fps: u16) {
+        self.interval = match max_fps {
+            0 => None,
+            max_fps => Some(Duration::from_secs(1) / u32::from(max_fps)),
+        };
+    }
+
+    /// Time remaining until the next frame may be drawn
+    ///
+    /// This is `None` whenever a frame can be drawn right away, so the first
+    /// frame after the terminal has been idle is never delayed.
+    fn delay(&self, now: Instant) -> Option<Duration> {
+        let next_frame = self.last_frame? + self.interval?;
+        if next_frame > now {
+            Some(next_frame - now)
+        } else {
+            None
+        }
+    }
+
+    fn frame_started(&mut self, now: Instant) {
+        self.last_frame = Some(now);
+    }
 }
 
 /// Can wakeup the render loop from other threads
+#[derive(Clone)]
 pub struct Notifier(window::Proxy);
 
-/// Types that are interested in when the display is resized
-pub trait OnResize {
-    fn on_resize(&mut self, size: &SizeInfo);
+/// Window changes which require the display to be updated
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DisplayUpdate {
+    /// New physical size of the window
+    Resize(PhysicalSize),
+
+    /// New DPI factor after the window was moved to a different monitor
+    DprChange(f64),
 }
 
 impl Notifier {
@@ -121,13 +358,43 @@ impl Notifier {
     }
 }
 
+impl OnWakeup for Notifier {
+    fn on_wakeup(&self) {
+        self.notify();
+    }
+}
+
+impl OnConfigReload for Notifier {
+    fn on_config_reload(&mut self) {
+        self.notify();
+    }
+}
+
 impl Display {
     pub fn notifier(&self) -> Notifier {
         Notifier(self.window.create_window_proxy())
     }
 
-    pub fn update_config(&mut self, config: &Config) {
-        self.render_timer = config.render_timer();
+    /// Identifier of the display's window in window events
+    #[inline]
+    pub fn window_id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Apply the parts of a reloaded config which have changed
+    ///
+    /// Font and padding changes are applied by the next `handle_resize`.
+    pub fn update_config(&mut self, config: &Config, changes: ConfigChanges) {
+        if changes.contains(ConfigChanges::DISPLAY) {
+            self.render_timer = config.render_timer();
+            self.draw_times = config.debug.draw_times();
+            self.frame_timer.set_max_fps(max_fps(config, self.software_gl));
+            self.window.set_configured_opacity(config.window.opacity());
+            self.window.set_bell_badge_enabled(config.window.bell_badge);
+        }
+
+        self.font_reloaded |= changes.contains(ConfigChanges::FONT);
+        self.padding_reloaded |= changes.contains(ConfigChanges::PADDING);
     }
 
     /// Get size info about the display
@@ -135,12 +402,11 @@ impl Display {
         &self.size_info
     }
 
-    pub fn new(config: &Config) -> Result<Display, Error> {
+    pub fn new(config: &Config, event_loop: &EventsLoop) -> Result<Display, Error> {
         // Extract some properties from config
         let render_timer = config.render_timer();
 
         // Guess DPR based on first monitor
-        let event_loop = EventsLoop::new();
         let estimated_dpr =
             event_loop.get_available_monitors().next().map(|m| m.get_hidpi_factor()).unwrap_or(1.);
 
@@ -165,13 +431,28 @@ impl Display {
             window.inner_size_pixels().expect("glutin returns window size").to_physical(dpr);
 
         // Create renderer
-        let mut renderer = QuadRenderer::new()?;
+        let mut renderer = QuadRenderer::new(config, window.renderer_api())?;
+
+        let software_gl = renderer.is_software();
+        if software_gl {
+            log!(
+                software_gl_log_level(config),
+                "OpenGL is rendered on the CPU; frame rate is limited to {} FPS and the visual \
+                 bell is disabled",
+                SOFTWARE_GL_MAX_FPS
+            );
+        }
 
-        let (glyph_cache, cell_width, cell_height) =
+        let (mut glyph_cache, cell_width, cell_height) =
             Self::new_glyph_cache(dpr, &mut renderer, config)?;
 
-        let mut padding_x = f64::from(config.window.padding.x) * dpr;
-        let mut padding_y = f64::from(config.window.padding.y) * dpr;
+        // Rasterize missing glyphs without blocking the draw, the new glyphs are picked up with
+        // the redraw after the worker wakes up the event loop
+        if !config.debug.synchronous_glyph_rasterization {
+            let notifier = Notifier(window.create_window_proxy());
+            let worker = GlyphWorker::new(move || notifier.notify());
+            glyph_cache.set_worker(worker, &config.font, dpr);
+        }
 
         if let Some((width, height)) =
             Self::calculate_dimensions(config, dpr, cell_width, cell_height)
@@ -182,44 +463,26 @@ impl Display {
                 viewport_size = PhysicalSize::new(width, height);
                 window.set_inner_size(viewport_size.to_logical(dpr));
             }
-        } else if config.window.dynamic_padding {
-            // Make sure additional padding is spread evenly
-            let cw = f64::from(cell_width);
-            let ch = f64::from(cell_height);
-            padding_x = padding_x + (viewport_size.width - 2. * padding_x) % cw / 2.;
-            padding_y = padding_y + (viewport_size.height - 2. * padding_y) % ch / 2.;
         }
 
-        padding_x = padding_x.floor();
-        padding_y = padding_y.floor();
+        let size_info =
+            Self::compute_size_info(config, viewport_size, dpr, cell_width, cell_height);
 
         // Update OpenGL projection
-        renderer.resize(viewport_size, padding_x as f32, padding_y as f32);
+        renderer.resize(&size_info);
 
         info!("Cell Size: {} x {}", cell_width, cell_height);
-        info!("Padding: {} x {}", padding_x, padding_y);
-
-        let size_info = SizeInfo {
-            dpr,
-            width: viewport_size.width as f32,
-            height: viewport_size.height as f32,
-            cell_width: cell_width as f32,
-            cell_height: cell_height as f32,
-            padding_x: padding_x as f32,
-            padding_y: padding_y as f32,
-        };
-
-        // Channel for resize events
-        //
-        // macOS has a callback for getting resize events, the channel is used
-        // to queue resize events until the next draw call. Unfortunately, it
-        // seems that the event loop is blocked until the window is done
-        // resizing. If any drawing were to happen during a resize, it would
-        // need to be in the callback.
-        let (tx, rx) = mpsc::channel();
+        info!(
+            "Padding: {} left, {} right, {} top, {} bottom",
+            size_info.padding_left,
+            size_info.padding_right,
+            size_info.padding_top,
+            size_info.padding_bottom
+        );
 
         // Clear screen
-        let background_color = config.colors.primary.background;
+        let scheme = config.colors.scheme.as_ref().map(String::as_str);
+        let background_color = config.scheme_colors(scheme).primary.background;
         renderer.with_api(config, &size_info, |api| {
             api.clear(background_color);
         });
@@ -230,21 +493,24 @@ impl Display {
 
         window.show();
 
-        // Set window position
+        // Set window position, embedded windows stay at the origin of their parent
         //
         // TODO: replace `set_position` with `with_position` once available
         // Upstream issue: https://github.com/tomaka/winit/issues/806
-        if let Some(position) = config.window.position {
+        if let (Some(position), None) = (config.window.position, config.window.embed) {
             let physical = PhysicalPosition::from((position.x, position.y));
             let logical = physical.to_logical(window.hidpi_factor());
             window.set_position(logical);
         }
 
-        #[allow(clippy::single_match)]
         match config.window.startup_mode() {
-            StartupMode::Fullscreen => window.set_fullscreen(true),
+            StartupMode::Fullscreen => {
+                window.set_fullscreen(true);
+            },
             #[cfg(target_os = "macos")]
-            StartupMode::SimpleFullscreen => window.set_simple_fullscreen(true),
+            StartupMode::SimpleFullscreen => {
+                window.set_simple_fullscreen(true);
+            },
             #[cfg(not(any(target_os = "macos", windows)))]
             StartupMode::Maximized if window.is_x11() => window.set_maximized(true),
             _ => (),
@@ -255,12 +521,25 @@ impl Display {
             renderer,
             glyph_cache,
             render_timer,
-            tx,
-            rx,
+            draw_times: config.debug.draw_times(),
+            last_draw_times: Instant::now(),
+            resize_queue: ResizeQueue::new(),
             meter: Meter::new(),
+            lock_meter: Meter::new(),
+            latency_meter: Meter::new(),
+            wakeups: Rate::new(),
+            grid_cells: Vec::new(),
+            last_frame: LastFrame::default(),
             font_size: config.font.size,
+            font_family: None,
+            font_reloaded: false,
+            padding_reloaded: false,
             size_info,
             last_message: None,
+            frame_timer: FrameTimer::new(max_fps(config, software_gl)),
+            pty_resize: PtyResize::default(),
+            ime_spot: ImeSpot::default(),
+            software_gl,
         })
     }
 
@@ -270,24 +549,28 @@ impl Display {
         cell_width: f32,
         cell_height: f32,
     ) -> Option<(f64, f64)> {
-        let dimensions = config.window.dimensions;
+        let dimensions = config.window.dimensions();
 
+        // Embedded windows take the size of their parent
         if dimensions.columns_u32() == 0
             || dimensions.lines_u32() == 0
             || config.window.startup_mode() != StartupMode::Windowed
+            || config.window.embed.is_some()
         {
             return None;
         }
 
-        let padding_x = f64::from(config.window.padding.x) * dpr;
-        let padding_y = f64::from(config.window.padding.y) * dpr;
+        let padding = config.window.padding;
+        let padding_x = f64::from(u16::from(padding.left) + u16::from(padding.right)) * dpr;
+        let padding_y = f64::from(u16::from(padding.top) + u16::from(padding.bottom)) * dpr
+            + f64::from(titlebar_overlap(config)) * dpr;
 
         // Calculate new size based on cols/lines specified in config
         let grid_width = cell_width as u32 * dimensions.columns_u32();
         let grid_height = cell_height as u32 * dimensions.lines_u32();
 
-        let width = (f64::from(grid_width) + 2. * padding_x).floor();
-        let height = (f64::from(grid_height) + 2. * padding_y).floor();
+        let width = (f64::from(grid_width) + padding_x).floor();
+        let height = (f64::from(grid_height) + padding_y).floor();
 
         Some((width, height))
     }
@@ -327,9 +610,13 @@ impl Display {
         let cache = &mut self.glyph_cache;
         let dpr = self.size_info.dpr;
         let size = self.font_size;
+        let font = match self.font_family {
+            Some(ref family) => config.font.clone().with_family(family),
+            None => config.font.clone(),
+        };
 
         self.renderer.with_loader(|mut api| {
-            let _ = cache.update_font_size(&config.font, size, dpr, &mut api);
+            let _ = cache.update_font_size(&font, size, dpr, &mut api);
         });
 
         let (cw, ch) = Self::compute_cell_size(config, &cache.font_metrics());
@@ -346,9 +633,50 @@ impl Display {
         )
     }
 
+    /// Compute the terminal size for a physical window size
+    fn compute_size_info(
+        config: &Config,
+        size: PhysicalSize,
+        dpr: f64,
+        cell_width: f32,
+        cell_height: f32,
+    ) -> SizeInfo {
+        let width = size.width as f32;
+        let height = size.height as f32;
+
+        let padding = config.window.padding;
+        let scale = |padding: u8| f32::from(padding) * dpr as f32;
+        let mut padding_left = scale(padding.left);
+        let mut padding_right = scale(padding.right);
+        let mut padding_top = scale(padding.top) + titlebar_overlap(config) * dpr as f32;
+        let mut padding_bottom = scale(padding.bottom);
+
+        // Spread the space which doesn't fit a full cell evenly between opposing edges
+        if config.window.dynamic_padding {
+            let extra_x = ((width - padding_left - padding_right) % cell_width) / 2.;
+            let extra_y = ((height - padding_top - padding_bottom) % cell_height) / 2.;
+            padding_left += extra_x;
+            padding_right += extra_x;
+            padding_top += extra_y;
+            padding_bottom += extra_y;
+        }
+
+        SizeInfo {
+            width,
+            height,
+            cell_width,
+            cell_height,
+            padding_left: padding_left.floor(),
+            padding_right: padding_right.floor(),
+            padding_top: padding_top.floor(),
+            padding_bottom: padding_bottom.floor(),
+            dpr,
+        }
+    }
+
     #[inline]
-    pub fn resize_channel(&self) -> mpsc::Sender<PhysicalSize> {
-        self.tx.clone()
+    pub fn resize_channel(&self) -> mpsc::Sender<DisplayUpdate> {
+        self.resize_queue.sender()
     }
 
     pub fn window(&mut self) -> &mut Window {
@@ -366,26 +694,30 @@ impl Display {
         let previous_cols = self.size_info.cols();
         let previous_lines = self.size_info.lines();
 
-        // Resize events new_size and are handled outside the poll_events
-        // iterator. This has the effect of coalescing multiple resize
-        // events into one.
-        let mut new_size = None;
-
-        // Take most recent resize event, if any
-        while let Ok(size) = self.rx.try_recv() {
-            new_size = Some(size);
-        }
+        let (mut new_size, new_dpr) = self.resize_queue.take();
 
         // Update the DPR
-        let dpr = self.window.hidpi_factor();
+        let dpr = new_dpr.unwrap_or(self.size_info.dpr);
+        let dpr_changed = (dpr - self.size_info.dpr).abs() > f64::EPSILON;
+
+        // Font size/family/DPI factor modification detected
+        let font_changed = terminal.font_size != self.font_size
+            || terminal.font_family() != self.font_family.as_ref().map(String::as_str)
+            || dpr_changed
+            || mem::replace(&mut self.font_reloaded, false);
+        let padding_changed = mem::replace(&mut self.padding_reloaded, false);
+
+        // Message bar update detected
+        let message_bar_changed = self.last_message != terminal.message_buffer_mut().message();
 
-        // Font size/DPI factor modification detected
-        let font_changed =
-            terminal.font_size != self.font_size || (dpr - self.size_info.dpr).abs() > f64::EPSILON;
+        // Only changes by Alacritty itself resize the pty right away
+        let immediate = font_changed || message_bar_changed || padding_changed;
 
         // Skip resize if nothing changed
         if let Some(new_size) = new_size {
             if !font_changed
+                && !padding_changed
+                && !message_bar_changed
                 && (new_size.width - f64::from(self.size_info.width)).abs() < f64::EPSILON
                 && (new_size.height - f64::from(self.size_info.height)).abs() < f64::EPSILON
             {
@@ -393,10 +725,7 @@ impl Display {
             }
         }
 
-        // Message bar update detected
-        let message_bar_changed = self.last_message != terminal.message_buffer_mut().message();
-
-        if font_changed || message_bar_changed {
+        if font_changed || message_bar_changed || padding_changed {
             if new_size == None {
                 // Force a resize to refresh things
                 new_size = Some(PhysicalSize::new(
@@ -406,93 +735,145 @@ impl Display {
             }
 
             self.font_size = terminal.font_size;
+            self.font_family = terminal.font_family().map(str::to_owned);
             self.last_message = terminal.message_buffer_mut().message();
             self.size_info.dpr = dpr;
         }
 
         if font_changed {
+            if dpr_changed {
+                info!("Device pixel ratio changed to {}", dpr);
+            }
+
+            // Rasterize glyphs again at the new DPR, font size and family
             self.update_glyph_cache(config);
         }
 
         if let Some(psize) = new_size.take() {
-            let width = psize.width as f32;
-            let height = psize.height as f32;
-            let cell_width = self.size_info.cell_width;
-            let cell_height = self.size_info.cell_height;
-
-            self.size_info.width = width;
-            self.size_info.height = height;
-
-            let mut padding_x = f32::from(config.window.padding.x) * dpr as f32;
-            let mut padding_y = f32::from(config.window.padding.y) * dpr as f32;
-
-            if config.window.dynamic_padding {
-                padding_x = padding_x + ((width - 2. * padding_x) % cell_width) / 2.;
-                padding_y = padding_y + ((height - 2. * padding_y) % cell_height) / 2.;
-            }
-
-            self.size_info.padding_x = padding_x.floor();
-            self.size_info.padding_y = padding_y.floor();
+            self.size_info = Self::compute_size_info(
+                config,
+                psize,
+                dpr,
+                self.size_info.cell_width,
+                self.size_info.cell_height,
+            );
 
             let size = &self.size_info;
             terminal.resize(size);
             processor_resize_handle.on_resize(size);
 
-            // Subtract message bar lines for pty size
-            let mut pty_size = *size;
-            if let Some(message) = terminal.message_buffer_mut().message() {
-                pty_size.height -= pty_size.cell_height * message.text(&size).len() as f32;
-            }
+            let pty_size = pty_size(size, terminal.message_buffer_mut().message().as_ref());
+            let grid_changed =
+                previous_cols != pty_size.cols() || previous_lines != pty_size.lines();
 
-            if message_bar_changed
-                || previous_cols != pty_size.cols()
-                || previous_lines != pty_size.lines()
-            {
-                pty_resize_handle.on_resize(&pty_size);
+            // The grid follows the window right away, the pty only once resizing has settled
+            if !immediate && (grid_changed || self.pty_resize.is_pending()) {
+                self.pty_resize.debounce(pty_size, Instant::now());
+            } else if message_bar_changed || grid_changed || self.pty_resize.is_pending() {
+                self.pty_resize.immediate(pty_resize_handle, &pty_size);
             }
 
             self.window.resize(psize);
-            self.renderer.resize(psize, self.size_info.padding_x, self.size_info.padding_y);
+            self.renderer.resize(&self.size_info);
         }
     }
 
+    /// Resize the pty once the window size has been stable long enough
+    ///
+    /// Returns the time this has to be called again at, while a resize is still pending.
+    pub fn update_pty_size(
+        &mut self,
+        pty_resize_handle: &mut dyn OnResize,
+        now: Instant,
+    ) -> Option<Instant> {
+        self.pty_resize.update(pty_resize_handle, now)
+    }
+
+    /// Time the next frame has to be postponed to, `None` if it can be drawn at `now`
+    ///
+    /// Frames are held back by the frame rate limit and by bursts of color changes, so a
+    /// rewritten palette is drawn at once.
+    pub fn next_frame(&self, terminal: &Term, now: Instant) -> Option<Instant> {
+        let delay = cmp::max(self.frame_timer.delay(now), terminal.palette_delay(now));
+        delay.map(|delay| now + delay)
+    }
+
+    /// Count a wakeup of the event loop for the render timer
+    #[inline]
+    pub fn record_wakeup(&mut self) {
+        self.wakeups.record(Instant::now());
+    }
+
     /// Draw the screen
     ///
-    /// A reference to Term whose state is being drawn must be provided.
+    /// A reference to Term whose state is being drawn must be provided. Returns `true` while
+    /// an animation requires more frames.
     ///
     /// This call may block if vsync is enabled
-    pub fn draw(&mut self, terminal: &FairMutex<Term>, config: &Config) {
-        let mut terminal = terminal.lock();
-        let size_info = *terminal.size_info();
-        let visual_bell_intensity = terminal.visual_bell.intensity();
-        let background_color = terminal.background_color();
+    pub fn draw(&mut self, terminal: &FairMutex<Term>, config: &Config) -> bool {
+        // Hold the terminal lock only while copying the state needed for this frame, rendering
+        // and window system calls happen after it has been released
+        let mut grid_cells = mem::replace(&mut self.grid_cells, Vec::new());
+        let frame = {
+            let mut terminal = terminal.lock();
+            let _sampler = self.lock_meter.sampler();
+            let (focused, software_gl) = (self.window.is_focused, self.software_gl);
+            FrameState::new(&mut terminal, config, focused, software_gl, &mut grid_cells)
+        };
+
+        let size_info = frame.size_info;
+        let background_color = frame.background_color;
         let metrics = self.glyph_cache.font_metrics();
 
-        let window_focused = self.window.is_focused;
-        let grid_cells: Vec<RenderableCell> =
-            terminal.renderable_cells(config, window_focused).collect();
+        // Upload glyphs which have been rasterized in the background since the last frame
+        let glyphs_loaded = {
+            let glyph_cache = &mut self.glyph_cache;
+            self.renderer.with_loader(|mut api| glyph_cache.load_rasterized(&mut api))
+        };
 
-        // Get message from terminal to ignore modifications after lock is dropped
-        let message_buffer = terminal.message_buffer_mut().message();
+        if frame.render_timer_toggled {
+            self.render_timer = !self.render_timer;
+        }
 
-        // Clear dirty flag
-        terminal.dirty = !terminal.visual_bell.completed();
+        // Follow background color changes, including the ones by escape sequences
+        if config.window.titlebar_color == TitlebarColor::Background {
+            self.window.set_titlebar_color(background_color);
+        }
 
-        if let Some(title) = terminal.get_next_title() {
-            self.window.set_title(&title);
+        if let Some(title) = &frame.title {
+            self.window.set_title(title);
         }
 
-        if let Some(mouse_cursor) = terminal.get_next_mouse_cursor() {
+        if let Some(mouse_cursor) = frame.mouse_cursor {
             self.window.set_mouse_cursor(mouse_cursor);
         }
 
-        if let Some(is_urgent) = terminal.next_is_urgent.take() {
+        if let Some(change) = frame.window_opacity {
+            self.window.change_opacity(change);
+        }
+
+        if let Some(is_urgent) = frame.is_urgent {
             // We don't need to set the urgent flag if we already have the
             // user's attention.
             if !is_urgent || !self.window.is_focused {
                 self.window.set_urgent(is_urgent);
             }
+
+            if is_urgent {
+                self.window.ring_bell_badge();
+            }
+        }
+
+        // Skip frames which would be identical to the one on screen, so the GPU can stay idle
+        if glyphs_loaded || frame.screenshot_requested || frame.render_timer_toggled {
+            self.last_frame.invalidate();
         }
+        if !self.last_frame.update(&frame.id) && !self.render_timer {
+            self.grid_cells = grid_cells;
+            return frame.animating;
+        }
+
+        self.frame_timer.frame_started(Instant::now());
 
         // Clear when terminal mutex isn't held. Mesa for
         // some reason takes a long time to call glClear(). The driver descends
@@ -503,23 +884,35 @@ impl Display {
         // worked around to some extent. Since this doesn't actually address the
         // issue of glClear being slow, less time is available for input
         // handling and rendering.
-        drop(terminal);
-
         self.renderer.with_api(config, &size_info, |api| {
             api.clear(background_color);
         });
 
+        // Extend the background of applications filling the screen into the padding
+        if frame.padding_color != background_color {
+            let padding = RenderRect::padding(&size_info, frame.padding_color);
+            self.renderer.draw_rects(&size_info, padding);
+        }
+
         {
             let glyph_cache = &mut self.glyph_cache;
             let mut lines = RenderLines::new();
 
+            // Draw rulers below the text
+            if frame.show_rulers {
+                let rulers = RenderRect::rulers(&config.window.rulers, &size_info);
+                if !rulers.is_empty() {
+                    self.renderer.draw_rects(&size_info, rulers);
+                }
+            }
+
             // Draw grid
             {
                 let _sampler = self.meter.sampler();
 
                 self.renderer.with_api(config, &size_info, |mut api| {
                     // Iterate over all non-empty cells in the grid
-                    for cell in grid_cells {
+                    for cell in grid_cells.drain(..) {
                         // Update underline/strikeout
                         lines.update(&cell);
 
@@ -529,24 +922,30 @@ impl Display {
                 });
             }
 
-            let mut rects = lines.into_rects(&metrics, &size_info);
+            // Draw visual bell below underlines and strikeouts
+            let color = config.visual_bell.color;
+            let alpha = if self.software_gl { 0. } else { frame.visual_bell_intensity as f32 };
+            let region = config.visual_bell.region;
+            let mut rects = RenderRect::visual_bell(region, &size_info, color, alpha);
+            rects.append(&mut lines.into_rects(&metrics, &size_info));
 
-            if let Some(message) = message_buffer {
+            if let Some(message) = &frame.message {
                 let text = message.text(&size_info);
 
                 // Create a new rectangle for the background
                 let start_line = size_info.lines().0 - text.len();
-                let y = size_info.padding_y + size_info.cell_height * start_line as f32;
+                let y = size_info.padding_top + size_info.cell_height * start_line as f32;
                 rects.push(RenderRect::new(
                     0.,
                     y,
                     size_info.width,
                     size_info.height - y,
                     message.color(),
+                    1.,
                 ));
 
                 // Draw rectangles including the new background
-                self.renderer.draw_rects(config, &size_info, visual_bell_intensity, rects);
+                self.renderer.draw_rects(&size_info, rects);
 
                 // Relay messages to the user
                 let mut offset = 1;
@@ -554,7 +953,7 @@ impl Display {
                     self.renderer.with_api(config, &size_info, |mut api| {
                         api.render_string(
                             &message_text,
-                            Line(size_info.lines().saturating_sub(offset)),
+                            Point::new(size_info.lines().saturating_sub(offset), Column(0)),
                             glyph_cache,
                             None,
                         );
@@ -563,20 +962,119 @@ impl Display {
                 }
             } else {
                 // Draw rectangles
-                self.renderer.draw_rects(config, &size_info, visual_bell_intensity, rects);
+                self.renderer.draw_rects(&size_info, rects);
             }
 
-            // Draw render timer
+            // Draw render timer, it stays within the terminal grid to keep the message bar visible
             if self.render_timer {
-                let timing = format!("{:.3} usec", self.meter.average());
+                let (grid_lines, grid_cols) = (frame.grid_lines, frame.grid_cols);
+                let timings = Timings {
+                    draw: self.meter.statistics(),
+                    lock: self.lock_meter.statistics(),
+                    latency: self.latency_meter.statistics(),
+                };
+                let throughput = frame.pty_throughput;
+                let wakeups = self.wakeups.per_second(Instant::now());
+                let text = render_timer_text(
+                    &timings,
+                    grid_cols,
+                    grid_lines,
+                    throughput,
+                    wakeups,
+                    frame.unhandled,
+                );
+                let width = text.first().map_or(0, String::len);
+                let position = config.debug.render_timer_position;
+                let origin =
+                    render_timer_origin(position, width, text.len(), grid_lines, grid_cols);
                 let color = Rgb { r: 0xd5, g: 0x4e, b: 0x53 };
                 self.renderer.with_api(config, &size_info, |mut api| {
-                    api.render_string(&timing[..], size_info.lines() - 2, glyph_cache, Some(color));
+                    for (i, line) in text.iter().take(grid_lines.0).enumerate() {
+                        let point = Point::new(origin.line + i, origin.col);
+                        api.render_string(line, point, glyph_cache, Some(color));
+                    }
                 });
             }
         }
 
+        // Keep the allocation of the cell buffer for the next frame
+        self.grid_cells = grid_cells;
+
+        // Capture the frame before it is presented, the back buffer is undefined after the swap
+        if frame.screenshot_requested {
+            let pixels = self.renderer.read_pixels(&size_info);
+            let directory = config.screenshot_directory().clone();
+            self.save_screenshot(pixels, &size_info, directory, frame.message_tx);
+        }
+
         self.window.swap_buffers().expect("swap buffers");
+
+        if let Some(input) = frame.input_timestamp {
+            self.latency_meter.add_latency(input, Instant::now());
+        }
+
+        self.log_draw_times();
+
+        frame.animating
+    }
+
+    /// Encode and write a screenshot without blocking the renderer
+    ///
+    /// The result is reported in the message bar once the file has been written.
+    fn save_screenshot(
+        &self,
+        pixels: Vec<u8>,
+        size_info: &SizeInfo,
+        directory: Option<PathBuf>,
+        message_tx: Sender<Message>,
+    ) {
+        let (width, height) = (size_info.width as u32, size_info.height as u32);
+        let directory = directory.unwrap_or_else(|| PathBuf::from("."));
+        let notifier = self.notifier();
+
+        crate::util::thread::spawn_named("screenshot", move || {
+            let screenshot = Screenshot::from_framebuffer(pixels, width, height);
+            match screenshot.save(&directory) {
+                Ok(path) => {
+                    info!("Saved screenshot to {:?}", path);
+                    let text = format!("Saved screenshot to {}", path.display());
+                    let _ = message_tx.send(Message::new(text, color::YELLOW));
+                },
+                Err(err) => error!("Unable to save screenshot in {:?}: {}", directory, err),
+            }
+
+            // Redraw to show the new message
+            notifier.notify();
+        });
+    }
+
+    /// Periodically log a summary of the render times
+    fn log_draw_times(&mut self) {
+        let interval = match self.draw_times {
+            Some(interval) => interval,
+            None => return,
+        };
+
+        if self.last_draw_times.elapsed() < interval {
+            return;
+        }
+        self.last_draw_times = Instant::now();
+
+        let stats = self.meter.statistics();
+        info!(
+            "Draw times: avg {:.3} usec, min {:.3} usec, p50 {:.3} usec, p95 {:.3} usec, p99 \
+             {:.3} usec, max {:.3} usec, {:.1} fps",
+            stats.average, stats.min, stats.p50, stats.p95, stats.p99, stats.max, stats.fps
+        );
+
+        let stats = self.lock_meter.statistics();
+        info!(
+            "Terminal lock held: avg {:.3} usec, p99 {:.3} usec, max {:.3} usec",
+            stats.average, stats.p99, stats.max
+        );
+
+        let stats = self.latency_meter.statistics();
+        info!("Input latency: p50 {:.3} usec, p99 {:.3} usec", stats.p50, stats.p99);
     }
 
     pub fn get_window_id(&self) -> Option<usize> {
@@ -584,16 +1082,22 @@ impl Display {
     }
 
     /// Adjust the IME editor position according to the new location of the cursor
+    ///
+    /// The editor is left alone while the window is unfocused or the cursor isn't visible, so it
+    /// doesn't follow applications which hide the cursor while they redraw the screen.
     pub fn update_ime_position(&mut self, terminal: &Term) {
-        let point = terminal.cursor().point;
-        let SizeInfo { cell_width: cw, cell_height: ch, padding_x: px, padding_y: py, .. } =
-            *terminal.size_info();
+        if !self.window.is_focused {
+            return;
+        }
 
-        let dpr = self.window().hidpi_factor();
-        let nspot_x = f64::from(px + point.col.0 as f32 * cw);
-        let nspot_y = f64::from(py + (point.line.0 + 1) as f32 * ch);
+        let position = match ime_position(terminal) {
+            Some(position) => position,
+            None => return,
+        };
 
-        self.window().set_ime_spot(PhysicalPosition::from((nspot_x, nspot_y)).to_logical(dpr));
+        if let Some(spot) = self.ime_spot.update(position, self.size_info.dpr) {
+            self.window.set_ime_spot(spot);
+        }
     }
 
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
@@ -601,3 +1105,1004 @@ impl Display {
         self.window.get_wayland_display()
     }
 }
+
+/// Terminal state required to draw a frame
+///
+/// Copied while the terminal is locked, so the lock can be released before anything is rendered.
+struct FrameState {
+    id: FrameId,
+    size_info: SizeInfo,
+    visual_bell_intensity: f64,
+    background_color: Rgb,
+    padding_color: Rgb,
+    message: Option<Message>,
+    message_tx: Sender<Message>,
+    show_rulers: bool,
+    grid_lines: Line,
+    grid_cols: Column,
+    pty_throughput: f64,
+    unhandled: UnhandledStats,
+    screenshot_requested: bool,
+    render_timer_toggled: bool,
+    title: Option<String>,
+
+    /// Arrival of the oldest input presented by this frame
+    input_timestamp: Option<Instant>,
+
+    mouse_cursor: Option<MouseCursorIcon>,
+    window_opacity: Option<OpacityChange>,
+    is_urgent: Option<bool>,
+
+    /// The visual bell is still fading out and needs another frame
+    animating: bool,
+}
+
+impl FrameState {
+    /// Take the state of the next frame, the renderable cells are written to `grid_cells`
+    ///
+    /// This also consumes all pending requests of the terminal and clears its dirty flag.
+    fn new(
+        terminal: &mut Term,
+        config: &Config,
+        window_focused: bool,
+        software_gl: bool,
+        grid_cells: &mut Vec<RenderableCell>,
+    ) -> FrameState {
+        let size_info = *terminal.size_info();
+        let visual_bell_intensity = terminal.visual_bell.intensity();
+
+        grid_cells.clear();
+        grid_cells.extend(terminal.renderable_cells(config, window_focused));
+
+        let show_rulers =
+            config.window.rulers.alt_screen || !terminal.mode().contains(TermMode::ALT_SCREEN);
+
+        let message = terminal.message_buffer_mut().message();
+        let id = FrameId::new(terminal, window_focused, message.clone());
+
+        terminal.clear_palette_burst();
+
+        // Clear dirty flag, the visual bell animation is skipped with software rendering
+        terminal.dirty = !software_gl && !terminal.visual_bell.completed();
+        let animating = terminal.dirty;
+
+        FrameState {
+            id,
+            size_info,
+            visual_bell_intensity,
+            background_color: terminal.background_color(),
+            padding_color: terminal.effective_background(),
+            message,
+            message_tx: terminal.message_buffer().tx(),
+            show_rulers,
+            grid_lines: terminal.grid().num_lines(),
+            grid_cols: terminal.grid().num_cols(),
+            pty_throughput: terminal.pty_throughput(),
+            unhandled: terminal.unhandled_stats(),
+            screenshot_requested: terminal.take_screenshot_request(),
+            render_timer_toggled: terminal.take_render_timer_toggle(),
+            title: terminal.get_next_title(),
+            input_timestamp: terminal.take_input_timestamp(),
+            mouse_cursor: terminal.get_next_mouse_cursor(),
+            window_opacity: terminal.next_window_opacity.take(),
+            is_urgent: terminal.next_is_urgent.take(),
+            animating,
+        }
+    }
+}
+
+/// Terminal state which determines the content of a frame
+///
+/// Frames with the same state are pixel-identical, as long as the config and the glyph cache
+/// haven't changed in between.
+#[derive(Debug, Clone, PartialEq)]
+struct FrameId {
+    generation: u64,
+    cursor: Option<(Point, CursorStyle)>,
+    window_focused: bool,
+    selection: Option<Selection>,
+    url_highlight: Option<RangeInclusive<Linear>>,
+    message: Option<Message>,
+    visual_bell: u8,
+    size_info: SizeInfo,
+}
+
+impl FrameId {
+    fn new(terminal: &Term, window_focused: bool, message: Option<Message>) -> FrameId {
+        let cursor = terminal.visible_cursor().map(|point| (point, terminal.cursor_style()));
+
+        FrameId {
+            generation: terminal.generation(),
+            cursor,
+            window_focused,
+            selection: terminal.selection().clone(),
+            url_highlight: terminal.grid().url_highlight.clone(),
+            message,
+            // Steps below a single alpha level aren't visible
+            visual_bell: (terminal.visual_bell.intensity() * 255.).round() as u8,
+            size_info: *terminal.size_info(),
+        }
+    }
+}
+
+/// Identity of the last rendered frame
+#[derive(Debug, Default)]
+struct LastFrame(Option<FrameId>);
+
+impl LastFrame {
+    /// Remember the next frame, returns `false` if it is identical to the last one
+    fn update(&mut self, id: &FrameId) -> bool {
+        if self.0.as_ref() == Some(id) {
+            return false;
+        }
+
+        self.0 = Some(id.clone());
+        true
+    }
+
+    /// Render the next frame even if the terminal state is unchanged
+    fn invalidate(&mut self) {
+        self.0 = None;
+    }
+}
+
+/// Position of the input method editor below the cursor, `None` while the cursor isn't drawn
+fn ime_position(terminal: &Term) -> Option<PhysicalPosition> {
+    let point = terminal.visible_cursor()?;
+    let SizeInfo { cell_width: cw, cell_height: ch, padding_left: px, padding_top: py, .. } =
+        *terminal.size_info();
+    let x = f64::from(px + point.col.0 as f32 * cw);
+    let y = f64::from(py + (point.line.0 + 1) as f32 * ch);
+
+    Some(PhysicalPosition::new(x, y))
+}
+
+/// Size of the pty, without the lines of the message bar
+fn pty_size(size: &SizeInfo, message: Option<&Message>) -> SizeInfo {
+    let mut pty_size = *size;
+    if let Some(message) = message {
+        pty_size.height -= pty_size.cell_height * message.text(size).len() as f32;
+    }
+    pty_size
+}
+
+/// Timings shown by the render timer
+#[derive(Debug, Default, Copy, Clone)]
+struct Timings {
+    draw: Statistics,
+    lock: Statistics,
+    latency: Statistics,
+}
+
+/// Lines of the render timer, padded to the same width and cut off at the last column
+fn render_timer_text(
+    timings: &Timings,
+    cols: Column,
+    lines: Line,
+    pty_throughput: f64,
+    wakeups: f64,
+    unhandled: UnhandledStats,
+) -> Vec<String> {
+    let (stats, lock, latency) = (&timings.draw, &timings.lock, &timings.latency);
+    let text = [
+        format!("{:.3} usec avg, {:.3} usec p99, {:.1} fps", stats.average, stats.p99, stats.fps),
+        format!("{:.3} usec min, {:.3} usec p50, {:.3} usec max", stats.min, stats.p50, stats.max),
+        format!("{:.3} usec avg, {:.3} usec max lock", lock.average, lock.max),
+        format!("{:.3} usec p50, {:.3} usec p99 input latency", latency.p50, latency.p99),
+        format!("{}x{} grid, {:.1} KiB/s pty", cols, lines, pty_throughput / 1024.),
+        format!("{:.1} wakeups/s", wakeups),
+        format!("{} unhandled sequences ({} kinds)", unhandled.total, unhandled.kinds),
+    ];
+
+    let width = text.iter().map(String::len).max().unwrap_or(0).min(cols.0);
+    text.iter().map(|line| format!("{:<width$.width$}", line, width = width)).collect()
+}
+
+/// Grid position of the top left cell of the render timer
+fn render_timer_origin(
+    position: RenderTimerPosition,
+    width: usize,
+    height: usize,
+    lines: Line,
+    cols: Column,
+) -> Point {
+    let line = match position {
+        RenderTimerPosition::TopLeft | RenderTimerPosition::TopRight => Line(0),
+        RenderTimerPosition::BottomLeft | RenderTimerPosition::BottomRight => {
+            Line(lines.0.saturating_sub(height))
+        },
+    };
+
+    let col = match position {
+        RenderTimerPosition::TopLeft | RenderTimerPosition::BottomLeft => Column(0),
+        RenderTimerPosition::TopRight | RenderTimerPosition::BottomRight => {
+            Column(cols.0.saturating_sub(width))
+        },
+    };
+
+    Point::new(line, col)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use glutin::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+    use glutin::{Event, WindowEvent, WindowId};
+
+    use crate::ansi::{self, Handler, Mode, UnhandledStats};
+    use crate::clipboard::Clipboard;
+    use crate::config::{Config, Decorations, Padding, RenderTimerPosition};
+    use crate::display::{
+        ime_position, render_timer_origin, render_timer_text, ImeSpot, Timings, SOFTWARE_GL_MAX_FPS,
+    };
+    use crate::display::{max_fps, software_gl_log_level, Display, FrameTimer};
+    use crate::display::{pty_size, FrameState, LastFrame, PtyResize, PTY_RESIZE_DELAY};
+    use crate::display::{DisplayUpdate, ResizeQueue};
+    use crate::event::event_batch_len;
+    use crate::index::{Column, Line, Point};
+    use crate::message_bar::{Message, MessageBuffer};
+    use crate::scheduler::Deadlines;
+    use crate::sync::FairMutex;
+    use crate::term::color;
+    use crate::term::mode::TermMode;
+    use crate::term::{SizeInfo, Term};
+    use crate::testing::{feed, TermBuilder};
+    use crate::tty::OnResize;
+
+    fn metrics(dpr: f64) -> font::Metrics {
+        font::Metrics {
+            average_advance: 7. * dpr,
+            line_height: 15. * dpr,
+            descent: -3. * dpr as f32,
+            underline_position: -2. * dpr as f32,
+            underline_thickness: dpr as f32,
+            strikeout_position: 4. * dpr as f32,
+            strikeout_thickness: dpr as f32,
+        }
+    }
+
+    #[test]
+    fn dpr_change_with_constant_logical_size() {
+        let mut config = Config::default();
+        config.window.padding = Padding::symmetric(2, 3);
+        let logical = LogicalSize::new(704., 606.);
+
+        let (cw, ch) = Display::compute_cell_size(&config, &metrics(1.));
+        let before = Display::compute_size_info(&config, logical.to_physical(1.), 1., cw, ch);
+
+        let (cw, ch) = Display::compute_cell_size(&config, &metrics(2.));
+        let after = Display::compute_size_info(&config, logical.to_physical(2.), 2., cw, ch);
+
+        assert_eq!((before.cell_width, before.cell_height), (7., 15.));
+        assert_eq!((after.cell_width, after.cell_height), (14., 30.));
+        assert_eq!((after.padding_left, after.padding_right), (4., 4.));
+        assert_eq!((after.padding_top, after.padding_bottom), (6., 6.));
+        assert_eq!(after.dpr, 2.);
+
+        // The grid stays the same since everything scaled uniformly
+        assert_eq!(before.cols(), Column(100));
+        assert_eq!(before.lines(), Line(40));
+        assert_eq!(after.cols(), before.cols());
+        assert_eq!(after.lines(), before.lines());
+    }
+
+    #[test]
+    fn resize_burst_renders_intermediate_sizes() {
+        let config = Config::default();
+        let queue = ResizeQueue::new();
+        let resize_tx = queue.sender();
+
+        let sizes = [(200., 100.), (300., 140.), (400., 180.), (500., 220.)];
+        let window_event =
+            |event| Event::WindowEvent { window_id: unsafe { WindowId::dummy() }, event };
+
+        let mut pending = Vec::new();
+        for &(width, height) in sizes.iter() {
+            pending.push(window_event(WindowEvent::Refresh));
+            pending.push(window_event(WindowEvent::Resized(LogicalSize::new(width, height))));
+        }
+        pending.push(window_event(WindowEvent::Refresh));
+
+        // Emulate the main loop, which takes the queued size and draws once per batch of events
+        let mut size_info =
+            Display::compute_size_info(&config, PhysicalSize::new(100., 100.), 2., 10., 20.);
+        let mut renderer_resizes = Vec::new();
+        while !pending.is_empty() {
+            let batch_len = event_batch_len(&pending);
+            for event in pending.drain(..batch_len) {
+                if let Event::WindowEvent { event: WindowEvent::Resized(lsize), .. } = event {
+                    let psize = lsize.to_physical(size_info.dpr);
+                    resize_tx.send(DisplayUpdate::Resize(psize)).unwrap();
+                }
+            }
+
+            if let (Some(psize), _) = queue.take() {
+                size_info = Display::compute_size_info(&config, psize, 2., 10., 20.);
+                renderer_resizes.push(size_info);
+            }
+        }
+
+        assert_eq!((size_info.width, size_info.height), (1000., 440.));
+        assert_eq!(size_info.cols(), Column(100));
+        assert_eq!(size_info.lines(), Line(22));
+
+        let grids: Vec<_> =
+            renderer_resizes.iter().map(|size| (size.cols(), size.lines())).collect();
+        assert_eq!(grids, vec![
+            (Column(40), Line(10)),
+            (Column(60), Line(14)),
+            (Column(80), Line(18)),
+            (Column(100), Line(22)),
+        ]);
+    }
+
+    #[test]
+    fn resize_queue_keeps_latest_update() {
+        let queue = ResizeQueue::new();
+        let resize_tx = queue.sender();
+        assert_eq!(queue.take(), (None, None));
+
+        resize_tx.send(DisplayUpdate::Resize(PhysicalSize::new(100., 100.))).unwrap();
+        resize_tx.send(DisplayUpdate::DprChange(2.)).unwrap();
+        resize_tx.send(DisplayUpdate::Resize(PhysicalSize::new(200., 150.))).unwrap();
+
+        assert_eq!(queue.take(), (Some(PhysicalSize::new(200., 150.)), Some(2.)));
+        assert_eq!(queue.take(), (None, None));
+    }
+
+    #[test]
+    fn dpr_change_with_fractional_scaling() {
+        let config = Config::default();
+        let logical = LogicalSize::new(700., 600.);
+
+        let (cw, ch) = Display::compute_cell_size(&config, &metrics(1.5));
+        let size = Display::compute_size_info(&config, logical.to_physical(1.5), 1.5, cw, ch);
+
+        // Cells are rounded down, so more of them fit into the window
+        assert_eq!((size.cell_width, size.cell_height), (10., 22.));
+        assert_eq!(size.cols(), Column(105));
+        assert_eq!(size.lines(), Line(40));
+    }
+
+    #[test]
+    fn asymmetric_padding() {
+        let mut config = Config::default();
+        config.window.padding = Padding { left: 5, right: 15, top: 30, bottom: 10 };
+
+        let size = Display::compute_size_info(&config, PhysicalSize::new(100., 100.), 1., 10., 20.);
+        assert_eq!((size.padding_left, size.padding_right), (5., 15.));
+        assert_eq!((size.padding_top, size.padding_bottom), (30., 10.));
+        assert_eq!(size.cols(), Column(8));
+        assert_eq!(size.lines(), Line(3));
+
+        let size = Display::compute_size_info(&config, PhysicalSize::new(200., 200.), 2., 20., 40.);
+        assert_eq!((size.padding_left, size.padding_right), (10., 30.));
+        assert_eq!((size.padding_top, size.padding_bottom), (60., 20.));
+        assert_eq!(size.cols(), Column(8));
+        assert_eq!(size.lines(), Line(3));
+    }
+
+    #[test]
+    fn asymmetric_dynamic_padding() {
+        let mut config = Config::default();
+        config.window.padding = Padding { left: 5, right: 15, top: 30, bottom: 10 };
+        config.window.dynamic_padding = true;
+
+        let size = Display::compute_size_info(&config, PhysicalSize::new(107., 115.), 1., 10., 20.);
+
+        // Leftover space is split evenly, keeping the configured difference
+        assert_eq!((size.padding_left, size.padding_right), (8., 18.));
+        assert_eq!((size.padding_top, size.padding_bottom), (37., 17.));
+        assert_eq!(size.cols(), Column(8));
+        assert_eq!(size.lines(), Line(3));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn transparent_titlebar_padding() {
+        let mut config = Config::default();
+        config.window.padding = Padding::symmetric(2, 3);
+        config.window.decorations = Decorations::Transparent;
+
+        let size = Display::compute_size_info(&config, PhysicalSize::new(100., 200.), 2., 10., 20.);
+
+        // The title bar height is scaled like the padding
+        assert_eq!((size.padding_top, size.padding_bottom), (50., 6.));
+        assert_eq!(size.lines(), Line(7));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn transparent_titlebar_dynamic_padding() {
+        let mut config = Config::default();
+        config.window.decorations = Decorations::Transparent;
+        config.window.dynamic_padding = true;
+
+        let size = Display::compute_size_info(&config, PhysicalSize::new(100., 200.), 1., 10., 20.);
+
+        // Only the space below the title bar is centered
+        assert_eq!((size.padding_top, size.padding_bottom), (31., 9.));
+        assert_eq!(size.lines(), Line(8));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn transparent_titlebar_ignored() {
+        let mut config = Config::default();
+        config.window.padding = Padding::symmetric(2, 3);
+        config.window.decorations = Decorations::Transparent;
+
+        let size = Display::compute_size_info(&config, PhysicalSize::new(100., 200.), 2., 10., 20.);
+
+        assert_eq!((size.padding_top, size.padding_bottom), (6., 6.));
+    }
+
+    #[test]
+    fn buttonless_titlebar_padding() {
+        let mut config = Config::default();
+        config.window.padding = Padding::symmetric(2, 3);
+        config.window.decorations = Decorations::Buttonless;
+
+        let size = Display::compute_size_info(&config, PhysicalSize::new(100., 200.), 2., 10., 20.);
+
+        assert_eq!((size.padding_top, size.padding_bottom), (6., 6.));
+    }
+
+    #[test]
+    fn frame_timer_unlimited() {
+        let mut frame_timer = FrameTimer::new(0);
+        let now = Instant::now();
+
+        frame_timer.frame_started(now);
+        assert_eq!(frame_timer.delay(now), None);
+    }
+
+    #[test]
+    fn frame_timer_first_frame_after_idle() {
+        let mut frame_timer = FrameTimer::new(50);
+        let start = Instant::now();
+
+        // Nothing was drawn yet
+        assert_eq!(frame_timer.delay(start), None);
+
+        // Idle for longer than the frame interval
+        frame_timer.frame_started(start);
+        assert_eq!(frame_timer.delay(start + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn frame_timer_limits_busy_frames() {
+        let mut frame_timer = FrameTimer::new(50);
+        let start = Instant::now();
+
+        frame_timer.frame_started(start);
+
+        let now = start + Duration::from_millis(5);
+        assert_eq!(frame_timer.delay(now), Some(Duration::from_millis(15)));
+
+        let now = start + Duration::from_millis(20);
+        assert_eq!(frame_timer.delay(now), None);
+
+        frame_timer.frame_started(now);
+        let now = now + Duration::from_millis(19);
+        assert_eq!(frame_timer.delay(now), Some(Duration::from_millis(1)));
+    }
+
+    /// Records the sizes the pty is resized to
+    #[derive(Default)]
+    struct PtySizes(Vec<(Column, Line)>);
+
+    impl OnResize for PtySizes {
+        fn on_resize(&mut self, size: &SizeInfo) {
+            self.0.push((size.cols(), size.lines()));
+        }
+    }
+
+    fn pty_resize_size(width: f32, height: f32) -> SizeInfo {
+        SizeInfo {
+            width,
+            height,
+            cell_width: 10.,
+            cell_height: 20.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
+            dpr: 1.,
+        }
+    }
+
+    #[test]
+    fn pty_resize_debounces_bursts() {
+        let mut pty_resize = PtyResize::default();
+        let mut sizes = PtySizes::default();
+        let start = Instant::now();
+
+        // Dragging the window border for a second, with one resize per frame
+        let mut now = start;
+        for i in 0..60 {
+            pty_resize.debounce(pty_resize_size(400. + i as f32 * 10., 200.), now);
+            pty_resize.update(&mut sizes, now);
+            now += Duration::from_millis(16);
+        }
+        assert!(sizes.0.is_empty());
+
+        // The final size is sent once it has been stable long enough
+        let deadline = pty_resize.update(&mut sizes, now).unwrap();
+        assert!(deadline <= now - Duration::from_millis(16) + PTY_RESIZE_DELAY);
+        assert_eq!(pty_resize.update(&mut sizes, deadline), None);
+        assert_eq!(pty_resize.update(&mut sizes, deadline + PTY_RESIZE_DELAY), None);
+        assert_eq!(sizes.0, vec![(Column(99), Line(10))]);
+    }
+
+    #[test]
+    fn pty_resize_immediate() {
+        let mut pty_resize = PtyResize::default();
+        let mut sizes = PtySizes::default();
+        let now = Instant::now();
+
+        // Resizes by Alacritty itself are sent right away and supersede pending ones
+        pty_resize.debounce(pty_resize_size(400., 200.), now);
+        pty_resize.immediate(&mut sizes, &pty_resize_size(800., 400.));
+        assert_eq!(sizes.0, vec![(Column(80), Line(20))]);
+
+        assert!(!pty_resize.is_pending());
+        assert_eq!(pty_resize.update(&mut sizes, now + PTY_RESIZE_DELAY), None);
+        assert_eq!(sizes.0.len(), 1);
+    }
+
+    #[test]
+    fn ime_spot_only_updated_on_change() {
+        let mut ime_spot = ImeSpot::default();
+        let position = PhysicalPosition::new(10., 20.);
+
+        // Repeated identical frames only move the editor once
+        let updates = (0..10).filter_map(|_| ime_spot.update(position, 1.)).count();
+        assert_eq!(updates, 1);
+
+        // Cursor movement
+        let position = PhysicalPosition::new(20., 20.);
+        let updates = (0..10).filter_map(|_| ime_spot.update(position, 1.)).count();
+        assert_eq!(updates, 1);
+
+        // DPR change with the same physical position
+        assert_eq!(ime_spot.update(position, 2.), Some((10., 10.).into()));
+        assert_eq!(ime_spot.update(position, 2.), None);
+    }
+
+    #[test]
+    fn ime_spot_kept_while_cursor_hidden() {
+        let mut terminal = TermBuilder::new(10, 5).build();
+        let mut ime_spot = ImeSpot::default();
+        let mut update = |terminal: &Term| {
+            ime_position(terminal).and_then(|position| ime_spot.update(position, 1.))
+        };
+
+        feed(&mut terminal, b"ab");
+        let spot = update(&terminal);
+        assert!(spot.is_some());
+
+        // Redraws with a hidden cursor don't move the editor
+        feed(&mut terminal, b"\x1b[?25l\x1b[4;8Hxyz");
+        assert_eq!(ime_position(&terminal), None);
+        assert_eq!(update(&terminal), None);
+
+        // Showing the cursor where it was hidden keeps the editor in place
+        feed(&mut terminal, b"\x1b[1;3H\x1b[?25h");
+        assert_eq!(update(&terminal), None);
+
+        feed(&mut terminal, b"\x1b[?25lcd\x1b[?25h");
+        assert_ne!(update(&terminal), None);
+    }
+
+    #[test]
+    fn software_gl_caps_max_fps() {
+        let mut config = Config::default();
+
+        config.window.max_fps = 0;
+        assert_eq!(max_fps(&config, false), 0);
+        assert_eq!(max_fps(&config, true), SOFTWARE_GL_MAX_FPS);
+
+        config.window.max_fps = 144;
+        assert_eq!(max_fps(&config, false), 144);
+        assert_eq!(max_fps(&config, true), SOFTWARE_GL_MAX_FPS);
+
+        config.window.max_fps = 10;
+        assert_eq!(max_fps(&config, true), 10);
+    }
+
+    #[test]
+    fn software_gl_notice_suppression() {
+        let config = Config::default();
+        assert_eq!(software_gl_log_level(&config), log::Level::Warn);
+
+        let config: Config = serde_yaml::from_str("debug:\n  allow_software_gl: true\n").unwrap();
+        assert_eq!(software_gl_log_level(&config), log::Level::Info);
+    }
+
+    #[test]
+    fn render_timer_corners() {
+        let origin =
+            |position, lines, cols| render_timer_origin(position, 30, 3, Line(lines), Column(cols));
+
+        assert_eq!(origin(RenderTimerPosition::TopLeft, 24, 80), Point::new(Line(0), Column(0)));
+        assert_eq!(origin(RenderTimerPosition::TopRight, 24, 80), Point::new(Line(0), Column(50)));
+        assert_eq!(
+            origin(RenderTimerPosition::BottomLeft, 24, 80),
+            Point::new(Line(21), Column(0))
+        );
+        assert_eq!(
+            origin(RenderTimerPosition::BottomRight, 24, 80),
+            Point::new(Line(21), Column(50))
+        );
+
+        assert_eq!(
+            origin(RenderTimerPosition::TopRight, 50, 132),
+            Point::new(Line(0), Column(102))
+        );
+        assert_eq!(
+            origin(RenderTimerPosition::BottomRight, 50, 132),
+            Point::new(Line(47), Column(102))
+        );
+    }
+
+    #[test]
+    fn render_timer_larger_than_grid() {
+        let origin =
+            render_timer_origin(RenderTimerPosition::BottomRight, 30, 3, Line(2), Column(20));
+        assert_eq!(origin, Point::new(Line(0), Column(0)));
+    }
+
+    #[test]
+    fn render_timer_text_width() {
+        let mut timings = Timings::default();
+        timings.latency.p50 = 8_000.;
+        timings.latency.p99 = 16_000.;
+
+        let unhandled = UnhandledStats { total: 12, kinds: 2 };
+
+        let text = render_timer_text(&timings, Column(80), Line(24), 2048., 3., unhandled);
+        assert_eq!(text.len(), 7);
+        assert_eq!(text[3].trim_end(), "8000.000 usec p50, 16000.000 usec p99 input latency");
+        assert_eq!(text[4].trim_end(), "80x24 grid, 2.0 KiB/s pty");
+        assert_eq!(text[5].trim_end(), "3.0 wakeups/s");
+        assert_eq!(text[6].trim_end(), "12 unhandled sequences (2 kinds)");
+        assert!(text.iter().all(|line| line.len() == text[0].len()));
+
+        let text = render_timer_text(&timings, Column(10), Line(24), 0., 0., unhandled);
+        assert!(text.iter().all(|line| line.len() == 10));
+    }
+
+    #[test]
+    fn frame_state_consumes_requests() {
+        let size = SizeInfo {
+            width: 100.,
+            height: 100.,
+            cell_width: 10.,
+            cell_height: 20.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
+            dpr: 1.,
+        };
+        let config = Config::default();
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        terminal.request_screenshot();
+        let mut grid_cells = Vec::new();
+
+        let frame = FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+        assert!(frame.screenshot_requested);
+        assert_eq!(frame.grid_lines, Line(5));
+        assert_eq!(frame.grid_cols, Column(10));
+        assert!(!grid_cells.is_empty());
+        assert!(!terminal.dirty);
+
+        let frame = FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+        assert!(!frame.screenshot_requested);
+    }
+
+    #[test]
+    fn frame_state_takes_presented_input() {
+        let size = SizeInfo {
+            width: 100.,
+            height: 100.,
+            cell_width: 10.,
+            cell_height: 20.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
+            dpr: 1.,
+        };
+        let config = Config::default();
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        let mut grid_cells = Vec::new();
+        let start = Instant::now();
+
+        // A keypress and the pty reads with its echo are coalesced into one frame
+        terminal.record_input(start);
+        terminal.record_input(start + Duration::from_millis(3));
+        terminal.record_pty_read(b"a");
+        terminal.record_input(start + Duration::from_millis(4));
+        terminal.record_pty_read(b"b");
+        let frame = FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+        assert_eq!(frame.input_timestamp, Some(start));
+
+        // Frames without pty output don't present any input
+        terminal.record_input(start + Duration::from_millis(20));
+        let frame = FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+        assert_eq!(frame.input_timestamp, None);
+        let frame = FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+        assert_eq!(frame.input_timestamp, None);
+    }
+
+    #[test]
+    fn dismissed_message_restores_pty_size() {
+        let size = SizeInfo {
+            width: 100.,
+            height: 100.,
+            cell_width: 10.,
+            cell_height: 20.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
+            dpr: 1.,
+        };
+        let config = Config::default();
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        let message = Message::new("warning".into(), color::YELLOW);
+        terminal.message_buffer().tx().send(message).unwrap();
+
+        // The message bar takes one line from the grid and pty
+        terminal.resize(&size);
+        let message = terminal.message_buffer_mut().message();
+        assert_eq!(terminal.grid().num_lines(), Line(4));
+        assert_eq!(pty_size(&size, message.as_ref()).lines(), Line(4));
+
+        terminal.dirty = false;
+        terminal.dismiss_message();
+        assert!(terminal.needs_draw());
+
+        let message = terminal.message_buffer_mut().message();
+        assert_eq!(message, None);
+        assert_eq!(pty_size(&size, message.as_ref()).lines(), Line(5));
+        terminal.resize(&size);
+        assert_eq!(terminal.grid().num_lines(), Line(5));
+    }
+
+    #[test]
+    fn font_family_switch_resizes_grid() {
+        let mut config: Config =
+            serde_yaml::from_str("font:\n  families: [Narrow, Wide]\n").unwrap();
+        config.window.padding = Padding::symmetric(2, 3);
+        let physical = PhysicalSize::new(704., 606.);
+        let wide = font::Metrics { average_advance: 10., line_height: 20., ..metrics(1.) };
+
+        let (cw, ch) = Display::compute_cell_size(&config, &metrics(1.));
+        let before = Display::compute_size_info(&config, physical, 1., cw, ch);
+        let mut terminal = Term::new(&config, before, MessageBuffer::new(), Clipboard::new_nop());
+
+        terminal.cycle_font_family(Instant::now());
+        terminal.cycle_font_family(Instant::now());
+        assert_eq!(terminal.font_family(), Some("Wide"));
+
+        let (cw, ch) = Display::compute_cell_size(&config, &wide);
+        let after = Display::compute_size_info(&config, physical, 1., cw, ch);
+        assert_eq!((after.cell_width, after.cell_height), (10., 20.));
+        assert_eq!((before.cols(), before.lines()), (Column(100), Line(40)));
+        assert_eq!((after.cols(), after.lines()), (Column(70), Line(30)));
+
+        // The notice of the new family takes a line from the pty
+        terminal.resize(&after);
+        let message = terminal.message_buffer_mut().message();
+        let pty_size = pty_size(&after, message.as_ref());
+        assert_eq!((pty_size.cols(), pty_size.lines()), (Column(70), Line(29)));
+        assert_eq!(terminal.grid().num_lines(), Line(29));
+    }
+
+    /// Count the frames rendered during one second of wakeups at 60 FPS
+    fn renders_per_second<F: FnMut(&mut Term, usize)>(mut update: F) -> usize {
+        let size = SizeInfo {
+            width: 100.,
+            height: 100.,
+            cell_width: 10.,
+            cell_height: 20.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
+            dpr: 1.,
+        };
+        let config = Config::default();
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        let mut grid_cells = Vec::new();
+        let mut last_frame = LastFrame::default();
+
+        // Put the initial frame on screen
+        let frame = FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+        assert!(last_frame.update(&frame.id));
+
+        (0..60)
+            .filter(|&i| {
+                update(&mut terminal, i);
+                let frame = FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+                last_frame.update(&frame.id)
+            })
+            .count()
+    }
+
+    #[test]
+    fn idle_terminal_skips_frames() {
+        let renders = renders_per_second(|terminal, _| {
+            // Wakeup without any changes, like mouse motion without a selection
+            terminal.dirty = true;
+        });
+
+        assert_eq!(renders, 0);
+    }
+
+    #[test]
+    fn blinking_cursor_renders() {
+        // Blink the cursor every 500ms
+        let renders = renders_per_second(|terminal, i| {
+            if i % 30 == 0 {
+                if terminal.mode().contains(TermMode::SHOW_CURSOR) {
+                    terminal.unset_mode(Mode::ShowCursor);
+                } else {
+                    terminal.set_mode(Mode::ShowCursor);
+                }
+            }
+        });
+
+        assert_eq!(renders, 2);
+    }
+
+    #[test]
+    fn pty_output_renders() {
+        let renders = renders_per_second(|terminal, _| {
+            terminal.input('x');
+            terminal.record_pty_read(b"x");
+        });
+
+        assert_eq!(renders, 60);
+    }
+
+    /// Drive an idle terminal for a simulated minute, returns the number of wakeups and renders
+    ///
+    /// Like the event loop, this blocks whenever the terminal isn't dirty, until a timer
+    /// scheduled after one of the `delays` expires.
+    fn idle_minute(delays: &[Duration]) -> (usize, usize) {
+        let size = SizeInfo {
+            width: 100.,
+            height: 100.,
+            cell_width: 10.,
+            cell_height: 20.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
+            dpr: 1.,
+        };
+        let config = Config::default();
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        let mut grid_cells = Vec::new();
+        let mut last_frame = LastFrame::default();
+
+        let start = Instant::now();
+        let end = start + Duration::from_secs(60);
+        let mut deadlines = Deadlines::default();
+        for &delay in delays {
+            deadlines.schedule(start + delay);
+        }
+
+        // Put the initial frame on screen
+        let frame = FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+        assert!(last_frame.update(&frame.id));
+
+        // Bounded, so a terminal which never becomes idle fails instead of hanging
+        let (mut wakeups, mut renders) = (0, 0);
+        while wakeups < 1_000 {
+            if !terminal.dirty {
+                let now = match deadlines.next() {
+                    Some(deadline) if deadline <= end => deadline,
+                    _ => break,
+                };
+
+                // Expired timers wake up the event loop with an `Awakened` event
+                deadlines.expire(now);
+                terminal.dirty = true;
+            }
+
+            wakeups += 1;
+            let frame = FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+            if last_frame.update(&frame.id) {
+                renders += 1;
+            }
+        }
+
+        (wakeups, renders)
+    }
+
+    #[test]
+    fn idle_terminal_blocks() {
+        assert_eq!(idle_minute(&[]), (0, 0));
+    }
+
+    #[test]
+    fn idle_terminal_wakes_up_for_timers() {
+        // Key chord timeouts, the last one expires after the simulated minute
+        let delays = [Duration::from_secs(1), Duration::from_secs(30), Duration::from_secs(90)];
+
+        assert_eq!(idle_minute(&delays), (2, 0));
+    }
+
+    #[test]
+    fn pty_reader_progresses_during_redraws() {
+        const CHUNKS: usize = 2_000;
+
+        let size = SizeInfo {
+            width: 1280.,
+            height: 800.,
+            cell_width: 8.,
+            cell_height: 16.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
+            dpr: 1.,
+        };
+        let config = Config::default();
+        let terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        let terminal = Arc::new(FairMutex::new(terminal));
+        let frames = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+
+        // Take frames in a loop, like the renderer does when the terminal is always dirty
+        let renderer = {
+            let (terminal, frames, done) = (terminal.clone(), frames.clone(), done.clone());
+            thread::spawn(move || {
+                let config = Config::default();
+                let mut grid_cells = Vec::new();
+                while !done.load(Ordering::Relaxed) {
+                    let mut terminal = terminal.lock();
+                    FrameState::new(&mut terminal, &config, true, false, &mut grid_cells);
+                    drop(terminal);
+                    frames.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        };
+
+        while frames.load(Ordering::Relaxed) == 0 {
+            thread::yield_now();
+        }
+
+        // Feed output to the terminal in chunks, like the pty reader
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let mut parser = ansi::Processor::new();
+        let chunk = "The quick brown fox jumps over the lazy dog 0123456789\r\n".repeat(20);
+        let mut written = 0;
+        while written < CHUNKS && Instant::now() < deadline {
+            let mut terminal = terminal.lock();
+            for byte in chunk.bytes() {
+                parser.advance(&mut *terminal, byte, &mut io::sink());
+            }
+            terminal.record_pty_read(chunk.as_bytes());
+            drop(terminal);
+            written += 1;
+        }
+
+        let frames_while_reading = frames.load(Ordering::Relaxed);
+        done.store(true, Ordering::Relaxed);
+        renderer.join().unwrap();
+
+        assert_eq!(written, CHUNKS);
+        assert!(frames_while_reading > 1);
+    }
+}
@@ -1,37 +1,31 @@
 //! Process window events
 use std::borrow::Cow;
 use std::env;
-#[cfg(unix)]
-use std::fs;
+use std::ffi::OsString;
+use std::mem;
+use std::path::PathBuf;
 use std::sync::mpsc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use glutin::dpi::PhysicalSize;
-use glutin::{self, ElementState, Event, ModifiersState, MouseButton};
+use glutin::{ElementState, Event, ModifiersState, WindowEvent};
 use parking_lot::MutexGuard;
 
 use crate::clipboard::ClipboardType;
-use crate::config::{self, Config, StartupMode};
-use crate::display::OnResize;
+use crate::config::{self, Config, OptionAsAlt};
+use crate::display::DisplayUpdate;
+use crate::event_loop::Notify;
 use crate::grid::Scroll;
-use crate::index::{Column, Line, Point, Side};
-use crate::input::{self, KeyBinding, MouseBinding};
+use crate::index::{Point, Side};
+use crate::input::{self, KeyBinding, Mouse, MouseBinding, OptionKeys, PendingKeys, WindowChanges};
+use crate::scheduler::Scheduler;
 use crate::selection::Selection;
 use crate::sync::FairMutex;
-use crate::term::{SizeInfo, Term};
-#[cfg(unix)]
-use crate::tty;
+use crate::term::{OpacityChange, SizeInfo, Term, TermMode};
+use crate::tty::cwd::{self, PtyProcesses};
+use crate::tty::OnResize;
 use crate::util::{limit, start_daemon};
 use crate::window::Window;
 
-/// Byte sequences are sent to a `Notify` in response to some events
-pub trait Notify {
-    /// Notify that an escape sequence should be written to the pty
-    ///
-    /// TODO this needs to be able to error somehow
-    fn notify<B: Into<Cow<'static, [u8]>>>(&mut self, _: B);
-}
-
 pub struct ActionContext<'a, N> {
     pub notifier: &'a mut N,
     pub terminal: &'a mut Term,
@@ -39,8 +33,21 @@ pub struct ActionContext<'a, N> {
     pub mouse: &'a mut Mouse,
     pub received_count: &'a mut usize,
     pub suppress_chars: &'a mut bool,
+    pub composing: &'a mut bool,
     pub last_modifiers: &'a mut ModifiersState,
+    pub option_keys: &'a mut OptionKeys,
+    pub pending_keys: &'a mut Option<PendingKeys>,
+    pub ui_mode: &'a mut TermMode,
     pub window_changes: &'a mut WindowChanges,
+    pub scheduler: &'a Scheduler,
+    pub processes: &'a PtyProcesses,
+}
+
+impl<'a, N> ActionContext<'a, N> {
+    /// Check if the last known pointer position is over the terminal content
+    fn pointer_over_content(&self) -> bool {
+        self.size_info.contains_point(self.mouse.x, self.mouse.y, false)
+    }
 }
 
 impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
@@ -65,11 +72,7 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
     }
 
     fn copy_selection(&mut self, ty: ClipboardType) {
-        if let Some(selected) = self.terminal.selection_to_string() {
-            if !selected.is_empty() {
-                self.terminal.clipboard().store(ty, selected);
-            }
-        }
+        self.terminal.copy_selection(ty);
     }
 
     fn selection_is_empty(&self) -> bool {
@@ -140,11 +143,33 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         &mut self.suppress_chars
     }
 
+    #[inline]
+    fn composing(&mut self) -> &mut bool {
+        &mut self.composing
+    }
+
     #[inline]
     fn last_modifiers(&mut self) -> &mut ModifiersState {
         &mut self.last_modifiers
     }
 
+    fn option_keys(&mut self) -> &mut OptionKeys {
+        &mut self.option_keys
+    }
+
+    #[inline]
+    fn pending_keys(&mut self) -> &mut Option<PendingKeys> {
+        &mut self.pending_keys
+    }
+
+    fn ui_mode(&mut self) -> &mut TermMode {
+        &mut self.ui_mode
+    }
+
+    fn schedule_wakeup(&mut self, delay: Duration) {
+        self.scheduler.schedule(delay);
+    }
+
     #[inline]
     fn hide_window(&mut self) {
         self.window_changes.hide = true;
@@ -163,21 +188,11 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
     fn spawn_new_instance(&mut self) {
         let alacritty = env::args().next().unwrap();
 
-        #[cfg(unix)]
-        let args = {
-            #[cfg(not(target_os = "freebsd"))]
-            let proc_prefix = "";
-            #[cfg(target_os = "freebsd")]
-            let proc_prefix = "/compat/linux";
-            let link_path = format!("{}/proc/{}/cwd", proc_prefix, tty::child_pid());
-            if let Ok(path) = fs::read_link(link_path) {
-                vec!["--working-directory".into(), path]
-            } else {
-                Vec::new()
-            }
+        let cwd = cwd::working_directory(self.processes, self.terminal.working_directory());
+        let args = match cwd {
+            Some(dir) => vec![OsString::from("--working-directory"), dir.into_os_string()],
+            None => Vec::new(),
         };
-        #[cfg(not(unix))]
-        let args: Vec<String> = Vec::new();
 
         match start_daemon(&alacritty, &args) {
             Ok(_) => debug!("Started new Alacritty process: {} {:?}", alacritty, args),
@@ -193,78 +208,104 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
     fn toggle_simple_fullscreen(&mut self) {
         self.window_changes.toggle_simple_fullscreen()
     }
-}
-
-/// The ActionContext can't really have direct access to the Window
-/// with the current design. Event handlers that want to change the
-/// window must set these flags instead. The processor will trigger
-/// the actual changes.
-#[derive(Default)]
-pub struct WindowChanges {
-    pub hide: bool,
-    pub toggle_fullscreen: bool,
-    #[cfg(target_os = "macos")]
-    pub toggle_simple_fullscreen: bool,
-}
 
-impl WindowChanges {
-    fn clear(&mut self) {
-        *self = WindowChanges::default();
+    fn toggle_maximized(&mut self) {
+        self.window_changes.toggle_maximized();
     }
 
-    fn toggle_fullscreen(&mut self) {
-        self.toggle_fullscreen = !self.toggle_fullscreen;
+    fn change_window_opacity(&mut self, change: OpacityChange) {
+        self.window_changes.opacity.push(change);
     }
 
-    #[cfg(target_os = "macos")]
-    fn toggle_simple_fullscreen(&mut self) {
-        self.toggle_simple_fullscreen = !self.toggle_simple_fullscreen;
+    fn create_new_window(&mut self) {
+        self.window_changes.create_window = true;
     }
 }
 
-pub enum ClickState {
-    None,
-    Click,
-    DoubleClick,
-    TripleClick,
+/// Visibility of the mouse pointer, which is hidden while typing and after inactivity
+///
+/// Both kinds of hiding are tracked separately and the pointer is only shown while neither
+/// applies, so they never make the pointer visible while the other one hides it.
+#[derive(Debug, Copy, Clone)]
+pub struct PointerHiding {
+    /// Hidden by a key press, until the pointer is used again
+    typing: bool,
+
+    /// Hidden since the pointer hasn't been used for the hide delay
+    idle: bool,
+
+    /// Pointer is over the terminal content, it's only hidden after inactivity there
+    over_content: bool,
+
+    /// Time of the last pointer motion or button press
+    last_activity: Instant,
+
+    /// Deadline of the timer which has been scheduled to check for inactivity
+    timer: Option<Instant>,
 }
 
-/// State of the mouse
-pub struct Mouse {
-    pub x: usize,
-    pub y: usize,
-    pub left_button_state: ElementState,
-    pub middle_button_state: ElementState,
-    pub right_button_state: ElementState,
-    pub last_click_timestamp: Instant,
-    pub click_state: ClickState,
-    pub scroll_px: i32,
-    pub line: Line,
-    pub column: Column,
-    pub cell_side: Side,
-    pub lines_scrolled: f32,
-    pub block_url_launcher: bool,
-    pub last_button: MouseButton,
+impl Default for PointerHiding {
+    fn default() -> PointerHiding {
+        PointerHiding {
+            typing: false,
+            idle: false,
+            over_content: false,
+            last_activity: Instant::now(),
+            timer: None,
+        }
+    }
 }
 
-impl Default for Mouse {
-    fn default() -> Mouse {
-        Mouse {
-            x: 0,
-            y: 0,
-            last_click_timestamp: Instant::now(),
-            left_button_state: ElementState::Released,
-            middle_button_state: ElementState::Released,
-            right_button_state: ElementState::Released,
-            click_state: ClickState::None,
-            scroll_px: 0,
-            line: Line(0),
-            column: Column(0),
-            cell_side: Side::Left,
-            lines_scrolled: 0.0,
-            block_url_launcher: false,
-            last_button: MouseButton::Other(0),
+impl PointerHiding {
+    /// A key has been pressed
+    pub fn key_pressed(&mut self) {
+        self.typing = true;
+    }
+
+    /// The pointer has been moved or a button has been used
+    pub fn pointer_used(&mut self, over_content: bool, now: Instant) {
+        self.typing = false;
+        self.idle = false;
+        self.over_content = over_content;
+        self.last_activity = now;
+    }
+
+    /// The pointer has entered or left the window
+    pub fn pointer_crossed(&mut self) {
+        self.typing = false;
+        self.idle = false;
+        self.over_content = false;
+    }
+
+    /// Hide the pointer once it has been inactive for `delay`
+    ///
+    /// Returns the deadline of a new timer which has to be scheduled to check again. No new
+    /// timer is requested while an earlier one is still pending.
+    pub fn update(&mut self, delay: Option<Duration>, now: Instant) -> Option<Instant> {
+        let delay = match delay {
+            Some(delay) if self.over_content && !self.idle => delay,
+            _ => return None,
+        };
+
+        let deadline = self.last_activity + delay;
+        if now >= deadline {
+            self.idle = true;
+            self.timer = None;
+            return None;
         }
+
+        match self.timer {
+            Some(timer) if timer > now => None,
+            _ => {
+                self.timer = Some(deadline);
+                self.timer
+            },
+        }
+    }
+
+    /// Check if the pointer should be visible
+    pub fn visible(&self, hide_when_typing: bool) -> bool {
+        !self.idle && !(hide_when_typing && self.typing)
     }
 }
 
@@ -277,23 +318,30 @@ pub struct Processor<N> {
     mouse_bindings: Vec<MouseBinding>,
     mouse_config: config::Mouse,
     scrolling_config: config::Scrolling,
+    key_chords_config: config::KeyChords,
     print_events: bool,
     wait_for_event: bool,
     notifier: N,
+    scheduler: Scheduler,
+    processes: PtyProcesses,
     mouse: Mouse,
-    resize_tx: mpsc::Sender<PhysicalSize>,
+    resize_tx: mpsc::Sender<DisplayUpdate>,
     size_info: SizeInfo,
     hide_mouse_when_typing: bool,
-    hide_mouse: bool,
+    pointer_hiding: PointerHiding,
     received_count: usize,
     suppress_chars: bool,
+    composing: bool,
     last_modifiers: ModifiersState,
+    option_keys: OptionKeys,
+    pending_keys: Option<PendingKeys>,
+    ui_mode: TermMode,
     pending_events: Vec<Event>,
     window_changes: WindowChanges,
+    window_requested: bool,
     save_to_clipboard: bool,
     alt_send_esc: bool,
-    is_fullscreen: bool,
-    is_simple_fullscreen: bool,
+    option_as_alt: OptionAsAlt,
 }
 
 /// Notify that the terminal was resized
@@ -312,7 +360,9 @@ impl<N: Notify> Processor<N> {
     /// pty.
     pub fn new(
         notifier: N,
-        resize_tx: mpsc::Sender<PhysicalSize>,
+        scheduler: Scheduler,
+        processes: PtyProcesses,
+        resize_tx: mpsc::Sender<DisplayUpdate>,
         config: &Config,
         size_info: SizeInfo,
     ) -> Processor<N> {
@@ -321,26 +371,30 @@ impl<N: Notify> Processor<N> {
             mouse_bindings: config.mouse_bindings.to_vec(),
             mouse_config: config.mouse.to_owned(),
             scrolling_config: config.scrolling,
+            key_chords_config: config.key_chords,
             print_events: config.debug.print_events,
             wait_for_event: true,
             notifier,
+            scheduler,
+            processes,
             resize_tx,
             mouse: Default::default(),
             size_info,
             hide_mouse_when_typing: config.mouse.hide_when_typing,
-            hide_mouse: false,
+            pointer_hiding: Default::default(),
             received_count: 0,
             suppress_chars: false,
+            composing: false,
             last_modifiers: Default::default(),
+            option_keys: Default::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
             pending_events: Vec::with_capacity(4),
             window_changes: Default::default(),
+            window_requested: false,
             save_to_clipboard: config.selection.save_to_clipboard,
             alt_send_esc: config.alt_send_esc(),
-            is_fullscreen: config.window.startup_mode() == StartupMode::Fullscreen,
-            #[cfg(target_os = "macos")]
-            is_simple_fullscreen: config.window.startup_mode() == StartupMode::SimpleFullscreen,
-            #[cfg(not(target_os = "macos"))]
-            is_simple_fullscreen: false,
+            option_as_alt: config.option_as_alt(),
         }
     }
 
@@ -350,9 +404,10 @@ impl<N: Notify> Processor<N> {
     fn handle_event<'a>(
         processor: &mut input::Processor<'a, ActionContext<'a, N>>,
         event: Event,
-        resize_tx: &mpsc::Sender<PhysicalSize>,
-        hide_mouse: &mut bool,
+        resize_tx: &mpsc::Sender<DisplayUpdate>,
+        pointer_hiding: &mut PointerHiding,
         window_is_focused: &mut bool,
+        dropped_files: &mut Vec<PathBuf>,
     ) {
         match event {
             // Pass on device events
@@ -360,21 +415,26 @@ impl<N: Notify> Processor<N> {
             Event::WindowEvent { event, .. } => {
                 use glutin::WindowEvent::*;
                 match event {
-                    CloseRequested => processor.ctx.terminal.exit(),
+                    // Embedded windows are destroyed together with their parent window
+                    CloseRequested | Destroyed => processor.ctx.terminal.exit(),
                     Resized(lsize) => {
                         // Resize events are emitted via glutin/winit with logical sizes
                         // However the terminal, window and renderer use physical sizes
                         // so a conversion must be done here
-                        resize_tx
-                            .send(lsize.to_physical(processor.ctx.size_info.dpr))
-                            .expect("send new size");
+                        let psize = lsize.to_physical(processor.ctx.size_info.dpr);
+                        resize_tx.send(DisplayUpdate::Resize(psize)).expect("send new size");
                         processor.ctx.terminal.dirty = true;
                     },
                     KeyboardInput { input, .. } => {
+                        let pressed = input.state == ElementState::Pressed;
+                        if pressed {
+                            processor.ctx.terminal.record_input(Instant::now());
+                        }
+
                         processor.process_key(input);
-                        if input.state == ElementState::Pressed {
+                        if pressed {
                             // Hide cursor while typing
-                            *hide_mouse = true;
+                            pointer_hiding.key_pressed();
                         }
                     },
                     ReceivedCharacter(c) => {
@@ -382,7 +442,8 @@ impl<N: Notify> Processor<N> {
                     },
                     MouseInput { state, button, modifiers, .. } => {
                         if !cfg!(target_os = "macos") || *window_is_focused {
-                            *hide_mouse = false;
+                            let over_content = processor.ctx.pointer_over_content();
+                            pointer_hiding.pointer_used(over_content, Instant::now());
                             processor.mouse_input(state, button, modifiers);
                             processor.ctx.terminal.dirty = true;
                         }
@@ -392,16 +453,18 @@ impl<N: Notify> Processor<N> {
                         let x: i32 = limit(x, 0, processor.ctx.size_info.width as i32);
                         let y: i32 = limit(y, 0, processor.ctx.size_info.height as i32);
 
-                        *hide_mouse = false;
-                        processor.mouse_moved(x as usize, y as usize, modifiers);
+                        let (x, y) = (x as usize, y as usize);
+                        let over_content = processor.ctx.size_info.contains_point(x, y, false);
+                        pointer_hiding.pointer_used(over_content, Instant::now());
+                        processor.mouse_moved(x, y, modifiers);
                     },
+                    CursorEntered { .. } | CursorLeft { .. } => pointer_hiding.pointer_crossed(),
                     MouseWheel { delta, phase, modifiers, .. } => {
-                        *hide_mouse = false;
+                        let over_content = processor.ctx.pointer_over_content();
+                        pointer_hiding.pointer_used(over_content, Instant::now());
                         processor.on_mouse_wheel(delta, phase, modifiers);
                     },
-                    Refresh => {
-                        processor.ctx.terminal.dirty = true;
-                    },
+                    Refresh => processor.ctx.terminal.request_redraw(),
                     Focused(is_focused) => {
                         *window_is_focused = is_focused;
 
@@ -412,18 +475,18 @@ impl<N: Notify> Processor<N> {
                             processor.ctx.terminal.reset_url_highlight();
                             processor.ctx.terminal.reset_mouse_cursor();
                             processor.ctx.terminal.dirty = true;
-                            *hide_mouse = false;
+                            pointer_hiding.pointer_crossed();
+
+                            // Option key releases are not received while unfocused
+                            *processor.ctx.option_keys = OptionKeys::default();
                         }
 
                         processor.on_focus_change(is_focused);
                     },
-                    DroppedFile(path) => {
-                        use crate::input::ActionContext;
-                        let path: String = path.to_string_lossy().into();
-                        processor.ctx.write_to_pty(path.into_bytes());
-                    },
+                    DroppedFile(path) => dropped_files.push(path),
                     HiDpiFactorChanged(new_dpr) => {
                         processor.ctx.size_info.dpr = new_dpr;
+                        resize_tx.send(DisplayUpdate::DprChange(new_dpr)).expect("send new dpr");
                         processor.ctx.terminal.dirty = true;
                     },
                     _ => (),
@@ -435,20 +498,63 @@ impl<N: Notify> Processor<N> {
         }
     }
 
-    /// Process events. When `wait_for_event` is set, this method is guaranteed
-    /// to process at least one event.
+    /// Queue an event of this processor's window
+    ///
+    /// All windows share the same event loop, so its events are routed to the
+    /// processors by the owner of the event loop.
+    #[inline]
+    pub fn push_event(&mut self, event: Event) {
+        self.pending_events.push(event);
+    }
+
+    /// Wake up the event loop at `deadline`
+    pub fn schedule_wakeup(&self, deadline: Instant) {
+        let now = Instant::now();
+        let delay = if deadline > now { deadline - now } else { Duration::from_secs(0) };
+        self.scheduler.schedule(delay);
+    }
+
+    /// Check if the event loop can wait for new events before this window has to be processed
+    #[inline]
+    pub fn is_idle(&self) -> bool {
+        self.wait_for_event && self.pending_events.is_empty()
+    }
+
+    /// Update the idle state after a frame has been drawn
+    ///
+    /// Drawing clears the dirty flag, unless an animation like the visual bell needs more
+    /// frames. Otherwise the event loop can block right away, instead of first processing
+    /// this window once more without any events.
+    #[inline]
+    pub fn frame_drawn(&mut self, animating: bool) {
+        self.wait_for_event = !animating && self.pending_events.is_empty();
+    }
+
+    /// Hold back drawing the dirty terminal until `deadline`
+    ///
+    /// The event loop can block until the wakeup at the deadline, instead of processing this
+    /// window over and over in the meantime.
+    pub fn postpone_frame(&mut self, deadline: Instant) {
+        self.schedule_wakeup(deadline);
+        self.wait_for_event = self.pending_events.is_empty();
+    }
+
+    /// Check if a new window has been requested since the last call
+    #[inline]
+    pub fn take_window_request(&mut self) -> bool {
+        mem::replace(&mut self.window_requested, false)
+    }
+
+    /// Process all queued events
     pub fn process_events<'a>(
         &mut self,
         term: &'a FairMutex<Term>,
         window: &mut Window,
     ) -> MutexGuard<'a, Term> {
-        // Terminal is lazily initialized the first time an event is returned
-        // from the blocking WaitEventsIterator. Otherwise, the pty reader would
-        // be blocked the entire time we wait for input!
+        // The terminal is only locked once the events have been received, otherwise the pty
+        // reader would be blocked the entire time we wait for input!
         let mut terminal;
 
-        self.pending_events.clear();
-
         {
             // Ditto on lazy initialization for context and processor.
             let context;
@@ -458,17 +564,6 @@ impl<N: Notify> Processor<N> {
 
             let resize_tx = &self.resize_tx;
 
-            if self.wait_for_event {
-                // A Vec is used here since wait_events can potentially yield
-                // multiple events before the interrupt is handled. For example,
-                // Resize and Moved events.
-                let pending_events = &mut self.pending_events;
-                window.wait_events(|e| {
-                    pending_events.push(e);
-                    glutin::ControlFlow::Break
-                });
-            }
-
             terminal = term.lock();
 
             context = ActionContext {
@@ -478,26 +573,44 @@ impl<N: Notify> Processor<N> {
                 size_info: &mut self.size_info,
                 received_count: &mut self.received_count,
                 suppress_chars: &mut self.suppress_chars,
+                composing: &mut self.composing,
                 last_modifiers: &mut self.last_modifiers,
+                option_keys: &mut self.option_keys,
+                pending_keys: &mut self.pending_keys,
+                ui_mode: &mut self.ui_mode,
                 window_changes: &mut self.window_changes,
+                scheduler: &self.scheduler,
+                processes: &self.processes,
             };
 
             processor = input::Processor {
                 ctx: context,
                 scrolling_config: &self.scrolling_config,
+                key_chords_config: &self.key_chords_config,
                 mouse_config: &self.mouse_config,
                 key_bindings: &self.key_bindings[..],
                 mouse_bindings: &self.mouse_bindings[..],
                 save_to_clipboard: self.save_to_clipboard,
                 alt_send_esc: self.alt_send_esc,
+                option_as_alt: self.option_as_alt,
             };
 
+            // Keys of an expired key chord are handled before the keys pressed after it
+            processor.check_key_chord_timeout();
+            processor.ctx.terminal_mut().expire_font_family_notice(Instant::now());
+
             let mut window_is_focused = window.is_focused;
 
-            // Scope needed to that hide_mouse isn't borrowed after the scope
+            // Report the focus state the application saw before the events of this batch
+            processor.report_focus_state(window_is_focused);
+
+            // Files dropped together are inserted at once
+            let mut dropped_files = Vec::new();
+
+            // Scope needed to that pointer_hiding isn't borrowed after the scope
             // ends.
             {
-                let hide_mouse = &mut self.hide_mouse;
+                let pointer_hiding = &mut self.pointer_hiding;
                 let mut process = |event| {
                     if print_events {
                         info!("glutin event: {:?}", event);
@@ -506,53 +619,212 @@ impl<N: Notify> Processor<N> {
                         &mut processor,
                         event,
                         resize_tx,
-                        hide_mouse,
+                        pointer_hiding,
                         &mut window_is_focused,
+                        &mut dropped_files,
                     );
                 };
 
-                for event in self.pending_events.drain(..) {
+                // Stop after the first resize so it is drawn before later events are handled,
+                // this keeps the window content in sync while it is being resized interactively
+                let batch_len = event_batch_len(&self.pending_events);
+                for event in self.pending_events.drain(..batch_len) {
                     process(event);
                 }
-
-                window.poll_events(process);
             }
 
-            if self.hide_mouse_when_typing {
-                window.set_mouse_visible(!self.hide_mouse);
+            processor.on_dropped_files(&dropped_files);
+
+            // Wake up again once the pointer could have been inactive long enough to hide it
+            let now = Instant::now();
+            if let Some(deadline) = self.pointer_hiding.update(self.mouse_config.hide_delay(), now)
+            {
+                self.scheduler.schedule(deadline - now);
             }
+            window.set_mouse_visible(self.pointer_hiding.visible(self.hide_mouse_when_typing));
 
-            window.is_focused = window_is_focused;
+            window.set_focused(window_is_focused);
         }
 
         if self.window_changes.hide {
             window.hide();
         }
 
+        let mut restored_size = None;
+
         #[cfg(target_os = "macos")]
         {
-            if self.window_changes.toggle_simple_fullscreen && !self.is_fullscreen {
-                window.set_simple_fullscreen(!self.is_simple_fullscreen);
-                self.is_simple_fullscreen = !self.is_simple_fullscreen;
+            if self.window_changes.toggle_simple_fullscreen {
+                restored_size = window.toggle_simple_fullscreen();
             }
         }
 
-        if self.window_changes.toggle_fullscreen && !self.is_simple_fullscreen {
-            window.set_fullscreen(!self.is_fullscreen);
-            self.is_fullscreen = !self.is_fullscreen;
+        if self.window_changes.toggle_fullscreen {
+            restored_size = window.toggle_fullscreen().or(restored_size);
+        }
+
+        if self.window_changes.toggle_maximized {
+            window.toggle_maximized();
+        }
+
+        for change in self.window_changes.opacity.drain(..) {
+            window.change_opacity(change);
         }
 
+        // Glutin doesn't always report the size change after leaving fullscreen
+        if let Some(size) = restored_size {
+            let psize = size.to_physical(window.hidpi_factor());
+            self.resize_tx.send(DisplayUpdate::Resize(psize)).expect("send new size");
+            terminal.dirty = true;
+        }
+
+        self.window_requested |= self.window_changes.create_window;
         self.window_changes.clear();
-        self.wait_for_event = !terminal.dirty;
+        self.wait_for_event = !terminal.dirty && self.pending_events.is_empty();
 
         terminal
     }
 
-    pub fn update_config(&mut self, config: &Config) {
+    pub fn update_bindings(&mut self, config: &Config) {
         self.key_bindings = config.key_bindings.to_vec();
         self.mouse_bindings = config.mouse_bindings.to_vec();
+        self.key_chords_config = config.key_chords;
+    }
+
+    pub fn update_config(&mut self, config: &Config) {
         self.mouse_config = config.mouse.to_owned();
+        self.scrolling_config = config.scrolling;
+        self.hide_mouse_when_typing = config.mouse.hide_when_typing;
         self.save_to_clipboard = config.selection.save_to_clipboard;
         self.alt_send_esc = config.alt_send_esc();
+        self.option_as_alt = config.option_as_alt();
+    }
+}
+
+/// Number of pending events which should be processed before the next draw
+///
+/// The batch ends right after the first resize event, all following events are
+/// kept in the queue until the resized terminal has been rendered.
+pub(crate) fn event_batch_len(events: &[Event]) -> usize {
+    events
+        .iter()
+        .position(|event| match event {
+            Event::WindowEvent { event: WindowEvent::Resized(_), .. } => true,
+            _ => false,
+        })
+        .map(|index| index + 1)
+        .unwrap_or_else(|| events.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use glutin::dpi::LogicalSize;
+    use glutin::{Event, WindowEvent, WindowId};
+
+    use super::{event_batch_len, PointerHiding};
+
+    fn window_event(event: WindowEvent) -> Event {
+        Event::WindowEvent { window_id: unsafe { WindowId::dummy() }, event }
+    }
+
+    fn resized(width: f64, height: f64) -> Event {
+        window_event(WindowEvent::Resized(LogicalSize::new(width, height)))
+    }
+
+    #[test]
+    fn batch_without_resize() {
+        let events = vec![window_event(WindowEvent::Refresh), window_event(WindowEvent::Refresh)];
+
+        assert_eq!(event_batch_len(&events), 2);
+        assert_eq!(event_batch_len(&[]), 0);
+    }
+
+    #[test]
+    fn batch_ends_after_resize() {
+        let events = vec![
+            window_event(WindowEvent::Refresh),
+            resized(100., 100.),
+            window_event(WindowEvent::Refresh),
+            resized(120., 110.),
+        ];
+
+        assert_eq!(event_batch_len(&events), 2);
+        assert_eq!(event_batch_len(&events[2..]), 2);
+        assert_eq!(event_batch_len(&events[1..2]), 1);
+    }
+
+    #[test]
+    fn idle_pointer_is_hidden() {
+        let delay = Some(Duration::from_secs(2));
+        let mut pointer = PointerHiding::default();
+        let start = pointer.last_activity;
+        let at = |ms| start + Duration::from_millis(ms);
+
+        pointer.pointer_used(true, at(0));
+        assert_eq!(pointer.update(delay, at(0)), Some(at(2000)));
+
+        // Motion restarts the inactivity, the pending timer is kept
+        pointer.pointer_used(true, at(1500));
+        assert_eq!(pointer.update(delay, at(1500)), None);
+        assert_eq!(pointer.update(delay, at(2000)), Some(at(3500)));
+        assert!(pointer.visible(false));
+
+        assert_eq!(pointer.update(delay, at(3500)), None);
+        assert!(!pointer.visible(false));
+
+        // Hidden pointers don't need any timers
+        assert_eq!(pointer.update(delay, at(5000)), None);
+
+        pointer.pointer_used(true, at(6000));
+        assert!(pointer.visible(false));
+    }
+
+    #[test]
+    fn pointer_outside_content_stays_visible() {
+        let delay = Some(Duration::from_secs(2));
+        let mut pointer = PointerHiding::default();
+        let start = pointer.last_activity;
+
+        pointer.pointer_used(false, start);
+        assert_eq!(pointer.update(delay, start + Duration::from_secs(5)), None);
+        assert!(pointer.visible(false));
+
+        // Leaving the window shows a hidden pointer again
+        pointer.pointer_used(true, start);
+        assert_eq!(pointer.update(delay, start + Duration::from_secs(5)), None);
+        assert!(!pointer.visible(false));
+        pointer.pointer_crossed();
+        assert!(pointer.visible(false));
+
+        // Never hidden without delay
+        pointer.pointer_used(true, start);
+        assert_eq!(pointer.update(None, start + Duration::from_secs(60)), None);
+        assert!(pointer.visible(false));
+    }
+
+    #[test]
+    fn typing_and_idle_hiding() {
+        let delay = Some(Duration::from_secs(2));
+        let mut pointer = PointerHiding::default();
+        let start = pointer.last_activity;
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Typing only hides the pointer if enabled
+        pointer.pointer_used(true, at(0));
+        pointer.key_pressed();
+        assert!(!pointer.visible(true));
+        assert!(pointer.visible(false));
+
+        // Typing doesn't count as pointer activity, the idle timer still fires
+        assert_eq!(pointer.update(delay, at(1)), Some(at(2)));
+        pointer.key_pressed();
+        assert_eq!(pointer.update(delay, at(2)), None);
+        assert!(!pointer.visible(false));
+
+        // Using the pointer again ends both kinds of hiding
+        pointer.pointer_used(true, at(3));
+        assert!(pointer.visible(true));
     }
 }
@@ -5,6 +5,7 @@ use std::fs::File;
 use std::io::{self, ErrorKind, Read, Write};
 use std::marker::Send;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use mio::{self, Events, PollOpt, Ready};
 use mio_extras::channel::{self, Receiver, Sender};
@@ -13,9 +14,11 @@ use mio_extras::channel::{self, Receiver, Sender};
 use mio::unix::UnixReady;
 
 use crate::ansi;
-use crate::display;
-use crate::event;
+use crate::config::Config;
+use crate::message_bar::Message;
+use crate::recording::Recorder;
 use crate::sync::FairMutex;
+use crate::term::color;
 use crate::term::Term;
 use crate::tty;
 use crate::util::thread;
@@ -30,18 +33,47 @@ pub enum Msg {
     Shutdown,
 }
 
+/// Byte sequences are sent to a `Notify` in response to some events
+pub trait Notify {
+    /// Notify that an escape sequence should be written to the pty
+    ///
+    /// TODO this needs to be able to error somehow
+    fn notify<B: Into<Cow<'static, [u8]>>>(&mut self, _: B);
+}
+
+/// Types which are woken up when the terminal has new content to draw
+///
+/// Output is only announced while the terminal isn't dirty already, so a burst of pty reads
+/// results in a single wakeup until the next frame starts drawing. The first read after the
+/// terminal has been drawn always wakes up right away.
+pub trait OnWakeup {
+    fn on_wakeup(&self);
+}
+
 /// The main event!.. loop.
 ///
 /// Handles all the pty I/O and runs the pty parser which updates terminal
 /// state.
-pub struct EventLoop<T: tty::EventedPty> {
+pub struct EventLoop<T: tty::EventedPty, W: OnWakeup> {
     poll: mio::Poll,
     pty: T,
     rx: Receiver<Msg>,
     tx: Sender<Msg>,
     terminal: Arc<FairMutex<Term>>,
-    display: display::Notifier,
+    wakeup: W,
     ref_test: bool,
+
+    /// Keep the terminal open after the child process exited
+    hold: bool,
+
+    /// Time the terminal stays open after the child process failed
+    exit_status_delay: Duration,
+
+    /// Size of the buffer for pty output
+    read_buffer_size: usize,
+
+    /// Log every pty read and the escape sequences parsed from it
+    print_events: bool,
 }
 
 /// Helper type which tracks how much of a buffer has been written.
@@ -78,11 +110,28 @@ pub struct State {
     write_list: VecDeque<Cow<'static, [u8]>>,
     writing: Option<Writing>,
     parser: ansi::Processor,
+
+    /// Handling of the terminal after the child process exited, the pty is not polled anymore
+    child_exit: Option<ChildExit>,
+}
+
+/// What happens to the terminal after its child process exited
+#[cfg_attr(windows, allow(dead_code))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ChildExit {
+    /// Close the terminal right away
+    Close,
+
+    /// Close the terminal once the exit status has been shown long enough
+    CloseAt(Instant),
+
+    /// Keep the terminal open until it is closed
+    Hold,
 }
 
 pub struct Notifier(pub Sender<Msg>);
 
-impl event::Notify for Notifier {
+impl Notify for Notifier {
     fn notify<B>(&mut self, bytes: B)
     where
         B: Into<Cow<'static, [u8]>>,
@@ -100,7 +149,12 @@ impl event::Notify for Notifier {
 
 impl Default for State {
     fn default() -> State {
-        State { write_list: VecDeque::new(), parser: ansi::Processor::new(), writing: None }
+        State {
+            write_list: VecDeque::new(),
+            parser: ansi::Processor::new(),
+            writing: None,
+            child_exit: None,
+        }
     }
 }
 
@@ -155,17 +209,18 @@ impl Writing {
     }
 }
 
-impl<T> EventLoop<T>
+impl<T, W> EventLoop<T, W>
 where
     T: tty::EventedPty + Send + 'static,
+    W: OnWakeup + Send + 'static,
 {
     /// Create a new event loop
     pub fn new(
         terminal: Arc<FairMutex<Term>>,
-        display: display::Notifier,
+        wakeup: W,
         pty: T,
-        ref_test: bool,
-    ) -> EventLoop<T> {
+        config: &Config,
+    ) -> EventLoop<T, W> {
         let (tx, rx) = channel::channel();
         EventLoop {
             poll: mio::Poll::new().expect("create mio Poll"),
@@ -173,8 +228,12 @@ where
             tx,
             rx,
             terminal,
-            display,
-            ref_test,
+            wakeup,
+            ref_test: config.debug.ref_test,
+            hold: config.hold,
+            exit_status_delay: config.exit_status_delay(),
+            read_buffer_size: config.pty_read_buffer_size(),
+            print_events: config.debug.print_events,
         }
     }
 
@@ -222,73 +281,10 @@ where
     }
 
     #[inline]
-    fn pty_read<X>(
-        &mut self,
-        state: &mut State,
-        buf: &mut [u8],
-        mut writer: Option<&mut X>,
-    ) -> io::Result<()>
-    where
-        X: Write,
-    {
-        const MAX_READ: usize = 0x1_0000;
-        let mut processed = 0;
-        let mut terminal = None;
-
-        // Flag to keep track if wakeup has already been sent
-        let mut send_wakeup = false;
-
-        loop {
-            match self.pty.reader().read(&mut buf[..]) {
-                Ok(0) => break,
-                Ok(got) => {
-                    // Record bytes read; used to limit time spent in pty_read.
-                    processed += got;
-
-                    // Send a copy of bytes read to a subscriber. Used for
-                    // example with ref test recording.
-                    writer = writer.map(|w| {
-                        w.write_all(&buf[..got]).unwrap();
-                        w
-                    });
-
-                    // Get reference to terminal. Lock is acquired on initial
-                    // iteration and held until there's no bytes left to parse
-                    // or we've reached MAX_READ.
-                    let terminal = if terminal.is_none() {
-                        terminal = Some(self.terminal.lock());
-                        let terminal = terminal.as_mut().unwrap();
-                        send_wakeup = !terminal.dirty;
-                        terminal
-                    } else {
-                        terminal.as_mut().unwrap()
-                    };
-
-                    // Run the parser
-                    for byte in &buf[..got] {
-                        state.parser.advance(&mut **terminal, *byte, &mut self.pty.writer());
-                    }
-
-                    // Exit if we've processed enough bytes
-                    if processed > MAX_READ {
-                        break;
-                    }
-                },
-                Err(err) => match err.kind() {
-                    ErrorKind::Interrupted | ErrorKind::WouldBlock => {
-                        break;
-                    },
-                    _ => return Err(err),
-                },
-            }
-        }
-
-        // Only request a draw if one hasn't already been requested.
-        if let Some(mut terminal) = terminal {
-            if send_wakeup {
-                self.display.notify();
-                terminal.dirty = true;
-            }
+    fn pty_read(&mut self, state: &mut State, buf: &mut [u8]) -> io::Result<()> {
+        let terminal = &self.terminal;
+        if parse_pty_output(&mut self.pty, &mut state.parser, terminal, self.print_events, buf)? {
+            self.wakeup.on_wakeup();
         }
 
         Ok(())
@@ -329,7 +325,7 @@ where
     pub fn spawn(mut self, state: Option<State>) -> thread::JoinHandle<(Self, State)> {
         thread::spawn_named("pty reader", move || {
             let mut state = state.unwrap_or_else(Default::default);
-            let mut buf = [0u8; 0x1000];
+            let mut buf = vec![0u8; self.read_buffer_size];
 
             let mut tokens = (0..).map(Into::into);
 
@@ -343,20 +339,39 @@ where
 
             let mut events = Events::with_capacity(1024);
 
-            let mut pipe = if self.ref_test {
-                Some(File::create("./alacritty.recording").expect("create alacritty recording"))
-            } else {
-                None
-            };
+            if self.ref_test {
+                let path = "./alacritty.recording";
+                let file: Box<dyn Write + Send> =
+                    Box::new(File::create(path).expect("create alacritty recording"));
+                let recorder = Recorder::new(file).expect("write alacritty recording");
+                self.terminal.lock().start_recording(recorder);
+            }
 
             'event_loop: loop {
-                if let Err(err) = self.poll.poll(&mut events, None) {
+                // Wake up to close the terminal after the exit status has been shown
+                let timeout = match state.child_exit {
+                    Some(ChildExit::CloseAt(deadline)) => {
+                        let now = Instant::now();
+                        Some(if deadline > now { deadline - now } else { Duration::from_secs(0) })
+                    },
+                    _ => None,
+                };
+
+                if let Err(err) = self.poll.poll(&mut events, timeout) {
                     match err.kind() {
                         ErrorKind::Interrupted => continue,
                         _ => panic!("EventLoop polling error: {:?}", err),
                     }
                 }
 
+                if let Some(ChildExit::CloseAt(deadline)) = state.child_exit {
+                    if deadline <= Instant::now() {
+                        self.terminal.lock().exit();
+                        self.wakeup.on_wakeup();
+                        break 'event_loop;
+                    }
+                }
+
                 for event in events.iter() {
                     match event.token() {
                         token if token == channel_token => {
@@ -365,18 +380,35 @@ where
                             }
                         },
 
-                        #[cfg(unix)]
                         token if token == self.pty.child_event_token() => {
-                            if let Some(tty::ChildEvent::Exited) = self.pty.next_child_event() {
-                                self.terminal.lock().exit();
-                                self.display.notify();
-                                break 'event_loop;
+                            let exit = process_child_event(
+                                &mut self.pty,
+                                &self.terminal,
+                                self.hold,
+                                self.exit_status_delay,
+                            );
+
+                            match exit {
+                                Some(ChildExit::Close) => {
+                                    self.terminal.lock().exit();
+                                    self.wakeup.on_wakeup();
+                                    break 'event_loop;
+                                },
+                                Some(exit) => {
+                                    // Read the last output before the pty stops being polled
+                                    let _ = self.pty_read(&mut state, &mut buf);
+                                    let _ = self.pty.deregister(&self.poll);
+                                    state.child_exit = Some(exit);
+                                    self.wakeup.on_wakeup();
+                                },
+                                None => (),
                             }
                         },
 
                         token
-                            if token == self.pty.read_token()
-                                || token == self.pty.write_token() =>
+                            if state.child_exit.is_none()
+                                && (token == self.pty.read_token()
+                                    || token == self.pty.write_token()) =>
                         {
                             #[cfg(unix)]
                             {
@@ -387,7 +419,7 @@ where
                             }
 
                             if event.readiness().is_readable() {
-                                if let Err(e) = self.pty_read(&mut state, &mut buf, pipe.as_mut()) {
+                                if let Err(e) = self.pty_read(&mut state, &mut buf) {
                                     #[cfg(target_os = "linux")]
                                     {
                                         // On Linux, a `read` on the master side of a PTY can fail
@@ -411,11 +443,15 @@ where
                                     break 'event_loop;
                                 }
                             }
-                        }
+                        },
                         _ => (),
                     }
                 }
 
+                if state.child_exit.is_some() {
+                    continue;
+                }
+
                 // Register write interest if necessary
                 let mut interest = Ready::readable();
                 if state.needs_write() {
@@ -434,3 +470,572 @@ where
         })
     }
 }
+
+/// Maximum number of bytes parsed before the terminal is unlocked
+const READ_BYTE_BUDGET: usize = 0x10_0000;
+
+/// Maximum time the terminal is locked for parsing, half of a frame at 60 Hz
+const READ_TIME_BUDGET: Duration = Duration::from_millis(8);
+
+/// Limits how much pty output is parsed while the terminal is locked
+///
+/// Once the budget is used up the terminal is unlocked, so keyboard input and drawing don't
+/// have to wait until a flood of output has been parsed.
+#[derive(Debug, Copy, Clone)]
+struct ReadBudget {
+    /// Bytes which may still be parsed
+    bytes: usize,
+
+    /// Time parsing has to stop at
+    deadline: Instant,
+}
+
+impl ReadBudget {
+    fn new(now: Instant) -> ReadBudget {
+        ReadBudget { bytes: READ_BYTE_BUDGET, deadline: now + READ_TIME_BUDGET }
+    }
+
+    /// Use up `bytes` of the budget, returns `false` once parsing has to stop
+    fn consume(&mut self, bytes: usize, now: Instant) -> bool {
+        self.bytes = self.bytes.saturating_sub(bytes);
+        self.bytes > 0 && now < self.deadline
+    }
+}
+
+/// Parse pty output until the pty has been drained or the read budget is used up
+///
+/// The terminal is locked once for all reads, so a single draw is requested for all of them.
+/// With `print_events`, the size of every read and all handler calls are logged.
+/// Returns `true` if the display has to be woken up to draw the output.
+fn parse_pty_output<T>(
+    pty: &mut T,
+    parser: &mut ansi::Processor,
+    terminal: &FairMutex<Term>,
+    print_events: bool,
+    buf: &mut [u8],
+) -> io::Result<bool>
+where
+    T: tty::EventedReadWrite,
+{
+    let mut budget = ReadBudget::new(Instant::now());
+    let mut locked = None;
+
+    // Flag to keep track if wakeup has already been sent
+    let mut send_wakeup = false;
+
+    loop {
+        match pty.reader().read(buf) {
+            Ok(0) => break,
+            Ok(got) => {
+                let read_at = Instant::now();
+
+                // Get reference to terminal. Lock is acquired on initial
+                // iteration and held until there's no bytes left to parse
+                // or the budget is used up.
+                let terminal = if locked.is_none() {
+                    locked = Some(terminal.lock());
+                    let terminal = locked.as_mut().unwrap();
+                    send_wakeup = !terminal.dirty;
+                    terminal
+                } else {
+                    locked.as_mut().unwrap()
+                };
+
+                terminal.record_input(read_at);
+
+                // Run the parser
+                if print_events {
+                    info!("Pty read: {} bytes", got);
+                    let mut handler = ansi::DebugHandler::new(&mut **terminal);
+                    parser.advance_bytes(&mut handler, &buf[..got], pty.writer());
+                } else {
+                    parser.advance_bytes(&mut **terminal, &buf[..got], pty.writer());
+                }
+                terminal.record_pty_read(&buf[..got]);
+                terminal.set_unhandled_stats(parser.unhandled_stats());
+
+                if !budget.consume(got, Instant::now()) {
+                    break;
+                }
+            },
+            Err(err) => match err.kind() {
+                ErrorKind::Interrupted | ErrorKind::WouldBlock => break,
+                _ => return Err(err),
+            },
+        }
+    }
+
+    // Only request a draw if one hasn't already been requested.
+    match locked {
+        Some(mut terminal) if send_wakeup => {
+            terminal.dirty = true;
+            Ok(true)
+        },
+        _ => Ok(false),
+    }
+}
+
+/// Handle the next event of the child process watcher
+///
+/// The exit status is stored in the terminal. Unless the child exited successfully, it's also
+/// shown in the message bar before the terminal is closed.
+fn process_child_event<T: tty::EventedPty>(
+    pty: &mut T,
+    terminal: &FairMutex<Term>,
+    hold: bool,
+    exit_status_delay: Duration,
+) -> Option<ChildExit> {
+    let status = match pty.next_child_event()? {
+        tty::ChildEvent::Exited(status) => status,
+    };
+
+    let description = tty::exit_description(status);
+    info!("{}", description);
+
+    let mut terminal = terminal.lock();
+    terminal.set_exit_status(status);
+
+    let exit = if hold {
+        ChildExit::Hold
+    } else if status.success() || exit_status_delay == Duration::from_secs(0) {
+        return Some(ChildExit::Close);
+    } else {
+        ChildExit::CloseAt(Instant::now() + exit_status_delay)
+    };
+
+    let color = if status.success() { color::YELLOW } else { color::RED };
+    let _ = terminal.message_buffer().tx().send(Message::new(description, color));
+    terminal.dirty = true;
+
+    Some(exit)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::{self, Read};
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::ansi::{self, Handler};
+    use crate::clipboard::Clipboard;
+    use crate::config::Config;
+    use crate::index::{Column, Line};
+    use crate::message_bar::{Message, MessageBuffer};
+    use crate::sync::FairMutex;
+    use crate::term::cell::Flags;
+    use crate::term::{color, SizeInfo, Term};
+    use crate::tty::{ChildEvent, EventedPty, EventedReadWrite};
+
+    use super::{parse_pty_output, process_child_event, ChildExit, ReadBudget};
+    use super::{EventLoop, OnWakeup, State, READ_BYTE_BUDGET, READ_TIME_BUDGET};
+
+    /// Pty with a child process watcher which reports queued events
+    struct MockPty<R = io::Empty> {
+        child_events: VecDeque<ChildEvent>,
+        reader: R,
+        writer: io::Sink,
+    }
+
+    impl MockPty {
+        fn new(child_events: Vec<ChildEvent>) -> Self {
+            MockPty { child_events: child_events.into(), reader: io::empty(), writer: io::sink() }
+        }
+    }
+
+    impl<R: Read> MockPty<R> {
+        /// Pty which produces the output of `reader`
+        fn reading(reader: R) -> Self {
+            MockPty { child_events: VecDeque::new(), reader, writer: io::sink() }
+        }
+    }
+
+    impl<R: Read> EventedReadWrite for MockPty<R> {
+        type Reader = R;
+        type Writer = io::Sink;
+
+        fn register(
+            &mut self,
+            _: &mio::Poll,
+            _: &mut dyn Iterator<Item = mio::Token>,
+            _: mio::Ready,
+            _: mio::PollOpt,
+        ) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn reregister(&mut self, _: &mio::Poll, _: mio::Ready, _: mio::PollOpt) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn deregister(&mut self, _: &mio::Poll) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn reader(&mut self) -> &mut R {
+            &mut self.reader
+        }
+
+        fn read_token(&self) -> mio::Token {
+            mio::Token(0)
+        }
+
+        fn writer(&mut self) -> &mut io::Sink {
+            &mut self.writer
+        }
+
+        fn write_token(&self) -> mio::Token {
+            mio::Token(0)
+        }
+    }
+
+    impl<R: Read> EventedPty for MockPty<R> {
+        fn child_event_token(&self) -> mio::Token {
+            mio::Token(1)
+        }
+
+        fn next_child_event(&mut self) -> Option<ChildEvent> {
+            self.child_events.pop_front()
+        }
+    }
+
+    /// Output of a program doing many tiny writes, every read returns a single byte
+    struct Trickle {
+        pending: bool,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.pending = !self.pending;
+            if self.pending {
+                buf[0] = b'x';
+                Ok(1)
+            } else {
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        }
+    }
+
+    /// Wakeup target which counts how often it has been woken up
+    #[derive(Clone, Default)]
+    struct CountingWakeup(Arc<AtomicUsize>);
+
+    impl CountingWakeup {
+        fn count(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    impl OnWakeup for CountingWakeup {
+        fn on_wakeup(&self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn terminal() -> FairMutex<Term> {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let config = Config::default();
+        FairMutex::new(Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop()))
+    }
+
+    /// Exit status of a child which exited with `code`
+    fn exited(code: i32) -> ChildEvent {
+        ChildEvent::Exited(ExitStatus::from_raw(code << 8))
+    }
+
+    #[test]
+    fn no_child_event() {
+        let terminal = terminal();
+        let mut pty = MockPty::new(Vec::new());
+
+        let exit = process_child_event(&mut pty, &terminal, false, Duration::from_secs(3));
+
+        assert_eq!(exit, None);
+        assert_eq!(terminal.lock().exit_status(), None);
+    }
+
+    #[test]
+    fn success_closes_right_away() {
+        let terminal = terminal();
+        let mut pty = MockPty::new(vec![exited(0)]);
+
+        let exit = process_child_event(&mut pty, &terminal, false, Duration::from_secs(3));
+
+        assert_eq!(exit, Some(ChildExit::Close));
+        let mut terminal = terminal.lock();
+        assert_eq!(terminal.exit_status().and_then(|status| status.code()), Some(0));
+        assert_eq!(terminal.message_buffer_mut().message(), None);
+    }
+
+    #[test]
+    fn failure_is_shown_before_closing() {
+        let terminal = terminal();
+        let mut pty = MockPty::new(vec![exited(2)]);
+
+        let before = Instant::now();
+        let exit = process_child_event(&mut pty, &terminal, false, Duration::from_secs(3));
+
+        match exit {
+            Some(ChildExit::CloseAt(deadline)) => {
+                assert!(deadline >= before + Duration::from_secs(3));
+            },
+            exit => panic!("unexpected child exit: {:?}", exit),
+        }
+
+        let mut terminal = terminal.lock();
+        assert_eq!(terminal.exit_status().and_then(|status| status.code()), Some(2));
+        assert_eq!(
+            terminal.message_buffer_mut().message(),
+            Some(Message::new("Process exited with code 2".into(), color::RED))
+        );
+    }
+
+    #[test]
+    fn failure_without_delay_closes_right_away() {
+        let terminal = terminal();
+        let mut pty = MockPty::new(vec![exited(2)]);
+
+        let exit = process_child_event(&mut pty, &terminal, false, Duration::from_secs(0));
+
+        assert_eq!(exit, Some(ChildExit::Close));
+        assert_eq!(terminal.lock().exit_status().and_then(|status| status.code()), Some(2));
+    }
+
+    #[test]
+    fn signal_is_shown() {
+        let terminal = terminal();
+        let mut pty = MockPty::new(vec![ChildEvent::Exited(ExitStatus::from_raw(libc::SIGKILL))]);
+
+        process_child_event(&mut pty, &terminal, false, Duration::from_secs(3));
+
+        let mut terminal = terminal.lock();
+        assert_eq!(terminal.exit_status().and_then(|status| status.signal()), Some(libc::SIGKILL));
+        assert_eq!(
+            terminal.message_buffer_mut().message(),
+            Some(Message::new("Process was terminated by SIGKILL".into(), color::RED))
+        );
+    }
+
+    #[test]
+    fn hold_always_shows_status() {
+        let terminal = terminal();
+        let mut pty = MockPty::new(vec![exited(0)]);
+
+        let exit = process_child_event(&mut pty, &terminal, true, Duration::from_secs(3));
+
+        assert_eq!(exit, Some(ChildExit::Hold));
+        assert_eq!(
+            terminal.lock().message_buffer_mut().message(),
+            Some(Message::new("Process exited with code 0".into(), color::YELLOW))
+        );
+    }
+
+    /// Parse everything the pty has to offer, like the event loop does when it's readable
+    fn parse<R: Read>(pty: &mut MockPty<R>, terminal: &FairMutex<Term>, buf: &mut [u8]) -> bool {
+        let mut parser = ansi::Processor::new();
+        parse_pty_output(pty, &mut parser, terminal, false, buf).unwrap()
+    }
+
+    #[test]
+    fn read_budget_limits_bytes() {
+        let now = Instant::now();
+        let mut budget = ReadBudget::new(now);
+
+        assert!(budget.consume(READ_BYTE_BUDGET - 1, now));
+        assert!(!budget.consume(1, now));
+    }
+
+    #[test]
+    fn read_budget_limits_time() {
+        let now = Instant::now();
+        let mut budget = ReadBudget::new(now);
+
+        assert!(budget.consume(1, now + READ_TIME_BUDGET / 2));
+        assert!(!budget.consume(1, now + READ_TIME_BUDGET));
+    }
+
+    #[test]
+    fn reads_are_parsed_together() {
+        let terminal = terminal();
+
+        // Takes multiple reads, the escape sequence is split between them
+        let mut pty = MockPty::reading(&b"ab\x1b[1mcd"[..]);
+        assert!(parse(&mut pty, &terminal, &mut [0; 4]));
+
+        let terminal = terminal.lock();
+        assert!(terminal.dirty);
+        assert_eq!(terminal.grid()[Line(0)][Column(1)].c, 'b');
+        assert_eq!(terminal.grid()[Line(0)][Column(3)].c, 'd');
+        assert!(terminal.grid()[Line(0)][Column(3)].flags.contains(Flags::BOLD));
+    }
+
+    #[test]
+    fn single_wakeup_until_drawn() {
+        let terminal = terminal();
+
+        assert!(parse(&mut MockPty::reading(&b"ab"[..]), &terminal, &mut [0; 4]));
+
+        // The draw which has been requested already includes this output
+        assert!(!parse(&mut MockPty::reading(&b"cd"[..]), &terminal, &mut [0; 4]));
+
+        // Nothing to read doesn't need a draw
+        terminal.lock().dirty = false;
+        assert!(!parse(&mut MockPty::new(Vec::new()), &terminal, &mut [0; 4]));
+    }
+
+    /// Event loop reading a trickle of output, together with its wakeup counter
+    fn trickling_event_loop(
+        terminal: &Arc<FairMutex<Term>>,
+    ) -> (EventLoop<MockPty<Trickle>, CountingWakeup>, CountingWakeup) {
+        let wakeup = CountingWakeup::default();
+        let pty = MockPty::reading(Trickle { pending: false });
+        let event_loop = EventLoop::new(terminal.clone(), wakeup.clone(), pty, &Config::default());
+        (event_loop, wakeup)
+    }
+
+    #[test]
+    fn flood_wakes_up_once_per_frame() {
+        let terminal = Arc::new(terminal());
+        let (mut event_loop, wakeup) = trickling_event_loop(&terminal);
+        let mut state = State::default();
+        let mut buf = [0; 16];
+
+        for frame in 1..=3 {
+            for _ in 0..1000 {
+                event_loop.pty_read(&mut state, &mut buf).unwrap();
+            }
+            assert_eq!(wakeup.count(), frame);
+
+            // Starting to draw the frame consumes the wakeup
+            terminal.lock().dirty = false;
+        }
+    }
+
+    #[test]
+    fn first_read_after_idle_wakes_up_immediately() {
+        let terminal = Arc::new(terminal());
+        let (mut event_loop, wakeup) = trickling_event_loop(&terminal);
+        let mut state = State::default();
+        let mut buf = [0; 16];
+
+        for wakeups in 1..=2 {
+            // The wakeup is sent by the read itself instead of being deferred
+            let start = Instant::now();
+            event_loop.pty_read(&mut state, &mut buf).unwrap();
+            assert_eq!(wakeup.count(), wakeups);
+            assert!(start.elapsed() < Duration::from_millis(50));
+
+            terminal.lock().dirty = false;
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn input_is_handled_during_flood() {
+        let terminal = Arc::new(terminal());
+        let flooding = Arc::new(AtomicBool::new(true));
+
+        let reader = {
+            let (terminal, flooding) = (terminal.clone(), flooding.clone());
+            thread::spawn(move || {
+                // Output which never ends, like `cat /dev/urandom`
+                let mut pty = MockPty::reading(io::repeat(b'x'));
+                let mut buf = [0; 0x1000];
+                while flooding.load(Ordering::Relaxed) {
+                    parse(&mut pty, &terminal, &mut buf);
+                }
+            })
+        };
+
+        // Let the flood keep the reader busy
+        thread::sleep(Duration::from_millis(50));
+
+        // Keyboard input has to lock the terminal, before it's echoed by the shell
+        let start = Instant::now();
+        terminal.lock().input('a');
+        let latency = start.elapsed();
+
+        flooding.store(false, Ordering::Relaxed);
+        reader.join().unwrap();
+
+        // The reader unlocks the terminal at least once per read budget, the limit leaves some
+        // slack for slow test machines
+        assert!(latency < Duration::from_millis(50), "input waited for {:?}", latency);
+    }
+
+    #[cfg(feature = "bench")]
+    mod benches {
+        extern crate test;
+
+        use std::io::{self, Read};
+
+        use crate::ansi;
+
+        use super::super::parse_pty_output;
+        use super::{terminal, MockPty};
+
+        /// Output of a program like `cat largefile`, with some colored words
+        fn output() -> Vec<u8> {
+            (0..20_000)
+                .flat_map(|i| format!("{} lorem \x1b[32mipsum\x1b[0m dolor\r\n", i).into_bytes())
+                .collect()
+        }
+
+        /// Parsing small reads byte by byte, like before reads were parsed in batches
+        #[bench]
+        fn pty_output_per_byte(b: &mut test::Bencher) {
+            let output = output();
+            let terminal = terminal();
+            let mut parser = ansi::Processor::new();
+
+            b.bytes = output.len() as u64;
+            b.iter(|| {
+                let mut reader = &output[..];
+                let mut buf = [0; 0x1000];
+                let mut terminal = terminal.lock();
+                loop {
+                    let got = reader.read(&mut buf).unwrap();
+                    if got == 0 {
+                        break;
+                    }
+
+                    for &byte in &buf[..got] {
+                        parser.advance(&mut *terminal, byte, &mut io::sink());
+                    }
+                }
+            });
+        }
+
+        /// Parsing the default read buffer size in batches
+        #[bench]
+        fn pty_output_batched(b: &mut test::Bencher) {
+            let output = output();
+            let terminal = terminal();
+            let mut parser = ansi::Processor::new();
+
+            b.bytes = output.len() as u64;
+            b.iter(|| {
+                let mut pty = MockPty::reading(&output[..]);
+                let mut buf = vec![0; 0x1_0000];
+                while !pty.reader.is_empty() {
+                    parse_pty_output(&mut pty, &mut parser, &terminal, false, &mut buf).unwrap();
+                }
+            });
+        }
+    }
+}
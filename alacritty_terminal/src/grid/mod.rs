@@ -105,6 +105,14 @@ pub struct Grid<T> {
     #[serde(default)]
     max_scroll_limit: usize,
 
+    /// Approximate memory used by the lines in the scrollback history, in bytes
+    #[serde(skip)]
+    history_memory: usize,
+
+    /// Limit on the memory used by the scrollback history, in bytes
+    #[serde(skip)]
+    max_history_memory: Option<usize>,
+
     /// Range for URL hover highlights
     #[serde(default)]
     pub url_highlight: Option<RangeInclusive<index::Linear>>,
@@ -119,7 +127,7 @@ pub enum Scroll {
     Bottom,
 }
 
-impl<T: GridCell + Copy + Clone> Grid<T> {
+impl<T: GridCell + Copy + Clone + PartialEq> Grid<T> {
     pub fn new(lines: index::Line, cols: index::Column, scrollback: usize, template: T) -> Grid<T> {
         let raw = Storage::with_capacity(lines, Row::new(cols, &template));
         Grid {
@@ -130,6 +138,8 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
             scroll_limit: 0,
             selection: None,
             max_scroll_limit: scrollback,
+            history_memory: 0,
+            max_history_memory: None,
             url_highlight: None,
         }
     }
@@ -162,10 +172,46 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
 
     /// Update the size of the scrollback history
     pub fn update_history(&mut self, history_size: usize, template: &T) {
-        self.raw.update_history(history_size, Row::new(self.cols, &template));
+        self.raw.update_history(history_size, self.history_row(template));
         self.max_scroll_limit = history_size;
         self.scroll_limit = min(self.scroll_limit, history_size);
         self.display_offset = min(self.display_offset, self.scroll_limit);
+
+        self.count_history_memory();
+        self.limit_history_memory(template);
+    }
+
+    /// Update the limit on the memory used by the scrollback history, in bytes
+    pub fn update_history_memory(&mut self, max_memory: Option<usize>, template: &T) {
+        self.max_history_memory = max_memory;
+        self.count_history_memory();
+        self.limit_history_memory(template);
+    }
+
+    /// Evict the oldest lines from the history until it fits in the memory limit
+    fn limit_history_memory(&mut self, template: &T) {
+        let max_memory = match self.max_history_memory {
+            Some(max_memory) => max_memory,
+            None => return,
+        };
+
+        while self.history_memory > max_memory && self.scroll_limit > 0 {
+            let index = *self.lines + self.scroll_limit - 1;
+            let released = self.raw[index].memory_usage();
+            self.history_memory = self.history_memory.saturating_sub(released);
+            self.scroll_limit -= 1;
+
+            // Reset the line in place to release its cells
+            self.raw[index].release(template);
+        }
+
+        self.display_offset = min(self.display_offset, self.scroll_limit);
+
+        // Evicted lines can't be part of the selection anymore
+        let history_end = (*self.lines + self.scroll_limit) as isize;
+        if self.selection.as_ref().map_or(false, |selection| selection.top_line() >= history_end) {
+            self.selection = None;
+        }
     }
 
     pub fn scroll_display(&mut self, scroll: Scroll) {
@@ -211,6 +257,9 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
             Ordering::Greater => self.shrink_cols(reflow, cols, template),
             Ordering::Equal => (),
         }
+
+        self.count_history_memory();
+        self.limit_history_memory(template);
     }
 
     fn increase_scroll_limit(&mut self, count: usize, template: &T) {
@@ -223,7 +272,31 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
                 max(self.scroll_limit - history_size, MIN_INIT_SIZE),
                 self.max_scroll_limit - history_size,
             );
-            self.raw.initialize(new, Row::new(self.cols, template));
+            self.raw.initialize(new, self.history_row(template));
+        }
+    }
+
+    /// Empty row for initializing the scrollback history, compacted to save memory
+    fn history_row(&self, template: &T) -> Row<T> {
+        let mut row = Row::new(self.cols, template);
+        row.compact();
+        row
+    }
+
+    /// Compact all rows in the scrollback history
+    ///
+    /// Rows which aren't visible can't be modified, so they're stored without their trailing
+    /// cells until they are reset or scroll back into the visible area.
+    fn compact_history(&mut self) {
+        for i in *self.lines..self.raw.len() {
+            self.raw[i].compact();
+        }
+    }
+
+    /// Restore the rows of the visible area which have been compacted in the history
+    fn inflate_visible(&mut self) {
+        for i in 0..*self.lines {
+            self.raw[i].inflate();
         }
     }
 
@@ -251,6 +324,9 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
 
         self.scroll_limit = self.scroll_limit.saturating_sub(*lines_added);
         self.display_offset = self.display_offset.saturating_sub(*lines_added);
+
+        // Lines pulled from the history have to be modifiable again
+        self.inflate_visible();
     }
 
     fn grow_cols(
@@ -263,6 +339,8 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
         let mut new_empty_lines = 0;
         let mut new_raw: Vec<Row<T>> = Vec::with_capacity(self.raw.len());
         for (i, mut row) in self.raw.drain().enumerate().rev() {
+            row.inflate();
+
             if let Some(last_row) = new_raw.last_mut() {
                 // Grow the current line if there's wrapped content available
                 if reflow
@@ -325,12 +403,15 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
         self.raw.replace_inner(reversed);
 
         self.cols = cols;
+        self.compact_history();
     }
 
     fn shrink_cols(&mut self, reflow: bool, cols: index::Column, template: &T) {
         let mut new_raw = Vec::with_capacity(self.raw.len());
         let mut buffered = None;
         for (i, mut row) in self.raw.drain().enumerate().rev() {
+            row.inflate();
+
             if let Some(buffered) = buffered.take() {
                 row.append_front(buffered);
             }
@@ -383,6 +464,7 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
         reversed.truncate(self.max_scroll_limit + self.lines.0);
         self.raw.replace_inner(reversed);
         self.cols = cols;
+        self.compact_history();
     }
 
     /// Remove lines from the visible area
@@ -426,6 +508,11 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
         //
         // To accommodate scroll regions, rows are reordered at the end.
         if region.start == Line(0) {
+            // Lines moved out of the history are no longer part of its memory
+            let lines = *self.num_lines();
+            let released = self.memory_usage(lines..lines + min(*positions, self.scroll_limit));
+            self.history_memory = self.history_memory.saturating_sub(released);
+
             // Rotate the entire line buffer. If there's a scrolling region
             // active, the bottom lines are restored in the next step.
             self.raw.rotate_up(*positions);
@@ -469,7 +556,13 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
                     min(self.display_offset + *positions, self.len() - self.num_lines().0);
             }
 
+            // Lines which are pushed out of the full history release their memory
+            let lines = *self.num_lines();
+            let scroll_limit = self.scroll_limit;
             self.increase_scroll_limit(*positions, template);
+            let dropped_start = max((lines + self.scroll_limit).saturating_sub(*positions), lines);
+            let released = self.memory_usage(dropped_start..lines + scroll_limit);
+            self.history_memory = self.history_memory.saturating_sub(released);
 
             // Rotate the entire line buffer. If there's a scrolling region
             // active, the bottom lines are restored in the next step.
@@ -495,6 +588,15 @@ impl<T: GridCell + Copy + Clone> Grid<T> {
             for i in 0..*positions {
                 self.raw[i + fixed_lines].reset(&template);
             }
+
+            // Compact the lines which have just been moved into the history
+            let added = lines..lines + min(*positions, self.scroll_limit);
+            for i in added.clone() {
+                self.raw[i].compact();
+            }
+            self.history_memory += self.memory_usage(added);
+
+            self.limit_history_memory(template);
         } else {
             // Subregion rotation
             for line in IndexRange(region.start..(region.end - positions)) {
@@ -544,6 +646,30 @@ impl<T> Grid<T> {
 
     pub fn clear_history(&mut self) {
         self.scroll_limit = 0;
+        self.history_memory = 0;
+    }
+
+    /// Approximate memory used by the scrollback history, in bytes
+    #[inline]
+    pub fn history_memory(&self) -> usize {
+        self.history_memory
+    }
+
+    /// Limit on the memory used by the scrollback history, in bytes
+    #[inline]
+    pub fn max_history_memory(&self) -> Option<usize> {
+        self.max_history_memory
+    }
+
+    /// Approximate memory used by a range of lines in the buffer, in bytes
+    fn memory_usage(&self, range: Range<usize>) -> usize {
+        range.map(|i| self.raw[i].memory_usage()).sum()
+    }
+
+    /// Count the memory used by all lines in the scrollback history
+    fn count_history_memory(&mut self) {
+        let lines = *self.lines;
+        self.history_memory = self.memory_usage(lines..lines + self.scroll_limit);
     }
 
     #[inline]
@@ -551,12 +677,27 @@ impl<T> Grid<T> {
         self.scroll_limit
     }
 
+    /// Maximum number of lines in the scrollback history
+    #[inline]
+    pub fn max_scroll_limit(&self) -> usize {
+        self.max_scroll_limit
+    }
+
     /// Total number of lines in the buffer, this includes scrollback + visible lines
     #[inline]
     pub fn len(&self) -> usize {
         self.raw.len()
     }
 
+    /// Check the invariants of a grid deserialized from untrusted data
+    #[cfg(any(test, feature = "session"))]
+    pub(crate) fn is_valid(&self) -> bool {
+        self.cols.0 > 0
+            && self.raw.is_valid(self.lines, self.cols)
+            && self.scroll_limit <= self.raw.len() - self.lines.0
+            && self.display_offset <= self.scroll_limit
+    }
+
     #[inline]
     pub fn history_size(&self) -> usize {
         self.raw.len().saturating_sub(*self.lines)
@@ -569,6 +710,7 @@ impl<T> Grid<T> {
     {
         let history_size = self.raw.len().saturating_sub(*self.lines);
         self.raw.initialize(self.max_scroll_limit - history_size, Row::new(self.cols, template));
+        self.count_history_memory();
     }
 
     /// This is used only for truncating before saving ref-tests
@@ -591,6 +733,150 @@ impl<T> Grid<T> {
     }
 }
 
+impl<T: GridCell> Grid<T> {
+    /// Iterate over the logical lines of the scrollback history and the screen
+    ///
+    /// Rows which are continued by a line wrap are joined into one logical line. Iteration starts
+    /// with the oldest line of the history, the iterator can be reversed to start at the bottom
+    /// of the screen instead.
+    pub fn logical_lines(&self) -> LogicalLines<'_, T> {
+        LogicalLines { grid: self, rows: 0..self.content_len() }
+    }
+
+    /// Logical line containing the buffer line of `point`
+    pub fn line_at(&self, point: Point<usize>) -> LogicalLine<'_, T> {
+        let mut top = point.line;
+        while top + 1 < self.content_len() && self.wraps(top + 1) {
+            top += 1;
+        }
+
+        let mut bottom = point.line;
+        while bottom > 0 && self.wraps(bottom) {
+            bottom -= 1;
+        }
+
+        LogicalLine { grid: self, top, bottom }
+    }
+
+    /// Number of buffer lines with content, the buffer may have more rows allocated already
+    #[inline]
+    fn content_len(&self) -> usize {
+        *self.lines + self.scroll_limit
+    }
+
+    /// Check if the row at the buffer `line` is continued in the row below it
+    #[inline]
+    fn wraps(&self, line: usize) -> bool {
+        self.raw[line][self.cols - 1].is_wrap()
+    }
+}
+
+/// Rows of the grid which are joined by line wraps
+///
+/// Rows are addressed by their buffer line like in `Point<usize>`, so the first row of a line
+/// has the highest line number.
+#[derive(Debug, Copy, Clone)]
+pub struct LogicalLine<'a, T> {
+    grid: &'a Grid<T>,
+
+    /// Buffer line of the first row
+    top: usize,
+
+    /// Buffer line of the last row
+    bottom: usize,
+}
+
+impl<'a, T> LogicalLine<'a, T> {
+    /// Position of the first cell of the line
+    #[inline]
+    pub fn start(&self) -> Point<usize> {
+        Point::new(self.top, Column(0))
+    }
+
+    /// Position of the last cell of the line
+    #[inline]
+    pub fn end(&self) -> Point<usize> {
+        Point::new(self.bottom, self.grid.cols - 1)
+    }
+
+    /// Check if the line has been wrapped onto more than one row
+    #[inline]
+    pub fn is_wrapped(&self) -> bool {
+        self.top > self.bottom
+    }
+
+    /// Check if at least one row of the line is in the scrollback history
+    #[inline]
+    pub fn in_history(&self) -> bool {
+        self.top >= *self.grid.lines
+    }
+
+    /// Check if at least one row of the line is shown in the viewport
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        let offset = self.grid.display_offset;
+        self.top >= offset && self.bottom < offset + *self.grid.lines
+    }
+
+    /// Rows of the line from top to bottom, together with their buffer line
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = (usize, &'a Row<T>)> {
+        let grid = self.grid;
+        (self.bottom..=self.top).rev().map(move |line| (line, &grid[line]))
+    }
+
+    /// Cells of the line from the first to the last one, together with their position
+    pub fn cells(&self) -> impl DoubleEndedIterator<Item = (Point<usize>, &'a T)> {
+        let cols = self.grid.cols.0;
+        self.rows().flat_map(move |(line, row)| {
+            (0..cols).map(move |col| (Point::new(line, Column(col)), &row[Column(col)]))
+        })
+    }
+}
+
+/// Iterator over the logical lines of a grid, see `Grid::logical_lines`
+pub struct LogicalLines<'a, T> {
+    grid: &'a Grid<T>,
+
+    /// Buffer lines of the rows which haven't been visited yet
+    rows: Range<usize>,
+}
+
+impl<'a, T: GridCell> Iterator for LogicalLines<'a, T> {
+    type Item = LogicalLine<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rows.start >= self.rows.end {
+            return None;
+        }
+
+        let top = self.rows.end - 1;
+        let mut bottom = top;
+        while bottom > self.rows.start && self.grid.wraps(bottom) {
+            bottom -= 1;
+        }
+        self.rows.end = bottom;
+
+        Some(LogicalLine { grid: self.grid, top, bottom })
+    }
+}
+
+impl<'a, T: GridCell> DoubleEndedIterator for LogicalLines<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.rows.start >= self.rows.end {
+            return None;
+        }
+
+        let bottom = self.rows.start;
+        let mut top = bottom;
+        while top + 1 < self.rows.end && self.grid.wraps(top + 1) {
+            top += 1;
+        }
+        self.rows.start = top + 1;
+
+        Some(LogicalLine { grid: self.grid, top, bottom })
+    }
+}
+
 pub struct GridIterator<'a, T> {
     /// Immutable grid reference
     grid: &'a Grid<T>,
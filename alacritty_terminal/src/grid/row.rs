@@ -15,6 +15,8 @@
 //! Defines the Row type which makes up lines in the grid
 
 use std::cmp::{max, min};
+use std::iter;
+use std::mem;
 use std::ops::{Index, IndexMut};
 use std::ops::{Range, RangeFrom, RangeFull, RangeTo, RangeToInclusive};
 use std::slice;
@@ -22,11 +24,24 @@ use std::slice;
 use crate::grid::GridCell;
 use crate::index::Column;
 
+/// Smallest number of trailing cells which is worth compacting a row for
+///
+/// Compacting reallocates the row, which isn't worth it for a few cells.
+const MIN_COMPACTION: usize = 8;
+
 /// A row in the grid
+///
+/// Rows in the scrollback history can be compacted, which drops the cells at the end of the row
+/// that are equal to the last cell. Reading a compacted row behaves exactly like reading the
+/// full row, but it has to be inflated with `Row::inflate` before it can be modified.
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Row<T> {
     inner: Vec<T>,
 
+    /// Number of cells after the last one in `inner` which are equal to it, but not stored
+    #[serde(default)]
+    trimmed: usize,
+
     /// occupied entries
     ///
     /// Semantically, this value can be understood as the **end** of an
@@ -40,7 +55,11 @@ pub struct Row<T> {
 
 impl<T: PartialEq> PartialEq for Row<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.inner == other.inner
+        if self.trimmed == 0 && other.trimmed == 0 {
+            return self.inner == other.inner;
+        }
+
+        self.len() == other.len() && (0..self.len()).all(|i| self[Column(i)] == other[Column(i)])
     }
 }
 
@@ -50,10 +69,51 @@ impl<T: Copy> Row<T> {
         T: GridCell,
     {
         let occ = if template.is_empty() { 0 } else { columns.0 };
-        Row { inner: vec![*template; columns.0], occ }
+        Row { inner: vec![*template; columns.0], trimmed: 0, occ }
+    }
+
+    /// Drop the cells at the end of the row which are equal to the last cell
+    ///
+    /// Only the last of these cells is kept, it stands in for all the others. Nothing is
+    /// dropped if too few cells could be removed, or if they're less than a quarter of the row.
+    pub fn compact(&mut self)
+    where
+        T: PartialEq,
+    {
+        let last = match self.inner.last() {
+            Some(last) => *last,
+            None => return,
+        };
+        let run = self.inner.iter().rev().take_while(|cell| **cell == last).count();
+        if run <= MIN_COMPACTION || run <= self.inner.len() / 4 {
+            return;
+        }
+
+        // Shrinking in place would leave the dropped cells as holes in the heap which are too
+        // small for a full row, copying releases the whole allocation for the next reset.
+        let len = self.inner.len() - (run - 1);
+        self.inner = self.inner[..len].to_vec();
+        self.trimmed += run - 1;
+        self.occ = min(self.occ, len);
+    }
+
+    /// Restore all cells of a compacted row
+    pub fn inflate(&mut self) {
+        if self.trimmed == 0 {
+            return;
+        }
+
+        let fill = self.inner[self.inner.len() - 1];
+        self.inner.reserve_exact(self.trimmed);
+        self.inner.extend(iter::repeat(fill).take(self.trimmed));
+        self.trimmed = 0;
+
+        // Only cells before `occ` are reset, which has to include the restored cells
+        self.occ = self.inner.len();
     }
 
     pub fn grow(&mut self, cols: Column, template: &T) {
+        self.inflate();
         if self.inner.len() >= cols.0 {
             return;
         }
@@ -65,6 +125,7 @@ impl<T: Copy> Row<T> {
     where
         T: GridCell,
     {
+        self.inflate();
         if self.inner.len() <= cols.0 {
             return None;
         }
@@ -88,6 +149,17 @@ impl<T: Copy> Row<T> {
     where
         T: GridCell,
     {
+        // Rebuilding a compacted row at full length is cheaper than restoring it first
+        if self.trimmed > 0 {
+            let len = self.len();
+            self.inner.clear();
+            self.inner.reserve_exact(len);
+            self.inner.resize(len, *template);
+            self.trimmed = 0;
+            self.occ = if template.is_empty() { 0 } else { len };
+            return;
+        }
+
         for item in &mut self.inner[..self.occ] {
             *item = *template;
         }
@@ -96,18 +168,64 @@ impl<T: Copy> Row<T> {
             self.occ = 0;
         }
     }
+
+    /// Reset the row to the template and release all but one of its cells
+    ///
+    /// The row's allocation is shrunk instead of replaced, the row reads like a reset one.
+    pub fn release(&mut self, template: &T)
+    where
+        T: GridCell,
+    {
+        let len = self.len();
+        if len == 0 {
+            return;
+        }
+
+        self.inner.truncate(1);
+        self.inner[0] = *template;
+        self.inner.shrink_to_fit();
+        self.trimmed = len - 1;
+        self.occ = if template.is_empty() { 0 } else { 1 };
+    }
 }
 
 #[allow(clippy::len_without_is_empty)]
 impl<T> Row<T> {
     #[inline]
     pub fn from_vec(vec: Vec<T>, occ: usize) -> Row<T> {
-        Row { inner: vec, occ }
+        Row { inner: vec, trimmed: 0, occ }
     }
 
     #[inline]
     pub fn len(&self) -> usize {
-        self.inner.len()
+        self.inner.len() + self.trimmed
+    }
+
+    /// Check that a row deserialized from untrusted data has `cols` cells
+    #[cfg(any(test, feature = "session"))]
+    pub(crate) fn is_valid(&self, cols: Column) -> bool {
+        self.inner.len().checked_add(self.trimmed) == Some(cols.0)
+            && (self.trimmed == 0 || !self.inner.is_empty())
+            && self.occ <= cols.0
+    }
+
+    /// Check if cells at the end of the row have been dropped by `Row::compact`
+    #[inline]
+    pub fn is_compacted(&self) -> bool {
+        self.trimmed > 0
+    }
+
+    /// Approximate number of bytes used by the row, including its allocated cells
+    #[inline]
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + self.inner.capacity() * mem::size_of::<T>()
+    }
+
+    /// Cell which has been dropped by compacting the row
+    #[cold]
+    fn trimmed_cell(&self, index: Column) -> &T {
+        assert!(index.0 < self.len(), "column {} out of bounds for row of {}", index.0, self.len());
+        &self.inner[self.inner.len() - 1]
     }
 
     #[inline]
@@ -117,6 +235,7 @@ impl<T> Row<T> {
 
     #[inline]
     pub fn last_mut(&mut self) -> Option<&mut T> {
+        debug_assert!(!self.is_compacted());
         self.occ = self.inner.len();
         self.inner.last_mut()
     }
@@ -126,12 +245,14 @@ impl<T> Row<T> {
     where
         T: GridCell,
     {
+        debug_assert!(!self.is_compacted());
         self.occ += vec.len();
         self.inner.append(vec);
     }
 
     #[inline]
     pub fn append_front(&mut self, mut vec: Vec<T>) {
+        debug_assert!(!self.is_compacted());
         self.occ += vec.len();
 
         vec.append(&mut self.inner);
@@ -148,6 +269,7 @@ impl<T> Row<T> {
 
     #[inline]
     pub fn front_split_off(&mut self, at: usize) -> Vec<T> {
+        debug_assert!(!self.is_compacted());
         self.occ = self.occ.saturating_sub(at);
 
         let mut split = self.inner.split_off(at);
@@ -162,6 +284,7 @@ impl<'a, T> IntoIterator for &'a mut Row<T> {
 
     #[inline]
     fn into_iter(self) -> slice::IterMut<'a, T> {
+        debug_assert!(!self.is_compacted());
         self.occ = self.len();
         self.inner.iter_mut()
     }
@@ -172,13 +295,17 @@ impl<T> Index<Column> for Row<T> {
 
     #[inline]
     fn index(&self, index: Column) -> &T {
-        &self.inner[index.0]
+        match self.inner.get(index.0) {
+            Some(cell) => cell,
+            None => self.trimmed_cell(index),
+        }
     }
 }
 
 impl<T> IndexMut<Column> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: Column) -> &mut T {
+        debug_assert!(!self.is_compacted());
         self.occ = max(self.occ, *index + 1);
         &mut self.inner[index.0]
     }
@@ -186,6 +313,9 @@ impl<T> IndexMut<Column> for Row<T> {
 
 // -----------------------------------------------------------------------------
 // Index ranges of columns
+//
+// Slices only contain the stored cells of compacted rows, so ranges which reach the end of the
+// row must not be used on them.
 // -----------------------------------------------------------------------------
 
 impl<T> Index<Range<Column>> for Row<T> {
@@ -200,6 +330,7 @@ impl<T> Index<Range<Column>> for Row<T> {
 impl<T> IndexMut<Range<Column>> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: Range<Column>) -> &mut [T] {
+        debug_assert!(!self.is_compacted());
         self.occ = max(self.occ, *index.end);
         &mut self.inner[(index.start.0)..(index.end.0)]
     }
@@ -217,6 +348,7 @@ impl<T> Index<RangeTo<Column>> for Row<T> {
 impl<T> IndexMut<RangeTo<Column>> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: RangeTo<Column>) -> &mut [T] {
+        debug_assert!(!self.is_compacted());
         self.occ = max(self.occ, *index.end);
         &mut self.inner[..(index.end.0)]
     }
@@ -227,6 +359,7 @@ impl<T> Index<RangeFrom<Column>> for Row<T> {
 
     #[inline]
     fn index(&self, index: RangeFrom<Column>) -> &[T] {
+        debug_assert!(!self.is_compacted());
         &self.inner[(index.start.0)..]
     }
 }
@@ -234,6 +367,7 @@ impl<T> Index<RangeFrom<Column>> for Row<T> {
 impl<T> IndexMut<RangeFrom<Column>> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: RangeFrom<Column>) -> &mut [T] {
+        debug_assert!(!self.is_compacted());
         self.occ = self.len();
         &mut self.inner[(index.start.0)..]
     }
@@ -244,6 +378,7 @@ impl<T> Index<RangeFull> for Row<T> {
 
     #[inline]
     fn index(&self, _: RangeFull) -> &[T] {
+        debug_assert!(!self.is_compacted());
         &self.inner[..]
     }
 }
@@ -251,6 +386,7 @@ impl<T> Index<RangeFull> for Row<T> {
 impl<T> IndexMut<RangeFull> for Row<T> {
     #[inline]
     fn index_mut(&mut self, _: RangeFull) -> &mut [T] {
+        debug_assert!(!self.is_compacted());
         self.occ = self.len();
         &mut self.inner[..]
     }
@@ -268,6 +404,7 @@ impl<T> Index<RangeToInclusive<Column>> for Row<T> {
 impl<T> IndexMut<RangeToInclusive<Column>> for Row<T> {
     #[inline]
     fn index_mut(&mut self, index: RangeToInclusive<Column>) -> &mut [T] {
+        debug_assert!(!self.is_compacted());
         self.occ = max(self.occ, *index.end);
         &mut self.inner[..=(index.end.0)]
     }
@@ -17,7 +17,7 @@ use std::vec::Drain;
 use static_assertions::assert_eq_size;
 
 use super::Row;
-use crate::index::Line;
+use crate::index::{Column, Line};
 
 /// Maximum number of invisible lines before buffer is resized
 const TRUNCATE_STEP: usize = 100;
@@ -189,6 +189,19 @@ impl<T> Storage<T> {
         self.len
     }
 
+    /// Check the invariants of a storage deserialized from untrusted data
+    ///
+    /// Visible rows are written to without inflating them first, so they can't be compacted.
+    #[cfg(any(test, feature = "session"))]
+    pub(crate) fn is_valid(&self, lines: Line, cols: Column) -> bool {
+        self.visible_lines.0.checked_add(1) == Some(lines.0)
+            && self.zero < self.inner.len()
+            && self.len >= lines.0
+            && self.len <= self.inner.len()
+            && self.inner.iter().all(|row| row.is_valid(cols))
+            && (0..lines.0).all(|line| !self[line].is_compacted())
+    }
+
     #[inline]
     /// Compute actual index in underlying storage given the requested index.
     fn compute_index(&self, requested: usize) -> usize {
@@ -214,13 +227,9 @@ impl<T> Storage<T> {
     /// Swap implementation for Row<T>.
     ///
     /// Exploits the known size of Row<T> to produce a slightly more efficient
-    /// swap than going through slice::swap.
-    ///
-    /// The default implementation from swap generates 8 movups and 4 movaps
-    /// instructions. This implementation achieves the swap in only 8 movups
-    /// instructions.
+    /// swap than going through slice::swap, by swapping one qword at a time.
     pub fn swap(&mut self, a: usize, b: usize) {
-        assert_eq_size!(Row<T>, [usize; 4]);
+        assert_eq_size!(Row<T>, [usize; 5]);
 
         let a = self.compute_index(a);
         let b = self.compute_index(b);
@@ -236,7 +245,7 @@ impl<T> Storage<T> {
             //
             // The optimizer unrolls this loop and vectorizes it.
             let mut tmp: usize;
-            for i in 0..4 {
+            for i in 0..5 {
                 tmp = *a_ptr.offset(i);
                 *a_ptr.offset(i) = *b_ptr.offset(i);
                 *b_ptr.offset(i) = tmp;
@@ -14,9 +14,11 @@
 
 //! Tests for the Grid
 
-use super::{BidirectionalIterator, Grid};
+use super::{BidirectionalIterator, Grid, LogicalLine, Scroll};
+use crate::ansi::{Color, NamedColor};
 use crate::grid::GridCell;
-use crate::index::{Column, Line, Point};
+use crate::index::{Column, Line, Point, Side};
+use crate::selection::Selection;
 use crate::term::cell::{Cell, Flags};
 
 impl GridCell for usize {
@@ -323,6 +325,255 @@ fn shrink_reflow_disabled() {
     assert_eq!(grid[0][Column(1)], cell('2'));
 }
 
+#[test]
+fn scroll_up_compacts_history() {
+    let mut grid = Grid::new(Line(2), Column(20), 5, Cell::default());
+    let mut red = Cell::default();
+    red.bg = Color::Named(NamedColor::Red);
+    grid[Line(0)][Column(0)] = cell('a');
+    for col in 1..20 {
+        grid[Line(0)][Column(col)] = red;
+    }
+    let row = grid[Line(0)].clone();
+
+    grid.scroll_up(&(Line(0)..Line(2)), Line(1), &Cell::default());
+
+    // Lines in the history are compacted, but still read like the original line
+    assert!(grid[2].is_compacted());
+    assert_eq!(grid[2].len(), 20);
+    assert_eq!(grid[2][Column(0)], cell('a'));
+    for col in 1..20 {
+        assert_eq!(grid[2][Column(col)], red);
+    }
+    assert_eq!(grid[2], row);
+
+    // Visible lines can still be modified
+    assert!(!grid[0].is_compacted());
+    assert!(!grid[1].is_compacted());
+}
+
+#[test]
+fn grow_lines_inflates_history() {
+    let mut grid = Grid::new(Line(2), Column(20), 5, Cell::default());
+    grid[Line(0)][Column(0)] = cell('a');
+    grid.scroll_up(&(Line(0)..Line(2)), Line(1), &Cell::default());
+    assert!(grid[2].is_compacted());
+
+    grid.resize(true, Line(3), Column(20), &mut Point::new(Line(1), Column(0)), &Cell::default());
+
+    assert!(!grid[Line(0)].is_compacted());
+    assert_eq!(grid[Line(0)].len(), 20);
+    assert_eq!(grid[Line(0)][Column(0)], cell('a'));
+    grid[Line(0)][Column(19)] = cell('b');
+    assert_eq!(grid[Line(0)][Column(19)], cell('b'));
+}
+
+#[test]
+fn resize_cols_keeps_compacted_history() {
+    let mut grid = Grid::new(Line(1), Column(20), 5, Cell::default());
+    for c in &['a', 'b', 'c'] {
+        grid[Line(0)][Column(0)] = cell(*c);
+        grid.scroll_up(&(Line(0)..Line(1)), Line(1), &Cell::default());
+    }
+
+    grid.resize(false, Line(1), Column(30), &mut Point::new(Line(0), Column(0)), &Cell::default());
+
+    for (i, c) in [(1, 'c'), (2, 'b'), (3, 'a')].iter() {
+        assert!(grid[*i].is_compacted());
+        assert_eq!(grid[*i].len(), 30);
+        assert_eq!(grid[*i][Column(0)], cell(*c));
+        assert_eq!(grid[*i][Column(29)], Cell::default());
+    }
+
+    grid.resize(false, Line(1), Column(10), &mut Point::new(Line(0), Column(0)), &Cell::default());
+
+    for (i, c) in [(1, 'c'), (2, 'b'), (3, 'a')].iter() {
+        assert!(grid[*i].is_compacted());
+        assert_eq!(grid[*i].len(), 10);
+        assert_eq!(grid[*i][Column(0)], cell(*c));
+    }
+}
+
+#[test]
+fn history_memory_limit() {
+    let mut grid = Grid::new(Line(10), Column(400), 100_000, Cell::default());
+    let max_memory = 1024 * 1024;
+    grid.update_history_memory(Some(max_memory), &Cell::default());
+
+    // Fill the history with wide lines which can't be compacted
+    for i in 0..1000 {
+        for col in 0..400 {
+            let c = (b'a' + ((i + col) % 26) as u8) as char;
+            grid[Line(9)][Column(col)] = cell(c);
+        }
+        grid.scroll_up(&(Line(0)..Line(10)), Line(1), &Cell::default());
+
+        assert!(grid.history_memory() <= max_memory);
+    }
+
+    // Lines were evicted before reaching the line limit
+    assert!(grid.scroll_limit() > 0);
+    assert!(grid.scroll_limit() < 1000);
+
+    // Tracked memory matches the lines in the history and stays within a line of the limit
+    let line_memory = grid[10].memory_usage();
+    let history_memory = grid.memory_usage(10..10 + grid.scroll_limit());
+    assert_eq!(grid.history_memory(), history_memory);
+    assert!(history_memory + line_memory > max_memory);
+
+    // Oldest remaining line is still intact
+    let oldest = grid.scroll_limit() + 9;
+    let i = 1000 - oldest;
+    assert_eq!(grid[oldest][Column(0)], cell((b'a' + (i % 26) as u8) as char));
+}
+
+#[test]
+fn history_memory_eviction_clamps_viewport() {
+    let mut grid = Grid::new(Line(2), Column(100), 1000, Cell::default());
+    for i in 0..100 {
+        for col in 0..100 {
+            grid[Line(1)][Column(col)] = cell((b'a' + ((i + col) % 26) as u8) as char);
+        }
+        grid.scroll_up(&(Line(0)..Line(2)), Line(1), &Cell::default());
+    }
+    assert_eq!(grid.scroll_limit(), 100);
+
+    grid.scroll_display(Scroll::Top);
+    grid.selection = Some(Selection::simple(Point::new(100, Column(0)), Side::Left));
+
+    let max_memory = grid.history_memory() / 2;
+    grid.update_history_memory(Some(max_memory), &Cell::default());
+
+    assert!(grid.history_memory() <= max_memory);
+    assert!(grid.scroll_limit() < 100);
+    assert_eq!(grid.display_offset(), grid.scroll_limit());
+    assert_eq!(grid.selection, None);
+
+    // Evicted lines are cleared and only keep a single cell
+    let evicted = 2 + grid.scroll_limit();
+    assert!(grid[evicted].is_compacted());
+    assert_eq!(grid[evicted].memory_usage(), grid.history_row(&Cell::default()).memory_usage());
+    assert_eq!(grid[evicted][Column(0)], Cell::default());
+}
+
+/// Grid with two lines on the screen and five in the history, a `+` marks wrapped rows
+///
+/// The last logical line starts in the history and ends on the screen.
+fn logical_line_grid() -> Grid<Cell> {
+    let mut grid = Grid::new(Line(2), Column(2), 10, Cell::default());
+    for (i, row) in ["ab+", "cd", "ef", "gh+", "ij+", "kl"].iter().enumerate() {
+        if i > 0 {
+            grid.scroll_up(&(Line(0)..Line(2)), Line(1), &Cell::default());
+        }
+
+        let wrapped = row.ends_with('+');
+        for (col, c) in row.chars().take(2).enumerate() {
+            grid[Line(1)][Column(col)] = if wrapped && col == 1 { wrap_cell(c) } else { cell(c) };
+        }
+    }
+    grid
+}
+
+fn text(line: &LogicalLine<'_, Cell>) -> String {
+    line.cells().map(|(_, cell)| cell.c).collect()
+}
+
+#[test]
+fn logical_lines_across_history() {
+    let grid = logical_line_grid();
+
+    let lines: Vec<_> = grid
+        .logical_lines()
+        .map(|line| (text(&line), line.start().line, line.end().line, line.in_history()))
+        .collect();
+    assert_eq!(lines, vec![
+        (String::from("  "), 6, 6, true),
+        (String::from("abcd"), 5, 4, true),
+        (String::from("ef"), 3, 3, true),
+        (String::from("ghijkl"), 2, 0, true),
+    ]);
+
+    let wrapped: Vec<_> = grid.logical_lines().map(|line| line.is_wrapped()).collect();
+    assert_eq!(wrapped, vec![false, true, false, true]);
+}
+
+#[test]
+fn logical_lines_reversed() {
+    let grid = logical_line_grid();
+
+    let lines: Vec<_> = grid.logical_lines().rev().map(|line| text(&line)).collect();
+    assert_eq!(lines, vec!["ghijkl", "ef", "abcd", "  "]);
+
+    // Both ends meet without splitting a line
+    let mut iter = grid.logical_lines();
+    assert_eq!(iter.next().map(|line| text(&line)), Some(String::from("  ")));
+    assert_eq!(iter.next_back().map(|line| text(&line)), Some(String::from("ghijkl")));
+    assert_eq!(iter.next_back().map(|line| text(&line)), Some(String::from("ef")));
+    assert_eq!(iter.next().map(|line| text(&line)), Some(String::from("abcd")));
+    assert!(iter.next().is_none());
+    assert!(iter.next_back().is_none());
+}
+
+#[test]
+fn logical_line_at_point() {
+    let grid = logical_line_grid();
+
+    for line in 0..3 {
+        let logical = grid.line_at(Point::new(line, Column(1)));
+        assert_eq!(logical.start(), Point::new(2, Column(0)));
+        assert_eq!(logical.end(), Point::new(0, Column(1)));
+    }
+
+    let logical = grid.line_at(Point::new(4, Column(0)));
+    assert_eq!(text(&logical), "abcd");
+    assert_eq!(logical.cells().next().map(|(point, _)| point), Some(Point::new(5, Column(0))));
+    assert_eq!(logical.cells().last().map(|(point, _)| point), Some(Point::new(4, Column(1))));
+
+    let rows: Vec<_> = logical.rows().map(|(line, row)| (line, row[Column(0)].c)).collect();
+    assert_eq!(rows, vec![(5, 'a'), (4, 'c')]);
+}
+
+#[test]
+fn logical_lines_follow_viewport() {
+    let mut grid = logical_line_grid();
+
+    let visible: Vec<_> = grid.logical_lines().map(|line| line.is_visible()).collect();
+    assert_eq!(visible, vec![false, false, false, true]);
+
+    grid.scroll_display(Scroll::Lines(3));
+    let visible: Vec<_> = grid.logical_lines().map(|line| line.is_visible()).collect();
+    assert_eq!(visible, vec![false, true, true, false]);
+}
+
+#[test]
+fn deserialized_grid_validity() {
+    let mut grid = Grid::new(Line(3), Column(4), 10, 0);
+    grid.scroll_up(&(Line(0)..Line(3)), Line(2), &0);
+    let value = serde_json::to_value(&grid).unwrap();
+    let valid = |edit: &dyn Fn(&mut serde_json::Value)| {
+        let mut value = value.clone();
+        edit(&mut value);
+        serde_json::from_value::<Grid<usize>>(value).unwrap().is_valid()
+    };
+
+    assert!(valid(&|_| ()));
+    assert!(!valid(&|value| value["raw"]["zero"] = 100.into()));
+    assert!(!valid(&|value| value["raw"]["visible_lines"] = 5.into()));
+    assert!(!valid(&|value| value["raw"]["len"] = 1000.into()));
+    assert!(!valid(&|value| value["lines"] = 0.into()));
+    assert!(!valid(&|value| value["cols"] = 5.into()));
+    assert!(!valid(&|value| value["scroll_limit"] = 100.into()));
+    assert!(!valid(&|value| value["display_offset"] = 3.into()));
+
+    // Visible rows have to be writable
+    assert!(!valid(&|value| {
+        let zero = value["raw"]["zero"].as_u64().unwrap() as usize;
+        let inner = value["raw"]["inner"].as_array_mut().unwrap();
+        let index = zero % inner.len();
+        inner[index] = serde_json::json!({ "inner": [0], "trimmed": 3, "occ": 0 });
+    }));
+}
+
 fn cell(c: char) -> Cell {
     let mut cell = Cell::default();
     cell.c = c;
@@ -20,27 +20,35 @@
 //! determine what to do when a non-modifier key is pressed.
 use crate::url::Url;
 use std::borrow::Cow;
+use std::fmt;
 use std::mem;
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use glutin::{
-    ElementState, KeyboardInput, ModifiersState, MouseButton, MouseCursor, MouseScrollDelta,
-    TouchPhase,
+    ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, TouchPhase,
+    VirtualKeyCode,
 };
 
-use crate::ansi::{ClearMode, Handler};
+use crate::ansi::{ClearMode, Handler, MouseCursorIcon};
 use crate::clipboard::ClipboardType;
 use crate::config::{self, Key};
-use crate::event::{ClickState, Mouse};
 use crate::grid::Scroll;
 use crate::index::{Column, Line, Point, Side};
 use crate::message_bar::{self, Message};
+use crate::term::color;
 use crate::term::mode::TermMode;
-use crate::term::{SizeInfo, Term};
+use crate::term::{OpacityChange, SizeInfo, Term, FONT_FAMILY_NOTICE_DURATION};
 use crate::util::start_daemon;
 
 pub const FONT_SIZE_STEP: f32 = 0.5;
 
+/// Opacity change of the window opacity actions
+pub const WINDOW_OPACITY_STEP: f32 = 0.1;
+
+/// Topic of the message which shows the chords of an incomplete key binding
+const KEY_CHORD_TOPIC: &str = "key chord";
+
 /// Processes input from glutin.
 ///
 /// An escape sequence may be emitted in case specific keys or key combinations
@@ -52,9 +60,32 @@ pub struct Processor<'a, A: 'a> {
     pub mouse_bindings: &'a [MouseBinding],
     pub mouse_config: &'a config::Mouse,
     pub scrolling_config: &'a config::Scrolling,
+    pub key_chords_config: &'a config::KeyChords,
     pub ctx: A,
     pub save_to_clipboard: bool,
     pub alt_send_esc: bool,
+    pub option_as_alt: config::OptionAsAlt,
+}
+
+/// Option keys which are currently held down
+///
+/// The modifier state does not tell left and right Alt apart, so the key
+/// events of both keys are tracked instead.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct OptionKeys {
+    pub left: bool,
+    pub right: bool,
+}
+
+impl OptionKeys {
+    fn update(&mut self, input: KeyboardInput) {
+        let pressed = input.state == ElementState::Pressed;
+        match input.virtual_keycode {
+            Some(VirtualKeyCode::LAlt) => self.left = pressed,
+            Some(VirtualKeyCode::RAlt) => self.right = pressed,
+            _ => (),
+        }
+    }
 }
 
 pub trait ActionContext {
@@ -73,7 +104,12 @@ pub trait ActionContext {
     fn mouse_coords(&self) -> Option<Point>;
     fn received_count(&mut self) -> &mut usize;
     fn suppress_chars(&mut self) -> &mut bool;
+    fn composing(&mut self) -> &mut bool;
     fn last_modifiers(&mut self) -> &mut ModifiersState;
+    fn option_keys(&mut self) -> &mut OptionKeys;
+    fn pending_keys(&mut self) -> &mut Option<PendingKeys>;
+    fn ui_mode(&mut self) -> &mut TermMode;
+    fn schedule_wakeup(&mut self, delay: Duration);
     fn scroll(&mut self, scroll: Scroll);
     fn hide_window(&mut self);
     fn terminal(&self) -> &Term;
@@ -82,6 +118,182 @@ pub trait ActionContext {
     fn toggle_fullscreen(&mut self);
     #[cfg(target_os = "macos")]
     fn toggle_simple_fullscreen(&mut self);
+    fn toggle_maximized(&mut self);
+    fn change_window_opacity(&mut self, change: OpacityChange);
+    fn create_new_window(&mut self);
+}
+
+/// The ActionContext can't really have direct access to the Window
+/// with the current design. Event handlers that want to change the
+/// window must set these flags instead. The processor will trigger
+/// the actual changes.
+#[derive(Default)]
+pub struct WindowChanges {
+    pub hide: bool,
+    pub toggle_fullscreen: bool,
+    #[cfg(target_os = "macos")]
+    pub toggle_simple_fullscreen: bool,
+    pub toggle_maximized: bool,
+    pub opacity: Vec<OpacityChange>,
+    pub create_window: bool,
+}
+
+impl WindowChanges {
+    pub fn clear(&mut self) {
+        *self = WindowChanges::default();
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        self.toggle_fullscreen = !self.toggle_fullscreen;
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn toggle_simple_fullscreen(&mut self) {
+        self.toggle_simple_fullscreen = !self.toggle_simple_fullscreen;
+    }
+
+    pub fn toggle_maximized(&mut self) {
+        self.toggle_maximized = !self.toggle_maximized;
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClickState {
+    None,
+    Click,
+    DoubleClick,
+    TripleClick,
+}
+
+impl ClickState {
+    /// State after the next press of a mouse button
+    ///
+    /// The press continues a multi-click if it repeats the `last` one within the threshold of
+    /// the current state. A fourth click starts over with a simple click.
+    pub fn next(self, last: Option<Click>, click: Click, config: &config::Mouse) -> ClickState {
+        let threshold = match self {
+            ClickState::Click => config.double_click.threshold,
+            ClickState::DoubleClick => config.triple_click.threshold,
+            ClickState::None | ClickState::TripleClick => return ClickState::Click,
+        };
+
+        match last {
+            Some(last) if last.is_repeated_by(click, threshold) => match self {
+                ClickState::Click => ClickState::DoubleClick,
+                _ => ClickState::TripleClick,
+            },
+            _ => ClickState::Click,
+        }
+    }
+}
+
+/// Press of a mouse button, as far as multi-click detection is concerned
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Click {
+    pub button: MouseButton,
+    pub point: Option<Point>,
+    pub timestamp: Instant,
+}
+
+impl Click {
+    /// Check if `next` repeats this click
+    ///
+    /// The same button has to be pressed in time, at most one cell away from this click.
+    fn is_repeated_by(self, next: Click, threshold: Duration) -> bool {
+        let distance = |a: usize, b: usize| if a > b { a - b } else { b - a };
+        let nearby = match (self.point, next.point) {
+            (Some(a), Some(b)) => {
+                distance(a.line.0, b.line.0) <= 1 && distance(a.col.0, b.col.0) <= 1
+            },
+            _ => false,
+        };
+
+        self.button == next.button
+            && nearby
+            && next.timestamp.duration_since(self.timestamp) < threshold
+    }
+}
+
+/// State of the mouse
+pub struct Mouse {
+    pub x: usize,
+    pub y: usize,
+    pub left_button_state: ElementState,
+    pub middle_button_state: ElementState,
+    pub right_button_state: ElementState,
+    pub last_click: Option<Click>,
+    pub click_state: ClickState,
+    pub scroll_delta: ScrollAccumulator,
+    pub line: Line,
+    pub column: Column,
+    pub cell_side: Side,
+    pub lines_scrolled: f32,
+    pub block_url_launcher: bool,
+
+    /// Button whose press has been handled by a mouse binding
+    pub bound_button: Option<MouseButton>,
+}
+
+impl Default for Mouse {
+    fn default() -> Mouse {
+        Mouse {
+            x: 0,
+            y: 0,
+            last_click: None,
+            left_button_state: ElementState::Released,
+            middle_button_state: ElementState::Released,
+            right_button_state: ElementState::Released,
+            click_state: ClickState::None,
+            scroll_delta: ScrollAccumulator::default(),
+            line: Line(0),
+            column: Column(0),
+            cell_side: Side::Left,
+            lines_scrolled: 0.0,
+            block_url_launcher: false,
+            bound_button: None,
+        }
+    }
+}
+
+/// Time after which the pixels left over from a scroll gesture are discarded
+const SCROLL_IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Scrolled pixels which have not added up to a full line yet
+#[derive(Debug, Copy, Clone)]
+pub struct ScrollAccumulator {
+    pixels: f64,
+    last_scroll: Instant,
+}
+
+impl Default for ScrollAccumulator {
+    fn default() -> ScrollAccumulator {
+        ScrollAccumulator { pixels: 0., last_scroll: Instant::now() }
+    }
+}
+
+impl ScrollAccumulator {
+    /// Add scrolled pixels and take all full lines which have accumulated
+    ///
+    /// The remainder is carried over to the next scroll, unless the direction has reversed or
+    /// the scroll came in after an idle timeout.
+    pub fn lines(&mut self, pixels: f64, line_height: f64, now: Instant) -> i32 {
+        let idle = now.duration_since(self.last_scroll) > SCROLL_IDLE_TIMEOUT;
+        if idle || self.pixels * pixels < 0. {
+            self.pixels = 0.;
+        }
+        self.last_scroll = now;
+
+        self.pixels += pixels;
+        let lines = (self.pixels / line_height).trunc();
+        self.pixels -= lines * line_height;
+
+        lines as i32
+    }
+
+    /// Discard the leftover pixels
+    pub fn reset(&mut self) {
+        self.pixels = 0.;
+    }
 }
 
 /// Describes a state and action to take in that state
@@ -105,6 +317,103 @@ pub struct Binding<T> {
     ///
     /// For example, this might be a key like "G", or a mouse button.
     pub trigger: T,
+
+    /// Key chords which have to be pressed in order before the trigger
+    ///
+    /// This is always empty for mouse bindings.
+    pub prefix: Vec<KeyChord>,
+}
+
+/// Key pressed together with modifiers, one step of a key binding's trigger
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyChord {
+    pub key: Key,
+    pub mods: ModifiersState,
+}
+
+impl KeyChord {
+    /// Check if the chord is pressed by a key event
+    fn matches(&self, input: KeyboardInput) -> bool {
+        let key = match self.key {
+            Key::Scancode(_) => Key::Scancode(input.scancode),
+            _ => match input.virtual_keycode {
+                Some(key) => Key::from_glutin_input(key),
+                None => return false,
+            },
+        };
+
+        self.key == key && self.mods == input.modifiers
+    }
+}
+
+impl From<KeyboardInput> for KeyChord {
+    fn from(input: KeyboardInput) -> KeyChord {
+        let key = match input.virtual_keycode {
+            Some(key) => Key::from_glutin_input(key),
+            None => Key::Scancode(input.scancode),
+        };
+
+        KeyChord { key, mods: input.modifiers }
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mods = [
+            (self.mods.ctrl, "Control"),
+            (self.mods.alt, "Alt"),
+            (self.mods.shift, "Shift"),
+            (self.mods.logo, "Super"),
+        ];
+        for (_, name) in mods.iter().filter(|(enabled, _)| *enabled) {
+            write!(f, "{}+", name)?;
+        }
+
+        match self.key {
+            Key::Scancode(scancode) => write!(f, "{}", scancode),
+            key => write!(f, "{:?}", key),
+        }
+    }
+}
+
+/// Chords of key bindings which have been pressed, waiting for the next chord
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingKeys {
+    keys: Vec<PendingKey>,
+
+    /// Time of the last key press, the chord is aborted once it's too old
+    timestamp: Instant,
+}
+
+/// Pressed key of a pending chord with the text it produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PendingKey {
+    input: KeyboardInput,
+    text: Vec<u8>,
+}
+
+impl PendingKeys {
+    fn new() -> PendingKeys {
+        PendingKeys { keys: Vec::new(), timestamp: Instant::now() }
+    }
+
+    fn push(&mut self, input: KeyboardInput) {
+        self.keys.push(PendingKey { input, text: Vec::new() });
+        self.timestamp = Instant::now();
+    }
+
+    /// Check if the pressed keys are the start of a binding's chords
+    fn starts(&self, chords: &[KeyChord], next: KeyboardInput) -> bool {
+        let inputs = self.keys.iter().map(|key| key.input).chain(Some(next));
+        chords.len() > self.keys.len()
+            && chords.iter().zip(inputs).all(|(chord, input)| chord.matches(input))
+    }
+
+    /// Check if the pressed keys are exactly a binding's chords
+    fn matches(&self, chords: &[KeyChord]) -> bool {
+        chords.len() == self.keys.len()
+            && chords.iter().zip(self.keys.iter()).all(|(chord, key)| chord.matches(key.input))
+    }
 }
 
 /// Bindings that are triggered by a keyboard key
@@ -121,6 +430,7 @@ impl Default for KeyBinding {
             mode: TermMode::NONE,
             notmode: TermMode::NONE,
             trigger: Key::A,
+            prefix: Vec::new(),
         }
     }
 }
@@ -133,6 +443,7 @@ impl Default for MouseBinding {
             mode: TermMode::NONE,
             notmode: TermMode::NONE,
             trigger: MouseButton::Left,
+            prefix: Vec::new(),
         }
     }
 }
@@ -159,6 +470,7 @@ impl<T: Eq> Binding<T> {
     pub fn triggers_match(&self, binding: &Binding<T>) -> bool {
         self.trigger == binding.trigger
             && self.mods == binding.mods
+            && self.prefix == binding.prefix
             && (self.mode.contains(binding.mode) || binding.mode.contains(self.mode))
             && (self.notmode.contains(binding.notmode) || binding.notmode.contains(self.notmode))
     }
@@ -167,8 +479,8 @@ impl<T: Eq> Binding<T> {
 impl<T> Binding<T> {
     /// Execute the action associate with this binding
     #[inline]
-    fn execute<A: ActionContext>(&self, ctx: &mut A, mouse_mode: bool) {
-        self.action.execute(ctx, mouse_mode)
+    fn execute<A: ActionContext>(&self, ctx: &mut A) {
+        self.action.execute(ctx)
     }
 
     /// Check that two mods descriptions for equivalence
@@ -194,9 +506,16 @@ pub enum Action {
     // Store current selection into clipboard
     Copy,
 
+    /// Store current selection into the selection buffer
+    CopyToPrimary,
+
     /// Paste contents of selection buffer
+    #[serde(alias = "PastePrimary")]
     PasteSelection,
 
+    /// Extend the current selection to the mouse position
+    ExpandSelection,
+
     /// Increase font size
     IncreaseFontSize,
 
@@ -234,15 +553,22 @@ pub enum Action {
     /// Hides the Alacritty window
     Hide,
 
-    /// Quits Alacritty.
+    /// Closes the window, Alacritty quits once the last window is closed.
     Quit,
 
-    /// Clears warning and error notices.
-    ClearLogNotice,
+    /// Dismiss the message shown in the message bar.
+    #[serde(alias = "ClearLogNotice")]
+    ClearNotice,
+
+    /// Suppress all messages except for errors, or show them again.
+    ToggleMessages,
 
     /// Spawn a new instance of Alacritty.
     SpawnNewInstance,
 
+    /// Open a new window in the running Alacritty process.
+    CreateNewWindow,
+
     /// Toggle fullscreen.
     ToggleFullscreen,
 
@@ -250,6 +576,36 @@ pub enum Action {
     #[cfg(target_os = "macos")]
     ToggleSimpleFullscreen,
 
+    /// Toggle maximized.
+    ToggleMaximized,
+
+    /// Make the window more opaque.
+    IncreaseWindowOpacity,
+
+    /// Make the window more transparent.
+    DecreaseWindowOpacity,
+
+    /// Reset the window opacity to the config value.
+    ResetWindowOpacity,
+
+    /// Save the window content to a PNG file.
+    Screenshot,
+
+    /// Show or hide the render timer.
+    ToggleRenderTimer,
+
+    /// Switch to the next color scheme.
+    CycleColorScheme,
+
+    /// Switch to a color scheme by name.
+    SetColorScheme(String),
+
+    /// Switch to the next font family of `font.families`.
+    CycleFont,
+
+    /// Pass the key on to character input, like when no binding matched.
+    ReceiveChar,
+
     /// No action.
     None,
 }
@@ -262,7 +618,7 @@ impl Default for Action {
 
 impl Action {
     #[inline]
-    fn execute<A: ActionContext>(&self, ctx: &mut A, mouse_mode: bool) {
+    fn execute<A: ActionContext>(&self, ctx: &mut A) {
         match *self {
             Action::Esc(ref s) => {
                 ctx.scroll(Scroll::Bottom);
@@ -271,15 +627,22 @@ impl Action {
             Action::Copy => {
                 ctx.copy_selection(ClipboardType::Clipboard);
             },
+            Action::CopyToPrimary => {
+                ctx.copy_selection(ClipboardType::Selection);
+            },
             Action::Paste => {
                 let text = ctx.terminal_mut().clipboard().load(ClipboardType::Clipboard);
-                self.paste(ctx, &text);
+                paste(ctx, &text);
             },
             Action::PasteSelection => {
-                // Only paste if mouse events are not captured by an application
-                if !mouse_mode {
-                    let text = ctx.terminal_mut().clipboard().load(ClipboardType::Selection);
-                    self.paste(ctx, &text);
+                let text = ctx.terminal_mut().clipboard().load(ClipboardType::Selection);
+                paste(ctx, &text);
+            },
+            Action::ExpandSelection => {
+                if let Some(point) = ctx.mouse_coords() {
+                    let side = ctx.mouse().cell_side;
+                    ctx.update_selection(point, side);
+                    ctx.copy_selection(ClipboardType::Selection);
                 }
             },
             Action::Command(ref program, ref args) => {
@@ -297,6 +660,18 @@ impl Action {
             Action::ToggleSimpleFullscreen => {
                 ctx.toggle_simple_fullscreen();
             },
+            Action::ToggleMaximized => {
+                ctx.toggle_maximized();
+            },
+            Action::IncreaseWindowOpacity => {
+                ctx.change_window_opacity(OpacityChange::Step(WINDOW_OPACITY_STEP));
+            },
+            Action::DecreaseWindowOpacity => {
+                ctx.change_window_opacity(OpacityChange::Step(-WINDOW_OPACITY_STEP));
+            },
+            Action::ResetWindowOpacity => {
+                ctx.change_window_opacity(OpacityChange::Reset);
+            },
             Action::Hide => {
                 ctx.hide_window();
             },
@@ -304,10 +679,12 @@ impl Action {
                 ctx.terminal_mut().exit();
             },
             Action::IncreaseFontSize => {
-                ctx.terminal_mut().change_font_size(FONT_SIZE_STEP);
+                let step = ctx.terminal().font_size_step();
+                ctx.terminal_mut().change_font_size(step);
             },
             Action::DecreaseFontSize => {
-                ctx.terminal_mut().change_font_size(-FONT_SIZE_STEP);
+                let step = ctx.terminal().font_size_step();
+                ctx.terminal_mut().change_font_size(-step);
             },
             Action::ResetFontSize => {
                 ctx.terminal_mut().reset_font_size();
@@ -333,33 +710,67 @@ impl Action {
             Action::ClearHistory => {
                 ctx.terminal_mut().clear_screen(ClearMode::Saved);
             },
-            Action::ClearLogNotice => {
-                ctx.terminal_mut().message_buffer_mut().pop();
+            Action::ClearNotice => {
+                ctx.terminal_mut().dismiss_message();
+            },
+            Action::ToggleMessages => {
+                ctx.terminal_mut().toggle_do_not_disturb();
             },
             Action::SpawnNewInstance => {
                 ctx.spawn_new_instance();
             },
-            Action::None => (),
+            Action::CreateNewWindow => {
+                ctx.create_new_window();
+            },
+            Action::Screenshot => {
+                ctx.terminal_mut().request_screenshot();
+            },
+            Action::ToggleRenderTimer => {
+                ctx.terminal_mut().toggle_render_timer();
+            },
+            Action::CycleColorScheme => {
+                ctx.terminal_mut().cycle_color_scheme();
+            },
+            Action::SetColorScheme(ref name) => {
+                ctx.terminal_mut().set_color_scheme(name);
+            },
+            Action::CycleFont => {
+                ctx.terminal_mut().cycle_font_family(Instant::now());
+                ctx.schedule_wakeup(FONT_FAMILY_NOTICE_DURATION);
+            },
+            Action::ReceiveChar | Action::None => (),
         }
     }
+}
 
-    fn paste<A: ActionContext>(&self, ctx: &mut A, contents: &str) {
-        if ctx.terminal().mode().contains(TermMode::BRACKETED_PASTE) {
-            ctx.write_to_pty(&b"\x1b[200~"[..]);
-            ctx.write_to_pty(contents.replace("\x1b", "").into_bytes());
-            ctx.write_to_pty(&b"\x1b[201~"[..]);
-        } else {
-            // In non-bracketed (ie: normal) mode, terminal applications cannot distinguish
-            // pasted data from keystrokes.
-            // In theory, we should construct the keystrokes needed to produce the data we are
-            // pasting... since that's neither practical nor sensible (and probably an impossible
-            // task to solve in a general way), we'll just replace line breaks (windows and unix
-            // style) with a single carriage return (\r, which is what the Enter key produces).
-            ctx.write_to_pty(contents.replace("\r\n", "\r").replace("\n", "\r").into_bytes());
-        }
+/// Write text to the pty like it was pasted
+///
+/// This is used for everything inserted on behalf of the user, like the clipboards and dropped
+/// files, so all of them are bracketed the same way.
+fn paste<A: ActionContext>(ctx: &mut A, contents: &str) {
+    if ctx.terminal().mode().contains(TermMode::BRACKETED_PASTE) {
+        // Without escapes, the text can't end the bracketed paste early
+        ctx.write_to_pty(&b"\x1b[200~"[..]);
+        ctx.write_to_pty(contents.replace("\x1b", "").into_bytes());
+        ctx.write_to_pty(&b"\x1b[201~"[..]);
+    } else {
+        // In non-bracketed (ie: normal) mode, terminal applications cannot distinguish
+        // pasted data from keystrokes.
+        // In theory, we should construct the keystrokes needed to produce the data we are
+        // pasting... since that's neither practical nor sensible (and probably an impossible
+        // task to solve in a general way), we'll just replace line breaks (windows and unix
+        // style) with a single carriage return (\r, which is what the Enter key produces).
+        ctx.write_to_pty(contents.replace("\r\n", "\r").replace("\n", "\r").into_bytes());
     }
 }
 
+/// Quote a path for a POSIX shell, followed by a space to separate it from the next argument
+///
+/// Paths which are not valid UTF-8 are converted lossily.
+fn quote_path(path: &Path) -> String {
+    format!("'{}' ", path.to_string_lossy().replace("'", "'\\''"))
+}
+
 trait RelaxedEq<T: ?Sized = Self> {
     fn relaxed_eq(&self, other: T) -> bool;
 }
@@ -405,7 +816,8 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
 
         // Check for URL at point with required modifiers held
         if self.mouse_config.url.mods().relaxed_eq(modifiers)
-            && (!self.ctx.terminal().mode().intersects(mouse_mode) || modifiers.shift)
+            && (!self.mouse_mode().intersects(mouse_mode)
+                || self.bypasses_mouse_reporting(modifiers))
             && self.mouse_config.url.launcher.is_some()
         {
             let buffer_point = self.ctx.terminal().visible_to_buffer(point);
@@ -449,18 +861,20 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         match self.mouse_position(point, modifiers) {
             MousePosition::Url(url) => {
                 let url_bounds = url.linear_bounds(self.ctx.terminal());
-                self.ctx.terminal_mut().set_url_highlight(url_bounds);
-                self.ctx.terminal_mut().set_mouse_cursor(MouseCursor::Hand);
-                self.ctx.terminal_mut().dirty = true;
+                let terminal = self.ctx.terminal_mut();
+                terminal.set_url_highlight(url_bounds);
+                let cursor = terminal.app_mouse_cursor().unwrap_or(MouseCursorIcon::Hand);
+                terminal.set_next_mouse_cursor(cursor);
+                terminal.dirty = true;
             },
             MousePosition::MessageBar => {
                 self.ctx.terminal_mut().reset_url_highlight();
-                self.ctx.terminal_mut().set_mouse_cursor(MouseCursor::Default);
+                self.ctx.terminal_mut().set_next_mouse_cursor(MouseCursorIcon::Default);
                 return;
             },
             MousePosition::MessageBarButton => {
                 self.ctx.terminal_mut().reset_url_highlight();
-                self.ctx.terminal_mut().set_mouse_cursor(MouseCursor::Hand);
+                self.ctx.terminal_mut().set_next_mouse_cursor(MouseCursorIcon::Hand);
                 return;
             },
             MousePosition::Terminal => {
@@ -470,10 +884,12 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
 
         if self.ctx.mouse().left_button_state == ElementState::Pressed
-            && (modifiers.shift || !self.ctx.terminal().mode().intersects(report_mode))
+            && self.ctx.mouse().bound_button != Some(MouseButton::Left)
+            && (self.bypasses_mouse_reporting(modifiers)
+                || !self.mouse_mode().intersects(report_mode))
         {
             self.ctx.update_selection(Point { line: point.line, col: point.col }, cell_side);
-        } else if self.ctx.terminal().mode().intersects(motion_mode)
+        } else if self.mouse_mode().intersects(motion_mode)
             && size_info.contains_point(x, y, false)
             && cell_changed
         {
@@ -483,7 +899,7 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
                 self.mouse_report(33, ElementState::Pressed, modifiers);
             } else if self.ctx.mouse().right_button_state == ElementState::Pressed {
                 self.mouse_report(34, ElementState::Pressed, modifiers);
-            } else if self.ctx.terminal().mode().contains(TermMode::MOUSE_MOTION) {
+            } else if self.mouse_mode().contains(TermMode::MOUSE_MOTION) {
                 self.mouse_report(35, ElementState::Pressed, modifiers);
             }
         }
@@ -493,12 +909,12 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         let size_info = self.ctx.size_info();
         let x = self.ctx.mouse().x;
 
-        let cell_x = x.saturating_sub(size_info.padding_x as usize) % size_info.cell_width as usize;
+        let cell_x =
+            x.saturating_sub(size_info.padding_left as usize) % size_info.cell_width as usize;
         let half_cell_width = (size_info.cell_width / 2.0) as usize;
 
-        let additional_padding =
-            (size_info.width - size_info.padding_x * 2.) % size_info.cell_width;
-        let end_of_grid = size_info.width - size_info.padding_x - additional_padding;
+        let additional_padding = size_info.grid_width() % size_info.cell_width;
+        let end_of_grid = size_info.width - size_info.padding_right - additional_padding;
 
         if cell_x > half_cell_width
             // Edge case when mouse leaves the window
@@ -538,6 +954,26 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         self.ctx.write_to_pty(msg.into_bytes());
     }
 
+    /// Mouse reporting modes of the terminal which are currently in effect
+    ///
+    /// Nothing is reported while the viewport is scrolled into the history, since the
+    /// application doesn't know about the lines shown there. Mouse input is handled by Alacritty
+    /// instead, until the view is back at the bottom.
+    fn mouse_mode(&self) -> TermMode {
+        let terminal = self.ctx.terminal();
+        if terminal.display_offset() > 0 {
+            return TermMode::empty();
+        }
+
+        *terminal.mode()
+            & (TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_MOTION | TermMode::MOUSE_DRAG)
+    }
+
+    /// Check if mouse input goes to Alacritty even though mouse reporting is enabled
+    fn bypasses_mouse_reporting(&self, modifiers: ModifiersState) -> bool {
+        self.mouse_config.bypass_modifiers().map_or(false, |mods| mods.relaxed_eq(modifiers))
+    }
+
     pub fn mouse_report(&mut self, button: u8, state: ElementState, modifiers: ModifiersState) {
         // Calculate modifiers value
         let mut mods = 0;
@@ -579,27 +1015,21 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         modifiers: ModifiersState,
         point: Option<Point>,
     ) {
-        let now = Instant::now();
-        let elapsed = self.ctx.mouse().last_click_timestamp.elapsed();
-        self.ctx.mouse_mut().last_click_timestamp = now;
+        let click = Click { button, point, timestamp: Instant::now() };
+        let last_click = self.ctx.mouse_mut().last_click.replace(click);
+        let click_state = self.ctx.mouse().click_state.next(last_click, click, self.mouse_config);
 
-        let button_changed = self.ctx.mouse().last_button != button;
-
-        self.ctx.mouse_mut().click_state = match self.ctx.mouse().click_state {
-            ClickState::Click
-                if !button_changed && elapsed < self.mouse_config.double_click.threshold =>
-            {
+        self.ctx.mouse_mut().click_state = match click_state {
+            ClickState::DoubleClick => {
                 self.ctx.mouse_mut().block_url_launcher = true;
                 self.on_mouse_double_click(button, point);
                 ClickState::DoubleClick
-            }
-            ClickState::DoubleClick
-                if !button_changed && elapsed < self.mouse_config.triple_click.threshold =>
-            {
+            },
+            ClickState::TripleClick => {
                 self.ctx.mouse_mut().block_url_launcher = true;
                 self.on_mouse_triple_click(button, point);
                 ClickState::TripleClick
-            }
+            },
             _ => {
                 // Don't launch URLs if this click cleared the selection
                 self.ctx.mouse_mut().block_url_launcher = !self.ctx.selection_is_empty();
@@ -618,7 +1048,9 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
 
                 let report_modes =
                     TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
-                if !modifiers.shift && self.ctx.terminal().mode().intersects(report_modes) {
+                if !self.bypasses_mouse_reporting(modifiers)
+                    && self.mouse_mode().intersects(report_modes)
+                {
                     let code = match button {
                         MouseButton::Left => 0,
                         MouseButton::Middle => 1,
@@ -643,7 +1075,7 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     ) {
         let report_modes =
             TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
-        if !modifiers.shift && self.ctx.terminal().mode().intersects(report_modes) {
+        if !self.bypasses_mouse_reporting(modifiers) && self.mouse_mode().intersects(report_modes) {
             let code = match button {
                 MouseButton::Left => 0,
                 MouseButton::Middle => 1,
@@ -672,9 +1104,14 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         let url = self.ctx.terminal().urls().drain(..).find(|url| url.contains(point))?;
         let text = self.ctx.terminal().url_to_string(&url);
 
+        self.launch(text)
+    }
+
+    /// Open a URL or file with the URL launcher
+    fn launch(&self, target: String) -> Option<()> {
         let launcher = self.mouse_config.url.launcher.as_ref()?;
         let mut args = launcher.args().to_vec();
-        args.push(text);
+        args.push(target);
 
         match start_daemon(launcher.program(), &args) {
             Ok(_) => debug!("Launched {} with args {:?}", launcher.program(), args),
@@ -692,17 +1129,17 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     ) {
         match delta {
             MouseScrollDelta::LineDelta(_columns, lines) => {
-                let new_scroll_px = lines * self.ctx.size_info().cell_height;
-                self.scroll_terminal(modifiers, new_scroll_px as i32);
+                let new_scroll_px = f64::from(lines * self.ctx.size_info().cell_height);
+                self.scroll_terminal(modifiers, new_scroll_px);
             },
             MouseScrollDelta::PixelDelta(lpos) => {
                 match phase {
                     TouchPhase::Started => {
                         // Reset offset to zero
-                        self.ctx.mouse_mut().scroll_px = 0;
+                        self.ctx.mouse_mut().scroll_delta.reset();
                     },
                     TouchPhase::Moved => {
-                        self.scroll_terminal(modifiers, lpos.y as i32);
+                        self.scroll_terminal(modifiers, lpos.y);
                     },
                     _ => (),
                 }
@@ -710,53 +1147,60 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
     }
 
-    fn scroll_terminal(&mut self, modifiers: ModifiersState, new_scroll_px: i32) {
+    fn scroll_terminal(&mut self, modifiers: ModifiersState, new_scroll_px: f64) {
         let mouse_modes =
             TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
-        let height = self.ctx.size_info().cell_height as i32;
+        let height = f64::from(self.ctx.size_info().cell_height);
+        let now = Instant::now();
 
         // Make sure the new and deprecated setting are both allowed
         let faux_multiplier = self.scrolling_config.faux_multiplier() as usize;
 
-        if self.ctx.terminal().mode().intersects(mouse_modes) {
-            self.ctx.mouse_mut().scroll_px += new_scroll_px;
+        // The viewport is scrolled while the bypass modifiers are held
+        let bypass = self.bypasses_mouse_reporting(modifiers);
 
-            let code = if new_scroll_px > 0 { 64 } else { 65 };
-            let lines = (self.ctx.mouse().scroll_px / height).abs();
+        if !bypass && self.mouse_mode().intersects(mouse_modes) {
+            let lines = self.ctx.mouse_mut().scroll_delta.lines(new_scroll_px, height, now);
 
-            for _ in 0..lines {
+            let code = if lines > 0 { 64 } else { 65 };
+            for _ in 0..lines.abs() {
                 self.mouse_report(code, ElementState::Pressed, modifiers);
             }
         } else if self.ctx.terminal().mode().contains(TermMode::ALT_SCREEN)
             && faux_multiplier > 0
-            && !modifiers.shift
+            && !bypass
         {
-            self.ctx.mouse_mut().scroll_px += new_scroll_px * faux_multiplier as i32;
+            let new_scroll_px = new_scroll_px * faux_multiplier as f64;
+            let lines = self.ctx.mouse_mut().scroll_delta.lines(new_scroll_px, height, now);
 
-            let cmd = if new_scroll_px > 0 { b'A' } else { b'B' };
-            let lines = (self.ctx.mouse().scroll_px / height).abs();
-
-            let mut content = Vec::with_capacity(lines as usize * 3);
-            for _ in 0..lines {
+            let cmd = if lines > 0 { b'A' } else { b'B' };
+            let mut content = Vec::with_capacity(lines.abs() as usize * 3);
+            for _ in 0..lines.abs() {
                 content.push(0x1b);
                 content.push(b'O');
                 content.push(cmd);
             }
             self.ctx.write_to_pty(content);
         } else {
-            let multiplier = i32::from(self.scrolling_config.multiplier());
-            self.ctx.mouse_mut().scroll_px += new_scroll_px * multiplier;
-
-            let lines = self.ctx.mouse().scroll_px / height;
+            let new_scroll_px = new_scroll_px * f64::from(self.scrolling_config.multiplier());
+            let lines = self.ctx.mouse_mut().scroll_delta.lines(new_scroll_px, height, now);
 
             self.ctx.scroll(Scroll::Lines(lines as isize));
         }
+    }
+
+    /// Insert the shell-quoted paths of files dropped onto the window
+    pub fn on_dropped_files(&mut self, paths: &[PathBuf]) {
+        if !self.mouse_config.drop_files() || paths.is_empty() {
+            return;
+        }
 
-        self.ctx.mouse_mut().scroll_px %= height;
+        let contents: String = paths.iter().map(|path| quote_path(path)).collect();
+        paste(&mut self.ctx, &contents);
     }
 
     pub fn on_focus_change(&mut self, is_focused: bool) {
-        if self.ctx.terminal().mode().contains(TermMode::FOCUS_IN_OUT) {
+        if self.ctx.terminal().reports_focus() {
             let chr = if is_focused { "I" } else { "O" };
 
             let msg = format!("\x1b[{}", chr);
@@ -764,6 +1208,13 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
     }
 
+    /// Send the current focus state once the application enabled focus reporting
+    pub fn report_focus_state(&mut self, is_focused: bool) {
+        if self.ctx.terminal_mut().take_focus_report_request() {
+            self.on_focus_change(is_focused);
+        }
+    }
+
     pub fn mouse_input(
         &mut self,
         state: ElementState,
@@ -787,41 +1238,130 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         } else {
             match state {
                 ElementState::Pressed => {
-                    self.process_mouse_bindings(modifiers, button);
-                    self.on_mouse_press(button, modifiers, point);
+                    // Bindings replace the built-in selection and reporting for this click
+                    if self.process_mouse_bindings(modifiers, button) {
+                        self.ctx.mouse_mut().bound_button = Some(button);
+                    } else {
+                        self.on_mouse_press(button, modifiers, point);
+                    }
+                },
+                ElementState::Released => {
+                    if self.ctx.mouse().bound_button == Some(button) {
+                        self.ctx.mouse_mut().bound_button = None;
+                    } else {
+                        self.on_mouse_release(button, modifiers, point);
+                    }
                 },
-                ElementState::Released => self.on_mouse_release(button, modifiers, point),
             }
         }
-
-        self.ctx.mouse_mut().last_button = button;
     }
 
     /// Process key input
     pub fn process_key(&mut self, input: KeyboardInput) {
+        self.ctx.option_keys().update(input);
+
         match input.state {
             ElementState::Pressed => {
                 *self.ctx.last_modifiers() = input.modifiers;
                 *self.ctx.received_count() = 0;
                 *self.ctx.suppress_chars() = false;
 
-                if self.process_key_bindings(input) {
+                if *self.ctx.composing() {
+                    self.process_compose_key(input);
+                } else if self.process_key_chords(input)
+                    || self.process_key_bindings(input, &PendingKeys::new())
+                    || self.process_keypad(input)
+                    || self.process_option_as_alt(input)
+                {
                     *self.ctx.suppress_chars() = true;
+                } else {
+                    // A dead key is only recognized by the absence of its character
+                    *self.ctx.composing() = is_text_key(input);
                 }
             },
             ElementState::Released => *self.ctx.suppress_chars() = false,
         }
     }
 
-    /// Process a received character
-    pub fn received_char(&mut self, c: char) {
-        if *self.ctx.suppress_chars() {
+    /// Process the key pressed after a dead key
+    ///
+    /// The composed character is received as text, so neither bindings nor the raw key
+    /// are used for it. Escape cancels the composition without writing anything.
+    fn process_compose_key(&mut self, input: KeyboardInput) {
+        // Shift might be needed for the key completing the composition
+        if is_modifier(input) {
             return;
         }
 
+        *self.ctx.composing() = false;
+
+        if input.virtual_keycode == Some(VirtualKeyCode::Escape) {
+            *self.ctx.suppress_chars() = true;
+        }
+    }
+
+    /// Send the application sequence of a keypad key in application keypad mode
+    ///
+    /// Keypad keys with NumLock off are reported as navigation keys, so digits are
+    /// only reported with NumLock on and stay numeric like in xterm. Macs don't
+    /// have a NumLock key.
+    fn process_keypad(&mut self, input: KeyboardInput) -> bool {
+        let mods = input.modifiers;
+        if mods.ctrl || mods.alt || mods.logo {
+            return false;
+        }
+
+        let app_keypad = self.ctx.terminal().mode().contains(TermMode::APP_KEYPAD);
+        let num_lock = cfg!(not(target_os = "macos"));
+        let sequence = match input
+            .virtual_keycode
+            .and_then(|key| keypad_sequence(key, app_keypad, num_lock))
+        {
+            Some(sequence) => sequence,
+            None => return false,
+        };
+
+        self.ctx.scroll(Scroll::Bottom);
+        self.ctx.clear_selection();
+
+        self.ctx.write_to_pty(sequence.as_bytes());
+
+        true
+    }
+
+    /// Send ESC followed by the key when it's pressed with an Option key used as Alt
+    ///
+    /// The character composed by macOS for this key is ignored, pressing the
+    /// key with the other Option key still composes characters as usual.
+    fn process_option_as_alt(&mut self, input: KeyboardInput) -> bool {
+        let keys = *self.ctx.option_keys();
+        if !input.modifiers.alt || !self.option_as_alt.matches(keys.left, keys.right) {
+            return false;
+        }
+
+        let c = match input.virtual_keycode.and_then(|key| key_char(key, input.modifiers.shift)) {
+            Some(c) => c,
+            None => return false,
+        };
+
         self.ctx.scroll(Scroll::Bottom);
         self.ctx.clear_selection();
 
+        self.ctx.write_to_pty(format!("\x1b{}", c).into_bytes());
+
+        true
+    }
+
+    /// Process a received character
+    pub fn received_char(&mut self, c: char) {
+        *self.ctx.composing() = false;
+
+        // Text of a pending key chord is kept until the chord is complete or aborted
+        let chord_pending = self.ctx.pending_keys().is_some();
+        if *self.ctx.suppress_chars() && !chord_pending {
+            return;
+        }
+
         let utf8_len = c.len_utf8();
         let mut bytes = Vec::with_capacity(utf8_len);
         unsafe {
@@ -830,6 +1370,7 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
 
         if self.alt_send_esc
+            && self.ctx.terminal().mode().contains(TermMode::ALT_SEND_ESC)
             && *self.ctx.received_count() == 0
             && self.ctx.last_modifiers().alt
             && utf8_len == 1
@@ -837,92 +1378,231 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
             bytes.insert(0, b'\x1b');
         }
 
-        self.ctx.write_to_pty(bytes);
-
         *self.ctx.received_count() += 1;
+
+        match self.ctx.pending_keys().as_mut().and_then(|pending| pending.keys.last_mut()) {
+            Some(key) => key.text.extend_from_slice(&bytes),
+            None => self.write_text(bytes),
+        }
     }
 
-    /// Attempts to find a binding and execute its action
-    ///
-    /// The provided mode, mods, and key must match what is allowed by a binding
-    /// for its action to be executed.
-    ///
-    /// Returns true if an action is executed.
-    fn process_key_bindings(&mut self, input: KeyboardInput) -> bool {
-        let mut has_binding = false;
-        for binding in self.key_bindings {
-            let is_triggered = match binding.trigger {
-                Key::Scancode(_) => binding.is_triggered_by(
-                    *self.ctx.terminal().mode(),
-                    input.modifiers,
-                    &Key::Scancode(input.scancode),
-                    false,
-                ),
-                _ => {
-                    if let Some(key) = input.virtual_keycode {
-                        let key = Key::from_glutin_input(key);
-                        binding.is_triggered_by(
-                            *self.ctx.terminal().mode(),
-                            input.modifiers,
-                            &key,
-                            false,
-                        )
-                    } else {
-                        false
-                    }
-                },
-            };
+    /// Write typed text to the pty
+    fn write_text(&mut self, bytes: Vec<u8>) {
+        self.ctx.scroll(Scroll::Bottom);
+        self.ctx.clear_selection();
+
+        self.ctx.write_to_pty(bytes);
+    }
 
-            if is_triggered {
-                // binding was triggered; run the action
-                binding.execute(&mut self.ctx, false);
-                has_binding = true;
+    /// Abort the pending key chord when the next chord hasn't been pressed in time
+    pub fn check_key_chord_timeout(&mut self) {
+        let timeout = self.key_chords_config.timeout();
+        let expired = match self.ctx.pending_keys() {
+            Some(pending) => pending.timestamp.elapsed() >= timeout,
+            None => false,
+        };
+
+        if expired {
+            if let Some(pending) = self.ctx.pending_keys().take() {
+                self.abort_key_chord(pending);
             }
         }
-
-        has_binding
     }
 
-    /// Attempts to find a binding and execute its action
+    /// Process the next chord of key bindings with multiple chords
     ///
-    /// The provided mode, mods, and key must match what is allowed by a binding
-    /// for its action to be executed.
+    /// A key which starts or continues the chords of a binding always waits for the next
+    /// chord, even when it's a binding of its own. Once a key doesn't fit any binding, the
+    /// pending keys are replayed as if they were pressed on their own.
     ///
-    /// Returns true if an action is executed.
-    fn process_mouse_bindings(&mut self, mods: ModifiersState, button: MouseButton) -> bool {
-        let mut has_binding = false;
-        for binding in self.mouse_bindings {
-            if binding.is_triggered_by(*self.ctx.terminal().mode(), mods, &button, true) {
-                // binding was triggered; run the action
-                let mouse_mode = !mods.shift
-                    && self.ctx.terminal().mode().intersects(
-                        TermMode::MOUSE_REPORT_CLICK
-                            | TermMode::MOUSE_DRAG
-                            | TermMode::MOUSE_MOTION,
-                    );
-                binding.execute(&mut self.ctx, mouse_mode);
-                has_binding = true;
-            }
+    /// Returns true if the key has been consumed.
+    fn process_key_chords(&mut self, input: KeyboardInput) -> bool {
+        let pending = self.ctx.pending_keys().take();
+
+        // Modifiers of the next chord neither continue nor abort the pending chord
+        if pending.is_some() && is_modifier(input) {
+            *self.ctx.pending_keys() = pending;
+            return true;
         }
 
-        has_binding
-    }
+        let mut pending = pending.unwrap_or_else(PendingKeys::new);
+        let mode = self.binding_mode();
+        let continues = self.key_bindings.iter().any(|binding| {
+            mode.contains(binding.mode)
+                && !mode.intersects(binding.notmode)
+                && pending.starts(&binding.prefix, input)
+        });
+
+        if continues {
+            pending.push(input);
+            self.show_key_chord_indicator(&pending);
+            self.ctx.schedule_wakeup(self.key_chords_config.timeout());
+            *self.ctx.pending_keys() = Some(pending);
+            return true;
+        }
 
-    /// Return the message bar's message if there is some at the specified point
-    fn message_at_point(&mut self, point: Option<Point>) -> Option<Message> {
-        if let (Some(point), Some(message)) =
-            (point, self.ctx.terminal_mut().message_buffer_mut().message())
-        {
-            let size = self.ctx.size_info();
-            if point.line.0 >= size.lines().saturating_sub(message.text(&size).len()) {
-                return Some(message);
-            }
+        if pending.keys.is_empty() {
+            return false;
         }
 
-        None
+        if self.process_key_bindings(input, &pending) {
+            self.hide_key_chord_indicator();
+            return true;
+        }
+
+        self.abort_key_chord(pending);
+
+        if self.key_chords_config.forward_aborted() {
+            // Process the key on its own once the pending keys are out of the way
+            self.process_key_chords(input)
+        } else {
+            // The key which aborted the chord is discarded together with it
+            true
+        }
     }
 
-    /// Whether the point is over the message bar's close button
+    /// Replay or discard the keys of an incomplete key chord
+    fn abort_key_chord(&mut self, pending: PendingKeys) {
+        self.hide_key_chord_indicator();
+
+        if !self.key_chords_config.forward_aborted() {
+            return;
+        }
+
+        let empty = PendingKeys::new();
+        for key in pending.keys {
+            if !self.process_key_bindings(key.input, &empty)
+                && !self.process_option_as_alt(key.input)
+            {
+                self.write_text(key.text);
+            }
+        }
+    }
+
+    fn show_key_chord_indicator(&mut self, pending: &PendingKeys) {
+        if !self.key_chords_config.indicator {
+            return;
+        }
+
+        let chords: Vec<String> =
+            pending.keys.iter().map(|key| KeyChord::from(key.input).to_string()).collect();
+        let mut message =
+            Message::new(format!("Key chord: {} ...", chords.join(" ")), color::YELLOW);
+        message.set_topic(KEY_CHORD_TOPIC.into());
+
+        let terminal = self.ctx.terminal_mut();
+        terminal.message_buffer_mut().remove_topic(KEY_CHORD_TOPIC);
+        let _ = terminal.message_buffer().tx().send(message);
+        terminal.dirty = true;
+    }
+
+    fn hide_key_chord_indicator(&mut self) {
+        if self.key_chords_config.indicator {
+            let terminal = self.ctx.terminal_mut();
+            terminal.message_buffer_mut().remove_topic(KEY_CHORD_TOPIC);
+            terminal.dirty = true;
+        }
+    }
+
+    /// Attempts to find a binding and execute its action
+    ///
+    /// The provided mode, mods, and key must match what is allowed by a binding
+    /// for its action to be executed. The chords pressed before the key have to
+    /// match the binding's prefix.
+    ///
+    /// Returns true if an action is executed.
+    fn process_key_bindings(&mut self, input: KeyboardInput, pending: &PendingKeys) -> bool {
+        let mode = self.binding_mode();
+        let bindings = self.key_bindings;
+        let triggered = bindings.iter().filter(|binding| {
+            if !pending.matches(&binding.prefix) {
+                return false;
+            }
+
+            match binding.trigger {
+                Key::Scancode(_) => binding.is_triggered_by(
+                    mode,
+                    input.modifiers,
+                    &Key::Scancode(input.scancode),
+                    false,
+                ),
+                _ => match input.virtual_keycode {
+                    Some(key) => binding.is_triggered_by(
+                        mode,
+                        input.modifiers,
+                        &Key::from_glutin_input(key),
+                        false,
+                    ),
+                    None => false,
+                },
+            }
+        });
+
+        self.execute_bindings(triggered.collect())
+    }
+
+    /// Terminal modes and modes of Alacritty's UI, which bindings are matched against
+    fn binding_mode(&mut self) -> TermMode {
+        *self.ctx.terminal().mode() | *self.ctx.ui_mode()
+    }
+
+    /// Execute the triggered bindings with the highest precedence
+    ///
+    /// Bindings which require one of the UI modes replace all other bindings for the same
+    /// trigger, bindings without such a mode are executed together like before.
+    ///
+    /// Returns false if no binding was executed, or if all of them let the input through with
+    /// `ReceiveChar`.
+    fn execute_bindings<T>(&mut self, mut triggered: Vec<&Binding<T>>) -> bool {
+        if triggered.iter().any(|binding| binding.mode.intersects(TermMode::UI)) {
+            triggered.retain(|binding| binding.mode.intersects(TermMode::UI));
+        }
+
+        for binding in &triggered {
+            binding.execute(&mut self.ctx);
+        }
+
+        triggered.iter().any(|binding| binding.action != Action::ReceiveChar)
+    }
+
+    /// Attempts to find a binding and execute its action
+    ///
+    /// The provided mode, mods, and key must match what is allowed by a binding
+    /// for its action to be executed.
+    ///
+    /// Returns true if an action is executed.
+    fn process_mouse_bindings(&mut self, mods: ModifiersState, button: MouseButton) -> bool {
+        // Clicks belong to the application while it has mouse reporting enabled, unless the
+        // bypass modifiers are held
+        let report_modes =
+            TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+        if !self.bypasses_mouse_reporting(mods) && self.mouse_mode().intersects(report_modes) {
+            return false;
+        }
+
+        let mode = self.binding_mode();
+        let bindings = self.mouse_bindings;
+        let triggered =
+            bindings.iter().filter(|binding| binding.is_triggered_by(mode, mods, &button, true));
+
+        self.execute_bindings(triggered.collect())
+    }
+
+    /// Return the message bar's message if there is some at the specified point
+    fn message_at_point(&mut self, point: Option<Point>) -> Option<Message> {
+        if let (Some(point), Some(message)) =
+            (point, self.ctx.terminal_mut().message_buffer_mut().message())
+        {
+            let size = self.ctx.size_info();
+            if point.line.0 >= size.lines().saturating_sub(message.text(&size).len()) {
+                return Some(message);
+            }
+        }
+
+        None
+    }
+
+    /// Whether the point is over the message bar's close button
     fn message_close_at_point(&self, point: Point, message: Message) -> bool {
         let size = self.ctx.size_info();
         point.col + message_bar::CLOSE_BUTTON_TEXT.len() >= size.cols()
@@ -934,9 +1614,14 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         match button_state {
             ElementState::Released => self.copy_selection(),
             ElementState::Pressed => {
+                let target = message.target().map(|target| target.to_string_lossy().into_owned());
+
                 if self.message_close_at_point(point, message) {
-                    self.ctx.terminal_mut().message_buffer_mut().pop();
+                    self.ctx.terminal_mut().dismiss_message();
                     self.ctx.terminal_mut().reset_mouse_cursor();
+                } else if let Some(target) = target {
+                    // Open the file the message is about, like the config file with an error
+                    self.launch(target);
                 }
 
                 self.ctx.clear_selection();
@@ -953,23 +1638,149 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     }
 }
 
+/// Check if the key is one of the modifier keys
+fn is_modifier(input: KeyboardInput) -> bool {
+    match input.virtual_keycode {
+        Some(VirtualKeyCode::LAlt)
+        | Some(VirtualKeyCode::RAlt)
+        | Some(VirtualKeyCode::LControl)
+        | Some(VirtualKeyCode::RControl)
+        | Some(VirtualKeyCode::LShift)
+        | Some(VirtualKeyCode::RShift)
+        | Some(VirtualKeyCode::LWin)
+        | Some(VirtualKeyCode::RWin) => true,
+        _ => false,
+    }
+}
+
+/// Check if a key is expected to produce text when it's pressed
+///
+/// Keys with Control or Super usually don't, so they're never taken for dead keys.
+fn is_text_key(input: KeyboardInput) -> bool {
+    let mods = input.modifiers;
+    !mods.ctrl
+        && !mods.logo
+        && input.virtual_keycode.and_then(|key| key_char(key, mods.shift)).is_some()
+}
+
+/// Sequence sent by a keypad key instead of its character
+///
+/// Only the application keypad mode has sequences of its own, except for digits
+/// and the decimal separator while NumLock is on.
+fn keypad_sequence(key: VirtualKeyCode, app_keypad: bool, num_lock: bool) -> Option<&'static str> {
+    use glutin::VirtualKeyCode::*;
+
+    match key {
+        _ if !app_keypad => None,
+        Numpad0 if !num_lock => Some("\x1bOp"),
+        Numpad1 if !num_lock => Some("\x1bOq"),
+        Numpad2 if !num_lock => Some("\x1bOr"),
+        Numpad3 if !num_lock => Some("\x1bOs"),
+        Numpad4 if !num_lock => Some("\x1bOt"),
+        Numpad5 if !num_lock => Some("\x1bOu"),
+        Numpad6 if !num_lock => Some("\x1bOv"),
+        Numpad7 if !num_lock => Some("\x1bOw"),
+        Numpad8 if !num_lock => Some("\x1bOx"),
+        Numpad9 if !num_lock => Some("\x1bOy"),
+        Decimal if !num_lock => Some("\x1bOn"),
+        NumpadComma => Some("\x1bOl"),
+        Subtract => Some("\x1bOm"),
+        Add => Some("\x1bOk"),
+        Multiply => Some("\x1bOj"),
+        Divide => Some("\x1bOo"),
+        NumpadEquals => Some("\x1bOX"),
+        NumpadEnter => Some("\x1bOM"),
+        _ => None,
+    }
+}
+
+/// Character of a key without any Option composition
+///
+/// The characters are based on the US keyboard layout, since the layout
+/// independent character is not available without the composition.
+fn key_char(key: VirtualKeyCode, shift: bool) -> Option<char> {
+    use glutin::VirtualKeyCode::*;
+
+    let (c, shifted) = match key {
+        A => ('a', 'A'),
+        B => ('b', 'B'),
+        C => ('c', 'C'),
+        D => ('d', 'D'),
+        E => ('e', 'E'),
+        F => ('f', 'F'),
+        G => ('g', 'G'),
+        H => ('h', 'H'),
+        I => ('i', 'I'),
+        J => ('j', 'J'),
+        K => ('k', 'K'),
+        L => ('l', 'L'),
+        M => ('m', 'M'),
+        N => ('n', 'N'),
+        O => ('o', 'O'),
+        P => ('p', 'P'),
+        Q => ('q', 'Q'),
+        R => ('r', 'R'),
+        S => ('s', 'S'),
+        T => ('t', 'T'),
+        U => ('u', 'U'),
+        V => ('v', 'V'),
+        W => ('w', 'W'),
+        X => ('x', 'X'),
+        Y => ('y', 'Y'),
+        Z => ('z', 'Z'),
+        Key1 => ('1', '!'),
+        Key2 => ('2', '@'),
+        Key3 => ('3', '#'),
+        Key4 => ('4', '$'),
+        Key5 => ('5', '%'),
+        Key6 => ('6', '^'),
+        Key7 => ('7', '&'),
+        Key8 => ('8', '*'),
+        Key9 => ('9', '('),
+        Key0 => ('0', ')'),
+        Space => (' ', ' '),
+        Minus => ('-', '_'),
+        Equals => ('=', '+'),
+        LBracket => ('[', '{'),
+        RBracket => (']', '}'),
+        Backslash => ('\\', '|'),
+        Semicolon => (';', ':'),
+        Apostrophe => ('\'', '"'),
+        Grave => ('`', '~'),
+        Comma => (',', '<'),
+        Period => ('.', '>'),
+        Slash => ('/', '?'),
+        _ => return None,
+    };
+
+    Some(if shift { shifted } else { c })
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
-    use std::time::Duration;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
 
-    use glutin::{ElementState, Event, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent};
+    use glutin::dpi::LogicalPosition;
+    use glutin::{
+        ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        TouchPhase, VirtualKeyCode, WindowEvent,
+    };
 
+    use crate::ansi::{self, Handler};
     use crate::clipboard::{Clipboard, ClipboardType};
     use crate::config::{self, ClickHandler, Config};
-    use crate::event::{ClickState, Mouse, WindowChanges};
     use crate::grid::Scroll;
-    use crate::index::{Point, Side};
+    use crate::index::{Column, Line, Point, Side};
     use crate::message_bar::MessageBuffer;
     use crate::selection::Selection;
-    use crate::term::{SizeInfo, Term, TermMode};
+    use crate::term::{OpacityChange, SizeInfo, Term, TermMode};
 
-    use super::{Action, Binding, Processor};
+    use super::{
+        keypad_sequence, quote_path, Action, Binding, Click, ClickState, Mouse, OptionKeys,
+        PendingKeys, Processor, ScrollAccumulator, WindowChanges, SCROLL_IDLE_TIMEOUT,
+    };
 
     const KEY: VirtualKeyCode = VirtualKeyCode::Key0;
 
@@ -983,10 +1794,23 @@ mod tests {
                 mode: TermMode::empty(),
                 notmode: TermMode::empty(),
                 trigger: Default::default(),
+                prefix: Vec::new(),
             }
         }
     }
 
+    #[test]
+    fn paste_primary_alias() {
+        let action: Action = serde_yaml::from_str("PastePrimary").unwrap();
+        assert_eq!(action, Action::PasteSelection);
+    }
+
+    #[test]
+    fn clear_log_notice_alias() {
+        let action: Action = serde_yaml::from_str("ClearLogNotice").unwrap();
+        assert_eq!(action, Action::ClearNotice);
+    }
+
     #[test]
     fn binding_matches_itself() {
         let binding = MockBinding::default();
@@ -1113,12 +1937,20 @@ mod tests {
         pub last_action: MultiClick,
         pub received_count: usize,
         pub suppress_chars: bool,
+        pub composing: bool,
         pub last_modifiers: ModifiersState,
+        pub option_keys: OptionKeys,
+        pub pending_keys: Option<PendingKeys>,
+        pub ui_mode: TermMode,
         pub window_changes: &'a mut WindowChanges,
+        pub written: Vec<u8>,
+        pub copied: Vec<ClipboardType>,
     }
 
     impl<'a> super::ActionContext for ActionContext<'a> {
-        fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, _val: B) {}
+        fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, val: B) {
+            self.written.extend_from_slice(&val.into());
+        }
 
         fn update_selection(&mut self, _point: Point, _side: Side) {}
 
@@ -1126,7 +1958,10 @@ mod tests {
 
         fn block_selection(&mut self, _point: Point, _side: Side) {}
 
-        fn copy_selection(&mut self, _: ClipboardType) {}
+        fn copy_selection(&mut self, ty: ClipboardType) {
+            self.terminal.copy_selection(ty);
+            self.copied.push(ty);
+        }
 
         fn clear_selection(&mut self) {}
 
@@ -1139,6 +1974,12 @@ mod tests {
         #[cfg(target_os = "macos")]
         fn toggle_simple_fullscreen(&mut self) {}
 
+        fn toggle_maximized(&mut self) {}
+
+        fn change_window_opacity(&mut self, _change: OpacityChange) {}
+
+        fn create_new_window(&mut self) {}
+
         fn terminal(&self) -> &Term {
             &self.terminal
         }
@@ -1190,9 +2031,27 @@ mod tests {
             &mut self.suppress_chars
         }
 
+        fn composing(&mut self) -> &mut bool {
+            &mut self.composing
+        }
+
         fn last_modifiers(&mut self) -> &mut ModifiersState {
             &mut self.last_modifiers
         }
+
+        fn option_keys(&mut self) -> &mut OptionKeys {
+            &mut self.option_keys
+        }
+
+        fn pending_keys(&mut self) -> &mut Option<PendingKeys> {
+            &mut self.pending_keys
+        }
+
+        fn ui_mode(&mut self) -> &mut TermMode {
+            &mut self.ui_mode
+        }
+
+        fn schedule_wakeup(&mut self, _delay: Duration) {}
     }
 
     macro_rules! test_clickstate {
@@ -1212,8 +2071,10 @@ mod tests {
                     height: 51.0,
                     cell_width: 3.0,
                     cell_height: 3.0,
-                    padding_x: 0.0,
-                    padding_y: 0.0,
+                    padding_left: 0.0,
+                    padding_right: 0.0,
+                    padding_top: 0.0,
+                    padding_bottom: 0.0,
                     dpr: 1.0,
                 };
 
@@ -1221,7 +2082,11 @@ mod tests {
 
                 let mut mouse = Mouse::default();
                 mouse.click_state = $initial_state;
-                mouse.last_button = $initial_button;
+                mouse.last_click = Some(Click {
+                    button: $initial_button,
+                    point: Some(Point::default()),
+                    timestamp: Instant::now(),
+                });
 
                 let mut selection = None;
 
@@ -1233,27 +2098,34 @@ mod tests {
                     last_action: MultiClick::None,
                     received_count: 0,
                     suppress_chars: false,
+                    composing: false,
                     last_modifiers: ModifiersState::default(),
+                    option_keys: OptionKeys::default(),
+                    pending_keys: None,
+                    ui_mode: TermMode::empty(),
                     window_changes: &mut WindowChanges::default(),
+                    written: Vec::new(),
+                    copied: Vec::new(),
+                };
+
+                let mut mouse_config = config::Mouse::default();
+                mouse_config.double_click = ClickHandler {
+                    threshold: Duration::from_millis(1000),
+                };
+                mouse_config.triple_click = ClickHandler {
+                    threshold: Duration::from_millis(1000),
                 };
 
                 let mut processor = Processor {
                     ctx: context,
-                    mouse_config: &config::Mouse {
-                        double_click: ClickHandler {
-                            threshold: Duration::from_millis(1000),
-                        },
-                        triple_click: ClickHandler {
-                            threshold: Duration::from_millis(1000),
-                        },
-                        hide_when_typing: false,
-                        url: Default::default(),
-                    },
+                    mouse_config: &mouse_config,
                     scrolling_config: &config::Scrolling::default(),
+                    key_chords_config: &config.key_chords,
                     key_bindings: &config.key_bindings[..],
                     mouse_bindings: &config.mouse_bindings[..],
                     save_to_clipboard: config.selection.save_to_clipboard,
                     alt_send_esc: config.alt_send_esc(),
+                    option_as_alt: config.option_as_alt(),
                 };
 
                 if let Event::WindowEvent { event: WindowEvent::MouseInput { state, button, modifiers, .. }, .. } = $input {
@@ -1287,135 +2159,2029 @@ mod tests {
         }
     }
 
-    test_clickstate! {
-        name: single_click,
-        initial_state: ClickState::None,
-        initial_button: MouseButton::Other(0),
-        input: Event::WindowEvent {
-            event: WindowEvent::MouseInput {
-                state: ElementState::Pressed,
-                button: MouseButton::Left,
-                device_id: unsafe { ::std::mem::transmute_copy(&0) },
-                modifiers: ModifiersState::default(),
-            },
-            window_id: unsafe { ::std::mem::transmute_copy(&0) },
-        },
-        end_state: ClickState::Click,
-        last_action: MultiClick::None
+    macro_rules! test_clipboard {
+        {
+            name: $name:ident,
+            save_to_clipboard: $save_to_clipboard:expr,
+            input: |$processor:ident| $input:expr,
+            copied: $copied:expr,
+            written: $written:expr
+        } => {
+            #[test]
+            fn $name() {
+                let config = Config::default();
+                let size = SizeInfo {
+                    width: 21.0,
+                    height: 51.0,
+                    cell_width: 3.0,
+                    cell_height: 3.0,
+                    padding_left: 0.0,
+                    padding_right: 0.0,
+                    padding_top: 0.0,
+                    padding_bottom: 0.0,
+                    dpr: 1.0,
+                };
+
+                let clipboard = Clipboard::new_memory();
+                let mut terminal = Term::new(&config, size, MessageBuffer::new(), clipboard);
+                terminal.clipboard().store(ClipboardType::Clipboard, "clipboard");
+                terminal.clipboard().store(ClipboardType::Selection, "primary");
+
+                let mut mouse = Mouse::default();
+                let mut selection = None;
+
+                let context = ActionContext {
+                    terminal: &mut terminal,
+                    selection: &mut selection,
+                    mouse: &mut mouse,
+                    size_info: &size,
+                    last_action: MultiClick::None,
+                    received_count: 0,
+                    suppress_chars: false,
+                    composing: false,
+                    last_modifiers: ModifiersState::default(),
+                    option_keys: OptionKeys::default(),
+                    pending_keys: None,
+                    ui_mode: TermMode::empty(),
+                    window_changes: &mut WindowChanges::default(),
+                    written: Vec::new(),
+                    copied: Vec::new(),
+                };
+
+                let mut $processor = Processor {
+                    ctx: context,
+                    mouse_config: &config.mouse,
+                    scrolling_config: &config.scrolling,
+                    key_chords_config: &config.key_chords,
+                    key_bindings: &config.key_bindings[..],
+                    mouse_bindings: &config.mouse_bindings[..],
+                    save_to_clipboard: $save_to_clipboard,
+                    alt_send_esc: config.alt_send_esc(),
+                    option_as_alt: config.option_as_alt(),
+                };
+
+                $input;
+
+                let copied: &[ClipboardType] = &$copied;
+                assert_eq!(&$processor.ctx.copied[..], copied);
+                assert_eq!(&$processor.ctx.written[..], &$written[..]);
+            }
+        }
     }
 
-    test_clickstate! {
-        name: double_click,
-        initial_state: ClickState::Click,
-        initial_button: MouseButton::Left,
-        input: Event::WindowEvent {
-            event: WindowEvent::MouseInput {
-                state: ElementState::Pressed,
-                button: MouseButton::Left,
-                device_id: unsafe { ::std::mem::transmute_copy(&0) },
-                modifiers: ModifiersState::default(),
-            },
-            window_id: unsafe { ::std::mem::transmute_copy(&0) },
+    test_clipboard! {
+        name: selection_release_copies_to_primary,
+        save_to_clipboard: false,
+        input: |processor| {
+            processor.mouse_input(ElementState::Released, MouseButton::Left, Default::default())
         },
-        end_state: ClickState::DoubleClick,
-        last_action: MultiClick::DoubleClick
+        copied: [ClipboardType::Selection],
+        written: b""
     }
 
-    test_clickstate! {
-        name: triple_click,
-        initial_state: ClickState::DoubleClick,
-        initial_button: MouseButton::Left,
-        input: Event::WindowEvent {
-            event: WindowEvent::MouseInput {
-                state: ElementState::Pressed,
-                button: MouseButton::Left,
-                device_id: unsafe { ::std::mem::transmute_copy(&0) },
-                modifiers: ModifiersState::default(),
-            },
-            window_id: unsafe { ::std::mem::transmute_copy(&0) },
+    test_clipboard! {
+        name: selection_release_copies_to_both_buffers,
+        save_to_clipboard: true,
+        input: |processor| {
+            processor.mouse_input(ElementState::Released, MouseButton::Left, Default::default())
         },
-        end_state: ClickState::TripleClick,
-        last_action: MultiClick::TripleClick
+        copied: [ClipboardType::Clipboard, ClipboardType::Selection],
+        written: b""
     }
 
-    test_clickstate! {
-        name: multi_click_separate_buttons,
-        initial_state: ClickState::DoubleClick,
-        initial_button: MouseButton::Left,
-        input: Event::WindowEvent {
-            event: WindowEvent::MouseInput {
-                state: ElementState::Pressed,
-                button: MouseButton::Right,
-                device_id: unsafe { ::std::mem::transmute_copy(&0) },
-                modifiers: ModifiersState::default(),
-            },
-            window_id: unsafe { ::std::mem::transmute_copy(&0) },
+    test_clipboard! {
+        name: middle_click_pastes_primary,
+        save_to_clipboard: false,
+        input: |processor| {
+            processor.mouse_input(ElementState::Pressed, MouseButton::Middle, Default::default())
         },
-        end_state: ClickState::Click,
-        last_action: MultiClick::None
+        copied: [],
+        written: b"primary"
     }
 
-    test_process_binding! {
-        name: process_binding_nomode_shiftmod_require_shift,
-        binding: Binding { trigger: KEY, mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }, action: Action::from("\x1b[1;2D"), mode: TermMode::NONE, notmode: TermMode::NONE },
-        triggers: true,
-        mode: TermMode::NONE,
-        mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }
+    test_clipboard! {
+        name: paste_action_reads_clipboard,
+        save_to_clipboard: false,
+        input: |processor| Action::Paste.execute(&mut processor.ctx),
+        copied: [],
+        written: b"clipboard"
     }
 
-    test_process_binding! {
-        name: process_binding_nomode_nomod_require_shift,
-        binding: Binding { trigger: KEY, mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }, action: Action::from("\x1b[1;2D"), mode: TermMode::NONE, notmode: TermMode::NONE },
-        triggers: false,
-        mode: TermMode::NONE,
-        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    test_clipboard! {
+        name: copy_action_writes_clipboard,
+        save_to_clipboard: false,
+        input: |processor| Action::Copy.execute(&mut processor.ctx),
+        copied: [ClipboardType::Clipboard],
+        written: b""
     }
 
-    test_process_binding! {
-        name: process_binding_nomode_controlmod,
-        binding: Binding { trigger: KEY, mods: ModifiersState { ctrl: true, shift: false, alt: false, logo: false }, action: Action::from("\x1b[1;5D"), mode: TermMode::NONE, notmode: TermMode::NONE },
-        triggers: true,
-        mode: TermMode::NONE,
-        mods: ModifiersState { ctrl: true, shift: false, alt: false, logo: false }
+    test_clipboard! {
+        name: copy_to_primary_action_writes_primary,
+        save_to_clipboard: false,
+        input: |processor| Action::CopyToPrimary.execute(&mut processor.ctx),
+        copied: [ClipboardType::Selection],
+        written: b""
     }
 
-    test_process_binding! {
-        name: process_binding_nomode_nomod_require_not_appcursor,
-        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }, action: Action::from("\x1b[D"), mode: TermMode::NONE, notmode: TermMode::APP_CURSOR },
-        triggers: true,
-        mode: TermMode::NONE,
-        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    test_clipboard! {
+        name: paste_primary_action_reads_primary,
+        save_to_clipboard: false,
+        input: |processor| Action::PasteSelection.execute(&mut processor.ctx),
+        copied: [],
+        written: b"primary"
     }
 
-    test_process_binding! {
-        name: process_binding_appcursormode_nomod_require_appcursor,
-        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }, action: Action::from("\x1bOD"), mode: TermMode::APP_CURSOR, notmode: TermMode::NONE },
-        triggers: true,
-        mode: TermMode::APP_CURSOR,
-        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    test_clipboard! {
+        name: paste_action_bracketed,
+        save_to_clipboard: false,
+        input: |processor| {
+            processor.ctx.terminal.set_mode(ansi::Mode::BracketedPaste);
+            processor.ctx.terminal.clipboard().store(ClipboardType::Clipboard, "a\nb\x1b[201~");
+            Action::Paste.execute(&mut processor.ctx);
+        },
+        copied: [],
+        written: b"\x1b[200~a\nb[201~\x1b[201~"
     }
 
-    test_process_binding! {
-        name: process_binding_nomode_nomod_require_appcursor,
-        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }, action: Action::from("\x1bOD"), mode: TermMode::APP_CURSOR, notmode: TermMode::NONE },
-        triggers: false,
-        mode: TermMode::NONE,
-        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    test_clipboard! {
+        name: paste_action_line_breaks,
+        save_to_clipboard: false,
+        input: |processor| {
+            processor.ctx.terminal.clipboard().store(ClipboardType::Clipboard, "a\r\nb\nc");
+            Action::Paste.execute(&mut processor.ctx);
+        },
+        copied: [],
+        written: b"a\rb\rc"
     }
 
-    test_process_binding! {
-        name: process_binding_appcursormode_appkeypadmode_nomod_require_appcursor,
-        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }, action: Action::from("\x1bOD"), mode: TermMode::APP_CURSOR, notmode: TermMode::NONE },
-        triggers: true,
-        mode: TermMode::APP_CURSOR | TermMode::APP_KEYPAD,
-        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    test_clipboard! {
+        name: middle_click_bracketed,
+        save_to_clipboard: false,
+        input: |processor| {
+            processor.ctx.terminal.set_mode(ansi::Mode::BracketedPaste);
+            processor.ctx.terminal.clipboard().store(ClipboardType::Selection, "a\nb\x1b[201~");
+            processor.mouse_input(ElementState::Pressed, MouseButton::Middle, Default::default());
+        },
+        copied: [],
+        written: b"\x1b[200~a\nb[201~\x1b[201~"
     }
 
-    test_process_binding! {
-        name: process_binding_fail_with_extra_mods,
-        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: true }, action: Action::from("arst"), mode: TermMode::NONE, notmode: TermMode::NONE },
-        triggers: false,
-        mode: TermMode::NONE,
-        mods: ModifiersState { shift: false, ctrl: false, alt: true, logo: true }
+    test_clipboard! {
+        name: middle_click_line_breaks,
+        save_to_clipboard: false,
+        input: |processor| {
+            processor.ctx.terminal.clipboard().store(ClipboardType::Selection, "a\r\nb\nc");
+            processor.mouse_input(ElementState::Pressed, MouseButton::Middle, Default::default());
+        },
+        copied: [],
+        written: b"a\rb\rc"
+    }
+
+    test_clipboard! {
+        name: copy_without_selection_keeps_clipboard,
+        save_to_clipboard: false,
+        input: |processor| {
+            Action::Copy.execute(&mut processor.ctx);
+            Action::CopyToPrimary.execute(&mut processor.ctx);
+            Action::Paste.execute(&mut processor.ctx);
+            Action::PasteSelection.execute(&mut processor.ctx);
+        },
+        copied: [ClipboardType::Clipboard, ClipboardType::Selection],
+        written: b"clipboardprimary"
+    }
+
+    macro_rules! test_option_as_alt {
+        {
+            name: $name:ident,
+            option_as_alt: $option_as_alt:expr,
+            option_key: $option_key:expr,
+            shift: $shift:expr,
+            composed: $composed:expr,
+            written: $written:expr
+        } => {
+            #[test]
+            fn $name() {
+                let config = Config::default();
+                let size = SizeInfo {
+                    width: 21.0,
+                    height: 51.0,
+                    cell_width: 3.0,
+                    cell_height: 3.0,
+                    padding_left: 0.0,
+                    padding_right: 0.0,
+                    padding_top: 0.0,
+                    padding_bottom: 0.0,
+                    dpr: 1.0,
+                };
+
+                let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+
+                let mut mouse = Mouse::default();
+                let mut selection = None;
+
+                let context = ActionContext {
+                    terminal: &mut terminal,
+                    selection: &mut selection,
+                    mouse: &mut mouse,
+                    size_info: &size,
+                    last_action: MultiClick::None,
+                    received_count: 0,
+                    suppress_chars: false,
+                    composing: false,
+                    last_modifiers: ModifiersState::default(),
+                    option_keys: OptionKeys::default(),
+                    pending_keys: None,
+                    ui_mode: TermMode::empty(),
+                    window_changes: &mut WindowChanges::default(),
+                    written: Vec::new(),
+                    copied: Vec::new(),
+                };
+
+                let mut processor = Processor {
+                    ctx: context,
+                    mouse_config: &config.mouse,
+                    scrolling_config: &config.scrolling,
+                    key_chords_config: &config.key_chords,
+                    key_bindings: &[],
+                    mouse_bindings: &[],
+                    save_to_clipboard: false,
+                    alt_send_esc: true,
+                    option_as_alt: $option_as_alt,
+                };
+
+                let modifiers = ModifiersState { alt: true, shift: $shift, ..Default::default() };
+                let key = |virtual_keycode, state| KeyboardInput {
+                    scancode: 0,
+                    state,
+                    virtual_keycode: Some(virtual_keycode),
+                    modifiers,
+                };
+
+                processor.process_key(key($option_key, ElementState::Pressed));
+                processor.process_key(key(VirtualKeyCode::B, ElementState::Pressed));
+                processor.received_char($composed);
+                processor.process_key(key(VirtualKeyCode::B, ElementState::Released));
+                processor.process_key(key($option_key, ElementState::Released));
+
+                assert_eq!(processor.ctx.written, $written.as_bytes());
+                assert_eq!(processor.ctx.option_keys, OptionKeys::default());
+            }
+        }
+    }
+
+    test_option_as_alt! {
+        name: option_as_alt_none,
+        option_as_alt: config::OptionAsAlt::None,
+        option_key: VirtualKeyCode::LAlt,
+        shift: false,
+        composed: '∫',
+        written: "∫"
+    }
+
+    test_option_as_alt! {
+        name: option_as_alt_only_left_with_left,
+        option_as_alt: config::OptionAsAlt::OnlyLeft,
+        option_key: VirtualKeyCode::LAlt,
+        shift: false,
+        composed: '∫',
+        written: "\x1bb"
+    }
+
+    test_option_as_alt! {
+        name: option_as_alt_only_left_with_right,
+        option_as_alt: config::OptionAsAlt::OnlyLeft,
+        option_key: VirtualKeyCode::RAlt,
+        shift: false,
+        composed: '∫',
+        written: "∫"
+    }
+
+    test_option_as_alt! {
+        name: option_as_alt_only_right_with_right,
+        option_as_alt: config::OptionAsAlt::OnlyRight,
+        option_key: VirtualKeyCode::RAlt,
+        shift: false,
+        composed: '∫',
+        written: "\x1bb"
+    }
+
+    test_option_as_alt! {
+        name: option_as_alt_only_right_with_left,
+        option_as_alt: config::OptionAsAlt::OnlyRight,
+        option_key: VirtualKeyCode::LAlt,
+        shift: false,
+        composed: '∫',
+        written: "∫"
+    }
+
+    test_option_as_alt! {
+        name: option_as_alt_both_with_left,
+        option_as_alt: config::OptionAsAlt::Both,
+        option_key: VirtualKeyCode::LAlt,
+        shift: false,
+        composed: '∫',
+        written: "\x1bb"
+    }
+
+    test_option_as_alt! {
+        name: option_as_alt_both_with_right_and_shift,
+        option_as_alt: config::OptionAsAlt::Both,
+        option_key: VirtualKeyCode::RAlt,
+        shift: true,
+        composed: 'ı',
+        written: "\x1bB"
+    }
+
+    macro_rules! test_alt_send_esc {
+        {
+            name: $name:ident,
+            alt_send_esc: $alt_send_esc:expr,
+            interpret_meta_key: $interpret_meta_key:expr,
+            key: $key:expr,
+            shift: $shift:expr,
+            received: $received:expr,
+            written: $written:expr
+        } => {
+            #[test]
+            fn $name() {
+                let config = Config::default();
+                let size = SizeInfo {
+                    width: 21.0,
+                    height: 51.0,
+                    cell_width: 3.0,
+                    cell_height: 3.0,
+                    padding_left: 0.0,
+                    padding_right: 0.0,
+                    padding_top: 0.0,
+                    padding_bottom: 0.0,
+                    dpr: 1.0,
+                };
+
+                let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+                if !$interpret_meta_key {
+                    terminal.unset_mode(ansi::Mode::InterpretMetaKey);
+                }
+
+                let mut mouse = Mouse::default();
+                let mut selection = None;
+
+                let context = ActionContext {
+                    terminal: &mut terminal,
+                    selection: &mut selection,
+                    mouse: &mut mouse,
+                    size_info: &size,
+                    last_action: MultiClick::None,
+                    received_count: 0,
+                    suppress_chars: false,
+                    composing: false,
+                    last_modifiers: ModifiersState::default(),
+                    option_keys: OptionKeys::default(),
+                    pending_keys: None,
+                    ui_mode: TermMode::empty(),
+                    window_changes: &mut WindowChanges::default(),
+                    written: Vec::new(),
+                    copied: Vec::new(),
+                };
+
+                let mut processor = Processor {
+                    ctx: context,
+                    mouse_config: &config.mouse,
+                    scrolling_config: &config.scrolling,
+                    key_chords_config: &config.key_chords,
+                    key_bindings: &[],
+                    mouse_bindings: &[],
+                    save_to_clipboard: false,
+                    alt_send_esc: $alt_send_esc,
+                    option_as_alt: config::OptionAsAlt::None,
+                };
+
+                let modifiers = ModifiersState { alt: true, shift: $shift, ..Default::default() };
+                let key = |state| KeyboardInput {
+                    scancode: 0,
+                    state,
+                    virtual_keycode: Some($key),
+                    modifiers,
+                };
+
+                processor.process_key(key(ElementState::Pressed));
+                processor.received_char($received);
+                processor.process_key(key(ElementState::Released));
+
+                assert_eq!(processor.ctx.written, $written.as_bytes());
+            }
+        }
+    }
+
+    test_alt_send_esc! {
+        name: alt_send_esc_letter,
+        alt_send_esc: true,
+        interpret_meta_key: true,
+        key: VirtualKeyCode::A,
+        shift: false,
+        received: 'a',
+        written: "\x1ba"
+    }
+
+    test_alt_send_esc! {
+        name: alt_send_esc_shifted_number,
+        alt_send_esc: true,
+        interpret_meta_key: true,
+        key: VirtualKeyCode::Key3,
+        shift: true,
+        received: '#',
+        written: "\x1b#"
+    }
+
+    test_alt_send_esc! {
+        name: alt_send_esc_disabled_letter,
+        alt_send_esc: false,
+        interpret_meta_key: true,
+        key: VirtualKeyCode::A,
+        shift: false,
+        received: 'a',
+        written: "a"
+    }
+
+    test_alt_send_esc! {
+        name: alt_send_esc_disabled_shifted_number,
+        alt_send_esc: false,
+        interpret_meta_key: true,
+        key: VirtualKeyCode::Key3,
+        shift: true,
+        received: '#',
+        written: "#"
+    }
+
+    test_alt_send_esc! {
+        name: alt_send_esc_overridden_by_meta_key_mode,
+        alt_send_esc: true,
+        interpret_meta_key: false,
+        key: VirtualKeyCode::A,
+        shift: false,
+        received: 'a',
+        written: "a"
+    }
+
+    test_clickstate! {
+        name: single_click,
+        initial_state: ClickState::None,
+        initial_button: MouseButton::Other(0),
+        input: Event::WindowEvent {
+            event: WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                device_id: unsafe { ::std::mem::transmute_copy(&0) },
+                modifiers: ModifiersState::default(),
+            },
+            window_id: unsafe { ::std::mem::transmute_copy(&0) },
+        },
+        end_state: ClickState::Click,
+        last_action: MultiClick::None
+    }
+
+    test_clickstate! {
+        name: double_click,
+        initial_state: ClickState::Click,
+        initial_button: MouseButton::Left,
+        input: Event::WindowEvent {
+            event: WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                device_id: unsafe { ::std::mem::transmute_copy(&0) },
+                modifiers: ModifiersState::default(),
+            },
+            window_id: unsafe { ::std::mem::transmute_copy(&0) },
+        },
+        end_state: ClickState::DoubleClick,
+        last_action: MultiClick::DoubleClick
+    }
+
+    test_clickstate! {
+        name: triple_click,
+        initial_state: ClickState::DoubleClick,
+        initial_button: MouseButton::Left,
+        input: Event::WindowEvent {
+            event: WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                device_id: unsafe { ::std::mem::transmute_copy(&0) },
+                modifiers: ModifiersState::default(),
+            },
+            window_id: unsafe { ::std::mem::transmute_copy(&0) },
+        },
+        end_state: ClickState::TripleClick,
+        last_action: MultiClick::TripleClick
+    }
+
+    test_clickstate! {
+        name: multi_click_separate_buttons,
+        initial_state: ClickState::DoubleClick,
+        initial_button: MouseButton::Left,
+        input: Event::WindowEvent {
+            event: WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                device_id: unsafe { ::std::mem::transmute_copy(&0) },
+                modifiers: ModifiersState::default(),
+            },
+            window_id: unsafe { ::std::mem::transmute_copy(&0) },
+        },
+        end_state: ClickState::Click,
+        last_action: MultiClick::None
+    }
+
+    test_process_binding! {
+        name: process_binding_nomode_shiftmod_require_shift,
+        binding: Binding { trigger: KEY, mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }, action: Action::from("\x1b[1;2D"), mode: TermMode::NONE, notmode: TermMode::NONE, prefix: Vec::new() },
+        triggers: true,
+        mode: TermMode::NONE,
+        mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }
+    }
+
+    test_process_binding! {
+        name: process_binding_nomode_nomod_require_shift,
+        binding: Binding { trigger: KEY, mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }, action: Action::from("\x1b[1;2D"), mode: TermMode::NONE, notmode: TermMode::NONE, prefix: Vec::new() },
+        triggers: false,
+        mode: TermMode::NONE,
+        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    }
+
+    test_process_binding! {
+        name: process_binding_nomode_controlmod,
+        binding: Binding { trigger: KEY, mods: ModifiersState { ctrl: true, shift: false, alt: false, logo: false }, action: Action::from("\x1b[1;5D"), mode: TermMode::NONE, notmode: TermMode::NONE, prefix: Vec::new() },
+        triggers: true,
+        mode: TermMode::NONE,
+        mods: ModifiersState { ctrl: true, shift: false, alt: false, logo: false }
+    }
+
+    test_process_binding! {
+        name: process_binding_nomode_nomod_require_not_appcursor,
+        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }, action: Action::from("\x1b[D"), mode: TermMode::NONE, notmode: TermMode::APP_CURSOR, prefix: Vec::new() },
+        triggers: true,
+        mode: TermMode::NONE,
+        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    }
+
+    test_process_binding! {
+        name: process_binding_appcursormode_nomod_require_appcursor,
+        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }, action: Action::from("\x1bOD"), mode: TermMode::APP_CURSOR, notmode: TermMode::NONE, prefix: Vec::new() },
+        triggers: true,
+        mode: TermMode::APP_CURSOR,
+        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    }
+
+    test_process_binding! {
+        name: process_binding_nomode_nomod_require_appcursor,
+        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }, action: Action::from("\x1bOD"), mode: TermMode::APP_CURSOR, notmode: TermMode::NONE, prefix: Vec::new() },
+        triggers: false,
+        mode: TermMode::NONE,
+        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    }
+
+    test_process_binding! {
+        name: process_binding_appcursormode_appkeypadmode_nomod_require_appcursor,
+        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }, action: Action::from("\x1bOD"), mode: TermMode::APP_CURSOR, notmode: TermMode::NONE, prefix: Vec::new() },
+        triggers: true,
+        mode: TermMode::APP_CURSOR | TermMode::APP_KEYPAD,
+        mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: false }
+    }
+
+    test_process_binding! {
+        name: process_binding_fail_with_extra_mods,
+        binding: Binding { trigger: KEY, mods: ModifiersState { shift: false, ctrl: false, alt: false, logo: true }, action: Action::from("arst"), mode: TermMode::NONE, notmode: TermMode::NONE, prefix: Vec::new() },
+        triggers: false,
+        mode: TermMode::NONE,
+        mods: ModifiersState { shift: false, ctrl: false, alt: true, logo: true }
+    }
+
+    const CHORD_BINDINGS: &str = "key_bindings:
+  - { keys: [{ key: A, mods: Control }, { key: V }], chars: chord }
+  - { key: B, mods: Control, chars: single }
+  - { keys: [{ key: B, mods: Control }, { key: C }], chars: overlap }
+";
+
+    /// Press keys with the chord bindings, returning what has been written to the pty
+    ///
+    /// Each key is pressed with or without Control and produces its character. When `expire`
+    /// is set, the pending chord is checked for its timeout after the last key.
+    fn press_chord_keys(
+        key_chords: &str,
+        keys: &[(VirtualKeyCode, bool, char)],
+        expire: bool,
+    ) -> (String, bool) {
+        let config: Config = serde_yaml::from_str(&format!("{}{}", CHORD_BINDINGS, key_chords))
+            .expect("deserialize config");
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &config.scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &config.key_bindings[..],
+            mouse_bindings: &config.mouse_bindings[..],
+            save_to_clipboard: false,
+            alt_send_esc: false,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        for &(key, ctrl, c) in keys {
+            let input = |state| KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(key),
+                modifiers: ModifiersState { ctrl, ..Default::default() },
+            };
+
+            processor.process_key(input(ElementState::Pressed));
+            processor.received_char(c);
+            processor.process_key(input(ElementState::Released));
+        }
+
+        if expire {
+            processor.check_key_chord_timeout();
+        }
+
+        let written = String::from_utf8(processor.ctx.written).unwrap();
+        (written, processor.ctx.pending_keys.is_some())
+    }
+
+    #[test]
+    fn key_chord_completes() {
+        let keys = [(VirtualKeyCode::A, true, '\x01'), (VirtualKeyCode::V, false, 'v')];
+        assert_eq!(press_chord_keys("", &keys, false), (String::from("chord"), false));
+    }
+
+    #[test]
+    fn key_chord_waits_for_next_chord() {
+        let keys = [(VirtualKeyCode::A, true, '\x01'), (VirtualKeyCode::LControl, true, '\0')];
+        assert_eq!(press_chord_keys("", &keys[..1], true), (String::new(), true));
+
+        // Modifiers for the next chord don't abort the chord
+        let (written, pending) = press_chord_keys("", &keys, true);
+        assert!(written.is_empty() && pending);
+    }
+
+    #[test]
+    fn key_chord_timeout_expires() {
+        let keys = [(VirtualKeyCode::A, true, '\x01')];
+        let timeout = "key_chords:\n  timeout: 0\n";
+        assert_eq!(press_chord_keys(timeout, &keys, true), (String::from("\x01"), false));
+
+        let discard = "key_chords:\n  timeout: 0\n  forward_aborted: false\n";
+        assert_eq!(press_chord_keys(discard, &keys, true), (String::new(), false));
+    }
+
+    #[test]
+    fn key_chord_abort_forwards_keys() {
+        let keys = [(VirtualKeyCode::A, true, '\x01'), (VirtualKeyCode::X, false, 'x')];
+        assert_eq!(press_chord_keys("", &keys, false), (String::from("\x01x"), false));
+    }
+
+    #[test]
+    fn key_chord_abort_swallows_keys() {
+        let keys = [(VirtualKeyCode::A, true, '\x01'), (VirtualKeyCode::X, false, 'x')];
+        let discard = "key_chords:\n  forward_aborted: false\n";
+        assert_eq!(press_chord_keys(discard, &keys, false), (String::new(), false));
+    }
+
+    #[test]
+    fn key_chord_prefix_overlaps_single_key_binding() {
+        // The chord wins while it can still be completed
+        let keys = [(VirtualKeyCode::B, true, '\x02'), (VirtualKeyCode::C, false, 'c')];
+        assert_eq!(press_chord_keys("", &keys, false), (String::from("overlap"), false));
+
+        // The single key binding runs once the chord is aborted
+        let keys = [(VirtualKeyCode::B, true, '\x02'), (VirtualKeyCode::X, false, 'x')];
+        assert_eq!(press_chord_keys("", &keys, false), (String::from("singlex"), false));
+
+        let timeout = "key_chords:\n  timeout: 0\n";
+        assert_eq!(press_chord_keys(timeout, &keys[..1], true), (String::from("single"), false));
+    }
+
+    const MODE_BINDINGS: &str = "key_bindings:
+  - { key: J, chars: global }
+  - { key: J, chars: vi, mode: Vi }
+  - { key: K, chars: global }
+  - { key: L, chars: global }
+  - { key: L, action: ReceiveChar, mode: Search }
+  - { key: H, chars: notvi, mode: ~Vi }
+  - { key: U, chars: global }
+  - { key: U, chars: appcursor, mode: AppCursor }
+";
+
+    /// Press a key with the mode bindings, returning what has been written to the pty
+    fn press_mode_key(
+        ui_mode: TermMode,
+        term_mode: Option<ansi::Mode>,
+        key: VirtualKeyCode,
+        c: char,
+    ) -> String {
+        let config: Config = serde_yaml::from_str(MODE_BINDINGS).expect("deserialize config");
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        if let Some(mode) = term_mode {
+            terminal.set_mode(mode);
+        }
+
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode,
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &config.scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &config.key_bindings[..],
+            mouse_bindings: &config.mouse_bindings[..],
+            save_to_clipboard: false,
+            alt_send_esc: false,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        let input = |state| KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState::default(),
+        };
+
+        processor.process_key(input(ElementState::Pressed));
+        processor.received_char(c);
+        processor.process_key(input(ElementState::Released));
+
+        String::from_utf8(processor.ctx.written).unwrap()
+    }
+
+    #[test]
+    fn ui_mode_binding_replaces_global_binding() {
+        assert_eq!(press_mode_key(TermMode::empty(), None, VirtualKeyCode::J, 'j'), "global");
+        assert_eq!(press_mode_key(TermMode::VI, None, VirtualKeyCode::J, 'j'), "vi");
+    }
+
+    #[test]
+    fn global_binding_in_ui_mode() {
+        assert_eq!(press_mode_key(TermMode::VI, None, VirtualKeyCode::K, 'k'), "global");
+        assert_eq!(press_mode_key(TermMode::SEARCH, None, VirtualKeyCode::J, 'j'), "global");
+    }
+
+    #[test]
+    fn receive_char_falls_through_to_input() {
+        assert_eq!(press_mode_key(TermMode::SEARCH, None, VirtualKeyCode::L, 'l'), "l");
+        assert_eq!(press_mode_key(TermMode::empty(), None, VirtualKeyCode::L, 'l'), "global");
+    }
+
+    #[test]
+    fn negated_ui_mode_binding() {
+        assert_eq!(press_mode_key(TermMode::empty(), None, VirtualKeyCode::H, 'h'), "notvi");
+        assert_eq!(press_mode_key(TermMode::VI, None, VirtualKeyCode::H, 'h'), "h");
+        assert_eq!(press_mode_key(TermMode::HINT, None, VirtualKeyCode::H, 'h'), "notvi");
+    }
+
+    #[test]
+    fn unbound_key_in_ui_mode() {
+        assert_eq!(press_mode_key(TermMode::VI, None, VirtualKeyCode::M, 'm'), "m");
+    }
+
+    #[test]
+    fn terminal_mode_bindings_run_together() {
+        let app_cursor = Some(ansi::Mode::CursorKeys);
+        assert_eq!(
+            press_mode_key(TermMode::empty(), app_cursor, VirtualKeyCode::U, 'u'),
+            "globalappcursor"
+        );
+        assert_eq!(
+            press_mode_key(TermMode::VI, app_cursor, VirtualKeyCode::U, 'u'),
+            "globalappcursor"
+        );
+    }
+
+    /// Scroll a touchpad gesture in a terminal mode, returning what has been written to the pty
+    fn scroll_pixels(mode: ansi::Mode, deltas: &[f64]) -> String {
+        let config = Config::default();
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        terminal.set_mode(mode);
+
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &config.scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &[],
+            mouse_bindings: &[],
+            save_to_clipboard: false,
+            alt_send_esc: false,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        let mods = ModifiersState::default();
+        let pixels = |y| MouseScrollDelta::PixelDelta(LogicalPosition::new(0., y));
+        processor.on_mouse_wheel(pixels(0.), TouchPhase::Started, mods);
+        for &delta in deltas {
+            processor.on_mouse_wheel(pixels(delta), TouchPhase::Moved, mods);
+        }
+        processor.on_mouse_wheel(pixels(0.), TouchPhase::Ended, mods);
+
+        String::from_utf8(processor.ctx.written).unwrap()
+    }
+
+    /// Scroll the mouse wheel up by two lines, returning what has been written to the pty and
+    /// the display offset afterwards
+    fn scroll_wheel(
+        config: &Config,
+        modes: &[ansi::Mode],
+        mods: ModifiersState,
+    ) -> (String, usize) {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        // Fill the scrollback, so there is something to scroll to
+        let mut terminal = Term::new(config, size, MessageBuffer::new(), Clipboard::new_nop());
+        for _ in 0..40 {
+            terminal.linefeed();
+        }
+        for &mode in modes {
+            terminal.set_mode(mode);
+        }
+
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let scrolling: config::Scrolling = serde_yaml::from_str("multiplier: 1").unwrap();
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &[],
+            mouse_bindings: &[],
+            save_to_clipboard: false,
+            alt_send_esc: false,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        processor.on_mouse_wheel(MouseScrollDelta::LineDelta(0., 2.), TouchPhase::Moved, mods);
+
+        let display_offset = processor.ctx.terminal.grid().display_offset();
+        (String::from_utf8(processor.ctx.written).unwrap(), display_offset)
+    }
+
+    const SHIFT: ModifiersState =
+        ModifiersState { shift: true, ctrl: false, alt: false, logo: false };
+
+    #[test]
+    fn wheel_reports_without_bypass_modifiers() {
+        let config = Config::default();
+        let mouse_modes = [
+            ansi::Mode::ReportMouseClicks,
+            ansi::Mode::ReportCellMouseMotion,
+            ansi::Mode::ReportAllMouseMotion,
+        ];
+
+        for &mode in mouse_modes.iter() {
+            let (written, offset) = scroll_wheel(&config, &[mode], ModifiersState::default());
+            assert_eq!(written, "\x1b[M`!!".repeat(2));
+            assert_eq!(offset, 0);
+        }
+
+        // Wheel events are reported even on the alternate screen
+        let modes = [ansi::Mode::SwapScreenAndSetRestoreCursor, ansi::Mode::ReportMouseClicks];
+        let (written, _) = scroll_wheel(&config, &modes, ModifiersState::default());
+        assert_eq!(written, "\x1b[M`!!".repeat(2));
+    }
+
+    #[test]
+    fn wheel_scrolls_viewport_with_bypass_modifiers() {
+        let config = Config::default();
+        let mouse_modes = [
+            ansi::Mode::ReportMouseClicks,
+            ansi::Mode::ReportCellMouseMotion,
+            ansi::Mode::ReportAllMouseMotion,
+        ];
+
+        for &mode in mouse_modes.iter() {
+            assert_eq!(scroll_wheel(&config, &[mode], SHIFT), (String::new(), 2));
+        }
+
+        // Without mouse reporting the modifiers don't change anything
+        assert_eq!(scroll_wheel(&config, &[], SHIFT), (String::new(), 2));
+    }
+
+    #[test]
+    fn bypass_modifiers_skip_alternate_scroll() {
+        let config = Config::default();
+        let faux_multiplier = config.scrolling.faux_multiplier() as usize;
+        let alt_screen = [ansi::Mode::SwapScreenAndSetRestoreCursor];
+
+        let (written, _) = scroll_wheel(&config, &alt_screen, ModifiersState::default());
+        assert_eq!(written, "\x1bOA".repeat(2 * faux_multiplier));
+
+        // The alternate screen has no scrollback to move the viewport through
+        assert_eq!(scroll_wheel(&config, &alt_screen, SHIFT), (String::new(), 0));
+    }
+
+    #[test]
+    fn configured_bypass_modifiers() {
+        let config: Config = serde_yaml::from_str("mouse:\n  bypass_modifiers: Control\n").unwrap();
+        let ctrl = ModifiersState { ctrl: true, ..ModifiersState::default() };
+        let mode = [ansi::Mode::ReportMouseClicks];
+
+        assert_eq!(scroll_wheel(&config, &mode, ctrl), (String::new(), 2));
+
+        // Shift is reported to the application as part of the wheel event
+        let (written, offset) = scroll_wheel(&config, &mode, SHIFT);
+        assert_eq!(written, "\x1b[Md!!".repeat(2));
+        assert_eq!(offset, 0);
+
+        // Without bypass modifiers all wheel events go to the application
+        let config: Config = serde_yaml::from_str("mouse:\n  bypass_modifiers: None\n").unwrap();
+        let (written, _) = scroll_wheel(&config, &mode, SHIFT);
+        assert_eq!(written, "\x1b[Md!!".repeat(2));
+    }
+
+    /// Small deltas of a slow touchpad gesture, 15 pixels in total
+    const TOUCHPAD_DELTAS: [f64; 12] =
+        [0.25, 0.5, 0.75, 1.25, 2., 0.5, 1.75, 3.5, 0.25, 1., 2.5, 0.75];
+
+    #[test]
+    fn touchpad_scroll_reports_accumulated_lines() {
+        let written = scroll_pixels(ansi::Mode::ReportMouseClicks, &TOUCHPAD_DELTAS);
+        assert_eq!(written.matches("\x1b[M").count(), 5);
+
+        // Deltas below one pixel are not lost
+        let written = scroll_pixels(ansi::Mode::ReportMouseClicks, &[0.5; 12]);
+        assert_eq!(written.matches("\x1b[M").count(), 2);
+    }
+
+    #[test]
+    fn touchpad_scroll_sends_accumulated_arrow_keys() {
+        let faux_multiplier = Config::default().scrolling.faux_multiplier() as usize;
+        let written = scroll_pixels(ansi::Mode::SwapScreenAndSetRestoreCursor, &TOUCHPAD_DELTAS);
+        assert_eq!(written, "\x1bOA".repeat(5 * faux_multiplier));
+
+        let deltas: Vec<f64> = TOUCHPAD_DELTAS.iter().map(|delta| -delta).collect();
+        let written = scroll_pixels(ansi::Mode::SwapScreenAndSetRestoreCursor, &deltas);
+        assert_eq!(written, "\x1bOB".repeat(5 * faux_multiplier));
+    }
+
+    /// Mouse events in the order they are reported by the window
+    #[derive(Clone, Copy)]
+    enum MouseEvent {
+        Press,
+        Release,
+        /// Move to the cell at column and line
+        Move(usize, usize),
+        Wheel(f32),
+    }
+
+    /// Replay left button and wheel events with mode 1002, after scrolling up into the history
+    ///
+    /// Returns what has been written to the pty, if the selection has been copied and the
+    /// display offset afterwards.
+    fn mouse_in_history(display_offset: usize, events: &[MouseEvent]) -> (String, bool, usize) {
+        let config = Config::default();
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        for _ in 0..40 {
+            terminal.linefeed();
+        }
+        terminal.set_mode(ansi::Mode::ReportCellMouseMotion);
+        terminal.scroll_display(Scroll::Lines(display_offset as isize));
+
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let scrolling: config::Scrolling = serde_yaml::from_str("multiplier: 1").unwrap();
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &[],
+            mouse_bindings: &[],
+            save_to_clipboard: false,
+            alt_send_esc: false,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        let mods = ModifiersState::default();
+        for &event in events {
+            match event {
+                MouseEvent::Press => {
+                    processor.mouse_input(ElementState::Pressed, MouseButton::Left, mods)
+                },
+                MouseEvent::Release => {
+                    processor.mouse_input(ElementState::Released, MouseButton::Left, mods)
+                },
+                MouseEvent::Move(col, line) => {
+                    processor.mouse_moved(col * 3 + 1, line * 3 + 1, mods)
+                },
+                MouseEvent::Wheel(lines) => processor.on_mouse_wheel(
+                    MouseScrollDelta::LineDelta(0., lines),
+                    TouchPhase::Moved,
+                    mods,
+                ),
+            }
+        }
+
+        let written = String::from_utf8(processor.ctx.written).unwrap();
+        let display_offset = processor.ctx.terminal.grid().display_offset();
+        (written, !processor.ctx.copied.is_empty(), display_offset)
+    }
+
+    const CLICK: [MouseEvent; 3] = [MouseEvent::Move(1, 1), MouseEvent::Press, MouseEvent::Release];
+
+    const DRAG: [MouseEvent; 4] =
+        [MouseEvent::Move(1, 1), MouseEvent::Press, MouseEvent::Move(2, 1), MouseEvent::Release];
+
+    #[test]
+    fn click_reported_at_bottom() {
+        assert_eq!(mouse_in_history(0, &CLICK), ("\x1b[M \"\"\x1b[M#\"\"".into(), false, 0));
+    }
+
+    #[test]
+    fn click_selects_in_history() {
+        assert_eq!(mouse_in_history(5, &CLICK), (String::new(), true, 5));
+    }
+
+    #[test]
+    fn drag_reported_at_bottom() {
+        let reports = "\x1b[M \"\"\x1b[M@#\"\x1b[M##\"";
+        assert_eq!(mouse_in_history(0, &DRAG), (reports.into(), false, 0));
+    }
+
+    #[test]
+    fn drag_selects_in_history() {
+        assert_eq!(mouse_in_history(5, &DRAG), (String::new(), true, 5));
+    }
+
+    #[test]
+    fn wheel_scrolls_history_in_mouse_mode() {
+        assert_eq!(
+            mouse_in_history(0, &[MouseEvent::Wheel(2.)]),
+            ("\x1b[M`!!".repeat(2), false, 0)
+        );
+
+        assert_eq!(mouse_in_history(5, &[MouseEvent::Wheel(2.)]), (String::new(), false, 7));
+        assert_eq!(mouse_in_history(5, &[MouseEvent::Wheel(-2.)]), (String::new(), false, 3));
+    }
+
+    #[test]
+    fn reporting_resumes_at_bottom() {
+        let mut events = vec![MouseEvent::Wheel(-2.)];
+        events.extend_from_slice(&CLICK);
+        assert_eq!(mouse_in_history(2, &events), ("\x1b[M \"\"\x1b[M#\"\"".into(), false, 0));
+    }
+
+    const MOUSE_BINDINGS: &str = "mouse_bindings:
+  - { mouse: Right, action: Copy }
+  - { mouse: Right, mods: Control, chars: control }
+  - { mouse: 8, chars: numeric }
+";
+
+    const COMPOSE_BINDINGS: &str = "key_bindings:
+  - { key: E, chars: bound }
+  - { key: Escape, chars: escape }
+";
+
+    /// Keyboard events in the order they are reported by the window
+    #[derive(Clone, Copy)]
+    enum KeyEvent {
+        Press(VirtualKeyCode, ModifiersState),
+        Release(VirtualKeyCode),
+        /// Input method commit, which is reported as a press without a key
+        Commit,
+        Char(char),
+    }
+
+    /// Replay keyboard events, returning what has been written to the pty
+    fn type_keys(events: &[KeyEvent]) -> String {
+        let config: Config = serde_yaml::from_str(COMPOSE_BINDINGS).expect("deserialize config");
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &config.scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &config.key_bindings[..],
+            mouse_bindings: &config.mouse_bindings[..],
+            save_to_clipboard: false,
+            alt_send_esc: true,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        for &event in events {
+            let input = |key, state, modifiers| KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: key,
+                modifiers,
+            };
+
+            match event {
+                KeyEvent::Press(key, mods) => {
+                    processor.process_key(input(Some(key), ElementState::Pressed, mods))
+                },
+                KeyEvent::Release(key) => processor.process_key(input(
+                    Some(key),
+                    ElementState::Released,
+                    ModifiersState::default(),
+                )),
+                KeyEvent::Commit => processor.process_key(input(
+                    None,
+                    ElementState::Pressed,
+                    ModifiersState::default(),
+                )),
+                KeyEvent::Char(c) => processor.received_char(c),
+            }
+        }
+
+        String::from_utf8(processor.ctx.written).unwrap()
+    }
+
+    const NO_MODS: ModifiersState =
+        ModifiersState { shift: false, ctrl: false, alt: false, logo: false };
+    const SHIFT: ModifiersState =
+        ModifiersState { shift: true, ctrl: false, alt: false, logo: false };
+    const ALT: ModifiersState =
+        ModifiersState { shift: false, ctrl: false, alt: true, logo: false };
+    const CTRL: ModifiersState =
+        ModifiersState { shift: false, ctrl: true, alt: false, logo: false };
+
+    #[test]
+    fn compose_binding_without_dead_key() {
+        use self::KeyEvent::*;
+
+        let events = [Press(VirtualKeyCode::E, NO_MODS), Char('e'), Release(VirtualKeyCode::E)];
+        assert_eq!(type_keys(&events), "bound");
+    }
+
+    #[test]
+    fn compose_dead_key_macos() {
+        use self::KeyEvent::*;
+
+        // The dead key is reported, but its character is held back by the input method
+        let events = [
+            Press(VirtualKeyCode::Apostrophe, NO_MODS),
+            Release(VirtualKeyCode::Apostrophe),
+            Press(VirtualKeyCode::E, NO_MODS),
+            Char('\u{e9}'),
+            Release(VirtualKeyCode::E),
+        ];
+        assert_eq!(type_keys(&events), "\u{e9}");
+    }
+
+    #[test]
+    fn compose_dead_key_with_shift() {
+        use self::KeyEvent::*;
+
+        let events = [
+            Press(VirtualKeyCode::Apostrophe, NO_MODS),
+            Release(VirtualKeyCode::Apostrophe),
+            Press(VirtualKeyCode::LShift, SHIFT),
+            Press(VirtualKeyCode::E, SHIFT),
+            Char('\u{c9}'),
+            Release(VirtualKeyCode::E),
+            Release(VirtualKeyCode::LShift),
+        ];
+        assert_eq!(type_keys(&events), "\u{c9}");
+    }
+
+    #[test]
+    fn compose_dead_key_x11() {
+        use self::KeyEvent::*;
+
+        // Presses filtered by the input method are not reported at all
+        let events = [
+            Release(VirtualKeyCode::Apostrophe),
+            Commit,
+            Char('\u{e9}'),
+            Release(VirtualKeyCode::E),
+        ];
+        assert_eq!(type_keys(&events), "\u{e9}");
+    }
+
+    #[test]
+    fn compose_cancelled_macos() {
+        use self::KeyEvent::*;
+
+        let events = [
+            Press(VirtualKeyCode::Apostrophe, NO_MODS),
+            Release(VirtualKeyCode::Apostrophe),
+            Press(VirtualKeyCode::Escape, NO_MODS),
+            Char('\x1b'),
+            Release(VirtualKeyCode::Escape),
+        ];
+        assert_eq!(type_keys(&events), "");
+
+        // Only the composition is cancelled
+        let mut events = events.to_vec();
+        events.extend_from_slice(&[
+            Press(VirtualKeyCode::Escape, NO_MODS),
+            Char('\x1b'),
+            Release(VirtualKeyCode::Escape),
+        ]);
+        assert_eq!(type_keys(&events), "escape");
+    }
+
+    #[test]
+    fn compose_cancelled_x11() {
+        use self::KeyEvent::*;
+
+        let events = [
+            Release(VirtualKeyCode::Apostrophe),
+            Release(VirtualKeyCode::Escape),
+            Press(VirtualKeyCode::Escape, NO_MODS),
+            Char('\x1b'),
+            Release(VirtualKeyCode::Escape),
+        ];
+        assert_eq!(type_keys(&events), "escape");
+    }
+
+    #[test]
+    fn compose_alt_prefixes_composed_char() {
+        use self::KeyEvent::*;
+
+        // Dead circumflex followed by Alt+Space composes a plain circumflex
+        let events = [
+            Press(VirtualKeyCode::Key6, SHIFT),
+            Release(VirtualKeyCode::Key6),
+            Press(VirtualKeyCode::Space, ALT),
+            Char('^'),
+            Release(VirtualKeyCode::Space),
+        ];
+        assert_eq!(type_keys(&events), "\x1b^");
+    }
+
+    #[test]
+    fn compose_not_started_by_control_keys() {
+        use self::KeyEvent::*;
+
+        let events = [
+            Press(VirtualKeyCode::Key1, CTRL),
+            Release(VirtualKeyCode::Key1),
+            Press(VirtualKeyCode::E, NO_MODS),
+            Char('e'),
+            Release(VirtualKeyCode::E),
+        ];
+        assert_eq!(type_keys(&events), "bound");
+    }
+
+    /// Sequences of every keypad key in application keypad mode, with NumLock off and on
+    const KEYPAD_SEQUENCES: [(VirtualKeyCode, Option<&str>, Option<&str>); 18] = [
+        (VirtualKeyCode::Numpad0, Some("\x1bOp"), None),
+        (VirtualKeyCode::Numpad1, Some("\x1bOq"), None),
+        (VirtualKeyCode::Numpad2, Some("\x1bOr"), None),
+        (VirtualKeyCode::Numpad3, Some("\x1bOs"), None),
+        (VirtualKeyCode::Numpad4, Some("\x1bOt"), None),
+        (VirtualKeyCode::Numpad5, Some("\x1bOu"), None),
+        (VirtualKeyCode::Numpad6, Some("\x1bOv"), None),
+        (VirtualKeyCode::Numpad7, Some("\x1bOw"), None),
+        (VirtualKeyCode::Numpad8, Some("\x1bOx"), None),
+        (VirtualKeyCode::Numpad9, Some("\x1bOy"), None),
+        (VirtualKeyCode::Decimal, Some("\x1bOn"), None),
+        (VirtualKeyCode::NumpadComma, Some("\x1bOl"), Some("\x1bOl")),
+        (VirtualKeyCode::Subtract, Some("\x1bOm"), Some("\x1bOm")),
+        (VirtualKeyCode::Add, Some("\x1bOk"), Some("\x1bOk")),
+        (VirtualKeyCode::Multiply, Some("\x1bOj"), Some("\x1bOj")),
+        (VirtualKeyCode::Divide, Some("\x1bOo"), Some("\x1bOo")),
+        (VirtualKeyCode::NumpadEquals, Some("\x1bOX"), Some("\x1bOX")),
+        (VirtualKeyCode::NumpadEnter, Some("\x1bOM"), Some("\x1bOM")),
+    ];
+
+    #[test]
+    fn keypad_sequences() {
+        for &(key, num_lock_off, num_lock_on) in KEYPAD_SEQUENCES.iter() {
+            assert_eq!(keypad_sequence(key, false, false), None, "{:?}", key);
+            assert_eq!(keypad_sequence(key, false, true), None, "{:?}", key);
+            assert_eq!(keypad_sequence(key, true, false), num_lock_off, "{:?}", key);
+            assert_eq!(keypad_sequence(key, true, true), num_lock_on, "{:?}", key);
+        }
+
+        assert_eq!(keypad_sequence(VirtualKeyCode::Key1, true, false), None);
+        assert_eq!(keypad_sequence(VirtualKeyCode::Return, true, false), None);
+    }
+
+    /// Press a keypad key, returning what has been written to the pty
+    fn press_keypad_key(app_keypad: bool, key: VirtualKeyCode, c: Option<char>) -> String {
+        let config = Config::default();
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        if app_keypad {
+            terminal.set_keypad_application_mode();
+        }
+
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &config.scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &config.key_bindings[..],
+            mouse_bindings: &config.mouse_bindings[..],
+            save_to_clipboard: false,
+            alt_send_esc: false,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        let input = |state| KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState::default(),
+        };
+
+        processor.process_key(input(ElementState::Pressed));
+        if let Some(c) = c {
+            processor.received_char(c);
+        }
+        processor.process_key(input(ElementState::Released));
+
+        String::from_utf8(processor.ctx.written).unwrap()
+    }
+
+    #[test]
+    fn keypad_enter() {
+        assert_eq!(press_keypad_key(false, VirtualKeyCode::NumpadEnter, None), "\n");
+        assert_eq!(press_keypad_key(true, VirtualKeyCode::NumpadEnter, None), "\x1bOM");
+    }
+
+    #[test]
+    fn keypad_operator() {
+        assert_eq!(press_keypad_key(false, VirtualKeyCode::Add, Some('+')), "+");
+        assert_eq!(press_keypad_key(true, VirtualKeyCode::Add, Some('+')), "\x1bOk");
+    }
+
+    #[test]
+    fn keypad_digit() {
+        assert_eq!(press_keypad_key(false, VirtualKeyCode::Numpad1, Some('1')), "1");
+
+        let expected = if cfg!(target_os = "macos") { "\x1bOq" } else { "1" };
+        assert_eq!(press_keypad_key(true, VirtualKeyCode::Numpad1, Some('1')), expected);
+    }
+
+    /// Drop files onto a terminal, returning what has been written to the pty
+    fn drop_files(bracketed_paste: bool, drop_files: bool, paths: &[PathBuf]) -> String {
+        let mut config = Config::default();
+        config.mouse.set_drop_files(drop_files);
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        if bracketed_paste {
+            terminal.set_mode(ansi::Mode::BracketedPaste);
+        }
+
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &config.scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &[],
+            mouse_bindings: &[],
+            save_to_clipboard: false,
+            alt_send_esc: false,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        processor.on_dropped_files(paths);
+
+        String::from_utf8(processor.ctx.written).unwrap()
+    }
+
+    #[test]
+    fn quote_path_with_spaces() {
+        assert_eq!(quote_path(Path::new("/tmp/a b/c")), "'/tmp/a b/c' ");
+    }
+
+    #[test]
+    fn quote_path_with_quotes() {
+        assert_eq!(quote_path(Path::new("it's \"here\"")), "'it'\\''s \"here\"' ");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quote_path_non_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/tmp/\xffname"));
+        assert_eq!(quote_path(path), "'/tmp/\u{fffd}name' ");
+    }
+
+    #[test]
+    fn dropped_files_are_space_separated() {
+        let paths = [PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b c")];
+        assert_eq!(drop_files(false, true, &paths), "'/tmp/a' '/tmp/b c' ");
+    }
+
+    #[test]
+    fn dropped_files_in_bracketed_paste() {
+        let paths = [PathBuf::from("/tmp/a")];
+        assert_eq!(drop_files(true, true, &paths), "\x1b[200~'/tmp/a' \x1b[201~");
+    }
+
+    #[test]
+    fn dropped_files_line_breaks() {
+        let paths = [PathBuf::from("/tmp/a\nb")];
+        assert_eq!(drop_files(false, true, &paths), "'/tmp/a\rb' ");
+        assert_eq!(drop_files(true, true, &paths), "\x1b[200~'/tmp/a\nb' \x1b[201~");
+    }
+
+    #[test]
+    fn dropped_files_disabled() {
+        let paths = [PathBuf::from("/tmp/a")];
+        assert_eq!(drop_files(true, false, &paths), "");
+    }
+
+    enum FocusEvent {
+        /// Output of the application, followed by the next event processing
+        Output(&'static [u8]),
+        /// Focus change of the window
+        Window(bool),
+    }
+
+    /// Send events to a terminal, returning what has been written to the pty
+    fn report_focus(is_focused: bool, events: &[FocusEvent]) -> String {
+        let config = Config::default();
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &config.scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &[],
+            mouse_bindings: &[],
+            save_to_clipboard: false,
+            alt_send_esc: false,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        let mut parser = ansi::Processor::new();
+        let mut is_focused = is_focused;
+        for event in events {
+            match event {
+                FocusEvent::Output(bytes) => {
+                    let mut replies = Vec::new();
+                    parser.advance_bytes(&mut *processor.ctx.terminal, bytes, &mut replies);
+                    processor.report_focus_state(is_focused);
+                },
+                FocusEvent::Window(focused) => {
+                    is_focused = *focused;
+                    processor.on_focus_change(is_focused);
+                },
+            }
+        }
+
+        String::from_utf8(processor.ctx.written).unwrap()
+    }
+
+    #[test]
+    fn focus_reported_when_enabled_while_focused() {
+        let events = [FocusEvent::Output(b"\x1b[?1004h")];
+        assert_eq!(report_focus(true, &events), "\x1b[I");
+    }
+
+    #[test]
+    fn focus_reported_when_enabled_while_unfocused() {
+        let events = [FocusEvent::Output(b"\x1b[?1004h"), FocusEvent::Window(true)];
+        assert_eq!(report_focus(false, &events), "\x1b[O\x1b[I");
+    }
+
+    #[test]
+    fn focus_not_reported_when_disabled() {
+        let events = [FocusEvent::Window(false), FocusEvent::Window(true)];
+        assert_eq!(report_focus(true, &events), "");
+
+        // Enabling and disabling in the same output doesn't report anything
+        let events = [FocusEvent::Output(b"\x1b[?1004h\x1b[?1004l"), FocusEvent::Window(false)];
+        assert_eq!(report_focus(true, &events), "");
+    }
+
+    #[test]
+    fn focus_flapping() {
+        let events = [
+            FocusEvent::Output(b"\x1b[?1004h"),
+            FocusEvent::Window(false),
+            FocusEvent::Window(true),
+            FocusEvent::Window(false),
+            FocusEvent::Window(true),
+            FocusEvent::Output(b"\x1b[?1004l"),
+            FocusEvent::Window(false),
+            FocusEvent::Window(true),
+        ];
+        assert_eq!(report_focus(true, &events), "\x1b[I\x1b[O\x1b[I\x1b[O\x1b[I");
+    }
+
+    #[test]
+    fn focus_reports_stop_with_alt_screen() {
+        let events = [
+            FocusEvent::Output(b"\x1b[?1049h\x1b[?1004h"),
+            FocusEvent::Window(false),
+            FocusEvent::Output(b"\x1b[?1004l\x1b[?1049l"),
+            FocusEvent::Window(true),
+        ];
+        assert_eq!(report_focus(true, &events), "\x1b[I\x1b[O");
+    }
+
+    /// Click a mouse button with the mouse bindings
+    ///
+    /// Returns what has been written to the pty, the clipboards which have been copied to and
+    /// whether the click has been handled as the start of a selection.
+    fn click_mouse(
+        button: MouseButton,
+        modifiers: ModifiersState,
+        report_clicks: bool,
+    ) -> (Vec<u8>, Vec<ClipboardType>, bool) {
+        use crate::ansi::{Handler, Mode};
+
+        let config: Config = serde_yaml::from_str(MOUSE_BINDINGS).expect("deserialize config");
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+
+        let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        if report_clicks {
+            terminal.set_mode(Mode::ReportMouseClicks);
+        }
+
+        let mut mouse = Mouse::default();
+        let mut selection = None;
+
+        let context = ActionContext {
+            terminal: &mut terminal,
+            selection: &mut selection,
+            mouse: &mut mouse,
+            size_info: &size,
+            last_action: MultiClick::None,
+            received_count: 0,
+            suppress_chars: false,
+            composing: false,
+            last_modifiers: ModifiersState::default(),
+            option_keys: OptionKeys::default(),
+            pending_keys: None,
+            ui_mode: TermMode::empty(),
+            window_changes: &mut WindowChanges::default(),
+            written: Vec::new(),
+            copied: Vec::new(),
+        };
+
+        let mut processor = Processor {
+            ctx: context,
+            mouse_config: &config.mouse,
+            scrolling_config: &config.scrolling,
+            key_chords_config: &config.key_chords,
+            key_bindings: &config.key_bindings[..],
+            mouse_bindings: &config.mouse_bindings[..],
+            save_to_clipboard: false,
+            alt_send_esc: false,
+            option_as_alt: config::OptionAsAlt::None,
+        };
+
+        processor.mouse_input(ElementState::Pressed, button, modifiers);
+        let selecting = match processor.ctx.mouse.click_state {
+            ClickState::Click => true,
+            _ => false,
+        };
+        processor.mouse_input(ElementState::Released, button, modifiers);
+
+        (processor.ctx.written, processor.ctx.copied, selecting)
+    }
+
+    #[test]
+    fn mouse_binding_replaces_selection() {
+        let (written, copied, selecting) =
+            click_mouse(MouseButton::Right, Default::default(), false);
+        assert_eq!(written, b"");
+        assert_eq!(copied, [ClipboardType::Clipboard]);
+        assert!(!selecting);
+    }
+
+    #[test]
+    fn mouse_binding_with_surplus_mods() {
+        let mods = ModifiersState { ctrl: true, ..Default::default() };
+        let (written, copied, _) = click_mouse(MouseButton::Right, mods, false);
+        assert_eq!(written, b"control");
+        assert_eq!(copied, [ClipboardType::Clipboard]);
+    }
+
+    #[test]
+    fn mouse_binding_numeric_button() {
+        let (written, _, selecting) = click_mouse(MouseButton::Other(8), Default::default(), false);
+        assert_eq!(written, b"numeric");
+        assert!(!selecting);
+    }
+
+    #[test]
+    fn unbound_mouse_button_selects() {
+        let (written, copied, selecting) =
+            click_mouse(MouseButton::Left, Default::default(), false);
+        assert_eq!(written, b"");
+        assert_eq!(copied, [ClipboardType::Selection]);
+        assert!(selecting);
+    }
+
+    #[test]
+    fn mouse_binding_suppressed_in_mouse_mode() {
+        let mods = ModifiersState { ctrl: true, ..Default::default() };
+        let (written, copied, _) = click_mouse(MouseButton::Right, mods, true);
+        assert!(written.starts_with(b"\x1b[M"));
+        assert!(copied.is_empty());
+    }
+
+    #[test]
+    fn mouse_binding_with_shift_in_mouse_mode() {
+        let mods = ModifiersState { shift: true, ..Default::default() };
+        let (written, copied, _) = click_mouse(MouseButton::Right, mods, true);
+        assert_eq!(written, b"");
+        assert_eq!(copied, [ClipboardType::Clipboard]);
+    }
+
+    #[test]
+    fn scroll_remainder_is_carried() {
+        let mut scroll = ScrollAccumulator::default();
+        let now = scroll.last_scroll;
+
+        assert_eq!(scroll.lines(2.5, 3., now), 0);
+        assert_eq!(scroll.lines(4., 3., now), 2);
+        assert_eq!(scroll.lines(2.5, 3., now), 1);
+        assert_eq!(scroll.pixels, 0.);
+
+        assert_eq!(scroll.lines(-7., 3., now), -2);
+        assert_eq!(scroll.pixels, -1.);
+    }
+
+    #[test]
+    fn scroll_reversal_discards_remainder() {
+        let mut scroll = ScrollAccumulator::default();
+        let now = scroll.last_scroll;
+
+        assert_eq!(scroll.lines(2.5, 3., now), 0);
+        assert_eq!(scroll.lines(-2.5, 3., now), 0);
+        assert_eq!(scroll.lines(-1., 3., now), -1);
+    }
+
+    #[test]
+    fn scroll_idle_timeout_discards_remainder() {
+        let mut scroll = ScrollAccumulator::default();
+        let start = scroll.last_scroll;
+
+        assert_eq!(scroll.lines(2.5, 3., start + Duration::from_millis(10)), 0);
+        assert_eq!(scroll.lines(1., 3., start + Duration::from_millis(20)), 1);
+
+        assert_eq!(scroll.lines(2.5, 3., start + Duration::from_millis(30)), 0);
+        let later = start + Duration::from_millis(40) + SCROLL_IDLE_TIMEOUT;
+        assert_eq!(scroll.lines(1., 3., later), 0);
+        assert_eq!(scroll.pixels, 1.);
+    }
+
+    /// Run the click state machine over presses at (milliseconds, line, column)
+    fn click_states(button_changes: &[usize], clicks: &[(u64, usize, usize)]) -> Vec<ClickState> {
+        let config = config::Mouse::default();
+        let start = Instant::now();
+
+        let mut state = ClickState::None;
+        let mut last = None;
+        let mut states = Vec::new();
+        for (i, &(ms, line, col)) in clicks.iter().enumerate() {
+            let button =
+                if button_changes.contains(&i) { MouseButton::Right } else { MouseButton::Left };
+            let point = Some(Point::new(Line(line), Column(col)));
+            let click = Click { button, point, timestamp: start + Duration::from_millis(ms) };
+
+            state = state.next(last, click, &config);
+            last = Some(click);
+            states.push(state);
+        }
+
+        states
+    }
+
+    #[test]
+    fn multi_click_cycles_back_to_click() {
+        let clicks = [(0, 5, 5), (100, 5, 5), (200, 5, 5), (300, 5, 5), (400, 5, 5)];
+        assert_eq!(click_states(&[], &clicks), vec![
+            ClickState::Click,
+            ClickState::DoubleClick,
+            ClickState::TripleClick,
+            ClickState::Click,
+            ClickState::DoubleClick,
+        ]);
+    }
+
+    #[test]
+    fn multi_click_allows_one_cell_of_movement() {
+        let clicks = [(0, 5, 5), (100, 6, 4), (200, 5, 5)];
+        assert_eq!(click_states(&[], &clicks), vec![
+            ClickState::Click,
+            ClickState::DoubleClick,
+            ClickState::TripleClick,
+        ]);
+
+        let clicks = [(0, 5, 5), (100, 5, 7), (200, 7, 7)];
+        assert_eq!(click_states(&[], &clicks), vec![ClickState::Click; 3]);
+    }
+
+    #[test]
+    fn multi_click_threshold() {
+        let clicks = [(0, 5, 5), (299, 5, 5), (600, 5, 5), (900, 5, 5)];
+        assert_eq!(click_states(&[], &clicks), vec![
+            ClickState::Click,
+            ClickState::DoubleClick,
+            ClickState::Click,
+            ClickState::Click,
+        ]);
+    }
+
+    #[test]
+    fn multi_click_with_other_button() {
+        let clicks = [(0, 5, 5), (100, 5, 5), (200, 5, 5)];
+        assert_eq!(click_states(&[1], &clicks), vec![ClickState::Click; 3]);
     }
 }
@@ -13,6 +13,89 @@
 // limitations under the License.
 //
 //! Alacritty - The GPU Enhanced Terminal
+//!
+//! Besides the GPU renderer and the window, this crate contains the terminal emulation which
+//! can be used on its own. The renderer, display, window, event and input modules are only
+//! available with the default `gui` feature, so other frontends can depend on this crate with
+//! `default-features = false`. Without it, glutin and the font rasterizer aren't built and the
+//! config has no font, mouse or key binding options.
+//!
+//! The emulation is driven by feeding pty output into the [`Processor`], which updates the
+//! state of a [`Term`]:
+//!
+//! ```
+//! use std::io;
+//!
+//! use alacritty_terminal::clipboard::Clipboard;
+//! use alacritty_terminal::config::Config;
+//! use alacritty_terminal::message_bar::MessageBuffer;
+//! use alacritty_terminal::{Column, Line, Processor, SizeInfo, Term};
+//!
+//! let size = SizeInfo {
+//!     width: 800.,
+//!     height: 480.,
+//!     cell_width: 10.,
+//!     cell_height: 20.,
+//!     padding_left: 0.,
+//!     padding_right: 0.,
+//!     padding_top: 0.,
+//!     padding_bottom: 0.,
+//!     dpr: 1.,
+//! };
+//! let mut term = Term::new(&Config::default(), size, MessageBuffer::new(), Clipboard::new_nop());
+//!
+//! // Replies to the application, like device status reports, are written to the sink
+//! let mut processor = Processor::new();
+//! processor.advance_bytes(&mut term, b"\x1b[1mhello", &mut io::sink());
+//!
+//! assert_eq!(term.grid()[Line(0)][Column(0)].c, 'h');
+//! assert_eq!(term.cursor().point.col, Column(5));
+//! ```
+//!
+//! In a real terminal the [`EventLoop`] reads from the pty on its own thread and wakes up the
+//! frontend through [`OnWakeup`] whenever there is new content to draw.
+//!
+//! Text which has been wrapped onto several rows is best read with [`Grid::logical_lines`],
+//! which joins the rows again and tells if a line is in the scrollback history or visible:
+//!
+//! ```
+//! # use std::io;
+//! # use alacritty_terminal::clipboard::Clipboard;
+//! # use alacritty_terminal::config::Config;
+//! # use alacritty_terminal::message_bar::MessageBuffer;
+//! # use alacritty_terminal::{Processor, SizeInfo, Term};
+//! # let size = SizeInfo {
+//! #     width: 50.,
+//! #     height: 40.,
+//! #     cell_width: 10.,
+//! #     cell_height: 20.,
+//! #     padding_left: 0.,
+//! #     padding_right: 0.,
+//! #     padding_top: 0.,
+//! #     padding_bottom: 0.,
+//! #     dpr: 1.,
+//! # };
+//! # let clipboard = Clipboard::new_nop();
+//! # let mut term = Term::new(&Config::default(), size, MessageBuffer::new(), clipboard);
+//! // Five columns and two lines, the wrapped line scrolls partially into the history
+//! Processor::new().advance_bytes(&mut term, b"hello world\r\n", &mut io::sink());
+//!
+//! let lines: Vec<String> = term
+//!     .grid()
+//!     .logical_lines()
+//!     .map(|line| line.cells().map(|(_, cell)| cell.c).collect())
+//!     .collect();
+//! assert_eq!(lines, ["hello world    ", "     "]);
+//!
+//! let line = term.grid().logical_lines().next().unwrap();
+//! assert!(line.is_wrapped() && line.in_history() && line.is_visible());
+//! ```
+//!
+//! [`Processor`]: ansi/struct.Processor.html
+//! [`Term`]: term/struct.Term.html
+//! [`EventLoop`]: event_loop/struct.EventLoop.html
+//! [`OnWakeup`]: event_loop/trait.OnWakeup.html
+//! [`Grid::logical_lines`]: grid/struct.Grid.html#method.logical_lines
 #![deny(clippy::all, clippy::if_not_else, clippy::enum_glob_use, clippy::wrong_pub_self_convention)]
 #![cfg_attr(feature = "nightly", feature(core_intrinsics))]
 #![cfg_attr(all(test, feature = "bench"), feature(test))]
@@ -29,31 +112,51 @@ extern crate objc;
 #[macro_use]
 pub mod macros;
 pub mod ansi;
+pub mod benchmark;
 pub mod clipboard;
 pub mod config;
+#[cfg(feature = "gui")]
 mod cursor;
+#[cfg(feature = "gui")]
 pub mod display;
+#[cfg(feature = "gui")]
 pub mod event;
 pub mod event_loop;
 pub mod grid;
 pub mod index;
+#[cfg(feature = "gui")]
 pub mod input;
 pub mod locale;
 pub mod message_bar;
 pub mod meter;
 pub mod panic;
+pub mod recording;
+#[cfg(feature = "gui")]
 pub mod renderer;
+pub mod scheduler;
+#[cfg(feature = "gui")]
+pub mod screenshot;
 pub mod selection;
 pub mod sync;
 pub mod term;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod tty;
 mod url;
 pub mod util;
+#[cfg(feature = "gui")]
 pub mod window;
 
+pub use crate::ansi::{Handler, Processor};
+pub use crate::event_loop::{EventLoop, Msg, Notifier, Notify, OnWakeup};
 pub use crate::grid::Grid;
-pub use crate::term::Term;
+pub use crate::index::{Column, Line, Point, Side};
+pub use crate::selection::Selection;
+pub use crate::term::cell::Cell;
+pub use crate::term::{SizeInfo, Term};
+pub use crate::tty::OnResize;
 
+#[cfg(feature = "gui")]
 pub mod gl {
     #![allow(clippy::all)]
     include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
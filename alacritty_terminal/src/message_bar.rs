@@ -12,9 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::path::{Path, PathBuf};
+
 use crossbeam_channel::{Receiver, Sender};
 
-use crate::term::color::Rgb;
+use crate::term::color::{self, Rgb};
 use crate::term::SizeInfo;
 
 pub const CLOSE_BUTTON_TEXT: &str = "[X]";
@@ -28,12 +30,13 @@ pub struct Message {
     text: String,
     color: Rgb,
     topic: Option<String>,
+    target: Option<PathBuf>,
 }
 
 impl Message {
     /// Create a new message
     pub fn new(text: String, color: Rgb) -> Message {
-        Message { text, color, topic: None }
+        Message { text, color, topic: None, target: None }
     }
 
     /// Formatted message text lines
@@ -98,6 +101,12 @@ impl Message {
         self.color
     }
 
+    /// Errors are shown in red, they are never suppressed
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        self.color == color::RED
+    }
+
     /// Message topic
     #[inline]
     pub fn topic(&self) -> Option<&String> {
@@ -110,6 +119,18 @@ impl Message {
         self.topic = Some(topic);
     }
 
+    /// File which is opened when the message is clicked
+    #[inline]
+    pub fn target(&self) -> Option<&Path> {
+        self.target.as_ref().map(PathBuf::as_path)
+    }
+
+    /// Update the file which is opened when the message is clicked
+    #[inline]
+    pub fn set_target(&mut self, target: PathBuf) {
+        self.target = Some(target);
+    }
+
     /// Right-pad text to fit a specific number of columns
     #[inline]
     fn pad_text(mut text: String, num_cols: usize) -> String {
@@ -125,13 +146,16 @@ pub struct MessageBuffer {
     current: Option<Message>,
     messages: Receiver<Message>,
     tx: Sender<Message>,
+
+    /// Drop all messages except for errors
+    do_not_disturb: bool,
 }
 
 impl MessageBuffer {
     /// Create new message buffer
     pub fn new() -> MessageBuffer {
         let (tx, messages) = crossbeam_channel::unbounded();
-        MessageBuffer { current: None, messages, tx }
+        MessageBuffer { current: None, messages, tx, do_not_disturb: false }
     }
 
     /// Check if there are any messages queued
@@ -146,11 +170,28 @@ impl MessageBuffer {
         if let Some(current) = &self.current {
             Some(current.clone())
         } else {
-            self.current = self.messages.try_recv().ok();
+            self.current = self.next_message();
             self.current.clone()
         }
     }
 
+    /// Whether messages other than errors are suppressed
+    #[inline]
+    pub fn do_not_disturb(&self) -> bool {
+        self.do_not_disturb
+    }
+
+    /// Start or stop suppressing all messages except for errors
+    ///
+    /// Messages which arrive while they are suppressed are dropped, including the current one.
+    pub fn toggle_do_not_disturb(&mut self) {
+        self.do_not_disturb = !self.do_not_disturb;
+
+        if self.do_not_disturb && self.current.as_ref().map_or(false, |m| !m.is_error()) {
+            self.current = self.next_message();
+        }
+    }
+
     /// Channel for adding new messages
     #[inline]
     pub fn tx(&self) -> Sender<Message> {
@@ -171,7 +212,7 @@ impl MessageBuffer {
         }
 
         // Remove the message itself
-        self.current = self.messages.try_recv().ok();
+        self.current = self.next_message();
     }
 
     /// Remove all messages with a specific topic
@@ -188,7 +229,15 @@ impl MessageBuffer {
         }
 
         // Remove the currently active message
-        self.current = self.messages.try_recv().ok();
+        if self.current.as_ref().and_then(Message::topic).map(String::as_str) == Some(topic) {
+            self.current = self.next_message();
+        }
+    }
+
+    /// Next queued message which isn't suppressed
+    fn next_message(&self) -> Option<Message> {
+        let do_not_disturb = self.do_not_disturb;
+        self.messages.try_iter().find(|message| !do_not_disturb || message.is_error())
     }
 }
 
@@ -213,8 +262,10 @@ mod test {
             height: 10.,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -233,8 +284,10 @@ mod test {
             height: 10.,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -253,8 +306,10 @@ mod test {
             height: 10.,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -273,8 +328,10 @@ mod test {
             height: 10.,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -293,8 +350,10 @@ mod test {
             height: 0.,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -313,8 +372,10 @@ mod test {
             height: (MIN_FREE_LINES + 2) as f32,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -336,8 +397,10 @@ mod test {
             height: 10.,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -356,8 +419,10 @@ mod test {
             height: (MIN_FREE_LINES + 2) as f32,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -376,8 +441,10 @@ mod test {
             height: 10.,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -434,8 +501,10 @@ mod test {
             height: 10.,
             cell_width: 1.,
             cell_height: 1.,
-            padding_x: 0.,
-            padding_y: 0.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
             dpr: 0.,
         };
 
@@ -470,4 +539,45 @@ mod test {
 
         assert_eq!(num_messages, 2);
     }
+
+    #[test]
+    fn pop_queued_messages() {
+        let mut message_buffer = MessageBuffer::new();
+        for text in &["one", "two", "three"] {
+            message_buffer.tx().send(Message::new(String::from(*text), color::YELLOW)).unwrap();
+        }
+
+        let mut texts = Vec::new();
+        while let Some(message) = message_buffer.message() {
+            texts.push(message.text);
+            message_buffer.pop();
+        }
+
+        assert_eq!(texts, vec!["one", "two", "three"]);
+        assert!(message_buffer.is_empty());
+    }
+
+    #[test]
+    fn do_not_disturb_shows_errors() {
+        let mut message_buffer = MessageBuffer::new();
+        let warning = Message::new(String::from("warning"), color::YELLOW);
+        let error = Message::new(String::from("error"), color::RED);
+        message_buffer.tx().send(warning.clone()).unwrap();
+        message_buffer.tx().send(warning.clone()).unwrap();
+        message_buffer.tx().send(error.clone()).unwrap();
+        message_buffer.tx().send(warning.clone()).unwrap();
+        assert_eq!(message_buffer.message(), Some(warning.clone()));
+
+        // The visible warning is hidden as well
+        message_buffer.toggle_do_not_disturb();
+        assert!(message_buffer.do_not_disturb());
+        assert_eq!(message_buffer.message(), Some(error));
+
+        message_buffer.pop();
+        assert_eq!(message_buffer.message(), None);
+
+        message_buffer.toggle_do_not_disturb();
+        message_buffer.tx().send(warning.clone()).unwrap();
+        assert_eq!(message_buffer.message(), Some(warning));
+    }
 }
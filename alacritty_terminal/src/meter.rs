@@ -14,7 +14,7 @@
 //
 //! Rendering time meter
 //!
-//! Used to track rendering times and provide moving averages.
+//! Used to track rendering times and provide moving averages and percentiles.
 //!
 //! # Examples
 //!
@@ -30,22 +30,41 @@
 //! // Get the moving average. The meter tracks a fixed number of samples, and
 //! // the average won't mean much until it's filled up at least once.
 //! println!("Average time: {}", meter.average());
+//!
+//! // Get more detailed statistics about the tracked samples.
+//! let stats = meter.statistics();
+//! println!("p99 time: {}, fps: {}", stats.p99, stats.fps);
 
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
-const NUM_SAMPLES: usize = 10;
+const NUM_SAMPLES: usize = 120;
+
+/// Time span over which the throughput is averaged
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(1);
 
 /// The meter
 #[derive(Default)]
 pub struct Meter {
-    /// Track last 60 timestamps
-    times: [f64; NUM_SAMPLES],
+    /// Track the last `NUM_SAMPLES` sample times in microseconds, with the
+    /// time they have been recorded at
+    samples: VecDeque<(Instant, f64)>,
+}
 
-    /// Average sample time in microseconds
-    avg: f64,
+/// Statistics about the samples currently tracked by a meter
+///
+/// All times are in microseconds.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Statistics {
+    pub min: f64,
+    pub max: f64,
+    pub average: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
 
-    /// Index of next time to update.
-    index: usize,
+    /// Estimated number of samples recorded per second
+    pub fps: f64,
 }
 
 /// Sampler
@@ -72,14 +91,14 @@ impl<'a> Sampler<'a> {
 
 impl<'a> Drop for Sampler<'a> {
     fn drop(&mut self) {
-        self.meter.add_sample(self.alive_duration());
+        self.meter.add_sample(self.alive_duration(), Instant::now());
     }
 }
 
 impl Meter {
     /// Create a meter
     pub fn new() -> Meter {
-        Default::default()
+        Meter { samples: VecDeque::with_capacity(NUM_SAMPLES) }
     }
 
     /// Get a sampler
@@ -89,22 +108,347 @@ impl Meter {
 
     /// Get the current average sample duration in microseconds
     pub fn average(&self) -> f64 {
-        self.avg
+        if self.samples.is_empty() {
+            return 0.;
+        }
+
+        self.samples.iter().map(|(_, usec)| usec).sum::<f64>() / self.samples.len() as f64
+    }
+
+    /// Get statistics about all tracked samples
+    pub fn statistics(&self) -> Statistics {
+        if self.samples.is_empty() {
+            return Statistics::default();
+        }
+
+        let mut sorted: Vec<f64> = self.samples.iter().map(|&(_, usec)| usec).collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("sample times are never NaN"));
+
+        Statistics {
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            average: self.average(),
+            p50: percentile(&sorted, 50.),
+            p95: percentile(&sorted, 95.),
+            p99: percentile(&sorted, 99.),
+            fps: self.fps(),
+        }
+    }
+
+    /// Estimate the sampling frequency from the timestamps of the tracked samples
+    fn fps(&self) -> f64 {
+        let (first, last) = match (self.samples.front(), self.samples.back()) {
+            (Some(&(first, _)), Some(&(last, _))) => (first, last),
+            _ => return 0.,
+        };
+
+        let elapsed = last.duration_since(first);
+        let secs = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        if secs > 0. {
+            (self.samples.len() - 1) as f64 / secs
+        } else {
+            0.
+        }
+    }
+
+    /// Add the time from an input to the frame presenting it
+    pub fn add_latency(&mut self, input: Instant, presented: Instant) {
+        self.add_sample(presented.duration_since(input), presented);
     }
 
     /// Add a sample
     ///
     /// Used by Sampler::drop.
-    fn add_sample(&mut self, sample: Duration) {
+    fn add_sample(&mut self, sample: Duration, timestamp: Instant) {
         let mut usec = 0f64;
 
         usec += f64::from(sample.subsec_nanos()) / 1e3;
         usec += (sample.as_secs() as f64) * 1e6;
 
-        let prev = self.times[self.index];
-        self.times[self.index] = usec;
-        self.avg -= prev / NUM_SAMPLES as f64;
-        self.avg += usec / NUM_SAMPLES as f64;
-        self.index = (self.index + 1) % NUM_SAMPLES;
+        if self.samples.len() == NUM_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((timestamp, usec));
+    }
+}
+
+/// Number of bytes transferred per second
+///
+/// Tracks the bytes recorded within the last `THROUGHPUT_WINDOW`.
+#[derive(Debug, Default)]
+pub struct Throughput {
+    /// Number of bytes with the time they have been recorded at
+    samples: VecDeque<(Instant, usize)>,
+}
+
+impl Throughput {
+    /// Create an empty throughput tracker
+    pub fn new() -> Throughput {
+        Throughput::default()
+    }
+
+    /// Record bytes transferred at `timestamp`
+    pub fn record(&mut self, bytes: usize, timestamp: Instant) {
+        // Discard samples which have left the window
+        while let Some(&(recorded, _)) = self.samples.front() {
+            if recorded + THROUGHPUT_WINDOW > timestamp {
+                break;
+            }
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back((timestamp, bytes));
+    }
+
+    /// Average bytes per second over the window ending at `now`
+    pub fn bytes_per_second(&self, now: Instant) -> f64 {
+        let bytes: usize = self
+            .samples
+            .iter()
+            .filter(|&&(recorded, _)| recorded + THROUGHPUT_WINDOW > now)
+            .map(|&(_, bytes)| bytes)
+            .sum();
+
+        let window = THROUGHPUT_WINDOW;
+        bytes as f64 / (window.as_secs() as f64 + f64::from(window.subsec_nanos()) / 1e9)
+    }
+}
+
+/// Number of events per second, like wakeups of the event loop
+///
+/// Counts the events recorded within the last `THROUGHPUT_WINDOW`.
+#[derive(Debug, Default)]
+pub struct Rate {
+    /// Times the events have been recorded at
+    events: VecDeque<Instant>,
+}
+
+impl Rate {
+    /// Create a rate without any events
+    pub fn new() -> Rate {
+        Rate::default()
+    }
+
+    /// Record an event at `timestamp`
+    pub fn record(&mut self, timestamp: Instant) {
+        // Discard events which have left the window
+        while let Some(&recorded) = self.events.front() {
+            if recorded + THROUGHPUT_WINDOW > timestamp {
+                break;
+            }
+            self.events.pop_front();
+        }
+
+        self.events.push_back(timestamp);
+    }
+
+    /// Events per second over the window ending at `now`
+    pub fn per_second(&self, now: Instant) -> f64 {
+        let events =
+            self.events.iter().filter(|&&recorded| recorded + THROUGHPUT_WINDOW > now).count();
+
+        let window = THROUGHPUT_WINDOW;
+        events as f64 / (window.as_secs() as f64 + f64::from(window.subsec_nanos()) / 1e9)
+    }
+}
+
+/// Oldest input which hasn't been presented yet
+///
+/// Inputs like keypresses and pty reads are coalesced until the next frame, which is attributed
+/// to the earliest of them. Only frames drawn after pty output has been parsed present an input,
+/// every other frame discards the pending one so it isn't attributed to a later, unrelated frame.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PendingInput {
+    oldest: Option<Instant>,
+    output: bool,
+}
+
+impl PendingInput {
+    /// Record an input which arrived at `timestamp`
+    pub fn record(&mut self, timestamp: Instant) {
+        self.oldest = Some(self.oldest.map_or(timestamp, |oldest| oldest.min(timestamp)));
+    }
+
+    /// Mark the pending inputs as processed, their effect is visible in the next frame
+    pub fn output(&mut self) {
+        self.output = self.oldest.is_some();
+    }
+
+    /// Take the timestamp of the oldest input presented by the next frame
+    pub fn take(&mut self) -> Option<Instant> {
+        let oldest = if self.output { self.oldest } else { None };
+        *self = PendingInput::default();
+        oldest
+    }
+}
+
+/// Nearest-rank percentile of an ascending, non-empty list of samples
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile / 100. * sorted.len() as f64).ceil() as usize;
+    sorted[rank.max(1).min(sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{percentile, Meter, PendingInput, Rate, Throughput, NUM_SAMPLES};
+
+    #[test]
+    fn percentile_nearest_rank() {
+        let samples: Vec<f64> = (1..=100).map(f64::from).collect();
+
+        assert_eq!(percentile(&samples, 50.), 50.);
+        assert_eq!(percentile(&samples, 95.), 95.);
+        assert_eq!(percentile(&samples, 99.), 99.);
+        assert_eq!(percentile(&samples, 100.), 100.);
+        assert_eq!(percentile(&samples, 0.), 1.);
+    }
+
+    #[test]
+    fn percentile_few_samples() {
+        let samples = [10., 20., 30., 40., 50.];
+
+        assert_eq!(percentile(&samples, 50.), 30.);
+        assert_eq!(percentile(&samples, 95.), 50.);
+        assert_eq!(percentile(&[7.], 99.), 7.);
+    }
+
+    #[test]
+    fn statistics_with_outlier() {
+        let mut meter = Meter::new();
+        let start = Instant::now();
+
+        // 99 fast frames and a single stutter, 10ms apart
+        for i in 0..100 {
+            let usec = if i == 42 { 10_000 } else { 1_000 };
+            let timestamp = start + Duration::from_millis(10 * i);
+            meter.add_sample(Duration::from_micros(usec), timestamp);
+        }
+
+        let stats = meter.statistics();
+        assert_eq!(stats.min, 1_000.);
+        assert_eq!(stats.max, 10_000.);
+        assert_eq!(stats.p50, 1_000.);
+        assert_eq!(stats.p99, 1_000.);
+        assert!((stats.average - 1_090.).abs() < 1e-6);
+        assert!((stats.fps - 100.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn samples_are_bounded() {
+        let mut meter = Meter::new();
+        let start = Instant::now();
+
+        for i in 0..NUM_SAMPLES as u64 * 2 {
+            meter.add_sample(Duration::from_micros(i), start + Duration::from_millis(i));
+        }
+
+        let stats = meter.statistics();
+        assert_eq!(stats.min, NUM_SAMPLES as f64);
+        assert_eq!(stats.max, (NUM_SAMPLES * 2 - 1) as f64);
+    }
+
+    #[test]
+    fn empty_meter() {
+        let meter = Meter::new();
+
+        assert_eq!(meter.average(), 0.);
+        assert_eq!(meter.statistics().fps, 0.);
+    }
+
+    #[test]
+    fn throughput_within_window() {
+        let mut throughput = Throughput::new();
+        let start = Instant::now();
+
+        throughput.record(1_000, start);
+        throughput.record(3_000, start + Duration::from_millis(500));
+
+        let now = start + Duration::from_millis(900);
+        assert!((throughput.bytes_per_second(now) - 4_000.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn throughput_drops_old_samples() {
+        let mut throughput = Throughput::new();
+        let start = Instant::now();
+
+        throughput.record(1_000, start);
+        throughput.record(3_000, start + Duration::from_millis(500));
+
+        // The first sample leaves the window after one second
+        let now = start + Duration::from_millis(1_000);
+        assert!((throughput.bytes_per_second(now) - 3_000.).abs() < 1e-6);
+
+        // Everything is gone once the pty stays silent
+        let now = start + Duration::from_millis(1_500);
+        assert_eq!(throughput.bytes_per_second(now), 0.);
+
+        // Recording prunes samples outside of the window
+        throughput.record(500, start + Duration::from_millis(2_000));
+        assert_eq!(throughput.samples.len(), 1);
+    }
+
+    #[test]
+    fn empty_throughput() {
+        let throughput = Throughput::new();
+
+        assert_eq!(throughput.bytes_per_second(Instant::now()), 0.);
+    }
+
+    #[test]
+    fn rate_counts_recent_events() {
+        let mut rate = Rate::new();
+        let start = Instant::now();
+
+        for i in 0..10 {
+            rate.record(start + Duration::from_millis(200 * i));
+        }
+
+        // Only the events of the last second are counted
+        let now = start + Duration::from_millis(1_900);
+        assert_eq!(rate.per_second(now), 5.);
+        assert_eq!(rate.events.len(), 5);
+
+        // An idle event loop doesn't record anything
+        assert_eq!(rate.per_second(start + Duration::from_secs(60)), 0.);
+    }
+
+    #[test]
+    fn latency_of_coalesced_inputs() {
+        let mut pending = PendingInput::default();
+        let mut meter = Meter::new();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // Keypress and its echo are presented by the same frame, measured from the keypress
+        pending.record(at(0));
+        pending.record(at(2));
+        pending.output();
+        pending.record(at(5));
+        pending.output();
+        meter.add_latency(pending.take().unwrap(), at(16));
+
+        // Frames without new input don't reuse the old timestamp
+        assert_eq!(pending.take(), None);
+
+        let stats = meter.statistics();
+        assert_eq!(stats.max, 16_000.);
+        assert_eq!(meter.samples.len(), 1);
+    }
+
+    #[test]
+    fn latency_without_output() {
+        let mut pending = PendingInput::default();
+        let start = Instant::now();
+
+        // An unrelated frame drawn before the echo discards the keypress
+        pending.record(start);
+        assert_eq!(pending.take(), None);
+
+        pending.record(start + Duration::from_millis(8));
+        pending.output();
+        assert_eq!(pending.take(), Some(start + Duration::from_millis(8)));
     }
 }
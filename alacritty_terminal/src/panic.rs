@@ -12,7 +12,71 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
-//! ANSI Terminal Stream Parsing
+//! Panic handling
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::panic::{self, PanicInfo};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+use backtrace::Backtrace;
+use crossbeam_channel::Sender;
+
+use crate::message_bar::Message;
+use crate::term::color;
+
+/// Write panics with their backtrace to the crash file at `path`
+///
+/// Panics on other threads than the main thread don't stop Alacritty, so they are also
+/// reported in the message bar and `wakeup` is called to display the message. The panic is
+/// passed on to the previously installed hook afterwards.
+pub fn report_crashes<F>(path: PathBuf, message_tx: Sender<Message>, wakeup: F)
+where
+    F: Fn() + Send + 'static,
+{
+    let wakeup = Mutex::new(wakeup);
+    let previous = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let thread = thread::current();
+        let name = thread.name().unwrap_or("<unnamed>");
+
+        let report = crash_report(name, info, &Backtrace::new());
+        if let Err(err) = write_crash_file(&path, &report) {
+            let _ = writeln!(io::stderr(), "Unable to write crash file {:?}: {}", path, err);
+        }
+
+        if name != "main" {
+            let text = format!("alacritty: internal error, see {}", path.display());
+            let mut message = Message::new(text, color::RED);
+            message.set_target(path.clone());
+            let _ = message_tx.send(message);
+
+            if let Ok(wakeup) = wakeup.lock() {
+                wakeup();
+            }
+        }
+
+        previous(info);
+    }));
+}
+
+fn crash_report(thread: &str, info: &PanicInfo<'_>, backtrace: &Backtrace) -> String {
+    format!(
+        "Alacritty {} crashed\n\nthread '{}' {}\n\n{:?}\n",
+        env!("CARGO_PKG_VERSION"),
+        thread,
+        info,
+        backtrace
+    )
+}
+
+/// Append a report to the crash file, previous crashes are kept
+fn write_crash_file(path: &Path, report: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+    file.write_all(report.as_bytes())
+}
 
 // Use the default behavior of the other platforms.
 #[cfg(not(windows))]
@@ -51,3 +115,46 @@ fn win32_string(value: &str) -> Vec<u16> {
     use std::os::windows::ffi::OsStrExt;
     OsStr::new(value).encode_wide().chain(once(0)).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::panic;
+    use std::process;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use crate::message_bar::MessageBuffer;
+    use crate::util::thread;
+
+    use super::report_crashes;
+
+    #[test]
+    fn panic_on_worker_thread() {
+        let path = env::temp_dir().join(format!("alacritty-test-{}-crash.txt", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut message_buffer = MessageBuffer::new();
+        let (wakeup_tx, wakeup_rx) = mpsc::channel();
+        report_crashes(path.clone(), message_buffer.tx(), move || {
+            let _ = wakeup_tx.send(());
+        });
+
+        let watcher = thread::spawn_named("watcher", || panic!("watcher test panic"));
+        assert!(watcher.join().is_err());
+
+        // Restore the default hook for the other tests
+        let _ = panic::take_hook();
+
+        // The main thread is still running and receives the wakeup
+        assert_eq!(wakeup_rx.recv_timeout(Duration::from_secs(5)), Ok(()));
+        let message = message_buffer.message().unwrap();
+        assert_eq!(message.target(), Some(path.as_path()));
+
+        let report = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(report.starts_with("Alacritty "));
+        assert!(report.contains("thread 'watcher' panicked at 'watcher test panic'"));
+    }
+}
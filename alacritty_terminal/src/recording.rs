@@ -0,0 +1,286 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recordings of terminal sessions, used for ref tests
+//!
+//! A recording starts with `HEADER`, followed by one JSON encoded `Event` per line. Recordings
+//! without the header are plain pty output from before resizes were recorded, they are loaded
+//! as a single output event.
+use std::io::{self, Write};
+use std::time::Instant;
+
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::ansi;
+use crate::index::{Column, Line};
+use crate::term::color::{self, Rgb};
+use crate::term::mode::TermMode;
+use crate::term::{SizeInfo, Term};
+
+/// First line of a recording in the event stream format
+pub const HEADER: &[u8] = b"alacritty-recording 1\n";
+
+/// Event of a recording, timestamps are milliseconds since the recording was started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// Bytes read from the pty
+    Output {
+        time: u64,
+        #[serde(with = "base64_bytes")]
+        bytes: Vec<u8>,
+    },
+
+    /// Resize of the grid
+    Resize { time: u64, cols: usize, lines: usize },
+
+    /// Terminal state which isn't part of the grid, written at the end of the recording
+    State(State),
+}
+
+/// Terminal state which can't be verified by comparing grids
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub colors: Vec<Rgb>,
+
+    /// Bits of the modes set by escape sequences
+    pub mode: u32,
+}
+
+impl<'a> From<&'a Term> for State {
+    fn from(terminal: &'a Term) -> State {
+        let colors = terminal.colors();
+        State {
+            colors: (0..color::COUNT).map(|index| colors[index]).collect(),
+            mode: (*terminal.mode() - TermMode::UI).bits(),
+        }
+    }
+}
+
+/// Writer for recordings in the event stream format
+pub struct Recorder<W> {
+    writer: W,
+    start: Instant,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut writer: W) -> io::Result<Recorder<W>> {
+        writer.write_all(HEADER)?;
+        Ok(Recorder { writer, start: Instant::now() })
+    }
+
+    pub fn output(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let time = self.time();
+        self.write(&Event::Output { time, bytes: bytes.to_vec() })
+    }
+
+    pub fn resize(&mut self, cols: Column, lines: Line) -> io::Result<()> {
+        let time = self.time();
+        self.write(&Event::Resize { time, cols: cols.0, lines: lines.0 })
+    }
+
+    pub fn state(&mut self, state: State) -> io::Result<()> {
+        self.write(&Event::State(state))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn time(&self) -> u64 {
+        let elapsed = self.start.elapsed();
+        elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())
+    }
+
+    /// Write a single event, flushed so nothing is lost if Alacritty crashes
+    fn write(&mut self, event: &Event) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, event)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// Load the events of a recording in either format
+pub fn parse(recording: &[u8]) -> io::Result<Vec<Event>> {
+    if !recording.starts_with(HEADER) {
+        return Ok(vec![Event::Output { time: 0, bytes: recording.to_vec() }]);
+    }
+
+    recording[HEADER.len()..]
+        .split(|&byte| byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).map_err(io::Error::from))
+        .collect()
+}
+
+/// All pty output of a recording, without the resizes
+pub fn output(events: &[Event]) -> Vec<u8> {
+    let mut output = Vec::new();
+    for event in events {
+        if let Event::Output { bytes, .. } = event {
+            output.extend_from_slice(bytes);
+        }
+    }
+    output
+}
+
+/// Apply all output and resizes of a recording to a terminal
+///
+/// The cell size and padding of the terminal are kept when resizing.
+pub fn replay(terminal: &mut Term, events: &[Event]) {
+    let mut parser = ansi::Processor::new();
+
+    for event in events {
+        match *event {
+            Event::Output { ref bytes, .. } => {
+                parser.advance_bytes(terminal, bytes, &mut io::sink());
+            },
+            Event::Resize { cols, lines, .. } => {
+                let size = resized(terminal.size_info(), cols, lines);
+                terminal.resize(&size);
+            },
+            Event::State(_) => (),
+        }
+    }
+}
+
+/// Size of the window required for a grid with `cols` and `lines`
+fn resized(size: &SizeInfo, cols: usize, lines: usize) -> SizeInfo {
+    let padding_x = size.padding_left + size.padding_right;
+    let padding_y = size.padding_top + size.padding_bottom;
+    SizeInfo {
+        width: padding_x + cols as f32 * size.cell_width,
+        height: padding_y + lines as f32 * size.cell_height,
+        ..*size
+    }
+}
+
+/// Output is stored as base64, since JSON arrays of bytes are several times larger
+mod base64_bytes {
+    use serde::de::Error;
+
+    use super::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::decode(&encoded).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clipboard::Clipboard;
+    use crate::config::Config;
+    use crate::index::{Column, Line};
+    use crate::message_bar::MessageBuffer;
+    use crate::term::color::Rgb;
+    use crate::term::{SizeInfo, Term};
+
+    use super::{output, parse, replay, Event, Recorder, State, HEADER};
+
+    fn terminal() -> Term {
+        let size = SizeInfo {
+            width: 100.,
+            height: 60.,
+            cell_width: 10.,
+            cell_height: 20.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
+            dpr: 1.,
+        };
+        Term::new(&Config::default(), size, MessageBuffer::new(), Clipboard::new_nop())
+    }
+
+    #[test]
+    fn round_trip() {
+        let state = State { colors: vec![Rgb { r: 1, g: 2, b: 3 }], mode: 0b101 };
+
+        let mut recorder = Recorder::new(Vec::new()).unwrap();
+        recorder.resize(Column(10), Line(3)).unwrap();
+        recorder.output(b"\x1b[31mred\r\n\xff\n").unwrap();
+        recorder.resize(Column(12), Line(4)).unwrap();
+        recorder.state(state.clone()).unwrap();
+        let recording = recorder.into_inner();
+
+        assert!(recording.starts_with(HEADER));
+        let events: Vec<_> = parse(&recording)
+            .unwrap()
+            .into_iter()
+            .map(|event| match event {
+                Event::Output { bytes, .. } => Event::Output { time: 0, bytes },
+                Event::Resize { cols, lines, .. } => Event::Resize { time: 0, cols, lines },
+                state => state,
+            })
+            .collect();
+        let expected = vec![
+            Event::Resize { time: 0, cols: 10, lines: 3 },
+            Event::Output { time: 0, bytes: b"\x1b[31mred\r\n\xff\n".to_vec() },
+            Event::Resize { time: 0, cols: 12, lines: 4 },
+            Event::State(state),
+        ];
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn plain_output_recording() {
+        let recording = b"\x1b[Hls\r\n";
+        let events = parse(recording).unwrap();
+
+        assert_eq!(events, vec![Event::Output { time: 0, bytes: recording.to_vec() }]);
+        assert_eq!(output(&events), recording.to_vec());
+    }
+
+    #[test]
+    fn invalid_event() {
+        let mut recording = HEADER.to_vec();
+        recording.extend_from_slice(b"{\"Resize\":{}}\n");
+
+        assert!(parse(&recording).is_err());
+    }
+
+    #[test]
+    fn replay_resizes() {
+        let mut terminal = terminal();
+        let events = vec![
+            Event::Output { time: 0, bytes: b"abc".to_vec() },
+            Event::Resize { time: 1, cols: 20, lines: 5 },
+            Event::Output { time: 2, bytes: b"\x1b[5;20Hz".to_vec() },
+        ];
+
+        replay(&mut terminal, &events);
+
+        assert_eq!(terminal.grid().num_cols(), Column(20));
+        assert_eq!(terminal.grid().num_lines(), Line(5));
+        assert_eq!(terminal.size_info().width, 200.);
+        assert_eq!(terminal.grid()[Line(0)][Column(2)].c, 'c');
+        assert_eq!(terminal.grid()[Line(4)][Column(19)].c, 'z');
+        assert_eq!(output(&events), b"abc\x1b[5;20Hz".to_vec());
+    }
+
+    #[test]
+    fn state_of_terminal() {
+        let mut terminal = terminal();
+        let state = State::from(&terminal);
+
+        replay(&mut terminal, &[Event::Output { time: 0, bytes: b"\x1b[?2004h".to_vec() }]);
+
+        assert_eq!(State::from(&terminal).colors, state.colors);
+        assert_ne!(State::from(&terminal).mode, state.mode);
+    }
+}
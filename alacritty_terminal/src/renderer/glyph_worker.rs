@@ -0,0 +1,297 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rasterization of glyphs on a background thread
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasherDefault;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use fnv::FnvHasher;
+use font::{self, FontKey, GlyphKey, Rasterize, RasterizedGlyph, Rasterizer};
+
+use crate::config;
+use crate::renderer::GlyphCache;
+
+/// Fonts the worker has to load to rasterize glyphs for the glyph cache
+pub struct FontRequest {
+    /// Font configuration with the current font size applied
+    pub font: config::Font,
+
+    pub dpr: f32,
+
+    /// Keys of the regular, bold and italic font in the glyph cache's rasterizer
+    pub keys: [FontKey; 3],
+}
+
+enum Request {
+    Font(FontRequest, usize),
+    Glyph(GlyphKey, usize),
+}
+
+struct Rasterized {
+    key: GlyphKey,
+    glyph: RasterizedGlyph,
+    generation: usize,
+}
+
+/// Glyphs which have been requested from the worker
+///
+/// Every font change starts a new generation, glyphs of older generations are discarded.
+#[derive(Debug, Default)]
+struct GlyphQueue {
+    pending: HashSet<GlyphKey, BuildHasherDefault<FnvHasher>>,
+    generation: usize,
+}
+
+impl GlyphQueue {
+    /// Mark a glyph as requested, returns `false` if it is already pending
+    fn request(&mut self, key: GlyphKey) -> bool {
+        self.pending.insert(key)
+    }
+
+    /// Remove a rasterized glyph from the queue, returns `false` if it's no longer wanted
+    fn complete(&mut self, key: GlyphKey, generation: usize) -> bool {
+        generation == self.generation && self.pending.remove(&key)
+    }
+
+    /// Drop all pending glyphs and start a new generation
+    fn flush(&mut self) -> usize {
+        self.pending.clear();
+        self.generation += 1;
+        self.generation
+    }
+}
+
+/// Handle to a thread with its own rasterizer, which loads glyphs missing from the glyph cache
+pub struct GlyphWorker {
+    tx: Sender<Request>,
+    rx: Receiver<Rasterized>,
+    queue: GlyphQueue,
+}
+
+impl GlyphWorker {
+    /// Spawn the worker thread, `wakeup` is called whenever new glyphs are ready
+    pub fn new<F>(wakeup: F) -> GlyphWorker
+    where
+        F: Fn() + Send + 'static,
+    {
+        let (request_tx, request_rx) = mpsc::channel();
+        let (glyph_tx, glyph_rx) = mpsc::channel();
+
+        crate::util::thread::spawn_named("glyph rasterizer", move || {
+            run(request_rx, glyph_tx, wakeup)
+        });
+
+        GlyphWorker::with_channels(request_tx, glyph_rx)
+    }
+
+    fn with_channels(tx: Sender<Request>, rx: Receiver<Rasterized>) -> GlyphWorker {
+        GlyphWorker { tx, rx, queue: GlyphQueue::default() }
+    }
+
+    /// Replace the fonts of the worker, this discards all pending glyphs
+    pub fn set_font(&mut self, font: FontRequest) {
+        let generation = self.queue.flush();
+        let _ = self.tx.send(Request::Font(font, generation));
+    }
+
+    /// Rasterize a glyph unless it has been requested already
+    pub fn request(&mut self, key: GlyphKey) {
+        if self.queue.request(key) {
+            let _ = self.tx.send(Request::Glyph(key, self.queue.generation));
+        }
+    }
+
+    /// Glyphs which have been rasterized since the last call
+    pub fn rasterized(&mut self) -> Vec<(GlyphKey, RasterizedGlyph)> {
+        let mut glyphs = Vec::new();
+        while let Ok(rasterized) = self.rx.try_recv() {
+            if self.queue.complete(rasterized.key, rasterized.generation) {
+                glyphs.push((rasterized.key, rasterized.glyph));
+            }
+        }
+        glyphs
+    }
+}
+
+/// Fonts loaded by the worker's rasterizer
+struct WorkerFonts {
+    rasterizer: Rasterizer,
+
+    /// Font keys of the glyph cache mapped to the keys of the worker's rasterizer
+    keys: HashMap<FontKey, FontKey, BuildHasherDefault<FnvHasher>>,
+
+    generation: usize,
+}
+
+impl WorkerFonts {
+    fn new(request: &FontRequest, generation: usize) -> Result<WorkerFonts, font::Error> {
+        let mut rasterizer = Rasterizer::new(request.dpr, request.font.use_thin_strokes())?;
+        let (regular, bold, italic) =
+            GlyphCache::compute_font_keys(&request.font, &mut rasterizer)?;
+
+        let keys = request.keys.iter().cloned().zip(vec![regular, bold, italic]).collect();
+
+        Ok(WorkerFonts { rasterizer, keys, generation })
+    }
+
+    fn rasterize(&mut self, mut key: GlyphKey) -> RasterizedGlyph {
+        match self.keys.get(&key.font_key) {
+            Some(&font_key) => key.font_key = font_key,
+            None => return RasterizedGlyph::default(),
+        }
+
+        self.rasterizer.get_glyph(key).unwrap_or_else(|_| RasterizedGlyph::default())
+    }
+}
+
+fn run<F: Fn()>(rx: Receiver<Request>, tx: Sender<Rasterized>, wakeup: F) {
+    let mut fonts: Option<WorkerFonts> = None;
+
+    // Stop once the glyph cache has been dropped
+    while let Ok(request) = rx.recv() {
+        let mut sent = false;
+
+        // Handle everything that's queued up before waking up the renderer
+        for request in Some(request).into_iter().chain(rx.try_iter()) {
+            match request {
+                Request::Font(request, generation) => {
+                    fonts = match WorkerFonts::new(&request, generation) {
+                        Ok(fonts) => Some(fonts),
+                        Err(err) => {
+                            error!("Unable to load fonts for the glyph rasterizer: {}", err);
+                            None
+                        },
+                    };
+                },
+                Request::Glyph(key, generation) => {
+                    // Skip glyphs of fonts which have been replaced already
+                    let glyph = match &mut fonts {
+                        Some(fonts) => {
+                            if fonts.generation > generation {
+                                continue;
+                            }
+                            fonts.rasterize(key)
+                        },
+                        None => RasterizedGlyph::default(),
+                    };
+
+                    if tx.send(Rasterized { key, glyph, generation }).is_err() {
+                        return;
+                    }
+                    sent = true;
+                },
+            }
+        }
+
+        if sent {
+            wakeup();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use font::{FontKey, GlyphKey, RasterizedGlyph, Size};
+
+    use super::{FontRequest, GlyphQueue, GlyphWorker, Rasterized, Request};
+    use crate::config;
+
+    fn key(c: char) -> GlyphKey {
+        GlyphKey { c, font_key: FontKey::next(), size: Size::new(12.) }
+    }
+
+    fn rasterized(key: GlyphKey, generation: usize) -> Rasterized {
+        Rasterized { key, glyph: RasterizedGlyph::default(), generation }
+    }
+
+    fn font_request() -> FontRequest {
+        let keys = [FontKey::next(), FontKey::next(), FontKey::next()];
+        FontRequest { font: config::Font::default(), dpr: 1., keys }
+    }
+
+    #[test]
+    fn queue_deduplicates_requests() {
+        let mut queue = GlyphQueue::default();
+        let a = key('a');
+
+        assert!(queue.request(a));
+        assert!(!queue.request(a));
+        assert!(queue.request(key('b')));
+
+        assert!(queue.complete(a, queue.generation));
+        assert!(queue.request(a));
+    }
+
+    #[test]
+    fn queue_flush_discards_pending_glyphs() {
+        let mut queue = GlyphQueue::default();
+        let a = key('a');
+        let generation = queue.generation;
+        queue.request(a);
+
+        assert_eq!(queue.flush(), generation + 1);
+        assert!(!queue.complete(a, generation));
+        assert!(queue.pending.is_empty());
+    }
+
+    #[test]
+    fn worker_sends_each_glyph_once() {
+        let (request_tx, request_rx) = mpsc::channel();
+        let (_glyph_tx, glyph_rx) = mpsc::channel();
+        let mut worker = GlyphWorker::with_channels(request_tx, glyph_rx);
+
+        let a = key('a');
+        worker.request(a);
+        worker.request(a);
+        worker.request(key('b'));
+
+        assert_eq!(request_rx.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn worker_drops_glyphs_after_font_change() {
+        let (request_tx, request_rx) = mpsc::channel();
+        let (glyph_tx, glyph_rx) = mpsc::channel();
+        let mut worker = GlyphWorker::with_channels(request_tx, glyph_rx);
+
+        let a = key('a');
+        worker.request(a);
+        let generation = match request_rx.try_recv() {
+            Ok(Request::Glyph(_, generation)) => generation,
+            _ => panic!("expected glyph request"),
+        };
+
+        worker.set_font(font_request());
+        match request_rx.try_recv() {
+            Ok(Request::Font(_, font_generation)) => assert!(font_generation > generation),
+            _ => panic!("expected font request"),
+        }
+
+        // Glyphs rasterized with the old font are discarded
+        glyph_tx.send(rasterized(a, generation)).unwrap();
+        assert!(worker.rasterized().is_empty());
+
+        // The glyph is requested again for the new font
+        worker.request(a);
+        let generation = match request_rx.try_recv() {
+            Ok(Request::Glyph(_, generation)) => generation,
+            _ => panic!("expected glyph request"),
+        };
+        glyph_tx.send(rasterized(a, generation)).unwrap();
+        assert_eq!(worker.rasterized().len(), 1);
+    }
+}
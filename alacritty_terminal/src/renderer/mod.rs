@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::collections::HashMap;
+use std::ffi::CStr;
 use std::fs::File;
 use std::hash::BuildHasherDefault;
 use std::io::{self, Read};
@@ -23,18 +24,19 @@ use std::time::Duration;
 
 use fnv::FnvHasher;
 use font::{self, FontDesc, FontKey, GlyphKey, Rasterize, RasterizedGlyph, Rasterizer};
-use glutin::dpi::PhysicalSize;
 use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
 
-use crate::config::{self, Config, Delta};
-use crate::cursor::{get_cursor_glyph, CursorKey};
+use crate::config::{self, Config, Delta, RendererApi};
+use crate::cursor::get_cursor_glyph;
 use crate::gl;
 use crate::gl::types::*;
-use crate::index::{Column, Line};
+use crate::index::Point;
+use crate::renderer::glyph_worker::{FontRequest, GlyphWorker};
 use crate::renderer::rects::RenderRect;
 use crate::term::color::Rgb;
-use crate::term::{self, cell, RenderableCell, RenderableCellContent};
+use crate::term::{self, cell, CursorKey, RenderableCell, RenderableCellContent};
 
+pub mod glyph_worker;
 pub mod rects;
 
 // Shader paths for live reload
@@ -121,6 +123,11 @@ pub struct TextShaderProgram {
     ///
     /// Rendering is split into two passes; 1 for backgrounds, and one for text
     u_background: GLint,
+
+    /// Gamma correction flag
+    ///
+    /// When set, colors are converted to linear space before blending
+    u_gamma_correct: GLint,
 }
 
 /// Rectangle drawing program
@@ -130,8 +137,6 @@ pub struct TextShaderProgram {
 pub struct RectShaderProgram {
     // Program id
     id: GLuint,
-    /// Rectangle color
-    u_color: GLint,
 }
 
 #[derive(Copy, Debug, Clone)]
@@ -177,6 +182,12 @@ pub struct GlyphCache {
     glyph_offset: Delta<i8>,
 
     metrics: ::font::Metrics,
+
+    /// Rasterizes missing glyphs in the background when set
+    worker: Option<GlyphWorker>,
+
+    /// Empty glyph drawn while a glyph is rasterized in the background
+    placeholder: Glyph,
 }
 
 impl GlyphCache {
@@ -207,6 +218,8 @@ impl GlyphCache {
             italic_key: italic,
             glyph_offset: font.glyph_offset,
             metrics,
+            worker: None,
+            placeholder: loader.load_glyph(&RasterizedGlyph::default()),
         };
 
         cache.load_glyphs_for_font(regular, loader);
@@ -219,10 +232,45 @@ impl GlyphCache {
     fn load_glyphs_for_font<L: LoadGlyph>(&mut self, font: FontKey, loader: &mut L) {
         let size = self.font_size;
         for i in 32u8..=128u8 {
-            self.get(GlyphKey { font_key: font, c: i as char, size }, loader);
+            self.load(GlyphKey { font_key: font, c: i as char, size }, loader);
+        }
+    }
+
+    /// Rasterize glyphs which aren't cached yet on a background thread
+    ///
+    /// Until a glyph has been loaded, an empty placeholder is drawn in its place.
+    pub fn set_worker(&mut self, mut worker: GlyphWorker, font: &config::Font, dpr: f64) {
+        worker.set_font(self.font_request(font, dpr));
+        self.worker = Some(worker);
+    }
+
+    /// Fonts the worker needs to rasterize glyphs for this cache
+    fn font_request(&self, font: &config::Font, dpr: f64) -> FontRequest {
+        FontRequest {
+            font: font.to_owned().with_size(self.font_size),
+            dpr: dpr as f32,
+            keys: [self.font_key, self.bold_key, self.italic_key],
         }
     }
 
+    /// Add all glyphs which have been rasterized in the background since the last call
+    ///
+    /// Returns `true` if any new glyphs were added.
+    pub fn load_rasterized<L: LoadGlyph>(&mut self, loader: &mut L) -> bool {
+        let rasterized = match &mut self.worker {
+            Some(worker) => worker.rasterized(),
+            None => return false,
+        };
+
+        let loaded = !rasterized.is_empty();
+        for (glyph_key, rasterized) in rasterized {
+            let glyph = self.upload(rasterized, loader);
+            self.cache.insert(glyph_key, glyph);
+        }
+
+        loaded
+    }
+
     /// Computes font keys for (Regular, Bold, Italic)
     fn compute_font_keys(
         font: &config::Font,
@@ -278,23 +326,46 @@ impl GlyphCache {
             .expect("metrics load since font is loaded at glyph cache creation")
     }
 
+    /// Get a glyph from the cache
+    ///
+    /// Missing glyphs are requested from the worker if there is one, otherwise they are
+    /// rasterized right away.
     pub fn get<'a, L>(&'a mut self, glyph_key: GlyphKey, loader: &mut L) -> &'a Glyph
     where
         L: LoadGlyph,
     {
-        let glyph_offset = self.glyph_offset;
-        let rasterizer = &mut self.rasterizer;
-        let metrics = &self.metrics;
-        self.cache.entry(glyph_key).or_insert_with(|| {
-            let mut rasterized =
-                rasterizer.get_glyph(glyph_key).unwrap_or_else(|_| Default::default());
-
-            rasterized.left += i32::from(glyph_offset.x);
-            rasterized.top += i32::from(glyph_offset.y);
-            rasterized.top -= metrics.descent as i32;
-
-            loader.load_glyph(&rasterized)
-        })
+        if let Some(worker) = &mut self.worker {
+            if !self.cache.contains_key(&glyph_key) {
+                worker.request(glyph_key);
+                return &self.placeholder;
+            }
+        }
+
+        self.load(glyph_key, loader)
+    }
+
+    /// Get a glyph from the cache, rasterizing it on this thread if necessary
+    fn load<'a, L>(&'a mut self, glyph_key: GlyphKey, loader: &mut L) -> &'a Glyph
+    where
+        L: LoadGlyph,
+    {
+        if !self.cache.contains_key(&glyph_key) {
+            let rasterized =
+                self.rasterizer.get_glyph(glyph_key).unwrap_or_else(|_| Default::default());
+            let glyph = self.upload(rasterized, loader);
+            self.cache.insert(glyph_key, glyph);
+        }
+
+        &self.cache[&glyph_key]
+    }
+
+    /// Apply the glyph offset and copy a rasterized glyph into graphics memory
+    fn upload<L: LoadGlyph>(&self, mut rasterized: RasterizedGlyph, loader: &mut L) -> Glyph {
+        rasterized.left += i32::from(self.glyph_offset.x);
+        rasterized.top += i32::from(self.glyph_offset.y);
+        rasterized.top -= self.metrics.descent as i32;
+
+        loader.load_glyph(&rasterized)
     }
 
     pub fn update_font_size<L: LoadGlyph>(
@@ -308,6 +379,7 @@ impl GlyphCache {
         loader.clear();
         self.cache = HashMap::default();
         self.cursor_cache = HashMap::default();
+        self.placeholder = loader.load_glyph(&RasterizedGlyph::default());
 
         // Update dpi scaling
         self.rasterizer.update_dpr(dpr as f32);
@@ -327,6 +399,12 @@ impl GlyphCache {
         self.italic_key = italic;
         self.metrics = metrics;
 
+        // Pending glyphs of the previous font are discarded by the worker
+        let request = self.font_request(&font, dpr);
+        if let Some(worker) = &mut self.worker {
+            worker.set_font(request);
+        }
+
         self.load_glyphs_for_font(regular, loader);
         self.load_glyphs_for_font(bold, loader);
         self.load_glyphs_for_font(italic, loader);
@@ -379,6 +457,42 @@ struct InstanceData {
     bg_a: f32,
 }
 
+/// Per-instance attributes of a rectangle
+#[derive(Debug, PartialEq)]
+#[repr(C)]
+struct RectInstanceData {
+    // position in normalized device coordinates
+    x: f32,
+    y: f32,
+    // size in normalized device coordinates
+    width: f32,
+    height: f32,
+    // color
+    r: f32,
+    g: f32,
+    b: f32,
+    a: f32,
+}
+
+impl RectInstanceData {
+    fn new(rect: &RenderRect, size: &term::SizeInfo, gamma_correct: bool) -> Self {
+        let center_x = size.width / 2.;
+        let center_y = size.height / 2.;
+        let [r, g, b] = normalize_color(rect.color, gamma_correct);
+
+        RectInstanceData {
+            x: (rect.x - center_x) / center_x,
+            y: -(rect.y - center_y) / center_y,
+            width: rect.width / center_x,
+            height: rect.height / center_y,
+            r,
+            g,
+            b,
+            a: rect.alpha,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct QuadRenderer {
     program: TextShaderProgram,
@@ -393,6 +507,9 @@ pub struct QuadRenderer {
     active_tex: GLuint,
     batch: Batch,
     rx: mpsc::Receiver<Msg>,
+    gamma_correct: bool,
+    api: RendererApi,
+    software: bool,
 }
 
 #[derive(Debug)]
@@ -403,6 +520,7 @@ pub struct RenderApi<'a> {
     current_atlas: &'a mut usize,
     program: &'a mut TextShaderProgram,
     config: &'a Config,
+    gamma_correct: bool,
 }
 
 #[derive(Debug)]
@@ -418,21 +536,33 @@ pub struct PackedVertex {
     y: f32,
 }
 
-#[derive(Debug, Default)]
+/// Cell instances waiting to be drawn, with the runs of consecutive cells sharing an atlas
+///
+/// All glyphs of a run are drawn with a single draw call, instead of flushing whenever the texture
+/// changes. Runs are drawn in the order the cells were added in, so overlapping glyphs like the
+/// cursor are still drawn on top of the cells before them.
+#[derive(Debug)]
 pub struct Batch {
-    tex: GLuint,
     instances: Vec<InstanceData>,
+    runs: Vec<BatchRun>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct BatchRun {
+    tex: GLuint,
+    len: usize,
 }
 
 impl Batch {
     #[inline]
     pub fn new() -> Batch {
-        Batch { tex: 0, instances: Vec::with_capacity(BATCH_MAX) }
+        Batch { instances: Vec::with_capacity(BATCH_MAX), runs: Vec::new() }
     }
 
     pub fn add_item(&mut self, cell: &RenderableCell, glyph: &Glyph) {
-        if self.is_empty() {
-            self.tex = glyph.tex_id;
+        match self.runs.last_mut() {
+            Some(run) if run.tex == glyph.tex_id => run.len += 1,
+            _ => self.runs.push(BatchRun { tex: glyph.tex_id, len: 1 }),
         }
 
         self.instances.push(InstanceData {
@@ -480,14 +610,9 @@ impl Batch {
         self.len() == 0
     }
 
-    #[inline]
-    pub fn size(&self) -> usize {
-        self.len() * size_of::<InstanceData>()
-    }
-
     pub fn clear(&mut self) {
-        self.tex = 0;
         self.instances.clear();
+        self.runs.clear();
     }
 }
 
@@ -495,10 +620,37 @@ impl Batch {
 const BATCH_MAX: usize = 0x1_0000;
 const ATLAS_SIZE: i32 = 1024;
 
+/// Parts of the `GL_RENDERER` string identifying drivers which rasterize on the CPU
+const SOFTWARE_RENDERERS: &[&str] = &[
+    "llvmpipe",
+    "softpipe",
+    "swrast",
+    "software rasterizer",
+    "swiftshader",
+    "microsoft basic render driver",
+    "apple software renderer",
+];
+
 impl QuadRenderer {
-    pub fn new() -> Result<QuadRenderer, Error> {
-        let program = TextShaderProgram::new()?;
-        let rect_program = RectShaderProgram::new()?;
+    pub fn new(config: &Config, api: RendererApi) -> Result<QuadRenderer, Error> {
+        // Linear blending is only possible when the framebuffer can convert back to sRGB. Since
+        // OpenGL ES can't disable that conversion, it must always blend in linear space.
+        let srgb = is_srgb_framebuffer(api);
+        let gamma_correct = match api {
+            RendererApi::Glsl3 => config.font.gamma_correct_blending && srgb,
+            RendererApi::Gles3 => srgb,
+        };
+
+        if config.font.gamma_correct_blending && !srgb {
+            warn!("Unable to get an sRGB framebuffer; disabling gamma correct blending");
+        }
+
+        let name = renderer_name();
+        info!("OpenGL renderer: {}", name);
+        let software = is_software_renderer(&name);
+
+        let program = TextShaderProgram::new(api, gamma_correct)?;
+        let rect_program = RectShaderProgram::new(api)?;
 
         let mut vao: GLuint = 0;
         let mut ebo: GLuint = 0;
@@ -511,8 +663,19 @@ impl QuadRenderer {
 
         unsafe {
             gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
-            gl::Enable(gl::MULTISAMPLE);
+            set_text_blending(api);
+
+            // Multisampling and sRGB toggles are not part of OpenGL ES
+            if api == RendererApi::Glsl3 {
+                gl::Enable(gl::MULTISAMPLE);
+
+                // Let OpenGL encode the linear blending results as sRGB
+                if gamma_correct {
+                    gl::Enable(gl::FRAMEBUFFER_SRGB);
+                } else {
+                    gl::Disable(gl::FRAMEBUFFER_SRGB);
+                }
+            }
 
             // Disable depth mask, as the renderer never uses depth tests
             gl::DepthMask(gl::FALSE);
@@ -545,75 +708,50 @@ impl QuadRenderer {
                 ptr::null(),
                 gl::STREAM_DRAW,
             );
-            // coords
+            set_instance_attributes(0);
+            for index in 0..5 {
+                gl::EnableVertexAttribArray(index);
+                gl::VertexAttribDivisor(index, 1);
+            }
+
+            // Rectangle setup
+            gl::GenVertexArrays(1, &mut rect_vao);
+            gl::GenBuffers(1, &mut rect_vbo);
+            gl::GenBuffers(1, &mut rect_ebo);
+            gl::BindVertexArray(rect_vao);
+            let indices: [i32; 6] = [0, 1, 3, 1, 2, 3];
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, rect_ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (size_of::<i32>() * indices.len()) as _,
+                indices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+
+            // Rectangle instances
+            gl::BindBuffer(gl::ARRAY_BUFFER, rect_vbo);
+            // position and size
             gl::VertexAttribPointer(
                 0,
-                2,
+                4,
                 gl::FLOAT,
                 gl::FALSE,
-                size_of::<InstanceData>() as i32,
+                size_of::<RectInstanceData>() as i32,
                 ptr::null(),
             );
             gl::EnableVertexAttribArray(0);
             gl::VertexAttribDivisor(0, 1);
-            // glyphoffset
+            // color
             gl::VertexAttribPointer(
                 1,
                 4,
                 gl::FLOAT,
                 gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (2 * size_of::<f32>()) as *const _,
+                size_of::<RectInstanceData>() as i32,
+                (4 * size_of::<f32>()) as *const _,
             );
             gl::EnableVertexAttribArray(1);
             gl::VertexAttribDivisor(1, 1);
-            // uv
-            gl::VertexAttribPointer(
-                2,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (6 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribDivisor(2, 1);
-            // color
-            gl::VertexAttribPointer(
-                3,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (10 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(3);
-            gl::VertexAttribDivisor(3, 1);
-            // color
-            gl::VertexAttribPointer(
-                4,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (13 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(4);
-            gl::VertexAttribDivisor(4, 1);
-
-            // Rectangle setup
-            gl::GenVertexArrays(1, &mut rect_vao);
-            gl::GenBuffers(1, &mut rect_vbo);
-            gl::GenBuffers(1, &mut rect_ebo);
-            gl::BindVertexArray(rect_vao);
-            let indices: [i32; 6] = [0, 1, 3, 1, 2, 3];
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, rect_ebo);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (size_of::<i32>() * indices.len()) as _,
-                indices.as_ptr() as *const _,
-                gl::STATIC_DRAW,
-            );
 
             // Cleanup
             gl::BindVertexArray(0);
@@ -665,6 +803,9 @@ impl QuadRenderer {
             active_tex: 0,
             batch: Batch::new(),
             rx: msg_rx,
+            gamma_correct,
+            api,
+            software,
         };
 
         let atlas = Atlas::new(ATLAS_SIZE);
@@ -673,15 +814,25 @@ impl QuadRenderer {
         Ok(renderer)
     }
 
-    // Draw all rectangles simultaneously to prevent excessive program swaps
-    pub fn draw_rects(
-        &mut self,
-        config: &Config,
-        props: &term::SizeInfo,
-        visual_bell_intensity: f64,
-        cell_line_rects: Vec<RenderRect>,
-    ) {
-        // Swap to rectangle rendering program
+    /// Check if the OpenGL driver renders on the CPU
+    #[inline]
+    pub fn is_software(&self) -> bool {
+        self.software
+    }
+
+    /// Draw all rectangles with a single instanced draw call
+    pub fn draw_rects(&mut self, props: &term::SizeInfo, rects: Vec<RenderRect>) {
+        // Skip fully transparent rectangles
+        let instances: Vec<RectInstanceData> = rects
+            .iter()
+            .filter(|rect| rect.alpha != 0.)
+            .map(|rect| RectInstanceData::new(rect, props, self.gamma_correct))
+            .collect();
+
+        if instances.is_empty() {
+            return;
+        }
+
         unsafe {
             // Swap program
             gl::UseProgram(self.rect_program.id);
@@ -692,46 +843,33 @@ impl QuadRenderer {
             // Change blending strategy
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
-            // Setup data and buffers
+            // Upload the instances to a new buffer, leaving the old one to pending draw calls
             gl::BindVertexArray(self.rect_vao);
             gl::BindBuffer(gl::ARRAY_BUFFER, self.rect_vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (instances.len() * size_of::<RectInstanceData>()) as isize,
+                instances.as_ptr() as *const _,
+                gl::STREAM_DRAW,
+            );
 
-            // Position
-            gl::VertexAttribPointer(
-                0,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                (size_of::<f32>() * 2) as _,
+            // Draw all the rectangles
+            gl::DrawElementsInstanced(
+                gl::TRIANGLES,
+                6,
+                gl::UNSIGNED_INT,
                 ptr::null(),
+                instances.len() as GLsizei,
             );
-            gl::EnableVertexAttribArray(0);
-        }
-
-        // Draw visual bell
-        let color = config.visual_bell.color;
-        let rect = RenderRect::new(0., 0., props.width, props.height, color);
-        self.render_rect(&rect, visual_bell_intensity as f32, props);
 
-        // Draw underlines and strikeouts
-        for cell_line_rect in cell_line_rects {
-            self.render_rect(&cell_line_rect, 255., props);
-        }
-
-        // Deactivate rectangle program again
-        unsafe {
             // Reset blending strategy
-            gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
+            set_text_blending(self.api);
 
             // Reset data and buffers
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::BindVertexArray(0);
 
-            let padding_x = props.padding_x as i32;
-            let padding_y = props.padding_y as i32;
-            let width = props.width as i32;
-            let height = props.height as i32;
-            gl::Viewport(padding_x, padding_y, width - 2 * padding_x, height - 2 * padding_y);
+            set_grid_viewport(props);
 
             // Disable program
             gl::UseProgram(0);
@@ -765,6 +903,7 @@ impl QuadRenderer {
             current_atlas: &mut self.current_atlas,
             program: &mut self.program,
             config,
+            gamma_correct: self.gamma_correct,
         });
 
         unsafe {
@@ -778,6 +917,28 @@ impl QuadRenderer {
         res
     }
 
+    /// Read the RGBA content of the framebuffer, starting with the bottom row
+    pub fn read_pixels(&self, props: &term::SizeInfo) -> Vec<u8> {
+        let width = props.width as usize;
+        let height = props.height as usize;
+        let mut pixels = vec![0u8; width * height * 4];
+
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+
+        pixels
+    }
+
     pub fn with_loader<F, T>(&mut self, func: F) -> T
     where
         F: FnOnce(LoaderApi<'_>) -> T,
@@ -795,17 +956,15 @@ impl QuadRenderer {
 
     pub fn reload_shaders(&mut self, props: &term::SizeInfo) {
         info!("Reloading shaders...");
-        let result = (TextShaderProgram::new(), RectShaderProgram::new());
+        let result = (
+            TextShaderProgram::new(self.api, self.gamma_correct),
+            RectShaderProgram::new(self.api),
+        );
         let (program, rect_program) = match result {
             (Ok(program), Ok(rect_program)) => {
                 unsafe {
                     gl::UseProgram(program.id);
-                    program.update_projection(
-                        props.width,
-                        props.height,
-                        props.padding_x,
-                        props.padding_y,
-                    );
+                    program.update_projection(props);
                     gl::UseProgram(0);
                 }
 
@@ -823,100 +982,49 @@ impl QuadRenderer {
         self.rect_program = rect_program;
     }
 
-    pub fn resize(&mut self, size: PhysicalSize, padding_x: f32, padding_y: f32) {
-        let (width, height): (u32, u32) = size.into();
-
+    pub fn resize(&mut self, props: &term::SizeInfo) {
         // viewport
         unsafe {
-            let width = width as i32;
-            let height = height as i32;
-            let padding_x = padding_x as i32;
-            let padding_y = padding_y as i32;
-            gl::Viewport(padding_x, padding_y, width - 2 * padding_x, height - 2 * padding_y);
+            set_grid_viewport(props);
 
             // update projection
             gl::UseProgram(self.program.id);
-            self.program.update_projection(
-                width as f32,
-                height as f32,
-                padding_x as f32,
-                padding_y as f32,
-            );
+            self.program.update_projection(props);
             gl::UseProgram(0);
         }
     }
-
-    // Render a rectangle
-    //
-    // This requires the rectangle program to be activated
-    fn render_rect(&mut self, rect: &RenderRect, alpha: f32, size: &term::SizeInfo) {
-        // Do nothing when alpha is fully transparent
-        if alpha == 0. {
-            return;
-        }
-
-        // Calculate rectangle position
-        let center_x = size.width / 2.;
-        let center_y = size.height / 2.;
-        let x = (rect.x - center_x) / center_x;
-        let y = -(rect.y - center_y) / center_y;
-        let width = rect.width / center_x;
-        let height = rect.height / center_y;
-
-        unsafe {
-            // Setup vertices
-            let vertices: [f32; 8] = [x + width, y, x + width, y - height, x, y - height, x, y];
-
-            // Load vertex data into array buffer
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (size_of::<f32>() * vertices.len()) as _,
-                vertices.as_ptr() as *const _,
-                gl::STATIC_DRAW,
-            );
-
-            // Color
-            self.rect_program.set_color(rect.color, alpha);
-
-            // Draw the rectangle
-            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, ptr::null());
-        }
-    }
 }
 
 impl<'a> RenderApi<'a> {
     pub fn clear(&self, color: Rgb) {
         let alpha = self.config.background_opacity();
+        let [r, g, b] = normalize_color(color, self.gamma_correct);
         unsafe {
-            gl::ClearColor(
-                (f32::from(color.r) / 255.0).min(1.0) * alpha,
-                (f32::from(color.g) / 255.0).min(1.0) * alpha,
-                (f32::from(color.b) / 255.0).min(1.0) * alpha,
-                alpha,
-            );
+            gl::ClearColor(r * alpha, g * alpha, b * alpha, alpha);
             gl::Clear(gl::COLOR_BUFFER_BIT);
         }
     }
 
     fn render_batch(&mut self) {
+        let instance_size = size_of::<InstanceData>();
+
         unsafe {
+            // Orphan the instance buffer, so writing to it doesn't wait for pending draw calls
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (BATCH_MAX * instance_size) as isize,
+                ptr::null(),
+                gl::STREAM_DRAW,
+            );
+
             gl::BufferSubData(
                 gl::ARRAY_BUFFER,
                 0,
-                self.batch.size() as isize,
+                (self.batch.len() * instance_size) as isize,
                 self.batch.instances.as_ptr() as *const _,
             );
-        }
-
-        // Bind texture if necessary
-        if *self.active_tex != self.batch.tex {
-            unsafe {
-                gl::BindTexture(gl::TEXTURE_2D, self.batch.tex);
-            }
-            *self.active_tex = self.batch.tex;
-        }
 
-        unsafe {
+            // The background pass doesn't sample the atlas, so all cells are drawn at once
             self.program.set_background_pass(true);
             gl::DrawElementsInstanced(
                 gl::TRIANGLES,
@@ -925,14 +1033,27 @@ impl<'a> RenderApi<'a> {
                 ptr::null(),
                 self.batch.len() as GLsizei,
             );
+
+            // Draw the glyphs with one call per run of cells sharing an atlas texture
             self.program.set_background_pass(false);
-            gl::DrawElementsInstanced(
-                gl::TRIANGLES,
-                6,
-                gl::UNSIGNED_INT,
-                ptr::null(),
-                self.batch.len() as GLsizei,
-            );
+            let mut first = 0;
+            for run in &self.batch.runs {
+                if *self.active_tex != run.tex {
+                    gl::BindTexture(gl::TEXTURE_2D, run.tex);
+                    *self.active_tex = run.tex;
+                }
+
+                set_instance_attributes(first);
+                gl::DrawElementsInstanced(
+                    gl::TRIANGLES,
+                    6,
+                    gl::UNSIGNED_INT,
+                    ptr::null(),
+                    run.len as GLsizei,
+                );
+                first += run.len;
+            }
+            set_instance_attributes(0);
         }
 
         self.batch.clear();
@@ -943,19 +1064,18 @@ impl<'a> RenderApi<'a> {
     pub fn render_string(
         &mut self,
         string: &str,
-        line: Line,
+        point: Point,
         glyph_cache: &mut GlyphCache,
         color: Option<Rgb>,
     ) {
         let bg_alpha = color.map(|_| 1.0).unwrap_or(0.0);
-        let col = Column(0);
 
         let cells = string
             .chars()
             .enumerate()
             .map(|(i, c)| RenderableCell {
-                line,
-                column: col + i,
+                line: point.line,
+                column: point.col + i,
                 inner: RenderableCellContent::Chars({
                     let mut chars = [' '; cell::MAX_ZEROWIDTH_CHARS + 1];
                     chars[0] = c;
@@ -975,11 +1095,6 @@ impl<'a> RenderApi<'a> {
 
     #[inline]
     fn add_render_item(&mut self, cell: &RenderableCell, glyph: &Glyph) {
-        // Flush batch if tex changing
-        if !self.batch.is_empty() && self.batch.tex != glyph.tex_id {
-            self.render_batch();
-        }
-
         self.batch.add_item(cell, glyph);
 
         // Render batch and clear if it's full
@@ -1130,14 +1245,18 @@ impl<'a> Drop for RenderApi<'a> {
 }
 
 impl TextShaderProgram {
-    pub fn new() -> Result<TextShaderProgram, ShaderCreationError> {
+    pub fn new(
+        api: RendererApi,
+        gamma_correct: bool,
+    ) -> Result<TextShaderProgram, ShaderCreationError> {
         let (vertex_src, fragment_src) = if cfg!(feature = "live-shader-reload") {
             (None, None)
         } else {
             (Some(TEXT_SHADER_V), Some(TEXT_SHADER_F))
         };
-        let vertex_shader = create_shader(TEXT_SHADER_V_PATH, gl::VERTEX_SHADER, vertex_src)?;
-        let fragment_shader = create_shader(TEXT_SHADER_F_PATH, gl::FRAGMENT_SHADER, fragment_src)?;
+        let vertex_shader = create_shader(api, TEXT_SHADER_V_PATH, gl::VERTEX_SHADER, vertex_src)?;
+        let fragment_shader =
+            create_shader(api, TEXT_SHADER_F_PATH, gl::FRAGMENT_SHADER, fragment_src)?;
         let program = create_program(vertex_shader, fragment_shader)?;
 
         unsafe {
@@ -1163,45 +1282,51 @@ impl TextShaderProgram {
         }
 
         // get uniform locations
-        let (projection, cell_dim, background) = unsafe {
+        let (projection, cell_dim, background, gamma) = unsafe {
             (
                 gl::GetUniformLocation(program, cptr!(b"projection\0")),
                 gl::GetUniformLocation(program, cptr!(b"cellDim\0")),
                 gl::GetUniformLocation(program, cptr!(b"backgroundPass\0")),
+                gl::GetUniformLocation(program, cptr!(b"gammaCorrect\0")),
             )
         };
 
-        assert_uniform_valid!(projection, cell_dim, background);
+        assert_uniform_valid!(projection, cell_dim, background, gamma);
 
         let shader = TextShaderProgram {
             id: program,
             u_projection: projection,
             u_cell_dim: cell_dim,
             u_background: background,
+            u_gamma_correct: gamma,
         };
 
         unsafe {
+            gl::Uniform1i(shader.u_gamma_correct, gamma_correct as GLint);
             gl::UseProgram(0);
         }
 
         Ok(shader)
     }
 
-    fn update_projection(&self, width: f32, height: f32, padding_x: f32, padding_y: f32) {
+    fn update_projection(&self, props: &term::SizeInfo) {
+        let grid_width = props.grid_width();
+        let grid_height = props.grid_height();
+
         // Bounds check
-        if (width as u32) < (2 * padding_x as u32) || (height as u32) < (2 * padding_y as u32) {
+        if grid_width < 0. || grid_height < 0. {
             return;
         }
 
         // Compute scale and offset factors, from pixel to ndc space. Y is inverted
-        //   [0, width - 2 * padding_x] to [-1, 1]
-        //   [height - 2 * padding_y, 0] to [-1, 1]
-        let scale_x = 2. / (width - 2. * padding_x);
-        let scale_y = -2. / (height - 2. * padding_y);
+        //   [0, grid_width] to [-1, 1]
+        //   [grid_height, 0] to [-1, 1]
+        let scale_x = 2. / grid_width;
+        let scale_y = -2. / grid_height;
         let offset_x = -1.;
         let offset_y = 1.;
 
-        info!("Width: {}, Height: {}", width, height);
+        info!("Width: {}, Height: {}", props.width, props.height);
 
         unsafe {
             gl::Uniform4f(self.u_projection, offset_x, offset_y, scale_x, scale_y);
@@ -1232,53 +1357,155 @@ impl Drop for TextShaderProgram {
 }
 
 impl RectShaderProgram {
-    pub fn new() -> Result<Self, ShaderCreationError> {
+    pub fn new(api: RendererApi) -> Result<Self, ShaderCreationError> {
         let (vertex_src, fragment_src) = if cfg!(feature = "live-shader-reload") {
             (None, None)
         } else {
             (Some(RECT_SHADER_V), Some(RECT_SHADER_F))
         };
-        let vertex_shader = create_shader(RECT_SHADER_V_PATH, gl::VERTEX_SHADER, vertex_src)?;
-        let fragment_shader = create_shader(RECT_SHADER_F_PATH, gl::FRAGMENT_SHADER, fragment_src)?;
+        let vertex_shader = create_shader(api, RECT_SHADER_V_PATH, gl::VERTEX_SHADER, vertex_src)?;
+        let fragment_shader =
+            create_shader(api, RECT_SHADER_F_PATH, gl::FRAGMENT_SHADER, fragment_src)?;
         let program = create_program(vertex_shader, fragment_shader)?;
 
         unsafe {
             gl::DeleteShader(fragment_shader);
             gl::DeleteShader(vertex_shader);
-            gl::UseProgram(program);
         }
 
-        // get uniform locations
-        let u_color = unsafe { gl::GetUniformLocation(program, b"color\0".as_ptr() as *const _) };
+        Ok(RectShaderProgram { id: program })
+    }
+}
 
-        let shader = RectShaderProgram { id: program, u_color };
+impl Drop for RectShaderProgram {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.id);
+        }
+    }
+}
 
-        unsafe { gl::UseProgram(0) }
+/// Point the text shader's instance attributes at the instance buffer, starting at `first`
+///
+/// This requires the text VAO and instance buffer to be bound.
+unsafe fn set_instance_attributes(first: usize) {
+    let stride = size_of::<InstanceData>();
+    let base = first * stride;
+
+    // (location, components, offset in floats)
+    let attributes = [
+        // coords
+        (0, 2, 0),
+        // glyphoffset
+        (1, 4, 2),
+        // uv
+        (2, 4, 6),
+        // color
+        (3, 3, 10),
+        // background color
+        (4, 4, 13),
+    ];
+
+    for &(index, size, offset) in &attributes {
+        gl::VertexAttribPointer(
+            index,
+            size,
+            gl::FLOAT,
+            gl::FALSE,
+            stride as i32,
+            (base + offset * size_of::<f32>()) as *const _,
+        );
+    }
+}
 
-        Ok(shader)
+/// Restrict drawing to the window area inside the padding
+///
+/// The viewport origin is at the bottom left, so it is offset by the bottom padding.
+fn set_grid_viewport(props: &term::SizeInfo) {
+    unsafe {
+        gl::Viewport(
+            props.padding_left as i32,
+            props.padding_bottom as i32,
+            props.grid_width() as i32,
+            props.grid_height() as i32,
+        );
     }
+}
 
-    fn set_color(&self, color: Rgb, alpha: f32) {
-        unsafe {
-            gl::Uniform4f(
-                self.u_color,
-                f32::from(color.r) / 255.,
-                f32::from(color.g) / 255.,
-                f32::from(color.b) / 255.,
-                alpha,
-            );
+/// Convert a color to normalized floats, optionally decoding sRGB to linear space
+fn normalize_color(color: Rgb, gamma_correct: bool) -> [f32; 3] {
+    let normalize = |c: u8| {
+        let c = f32::from(c) / 255.;
+        if gamma_correct {
+            srgb_to_linear(c)
+        } else {
+            c
         }
+    };
+
+    [normalize(color.r), normalize(color.g), normalize(color.b)]
+}
+
+/// Convert a single normalized sRGB component to linear space
+///
+/// This must be kept in sync with the `srgbToLinear` function in the text shader.
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
     }
 }
 
-impl Drop for RectShaderProgram {
-    fn drop(&mut self) {
-        unsafe {
-            gl::DeleteProgram(self.id);
+/// Name of the OpenGL renderer reported by the driver
+fn renderer_name() -> String {
+    unsafe {
+        let name = gl::GetString(gl::RENDERER);
+        if name.is_null() {
+            return String::new();
+        }
+
+        CStr::from_ptr(name as *const _).to_string_lossy().into_owned()
+    }
+}
+
+/// Check if a `GL_RENDERER` string belongs to a driver which rasterizes on the CPU
+fn is_software_renderer(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SOFTWARE_RENDERERS.iter().any(|software| name.contains(software))
+}
+
+/// Blend text using its subpixel coverage when dual-source blending is available
+fn set_text_blending(api: RendererApi) {
+    unsafe {
+        match api {
+            RendererApi::Glsl3 => gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR),
+            RendererApi::Gles3 => gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
         }
     }
 }
 
+/// Check if the default framebuffer stores its color values with sRGB encoding
+fn is_srgb_framebuffer(api: RendererApi) -> bool {
+    // OpenGL ES has no stereo buffers, so the back buffer can't be selected as `BACK_LEFT`
+    let attachment = match api {
+        RendererApi::Glsl3 => gl::BACK_LEFT,
+        RendererApi::Gles3 => gl::BACK,
+    };
+
+    let mut encoding: GLint = 0;
+    unsafe {
+        gl::GetFramebufferAttachmentParameteriv(
+            gl::FRAMEBUFFER,
+            attachment,
+            gl::FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING,
+            &mut encoding,
+        );
+    }
+
+    encoding as GLenum == gl::SRGB
+}
+
 fn create_program(vertex: GLuint, fragment: GLuint) -> Result<GLuint, ShaderCreationError> {
     unsafe {
         let program = gl::CreateProgram();
@@ -1297,7 +1524,19 @@ fn create_program(vertex: GLuint, fragment: GLuint) -> Result<GLuint, ShaderCrea
     }
 }
 
+/// Preamble for the shader sources, which don't declare a GLSL version themselves
+fn shader_header(api: RendererApi) -> &'static str {
+    match api {
+        RendererApi::Glsl3 => "#version 330 core\n",
+        // Uniforms shared between stages need matching precision in both of them
+        RendererApi::Gles3 => {
+            "#version 300 es\nprecision highp float;\nprecision highp int;\n#define GLES\n"
+        },
+    }
+}
+
 fn create_shader(
+    api: RendererApi,
     path: &str,
     kind: GLenum,
     source: Option<&'static str>,
@@ -1310,11 +1549,13 @@ fn create_shader(
         &from_disk[..]
     };
 
-    let len: [GLint; 1] = [source.len() as GLint];
+    let header = shader_header(api);
+    let sources = [header.as_ptr() as *const GLchar, source.as_ptr() as *const GLchar];
+    let lens: [GLint; 2] = [header.len() as GLint, source.len() as GLint];
 
     let shader = unsafe {
         let shader = gl::CreateShader(kind);
-        gl::ShaderSource(shader, 1, &(source.as_ptr() as *const _), len.as_ptr());
+        gl::ShaderSource(shader, 2, sources.as_ptr(), lens.as_ptr());
         gl::CompileShader(shader);
         shader
     };
@@ -1632,3 +1873,195 @@ impl Atlas {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{is_software_renderer, normalize_color, shader_header, srgb_to_linear};
+    use super::{Batch, BatchRun, Glyph, RectInstanceData, BATCH_MAX};
+    use super::{RECT_SHADER_F, RECT_SHADER_V, TEXT_SHADER_F, TEXT_SHADER_V};
+    use crate::config::RendererApi;
+    use crate::index::{Column, Line};
+    use crate::renderer::rects::RenderRect;
+    use crate::term::cell::{self, Flags};
+    use crate::term::color::Rgb;
+    use crate::term::{RenderableCell, RenderableCellContent, SizeInfo};
+
+    fn cell(column: usize) -> RenderableCell {
+        RenderableCell {
+            line: Line(0),
+            column: Column(column),
+            inner: RenderableCellContent::Chars([' '; cell::MAX_ZEROWIDTH_CHARS + 1]),
+            fg: Rgb::default(),
+            bg: Rgb::default(),
+            bg_alpha: 0.,
+            flags: Flags::empty(),
+        }
+    }
+
+    fn glyph(tex_id: u32) -> Glyph {
+        Glyph {
+            tex_id,
+            top: 0.,
+            left: 0.,
+            width: 0.,
+            height: 0.,
+            uv_bot: 0.,
+            uv_left: 0.,
+            uv_width: 0.,
+            uv_height: 0.,
+        }
+    }
+
+    fn size_info() -> SizeInfo {
+        SizeInfo {
+            width: 200.,
+            height: 100.,
+            cell_width: 10.,
+            cell_height: 20.,
+            padding_left: 0.,
+            padding_right: 0.,
+            padding_top: 0.,
+            padding_bottom: 0.,
+            dpr: 1.,
+        }
+    }
+
+    #[test]
+    fn srgb_to_linear_endpoints() {
+        assert_eq!(srgb_to_linear(0.), 0.);
+        assert!((srgb_to_linear(1.) - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn srgb_to_linear_midtones() {
+        // sRGB 50% gray is roughly 21.4% linear intensity
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+
+        // Values below the threshold use the linear segment
+        assert!((srgb_to_linear(0.04) - 0.04 / 12.92).abs() < 1e-6);
+    }
+
+    #[test]
+    fn normalize_color_without_gamma() {
+        let color = Rgb { r: 0xff, g: 0x80, b: 0 };
+        let [r, g, b] = normalize_color(color, false);
+        assert_eq!(r, 1.);
+        assert!((g - 128. / 255.).abs() < 1e-6);
+        assert_eq!(b, 0.);
+    }
+
+    #[test]
+    fn normalize_color_with_gamma() {
+        let color = Rgb { r: 0xff, g: 0x80, b: 0 };
+        let [r, g, b] = normalize_color(color, true);
+        assert!((r - 1.).abs() < 1e-6);
+        assert!(g < 128. / 255.);
+        assert_eq!(b, 0.);
+    }
+
+    #[test]
+    fn shader_headers_declare_version_first() {
+        assert!(shader_header(RendererApi::Glsl3).starts_with("#version 330 core\n"));
+        assert!(shader_header(RendererApi::Gles3).starts_with("#version 300 es\n"));
+    }
+
+    #[test]
+    fn shader_sources_leave_version_to_header() {
+        for source in &[TEXT_SHADER_F, TEXT_SHADER_V, RECT_SHADER_F, RECT_SHADER_V] {
+            assert!(source.lines().all(|line| !line.starts_with("#version")));
+        }
+    }
+
+    #[test]
+    fn gles_text_shader_avoids_dual_source_blending() {
+        assert!(shader_header(RendererApi::Gles3).contains("#define GLES\n"));
+        assert!(!shader_header(RendererApi::Glsl3).contains("GLES"));
+        assert!(TEXT_SHADER_F.contains("#if defined(GLES)\nout vec4 color;\n#else"));
+    }
+
+    #[test]
+    fn software_renderers() {
+        assert!(is_software_renderer("llvmpipe (LLVM 8.0, 256 bits)"));
+        assert!(is_software_renderer("Software Rasterizer"));
+        assert!(is_software_renderer("Mesa DRI swrast"));
+        assert!(is_software_renderer("Google SwiftShader"));
+        assert!(is_software_renderer("Microsoft Basic Render Driver"));
+    }
+
+    #[test]
+    fn hardware_renderers() {
+        assert!(!is_software_renderer("Mesa DRI Intel(R) HD Graphics 620 (Kaby Lake GT2)"));
+        assert!(!is_software_renderer("GeForce GTX 1080/PCIe/SSE2"));
+        assert!(!is_software_renderer("AMD Radeon Pro 560 OpenGL Engine"));
+        assert!(!is_software_renderer(""));
+    }
+
+    #[test]
+    fn batch_keeps_draw_order() {
+        let mut batch = Batch::new();
+        batch.add_item(&cell(0), &glyph(2));
+        batch.add_item(&cell(1), &glyph(1));
+        batch.add_item(&cell(2), &glyph(2));
+        batch.add_item(&cell(3), &glyph(2));
+
+        // Cells are drawn in the order they were added, overlapping glyphs stay on top
+        let columns: Vec<f32> = batch.instances.iter().map(|instance| instance.col).collect();
+        assert_eq!(columns, vec![0., 1., 2., 3.]);
+        assert_eq!(batch.runs, vec![
+            BatchRun { tex: 2, len: 1 },
+            BatchRun { tex: 1, len: 1 },
+            BatchRun { tex: 2, len: 2 },
+        ]);
+        assert_eq!(batch.len(), 4);
+    }
+
+    #[test]
+    fn cleared_batch_starts_new_run() {
+        let mut batch = Batch::new();
+        batch.add_item(&cell(0), &glyph(1));
+        batch.add_item(&cell(1), &glyph(2));
+        batch.clear();
+
+        assert!(batch.is_empty());
+        assert!(batch.runs.is_empty());
+
+        batch.add_item(&cell(0), &glyph(2));
+        assert_eq!(batch.runs, vec![BatchRun { tex: 2, len: 1 }]);
+    }
+
+    #[test]
+    fn batch_is_full_across_runs() {
+        let mut batch = Batch::new();
+        for i in 0..BATCH_MAX {
+            batch.add_item(&cell(0), &glyph(i as u32 % 2));
+        }
+
+        assert!(batch.full());
+    }
+
+    #[test]
+    fn rect_instance_covering_window() {
+        let color = Rgb { r: 0xff, g: 0, b: 0 };
+        let rect = RenderRect::new(0., 0., 200., 100., color, 0.5);
+
+        assert_eq!(RectInstanceData::new(&rect, &size_info(), false), RectInstanceData {
+            x: -1.,
+            y: 1.,
+            width: 2.,
+            height: 2.,
+            r: 1.,
+            g: 0.,
+            b: 0.,
+            a: 0.5,
+        });
+    }
+
+    #[test]
+    fn rect_instance_bottom_right_quarter() {
+        let rect = RenderRect::new(100., 50., 100., 50., Rgb::default(), 1.);
+        let instance = RectInstanceData::new(&rect, &size_info(), false);
+
+        assert_eq!((instance.x, instance.y), (0., 0.));
+        assert_eq!((instance.width, instance.height), (1., 1.));
+    }
+}
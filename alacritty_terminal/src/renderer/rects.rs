@@ -11,14 +11,19 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::collections::HashMap;
-
 use font::Metrics;
 
+use crate::config::{Rulers, VisualBellRegion};
 use crate::index::Point;
 use crate::term::cell::Flags;
 use crate::term::color::Rgb;
-use crate::term::{RenderableCell, SizeInfo};
+use crate::term::{RenderableCell, RenderableCellContent, SizeInfo};
+
+/// Height of the visual bell bars in logical pixels
+const VISUAL_BELL_BAR_HEIGHT: f32 = 4.;
+
+/// Edge length of the visual bell corner squares in logical pixels
+const VISUAL_BELL_CORNER_SIZE: f32 = 16.;
 
 #[derive(Debug, Copy, Clone)]
 pub struct RenderRect {
@@ -27,11 +32,94 @@ pub struct RenderRect {
     pub width: f32,
     pub height: f32,
     pub color: Rgb,
+    pub alpha: f32,
 }
 
 impl RenderRect {
-    pub fn new(x: f32, y: f32, width: f32, height: f32, color: Rgb) -> Self {
-        RenderRect { x, y, width, height, color }
+    pub fn new(x: f32, y: f32, width: f32, height: f32, color: Rgb, alpha: f32) -> Self {
+        RenderRect { x, y, width, height, color, alpha }
+    }
+
+    /// Vertical lines after the configured ruler columns
+    ///
+    /// Rulers which would be drawn outside of the grid are skipped.
+    pub fn rulers(rulers: &Rulers, size: &SizeInfo) -> Vec<RenderRect> {
+        let num_cols = size.cols().0;
+        let height = size.lines().0 as f32 * size.cell_height;
+        let thickness = f32::from(rulers.thickness());
+
+        rulers
+            .columns
+            .iter()
+            .filter(|&&column| column > 0 && column < num_cols)
+            .map(|&column| {
+                // Align to the pixel grid to keep the lines sharp
+                let x = (size.padding_left + column as f32 * size.cell_width).floor();
+                let y = size.padding_top;
+                RenderRect::new(x, y, thickness, height, rulers.color, rulers.opacity())
+            })
+            .collect()
+    }
+
+    /// Window padding around the grid, including space left over by partial cells
+    pub fn padding(size: &SizeInfo, color: Rgb) -> Vec<RenderRect> {
+        let grid_right = size.padding_left + size.cols().0 as f32 * size.cell_width;
+        let grid_bottom = size.padding_top + size.lines().0 as f32 * size.cell_height;
+        let grid_height = grid_bottom - size.padding_top;
+        let rect = |x: f32, y: f32, width: f32, height: f32| {
+            RenderRect::new(x, y, width, height, color, 1.)
+        };
+
+        vec![
+            rect(0., 0., size.width, size.padding_top),
+            rect(0., grid_bottom, size.width, size.height - grid_bottom),
+            rect(0., size.padding_top, size.padding_left, grid_height),
+            rect(grid_right, size.padding_top, size.width - grid_right, grid_height),
+        ]
+        .into_iter()
+        .filter(|rect| rect.width > 0. && rect.height > 0.)
+        .collect()
+    }
+
+    /// Flash of the visual bell covering the configured region
+    ///
+    /// Except for the `Full` region, which covers the entire window, the
+    /// flash is drawn inside the padding.
+    pub fn visual_bell(
+        region: VisualBellRegion,
+        size: &SizeInfo,
+        color: Rgb,
+        alpha: f32,
+    ) -> Vec<RenderRect> {
+        let scale = size.dpr as f32;
+        let left = size.padding_left;
+        let top = size.padding_top;
+        let right = size.width - size.padding_right;
+        let bottom = size.height - size.padding_bottom;
+        let rect = |x: f32, y: f32, width: f32, height: f32| {
+            RenderRect::new(x, y, width, height, color, alpha)
+        };
+
+        match region {
+            VisualBellRegion::Full => vec![rect(0., 0., size.width, size.height)],
+            VisualBellRegion::TopBar => {
+                let height = (VISUAL_BELL_BAR_HEIGHT * scale).floor();
+                vec![rect(left, top, right - left, height)]
+            },
+            VisualBellRegion::BottomBar => {
+                let height = (VISUAL_BELL_BAR_HEIGHT * scale).floor();
+                vec![rect(left, bottom - height, right - left, height)]
+            },
+            VisualBellRegion::Corners => {
+                let side = (VISUAL_BELL_CORNER_SIZE * scale).floor();
+                vec![
+                    rect(left, top, side, side),
+                    rect(right - side, top, side, side),
+                    rect(left, bottom - side, side, side),
+                    rect(right - side, bottom - side, side, side),
+                ]
+            },
+        }
     }
 }
 
@@ -65,14 +153,19 @@ impl RenderLine {
             y = max_y;
         }
 
-        RenderRect::new(start_x + size.padding_x, y + size.padding_y, width, height, self.color)
+        let x = start_x + size.padding_left;
+        RenderRect::new(x, y + size.padding_top, width, height, self.color, 1.)
     }
 }
 
 /// Lines for underline and strikeout.
+///
+/// Neighboring cells are merged into a single line as long as their line color is the same,
+/// underline and strikeout are tracked independently.
 #[derive(Default)]
 pub struct RenderLines {
-    inner: HashMap<Flags, Vec<RenderLine>>,
+    underlines: Vec<RenderLine>,
+    strikeouts: Vec<RenderLine>,
 }
 
 impl RenderLines {
@@ -81,42 +174,343 @@ impl RenderLines {
     }
 
     pub fn into_rects(self, metrics: &Metrics, size: &SizeInfo) -> Vec<RenderRect> {
-        self.inner
-            .into_iter()
-            .map(|(flag, lines)| -> Vec<RenderRect> {
-                lines.into_iter().map(|line| line.into_rect(flag, &metrics, &size)).collect()
-            })
-            .flatten()
-            .collect()
+        let underlines =
+            self.underlines.into_iter().map(|line| line.into_rect(Flags::UNDERLINE, metrics, size));
+        let strikeouts =
+            self.strikeouts.into_iter().map(|line| line.into_rect(Flags::STRIKEOUT, metrics, size));
+        underlines.chain(strikeouts).collect()
     }
 
     /// Update the stored lines with the next cell info.
     pub fn update(&mut self, cell: &RenderableCell) {
-        for flag in &[Flags::UNDERLINE, Flags::STRIKEOUT] {
-            if !cell.flags.contains(*flag) {
-                continue;
-            }
+        // The cursor is drawn on top of its cell, which follows with its own colors
+        if let RenderableCellContent::Cursor(_) = cell.inner {
+            return;
+        }
 
-            // Check if there's an active line
-            if let Some(line) = self.inner.get_mut(flag).and_then(|lines| lines.last_mut()) {
-                if cell.line == line.start.line
-                    && cell.fg == line.color
-                    && cell.column == line.end.col + 1
-                {
-                    // Update the length of the line
-                    line.end = cell.into();
-                    continue;
-                }
-            }
+        if cell.flags.contains(Flags::UNDERLINE) {
+            Self::extend(&mut self.underlines, cell);
+        }
 
-            // Start new line if there currently is none
-            let line = RenderLine { start: cell.into(), end: cell.into(), color: cell.fg };
-            match self.inner.get_mut(flag) {
-                Some(lines) => lines.push(line),
-                None => {
-                    self.inner.insert(*flag, vec![line]);
-                },
+        if cell.flags.contains(Flags::STRIKEOUT) {
+            Self::extend(&mut self.strikeouts, cell);
+        }
+    }
+
+    /// Continue the last line with `cell`, starting a new one at gaps and color changes
+    fn extend(lines: &mut Vec<RenderLine>, cell: &RenderableCell) {
+        let point: Point = cell.into();
+
+        if let Some(line) = lines.last_mut() {
+            let adjacent = point.line == line.end.line && point.col == line.end.col + 1;
+            if adjacent && cell.fg == line.color {
+                line.end = point;
+                return;
             }
         }
+
+        lines.push(RenderLine { start: point, end: point, color: cell.fg });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use font::Metrics;
+
+    use crate::ansi::CursorStyle;
+    use crate::config::{Rulers, VisualBellRegion};
+    use crate::index::{Column, Line};
+    use crate::renderer::rects::{RenderLines, RenderRect};
+    use crate::term::cell::{Flags, MAX_ZEROWIDTH_CHARS};
+    use crate::term::color::Rgb;
+    use crate::term::{CursorKey, RenderableCell, RenderableCellContent, SizeInfo};
+
+    fn size_info(cell_width: f32, dpr: f64) -> SizeInfo {
+        let scale = dpr as f32;
+        SizeInfo {
+            width: 2. * 5. * scale + 100. * cell_width * scale,
+            height: 2. * 5. * scale + 30. * 16. * scale,
+            cell_width: cell_width * scale,
+            cell_height: 16. * scale,
+            padding_left: 5. * scale,
+            padding_right: 5. * scale,
+            padding_top: 5. * scale,
+            padding_bottom: 5. * scale,
+            dpr,
+        }
+    }
+
+    fn rulers(columns: Vec<usize>) -> Rulers {
+        let mut rulers = Rulers::default();
+        rulers.columns = columns;
+        rulers
+    }
+
+    fn positions(rulers: &Rulers, size: &SizeInfo) -> Vec<f32> {
+        RenderRect::rulers(rulers, size).iter().map(|rect| rect.x).collect()
+    }
+
+    #[test]
+    fn rulers_follow_cell_width() {
+        let rulers = rulers(vec![80, 90]);
+
+        assert_eq!(positions(&rulers, &size_info(7., 1.)), vec![565., 635.]);
+        assert_eq!(positions(&rulers, &size_info(9., 1.)), vec![725., 815.]);
+    }
+
+    #[test]
+    fn rulers_scale_with_dpr() {
+        let rulers = rulers(vec![80]);
+
+        assert_eq!(positions(&rulers, &size_info(7., 2.)), vec![1130.]);
+        assert_eq!(positions(&rulers, &size_info(7.5, 1.5)), vec![907.]);
+    }
+
+    #[test]
+    fn rulers_cover_grid_height() {
+        let rulers = rulers(vec![80]);
+        let size = size_info(7., 2.);
+
+        let rect = RenderRect::rulers(&rulers, &size)[0];
+        assert_eq!(rect.y, 10.);
+        assert_eq!(rect.height, 30. * 32.);
+        assert_eq!(rect.width, 1.);
+        assert_eq!(rect.alpha, rulers.opacity());
+    }
+
+    #[test]
+    fn rulers_outside_grid_are_skipped() {
+        let rulers = rulers(vec![0, 100, 120]);
+
+        assert!(RenderRect::rulers(&rulers, &size_info(7., 1.)).is_empty());
+    }
+
+    fn visual_bell(region: VisualBellRegion, size: &SizeInfo) -> Vec<(f32, f32, f32, f32)> {
+        RenderRect::visual_bell(region, size, Rgb::default(), 0.5)
+            .iter()
+            .map(|rect| (rect.x, rect.y, rect.width, rect.height))
+            .collect()
+    }
+
+    #[test]
+    fn visual_bell_full() {
+        let region = VisualBellRegion::Full;
+
+        assert_eq!(visual_bell(region, &size_info(7., 1.)), vec![(0., 0., 710., 490.)]);
+        assert_eq!(visual_bell(region, &size_info(7., 2.)), vec![(0., 0., 1420., 980.)]);
+    }
+
+    #[test]
+    fn visual_bell_top_bar() {
+        let region = VisualBellRegion::TopBar;
+
+        assert_eq!(visual_bell(region, &size_info(7., 1.)), vec![(5., 5., 700., 4.)]);
+        assert_eq!(visual_bell(region, &size_info(7., 2.)), vec![(10., 10., 1400., 8.)]);
+    }
+
+    #[test]
+    fn visual_bell_bottom_bar() {
+        let region = VisualBellRegion::BottomBar;
+
+        assert_eq!(visual_bell(region, &size_info(7., 1.)), vec![(5., 481., 700., 4.)]);
+        assert_eq!(visual_bell(region, &size_info(7., 2.)), vec![(10., 962., 1400., 8.)]);
+    }
+
+    #[test]
+    fn visual_bell_corners() {
+        let region = VisualBellRegion::Corners;
+
+        assert_eq!(visual_bell(region, &size_info(7., 1.)), vec![
+            (5., 5., 16., 16.),
+            (689., 5., 16., 16.),
+            (5., 469., 16., 16.),
+            (689., 469., 16., 16.),
+        ]);
+        assert_eq!(visual_bell(region, &size_info(7., 2.)), vec![
+            (10., 10., 32., 32.),
+            (1378., 10., 32., 32.),
+            (10., 938., 32., 32.),
+            (1378., 938., 32., 32.),
+        ]);
+    }
+
+    #[test]
+    fn visual_bell_alpha() {
+        let rects = RenderRect::visual_bell(
+            VisualBellRegion::Corners,
+            &size_info(7., 1.),
+            Rgb::default(),
+            0.25,
+        );
+
+        assert!(rects.iter().all(|rect| rect.alpha == 0.25));
+    }
+
+    fn padding(size: &SizeInfo) -> Vec<(f32, f32, f32, f32)> {
+        RenderRect::padding(size, Rgb::default())
+            .iter()
+            .map(|rect| (rect.x, rect.y, rect.width, rect.height))
+            .collect()
+    }
+
+    #[test]
+    fn padding_around_grid() {
+        assert_eq!(padding(&size_info(7., 1.)), vec![
+            (0., 0., 710., 5.),
+            (0., 485., 710., 5.),
+            (0., 5., 5., 480.),
+            (705., 5., 5., 480.),
+        ]);
+    }
+
+    #[test]
+    fn padding_includes_partial_cells() {
+        let mut size = size_info(7., 1.);
+        size.width += 4.;
+        size.height += 20.;
+
+        assert_eq!(padding(&size), vec![
+            (0., 0., 714., 5.),
+            (0., 501., 714., 9.),
+            (0., 5., 5., 496.),
+            (705., 5., 9., 496.),
+        ]);
+    }
+
+    #[test]
+    fn padding_without_padding() {
+        let mut size = size_info(7., 1.);
+        size.width = 700.;
+        size.height = 480.;
+        size.padding_left = 0.;
+        size.padding_right = 0.;
+        size.padding_top = 0.;
+        size.padding_bottom = 0.;
+
+        assert!(padding(&size).is_empty());
+    }
+
+    const RED: Rgb = Rgb { r: 0xff, g: 0, b: 0 };
+    const BLUE: Rgb = Rgb { r: 0, g: 0, b: 0xff };
+
+    fn cell(column: usize, fg: Rgb, flags: Flags) -> RenderableCell {
+        RenderableCell {
+            line: Line(0),
+            column: Column(column),
+            inner: RenderableCellContent::Chars([' '; MAX_ZEROWIDTH_CHARS + 1]),
+            fg,
+            bg: Rgb::default(),
+            bg_alpha: 0.,
+            flags,
+        }
+    }
+
+    /// Position, width and color of the lines drawn for `cells`
+    fn lines(cells: &[RenderableCell]) -> Vec<(f32, f32, f32, Rgb)> {
+        let metrics = Metrics {
+            average_advance: 7.,
+            line_height: 16.,
+            descent: -4.,
+            underline_position: -2.,
+            underline_thickness: 1.,
+            strikeout_position: 4.,
+            strikeout_thickness: 1.,
+        };
+
+        let mut lines = RenderLines::new();
+        for cell in cells {
+            lines.update(cell);
+        }
+
+        lines
+            .into_rects(&metrics, &size_info(7., 1.))
+            .iter()
+            .map(|rect| (rect.x, rect.y, rect.width, rect.color))
+            .collect()
+    }
+
+    #[test]
+    fn lines_merge_same_color() {
+        let cells: Vec<_> = (0..3).map(|col| cell(col, RED, Flags::UNDERLINE)).collect();
+
+        assert_eq!(lines(&cells), vec![(5., 18.5, 21., RED)]);
+    }
+
+    #[test]
+    fn lines_split_at_color_change() {
+        let cells = [
+            cell(0, RED, Flags::UNDERLINE),
+            cell(1, RED, Flags::UNDERLINE),
+            cell(2, BLUE, Flags::UNDERLINE),
+            cell(3, RED, Flags::UNDERLINE),
+        ];
+
+        assert_eq!(lines(&cells), vec![
+            (5., 18.5, 14., RED),
+            (19., 18.5, 7., BLUE),
+            (26., 18.5, 7., RED),
+        ]);
+    }
+
+    #[test]
+    fn lines_split_at_gap() {
+        let cells = [cell(0, RED, Flags::STRIKEOUT), cell(2, RED, Flags::STRIKEOUT)];
+
+        assert_eq!(lines(&cells), vec![(5., 12.5, 7., RED), (19., 12.5, 7., RED)]);
+    }
+
+    #[test]
+    fn underline_and_strikeout_are_independent() {
+        let both = Flags::UNDERLINE | Flags::STRIKEOUT;
+        let cells = [
+            cell(0, RED, both),
+            cell(1, BLUE, Flags::STRIKEOUT),
+            cell(2, BLUE, both),
+            cell(3, BLUE, Flags::UNDERLINE),
+        ];
+
+        assert_eq!(lines(&cells), vec![
+            (5., 18.5, 7., RED),
+            (19., 18.5, 14., BLUE),
+            (5., 12.5, 7., RED),
+            (12., 12.5, 14., BLUE),
+        ]);
+    }
+
+    #[test]
+    fn lines_continue_after_cursor() {
+        let key = CursorKey { style: CursorStyle::Underline, is_wide: false };
+        let mut cursor = cell(1, BLUE, Flags::UNDERLINE);
+        cursor.inner = RenderableCellContent::Cursor(key);
+
+        let cells = [
+            cell(0, RED, Flags::UNDERLINE),
+            cursor,
+            cell(1, RED, Flags::UNDERLINE),
+            cell(2, RED, Flags::UNDERLINE),
+        ];
+
+        assert_eq!(lines(&cells), vec![(5., 18.5, 21., RED)]);
+    }
+
+    #[test]
+    fn lines_follow_inverted_cursor_cell() {
+        let key = CursorKey { style: CursorStyle::Block, is_wide: false };
+        let mut cursor = cell(1, RED, Flags::UNDERLINE);
+        cursor.inner = RenderableCellContent::Cursor(key);
+
+        // The block cursor swaps the colors of the cell below it
+        let cells = [
+            cell(0, RED, Flags::UNDERLINE),
+            cursor,
+            cell(1, BLUE, Flags::UNDERLINE),
+            cell(2, RED, Flags::UNDERLINE),
+        ];
+
+        assert_eq!(lines(&cells), vec![
+            (5., 18.5, 7., RED),
+            (12., 18.5, 7., BLUE),
+            (19., 18.5, 7., RED),
+        ]);
     }
 }
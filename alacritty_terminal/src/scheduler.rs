@@ -0,0 +1,166 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timers which wake up the event loop
+//!
+//! An idle event loop blocks until it receives the next window event, so timers have to
+//! wake it up from another thread once their deadline has passed.
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// Deadlines of the pending timers
+#[derive(Debug, Default)]
+pub struct Deadlines {
+    deadlines: Vec<Instant>,
+}
+
+impl Deadlines {
+    /// Add a timer which expires at `deadline`
+    pub fn schedule(&mut self, deadline: Instant) {
+        self.deadlines.push(deadline);
+    }
+
+    /// Earliest pending deadline
+    pub fn next(&self) -> Option<Instant> {
+        self.deadlines.iter().min().cloned()
+    }
+
+    /// Time left at `now` until the earliest deadline, `None` if there are no timers
+    pub fn timeout(&self, now: Instant) -> Option<Duration> {
+        let deadline = self.next()?;
+        Some(if deadline > now { deadline - now } else { Duration::from_secs(0) })
+    }
+
+    /// Remove all timers which have expired at `now`, returns `true` if there were any
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let pending = self.deadlines.len();
+        self.deadlines.retain(|&deadline| deadline > now);
+        self.deadlines.len() != pending
+    }
+}
+
+/// Handle to a thread which wakes up the event loop when timers expire
+///
+/// The thread waits for the earliest deadline without polling, so it doesn't cause any
+/// wakeups while no timers are pending. All windows share the thread through clones of the
+/// handle, it stops once every handle has been dropped.
+#[derive(Clone)]
+pub struct Scheduler {
+    tx: Sender<Instant>,
+}
+
+impl Scheduler {
+    /// Spawn the timer thread, `wakeup` is called whenever timers have expired
+    pub fn new<F>(wakeup: F) -> Scheduler
+    where
+        F: Fn() + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+
+        crate::util::thread::spawn_named("timer", move || run(rx, wakeup));
+
+        Scheduler { tx }
+    }
+
+    /// Wake up the event loop once `delay` has passed
+    pub fn schedule(&self, delay: Duration) {
+        let _ = self.tx.send(Instant::now() + delay);
+    }
+}
+
+fn run<F: Fn()>(rx: Receiver<Instant>, wakeup: F) {
+    let mut deadlines = Deadlines::default();
+
+    // Stop once all handles of the scheduler have been dropped
+    loop {
+        let received = match deadlines.timeout(Instant::now()) {
+            Some(timeout) => rx.recv_timeout(timeout),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match received {
+            Ok(deadline) => deadlines.schedule(deadline),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if deadlines.expire(Instant::now()) {
+            wakeup();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    use super::{Deadlines, Scheduler};
+
+    #[test]
+    fn earliest_deadline_first() {
+        let now = Instant::now();
+        let mut deadlines = Deadlines::default();
+        assert_eq!(deadlines.next(), None);
+        assert_eq!(deadlines.timeout(now), None);
+
+        deadlines.schedule(now + Duration::from_millis(300));
+        deadlines.schedule(now + Duration::from_millis(100));
+        assert_eq!(deadlines.next(), Some(now + Duration::from_millis(100)));
+        assert_eq!(deadlines.timeout(now), Some(Duration::from_millis(100)));
+
+        // Overdue timers don't wait at all
+        let later = now + Duration::from_millis(200);
+        assert_eq!(deadlines.timeout(later), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn expire_passed_deadlines() {
+        let now = Instant::now();
+        let mut deadlines = Deadlines::default();
+        deadlines.schedule(now + Duration::from_millis(100));
+        deadlines.schedule(now + Duration::from_millis(300));
+
+        assert!(!deadlines.expire(now));
+        assert!(deadlines.expire(now + Duration::from_millis(100)));
+        assert_eq!(deadlines.next(), Some(now + Duration::from_millis(300)));
+        assert!(deadlines.expire(now + Duration::from_secs(1)));
+        assert_eq!(deadlines.next(), None);
+    }
+
+    #[test]
+    fn scheduler_wakes_up() {
+        let (tx, rx) = mpsc::channel();
+        let scheduler = Scheduler::new(move || {
+            let _ = tx.send(());
+        });
+
+        scheduler.schedule(Duration::from_millis(10));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(()));
+    }
+
+    #[test]
+    fn shared_scheduler_outlives_original() {
+        let (tx, rx) = mpsc::channel();
+        let scheduler = Scheduler::new(move || {
+            let _ = tx.send(());
+        });
+
+        let shared = scheduler.clone();
+        drop(scheduler);
+
+        shared.schedule(Duration::from_millis(10));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(()));
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Saving the window content to PNG files
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::png::PNGEncoder;
+use image::ColorType;
+
+/// Bytes per pixel of the RGBA framebuffer content
+const BYTES_PER_PIXEL: usize = 4;
+
+/// RGBA pixels of a rendered frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl Screenshot {
+    /// Create a screenshot from the output of `glReadPixels`
+    ///
+    /// OpenGL returns rows from the bottom to the top of the window, so they
+    /// are flipped to get the top-to-bottom order expected by image formats.
+    pub fn from_framebuffer(mut pixels: Vec<u8>, width: u32, height: u32) -> Screenshot {
+        assert_eq!(pixels.len(), width as usize * height as usize * BYTES_PER_PIXEL);
+
+        flip_vertically(&mut pixels, width as usize * BYTES_PER_PIXEL);
+
+        Screenshot { pixels, width, height }
+    }
+
+    /// Encode the screenshot as PNG
+    pub fn encode_png(&self) -> io::Result<Vec<u8>> {
+        let mut png = Vec::new();
+        PNGEncoder::new(&mut png).encode(
+            &self.pixels,
+            self.width,
+            self.height,
+            ColorType::RGBA(8),
+        )?;
+        Ok(png)
+    }
+
+    /// Write the screenshot to a new timestamped file in `directory`
+    pub fn save(&self, directory: &Path) -> io::Result<PathBuf> {
+        let path = directory.join(file_name(SystemTime::now()));
+        fs::write(&path, self.encode_png()?)?;
+        Ok(path)
+    }
+}
+
+/// Reverse the order of all rows in a pixel buffer
+fn flip_vertically(pixels: &mut [u8], row_len: usize) {
+    if row_len == 0 {
+        return;
+    }
+
+    let rows = pixels.len() / row_len;
+    for row in 0..rows / 2 {
+        let (top, bottom) = pixels.split_at_mut((rows - row - 1) * row_len);
+        top[row * row_len..(row + 1) * row_len].swap_with_slice(&mut bottom[..row_len]);
+    }
+}
+
+/// Name of a screenshot taken at `time`
+fn file_name(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("Alacritty-{}{:03}.png", since_epoch.as_secs(), since_epoch.subsec_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use image::GenericImageView;
+
+    use super::{file_name, flip_vertically, Screenshot};
+
+    #[test]
+    fn flip_even_rows() {
+        let mut pixels = vec![1, 1, 2, 2, 3, 3, 4, 4];
+        flip_vertically(&mut pixels, 2);
+        assert_eq!(pixels, vec![4, 4, 3, 3, 2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn flip_odd_rows() {
+        let mut pixels = vec![1, 1, 2, 2, 3, 3];
+        flip_vertically(&mut pixels, 2);
+        assert_eq!(pixels, vec![3, 3, 2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn flip_empty() {
+        let mut pixels = Vec::new();
+        flip_vertically(&mut pixels, 0);
+        assert!(pixels.is_empty());
+    }
+
+    #[test]
+    fn png_has_top_row_first() {
+        // 2x2 framebuffer, bottom row red and top row blue
+        let red = [0xff, 0, 0, 0xff];
+        let blue = [0, 0, 0xff, 0xff];
+        let pixels = [red, red, blue, blue].concat();
+
+        let screenshot = Screenshot::from_framebuffer(pixels, 2, 2);
+        let png = screenshot.encode_png().unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+
+        assert_eq!(image.dimensions(), (2, 2));
+        assert_eq!(image.to_rgba().get_pixel(0, 0).data, blue);
+        assert_eq!(image.to_rgba().get_pixel(1, 1).data, red);
+    }
+
+    #[test]
+    fn timestamped_file_name() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_565_000_000_042);
+        assert_eq!(file_name(time), "Alacritty-1565000000042.png");
+    }
+}
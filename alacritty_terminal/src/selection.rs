@@ -18,10 +18,11 @@
 //! finalized when the button is released. The selection should be cleared
 //! when text is added/removed/scrolled on the screen. The selection should
 //! also be cleared if the user clicks off of the selection.
+use std::cmp::max;
 use std::ops::Range;
 
 use crate::index::{Column, Line, Point, Side};
-use crate::term::cell::Flags;
+use crate::term::cluster;
 use crate::term::{Search, Term};
 
 /// Describes a region of a 2-dimensional area
@@ -92,6 +93,20 @@ impl Selection {
         }
     }
 
+    /// Topmost buffer line which is part of the selection
+    pub fn top_line(&self) -> isize {
+        let (start, end) = match *self {
+            Selection::Simple { ref region } | Selection::Block { ref region } => {
+                (region.start.point.line, region.end.point.line)
+            },
+            Selection::Semantic { ref region } | Selection::Lines { ref region } => {
+                (region.start.line, region.end.line)
+            },
+        };
+
+        max(start, end)
+    }
+
     pub fn simple(location: Point<usize>, side: Side) -> Selection {
         Selection::Simple {
             region: Range {
@@ -196,20 +211,16 @@ impl Selection {
             Selection::Lines { .. } => Selection::span_lines(term, start, end),
         };
 
-        // Expand selection across double-width cells
+        // Expand selection across double-width cells and clusters, they can't be split
         span.map(|mut span| {
             let grid = term.grid();
 
-            if span.end.col < cols
-                && grid[span.end.line][span.end.col].flags.contains(Flags::WIDE_CHAR_SPACER)
-            {
-                span.end.col = Column(span.end.col.saturating_sub(1));
+            if span.end.col < cols {
+                span.end.col = cluster::bounds(&grid[span.end.line], span.end.col).start;
             }
 
-            if span.start.col.0 < cols.saturating_sub(1)
-                && grid[span.start.line][span.start.col].flags.contains(Flags::WIDE_CHAR)
-            {
-                span.start.col += 1;
+            if span.start.col < cols {
+                span.start.col = cluster::bounds(&grid[span.start.line], span.start.col).end - 1;
             }
 
             span
@@ -404,8 +415,10 @@ mod test {
             height: height as f32,
             cell_width: 1.0,
             cell_height: 1.0,
-            padding_x: 0.0,
-            padding_y: 0.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
             dpr: 1.0,
         };
         Term::new(&Default::default(), size, MessageBuffer::new(), Clipboard::new_nop())
@@ -589,4 +602,25 @@ mod test {
             is_block: false,
         });
     }
+
+    #[test]
+    fn cluster_expansion() {
+        let mut term = term(10, 1);
+        let mut grid = Grid::new(Line(1), Column(10), 0, Cell::default());
+        grid[Line(0)][Column(3)].c = '\u{1f1fa}';
+        grid[Line(0)][Column(4)].c = '\u{1f1f8}';
+        grid[Line(0)][Column(6)].c = '\u{1f1ea}';
+        grid[Line(0)][Column(7)].c = '\u{1f1fa}';
+        mem::swap(term.grid_mut(), &mut grid);
+
+        // Selecting half of a flag selects all of it
+        let mut selection = Selection::simple(Point::new(0, Column(4)), Side::Left);
+        selection.update(Point::new(0, Column(6)), Side::Right);
+
+        assert_eq!(selection.to_span(&term).unwrap(), Span {
+            start: Point::new(0, Column(7)),
+            end: Point::new(0, Column(3)),
+            is_block: false,
+        });
+    }
 }
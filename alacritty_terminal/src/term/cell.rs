@@ -98,9 +98,10 @@ impl LineLength for grid::Row<Cell> {
             return Column(self.len());
         }
 
-        for (index, cell) in self[..].iter().rev().enumerate() {
+        for index in (0..self.len()).rev() {
+            let cell = &self[Column(index)];
             if cell.c != ' ' || cell.extra[0] != ' ' {
-                length = Column(self.len() - index);
+                length = Column(index + 1);
                 break;
             }
         }
@@ -0,0 +1,175 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Grapheme clusters which are spread over several cells
+//!
+//! Combining marks are stored in the cell of their base character, but emoji sequences like
+//! flags, skin tones and ZWJ families are written to consecutive cells. Only the rules joining
+//! these cells are implemented here, instead of the full Unicode segmentation algorithm.
+use std::cmp::max;
+use std::ops::Range;
+
+use crate::grid::Row;
+use crate::index::Column;
+use crate::term::cell::{Cell, Flags};
+
+/// Zero width joiner, stored with the character before it
+const ZWJ: char = '\u{200d}';
+
+#[inline]
+fn is_regional_indicator(c: char) -> bool {
+    c >= '\u{1f1e6}' && c <= '\u{1f1ff}'
+}
+
+/// Skin tone modifiers
+#[inline]
+fn is_emoji_modifier(c: char) -> bool {
+    c >= '\u{1f3fb}' && c <= '\u{1f3ff}'
+}
+
+/// Check if a cell can't be part of a cluster spanning several cells
+#[inline]
+fn is_single(cell: &Cell) -> bool {
+    !cell.flags.intersects(Flags::WIDE_CHAR | Flags::WIDE_CHAR_SPACER)
+        && cell.extra[0] == ' '
+        && !is_regional_indicator(cell.c)
+        && !is_emoji_modifier(cell.c)
+}
+
+/// Check if `cell` continues the cluster of the character before it
+///
+/// `unpaired` is set when `prev` is a regional indicator which isn't part of a flag yet.
+#[inline]
+fn joins(prev: &Cell, cell: &Cell, unpaired: bool) -> bool {
+    let emoji = prev.flags.contains(Flags::WIDE_CHAR) && cell.flags.contains(Flags::WIDE_CHAR);
+    (emoji && (prev.extra.contains(&ZWJ) || is_emoji_modifier(cell.c)))
+        || (unpaired && is_regional_indicator(cell.c))
+}
+
+/// Columns of the cluster containing `col`, including the spacers of wide characters
+pub fn bounds(row: &Row<Cell>, col: Column) -> Range<Column> {
+    if is_single(&row[col]) {
+        return col..col + 1;
+    }
+
+    let mut start = Column(0);
+    let mut prev: Option<&Cell> = None;
+    let mut unpaired = false;
+    for index in 0..row.len() {
+        let cell = &row[Column(index)];
+        if cell.flags.contains(Flags::WIDE_CHAR_SPACER) && prev.is_some() {
+            continue;
+        }
+
+        let joined = prev.map_or(false, |prev| joins(prev, cell, unpaired));
+        if !joined {
+            if index > col.0 {
+                return start..Column(index);
+            }
+            start = Column(index);
+        }
+
+        unpaired = is_regional_indicator(cell.c) && !(joined && unpaired);
+        prev = Some(cell);
+    }
+
+    start..Column(row.len())
+}
+
+/// Columns of all clusters which are at least partially inside `cols`
+pub fn expand(row: &Row<Cell>, cols: Range<Column>) -> Range<Column> {
+    if cols.start >= cols.end {
+        return cols;
+    }
+
+    let start = bounds(row, cols.start).start;
+    let end = bounds(row, cols.end - 1).end;
+    start..max(end, cols.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bounds, expand};
+    use crate::grid::Row;
+    use crate::index::Column;
+    use crate::term::cell::{Cell, Flags};
+
+    /// Row with the cells of `text` as they're written by the terminal
+    fn row(text: &str) -> Row<Cell> {
+        let mut row = Row::new(Column(10), &Cell::default());
+        let mut col = 0;
+        for c in text.chars() {
+            match c {
+                '\u{200d}' | '\u{301}' => {
+                    let mut base = col - 1;
+                    if row[Column(base)].flags.contains(Flags::WIDE_CHAR_SPACER) {
+                        base -= 1;
+                    }
+                    row[Column(base)].push_extra(c);
+                },
+                '\u{1f1e6}'..='\u{1f1ff}' | 'a'..='z' => {
+                    row[Column(col)].c = c;
+                    col += 1;
+                },
+                _ => {
+                    row[Column(col)].c = c;
+                    row[Column(col)].flags.insert(Flags::WIDE_CHAR);
+                    row[Column(col + 1)].flags.insert(Flags::WIDE_CHAR_SPACER);
+                    col += 2;
+                },
+            }
+        }
+        row
+    }
+
+    #[test]
+    fn single_cells() {
+        let row = row("ae\u{301}");
+        assert_eq!(bounds(&row, Column(0)), Column(0)..Column(1));
+        assert_eq!(bounds(&row, Column(1)), Column(1)..Column(2));
+        assert_eq!(bounds(&row, Column(5)), Column(5)..Column(6));
+    }
+
+    #[test]
+    fn flags_pair_up() {
+        // Three regional indicators are a flag and a lone indicator
+        let row = row("a\u{1f1fa}\u{1f1f8}\u{1f1ea}b");
+        assert_eq!(bounds(&row, Column(1)), Column(1)..Column(3));
+        assert_eq!(bounds(&row, Column(2)), Column(1)..Column(3));
+        assert_eq!(bounds(&row, Column(3)), Column(3)..Column(4));
+    }
+
+    #[test]
+    fn skin_tone_modifier() {
+        let row = row("a\u{1f44d}\u{1f3fd}b");
+        for col in 1..5 {
+            assert_eq!(bounds(&row, Column(col)), Column(1)..Column(5));
+        }
+        assert_eq!(bounds(&row, Column(5)), Column(5)..Column(6));
+    }
+
+    #[test]
+    fn zwj_family() {
+        let row = row("\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}a");
+        assert_eq!(bounds(&row, Column(3)), Column(0)..Column(6));
+        assert_eq!(expand(&row, Column(5)..Column(7)), Column(0)..Column(7));
+    }
+
+    #[test]
+    fn separate_wide_chars() {
+        let row = row("\u{65e5}\u{672c}");
+        assert_eq!(bounds(&row, Column(1)), Column(0)..Column(2));
+        assert_eq!(expand(&row, Column(1)..Column(3)), Column(0)..Column(4));
+    }
+}
@@ -1,25 +1,59 @@
-use std::fmt;
+use std::collections::HashMap;
 use std::ops::{Index, IndexMut, Mul};
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+use std::{cmp, fmt};
 
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer};
 
 use crate::ansi;
-use crate::config::Colors;
+use crate::config::{Colors, Config};
 
 pub const COUNT: usize = 269;
 
 pub const RED: Rgb = Rgb { r: 0xff, g: 0x0, b: 0x0 };
 pub const YELLOW: Rgb = Rgb { r: 0xff, g: 0xff, b: 0x0 };
+pub const BLACK: Rgb = Rgb { r: 0x0, g: 0x0, b: 0x0 };
+pub const WHITE: Rgb = Rgb { r: 0xff, g: 0xff, b: 0xff };
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone, Default, Serialize)]
+/// Time without further color changes after which a changed palette is drawn
+pub const PALETTE_BATCH_WINDOW: Duration = Duration::from_millis(8);
+
+/// Longest time drawing is delayed by a single burst of color changes
+pub const PALETTE_BATCH_LIMIT: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Default, Serialize)]
 pub struct Rgb {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
+impl Rgb {
+    /// Relative luminance, as defined by WCAG 2.0
+    fn luminance(self) -> f64 {
+        let channel = |value: u8| {
+            let value = f64::from(value) / 255.;
+            if value <= 0.03928 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        };
+
+        0.2126 * channel(self.r) + 0.7152 * channel(self.g) + 0.0722 * channel(self.b)
+    }
+
+    /// Contrast ratio between two colors
+    ///
+    /// This ranges from 1 for identical colors to 21 for black and white.
+    pub fn contrast(self, other: Rgb) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+}
+
 // a multiply function for Rgb, as the default dim is just *2/3
 impl Mul<f32> for Rgb {
     type Output = Rgb;
@@ -72,6 +106,12 @@ impl<'de> Deserialize<'de> for Rgb {
             }
         }
 
+        // Binary formats like saved sessions can only contain the struct form
+        if !deserializer.is_human_readable() {
+            let RgbDerivedDeser { r, g, b } = RgbDerivedDeser::deserialize(deserializer)?;
+            return Ok(Rgb { r, g, b });
+        }
+
         // Return an error if the syntax is incorrect
         let value = serde_yaml::Value::deserialize(deserializer)?;
 
@@ -185,8 +225,9 @@ impl List {
         self[ansi::NamedColor::Cursor] = colors.cursor.cursor.unwrap_or_else(Rgb::default);
 
         // Dims
+        let dim_factor = colors.dim_factor();
         self[ansi::NamedColor::DimForeground] =
-            colors.primary.dim_foreground.unwrap_or(colors.primary.foreground * 0.66);
+            colors.primary.dim_foreground.unwrap_or(colors.primary.foreground * dim_factor);
         match colors.dim {
             Some(ref dim) => {
                 trace!("Using config-provided dim colors");
@@ -201,14 +242,14 @@ impl List {
             },
             None => {
                 trace!("Deriving dim colors from normal colors");
-                self[ansi::NamedColor::DimBlack] = colors.normal().black * 0.66;
-                self[ansi::NamedColor::DimRed] = colors.normal().red * 0.66;
-                self[ansi::NamedColor::DimGreen] = colors.normal().green * 0.66;
-                self[ansi::NamedColor::DimYellow] = colors.normal().yellow * 0.66;
-                self[ansi::NamedColor::DimBlue] = colors.normal().blue * 0.66;
-                self[ansi::NamedColor::DimMagenta] = colors.normal().magenta * 0.66;
-                self[ansi::NamedColor::DimCyan] = colors.normal().cyan * 0.66;
-                self[ansi::NamedColor::DimWhite] = colors.normal().white * 0.66;
+                self[ansi::NamedColor::DimBlack] = colors.normal().black * dim_factor;
+                self[ansi::NamedColor::DimRed] = colors.normal().red * dim_factor;
+                self[ansi::NamedColor::DimGreen] = colors.normal().green * dim_factor;
+                self[ansi::NamedColor::DimYellow] = colors.normal().yellow * dim_factor;
+                self[ansi::NamedColor::DimBlue] = colors.normal().blue * dim_factor;
+                self[ansi::NamedColor::DimMagenta] = colors.normal().magenta * dim_factor;
+                self[ansi::NamedColor::DimCyan] = colors.normal().cyan * dim_factor;
+                self[ansi::NamedColor::DimWhite] = colors.normal().white * dim_factor;
             },
         }
     }
@@ -317,3 +358,255 @@ impl IndexMut<u8> for List {
         &mut self.0[idx as usize]
     }
 }
+
+/// Color lists of the color schemes in the config
+///
+/// The schemes are kept in the order of the config file, which is also the order they are
+/// cycled through.
+#[derive(Debug, Default, Clone)]
+pub struct Schemes {
+    schemes: Vec<(String, List)>,
+
+    /// Index of the active scheme, the `colors` section is used without one
+    active: Option<usize>,
+}
+
+impl Schemes {
+    /// Load all schemes of the config
+    ///
+    /// The scheme `active` stays selected if it still exists, otherwise `colors.scheme` is used.
+    pub fn new(config: &Config, active: Option<&str>) -> Schemes {
+        let schemes: Vec<_> = config
+            .schemes
+            .iter()
+            .map(|scheme| (scheme.name.clone(), List::from(&scheme.colors)))
+            .collect();
+
+        let position = |name: &str| schemes.iter().position(|(scheme, _)| scheme == name);
+        let active = active.and_then(position).or_else(|| {
+            let name = config.colors.scheme.as_ref()?;
+            let index = position(name);
+            if index.is_none() {
+                warn!("Unknown color scheme {:?}", name);
+            }
+            index
+        });
+
+        Schemes { schemes, active }
+    }
+
+    /// Name of the active scheme
+    #[inline]
+    pub fn active(&self) -> Option<&str> {
+        self.active.map(|index| self.schemes[index].0.as_str())
+    }
+
+    /// Colors of the active scheme
+    #[inline]
+    pub fn list(&self) -> Option<&List> {
+        self.active.map(|index| &self.schemes[index].1)
+    }
+
+    /// Activate the scheme `name`, returns `false` if it doesn't exist
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.schemes.iter().position(|(scheme, _)| scheme == name) {
+            Some(index) => {
+                self.active = Some(index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Activate the scheme after the active one, returns `false` if there are no schemes
+    pub fn cycle(&mut self) -> bool {
+        if self.schemes.is_empty() {
+            return false;
+        }
+
+        self.active = Some(self.active.map_or(0, |index| (index + 1) % self.schemes.len()));
+        true
+    }
+}
+
+/// Dim variants of colors without an entry in the dim palette
+///
+/// Dim text usually only uses a handful of colors, so each one is scaled once per frame.
+#[derive(Debug)]
+pub struct DimColors {
+    factor: f32,
+    cache: HashMap<Rgb, Rgb>,
+}
+
+impl DimColors {
+    pub fn new(factor: f32) -> Self {
+        DimColors { factor, cache: HashMap::new() }
+    }
+
+    /// Dim variant of `color`
+    pub fn get(&mut self, color: Rgb) -> Rgb {
+        let factor = self.factor;
+        *self.cache.entry(color).or_insert_with(|| color * factor)
+    }
+}
+
+/// Burst of color changes by escape sequences
+///
+/// Theme switching scripts rewrite the whole palette with one escape sequence per color, which
+/// might be split over several pty reads. Drawing is held back until the burst is over, so the
+/// new palette shows up at once instead of flickering through every intermediate state.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct PaletteBurst {
+    /// Time of the first change since the last frame
+    started: Option<Instant>,
+
+    /// Time of the latest change
+    last: Option<Instant>,
+}
+
+impl PaletteBurst {
+    /// Record a color change at `now`
+    pub fn change(&mut self, now: Instant) {
+        self.started = self.started.or(Some(now));
+        self.last = Some(now);
+    }
+
+    /// Time to wait at `now` before drawing, `None` once the burst is over or has taken too long
+    pub fn delay(&self, now: Instant) -> Option<Duration> {
+        let (started, last) = (self.started?, self.last?);
+        let end = cmp::min(last + PALETTE_BATCH_WINDOW, started + PALETTE_BATCH_LIMIT);
+        if end > now {
+            Some(end - now)
+        } else {
+            None
+        }
+    }
+
+    /// Forget the changes once they have been drawn
+    pub fn clear(&mut self) {
+        *self = PaletteBurst::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{
+        PaletteBurst, Rgb, Schemes, BLACK, PALETTE_BATCH_LIMIT, PALETTE_BATCH_WINDOW, RED, WHITE,
+    };
+    use crate::ansi::NamedColor;
+    use crate::config::Config;
+
+    static SCHEMES: &str = "
+schemes:
+  dark:
+    primary:
+      background: '0x000000'
+  light:
+    primary:
+      background: '0xffffff'
+  solarized:
+    primary:
+      background: '0x002b36'
+";
+
+    fn config(scheme: Option<&str>) -> Config {
+        let mut config: Config = serde_yaml::from_str(SCHEMES).unwrap();
+        config.colors.scheme = scheme.map(String::from);
+        config
+    }
+
+    #[test]
+    fn scheme_from_config() {
+        let schemes = Schemes::new(&config(Some("light")), None);
+        assert_eq!(schemes.active(), Some("light"));
+        assert_eq!(schemes.list().unwrap()[NamedColor::Background].r, 0xff);
+
+        let schemes = Schemes::new(&config(Some("missing")), None);
+        assert_eq!(schemes.active(), None);
+        assert!(schemes.list().is_none());
+    }
+
+    #[test]
+    fn reload_keeps_active_scheme() {
+        let schemes = Schemes::new(&config(Some("dark")), Some("solarized"));
+        assert_eq!(schemes.active(), Some("solarized"));
+
+        // Falls back to the config when the active scheme has been removed
+        let schemes = Schemes::new(&config(Some("dark")), Some("removed"));
+        assert_eq!(schemes.active(), Some("dark"));
+    }
+
+    #[test]
+    fn cycle_in_config_order() {
+        let mut schemes = Schemes::new(&config(None), None);
+
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            assert!(schemes.cycle());
+            order.push(schemes.active().unwrap().to_owned());
+        }
+
+        assert_eq!(order, vec!["dark", "light", "solarized", "dark"]);
+    }
+
+    #[test]
+    fn cycle_without_schemes() {
+        let mut schemes = Schemes::new(&Config::default(), None);
+        assert!(!schemes.cycle());
+        assert_eq!(schemes.active(), None);
+    }
+
+    #[test]
+    fn select_scheme() {
+        let mut schemes = Schemes::new(&config(None), None);
+        assert!(!schemes.select("missing"));
+        assert_eq!(schemes.active(), None);
+
+        assert!(schemes.select("light"));
+        assert!(schemes.cycle());
+        assert_eq!(schemes.active(), Some("solarized"));
+    }
+
+    #[test]
+    fn palette_burst_waits_for_last_change() {
+        let mut burst = PaletteBurst::default();
+        let start = Instant::now();
+        assert_eq!(burst.delay(start), None);
+
+        burst.change(start);
+        burst.change(start + Duration::from_millis(5));
+        assert_eq!(burst.delay(start + Duration::from_millis(5)), Some(PALETTE_BATCH_WINDOW));
+        assert_eq!(burst.delay(start + Duration::from_millis(5) + PALETTE_BATCH_WINDOW), None);
+
+        burst.clear();
+        assert_eq!(burst.delay(start), None);
+    }
+
+    #[test]
+    fn palette_burst_is_limited() {
+        let mut burst = PaletteBurst::default();
+        let start = Instant::now();
+
+        // A steady stream of changes can't hold back drawing forever
+        let step = Duration::from_millis(1);
+        let mut now = start;
+        while now < start + PALETTE_BATCH_LIMIT * 2 {
+            burst.change(now);
+            now += step;
+        }
+        assert_eq!(burst.delay(now), None);
+        assert_eq!(burst.delay(start + PALETTE_BATCH_LIMIT - step), Some(step));
+    }
+
+    #[test]
+    fn contrast() {
+        assert!((BLACK.contrast(WHITE) - 21.).abs() < 0.01);
+        assert!((WHITE.contrast(BLACK) - 21.).abs() < 0.01);
+        assert!((RED.contrast(RED) - 1.).abs() < 0.01);
+
+        let gray = Rgb { r: 0x77, g: 0x77, b: 0x77 };
+        assert!(gray.contrast(WHITE) > 4.4 && gray.contrast(WHITE) < 4.5);
+    }
+}
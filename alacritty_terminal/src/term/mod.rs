@@ -15,40 +15,67 @@
 //! Exports the `Term` type which is a high-level API for the Grid
 use std::cmp::{max, min};
 use std::ops::{Index, IndexMut, Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
 use std::time::{Duration, Instant};
 use std::{io, mem, ptr};
 
-use font::{self, Size};
-use glutin::MouseCursor;
+#[cfg(feature = "gui")]
+use font::Size;
 use rfind_url::{Parser, ParserState};
-use unicode_width::UnicodeWidthChar;
 
 use crate::ansi::{
-    self, Attr, CharsetIndex, Color, CursorStyle, Handler, NamedColor, StandardCharset, TermInfo,
+    self, Attr, CharsetIndex, Color, CursorStyle, Handler, MouseCursorIcon, NamedColor,
+    StandardCharset, StringTerminator, TermInfo, UnhandledStats,
 };
 use crate::clipboard::{Clipboard, ClipboardType};
-use crate::config::{Config, VisualBellAnimation};
-use crate::cursor::CursorKey;
+use crate::config::{AmbiguousWidth, Colors, Config, ReplyTerminator, VisualBellAnimation};
 use crate::grid::{
-    BidirectionalIterator, DisplayIter, Grid, GridCell, IndexRegion, Indexed, Scroll,
+    BidirectionalIterator, DisplayIter, Grid, GridCell, IndexRegion, Indexed, Row, Scroll,
 };
 use crate::index::{self, Column, Contains, IndexRange, Line, Linear, Point};
-use crate::input::FONT_SIZE_STEP;
-use crate::message_bar::MessageBuffer;
+use crate::message_bar::{Message, MessageBuffer};
+use crate::meter::{PendingInput, Throughput};
+use crate::recording::{self, Recorder};
 use crate::selection::{self, Selection, SelectionRange, Span};
 use crate::term::cell::{Cell, Flags, LineLength};
-use crate::term::color::Rgb;
+use crate::term::color::{DimColors, PaletteBurst, Rgb};
 use crate::url::Url;
 
 #[cfg(windows)]
 use crate::tty;
 
 pub mod cell;
+pub mod cluster;
 pub mod color;
+#[cfg(feature = "session")]
+pub mod session;
 
 /// Used to match equal brackets, when performing a bracket-pair selection.
 const BRACKET_PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
 
+/// Message bar topic of the color scheme change notice
+const COLOR_SCHEME_TOPIC: &str = "color scheme";
+
+/// Message bar topic of the font family change notice
+#[cfg(feature = "gui")]
+const FONT_FAMILY_TOPIC: &str = "font family";
+
+/// Time the font family change notice is shown for
+#[cfg(feature = "gui")]
+pub const FONT_FAMILY_NOTICE_DURATION: Duration = Duration::from_secs(2);
+
+/// Smallest font size in points which can be reached with the font size actions
+#[cfg(feature = "gui")]
+const MIN_FONT_SIZE: f32 = 1.;
+
+/// Largest font size in points which can be reached with the font size actions
+#[cfg(feature = "gui")]
+const MAX_FONT_SIZE: f32 = 200.;
+
+/// Minimum contrast of the cursor to the cell background and of the text under the cursor
+const MIN_CURSOR_CONTRAST: f64 = 1.5;
+
 /// A type that can expand a given point to a region
 ///
 /// Usually this is implemented for some 2-D array type since
@@ -173,21 +200,31 @@ impl selection::Dimensions for Term {
 pub struct RenderableCellsIter<'a> {
     inner: DisplayIter<'a, Cell>,
     grid: &'a Grid<Cell>,
-    cursor: &'a Point,
+    cursor: Point,
     cursor_offset: usize,
     cursor_key: Option<CursorKey>,
     cursor_style: CursorStyle,
     config: &'a Config,
+
+    /// Cursor color set in the config or with OSC 12
+    cursor_color: Option<Rgb>,
+
+    /// Colors of the active color scheme
+    scheme: &'a Colors,
+
     colors: &'a color::List,
+    dim_colors: DimColors,
     selection: Option<SelectionRange>,
     url_highlight: &'a Option<RangeInclusive<index::Linear>>,
+
+    /// Swap foreground and background of all cells, see DECSCNM
+    reverse_video: bool,
 }
 
 impl<'a> RenderableCellsIter<'a> {
     /// Create the renderable cells iterator
     ///
-    /// The cursor and terminal mode are required for properly displaying the
-    /// cursor.
+    /// The cursor style has to be resolved already, see `resolve_cursor_style`.
     fn new<'b>(
         term: &'b Term,
         config: &'b Config,
@@ -223,10 +260,18 @@ impl<'a> RenderableCellsIter<'a> {
         });
 
         // Load cursor glyph
-        let cursor = &term.cursor.point;
-        let cursor_visible = term.mode.contains(TermMode::SHOW_CURSOR) && grid.contains(cursor);
+        let mut cursor = term.cursor.point;
+        let cursor_visible = cursor_style != CursorStyle::Hidden && grid.contains(&cursor);
         let cursor_key = if cursor_visible {
-            let is_wide = grid[cursor].flags.contains(cell::Flags::WIDE_CHAR)
+            // Cover the whole character when the cursor is on the spacer of a wide char
+            if cursor.col > Column(0)
+                && grid[&cursor].flags.contains(cell::Flags::WIDE_CHAR_SPACER)
+                && grid[cursor.line][cursor.col - 1].flags.contains(cell::Flags::WIDE_CHAR)
+            {
+                cursor.col -= 1;
+            }
+
+            let is_wide = grid[&cursor].flags.contains(cell::Flags::WIDE_CHAR)
                 && (cursor.col + 1) < grid.num_cols();
             Some(CursorKey { style: cursor_style, is_wide })
         } else {
@@ -235,6 +280,15 @@ impl<'a> RenderableCellsIter<'a> {
             None
         };
 
+        let scheme = config.scheme_colors(term.color_schemes.active());
+
+        let cursor_index = NamedColor::Cursor as usize;
+        let cursor_color = if scheme.cursor.cursor.is_some() || term.color_modified[cursor_index] {
+            Some(term.colors[cursor_index])
+        } else {
+            None
+        };
+
         RenderableCellsIter {
             cursor,
             cursor_offset,
@@ -243,13 +297,24 @@ impl<'a> RenderableCellsIter<'a> {
             selection: selection_range,
             url_highlight: &grid.url_highlight,
             config,
+            cursor_color,
+            scheme,
             colors: &term.colors,
+            dim_colors: DimColors::new(scheme.dim_factor()),
             cursor_key,
             cursor_style,
+            reverse_video: term.mode.contains(TermMode::REVERSE_VIDEO),
         }
     }
 }
 
+/// A key for caching cursor glyphs
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash, Deserialize)]
+pub struct CursorKey {
+    pub style: CursorStyle,
+    pub is_wide: bool,
+}
+
 #[derive(Clone, Debug)]
 pub enum RenderableCellContent {
     Chars([char; cell::MAX_ZEROWIDTH_CHARS + 1]),
@@ -269,16 +334,24 @@ pub struct RenderableCell {
 }
 
 impl RenderableCell {
-    fn new(config: &Config, colors: &color::List, cell: Indexed<Cell>, selected: bool) -> Self {
+    fn new(
+        config: &Config,
+        scheme: &Colors,
+        colors: &color::List,
+        dim_colors: &mut DimColors,
+        cell: Indexed<Cell>,
+        selected: bool,
+        reverse_video: bool,
+    ) -> Self {
         // Lookup RGB values
-        let mut fg_rgb = Self::compute_fg_rgb(config, colors, cell.fg, cell.flags);
+        let mut fg_rgb = Self::compute_fg_rgb(config, colors, dim_colors, cell.fg, cell.flags);
         let mut bg_rgb = Self::compute_bg_rgb(colors, cell.bg);
 
-        let selection_background = config.colors.selection.background;
+        let selection_background = scheme.selection.background;
         if let (true, Some(col)) = (selected, selection_background) {
             // Override selection background with config colors
             bg_rgb = col;
-        } else if selected ^ cell.inverse() {
+        } else if selected ^ cell.inverse() ^ reverse_video {
             if fg_rgb == bg_rgb && !cell.flags.contains(Flags::HIDDEN) {
                 // Reveal inversed text when fg/bg is the same
                 fg_rgb = colors[NamedColor::Background];
@@ -290,7 +363,7 @@ impl RenderableCell {
         }
 
         // Override selection text with config colors
-        if let (true, Some(col)) = (selected, config.colors.selection.text) {
+        if let (true, Some(col)) = (selected, scheme.selection.text) {
             fg_rgb = col;
         }
 
@@ -300,51 +373,49 @@ impl RenderableCell {
             inner: RenderableCellContent::Chars(cell.chars()),
             fg: fg_rgb,
             bg: bg_rgb,
-            bg_alpha: Self::compute_bg_alpha(colors, bg_rgb),
+            bg_alpha: Self::compute_bg_alpha(colors, bg_rgb, reverse_video),
             flags: cell.flags,
         }
     }
 
-    fn compute_fg_rgb(config: &Config, colors: &color::List, fg: Color, flags: cell::Flags) -> Rgb {
+    fn compute_fg_rgb(
+        config: &Config,
+        colors: &color::List,
+        dim_colors: &mut DimColors,
+        fg: Color,
+        flags: cell::Flags,
+    ) -> Rgb {
+        // Dim text is never promoted to bright colors, just like in xterm
+        let dim = flags.contains(Flags::DIM);
+        let bright =
+            !dim && flags.contains(Flags::BOLD) && config.draw_bold_text_with_bright_colors();
+
         match fg {
+            Color::Spec(rgb) if dim => dim_colors.get(rgb),
             Color::Spec(rgb) => rgb,
-            Color::Named(ansi) => {
-                match (config.draw_bold_text_with_bright_colors(), flags & Flags::DIM_BOLD) {
-                    // If no bright foreground is set, treat it like the BOLD flag doesn't exist
-                    (_, cell::Flags::DIM_BOLD)
-                        if ansi == NamedColor::Foreground
-                            && config.colors.primary.bright_foreground.is_none() =>
-                    {
-                        colors[NamedColor::DimForeground]
-                    },
-                    // Draw bold text in bright colors *and* contains bold flag.
-                    (true, cell::Flags::BOLD) => colors[ansi.to_bright()],
-                    // Cell is marked as dim and not bold
-                    (_, cell::Flags::DIM) | (false, cell::Flags::DIM_BOLD) => colors[ansi.to_dim()],
-                    // None of the above, keep original color.
-                    _ => colors[ansi],
-                }
+            Color::Named(ansi) if dim => match ansi.to_dim() {
+                // Scale colors without a dim variant in the palette
+                dim_ansi if dim_ansi == ansi => dim_colors.get(colors[ansi]),
+                dim_ansi => colors[dim_ansi],
             },
-            Color::Indexed(idx) => {
-                let idx = match (
-                    config.draw_bold_text_with_bright_colors(),
-                    flags & Flags::DIM_BOLD,
-                    idx,
-                ) {
-                    (true, cell::Flags::BOLD, 0..=7) => idx as usize + 8,
-                    (false, cell::Flags::DIM, 8..=15) => idx as usize - 8,
-                    (false, cell::Flags::DIM, 0..=7) => idx as usize + 260,
-                    _ => idx as usize,
-                };
-
-                colors[idx]
+            Color::Named(ansi) if bright => colors[ansi.to_bright()],
+            Color::Named(ansi) => colors[ansi],
+            Color::Indexed(idx) if dim => match idx {
+                0..=7 => colors[NamedColor::DimBlack as usize + idx as usize],
+                8..=15 => colors[idx - 8],
+                _ => dim_colors.get(colors[idx]),
             },
+            Color::Indexed(idx @ 0..=7) if bright => colors[idx + 8],
+            Color::Indexed(idx) => colors[idx],
         }
     }
 
+    /// Cells with the background of the screen are transparent
     #[inline]
-    fn compute_bg_alpha(colors: &color::List, bg: Rgb) -> f32 {
-        if colors[NamedColor::Background] == bg {
+    fn compute_bg_alpha(colors: &color::List, bg: Rgb, reverse_video: bool) -> f32 {
+        let background =
+            if reverse_video { NamedColor::Foreground } else { NamedColor::Background };
+        if colors[background] == bg {
             0.
         } else {
             1.
@@ -361,6 +432,40 @@ impl RenderableCell {
     }
 }
 
+impl<'a> RenderableCellsIter<'a> {
+    /// Colors of the cursor and of the text under a block cursor
+    ///
+    /// The colors of `cell` are resolved already, starting with the cell's own colors and then
+    /// the selection. The cursor colors are applied last, so the cursor is visible on top of a
+    /// selection and the text under it is readable independent of the theme.
+    fn cursor_colors(&self, cell: &RenderableCell) -> (Rgb, Rgb) {
+        let cursor_color = self.cursor_color.unwrap_or(cell.fg);
+        let cursor_color = contrasting(cell.bg, &[cursor_color, cell.fg]);
+
+        let text_color = self.scheme.cursor_text_color().unwrap_or(cell.bg);
+        let text_color = contrasting(cursor_color, &[text_color, cell.bg]);
+
+        (cursor_color, text_color)
+    }
+}
+
+/// First of the `candidates` which is distinguishable from `background`
+///
+/// Black or white is used when none of the candidates has enough contrast.
+fn contrasting(background: Rgb, candidates: &[Rgb]) -> Rgb {
+    candidates
+        .iter()
+        .cloned()
+        .find(|color| color.contrast(background) >= MIN_CURSOR_CONTRAST)
+        .unwrap_or_else(|| {
+            if background.contrast(color::BLACK) > background.contrast(color::WHITE) {
+                color::BLACK
+            } else {
+                color::WHITE
+            }
+        })
+}
+
 impl<'a> Iterator for RenderableCellsIter<'a> {
     type Item = RenderableCell;
 
@@ -381,7 +486,7 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
                 // Handle cursor
                 if let Some(cursor_key) = self.cursor_key.take() {
                     let cell = Indexed {
-                        inner: self.grid[self.cursor],
+                        inner: self.grid[&self.cursor],
                         column: self.cursor.col,
                         // Using `self.cursor.line` leads to inconsitent cursor position when
                         // scrolling. See https://github.com/jwilm/alacritty/issues/2570 for more
@@ -389,26 +494,36 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
                         line: self.inner.line(),
                     };
 
-                    let mut renderable_cell =
-                        RenderableCell::new(self.config, self.colors, cell, selected);
+                    let mut renderable_cell = RenderableCell::new(
+                        self.config,
+                        self.scheme,
+                        self.colors,
+                        &mut self.dim_colors,
+                        cell,
+                        selected,
+                        self.reverse_video,
+                    );
 
                     renderable_cell.inner = RenderableCellContent::Cursor(cursor_key);
-
-                    if let Some(color) = self.config.cursor_cursor_color() {
-                        renderable_cell.fg = RenderableCell::compute_bg_rgb(self.colors, color);
-                    }
+                    renderable_cell.fg = self.cursor_colors(&renderable_cell).0;
 
                     return Some(renderable_cell);
                 } else {
-                    let mut cell =
-                        RenderableCell::new(self.config, self.colors, self.inner.next()?, selected);
+                    let cell = self.inner.next()?;
+                    let mut cell = RenderableCell::new(
+                        self.config,
+                        self.scheme,
+                        self.colors,
+                        &mut self.dim_colors,
+                        cell,
+                        selected,
+                        self.reverse_video,
+                    );
 
                     if self.cursor_style == CursorStyle::Block {
-                        std::mem::swap(&mut cell.bg, &mut cell.fg);
-
-                        if let Some(color) = self.config.cursor_text_color() {
-                            cell.fg = color;
-                        }
+                        let (cursor_color, text_color) = self.cursor_colors(&cell);
+                        cell.fg = text_color;
+                        cell.bg = cursor_color;
                     }
 
                     return Some(cell);
@@ -430,7 +545,15 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
                 }
 
                 if !cell.is_empty() || selected {
-                    return Some(RenderableCell::new(self.config, self.colors, cell, selected));
+                    return Some(RenderableCell::new(
+                        self.config,
+                        self.scheme,
+                        self.colors,
+                        &mut self.dim_colors,
+                        cell,
+                        selected,
+                        self.reverse_video,
+                    ));
                 }
             }
         }
@@ -441,29 +564,38 @@ pub mod mode {
     use bitflags::bitflags;
 
     bitflags! {
-        pub struct TermMode: u16 {
-            const SHOW_CURSOR         = 0b00_0000_0000_0001;
-            const APP_CURSOR          = 0b00_0000_0000_0010;
-            const APP_KEYPAD          = 0b00_0000_0000_0100;
-            const MOUSE_REPORT_CLICK  = 0b00_0000_0000_1000;
-            const BRACKETED_PASTE     = 0b00_0000_0001_0000;
-            const SGR_MOUSE           = 0b00_0000_0010_0000;
-            const MOUSE_MOTION        = 0b00_0000_0100_0000;
-            const LINE_WRAP           = 0b00_0000_1000_0000;
-            const LINE_FEED_NEW_LINE  = 0b00_0001_0000_0000;
-            const ORIGIN              = 0b00_0010_0000_0000;
-            const INSERT              = 0b00_0100_0000_0000;
-            const FOCUS_IN_OUT        = 0b00_1000_0000_0000;
-            const ALT_SCREEN          = 0b01_0000_0000_0000;
-            const MOUSE_DRAG          = 0b10_0000_0000_0000;
-            const ANY                 = 0b11_1111_1111_1111;
+        pub struct TermMode: u32 {
+            const SHOW_CURSOR         = 0b000_0000_0000_0000_0001;
+            const APP_CURSOR          = 0b000_0000_0000_0000_0010;
+            const APP_KEYPAD          = 0b000_0000_0000_0000_0100;
+            const MOUSE_REPORT_CLICK  = 0b000_0000_0000_0000_1000;
+            const BRACKETED_PASTE     = 0b000_0000_0000_0001_0000;
+            const SGR_MOUSE           = 0b000_0000_0000_0010_0000;
+            const MOUSE_MOTION        = 0b000_0000_0000_0100_0000;
+            const LINE_WRAP           = 0b000_0000_0000_1000_0000;
+            const LINE_FEED_NEW_LINE  = 0b000_0000_0001_0000_0000;
+            const ORIGIN              = 0b000_0000_0010_0000_0000;
+            const INSERT              = 0b000_0000_0100_0000_0000;
+            const FOCUS_IN_OUT        = 0b000_0000_1000_0000_0000;
+            const ALT_SCREEN          = 0b000_0001_0000_0000_0000;
+            const MOUSE_DRAG          = 0b000_0010_0000_0000_0000;
+            const ALT_SEND_ESC        = 0b000_0100_0000_0000_0000;
+            const REVERSE_VIDEO       = 0b100_0000_0000_0000_0000;
+            /// Modes which the primary and the alternate screen keep for themselves
+            const SCREEN              = 0b000_0000_0010_1000_0000;
+            /// Modes of Alacritty's UI, these are never set by the terminal itself
+            const VI                  = 0b000_1000_0000_0000_0000;
+            const SEARCH              = 0b001_0000_0000_0000_0000;
+            const HINT                = 0b010_0000_0000_0000_0000;
+            const UI                  = 0b011_1000_0000_0000_0000;
+            const ANY                 = 0b111_1111_1111_1111_1111;
             const NONE                = 0;
         }
     }
 
     impl Default for TermMode {
         fn default() -> TermMode {
-            TermMode::SHOW_CURSOR | TermMode::LINE_WRAP
+            TermMode::SHOW_CURSOR | TermMode::LINE_WRAP | TermMode::ALT_SEND_ESC
         }
     }
 }
@@ -550,6 +682,15 @@ pub struct Cursor {
     charsets: Charsets,
 }
 
+/// Cursor stored by DECSC, each screen has its own
+#[derive(Default, Copy, Clone)]
+struct CursorSave {
+    cursor: Cursor,
+
+    /// Whether the cursor was waiting to wrap after writing to the last column
+    input_needs_wrap: bool,
+}
+
 pub struct VisualBell {
     /// Visual bell animation
     animation: VisualBellAnimation,
@@ -671,13 +812,27 @@ impl VisualBell {
     }
 }
 
+/// Change of the window opacity
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OpacityChange {
+    /// Add to the current opacity
+    Step(f32),
+
+    /// Use a specific opacity
+    Set(f32),
+
+    /// Go back to the configured opacity
+    Reset,
+}
+
 pub struct Term {
     /// The grid
     grid: Grid<Cell>,
 
     /// Tracks if the next call to input will need to first handle wrapping.
     /// This is true after the last column is set with the input function. Any function that
-    /// implicitly sets the line or column needs to set this to false to avoid wrapping twice.
+    /// implicitly sets the line or column, or edits the line at the cursor, needs to set this to
+    /// false to avoid wrapping twice. Only attribute changes and saving the cursor keep it.
     /// input_needs_wrap ensures that cursor.col is always valid for use into indexing into
     /// arrays. Without it we would have to sanitize cursor.col every time we used it.
     input_needs_wrap: bool,
@@ -687,8 +842,35 @@ pub struct Term {
     /// Would be nice to avoid the allocation...
     next_title: Option<String>,
 
+    /// Last title set by the application
+    title: Option<String>,
+
     /// Got a request to set the mouse cursor; it's buffered here until the next draw
-    next_mouse_cursor: Option<MouseCursor>,
+    next_mouse_cursor: Option<MouseCursorIcon>,
+
+    /// Mouse cursor shape set by the application, used instead of the automatic shape
+    app_mouse_cursor: Option<MouseCursorIcon>,
+
+    /// Focus reporting was enabled; the current focus state is sent on the next event processing
+    focus_report_requested: bool,
+
+    /// Got a request to capture a screenshot; it's buffered here until the next draw
+    screenshot_requested: bool,
+
+    /// Got a request to toggle the render timer; it's buffered here until the next draw
+    render_timer_toggled: bool,
+
+    /// Bytes read from the pty
+    pty_throughput: Throughput,
+
+    /// Oldest input which hasn't been drawn yet, for measuring the input latency
+    pending_input: PendingInput,
+
+    /// Color changes by escape sequences which haven't been drawn yet
+    palette_burst: PaletteBurst,
+
+    /// Escape sequences the parser couldn't handle
+    unhandled_stats: UnhandledStats,
 
     /// Alternate grid
     alt_grid: Grid<Cell>,
@@ -713,22 +895,59 @@ pub struct Term {
     scroll_region: Range<Line>,
 
     /// Font size
+    #[cfg(feature = "gui")]
     pub font_size: Size,
+    #[cfg(feature = "gui")]
     original_font_size: Size,
 
+    /// Change of the font size with the font size actions
+    #[cfg(feature = "gui")]
+    font_size_step: Size,
+
+    /// Font size has been changed since it was last set from the config
+    #[cfg(feature = "gui")]
+    font_size_modified: bool,
+
+    /// Font families which can be cycled through
+    #[cfg(feature = "gui")]
+    font_families: Vec<String>,
+
+    /// Family of the normal font in the config
+    #[cfg(feature = "gui")]
+    configured_font_family: String,
+
+    /// Index of the font family used instead of the configured one
+    #[cfg(feature = "gui")]
+    font_family: Option<usize>,
+
+    /// Time at which the font family change notice is removed again
+    #[cfg(feature = "gui")]
+    font_family_notice_until: Option<Instant>,
+
     /// Size
     size_info: SizeInfo,
 
     pub dirty: bool,
 
+    /// Changed whenever the rendered content of the terminal might have changed
+    generation: u64,
+
     pub visual_bell: VisualBell,
     pub next_is_urgent: Option<bool>,
 
+    /// Window opacity change requested by an escape sequence
+    pub next_window_opacity: Option<OpacityChange>,
+
     /// Saved cursor from main grid
-    cursor_save: Cursor,
+    cursor_save: CursorSave,
 
     /// Saved cursor from alt grid
-    cursor_save_alt: Cursor,
+    cursor_save_alt: CursorSave,
+
+    /// Modes in `TermMode::SCREEN` of the screen which isn't shown
+    ///
+    /// Like in xterm, all other modes like mouse reporting and bracketed paste are shared.
+    inactive_screen_mode: TermMode,
 
     semantic_escape_chars: String,
 
@@ -741,6 +960,9 @@ pub struct Term {
     /// Original colors from config
     original_colors: color::List,
 
+    /// Color schemes of the config and the one which is currently used
+    color_schemes: color::Schemes,
+
     /// Current style of the cursor
     cursor_style: Option<CursorStyle>,
 
@@ -750,24 +972,55 @@ pub struct Term {
     /// Whether to permit updating the terminal title
     dynamic_title: bool,
 
+    /// Whether to permit changing the window opacity
+    opacity_escape: bool,
+
+    /// Maximum size of clipboard content set by escape sequences, in bytes
+    max_clipboard_escape_size: usize,
+
+    /// Whether invalid UTF-8 in clipboard content set by escape sequences is replaced
+    lossy_clipboard_escape: bool,
+
     /// Number of spaces in one tab
     tabspaces: usize,
 
+    /// Columns occupied by ambiguous width characters written from now on
+    ambiguous_width: AmbiguousWidth,
+
+    /// Terminator of replies to queries
+    reply_terminator: ReplyTerminator,
+
     /// Automatically scroll to bottom when new lines are added
     auto_scroll: bool,
 
+    /// Use the background of the cells at the edges of the screen for the padding
+    extend_background: bool,
+
+    /// Whether to answer queries for the scrollback status
+    status_escape: bool,
+
     /// Buffer to store messages for the message bar
     message_buffer: MessageBuffer,
 
     /// Hint that Alacritty should be closed
     should_exit: bool,
 
+    /// Exit status of the child process, once it exited
+    exit_status: Option<ExitStatus>,
+
     /// Clipboard access coupled to the active window
     clipboard: Clipboard,
+
+    /// Working directory last reported by the shell with OSC 7
+    working_directory: Option<PathBuf>,
+
+    /// Recording of pty output and resizes for ref tests
+    recorder: Option<Recorder<Box<dyn io::Write + Send>>>,
 }
 
 /// Terminal size info
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SerializedSizeInfo")]
 pub struct SizeInfo {
     /// Terminal window width
     pub width: f32,
@@ -781,42 +1034,59 @@ pub struct SizeInfo {
     /// Height of individual cell
     pub cell_height: f32,
 
-    /// Horizontal window padding
-    pub padding_x: f32,
+    /// Window padding at the left edge
+    pub padding_left: f32,
+
+    /// Window padding at the right edge
+    pub padding_right: f32,
+
+    /// Window padding at the top edge
+    pub padding_top: f32,
 
-    /// Horizontal window padding
-    pub padding_y: f32,
+    /// Window padding at the bottom edge
+    pub padding_bottom: f32,
 
     /// DPI factor of the current window
-    #[serde(default)]
     pub dpr: f64,
 }
 
 impl SizeInfo {
     #[inline]
     pub fn lines(&self) -> Line {
-        Line(((self.height - 2. * self.padding_y) / self.cell_height) as usize)
+        Line((self.grid_height() / self.cell_height) as usize)
     }
 
     #[inline]
     pub fn cols(&self) -> Column {
-        Column(((self.width - 2. * self.padding_x) / self.cell_width) as usize)
+        Column((self.grid_width() / self.cell_width) as usize)
+    }
+
+    /// Width of the window without padding
+    #[inline]
+    pub fn grid_width(&self) -> f32 {
+        self.width - self.padding_left - self.padding_right
+    }
+
+    /// Height of the window without padding
+    #[inline]
+    pub fn grid_height(&self) -> f32 {
+        self.height - self.padding_top - self.padding_bottom
     }
 
     pub fn contains_point(&self, x: usize, y: usize, include_padding: bool) -> bool {
         if include_padding {
             x < self.width as usize && y < self.height as usize
         } else {
-            x < (self.width - self.padding_x) as usize
-                && x >= self.padding_x as usize
-                && y < (self.height - self.padding_y) as usize
-                && y >= self.padding_y as usize
+            x < (self.width - self.padding_right) as usize
+                && x >= self.padding_left as usize
+                && y < (self.height - self.padding_bottom) as usize
+                && y >= self.padding_top as usize
         }
     }
 
     pub fn pixels_to_coords(&self, x: usize, y: usize) -> Point {
-        let col = Column(x.saturating_sub(self.padding_x as usize) / (self.cell_width as usize));
-        let line = Line(y.saturating_sub(self.padding_y as usize) / (self.cell_height as usize));
+        let col = Column(x.saturating_sub(self.padding_left as usize) / (self.cell_width as usize));
+        let line = Line(y.saturating_sub(self.padding_top as usize) / (self.cell_height as usize));
 
         Point {
             line: min(line, Line(self.lines().saturating_sub(1))),
@@ -825,6 +1095,66 @@ impl SizeInfo {
     }
 }
 
+/// Serialized size info
+///
+/// Padding was symmetric in older ref tests, so `padding_x` and `padding_y`
+/// are accepted for both opposing edges.
+#[derive(Deserialize)]
+struct SerializedSizeInfo {
+    width: f32,
+    height: f32,
+    cell_width: f32,
+    cell_height: f32,
+    #[serde(default)]
+    padding_x: f32,
+    #[serde(default)]
+    padding_y: f32,
+    padding_left: Option<f32>,
+    padding_right: Option<f32>,
+    padding_top: Option<f32>,
+    padding_bottom: Option<f32>,
+    #[serde(default)]
+    dpr: f64,
+}
+
+impl From<SerializedSizeInfo> for SizeInfo {
+    fn from(size: SerializedSizeInfo) -> Self {
+        SizeInfo {
+            width: size.width,
+            height: size.height,
+            cell_width: size.cell_width,
+            cell_height: size.cell_height,
+            padding_left: size.padding_left.unwrap_or(size.padding_x),
+            padding_right: size.padding_right.unwrap_or(size.padding_x),
+            padding_top: size.padding_top.unwrap_or(size.padding_y),
+            padding_bottom: size.padding_bottom.unwrap_or(size.padding_y),
+            dpr: size.dpr,
+        }
+    }
+}
+
+/// Cursor shape drawn in the window
+///
+/// Each source overrides the ones before it: the config default, the shape set by the
+/// application with DECSCUSR, the shape for unfocused windows and hiding the cursor.
+pub fn resolve_cursor_style(
+    default: CursorStyle,
+    app: Option<CursorStyle>,
+    unfocused: Option<CursorStyle>,
+    visible: bool,
+) -> CursorStyle {
+    if !visible {
+        return CursorStyle::Hidden;
+    }
+
+    unfocused.or(app).unwrap_or(default)
+}
+
+/// Memory limit of the scrollback history in bytes
+fn history_memory(config: &Config) -> Option<usize> {
+    config.scrolling.max_memory().map(|megabytes| (megabytes as usize).saturating_mul(1024 * 1024))
+}
+
 impl Term {
     pub fn selection(&self) -> &Option<Selection> {
         &self.grid.selection
@@ -839,19 +1169,193 @@ impl Term {
         self.next_title.take()
     }
 
+    /// Last title set by the application
+    #[inline]
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_ref().map(String::as_str)
+    }
+
     #[inline]
     pub fn scroll_display(&mut self, scroll: Scroll) {
         self.grid.scroll_display(scroll);
         self.reset_url_highlight();
         self.reset_mouse_cursor();
-        self.dirty = true;
+        self.content_changed();
+    }
+
+    /// Number of lines in the scrollback history of the current screen
+    #[inline]
+    pub fn history_lines(&self) -> usize {
+        self.grid.scroll_limit()
+    }
+
+    /// Number of lines the view is scrolled up into the history
+    #[inline]
+    pub fn display_offset(&self) -> usize {
+        self.grid.display_offset()
     }
 
     #[inline]
-    pub fn get_next_mouse_cursor(&mut self) -> Option<MouseCursor> {
+    pub fn get_next_mouse_cursor(&mut self) -> Option<MouseCursorIcon> {
         self.next_mouse_cursor.take()
     }
 
+    /// Show a mouse cursor on the next draw
+    #[inline]
+    pub fn set_next_mouse_cursor(&mut self, cursor: MouseCursorIcon) {
+        self.next_mouse_cursor = Some(cursor);
+        self.dirty = true;
+    }
+
+    /// Mouse cursor shape set by the application with OSC 22
+    #[inline]
+    pub fn app_mouse_cursor(&self) -> Option<MouseCursorIcon> {
+        self.app_mouse_cursor
+    }
+
+    /// Check if focus changes are reported to the application
+    #[inline]
+    pub fn reports_focus(&self) -> bool {
+        self.mode.contains(TermMode::FOCUS_IN_OUT)
+    }
+
+    /// Check if the current focus state has to be reported since focus reporting was enabled
+    #[inline]
+    pub fn take_focus_report_request(&mut self) -> bool {
+        let requested = self.focus_report_requested;
+        self.focus_report_requested = false;
+        requested && self.reports_focus()
+    }
+
+    /// Capture the content of the window after the next draw
+    #[inline]
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+        self.dirty = true;
+    }
+
+    #[inline]
+    pub fn take_screenshot_request(&mut self) -> bool {
+        mem::replace(&mut self.screenshot_requested, false)
+    }
+
+    /// Show or hide the render timer with the next draw
+    #[inline]
+    pub fn toggle_render_timer(&mut self) {
+        self.render_timer_toggled = !self.render_timer_toggled;
+        self.dirty = true;
+    }
+
+    #[inline]
+    pub fn take_render_timer_toggle(&mut self) -> bool {
+        mem::replace(&mut self.render_timer_toggled, false)
+    }
+
+    /// Account for bytes read from the pty
+    ///
+    /// Escape sequences are not tracked individually, so this also marks the content as changed
+    /// for everything the parser has done with these bytes.
+    #[inline]
+    pub fn record_pty_read(&mut self, bytes: &[u8]) {
+        self.pty_throughput.record(bytes.len(), Instant::now());
+        self.pending_input.output();
+        self.generation = self.generation.wrapping_add(1);
+        self.record(|recorder| recorder.output(bytes));
+    }
+
+    /// Remember when a keypress or pty read happened, to measure the latency until it's drawn
+    #[inline]
+    pub fn record_input(&mut self, timestamp: Instant) {
+        self.pending_input.record(timestamp);
+    }
+
+    /// Time of the oldest input presented by the next frame
+    #[inline]
+    pub fn take_input_timestamp(&mut self) -> Option<Instant> {
+        self.pending_input.take()
+    }
+
+    /// Time to wait at `now` before drawing, until a burst of color changes is over
+    #[inline]
+    pub fn palette_delay(&self, now: Instant) -> Option<Duration> {
+        self.palette_burst.delay(now)
+    }
+
+    /// Forget the color changes which are drawn by the next frame
+    #[inline]
+    pub fn clear_palette_burst(&mut self) {
+        self.palette_burst.clear();
+    }
+
+    /// Record all pty output and resizes from now on, starting with the current size
+    pub fn start_recording(&mut self, recorder: Recorder<Box<dyn io::Write + Send>>) {
+        self.recorder = Some(recorder);
+
+        let (cols, lines) = (self.grid.num_cols(), self.grid.num_lines());
+        self.record(|recorder| recorder.resize(cols, lines));
+    }
+
+    /// Write the state which isn't part of the grid and stop recording
+    pub fn finish_recording(&mut self) {
+        let state = recording::State::from(&*self);
+        self.record(|recorder| recorder.state(state));
+        self.recorder = None;
+    }
+
+    fn record<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Recorder<Box<dyn io::Write + Send>>) -> io::Result<()>,
+    {
+        let result = match self.recorder.as_mut() {
+            Some(recorder) => f(recorder),
+            None => return,
+        };
+
+        if let Err(err) = result {
+            error!("Unable to write recording: {}", err);
+            self.recorder = None;
+        }
+    }
+
+    /// Bytes per second read from the pty
+    #[inline]
+    pub fn pty_throughput(&self) -> f64 {
+        self.pty_throughput.bytes_per_second(Instant::now())
+    }
+
+    #[inline]
+    pub fn set_unhandled_stats(&mut self, stats: UnhandledStats) {
+        self.unhandled_stats = stats;
+    }
+
+    /// Escape sequences which have not been handled by the parser
+    #[inline]
+    pub fn unhandled_stats(&self) -> UnhandledStats {
+        self.unhandled_stats
+    }
+
+    /// Counter which changes with every modification of the rendered content
+    ///
+    /// The cursor, selection, URL highlight, message bar and visual bell are not included.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Draw the next frame even if the terminal state hasn't changed
+    ///
+    /// This is necessary when the window system has discarded the window content.
+    #[inline]
+    pub fn request_redraw(&mut self) {
+        self.content_changed();
+    }
+
+    #[inline]
+    fn content_changed(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.dirty = true;
+    }
+
     pub fn new(
         config: &Config,
         size: SizeInfo,
@@ -862,7 +1366,8 @@ impl Term {
         let num_lines = size.lines();
 
         let history_size = config.scrolling.history() as usize;
-        let grid = Grid::new(num_lines, num_cols, history_size, Cell::default());
+        let mut grid = Grid::new(num_lines, num_cols, history_size, Cell::default());
+        grid.update_history_memory(history_memory(config), &Cell::default());
         let alt = Grid::new(num_lines, num_cols, 0 /* scroll history */, Cell::default());
 
         let tabspaces = config.tabspaces();
@@ -870,24 +1375,52 @@ impl Term {
 
         let scroll_region = Line(0)..grid.num_lines();
 
-        let colors = color::List::from(&config.colors);
+        let color_schemes = color::Schemes::new(config, None);
+        let colors =
+            color_schemes.list().cloned().unwrap_or_else(|| color::List::from(&config.colors));
 
         Term {
             next_title: None,
+            title: None,
             next_mouse_cursor: None,
+            app_mouse_cursor: None,
+            focus_report_requested: false,
+            screenshot_requested: false,
+            render_timer_toggled: false,
+            pty_throughput: Throughput::new(),
+            pending_input: PendingInput::default(),
+            palette_burst: PaletteBurst::default(),
+            unhandled_stats: Default::default(),
             dirty: false,
+            generation: 0,
             visual_bell: VisualBell::new(config),
             next_is_urgent: None,
+            next_window_opacity: None,
             input_needs_wrap: false,
             grid,
             alt_grid: alt,
             alt: false,
+            #[cfg(feature = "gui")]
             font_size: config.font.size,
+            #[cfg(feature = "gui")]
             original_font_size: config.font.size,
+            #[cfg(feature = "gui")]
+            font_size_step: config.font.size_step(),
+            #[cfg(feature = "gui")]
+            font_size_modified: false,
+            #[cfg(feature = "gui")]
+            font_families: config.font.families().to_vec(),
+            #[cfg(feature = "gui")]
+            configured_font_family: config.font.normal().family.clone(),
+            #[cfg(feature = "gui")]
+            font_family: None,
+            #[cfg(feature = "gui")]
+            font_family_notice_until: None,
             active_charset: Default::default(),
             cursor: Default::default(),
             cursor_save: Default::default(),
             cursor_save_alt: Default::default(),
+            inactive_screen_mode: TermMode::default() & TermMode::SCREEN,
             tabs,
             mode: Default::default(),
             scroll_region,
@@ -895,161 +1428,286 @@ impl Term {
             colors,
             color_modified: [false; color::COUNT],
             original_colors: colors,
+            color_schemes,
             semantic_escape_chars: config.selection.semantic_escape_chars().to_owned(),
             cursor_style: None,
             default_cursor_style: config.cursor.style,
             dynamic_title: config.dynamic_title(),
+            opacity_escape: config.window.opacity_escape,
+            max_clipboard_escape_size: config.selection.max_clipboard_escape_size(),
+            lossy_clipboard_escape: config.selection.lossy_clipboard_escape,
             tabspaces,
+            ambiguous_width: config.terminal.ambiguous_width,
+            reply_terminator: config.terminal.reply_terminator,
             auto_scroll: config.scrolling.auto_scroll,
+            extend_background: config.window.extend_background,
+            status_escape: config.scrolling.status_escape,
             message_buffer,
             should_exit: false,
+            exit_status: None,
             clipboard,
+            working_directory: None,
+            recorder: None,
         }
     }
 
+    #[cfg(feature = "gui")]
     pub fn change_font_size(&mut self, delta: f32) {
-        // Saturating addition with minimum font size FONT_SIZE_STEP
         let new_size = self.font_size + Size::new(delta);
-        self.font_size = max(new_size, Size::new(FONT_SIZE_STEP));
-        self.dirty = true;
+        self.font_size = min(max(new_size, Size::new(MIN_FONT_SIZE)), Size::new(MAX_FONT_SIZE));
+        self.font_size_modified = true;
+        self.content_changed();
+    }
+
+    /// Change of the font size with the font size actions, in points
+    #[cfg(feature = "gui")]
+    #[inline]
+    pub fn font_size_step(&self) -> f32 {
+        self.font_size_step.as_f32_pts()
     }
 
+    /// Go back to the font size of the config, including changes of live config reloads
+    #[cfg(feature = "gui")]
     pub fn reset_font_size(&mut self) {
         self.font_size = self.original_font_size;
-        self.dirty = true;
+        self.font_size_modified = false;
+        self.content_changed();
     }
 
     pub fn update_config(&mut self, config: &Config) {
         self.semantic_escape_chars = config.selection.semantic_escape_chars().to_owned();
-        self.original_colors.fill_named(&config.colors);
-        self.original_colors.fill_cube(&config.colors);
-        self.original_colors.fill_gray_ramp(&config.colors);
-        for i in 0..color::COUNT {
-            if !self.color_modified[i] {
-                self.colors[i] = self.original_colors[i];
-            }
-        }
+        self.update_colors(config);
         self.visual_bell.update_config(config);
         self.default_cursor_style = config.cursor.style;
         self.dynamic_title = config.dynamic_title();
+        self.opacity_escape = config.window.opacity_escape;
+        self.max_clipboard_escape_size = config.selection.max_clipboard_escape_size();
+        self.lossy_clipboard_escape = config.selection.lossy_clipboard_escape;
+        self.ambiguous_width = config.terminal.ambiguous_width;
+        self.reply_terminator = config.terminal.reply_terminator;
         self.auto_scroll = config.scrolling.auto_scroll;
+        self.extend_background = config.window.extend_background;
+        self.status_escape = config.scrolling.status_escape;
         self.grid.update_history(config.scrolling.history() as usize, &self.cursor.template);
+        self.grid.update_history_memory(history_memory(config), &self.cursor.template);
+        self.content_changed();
     }
 
-    #[inline]
-    pub fn needs_draw(&self) -> bool {
-        self.dirty
-    }
-
-    pub fn selection_to_string(&self) -> Option<String> {
-        trait Append {
-            fn append(
-                &mut self,
-                append_newline: bool,
-                grid: &Grid<Cell>,
-                tabs: &TabStops,
-                line: usize,
-                cols: Range<Column>,
-            );
-        }
-
-        impl Append for String {
-            fn append(
-                &mut self,
-                append_newline: bool,
-                grid: &Grid<Cell>,
-                tabs: &TabStops,
-                mut line: usize,
-                cols: Range<Column>,
-            ) {
-                // Select until last line still within the buffer
-                line = min(line, grid.len() - 1);
-
-                let grid_line = &grid[line];
-                let line_length = grid_line.line_length();
-                let line_end = min(line_length, cols.end + 1);
-
-                if cols.start < line_end {
-                    let mut tab_mode = false;
-
-                    for col in IndexRange::from(cols.start..line_end) {
-                        let cell = grid_line[col];
-
-                        if tab_mode {
-                            // Skip over whitespace until next tab-stop once a tab was found
-                            if tabs[col] {
-                                tab_mode = false;
-                            } else if cell.c == ' ' {
-                                continue;
-                            }
-                        }
-
-                        if !cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
-                            self.push(cell.c);
-                            for c in (&cell.chars()[1..]).iter().filter(|c| **c != ' ') {
-                                self.push(*c);
-                            }
-                        }
-
-                        if cell.c == '\t' {
-                            tab_mode = true;
-                        }
-                    }
-                }
+    /// Replace all colors which haven't been changed by escape sequences
+    ///
+    /// The active color scheme is kept if it still exists.
+    pub fn update_colors(&mut self, config: &Config) {
+        let color_schemes = color::Schemes::new(config, self.color_schemes.active());
+        self.color_schemes = color_schemes;
+
+        match self.color_schemes.list() {
+            Some(list) => self.original_colors = *list,
+            None => {
+                self.original_colors.fill_named(&config.colors);
+                self.original_colors.fill_cube(&config.colors);
+                self.original_colors.fill_gray_ramp(&config.colors);
+            },
+        }
 
-                if append_newline
-                    || (cols.end >= grid.num_cols() - 1
-                        && (line_end == Column(0)
-                            || !grid[line][line_end - 1].flags.contains(cell::Flags::WRAPLINE)))
-                {
-                    self.push('\n');
-                }
+        for i in 0..color::COUNT {
+            if !self.color_modified[i] {
+                self.colors[i] = self.original_colors[i];
             }
         }
+        self.content_changed();
+    }
 
-        let selection = self.grid.selection.clone()?;
-        let Span { mut start, mut end, is_block } = selection.to_span(self)?;
-
-        let mut res = String::new();
+    /// Name of the color scheme which is currently used
+    #[inline]
+    pub fn color_scheme(&self) -> Option<&str> {
+        self.color_schemes.active()
+    }
 
-        if start > end {
-            ::std::mem::swap(&mut start, &mut end);
+    /// Switch to the color scheme `name`
+    pub fn set_color_scheme(&mut self, name: &str) {
+        if self.color_schemes.select(name) {
+            self.apply_color_scheme();
+        } else {
+            warn!("Unknown color scheme {:?}", name);
         }
+    }
 
-        let line_count = end.line - start.line;
-
-        // Setup block selection start/end point limits
-        let (limit_start, limit_end) =
-            if is_block { (end.col, start.col) } else { (Column(0), self.grid.num_cols()) };
+    /// Switch to the next color scheme of the config
+    pub fn cycle_color_scheme(&mut self) {
+        if self.color_schemes.cycle() {
+            self.apply_color_scheme();
+        }
+    }
 
-        match line_count {
-            // Selection within single line
-            0 => {
-                res.append(false, &self.grid, &self.tabs, start.line, start.col..end.col);
+    fn apply_color_scheme(&mut self) {
+        let name = match (self.color_schemes.active(), self.color_schemes.list()) {
+            (Some(name), Some(list)) => {
+                self.original_colors = *list;
+                name.to_owned()
             },
+            _ => return,
+        };
 
-            // Selection ends on line following start
-            1 => {
-                // Ending line
-                res.append(is_block, &self.grid, &self.tabs, end.line, end.col..limit_end);
+        // Colors changed by escape sequences are dropped, like on a terminal reset
+        self.colors = self.original_colors;
+        self.color_modified = [false; color::COUNT];
 
-                // Starting line
-                res.append(false, &self.grid, &self.tabs, start.line, limit_start..start.col);
-            },
+        // Only show the most recent scheme change
+        let mut message = Message::new(format!("Color scheme: {}", name), color::YELLOW);
+        message.set_topic(COLOR_SCHEME_TOPIC.into());
+        self.message_buffer.remove_topic(COLOR_SCHEME_TOPIC);
+        let _ = self.message_buffer.tx().send(message);
 
-            // Multi line selection
-            _ => {
-                // Ending line
-                res.append(is_block, &self.grid, &self.tabs, end.line, end.col..limit_end);
+        self.content_changed();
+    }
 
-                let middle_range = (start.line + 1)..(end.line);
-                for line in middle_range.rev() {
-                    res.append(is_block, &self.grid, &self.tabs, line, limit_start..limit_end);
-                }
+    /// Apply a new font configuration
+    ///
+    /// A new font size in the config is only used right away if the size hasn't been changed
+    /// with the font size actions, otherwise it's used once the size is reset.
+    #[cfg(feature = "gui")]
+    pub fn update_font(&mut self, config: &Config) {
+        self.original_font_size = config.font.size;
+        if !self.font_size_modified {
+            self.font_size = config.font.size;
+        }
+        self.font_size_step = config.font.size_step();
+        self.configured_font_family = config.font.normal().family.clone();
 
-                // Starting line
-                res.append(false, &self.grid, &self.tabs, start.line, limit_start..start.col);
-            },
+        // Indices into the old list of families are meaningless for a changed list
+        if self.font_families[..] != *config.font.families() {
+            self.font_families = config.font.families().to_vec();
+            self.font_family = None;
+        }
+
+        self.content_changed();
+    }
+
+    /// Font family which is used instead of the family of the config
+    #[cfg(feature = "gui")]
+    #[inline]
+    pub fn font_family(&self) -> Option<&str> {
+        self.font_family.map(|index| self.font_families[index].as_str())
+    }
+
+    /// Switch to the next font family of the config
+    ///
+    /// Starts after the configured family when it is part of the list, otherwise with the
+    /// first family.
+    #[cfg(feature = "gui")]
+    pub fn cycle_font_family(&mut self, now: Instant) {
+        if self.font_families.is_empty() {
+            return;
+        }
+
+        let current = self.font_family.or_else(|| {
+            self.font_families.iter().position(|family| *family == self.configured_font_family)
+        });
+        let next = current.map_or(0, |index| (index + 1) % self.font_families.len());
+        self.font_family = Some(next);
+
+        let name = &self.font_families[next];
+        let mut message = Message::new(format!("Font: {}", name), color::YELLOW);
+        message.set_topic(FONT_FAMILY_TOPIC.into());
+        self.message_buffer.remove_topic(FONT_FAMILY_TOPIC);
+        let _ = self.message_buffer.tx().send(message);
+        self.font_family_notice_until = Some(now + FONT_FAMILY_NOTICE_DURATION);
+
+        self.content_changed();
+    }
+
+    /// Remove the font family change notice once it has been shown long enough
+    #[cfg(feature = "gui")]
+    pub fn expire_font_family_notice(&mut self, now: Instant) {
+        match self.font_family_notice_until {
+            Some(until) if until <= now => (),
+            _ => return,
+        }
+
+        self.font_family_notice_until = None;
+        self.message_buffer.remove_topic(FONT_FAMILY_TOPIC);
+        self.dirty = true;
+    }
+
+    #[inline]
+    pub fn needs_draw(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn selection_to_string(&self) -> Option<String> {
+        /// Append the text of the selected columns of a row
+        fn append(res: &mut String, row: &Row<Cell>, tabs: &TabStops, cols: Range<Column>) {
+            let line_end = min(row.line_length(), cols.end + 1);
+            if cols.start >= line_end {
+                return;
+            }
+
+            let mut tab_mode = false;
+            for col in IndexRange::from(cols.start..line_end) {
+                let cell = row[col];
+
+                if tab_mode {
+                    // Skip over whitespace until next tab-stop once a tab was found
+                    if tabs[col] {
+                        tab_mode = false;
+                    } else if cell.c == ' ' {
+                        continue;
+                    }
+                }
+
+                if !cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                    res.push(cell.c);
+                    for c in (&cell.chars()[1..]).iter().filter(|c| **c != ' ') {
+                        res.push(*c);
+                    }
+                }
+
+                if cell.c == '\t' {
+                    tab_mode = true;
+                }
+            }
+        }
+
+        let selection = self.grid.selection.clone()?;
+        let Span { mut start, mut end, is_block } = selection.to_span(self)?;
+
+        if start > end {
+            ::std::mem::swap(&mut start, &mut end);
+        }
+
+        // Setup block selection start/end point limits
+        let (limit_start, limit_end) =
+            if is_block { (end.col, start.col) } else { (Column(0), self.grid.num_cols()) };
+        let last_col = self.grid.num_cols() - 1;
+
+        let mut res = String::new();
+
+        // Select until last line still within the buffer
+        let mut line = min(end.line, self.grid.len() - 1);
+        while line >= start.line {
+            let logical = self.grid.line_at(Point::new(line, Column(0)));
+            let bottom = max(logical.end().line, start.line);
+
+            for row in (bottom..=line).rev() {
+                let cols = match (row == end.line, row == start.line) {
+                    (true, true) => start.col..end.col,
+                    (true, false) => end.col..limit_end,
+                    (false, true) => limit_start..start.col,
+                    (false, false) => limit_start..limit_end,
+                };
+                append(&mut res, &self.grid[row], &self.tabs, cols.clone());
+
+                // Rows are only joined when the selection reaches the wrap into the next one
+                let line_break = cols.end >= last_col && row == logical.end().line;
+                if (is_block && row != start.line) || line_break {
+                    res.push('\n');
+                }
+            }
+
+            if bottom == 0 {
+                break;
+            }
+            line = bottom - 1;
         }
 
         Some(res)
@@ -1089,6 +1747,7 @@ impl Term {
     /// Mutable access for swapping out the grid during tests
     #[cfg(test)]
     pub fn grid_mut(&mut self) -> &mut Grid<Cell> {
+        self.generation = self.generation.wrapping_add(1);
         &mut self.grid
     }
 
@@ -1104,13 +1763,42 @@ impl Term {
     ) -> RenderableCellsIter<'_> {
         let selection = self.grid.selection.as_ref().and_then(|s| s.to_span(self));
 
-        let cursor = if window_focused || !config.cursor.unfocused_hollow() {
-            self.cursor_style.unwrap_or(self.default_cursor_style)
+        let cursor = self.rendered_cursor_style(config, window_focused);
+
+        RenderableCellsIter::new(&self, config, selection, cursor)
+    }
+
+    /// Cursor shape drawn in the window, after applying all overrides
+    pub fn rendered_cursor_style(&self, config: &Config, window_focused: bool) -> CursorStyle {
+        let unfocused = if window_focused || !config.cursor.unfocused_hollow() {
+            None
         } else {
-            CursorStyle::HollowBlock
+            Some(CursorStyle::HollowBlock)
         };
 
-        RenderableCellsIter::new(&self, config, selection, cursor)
+        resolve_cursor_style(
+            self.default_cursor_style,
+            self.cursor_style,
+            unfocused,
+            self.mode.contains(TermMode::SHOW_CURSOR),
+        )
+    }
+
+    /// Position of the drawn cursor in the viewport
+    ///
+    /// This is `None` while the application hides the cursor, or when it's scrolled out of view.
+    pub fn visible_cursor(&self) -> Option<Point> {
+        if !self.mode.contains(TermMode::SHOW_CURSOR) || self.cursor_style() == CursorStyle::Hidden
+        {
+            return None;
+        }
+
+        let line = self.cursor.point.line + self.grid.display_offset();
+        if line < self.grid.num_lines() {
+            Some(Point::new(line, self.cursor.point.col))
+        } else {
+            None
+        }
     }
 
     /// Resize terminal to new dimensions
@@ -1118,9 +1806,9 @@ impl Term {
         debug!("Resizing terminal");
 
         // Bounds check; lots of math assumes width and height are > 0
-        if size.width as usize <= 2 * self.size_info.padding_x as usize
-            || size.height as usize <= 2 * self.size_info.padding_y as usize
-        {
+        let padding_x = self.size_info.padding_left + self.size_info.padding_right;
+        let padding_y = self.size_info.padding_top + self.size_info.padding_bottom;
+        if size.width as usize <= padding_x as usize || size.height as usize <= padding_y as usize {
             return;
         }
 
@@ -1134,6 +1822,7 @@ impl Term {
         }
 
         self.size_info = *size;
+        self.content_changed();
 
         if old_cols == num_cols && old_lines == num_lines {
             debug!("Term::resize dimensions unchanged");
@@ -1160,17 +1849,19 @@ impl Term {
             self.grid.scroll_up(&(Line(0)..old_lines), lines, &self.cursor.template);
         }
 
-        // Scroll up alt grid as well
-        if self.cursor_save_alt.point.line >= num_lines {
-            let lines = self.cursor_save_alt.point.line - num_lines + 1;
-            self.alt_grid.scroll_up(&(Line(0)..old_lines), lines, &self.cursor_save_alt.template);
+        // Scroll up alt grid as well, its cursor is saved while it isn't shown
+        let alt_cursor =
+            if self.alt { &self.cursor_save.cursor } else { &self.cursor_save_alt.cursor };
+        if alt_cursor.point.line >= num_lines {
+            let lines = alt_cursor.point.line - num_lines + 1;
+            self.alt_grid.scroll_up(&(Line(0)..old_lines), lines, &alt_cursor.template);
         }
 
         // Move prompt down when growing if scrollback lines are available
         if num_lines > old_lines {
             if self.mode.contains(TermMode::ALT_SCREEN) {
                 let growage = min(num_lines - old_lines, Line(self.alt_grid.scroll_limit()));
-                self.cursor_save.point.line += growage;
+                self.cursor_save.cursor.point.line += growage;
             } else {
                 let growage = min(num_lines - old_lines, Line(self.grid.scroll_limit()));
                 self.cursor.point.line += growage;
@@ -1181,8 +1872,11 @@ impl Term {
 
         // Resize grids to new size
         let is_alt = self.mode.contains(TermMode::ALT_SCREEN);
-        let alt_cursor_point =
-            if is_alt { &mut self.cursor_save.point } else { &mut self.cursor_save_alt.point };
+        let alt_cursor_point = if is_alt {
+            &mut self.cursor_save.cursor.point
+        } else {
+            &mut self.cursor_save_alt.cursor.point
+        };
         self.grid.resize(!is_alt, num_lines, num_cols, &mut self.cursor.point, &Cell::default());
         self.alt_grid.resize(is_alt, num_lines, num_cols, alt_cursor_point, &Cell::default());
 
@@ -1192,13 +1886,16 @@ impl Term {
         // Ensure cursors are in-bounds.
         self.cursor.point.col = min(self.cursor.point.col, num_cols - 1);
         self.cursor.point.line = min(self.cursor.point.line, num_lines - 1);
-        self.cursor_save.point.col = min(self.cursor_save.point.col, num_cols - 1);
-        self.cursor_save.point.line = min(self.cursor_save.point.line, num_lines - 1);
-        self.cursor_save_alt.point.col = min(self.cursor_save_alt.point.col, num_cols - 1);
-        self.cursor_save_alt.point.line = min(self.cursor_save_alt.point.line, num_lines - 1);
+        for save in &mut [&mut self.cursor_save, &mut self.cursor_save_alt] {
+            save.cursor.point.col = min(save.cursor.point.col, num_cols - 1);
+            save.cursor.point.line = min(save.cursor.point.line, num_lines - 1);
+        }
 
         // Recreate tabs list
         self.tabs = TabStops::new(self.grid.num_cols(), self.tabspaces);
+
+        let (cols, lines) = (self.grid.num_cols(), self.grid.num_lines());
+        self.record(|recorder| recorder.resize(cols, lines));
     }
 
     #[inline]
@@ -1206,6 +1903,12 @@ impl Term {
         &self.size_info
     }
 
+    /// Colors used for rendering
+    #[inline]
+    pub fn colors(&self) -> &color::List {
+        &self.colors
+    }
+
     #[inline]
     pub fn mode(&self) -> &TermMode {
         &self.mode
@@ -1216,6 +1919,12 @@ impl Term {
         &self.cursor
     }
 
+    /// Cursor shape requested by escape sequences or the config
+    #[inline]
+    pub fn cursor_style(&self) -> CursorStyle {
+        self.cursor_style.unwrap_or(self.default_cursor_style)
+    }
+
     pub fn swap_alt(&mut self) {
         if self.alt {
             let template = &self.cursor.template;
@@ -1224,6 +1933,13 @@ impl Term {
 
         self.alt = !self.alt;
         ::std::mem::swap(&mut self.grid, &mut self.alt_grid);
+
+        // The cursor isn't in the last column of the other screen's text
+        self.input_needs_wrap = false;
+
+        let screen_mode = self.mode & TermMode::SCREEN;
+        self.mode = (self.mode - TermMode::SCREEN) | self.inactive_screen_mode;
+        self.inactive_screen_mode = screen_mode;
     }
 
     /// Scroll screen down
@@ -1250,6 +1966,7 @@ impl Term {
     fn scroll_up_relative(&mut self, origin: Line, lines: Line) {
         trace!("Scrolling up relative: origin={}, lines={}", origin, lines);
         let lines = min(lines, self.scroll_region.end - self.scroll_region.start);
+        let lines = min(lines, self.scroll_region.end - origin);
 
         // Scroll from origin to bottom less number of lines
         let mut template = self.cursor.template;
@@ -1268,9 +1985,65 @@ impl Term {
         self.grid.region_mut(..).each(|c| c.reset(&template));
     }
 
+    /// Background of the screen, this is the foreground color in reverse video mode
     #[inline]
     pub fn background_color(&self) -> Rgb {
-        self.colors[NamedColor::Background]
+        if self.mode.contains(TermMode::REVERSE_VIDEO) {
+            self.colors[NamedColor::Foreground]
+        } else {
+            self.colors[NamedColor::Background]
+        }
+    }
+
+    /// Foreground of the screen, this is the background color in reverse video mode
+    #[inline]
+    pub fn foreground_color(&self) -> Rgb {
+        if self.mode.contains(TermMode::REVERSE_VIDEO) {
+            self.colors[NamedColor::Background]
+        } else {
+            self.colors[NamedColor::Foreground]
+        }
+    }
+
+    /// Background color of the window padding
+    ///
+    /// With `window.extend_background`, this is the background of more than half of the cells
+    /// at the edges of the visible screen, so applications filling the screen with a color of
+    /// their own don't get framed by the terminal background. Otherwise, and when no color has a
+    /// majority, it's the terminal background including changes by escape sequences.
+    pub fn effective_background(&self) -> Rgb {
+        let background = self.background_color();
+        if !self.extend_background {
+            return background;
+        }
+
+        let num_lines = self.grid.num_lines().0;
+        let num_cols = self.grid.num_cols().0;
+        let edge = (0..num_lines).flat_map(|line| {
+            // Only the first and last cell of lines between the top and bottom
+            let step = if line == 0 || line + 1 == num_lines { 1 } else { max(num_cols - 1, 1) };
+            (0..num_cols).step_by(step).map(move |col| Point::new(Line(line), Column(col)))
+        });
+
+        let reverse_video = self.mode.contains(TermMode::REVERSE_VIDEO);
+        let mut counts: Vec<(Rgb, usize)> = Vec::new();
+        let mut total = 0;
+        for point in edge {
+            let cell = &self.grid[self.grid.visible_to_buffer(point).line][point.col];
+            let color = if cell.inverse() ^ reverse_video { cell.fg } else { cell.bg };
+            let rgb = RenderableCell::compute_bg_rgb(&self.colors, color);
+
+            match counts.iter_mut().find(|(color, _)| *color == rgb) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((rgb, 1)),
+            }
+            total += 1;
+        }
+
+        counts
+            .into_iter()
+            .find(|&(_, count)| count * 2 > total)
+            .map_or(background, |(color, _)| color)
     }
 
     #[inline]
@@ -1283,6 +2056,20 @@ impl Term {
         &self.message_buffer
     }
 
+    /// Remove the message shown in the message bar
+    ///
+    /// This requests a draw, which gives the lines of the message bar back to the grid and pty.
+    pub fn dismiss_message(&mut self) {
+        self.message_buffer.pop();
+        self.dirty = true;
+    }
+
+    /// Start or stop suppressing all messages except for errors
+    pub fn toggle_do_not_disturb(&mut self) {
+        self.message_buffer.toggle_do_not_disturb();
+        self.dirty = true;
+    }
+
     #[inline]
     pub fn exit(&mut self) {
         self.should_exit = true;
@@ -1293,21 +2080,37 @@ impl Term {
         self.should_exit
     }
 
+    #[inline]
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.exit_status
+    }
+
+    #[inline]
+    pub fn set_exit_status(&mut self, status: ExitStatus) {
+        self.exit_status = Some(status);
+    }
+
+    /// Working directory reported by the shell, if it still exists
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.working_directory.as_ref().map(PathBuf::as_path).filter(|dir| dir.is_dir())
+    }
+
     #[inline]
     pub fn set_url_highlight(&mut self, hl: RangeInclusive<index::Linear>) {
         self.grid.url_highlight = Some(hl);
     }
 
+    /// Show the mouse cursor for hovering over the terminal's text
     #[inline]
     pub fn reset_mouse_cursor(&mut self) {
         let mouse_mode =
             TermMode::MOUSE_MOTION | TermMode::MOUSE_DRAG | TermMode::MOUSE_REPORT_CLICK;
-        let mouse_cursor = if self.mode().intersects(mouse_mode) {
-            MouseCursor::Default
-        } else {
-            MouseCursor::Text
+        let mouse_cursor = match self.app_mouse_cursor {
+            Some(mouse_cursor) => mouse_cursor,
+            None if self.mode().intersects(mouse_mode) => MouseCursorIcon::Default,
+            None => MouseCursorIcon::Text,
         };
-        self.set_mouse_cursor(mouse_cursor);
+        self.set_next_mouse_cursor(mouse_cursor);
     }
 
     #[inline]
@@ -1320,50 +2123,54 @@ impl Term {
         &mut self.clipboard
     }
 
+    /// Store the selected text in a clipboard
+    ///
+    /// The clipboard is left untouched when nothing is selected.
+    pub fn copy_selection(&mut self, ty: ClipboardType) {
+        match self.selection_to_string() {
+            Some(selected) if !selected.is_empty() => self.clipboard.store(ty, selected),
+            _ => (),
+        }
+    }
+
     pub fn urls(&self) -> Vec<Url> {
-        let display_offset = self.grid.display_offset();
         let num_cols = self.grid.num_cols().0;
-        let last_col = Column(num_cols - 1);
-        let last_line = self.grid.num_lines() - 1;
+        let viewport_top = self.grid.display_offset() + self.grid.num_lines().0;
 
-        let grid_end_point = Point::new(0, last_col);
-        let mut iter = self.grid.iter_from(grid_end_point);
-
-        let mut parser = Parser::new();
-        let mut extra_url_len = 0;
         let mut urls = Vec::new();
-
-        let mut c = Some(iter.cell());
-        while let Some(cell) = c {
-            let point = iter.point();
-            c = iter.prev();
-
-            // Skip double-width cell but extend URL length
-            if cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
-                extra_url_len += 1;
+        for line in self.grid.logical_lines().rev() {
+            // Lines are visited from the bottom, so everything after the viewport is hidden
+            if !line.is_visible() {
+                if line.end().line >= viewport_top {
+                    break;
+                }
                 continue;
             }
 
-            // Interrupt URLs on line break
-            if point.col == last_col && !cell.flags.contains(cell::Flags::WRAPLINE) {
-                extra_url_len = 0;
-                parser.reset();
-            }
+            // URLs are parsed backwards, starting at the last cell of the line
+            let mut parser = Parser::new();
+            let mut extra_url_len = 0;
+            for (point, cell) in line.cells().rev() {
+                // Skip double-width cell but extend URL length
+                if cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                    extra_url_len += 1;
+                    continue;
+                }
 
-            // Advance parser
-            match parser.advance(cell.c) {
-                ParserState::Url(length) => {
-                    urls.push(Url::new(point, length + extra_url_len, num_cols))
-                },
-                ParserState::NoUrl => {
-                    extra_url_len = 0;
+                match parser.advance(cell.c) {
+                    ParserState::Url(length) => {
+                        urls.push(Url::new(point, length + extra_url_len, num_cols))
+                    },
+                    ParserState::NoUrl => {
+                        extra_url_len = 0;
 
-                    // Stop searching for URLs once the viewport has been left without active URL
-                    if point.line > last_line.0 + display_offset {
-                        break;
-                    }
-                },
-                _ => (),
+                        // Stop once the viewport has been left without an active URL
+                        if point.line >= viewport_top {
+                            break;
+                        }
+                    },
+                    _ => (),
+                }
             }
         }
 
@@ -1390,6 +2197,26 @@ impl Term {
 
         url_text
     }
+
+    /// Write the reply to a color query, with the configured or the query's terminator
+    fn write_color_reply(
+        &self,
+        writer: &mut dyn io::Write,
+        prefix: &str,
+        index: usize,
+        terminator: StringTerminator,
+    ) {
+        let color = self.colors[index];
+        let response = format!(
+            "\x1b]{};rgb:{1:02x}{1:02x}/{2:02x}{2:02x}/{3:02x}{3:02x}{4}",
+            prefix,
+            color.r,
+            color.g,
+            color.b,
+            self.reply_terminator.resolve(terminator).as_str(),
+        );
+        let _ = writer.write_all(response.as_bytes());
+    }
 }
 
 impl TermInfo for Term {
@@ -1423,14 +2250,17 @@ impl ansi::Handler for Term {
                     self.next_title = Some(format!("Alacritty {}", title.trim()));
                 }
             }
+
+            self.title = self.next_title.clone();
         }
     }
 
-    /// Set the mouse cursor
+    /// Set the mouse cursor shape requested by the application
     #[inline]
-    fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
-        self.next_mouse_cursor = Some(cursor);
-        self.dirty = true;
+    fn set_mouse_cursor(&mut self, cursor: Option<MouseCursorIcon>) {
+        trace!("Setting mouse cursor: {:?}", cursor);
+        self.app_mouse_cursor = cursor;
+        self.reset_mouse_cursor();
     }
 
     /// A character to be displayed
@@ -1441,11 +2271,38 @@ impl ansi::Handler for Term {
             self.scroll_display(Scroll::Bottom);
         }
 
-        if self.input_needs_wrap {
-            if !self.mode.contains(TermMode::LINE_WRAP) {
-                return;
+        // Number of cells the char will occupy
+        let width = self.ambiguous_width.char_width(c);
+
+        // Zero-width characters belong to the last character, even before wrapping the line
+        if width == Some(0) {
+            let line = self.cursor.point.line;
+            let mut col = if self.input_needs_wrap {
+                self.cursor.point.col.0
+            } else {
+                self.cursor.point.col.0.saturating_sub(1)
+            };
+            if self.grid[line][Column(col)].flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                col = col.saturating_sub(1);
             }
+            self.grid[line][Column(col)].push_extra(c);
+            return;
+        }
+
+        // Wide chars which don't fit into the last column start on the next line
+        if width == Some(2)
+            && self.cursor.point.col + 1 >= self.grid.num_cols()
+            && self.mode.contains(TermMode::LINE_WRAP)
+        {
+            self.input_needs_wrap = true;
+        }
+
+        // Without autowrap the last column is overwritten instead
+        if self.input_needs_wrap && !self.mode.contains(TermMode::LINE_WRAP) {
+            self.input_needs_wrap = false;
+        }
 
+        if self.input_needs_wrap {
             trace!("Wrapping input");
 
             {
@@ -1465,8 +2322,7 @@ impl ansi::Handler for Term {
             self.input_needs_wrap = false;
         }
 
-        // Number of cells the char will occupy
-        if let Some(width) = c.width() {
+        if let Some(width) = width {
             let num_cols = self.grid.num_cols();
 
             // If in insert mode, first shift cells to the right.
@@ -1483,15 +2339,18 @@ impl ansi::Handler for Term {
                 }
             }
 
-            // Handle zero-width characters
-            if width == 0 {
-                let mut col = self.cursor.point.col.0.saturating_sub(1);
+            // Overwriting part of a cluster removes all of it, instead of leaving fragments
+            if !self.mode.contains(TermMode::INSERT) {
                 let line = self.cursor.point.line;
-                if self.grid[line][Column(col)].flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
-                    col = col.saturating_sub(1);
+                let col = self.cursor.point.col;
+                let cols = col..min(col + width, num_cols);
+                let cluster = cluster::expand(&self.grid[line], cols.clone());
+                if cluster != cols {
+                    let template = self.cursor.template;
+                    for cell in &mut self.grid[line][cluster] {
+                        cell.reset(&template);
+                    }
                 }
-                self.grid[line][Column(col)].push_extra(c);
-                return;
             }
 
             let cell = &mut self.grid[&self.cursor.point];
@@ -1536,7 +2395,7 @@ impl ansi::Handler for Term {
             (Line(0), self.grid.num_lines() - 1)
         };
 
-        self.cursor.point.line = min(line + y_offset, max_y);
+        self.cursor.point.line = min(Line(line.0.saturating_add(y_offset.0)), max_y);
         self.cursor.point.col = min(col, self.grid.num_cols() - 1);
         self.input_needs_wrap = false;
     }
@@ -1555,6 +2414,8 @@ impl ansi::Handler for Term {
 
     #[inline]
     fn insert_blank(&mut self, count: Column) {
+        self.input_needs_wrap = false;
+
         // Ensure inserting within terminal bounds
 
         let count = min(count, self.size_info.cols() - self.cursor.point.col);
@@ -1590,14 +2451,15 @@ impl ansi::Handler for Term {
     #[inline]
     fn move_down(&mut self, lines: Line) {
         trace!("Moving down: {}", lines);
-        let move_to = self.cursor.point.line + lines;
+        let move_to = Line(self.cursor.point.line.0.saturating_add(lines.0));
         self.goto(move_to, self.cursor.point.col)
     }
 
     #[inline]
     fn move_forward(&mut self, cols: Column) {
         trace!("Moving forward: {}", cols);
-        self.cursor.point.col = min(self.cursor.point.col + cols, self.grid.num_cols() - 1);
+        let move_to = Column(self.cursor.point.col.0.saturating_add(cols.0));
+        self.cursor.point.col = min(move_to, self.grid.num_cols() - 1);
         self.input_needs_wrap = false;
     }
 
@@ -1609,12 +2471,12 @@ impl ansi::Handler for Term {
     }
 
     #[inline]
-    fn identify_terminal<W: io::Write>(&mut self, writer: &mut W) {
+    fn identify_terminal(&mut self, writer: &mut dyn io::Write) {
         let _ = writer.write_all(b"\x1b[?6c");
     }
 
     #[inline]
-    fn device_status<W: io::Write>(&mut self, writer: &mut W, arg: usize) {
+    fn device_status(&mut self, writer: &mut dyn io::Write, arg: usize) {
         trace!("Reporting device status: {}", arg);
         match arg {
             5 => {
@@ -1632,7 +2494,7 @@ impl ansi::Handler for Term {
     #[inline]
     fn move_down_and_cr(&mut self, lines: Line) {
         trace!("Moving down and cr: {}", lines);
-        let move_to = self.cursor.point.line + lines;
+        let move_to = Line(self.cursor.point.line.0.saturating_add(lines.0));
         self.goto(move_to, Column(0))
     }
 
@@ -1693,6 +2555,7 @@ impl ansi::Handler for Term {
     #[inline]
     fn linefeed(&mut self) {
         trace!("Linefeed");
+        self.input_needs_wrap = false;
         let next = self.cursor.point.line + 1;
         if next == self.scroll_region.end {
             self.scroll_up(Line(1));
@@ -1785,26 +2648,36 @@ impl ansi::Handler for Term {
     #[inline]
     fn erase_chars(&mut self, count: Column) {
         trace!("Erasing chars: count={}, col={}", count, self.cursor.point.col);
+        self.input_needs_wrap = false;
         let start = self.cursor.point.col;
-        let end = min(start + count, self.grid.num_cols());
+        let end = min(Column(start.0.saturating_add(count.0)), self.grid.num_cols());
 
+        // Clusters are erased entirely, even the cells before the cursor
         let row = &mut self.grid[self.cursor.point.line];
+        let cluster = cluster::expand(row, start..end);
         let template = self.cursor.template; // Cleared cells have current background color set
-        for c in &mut row[start..end] {
+        for c in &mut row[cluster] {
             c.reset(&template);
         }
     }
 
     #[inline]
     fn delete_chars(&mut self, count: Column) {
-        // Ensure deleting within terminal bounds
-        let count = min(count, self.size_info.cols());
-
+        self.input_needs_wrap = false;
+        let cols = self.grid.num_cols();
         let start = self.cursor.point.col;
-        let end = min(start + count, self.grid.num_cols() - 1);
-        let n = (self.size_info.cols() - end).0;
+        let end = min(Column(start.0.saturating_add(count.0)), cols);
 
+        // The last cluster is deleted entirely, its cells before the cursor are cleared
         let line = &mut self.grid[self.cursor.point.line];
+        let cluster = cluster::expand(line, start..end);
+        let template = self.cursor.template;
+        for c in &mut line[cluster.start..start] {
+            c.reset(&template);
+        }
+
+        let end = cluster.end;
+        let n = (cols - end).0;
 
         unsafe {
             let src = line[end..].as_ptr();
@@ -1813,11 +2686,8 @@ impl ansi::Handler for Term {
             ptr::copy(src, dst, n);
         }
 
-        // Clear last `count` cells in line. If deleting 1 char, need to delete
-        // 1 cell.
-        let template = self.cursor.template;
-        let end = self.size_info.cols() - count;
-        for c in &mut line[end..] {
+        // Clear the cells which were moved to the left
+        for c in &mut line[start + n..] {
             c.reset(&template);
         }
     }
@@ -1826,6 +2696,8 @@ impl ansi::Handler for Term {
     fn move_backward_tabs(&mut self, count: i64) {
         trace!("Moving backward {} tabs", count);
 
+        // There can't be more tab stops than columns
+        let count = min(count, self.grid.num_cols().0 as i64);
         for _ in 0..count {
             let mut col = self.cursor.point.col;
             for i in (0..(col.0)).rev() {
@@ -1836,6 +2708,8 @@ impl ansi::Handler for Term {
             }
             self.cursor.point.col = col;
         }
+
+        self.input_needs_wrap = false;
     }
 
     #[inline]
@@ -1846,9 +2720,10 @@ impl ansi::Handler for Term {
     #[inline]
     fn save_cursor_position(&mut self) {
         trace!("Saving cursor position");
-        let cursor = if self.alt { &mut self.cursor_save_alt } else { &mut self.cursor_save };
+        let save = if self.alt { &mut self.cursor_save_alt } else { &mut self.cursor_save };
 
-        *cursor = self.cursor;
+        save.cursor = self.cursor;
+        save.input_needs_wrap = self.input_needs_wrap;
     }
 
     #[inline]
@@ -1856,7 +2731,8 @@ impl ansi::Handler for Term {
         trace!("Restoring cursor position");
         let source = if self.alt { &self.cursor_save_alt } else { &self.cursor_save };
 
-        self.cursor = *source;
+        self.cursor = source.cursor;
+        self.input_needs_wrap = source.input_needs_wrap;
         self.cursor.point.line = min(self.cursor.point.line, self.grid.num_lines() - 1);
         self.cursor.point.col = min(self.cursor.point.col, self.grid.num_cols() - 1);
     }
@@ -1864,6 +2740,7 @@ impl ansi::Handler for Term {
     #[inline]
     fn clear_line(&mut self, mode: ansi::LineClearMode) {
         trace!("Clearing line: {:?}", mode);
+        self.input_needs_wrap = false;
         let mut template = self.cursor.template;
         template.flags ^= template.flags;
 
@@ -1895,34 +2772,101 @@ impl ansi::Handler for Term {
     #[inline]
     fn set_color(&mut self, index: usize, color: Rgb) {
         trace!("Setting color[{}] = {:?}", index, color);
+        if self.colors[index] != color {
+            self.palette_burst.change(Instant::now());
+        }
         self.colors[index] = color;
         self.color_modified[index] = true;
     }
 
     /// Write a foreground/background color escape sequence with the current color
     #[inline]
-    fn dynamic_color_sequence<W: io::Write>(&mut self, writer: &mut W, code: u8, index: usize) {
+    fn dynamic_color_sequence(
+        &mut self,
+        writer: &mut dyn io::Write,
+        code: u8,
+        index: usize,
+        terminator: StringTerminator,
+    ) {
         trace!("Writing escape sequence for dynamic color code {}: color[{}]", code, index);
-        let color = self.colors[index];
-        let response = format!(
-            "\x1b]{};rgb:{1:02x}{1:02x}/{2:02x}{2:02x}/{3:02x}{3:02x}\x07",
-            code, color.r, color.g, color.b
-        );
-        let _ = writer.write_all(response.as_bytes());
+        self.write_color_reply(writer, &code.to_string(), index, terminator);
+    }
+
+    /// Write an OSC 4 escape sequence with the current indexed color
+    #[inline]
+    fn indexed_color_sequence(
+        &mut self,
+        writer: &mut dyn io::Write,
+        index: usize,
+        terminator: StringTerminator,
+    ) {
+        trace!("Writing escape sequence for color[{}]", index);
+        self.write_color_reply(writer, &format!("4;{}", index), index, terminator);
     }
 
     /// Reset the indexed color to original value
     #[inline]
     fn reset_color(&mut self, index: usize) {
         trace!("Resetting color[{}]", index);
+        if self.colors[index] != self.original_colors[index] {
+            self.palette_burst.change(Instant::now());
+        }
         self.colors[index] = self.original_colors[index];
         self.color_modified[index] = false;
     }
 
     /// Set the clipboard
     #[inline]
-    fn set_clipboard(&mut self, string: &str) {
-        self.clipboard.store(ClipboardType::Clipboard, string);
+    fn set_clipboard(&mut self, clipboard: u8, string: &str) {
+        let clipboard_type = match clipboard {
+            b'c' => ClipboardType::Clipboard,
+            b'p' | b's' => ClipboardType::Selection,
+            _ => return,
+        };
+
+        self.clipboard.store(clipboard_type, string);
+    }
+
+    #[inline]
+    fn clipboard_size_limit(&self) -> usize {
+        self.max_clipboard_escape_size
+    }
+
+    #[inline]
+    fn clipboard_lossy_utf8(&self) -> bool {
+        self.lossy_clipboard_escape
+    }
+
+    #[inline]
+    fn set_window_opacity(&mut self, opacity: Option<f32>) {
+        if !self.opacity_escape {
+            debug!("Ignoring window opacity escape, it is disabled in the config");
+            return;
+        }
+
+        self.next_window_opacity = Some(opacity.map_or(OpacityChange::Reset, OpacityChange::Set));
+    }
+
+    #[inline]
+    fn scroll_status(&mut self, writer: &mut dyn io::Write, terminator: StringTerminator) {
+        if !self.status_escape {
+            debug!("Ignoring scrollback status query, it is disabled in the config");
+            return;
+        }
+
+        let response = format!(
+            "\x1b]5522;{};{}{}",
+            self.history_lines(),
+            self.display_offset(),
+            self.reply_terminator.resolve(terminator).as_str(),
+        );
+        let _ = writer.write_all(response.as_bytes());
+    }
+
+    #[inline]
+    fn set_working_directory(&mut self, path: PathBuf) {
+        trace!("Setting working directory to {:?}", path);
+        self.working_directory = Some(path);
     }
 
     #[inline]
@@ -1937,6 +2881,7 @@ impl ansi::Handler for Term {
 
         match mode {
             ansi::ClearMode::Below => {
+                self.input_needs_wrap = false;
                 for cell in &mut self.grid[self.cursor.point.line][self.cursor.point.col..] {
                     cell.reset(&template);
                 }
@@ -1946,8 +2891,12 @@ impl ansi::Handler for Term {
                         .each(|cell| cell.reset(&template));
                 }
             },
-            ansi::ClearMode::All => self.grid.region_mut(..).each(|c| c.reset(&template)),
+            ansi::ClearMode::All => {
+                self.input_needs_wrap = false;
+                self.grid.region_mut(..).each(|c| c.reset(&template));
+            },
             ansi::ClearMode::Above => {
+                self.input_needs_wrap = false;
                 // If clearing more than one line
                 if self.cursor.point.line > Line(1) {
                     // Fully clear all lines before the current line
@@ -1987,14 +2936,23 @@ impl ansi::Handler for Term {
         }
         self.input_needs_wrap = false;
         self.next_title = None;
+        self.title = None;
         self.next_mouse_cursor = None;
+        self.app_mouse_cursor = None;
+        self.focus_report_requested = false;
         self.cursor = Default::default();
         self.active_charset = Default::default();
         self.mode = Default::default();
-        self.font_size = self.original_font_size;
+        #[cfg(feature = "gui")]
+        {
+            self.font_size = self.original_font_size;
+            self.font_size_modified = false;
+        }
         self.next_is_urgent = None;
+        self.next_window_opacity = None;
         self.cursor_save = Default::default();
         self.cursor_save_alt = Default::default();
+        self.inactive_screen_mode = TermMode::default() & TermMode::SCREEN;
         self.colors = self.original_colors;
         self.color_modified = [false; color::COUNT];
         self.cursor_style = None;
@@ -2006,6 +2964,7 @@ impl ansi::Handler for Term {
     #[inline]
     fn reverse_index(&mut self) {
         trace!("Reversing index");
+        self.input_needs_wrap = false;
         // if cursor is at the top
         if self.cursor.point.line == self.scroll_region.start {
             self.scroll_down(Line(1));
@@ -2057,29 +3016,33 @@ impl ansi::Handler for Term {
                     self.mode.insert(TermMode::ALT_SCREEN);
                     self.save_cursor_position();
                     self.swap_alt();
-                    self.save_cursor_position();
                 }
             },
             ansi::Mode::ShowCursor => self.mode.insert(TermMode::SHOW_CURSOR),
             ansi::Mode::CursorKeys => self.mode.insert(TermMode::APP_CURSOR),
             ansi::Mode::ReportMouseClicks => {
                 self.mode.insert(TermMode::MOUSE_REPORT_CLICK);
-                self.set_mouse_cursor(MouseCursor::Default);
+                self.reset_mouse_cursor();
             },
             ansi::Mode::ReportCellMouseMotion => {
                 self.mode.insert(TermMode::MOUSE_DRAG);
-                self.set_mouse_cursor(MouseCursor::Default);
+                self.reset_mouse_cursor();
             },
             ansi::Mode::ReportAllMouseMotion => {
                 self.mode.insert(TermMode::MOUSE_MOTION);
-                self.set_mouse_cursor(MouseCursor::Default);
+                self.reset_mouse_cursor();
+            },
+            ansi::Mode::ReportFocusInOut => {
+                self.mode.insert(TermMode::FOCUS_IN_OUT);
+                self.focus_report_requested = true;
             },
-            ansi::Mode::ReportFocusInOut => self.mode.insert(TermMode::FOCUS_IN_OUT),
             ansi::Mode::BracketedPaste => self.mode.insert(TermMode::BRACKETED_PASTE),
             ansi::Mode::SgrMouse => self.mode.insert(TermMode::SGR_MOUSE),
+            ansi::Mode::InterpretMetaKey => self.mode.insert(TermMode::ALT_SEND_ESC),
             ansi::Mode::LineWrap => self.mode.insert(TermMode::LINE_WRAP),
             ansi::Mode::LineFeedNewLine => self.mode.insert(TermMode::LINE_FEED_NEW_LINE),
             ansi::Mode::Origin => self.mode.insert(TermMode::ORIGIN),
+            ansi::Mode::ReverseVideo => self.mode.insert(TermMode::REVERSE_VIDEO),
             ansi::Mode::DECCOLM => self.deccolm(),
             ansi::Mode::Insert => self.mode.insert(TermMode::INSERT), // heh
             ansi::Mode::BlinkingCursor => {
@@ -2095,31 +3058,40 @@ impl ansi::Handler for Term {
             ansi::Mode::SwapScreenAndSetRestoreCursor => {
                 if self.alt {
                     self.mode.remove(TermMode::ALT_SCREEN);
-                    self.restore_cursor_position();
                     self.swap_alt();
                     self.restore_cursor_position();
+
+                    // Shapes set by fullscreen applications don't make sense for the shell
+                    if self.app_mouse_cursor.take().is_some() {
+                        self.reset_mouse_cursor();
+                    }
                 }
             },
             ansi::Mode::ShowCursor => self.mode.remove(TermMode::SHOW_CURSOR),
             ansi::Mode::CursorKeys => self.mode.remove(TermMode::APP_CURSOR),
             ansi::Mode::ReportMouseClicks => {
                 self.mode.remove(TermMode::MOUSE_REPORT_CLICK);
-                self.set_mouse_cursor(MouseCursor::Text);
+                self.reset_mouse_cursor();
             },
             ansi::Mode::ReportCellMouseMotion => {
                 self.mode.remove(TermMode::MOUSE_DRAG);
-                self.set_mouse_cursor(MouseCursor::Text);
+                self.reset_mouse_cursor();
             },
             ansi::Mode::ReportAllMouseMotion => {
                 self.mode.remove(TermMode::MOUSE_MOTION);
-                self.set_mouse_cursor(MouseCursor::Text);
+                self.reset_mouse_cursor();
+            },
+            ansi::Mode::ReportFocusInOut => {
+                self.mode.remove(TermMode::FOCUS_IN_OUT);
+                self.focus_report_requested = false;
             },
-            ansi::Mode::ReportFocusInOut => self.mode.remove(TermMode::FOCUS_IN_OUT),
             ansi::Mode::BracketedPaste => self.mode.remove(TermMode::BRACKETED_PASTE),
             ansi::Mode::SgrMouse => self.mode.remove(TermMode::SGR_MOUSE),
+            ansi::Mode::InterpretMetaKey => self.mode.remove(TermMode::ALT_SEND_ESC),
             ansi::Mode::LineWrap => self.mode.remove(TermMode::LINE_WRAP),
             ansi::Mode::LineFeedNewLine => self.mode.remove(TermMode::LINE_FEED_NEW_LINE),
             ansi::Mode::Origin => self.mode.remove(TermMode::ORIGIN),
+            ansi::Mode::ReverseVideo => self.mode.remove(TermMode::REVERSE_VIDEO),
             ansi::Mode::DECCOLM => self.deccolm(),
             ansi::Mode::Insert => self.mode.remove(TermMode::INSERT),
             ansi::Mode::BlinkingCursor => {
@@ -2131,8 +3103,16 @@ impl ansi::Handler for Term {
     #[inline]
     fn set_scrolling_region(&mut self, region: Range<Line>) {
         trace!("Setting scrolling region: {:?}", region);
-        self.scroll_region.start = min(region.start, self.grid.num_lines());
-        self.scroll_region.end = min(region.end, self.grid.num_lines());
+        let start = min(region.start, self.grid.num_lines());
+        let end = min(region.end, self.grid.num_lines());
+
+        // Scrolling an empty region is impossible, so the old one is kept
+        if start >= end {
+            debug!("Ignoring invalid scrolling region: {:?}", region);
+            return;
+        }
+
+        self.scroll_region = start..end;
         self.goto(Line(0), Column(0));
     }
 
@@ -2204,19 +3184,33 @@ impl IndexMut<Column> for TabStops {
 #[cfg(test)]
 mod tests {
     use std::mem;
+    use std::time::Instant;
 
+    #[cfg(feature = "gui")]
     use font::Size;
     use serde_json;
 
-    use crate::ansi::{self, CharsetIndex, Handler, StandardCharset};
-    use crate::clipboard::Clipboard;
-    use crate::config::Config;
+    use crate::ansi::{self, CharsetIndex, CursorStyle, Handler, MouseCursorIcon, StandardCharset};
+    use crate::clipboard::{Clipboard, ClipboardType};
+    use crate::config::{AmbiguousWidth, Config, ReplyTerminator};
     use crate::grid::{Grid, Scroll};
     use crate::index::{Column, Line, Point, Side};
+    #[cfg(feature = "gui")]
     use crate::input::FONT_SIZE_STEP;
+    #[cfg(feature = "gui")]
+    use crate::message_bar::Message;
     use crate::message_bar::MessageBuffer;
     use crate::selection::Selection;
-    use crate::term::{cell, Cell, SizeInfo, Term};
+    use crate::term::color::{self, Rgb};
+    use crate::term::{
+        cell, resolve_cursor_style, Cell, CursorKey, OpacityChange, RenderableCellContent,
+        SizeInfo, Term, TermMode,
+    };
+    #[cfg(feature = "gui")]
+    use crate::term::{
+        FONT_FAMILY_NOTICE_DURATION, FONT_FAMILY_TOPIC, MAX_FONT_SIZE, MIN_FONT_SIZE,
+    };
+    use crate::testing::{assert_snapshot, feed, MockWriter, TermBuilder};
 
     #[test]
     fn semantic_selection_works() {
@@ -2225,8 +3219,10 @@ mod tests {
             height: 51.0,
             cell_width: 3.0,
             cell_height: 3.0,
-            padding_x: 0.0,
-            padding_y: 0.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
             dpr: 1.0,
         };
         let mut term =
@@ -2270,8 +3266,10 @@ mod tests {
             height: 51.0,
             cell_width: 3.0,
             cell_height: 3.0,
-            padding_x: 0.0,
-            padding_y: 0.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
             dpr: 1.0,
         };
         let mut term =
@@ -2290,35 +3288,94 @@ mod tests {
     }
 
     #[test]
-    fn selecting_empty_line() {
+    fn copy_selection_to_each_buffer() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
             cell_width: 3.0,
             cell_height: 3.0,
-            padding_x: 0.0,
-            padding_y: 0.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
             dpr: 1.0,
         };
         let mut term =
-            Term::new(&Default::default(), size, MessageBuffer::new(), Clipboard::new_nop());
-        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(3), 0, Cell::default());
-        for l in 0..3 {
-            if l != 1 {
-                for c in 0..3 {
-                    grid[Line(l)][Column(c)].c = 'a';
-                }
-            }
-        }
+            Term::new(&Default::default(), size, MessageBuffer::new(), Clipboard::new_memory());
+        term.clipboard().store(ClipboardType::Clipboard, "clipboard");
+        term.clipboard().store(ClipboardType::Selection, "primary");
 
-        mem::swap(&mut term.grid, &mut grid);
+        // Nothing is selected
+        term.copy_selection(ClipboardType::Clipboard);
+        term.copy_selection(ClipboardType::Selection);
+        assert_eq!(term.clipboard().load(ClipboardType::Clipboard), "clipboard");
+        assert_eq!(term.clipboard().load(ClipboardType::Selection), "primary");
 
-        let mut selection = Selection::simple(Point { line: 2, col: Column(0) }, Side::Left);
-        selection.update(Point { line: 0, col: Column(2) }, Side::Right);
+        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(5), 0, Cell::default());
+        for i in 0..5 {
+            grid[Line(0)][Column(i)].c = 'a';
+        }
+        mem::swap(&mut term.grid, &mut grid);
+        *term.selection_mut() = Some(Selection::lines(Point { line: 0, col: Column(3) }));
+
+        term.copy_selection(ClipboardType::Selection);
+        assert_eq!(term.clipboard().load(ClipboardType::Clipboard), "clipboard");
+        assert_eq!(term.clipboard().load(ClipboardType::Selection), "aaaaa\n");
+
+        term.copy_selection(ClipboardType::Clipboard);
+        assert_eq!(term.clipboard().load(ClipboardType::Clipboard), "aaaaa\n");
+    }
+
+    #[test]
+    fn selecting_empty_line() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+        let mut term =
+            Term::new(&Default::default(), size, MessageBuffer::new(), Clipboard::new_nop());
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(3), 0, Cell::default());
+        for l in 0..3 {
+            if l != 1 {
+                for c in 0..3 {
+                    grid[Line(l)][Column(c)].c = 'a';
+                }
+            }
+        }
+
+        mem::swap(&mut term.grid, &mut grid);
+
+        let mut selection = Selection::simple(Point { line: 2, col: Column(0) }, Side::Left);
+        selection.update(Point { line: 0, col: Column(2) }, Side::Right);
         *term.selection_mut() = Some(selection);
         assert_eq!(term.selection_to_string(), Some("aaa\n\naaa\n".into()));
     }
 
+    #[test]
+    fn url_and_selection_wrapped_into_history() {
+        let mut term = TermBuilder::new(10, 2).build();
+        feed(&mut term, b"https://example.org/a\r\nx");
+
+        // The URL starts two rows up in the scrollback history
+        let urls = term.urls();
+        assert_eq!(urls.len(), 1);
+        assert_eq!(urls[0].start, Point::new(3, Column(0)));
+        assert_eq!(urls[0].end, Point::new(1, Column(0)));
+        assert_eq!(term.url_to_string(&urls[0]), "https://example.org/a");
+
+        let mut selection = Selection::simple(Point { line: 3, col: Column(0) }, Side::Left);
+        selection.update(Point { line: 1, col: Column(9) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some("https://example.org/a\n".into()));
+    }
+
     /// Check that the grid can be serialized back and forth losslessly
     ///
     /// This test is in the term module as opposed to the grid since we want to
@@ -2336,36 +3393,425 @@ mod tests {
 
     #[test]
     fn input_line_drawing_character() {
-        let size = SizeInfo {
-            width: 21.0,
-            height: 51.0,
-            cell_width: 3.0,
-            cell_height: 3.0,
-            padding_x: 0.0,
-            padding_y: 0.0,
-            dpr: 1.0,
-        };
-        let mut term =
-            Term::new(&Default::default(), size, MessageBuffer::new(), Clipboard::new_nop());
-        let cursor = Point::new(Line(0), Column(0));
+        let mut term = TermBuilder::new(7, 17).build();
         term.configure_charset(CharsetIndex::G0, StandardCharset::SpecialCharacterAndLineDrawing);
         term.input('a');
 
-        assert_eq!(term.grid()[&cursor].c, '▒');
+        assert_snapshot(&term, "▒\n-- cursor 0:1");
+    }
+
+    #[test]
+    fn input_line_drawing_escape() {
+        let mut term = TermBuilder::new(7, 17).build();
+        feed(&mut term, b"\x1b(0lqk\x1b(Bq");
+
+        assert_snapshot(&term, "┌─┐q\n-- cursor 0:4");
+    }
+
+    #[test]
+    fn csi_extreme_parameters() {
+        let actions = "@ABCDEFGHIJKLMPSTXZabdefgr`";
+        let params = ["2147483647", "999999999", "9223372036854775807", "99999999999999999999"];
+        let modes = ["", "\x1b[?6h", "\x1b[5;10r", "\x1b[?6h\x1b[5;10r"];
+
+        for mode in &modes {
+            for param in &params {
+                for action in actions.chars() {
+                    let mut term = TermBuilder::new(7, 17).build();
+                    feed(&mut term, format!("{}\x1b[10;5Hx", mode).as_bytes());
+
+                    for sequence in &[
+                        format!("\x1b[{}{}", param, action),
+                        format!("\x1b[{0};{0}{1}", param, action),
+                        format!("\x1b[1;{}{}", param, action),
+                    ] {
+                        feed(&mut term, sequence.as_bytes());
+                        feed(&mut term, b"y\r\n\x1b[Mz");
+
+                        let point = term.cursor().point;
+                        assert!(point.line < Line(17), "{:?} {:?}", mode, sequence);
+                        assert!(point.col < Column(7), "{:?} {:?}", mode, sequence);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn csi_position_saturated() {
+        let mut term = TermBuilder::new(7, 17).build();
+        feed(&mut term, b"\x1b[2147483647;2147483647Hx");
+
+        assert_eq!(term.cursor().point, Point::new(Line(16), Column(6)));
+        assert_eq!(term.grid()[Line(16)][Column(6)].c, 'x');
+    }
+
+    #[test]
+    fn delete_lines_below_cursor_only() {
+        let mut term = TermBuilder::new(7, 4).build();
+        feed(&mut term, b"1\r\n2\r\n3\r\n4\x1b[3H\x1b[999M");
+
+        assert_snapshot(&term, "1\n2\n-- cursor 2:0");
+    }
+
+    #[test]
+    fn empty_scrolling_region_ignored() {
+        let mut term = TermBuilder::new(7, 4).build();
+        feed(&mut term, b"a\r\nb\r\nc\r\nd\x1b[4;2r\x1b[5;5r\n");
+
+        assert_eq!(term.scroll_region, Line(0)..Line(4));
+        assert_snapshot(&term, "b\nc\nd\n-- cursor 3:1");
+    }
+
+    #[test]
+    fn ambiguous_width_single() {
+        let mut term = TermBuilder::new(8, 2).build();
+        feed(&mut term, "§±α§".as_bytes());
+
+        assert_eq!(term.cursor().point, Point::new(Line(0), Column(4)));
+        for col in 0..4 {
+            assert_eq!(term.grid()[Line(0)][Column(col)].flags, cell::Flags::empty());
+        }
+    }
+
+    #[test]
+    fn ambiguous_width_double() {
+        let mut config = Config::default();
+        config.terminal.ambiguous_width = AmbiguousWidth::Double;
+        let mut term = TermBuilder::new(8, 2).config(&config).build();
+        feed(&mut term, "§±α".as_bytes());
+
+        assert_eq!(term.cursor().point, Point::new(Line(0), Column(6)));
+        for col in (0..6).step_by(2) {
+            assert_eq!(term.grid()[Line(0)][Column(col)].flags, cell::Flags::WIDE_CHAR);
+            assert_eq!(term.grid()[Line(0)][Column(col + 1)].flags, cell::Flags::WIDE_CHAR_SPACER);
+        }
+
+        *term.selection_mut() = Some(Selection::lines(Point { line: 1, col: Column(0) }));
+        assert_eq!(term.selection_to_string(), Some(String::from("§±α\n")));
+
+        // Wraps like any other wide character
+        feed(&mut term, "§x".as_bytes());
+        assert_eq!(term.cursor().point, Point::new(Line(1), Column(1)));
+        assert!(term.grid()[Line(0)][Column(7)].flags.contains(cell::Flags::WIDE_CHAR_SPACER));
+    }
+
+    #[test]
+    fn ambiguous_width_change_keeps_cells() {
+        let mut config = Config::default();
+        config.terminal.ambiguous_width = AmbiguousWidth::Double;
+        let mut term = TermBuilder::new(8, 2).config(&config).build();
+        feed(&mut term, "§".as_bytes());
+
+        term.update_config(&Config::default());
+        feed(&mut term, "§".as_bytes());
+
+        assert_eq!(term.cursor().point, Point::new(Line(0), Column(3)));
+        assert_eq!(term.grid()[Line(0)][Column(0)].flags, cell::Flags::WIDE_CHAR);
+        assert_eq!(term.grid()[Line(0)][Column(2)].flags, cell::Flags::empty());
+    }
+
+    #[test]
+    fn color_query_reply_terminator() {
+        let mut term = TermBuilder::new(8, 2).build();
+        feed(&mut term, b"\x1b]11;#102030\x07");
+
+        let writer = feed(&mut term, b"\x1b]11;?\x07");
+        assert_eq!(writer.text(), "\x1b]11;rgb:1010/2020/3030\x07");
+
+        let writer = feed(&mut term, b"\x1b]11;?\x1b\\");
+        assert_eq!(writer.text(), "\x1b]11;rgb:1010/2020/3030\x1b\\");
+
+        let writer = feed(&mut term, b"\x1b]4;1;#ff0000\x07\x1b]4;1;?\x1b\\");
+        assert_eq!(writer.text(), "\x1b]4;1;rgb:ffff/0000/0000\x1b\\");
+    }
+
+    #[test]
+    fn color_query_forced_terminator() {
+        let mut config = Config::default();
+        config.terminal.reply_terminator = ReplyTerminator::Bel;
+        let mut term = TermBuilder::new(8, 2).config(&config).build();
+        feed(&mut term, b"\x1b]11;#102030\x07");
+
+        let writer = feed(&mut term, b"\x1b]11;?\x1b\\");
+        assert_eq!(writer.text(), "\x1b]11;rgb:1010/2020/3030\x07");
+
+        config.terminal.reply_terminator = ReplyTerminator::St;
+        term.update_config(&config);
+        let writer = feed(&mut term, b"\x1b]11;?\x07");
+        assert_eq!(writer.text(), "\x1b]11;rgb:1010/2020/3030\x1b\\");
+    }
+
+    #[test]
+    fn alt_screen_mid_wrapped_line() {
+        let mut term = TermBuilder::new(5, 3).build();
+        feed(&mut term, b"\x1b[1mabcde\x1b[?1049h\x1b[0;31mxy\x1b[?7l\x1b[?2004h\x1b[?1049l");
+        assert!(term.mode().contains(TermMode::LINE_WRAP | TermMode::BRACKETED_PASTE));
+
+        feed(&mut term, b"f");
+        assert_snapshot(&term, "abcde\nf\n-- cursor 1:1\n0:0-4 BOLD\n1:0-0 BOLD");
+
+        // The alternate screen keeps its own modes
+        feed(&mut term, b"\x1b[?1049h");
+        assert!(!term.mode().contains(TermMode::LINE_WRAP));
+        assert!(term.mode().contains(TermMode::BRACKETED_PASTE));
+    }
+
+    #[test]
+    fn saved_cursor_per_screen() {
+        let mut term = TermBuilder::new(5, 3).build();
+        feed(&mut term, b"\x1b[2;3H\x1b7\x1b[?1049h\x1b[3;1H\x1b7\x1b[?1049l");
+        assert_eq!(term.cursor().point, Point::new(Line(1), Column(2)));
+
+        feed(&mut term, b"\x1b[?1049h\x1b[H\x1b8");
+        assert_eq!(term.cursor().point, Point::new(Line(2), Column(0)));
+    }
+
+    #[test]
+    fn combining_mark_at_line_end() {
+        let mut term = TermBuilder::new(4, 3).build();
+        feed(&mut term, "abcd\u{301}e\r\nab\u{65e5}\u{301}".as_bytes());
+
+        assert_snapshot(&term, "abcd\u{301}\ne\nab\u{65e5}\u{301}\n-- cursor 2:3");
+    }
+
+    #[test]
+    fn erase_chars_in_flag() {
+        let mut term = TermBuilder::new(8, 2).build();
+        feed(&mut term, "a\u{1f1fa}\u{1f1f8}b\x1b[1;3H\x1b[X".as_bytes());
+
+        assert_snapshot(&term, "a  b\n-- cursor 0:2");
+    }
+
+    #[test]
+    fn delete_chars_in_skin_tone_emoji() {
+        let mut term = TermBuilder::new(8, 2).build();
+        feed(&mut term, "a\u{1f44d}\u{1f3fd}b\x1b[1;4H\x1b[P".as_bytes());
+
+        assert_snapshot(&term, "a  b\n-- cursor 0:3");
+        assert_eq!(term.grid()[Line(0)][Column(1)].flags, cell::Flags::empty());
+    }
+
+    #[test]
+    fn delete_chars_past_line_end() {
+        let mut term = TermBuilder::new(10, 2).build();
+        feed(&mut term, b"0123456789\x1b[1;6H\x1b[8P");
+
+        assert_snapshot(&term, "01234\n-- cursor 0:5");
+    }
+
+    #[test]
+    fn overwrite_part_of_cluster() {
+        // Shells erase the last character by moving back and writing a space
+        let mut term = TermBuilder::new(8, 2).build();
+        feed(&mut term, "e\u{301}\x08x\r\n\u{1f44d}\u{1f3fd}\x08 ".as_bytes());
+
+        assert_snapshot(&term, "x\n-- cursor 1:4");
+        assert_eq!(term.grid()[Line(1)][Column(0)].flags, cell::Flags::empty());
+    }
+
+    #[test]
+    fn linefeed_clears_pending_wrap() {
+        let mut term = TermBuilder::new(5, 3).build();
+        feed(&mut term, b"abcde\nf\x1b[3;5Hg\x1bMh");
+
+        assert_snapshot(&term, "abcde\n    h\n    g\n-- cursor 1:4");
     }
 
+    #[test]
+    fn edits_clear_pending_wrap() {
+        for edit in &["\x1b[@", "\x1b[P", "\x1b[X", "\x1b[K", "\x1b[J"] {
+            let mut term = TermBuilder::new(5, 2).build();
+            feed(&mut term, format!("abcde{}f", edit).as_bytes());
+
+            assert_snapshot(&term, "abcdf\n-- cursor 0:4");
+        }
+    }
+
+    #[test]
+    fn backspace_from_pending_wrap() {
+        let mut term = TermBuilder::new(5, 2).build();
+        feed(&mut term, b"abcde\x08f");
+
+        assert_snapshot(&term, "abcfe\n-- cursor 0:4");
+    }
+
+    #[test]
+    fn attributes_keep_pending_wrap() {
+        let mut term = TermBuilder::new(5, 2).build();
+        feed(&mut term, b"abcde\x1b[1mf");
+
+        assert_snapshot(&term, "abcde\nf\n-- cursor 1:1\n1:0-0 BOLD");
+    }
+
+    #[test]
+    fn no_autowrap_overwrites_last_column() {
+        let mut term = TermBuilder::new(5, 2).build();
+        feed(&mut term, "\x1b[?7labcdefg\u{301}".as_bytes());
+
+        assert_snapshot(&term, "abcdg\u{301}\n-- cursor 0:4");
+    }
+
+    #[test]
+    fn wide_char_at_last_column() {
+        let mut term = TermBuilder::new(5, 2).build();
+        feed(&mut term, "abcd\u{65e5}".as_bytes());
+
+        assert_snapshot(&term, "abcd\n\u{65e5}\n-- cursor 1:2");
+        assert!(term.grid()[Line(0)][Column(4)].flags.contains(cell::Flags::WRAPLINE));
+    }
+
+    #[test]
+    fn zsh_rprompt_redraw() {
+        // With a right prompt in the last column, zsh writes a space to get to the next line and
+        // then moves back to the end of the left prompt
+        let mut term = TermBuilder::new(20, 4).build();
+        feed(&mut term, b"% \x1b[15G[main] \r\x1b[A\x1b[2Cecho");
+        assert_snapshot(&term, "% echo        [main]\n-- cursor 0:6");
+
+        // The right prompt is erased once the command line reaches it
+        feed(&mut term, b"\x1b[K hello world!!\r\nhello world!!\r\n");
+        assert_snapshot(&term, "% echo hello world!!\nhello world!!\n-- cursor 2:0");
+    }
+
+    /// Terminal with a screen of 5x3 cells, which extends its background into the padding
+    fn extended_background(output: &[u8]) -> Term {
+        let mut config = Config::default();
+        config.window.extend_background = true;
+
+        let mut term = TermBuilder::new(5, 3).config(&config).build();
+        feed(&mut term, output);
+        term
+    }
+
+    #[test]
+    fn padding_of_full_screen_background() {
+        let term = extended_background(b"\x1b[44m\x1b[2J");
+        assert_eq!(term.effective_background(), term.colors()[ansi::NamedColor::Blue]);
+
+        let term = extended_background(b"\x1b[48;2;1;2;3m\x1b[2J");
+        assert_eq!(term.effective_background(), Rgb { r: 1, g: 2, b: 3 });
+
+        // Inverse cells show their foreground as background
+        let term = extended_background(b"\x1b[7m               ");
+        assert_eq!(term.effective_background(), term.foreground_color());
+    }
+
+    #[test]
+    fn padding_ignores_status_line() {
+        let term = extended_background(b"\x1b[44m\x1b[2J\x1b[3H\x1b[42m\x1b[2K");
+        assert_eq!(term.effective_background(), term.colors()[ansi::NamedColor::Blue]);
+    }
+
+    #[test]
+    fn padding_without_majority() {
+        let term = extended_background(b"");
+        assert_eq!(term.effective_background(), term.background_color());
+
+        // Only five of the twelve cells at the edges are colored
+        let term = extended_background(b"\x1b[44m\x1b[2K");
+        assert_eq!(term.effective_background(), term.background_color());
+
+        let term = extended_background(b"\x1b[44m\x1b[2K\x1b[3H\x1b[42m\x1b[2K");
+        assert_eq!(term.effective_background(), term.background_color());
+    }
+
+    #[test]
+    fn padding_follows_dynamic_background() {
+        let term = extended_background(b"\x1b]11;#102030\x07");
+        assert_eq!(term.effective_background(), Rgb { r: 0x10, g: 0x20, b: 0x30 });
+
+        let mut term = TermBuilder::new(5, 3).build();
+        feed(&mut term, b"\x1b]11;#102030\x07\x1b[44m\x1b[2J");
+        assert_eq!(term.effective_background(), Rgb { r: 0x10, g: 0x20, b: 0x30 });
+    }
+
+    /// Autowrap screen of the cursor movement tests in vttest, on a smaller screen
+    #[test]
+    fn vttest_autowrap() {
+        let (width, region) = (10, 6);
+        let left = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let right = "abcdefghijklmnopqrstuvwxyz";
+        let cup = |line: usize, col: usize| format!("\x1b[{};{}H", line, col);
+
+        let mut output = format!("autowrap\r\nin order\x1b[3;{}r\x1b[?6h", region + 3);
+        for i in 0..left.len() {
+            let (l, r) = (&left[i..=i], &right[i..=i]);
+            match i % 4 {
+                // Reference
+                0 => {
+                    output += &format!("{}{}", cup(region + 1, 1), l);
+                    output += &format!("{}{}\n", cup(region + 1, width), r);
+                },
+                // Simple wrapping, then backspace at the right margin
+                1 => {
+                    output += &format!("{}{}{}", cup(region, width), &right[i - 1..i], l);
+                    output += &format!("{}{}\x08 {}\n", cup(region + 1, width), l, r);
+                },
+                // Tab to the right margin
+                2 => {
+                    output += &format!("{}{}\x08\x08\t\t{}", cup(region + 1, width), l, r);
+                    output += &format!("{}\x08{}\n", cup(region + 1, 2), l);
+                },
+                // Newline at the right margin
+                _ => {
+                    output += &format!("{}\n{}{}", cup(region + 1, width), cup(region, 1), l);
+                    output += &format!("{}{}", cup(region, width), r);
+                },
+            }
+        }
+        output.push_str("\x1b[?6l\x1b[r\x1b[10;1H");
+
+        let mut term = TermBuilder::new(width, 12).build();
+        feed(&mut term, output.as_bytes());
+
+        assert_snapshot(
+            &term,
+            "
+autowrap
+in order
+U        u
+V        v
+W        w
+X        x
+Y        y
+Z        z
+-- cursor 9:0",
+        );
+    }
+
+    #[test]
+    fn palette_rewrite_drawn_once() {
+        let mut term = TermBuilder::new(7, 17).build();
+        let palette: String = (0..256)
+            .map(|i| format!("\x1b]4;{};rgb:{:02x}/{:02x}/01\x07", i, i, 255 - i))
+            .collect();
+
+        // The palette is split over several reads, but drawn by a single frame
+        let mut parser = ansi::Processor::new();
+        for chunk in palette.as_bytes().chunks(1024) {
+            parser.advance_bytes(&mut term, chunk, &mut MockWriter::new());
+        }
+        let now = Instant::now();
+        assert!(term.palette_delay(now).is_some());
+        assert_eq!(term.palette_delay(now + color::PALETTE_BATCH_WINDOW), None);
+        assert_eq!(term.colors[255usize], Rgb { r: 0xff, g: 0, b: 1 });
+        term.clear_palette_burst();
+
+        // Setting the same colors again doesn't require another frame
+        feed(&mut term, palette.as_bytes());
+        assert_eq!(term.palette_delay(Instant::now()), None);
+
+        // Resetting all colors takes the same path
+        feed(&mut term, b"\x1b]104\x07");
+        assert!(term.palette_delay(Instant::now()).is_some());
+        assert_eq!(term.colors[255usize], term.original_colors[255usize]);
+    }
+
+    #[cfg(feature = "gui")]
     fn change_font_size_works(font_size: f32) {
-        let size = SizeInfo {
-            width: 21.0,
-            height: 51.0,
-            cell_width: 3.0,
-            cell_height: 3.0,
-            padding_x: 0.0,
-            padding_y: 0.0,
-            dpr: 1.0,
-        };
         let config: Config = Default::default();
-        let mut term: Term = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        let mut term = TermBuilder::new(7, 17).config(&config).build();
         term.change_font_size(font_size);
 
         let expected_font_size: Size = config.font.size + Size::new(font_size);
@@ -2373,44 +3819,56 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "gui")]
     fn increase_font_size_works() {
         change_font_size_works(10.0);
     }
 
     #[test]
+    #[cfg(feature = "gui")]
     fn decrease_font_size_works() {
         change_font_size_works(-10.0);
     }
 
     #[test]
+    #[cfg(feature = "gui")]
     fn prevent_font_below_threshold_works() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
             cell_width: 3.0,
             cell_height: 3.0,
-            padding_x: 0.0,
-            padding_y: 0.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
             dpr: 1.0,
         };
         let config: Config = Default::default();
         let mut term: Term = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
 
         term.change_font_size(-100.0);
+        assert_eq!(term.font_size, Size::new(MIN_FONT_SIZE));
 
-        let expected_font_size: Size = Size::new(FONT_SIZE_STEP);
-        assert_eq!(term.font_size, expected_font_size);
+        // Holding down the increase binding stops at the maximum size
+        for _ in 0..1000 {
+            term.change_font_size(FONT_SIZE_STEP);
+        }
+        assert_eq!(term.font_size, Size::new(MAX_FONT_SIZE));
     }
 
     #[test]
+    #[cfg(feature = "gui")]
     fn reset_font_size_works() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
             cell_width: 3.0,
             cell_height: 3.0,
-            padding_x: 0.0,
-            padding_y: 0.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
             dpr: 1.0,
         };
         let config: Config = Default::default();
@@ -2424,19 +3882,589 @@ mod tests {
     }
 
     #[test]
-    fn clear_saved_lines() {
+    #[cfg(feature = "gui")]
+    fn update_font_keeps_changed_size() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
             cell_width: 3.0,
             cell_height: 3.0,
-            padding_x: 0.0,
-            padding_y: 0.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
             dpr: 1.0,
         };
-        let config: Config = Default::default();
+        let mut config: Config = Default::default();
         let mut term: Term = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
 
+        // Other font options don't reset the size
+        term.change_font_size(10.0);
+        config.font.offset.x = 1;
+        term.update_font(&config);
+        assert_eq!(term.font_size, config.font.size + Size::new(10.0));
+
+        // The changed size also wins over a new size in the config
+        config.font.size = Size::new(20.0);
+        term.update_font(&config);
+        assert_eq!(term.font_size, Size::new(21.0));
+
+        // Resets use the size of the latest config
+        term.reset_font_size();
+        assert_eq!(term.font_size, Size::new(20.0));
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn update_font_replaces_unchanged_size() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+        let mut config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+
+        config.font.size = Size::new(20.0);
+        term.update_font(&config);
+        assert_eq!(term.font_size, Size::new(20.0));
+
+        // A reset makes the size follow the config again
+        term.change_font_size(10.0);
+        term.reset_font_size();
+        config.font.size = Size::new(15.0);
+        term.update_font(&config);
+        assert_eq!(term.font_size, Size::new(15.0));
+    }
+
+    #[test]
+    fn cursor_style_precedence() {
+        use crate::ansi::CursorStyle::*;
+
+        let cases = [
+            (None, None, true, Block),
+            (Some(Beam), None, true, Beam),
+            (None, Some(HollowBlock), true, HollowBlock),
+            (Some(Beam), Some(HollowBlock), true, HollowBlock),
+            (None, None, false, Hidden),
+            (Some(Beam), None, false, Hidden),
+            (None, Some(HollowBlock), false, Hidden),
+            (Some(Beam), Some(HollowBlock), false, Hidden),
+        ];
+
+        for &(app, unfocused, visible, expected) in cases.iter() {
+            assert_eq!(resolve_cursor_style(Block, app, unfocused, visible), expected);
+        }
+    }
+
+    #[test]
+    fn cursor_style_reset_while_unfocused() {
+        let config: Config = serde_yaml::from_str("cursor:\n  style: Underline\n").unwrap();
+        let mut term = TermBuilder::new(5, 1).config(&config).build();
+
+        feed(&mut term, b"\x1b[6 q");
+        assert_eq!(term.rendered_cursor_style(&config, true), CursorStyle::Beam);
+        assert_eq!(term.rendered_cursor_style(&config, false), CursorStyle::HollowBlock);
+
+        // DECSCUSR 0 goes back to the config default once the window is focused again
+        feed(&mut term, b"\x1b[0 q");
+        assert_eq!(term.rendered_cursor_style(&config, false), CursorStyle::HollowBlock);
+        assert_eq!(term.rendered_cursor_style(&config, true), CursorStyle::Underline);
+
+        feed(&mut term, b"\x1b[?25l");
+        assert_eq!(term.rendered_cursor_style(&config, true), CursorStyle::Hidden);
+    }
+
+    #[test]
+    fn visible_cursor() {
+        let mut config = Config::default();
+        config.scrolling.set_history(10);
+        let mut term = TermBuilder::new(5, 2).config(&config).build();
+        let has_cursor = |term: &Term| {
+            term.renderable_cells(&config, true).any(|cell| match cell.inner {
+                RenderableCellContent::Cursor(_) => true,
+                _ => false,
+            })
+        };
+
+        feed(&mut term, b"a\r\nb\r\ncd");
+        assert_eq!(term.visible_cursor(), Some(Point::new(Line(1), Column(2))));
+        assert!(has_cursor(&term));
+
+        // Hidden by the application
+        feed(&mut term, b"\x1b[?25l");
+        assert_eq!(term.visible_cursor(), None);
+        assert!(!has_cursor(&term));
+
+        // Scrolled out of view
+        feed(&mut term, b"\x1b[?25h");
+        term.scroll_display(Scroll::Lines(1));
+        assert_eq!(term.visible_cursor(), None);
+
+        term.scroll_display(Scroll::Bottom);
+        assert_eq!(term.visible_cursor(), Some(Point::new(Line(1), Column(2))));
+        assert!(has_cursor(&term));
+    }
+
+    /// Column and cursor key of the cursor after writing `text`
+    fn render_cursor(text: &str) -> (Column, CursorKey) {
+        let config = Config::default();
+        let mut term = TermBuilder::new(5, 1).config(&config).build();
+        feed(&mut term, text.as_bytes());
+
+        term.renderable_cells(&config, true)
+            .find_map(|cell| match cell.inner {
+                RenderableCellContent::Cursor(key) => Some((cell.column, key)),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn cursor_on_wide_char() {
+        let (column, key) = render_cursor("a\u{4e2d}b\x1b[2G");
+        assert_eq!(column, Column(1));
+        assert!(key.is_wide);
+
+        let (column, key) = render_cursor("a\u{4e2d}b\x1b[4G");
+        assert_eq!(column, Column(3));
+        assert!(!key.is_wide);
+    }
+
+    #[test]
+    fn cursor_on_wide_char_spacer() {
+        let (column, key) = render_cursor("a\u{4e2d}b\x1b[3G");
+        assert_eq!(column, Column(1));
+        assert!(key.is_wide);
+    }
+
+    #[test]
+    fn cursor_on_wide_char_in_last_column() {
+        // The wide char fills the last two columns, the cursor waits on its spacer
+        let (column, key) = render_cursor("abc\u{4e2d}");
+        assert_eq!(column, Column(3));
+        assert!(key.is_wide);
+
+        // Without space for the spacer, the wide char wraps and the cursor follows it
+        let (column, key) = render_cursor("abcd\u{4e2d}");
+        assert_eq!(column, Column(2));
+        assert!(!key.is_wide);
+
+        // Without autowrap there's no space for the spacer, the cursor isn't widened past the grid
+        let (column, key) = render_cursor("\x1b[?7labcd\u{4e2d}");
+        assert_eq!(column, Column(4));
+        assert!(!key.is_wide);
+    }
+
+    /// Colors of the cursor and of the first cell after writing `bytes` and moving to it
+    ///
+    /// Returns the cursor color, if the cursor is visible, and the text and background colors.
+    fn cursor_cell_colors(
+        config: &Config,
+        bytes: &[u8],
+        selected: bool,
+    ) -> (Option<Rgb>, Rgb, Rgb) {
+        let mut term = TermBuilder::new(5, 1).config(config).build();
+        feed(&mut term, bytes);
+        feed(&mut term, b"\r");
+        if selected {
+            *term.selection_mut() = Some(Selection::lines(Point { line: 0, col: Column(0) }));
+        }
+
+        let cells: Vec<_> =
+            term.renderable_cells(config, true).filter(|cell| cell.column == Column(0)).collect();
+        let cursor = cells.iter().find_map(|cell| match cell.inner {
+            RenderableCellContent::Cursor(_) => Some(cell.fg),
+            _ => None,
+        });
+        let text = cells
+            .iter()
+            .find(|cell| match cell.inner {
+                RenderableCellContent::Chars(_) => true,
+                _ => false,
+            })
+            .unwrap();
+
+        (cursor, text.fg, text.bg)
+    }
+
+    static SELECTION_LIKE_CURSOR: &str = "
+colors:
+  cursor:
+    cursor: '0x4040ff'
+  selection:
+    background: '0x4040ff'
+    text: '0xffffff'
+";
+
+    #[test]
+    fn cursor_on_inverted_selection() {
+        let config = Config::default();
+        let fg = config.colors.primary.foreground;
+        let bg = config.colors.primary.background;
+
+        // Selected cells are inverted, the block cursor is drawn in the selected text color
+        assert_eq!(cursor_cell_colors(&config, b"abc", true), (Some(bg), fg, bg));
+        assert_eq!(cursor_cell_colors(&config, b"abc", false), (Some(fg), bg, fg));
+
+        // Inverse text is inverted by the selection once and by the cursor again
+        assert_eq!(cursor_cell_colors(&config, b"\x1b[7mabc", true), (Some(fg), bg, fg));
+    }
+
+    #[test]
+    fn cursor_on_explicit_selection() {
+        let config: Config = serde_yaml::from_str(SELECTION_LIKE_CURSOR).unwrap();
+        let cursor = Rgb { r: 0x40, g: 0x40, b: 0xff };
+
+        // The cursor color would vanish in the selection, the selected text color is used instead
+        assert_eq!(
+            cursor_cell_colors(&config, b"abc", true),
+            (Some(color::WHITE), cursor, color::WHITE)
+        );
+
+        let bg = config.colors.primary.background;
+        assert_eq!(cursor_cell_colors(&config, b"abc", false), (Some(cursor), bg, cursor));
+    }
+
+    #[test]
+    fn cursor_text_contrast() {
+        let config: Config = serde_yaml::from_str(
+            "colors:\n  cursor:\n    cursor: '0xffffff'\n    text: '0xfefefe'\n",
+        )
+        .unwrap();
+        let bg = config.colors.primary.background;
+
+        // Text in almost the cursor color falls back to the cell background
+        assert_eq!(
+            cursor_cell_colors(&config, b"abc", false),
+            (Some(color::WHITE), bg, color::WHITE)
+        );
+
+        // Neither the cursor color nor the text color stand out from a white background
+        let text = Rgb { r: 0xfe, g: 0xfe, b: 0xfe };
+        let white_bg = b"\x1b[48;2;255;255;255mabc";
+        assert_eq!(
+            cursor_cell_colors(&config, white_bg, false),
+            (Some(color::BLACK), text, color::BLACK)
+        );
+    }
+
+    #[test]
+    fn cursor_color_escape() {
+        let config = Config::default();
+        let bg = config.colors.primary.background;
+
+        let (cursor, text, _) = cursor_cell_colors(&config, b"\x1b]12;#ff0000\x07abc", true);
+        assert_eq!(cursor, Some(color::RED));
+        assert_eq!(text, config.colors.primary.foreground);
+
+        let (cursor, text, _) = cursor_cell_colors(&config, b"\x1b]12;#ff0000\x07abc", false);
+        assert_eq!(cursor, Some(color::RED));
+        assert_eq!(text, bg);
+    }
+
+    #[test]
+    fn hidden_cursor_on_selection() {
+        let config = Config::default();
+        let fg = config.colors.primary.foreground;
+        let bg = config.colors.primary.background;
+        assert_eq!(cursor_cell_colors(&config, b"\x1b[?25labc", true), (None, bg, fg));
+
+        let config: Config = serde_yaml::from_str(SELECTION_LIKE_CURSOR).unwrap();
+        let selection = Rgb { r: 0x40, g: 0x40, b: 0xff };
+        assert_eq!(
+            cursor_cell_colors(&config, b"\x1b[?25labc", true),
+            (None, color::WHITE, selection)
+        );
+    }
+
+    /// Foreground color of the first cell after writing `bytes`
+    fn first_fg(config: &Config, bytes: &[u8]) -> Rgb {
+        let mut term = TermBuilder::new(5, 1).config(config).build();
+        feed(&mut term, bytes);
+        term.renderable_cells(config, false).find(|cell| cell.column == Column(0)).unwrap().fg
+    }
+
+    #[test]
+    fn dim_truecolor() {
+        let config = Config::default();
+        let rgb = Rgb { r: 200, g: 100, b: 50 };
+        assert_eq!(first_fg(&config, b"\x1b[38;2;200;100;50ma"), rgb);
+        assert_eq!(first_fg(&config, b"\x1b[2;38;2;200;100;50ma"), rgb * 0.66);
+    }
+
+    #[test]
+    fn dim_indexed() {
+        let config = Config::default();
+        let colors = color::List::from(&config.colors);
+        assert_eq!(first_fg(&config, b"\x1b[2;38;5;196ma"), colors[196u8] * 0.66);
+        assert_eq!(first_fg(&config, b"\x1b[2;38;5;1ma"), colors[ansi::NamedColor::DimRed]);
+        assert_eq!(first_fg(&config, b"\x1b[2;38;5;9ma"), colors[ansi::NamedColor::Red]);
+    }
+
+    #[test]
+    fn dim_bold_named() {
+        let config = Config::default();
+        let colors = color::List::from(&config.colors);
+        assert_eq!(first_fg(&config, b"\x1b[1;31ma"), colors[ansi::NamedColor::BrightRed]);
+        assert_eq!(first_fg(&config, b"\x1b[1;2;31ma"), colors[ansi::NamedColor::DimRed]);
+        assert_eq!(first_fg(&config, b"\x1b[2;1ma"), colors[ansi::NamedColor::DimForeground]);
+    }
+
+    #[test]
+    fn dim_factor() {
+        let config: Config = serde_yaml::from_str("colors:\n  dim_factor: 0.5\n").unwrap();
+        let colors = color::List::from(&config.colors);
+        assert_eq!(first_fg(&config, b"\x1b[2;38;2;200;100;50ma"), Rgb { r: 100, g: 50, b: 25 });
+        assert_eq!(first_fg(&config, b"\x1b[2;38;5;196ma"), colors[196u8] * 0.5);
+        assert_eq!(colors[ansi::NamedColor::DimRed], colors[ansi::NamedColor::Red] * 0.5);
+    }
+
+    /// Resolved colors and background opacity of the text at `point`
+    fn resolved_colors(term: &Term, point: Point) -> (Rgb, Rgb, f32) {
+        let config = Config::default();
+        let cell = term
+            .renderable_cells(&config, false)
+            .filter(|cell| match cell.inner {
+                RenderableCellContent::Chars(_) => true,
+                RenderableCellContent::Cursor(_) => false,
+            })
+            .find(|cell| cell.line == point.line && cell.column == point.col)
+            .unwrap();
+        (cell.fg, cell.bg, cell.bg_alpha)
+    }
+
+    #[test]
+    fn reverse_video_swaps_colors() {
+        let mut term = TermBuilder::new(5, 1).build();
+        let colors = *term.colors();
+        let foreground = colors[ansi::NamedColor::Foreground];
+        let background = colors[ansi::NamedColor::Background];
+        let first = Point::new(Line(0), Column(0));
+
+        feed(&mut term, b"a\r");
+        assert_eq!(resolved_colors(&term, first), (foreground, background, 0.));
+
+        feed(&mut term, b"\x1b[?5h");
+        assert!(term.mode().contains(TermMode::REVERSE_VIDEO));
+        assert_eq!(resolved_colors(&term, first), (background, foreground, 0.));
+        assert_eq!(term.background_color(), foreground);
+        assert_eq!(term.foreground_color(), background);
+
+        // Explicit colors are swapped as well and inverse cells are shown like normal ones
+        feed(&mut term, b"\x1b[31;44ma\x1b[0;7ma\r");
+        let red = colors[ansi::NamedColor::Red];
+        let blue = colors[ansi::NamedColor::Blue];
+        assert_eq!(resolved_colors(&term, first), (blue, red, 1.));
+        assert_eq!(
+            resolved_colors(&term, Point::new(Line(0), Column(1))),
+            (foreground, background, 1.)
+        );
+
+        // Only the resolved colors change, not the cells
+        assert_eq!(term.grid()[Line(0)][Column(0)].fg, ansi::Color::Named(ansi::NamedColor::Red));
+
+        feed(&mut term, b"\x1b[?5l");
+        assert_eq!(resolved_colors(&term, first), (red, blue, 1.));
+        assert_eq!(term.background_color(), background);
+    }
+
+    #[test]
+    fn reverse_video_selection() {
+        let mut term = TermBuilder::new(5, 1).build();
+        feed(&mut term, b"\x1b[?5ha\r");
+        *term.selection_mut() = Some(Selection::lines(Point { line: 0, col: Column(0) }));
+
+        // The selection inverts the reversed colors again
+        let colors = term.colors();
+        let expected = (colors[ansi::NamedColor::Foreground], colors[ansi::NamedColor::Background]);
+        let (fg, bg, _) = resolved_colors(&term, Point::new(Line(0), Column(0)));
+        assert_eq!((fg, bg), expected);
+    }
+
+    #[test]
+    fn reverse_video_reset() {
+        let mut term = TermBuilder::new(5, 1).build();
+        feed(&mut term, b"\x1b[?5h\x1bc");
+        assert!(!term.mode().contains(TermMode::REVERSE_VIDEO));
+        assert_eq!(term.background_color(), term.colors()[ansi::NamedColor::Background]);
+    }
+
+    #[test]
+    fn vttest_reverse_video_screen() {
+        let mut term = TermBuilder::new(20, 3).build();
+        let colors = *term.colors();
+        let foreground = colors[ansi::NamedColor::Foreground];
+        let background = colors[ansi::NamedColor::Background];
+        let text = Point::new(Line(0), Column(0));
+
+        feed(&mut term, b"\x1b[2J\x1b[?5h\x1b[HReverse video");
+        assert_eq!(resolved_colors(&term, text), (background, foreground, 0.));
+        assert_eq!(term.background_color(), foreground);
+
+        feed(&mut term, b"\x1b[2J\x1b[?5l\x1b[HNormal video");
+        assert_eq!(resolved_colors(&term, text), (foreground, background, 0.));
+        assert_eq!(term.background_color(), background);
+        assert_snapshot(&term, "Normal video\n-- cursor 0:12");
+    }
+
+    fn scheme_term(config: &Config) -> Term {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        };
+        Term::new(config, size, MessageBuffer::new(), Clipboard::new_nop())
+    }
+
+    fn scheme_config() -> Config {
+        let yaml = "
+schemes:
+  dark:
+    primary:
+      foreground: '0x110000'
+  light:
+    primary:
+      foreground: '0xff0000'
+";
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn color_scheme_drops_escape_colors() {
+        let config = scheme_config();
+        let mut term = scheme_term(&config);
+        let foreground = ansi::NamedColor::Foreground as usize;
+        let blue = ansi::NamedColor::Blue as usize;
+
+        term.set_color(blue, Rgb { r: 1, g: 2, b: 3 });
+        term.set_color_scheme("light");
+
+        assert_eq!(term.color_scheme(), Some("light"));
+        assert_eq!(term.colors[foreground], Rgb { r: 0xff, g: 0, b: 0 });
+        assert_eq!(term.colors[blue], term.original_colors[blue]);
+        assert!(term.message_buffer_mut().message().is_some());
+
+        // Colors set after the switch are kept on config reload
+        term.set_color(foreground, Rgb { r: 1, g: 2, b: 3 });
+        term.update_colors(&config);
+
+        assert_eq!(term.color_scheme(), Some("light"));
+        assert_eq!(term.colors[foreground], Rgb { r: 1, g: 2, b: 3 });
+    }
+
+    #[test]
+    fn unknown_color_scheme() {
+        let config = scheme_config();
+        let mut term = scheme_term(&config);
+        let foreground = ansi::NamedColor::Foreground as usize;
+
+        term.set_color(foreground, Rgb { r: 1, g: 2, b: 3 });
+        term.set_color_scheme("missing");
+
+        assert_eq!(term.color_scheme(), None);
+        assert_eq!(term.colors[foreground], Rgb { r: 1, g: 2, b: 3 });
+        assert!(term.message_buffer_mut().message().is_none());
+    }
+
+    #[test]
+    fn cycle_color_scheme() {
+        let config = scheme_config();
+        let mut term = scheme_term(&config);
+
+        term.cycle_color_scheme();
+        assert_eq!(term.color_scheme(), Some("dark"));
+        term.cycle_color_scheme();
+        assert_eq!(term.color_scheme(), Some("light"));
+        term.cycle_color_scheme();
+        assert_eq!(term.color_scheme(), Some("dark"));
+        assert_eq!(term.colors[ansi::NamedColor::Foreground], Rgb { r: 0x11, g: 0, b: 0 });
+    }
+
+    #[cfg(feature = "gui")]
+    fn font_family_config(families: &str) -> Config {
+        let yaml = format!("font:\n  normal:\n    family: B\n  families: {}\n", families);
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn cycle_font_family() {
+        let config = font_family_config("[A, B, C]");
+        let mut term = scheme_term(&config);
+        let now = Instant::now();
+
+        assert_eq!(term.font_family(), None);
+        term.cycle_font_family(now);
+        assert_eq!(term.font_family(), Some("C"));
+        term.cycle_font_family(now);
+        assert_eq!(term.font_family(), Some("A"));
+        term.cycle_font_family(now);
+        assert_eq!(term.font_family(), Some("B"));
+
+        let mut message = Message::new("Font: B".into(), color::YELLOW);
+        message.set_topic(FONT_FAMILY_TOPIC.into());
+        assert_eq!(term.message_buffer_mut().message(), Some(message));
+
+        // Starts with the first family if the configured one isn't listed
+        let mut term = scheme_term(&font_family_config("[X, Y]"));
+        term.cycle_font_family(now);
+        assert_eq!(term.font_family(), Some("X"));
+
+        // Nothing to switch to without families
+        let mut term = scheme_term(&Config::default());
+        term.cycle_font_family(now);
+        assert_eq!(term.font_family(), None);
+        assert!(term.message_buffer_mut().message().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn font_family_notice_expires() {
+        let config = font_family_config("[A, B]");
+        let mut term = scheme_term(&config);
+        let now = Instant::now();
+
+        term.cycle_font_family(now);
+        term.expire_font_family_notice(now + FONT_FAMILY_NOTICE_DURATION / 2);
+        assert!(term.message_buffer_mut().message().is_some());
+
+        term.expire_font_family_notice(now + FONT_FAMILY_NOTICE_DURATION);
+        assert!(term.message_buffer_mut().message().is_none());
+        assert_eq!(term.font_family(), Some("A"));
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn font_family_reset_on_changed_families() {
+        let config = font_family_config("[A, B, C]");
+        let mut term = scheme_term(&config);
+        term.cycle_font_family(Instant::now());
+
+        // Unchanged list keeps the active family
+        term.update_font(&font_family_config("[A, B, C]"));
+        assert_eq!(term.font_family(), Some("C"));
+
+        term.update_font(&font_family_config("[A, C]"));
+        assert_eq!(term.font_family(), None);
+    }
+
+    #[test]
+    fn clear_saved_lines() {
+        let mut term = TermBuilder::new(7, 17).build();
+
         // Add one line of scrollback
         term.grid.scroll_up(&(Line(0)..Line(1)), Line(1), &Cell::default());
 
@@ -2448,6 +4476,159 @@ mod tests {
         scrolled_grid.scroll_display(Scroll::Top);
         assert_eq!(term.grid, scrolled_grid);
     }
+
+    fn asymmetric_size() -> SizeInfo {
+        SizeInfo {
+            width: 100.0,
+            height: 100.0,
+            cell_width: 10.0,
+            cell_height: 20.0,
+            padding_left: 5.0,
+            padding_right: 15.0,
+            padding_top: 30.0,
+            padding_bottom: 10.0,
+            dpr: 1.0,
+        }
+    }
+
+    #[test]
+    fn asymmetric_padding_grid_dimensions() {
+        let size = asymmetric_size();
+
+        assert_eq!(size.cols(), Column(8));
+        assert_eq!(size.lines(), Line(3));
+    }
+
+    #[test]
+    fn asymmetric_padding_pixels_to_coords() {
+        let size = asymmetric_size();
+
+        assert_eq!(size.pixels_to_coords(5, 30), Point::new(Line(0), Column(0)));
+        assert_eq!(size.pixels_to_coords(14, 49), Point::new(Line(0), Column(0)));
+        assert_eq!(size.pixels_to_coords(15, 50), Point::new(Line(1), Column(1)));
+        assert_eq!(size.pixels_to_coords(84, 89), Point::new(Line(2), Column(7)));
+
+        // Padding is clamped to the closest cell
+        assert_eq!(size.pixels_to_coords(0, 0), Point::new(Line(0), Column(0)));
+        assert_eq!(size.pixels_to_coords(99, 99), Point::new(Line(2), Column(7)));
+    }
+
+    #[test]
+    fn asymmetric_padding_contains_point() {
+        let size = asymmetric_size();
+
+        assert!(size.contains_point(5, 30, false));
+        assert!(size.contains_point(84, 89, false));
+        assert!(!size.contains_point(4, 30, false));
+        assert!(!size.contains_point(5, 29, false));
+        assert!(!size.contains_point(85, 50, false));
+        assert!(!size.contains_point(50, 90, false));
+        assert!(size.contains_point(99, 99, true));
+    }
+
+    #[test]
+    fn size_info_symmetric_padding_serde() {
+        let serialized = r#"{"width":100.0,"height":100.0,"cell_width":10.0,"cell_height":20.0,
+                             "padding_x":3.0,"padding_y":4.0}"#;
+        let size: SizeInfo = serde_json::from_str(serialized).unwrap();
+
+        assert_eq!((size.padding_left, size.padding_right), (3.0, 3.0));
+        assert_eq!((size.padding_top, size.padding_bottom), (4.0, 4.0));
+    }
+
+    #[test]
+    fn osc52_clipboard_targets() {
+        let config: Config = Default::default();
+        let size = asymmetric_size();
+        let mut term = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_memory());
+
+        term.set_clipboard(b'c', "clipboard");
+        term.set_clipboard(b'p', "primary");
+        assert_eq!(term.clipboard().load(ClipboardType::Clipboard), "clipboard");
+        assert_eq!(term.clipboard().load(ClipboardType::Selection), "primary");
+
+        term.set_clipboard(b's', "selection");
+        assert_eq!(term.clipboard().load(ClipboardType::Selection), "selection");
+
+        // Cut buffers are not supported
+        term.set_clipboard(b'0', "cut buffer");
+        assert_eq!(term.clipboard().load(ClipboardType::Clipboard), "clipboard");
+        assert_eq!(term.clipboard().load(ClipboardType::Selection), "selection");
+    }
+
+    #[test]
+    fn window_opacity_escape() {
+        let mut config: Config = Default::default();
+        let size = asymmetric_size();
+        let mut term = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+
+        // Disabled by default
+        term.set_window_opacity(Some(0.5));
+        assert_eq!(term.next_window_opacity, None);
+
+        config.window.opacity_escape = true;
+        term.update_config(&config);
+
+        term.set_window_opacity(Some(0.5));
+        assert_eq!(term.next_window_opacity.take(), Some(OpacityChange::Set(0.5)));
+
+        term.set_window_opacity(None);
+        assert_eq!(term.next_window_opacity.take(), Some(OpacityChange::Reset));
+    }
+
+    #[test]
+    fn app_mouse_cursor() {
+        let mut term = TermBuilder::new(10, 5).build();
+
+        feed(&mut term, b"\x1b]22;crosshair\x07");
+        assert_eq!(term.get_next_mouse_cursor(), Some(MouseCursorIcon::Crosshair));
+
+        // Hovering over text and enabling mouse reporting keep the shape
+        term.reset_mouse_cursor();
+        assert_eq!(term.get_next_mouse_cursor(), Some(MouseCursorIcon::Crosshair));
+        feed(&mut term, b"\x1b[?1000h");
+        assert_eq!(term.get_next_mouse_cursor(), Some(MouseCursorIcon::Crosshair));
+
+        // Unknown names are ignored
+        feed(&mut term, b"\x1b]22;spinner\x07");
+        assert_eq!(term.get_next_mouse_cursor(), None);
+
+        feed(&mut term, b"\x1b]22;default\x07");
+        assert_eq!(term.get_next_mouse_cursor(), Some(MouseCursorIcon::Default));
+        feed(&mut term, b"\x1b[?1000l");
+        assert_eq!(term.get_next_mouse_cursor(), Some(MouseCursorIcon::Text));
+    }
+
+    #[test]
+    fn app_mouse_cursor_reset_by_leaving_alt_screen() {
+        let mut term = TermBuilder::new(10, 5).build();
+
+        feed(&mut term, b"\x1b[?1049h\x1b]22;pointer\x07");
+        assert_eq!(term.get_next_mouse_cursor(), Some(MouseCursorIcon::Hand));
+
+        feed(&mut term, b"\x1b[?1049l");
+        assert_eq!(term.get_next_mouse_cursor(), Some(MouseCursorIcon::Text));
+        assert_eq!(term.app_mouse_cursor(), None);
+    }
+
+    #[test]
+    fn scroll_status_escape() {
+        let mut config = Config::default();
+        config.scrolling.set_history(1000);
+        let mut term = TermBuilder::new(10, 5).config(&config).build();
+        feed(&mut term, "\r\n".repeat(204).as_bytes());
+        term.scroll_display(Scroll::Lines(100));
+
+        // Disabled by default
+        assert_eq!(feed(&mut term, b"\x1b]5522;?\x07").bytes(), b"");
+
+        config.scrolling.status_escape = true;
+        term.update_config(&config);
+
+        assert_eq!(feed(&mut term, b"\x1b]5522;?\x07").text(), "\x1b]5522;200;100\x07");
+        assert_eq!(feed(&mut term, b"\x1b]5522;?\x1b\\").text(), "\x1b]5522;200;100\x1b\\");
+        assert_eq!((term.history_lines(), term.display_offset()), (200, 100));
+    }
 }
 
 #[cfg(all(test, feature = "bench"))]
@@ -0,0 +1,525 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Saving and restoring the state of a terminal
+//!
+//! A session starts with `MAGIC` and the little endian `VERSION` of the format, followed by the
+//! bincode encoded state. Sessions of other versions are rejected instead of being converted.
+use std::borrow::Cow;
+use std::cmp::min;
+use std::io::{self, Read, Write};
+
+use crate::ansi;
+use crate::grid::Grid;
+use crate::index::{Line, Point};
+use crate::term::cell::Cell;
+use crate::term::color::{self, Rgb};
+use crate::term::mode::TermMode;
+use crate::term::{Cursor, CursorSave, SizeInfo, TabStops, Term};
+
+/// Start of every saved session
+pub const MAGIC: &[u8] = b"alacritty-session";
+
+/// Version of the session format, increased with every incompatible change
+pub const VERSION: u32 = 2;
+
+/// Maximum number of bytes decoded from a session, far above the size of a full history
+const MAX_STATE_SIZE: u64 = 1 << 30;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Error reading or writing the session
+    Io(io::Error),
+
+    /// Error encoding or decoding the terminal state
+    Encoding(bincode::Error),
+
+    /// The data doesn't start with `MAGIC`
+    NotASession,
+
+    /// The session was saved with a different version of the format
+    Version(u32),
+
+    /// The saved grids don't have a valid size or layout
+    InvalidGrid,
+}
+
+impl ::std::error::Error for Error {
+    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::Encoding(ref err) => Some(err),
+            Error::NotASession | Error::Version(_) | Error::InvalidGrid => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref err) => err.description(),
+            Error::Encoding(ref err) => err.description(),
+            Error::NotASession => "not a saved terminal session",
+            Error::Version(_) => "unsupported session version",
+            Error::InvalidGrid => "invalid grid in session",
+        }
+    }
+}
+
+impl ::std::fmt::Display for Error {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        match *self {
+            Error::Io(ref err) => err.fmt(f),
+            Error::Encoding(ref err) => err.fmt(f),
+            Error::NotASession => write!(f, "Not a saved terminal session"),
+            Error::Version(version) => {
+                write!(f, "Unsupported session version {}, expected {}", version, VERSION)
+            },
+            Error::InvalidGrid => write!(f, "Invalid grid in session"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(val: io::Error) -> Error {
+        Error::Io(val)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(val: bincode::Error) -> Error {
+        Error::Encoding(val)
+    }
+}
+
+/// Position and attributes of a cursor, the character sets aren't saved
+#[derive(Serialize, Deserialize)]
+struct SavedCursor {
+    point: Point,
+    template: Cell,
+}
+
+impl SavedCursor {
+    /// Cursor inside a grid of `grid`'s size
+    fn restore(&self, grid: &Grid<Cell>) -> Cursor {
+        let mut point = self.point;
+        point.line = min(point.line, grid.num_lines() - 1);
+        point.col = min(point.col, grid.num_cols() - 1);
+        Cursor { point, template: self.template, ..Default::default() }
+    }
+}
+
+impl<'a> From<&'a Cursor> for SavedCursor {
+    fn from(cursor: &'a Cursor) -> SavedCursor {
+        SavedCursor { point: cursor.point, template: cursor.template }
+    }
+}
+
+/// Everything written to a session
+///
+/// The grids are borrowed while saving, so the scrollback doesn't have to be copied.
+#[derive(Serialize, Deserialize)]
+struct State<'a> {
+    grid: Cow<'a, Grid<Cell>>,
+    alt_grid: Cow<'a, Grid<Cell>>,
+    alt: bool,
+    cursor: SavedCursor,
+    cursor_save: SavedCursor,
+    cursor_save_alt: SavedCursor,
+
+    /// Bits of the modes set by escape sequences
+    mode: u32,
+
+    /// Bits of the modes of the screen which isn't shown
+    inactive_screen_mode: u32,
+
+    /// Colors changed by escape sequences
+    colors: Vec<(usize, Rgb)>,
+
+    title: Option<String>,
+    tabs: Cow<'a, [bool]>,
+    scroll_region: (Line, Line),
+}
+
+impl Term {
+    /// Write the terminal state to `writer`
+    ///
+    /// This includes both screens with the scrollback history, the cursors, the modes, the
+    /// colors changed by escape sequences, the title and the tab stops.
+    pub fn serialize_state<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let colors = (0..color::COUNT)
+            .filter(|&index| self.color_modified[index])
+            .map(|index| (index, self.colors[index]))
+            .collect();
+
+        let state = State {
+            grid: Cow::Borrowed(&self.grid),
+            alt_grid: Cow::Borrowed(&self.alt_grid),
+            alt: self.alt,
+            cursor: SavedCursor::from(&self.cursor),
+            cursor_save: SavedCursor::from(&self.cursor_save.cursor),
+            cursor_save_alt: SavedCursor::from(&self.cursor_save_alt.cursor),
+            mode: (self.mode - TermMode::UI).bits(),
+            inactive_screen_mode: self.inactive_screen_mode.bits(),
+            colors,
+            title: self.title.clone(),
+            tabs: Cow::Borrowed(&self.tabs.tabs),
+            scroll_region: (self.scroll_region.start, self.scroll_region.end),
+        };
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, &state)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Replace the terminal state with a session written by `serialize_state`
+    ///
+    /// The history limits of the current configuration are kept. When the saved grid doesn't
+    /// match `size`, it's resized like the window was resized.
+    pub fn restore_state<R: Read>(&mut self, reader: R, size: &SizeInfo) -> Result<(), Error> {
+        let state = read_state(reader)?;
+
+        let mut grid = state.grid.into_owned();
+        let mut alt_grid = state.alt_grid.into_owned();
+        if state.alt {
+            keep_history_limits(&mut alt_grid, self.primary_grid());
+        } else {
+            keep_history_limits(&mut grid, self.primary_grid());
+        }
+        grid.selection = None;
+        grid.url_highlight = None;
+        alt_grid.selection = None;
+        alt_grid.url_highlight = None;
+
+        self.cursor = state.cursor.restore(&grid);
+        self.cursor_save =
+            CursorSave { cursor: state.cursor_save.restore(&grid), ..Default::default() };
+        self.cursor_save_alt =
+            CursorSave { cursor: state.cursor_save_alt.restore(&grid), ..Default::default() };
+        self.input_needs_wrap = false;
+        self.active_charset = Default::default();
+
+        self.tabs = if state.tabs.len() == grid.num_cols().0 {
+            TabStops { tabs: state.tabs.into_owned() }
+        } else {
+            TabStops::new(grid.num_cols(), self.tabspaces)
+        };
+
+        let (start, end) = state.scroll_region;
+        self.scroll_region = if start < end && end <= grid.num_lines() {
+            start..end
+        } else {
+            Line(0)..grid.num_lines()
+        };
+
+        self.mode =
+            (self.mode & TermMode::UI) | (TermMode::from_bits_truncate(state.mode) - TermMode::UI);
+        self.inactive_screen_mode =
+            TermMode::from_bits_truncate(state.inactive_screen_mode) & TermMode::SCREEN;
+
+        self.colors = self.original_colors;
+        self.color_modified = [false; color::COUNT];
+        for (index, rgb) in state.colors.into_iter().filter(|&(index, _)| index < color::COUNT) {
+            self.colors[index] = rgb;
+            self.color_modified[index] = true;
+        }
+
+        // The title is passed to the window system, so it's bounded like the OSC title
+        self.title = state.title.map(|title| ansi::sanitize_title(&title));
+        if self.dynamic_title {
+            self.next_title = self.title.clone();
+        }
+
+        self.grid = grid;
+        self.alt_grid = alt_grid;
+        self.alt = state.alt;
+
+        self.resize(size);
+        self.content_changed();
+
+        Ok(())
+    }
+
+    /// Restore the primary screen of a session as scrollback history
+    ///
+    /// This is meant for a terminal which hasn't received any output yet. The saved lines up to
+    /// the cursor are moved into the history, so a new shell starts on an empty screen below the
+    /// previous content. Nothing but the content is restored.
+    pub fn restore_history<R: Read>(&mut self, reader: R, size: &SizeInfo) -> Result<(), Error> {
+        let state = read_state(reader)?;
+
+        // The primary screen's cursor is saved while the alternate screen is active
+        let (grid, cursor) = if state.alt {
+            (state.alt_grid, state.cursor_save)
+        } else {
+            (state.grid, state.cursor)
+        };
+
+        let mut grid = grid.into_owned();
+        keep_history_limits(&mut grid, self.primary_grid());
+        grid.selection = None;
+        grid.url_highlight = None;
+
+        self.cursor.point = cursor.restore(&grid).point;
+        self.grid = grid;
+        self.resize(size);
+
+        let region = Line(0)..self.grid.num_lines();
+        let lines = self.cursor.point.line + 1;
+        self.grid.scroll_up(&region, lines, &Cell::default());
+        self.cursor.point = Point::default();
+        self.content_changed();
+
+        Ok(())
+    }
+
+    /// Grid of the primary screen, which has the scrollback history
+    fn primary_grid(&self) -> &Grid<Cell> {
+        if self.alt {
+            &self.alt_grid
+        } else {
+            &self.grid
+        }
+    }
+}
+
+/// Read the state of a session in the current format
+fn read_state<R: Read>(mut reader: R) -> Result<State<'static>, Error> {
+    let mut magic = vec![0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(Error::NotASession);
+    }
+
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != VERSION {
+        return Err(Error::Version(version));
+    }
+
+    let state: State<'static> = bincode::config().limit(MAX_STATE_SIZE).deserialize_from(reader)?;
+
+    // Lots of math assumes both grids are consistent and have the same size
+    if !state.grid.is_valid()
+        || !state.alt_grid.is_valid()
+        || state.alt_grid.num_lines() != state.grid.num_lines()
+        || state.alt_grid.num_cols() != state.grid.num_cols()
+    {
+        return Err(Error::InvalidGrid);
+    }
+
+    Ok(state)
+}
+
+/// Apply the history limits of the current configuration to a restored grid
+fn keep_history_limits(grid: &mut Grid<Cell>, current: &Grid<Cell>) {
+    let template = Cell::default();
+
+    // Updating the history allocates all of it, so it's only done if the limit changed
+    if grid.max_scroll_limit() != current.max_scroll_limit() {
+        grid.update_history(current.max_scroll_limit(), &template);
+    }
+
+    grid.update_history_memory(current.max_history_memory(), &template);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::ansi::{self, Handler};
+    use crate::config::Config;
+    use crate::grid::Grid;
+    use crate::index::{Column, Line, Point};
+    use crate::term::cell::Cell;
+    use crate::term::color::Rgb;
+    use crate::term::mode::TermMode;
+    use crate::term::Term;
+    use crate::testing::{feed, size_info as size, TermBuilder};
+
+    use super::{Error, SavedCursor, State, MAGIC, VERSION};
+
+    fn terminal(cols: usize, lines: usize) -> Term {
+        let mut config = Config::default();
+        config.scrolling.set_history(100);
+        TermBuilder::new(cols, lines).config(&config).build()
+    }
+
+    fn session(bytes: &[u8]) -> (Term, Vec<u8>) {
+        let mut term = terminal(10, 4);
+        feed(&mut term, bytes);
+
+        let mut session = Vec::new();
+        term.serialize_state(&mut session).unwrap();
+        (term, session)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (term, session) = session(
+            b"1\r\n2\r\n3\r\n4\r\n\x1b[31m5\x1b]2;title\x07\x1b]4;1;rgb:01/02/03\x07\x1b[?1h\x1b[3g",
+        );
+
+        let mut restored = terminal(10, 4);
+        restored.restore_state(&session[..], &size(10, 4)).unwrap();
+
+        assert_eq!(restored.grid(), term.grid());
+        assert_eq!(restored.grid().scroll_limit(), 1);
+        assert_eq!(restored.cursor.point, term.cursor.point);
+        assert_eq!(restored.cursor.template, term.cursor.template);
+        assert!(restored.mode().contains(TermMode::APP_CURSOR));
+        assert_eq!(restored.colors()[1usize], Rgb { r: 1, g: 2, b: 3 });
+        assert!(restored.color_modified[1]);
+        assert_eq!(restored.title(), Some("title"));
+        assert_eq!(restored.get_next_title(), Some(String::from("title")));
+        assert!(restored.tabs.tabs.iter().all(|&tab| !tab));
+    }
+
+    #[test]
+    fn round_trip_alt_screen() {
+        let (term, session) = session(b"main\x1b[?7l\x1b[?1049halt");
+
+        let mut restored = terminal(10, 4);
+        restored.restore_state(&session[..], &size(10, 4)).unwrap();
+
+        assert!(restored.alt);
+        assert_eq!(restored.grid(), term.grid());
+        assert_eq!(restored.alt_grid, term.alt_grid);
+
+        restored.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert_eq!(restored.grid()[Line(0)][Column(0)].c, 'm');
+        assert_eq!(restored.cursor.point, Point::new(Line(0), Column(4)));
+        assert!(!restored.mode().contains(TermMode::LINE_WRAP));
+    }
+
+    #[test]
+    fn restore_different_size() {
+        let (_, session) = session(b"1\r\n2\r\n3\r\n4\r\n0123456789");
+
+        let mut restored = terminal(5, 2);
+        restored.restore_state(&session[..], &size(5, 2)).unwrap();
+
+        assert_eq!(restored.grid().num_lines(), Line(2));
+        assert_eq!(restored.grid().num_cols(), Column(5));
+        assert_eq!(restored.tabs.tabs.len(), 5);
+        assert!(restored.cursor.point.line < Line(2));
+        assert!(restored.cursor.point.col < Column(5));
+
+        // The long line is reflowed instead of truncated
+        assert_eq!(restored.grid()[Line(0)][Column(0)].c, '0');
+        assert_eq!(restored.grid()[Line(1)][Column(0)].c, '5');
+    }
+
+    #[test]
+    fn restore_history() {
+        let (_, session) = session(b"1\r\n2\r\n$ ");
+
+        let mut restored = terminal(10, 4);
+        restored.restore_history(&session[..], &size(10, 4)).unwrap();
+
+        assert_eq!(restored.cursor.point, Point::default());
+        assert_eq!(restored.grid().scroll_limit(), 3);
+        assert!(restored.grid()[Line(0)].is_empty());
+        assert_eq!(restored.grid()[6][Column(0)].c, '1');
+        assert_eq!(restored.grid()[4][Column(0)].c, '$');
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let (_, mut session) = session(b"content");
+        let version = MAGIC.len()..MAGIC.len() + 4;
+        session[version].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+        let mut restored = terminal(10, 4);
+        match restored.restore_state(&session[..], &size(10, 4)) {
+            Err(Error::Version(version)) => assert_eq!(version, VERSION + 1),
+            result => panic!("Expected version mismatch, got {:?}", result),
+        }
+        assert!(restored.grid()[Line(0)].is_empty());
+    }
+
+    #[test]
+    fn invalid_grid() {
+        let (term, _) = session(b"content");
+        let mut grid = serde_json::to_value(term.grid()).unwrap();
+        grid["raw"]["zero"] = 100.into();
+        let grid: Grid<Cell> = serde_json::from_value(grid).unwrap();
+
+        let state = State {
+            grid: Cow::Owned(grid),
+            alt_grid: Cow::Borrowed(&term.alt_grid),
+            alt: false,
+            cursor: SavedCursor::from(&term.cursor),
+            cursor_save: SavedCursor::from(&term.cursor),
+            cursor_save_alt: SavedCursor::from(&term.cursor),
+            mode: 0,
+            inactive_screen_mode: 0,
+            colors: Vec::new(),
+            title: None,
+            tabs: Cow::Borrowed(&term.tabs.tabs),
+            scroll_region: (Line(0), Line(4)),
+        };
+        let mut session = MAGIC.to_vec();
+        session.extend_from_slice(&VERSION.to_le_bytes());
+        bincode::serialize_into(&mut session, &state).unwrap();
+
+        let mut restored = terminal(10, 4);
+        match restored.restore_state(&session[..], &size(10, 4)) {
+            Err(Error::InvalidGrid) => (),
+            result => panic!("Expected invalid grid, got {:?}", result),
+        }
+        assert!(restored.grid()[Line(0)].is_empty());
+    }
+
+    #[test]
+    fn restore_sanitizes_title() {
+        let (term, _) = session(b"content");
+        let title = format!("a\x1bb{}", "c".repeat(ansi::MAX_TITLE_LENGTH));
+        let state = State {
+            grid: Cow::Borrowed(term.grid()),
+            alt_grid: Cow::Borrowed(&term.alt_grid),
+            alt: false,
+            cursor: SavedCursor::from(&term.cursor),
+            cursor_save: SavedCursor::from(&term.cursor),
+            cursor_save_alt: SavedCursor::from(&term.cursor),
+            mode: 0,
+            inactive_screen_mode: 0,
+            colors: Vec::new(),
+            title: Some(title),
+            tabs: Cow::Borrowed(&term.tabs.tabs),
+            scroll_region: (Line(0), Line(4)),
+        };
+        let mut session = MAGIC.to_vec();
+        session.extend_from_slice(&VERSION.to_le_bytes());
+        bincode::serialize_into(&mut session, &state).unwrap();
+
+        let mut restored = terminal(10, 4);
+        restored.restore_state(&session[..], &size(10, 4)).unwrap();
+
+        let title = restored.title().unwrap();
+        assert!(title.starts_with("abc"));
+        assert_eq!(title.chars().count(), ansi::MAX_TITLE_LENGTH);
+    }
+
+    #[test]
+    fn not_a_session() {
+        let mut restored = terminal(10, 4);
+        match restored.restore_state(&b"alacritty-recording 1\n"[..], &size(10, 4)) {
+            Err(Error::NotASession) => (),
+            result => panic!("Expected invalid session, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,667 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for testing code which drives a `Term` without a window
+//!
+//! Output is parsed by a terminal with `feed`, its screen is then compared to a snapshot:
+//!
+//! ```
+//! use alacritty_terminal::testing::{assert_snapshot, feed, TermBuilder};
+//!
+//! let mut term = TermBuilder::new(10, 3).build();
+//! feed(&mut term, b"\x1b[1mbold\x1b[0m\r\nplain");
+//!
+//! assert_snapshot(&term, "
+//! bold
+//! plain
+//! -- cursor 1:5
+//! 0:0-3 BOLD
+//! ");
+//! ```
+//!
+//! The crate's own tests can always use this module, other crates enable it with the `testing`
+//! feature.
+use std::borrow::Cow;
+use std::cmp::{self, max};
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use crate::ansi::{
+    Attr, CharsetIndex, ClearMode, Color, CursorStyle, Handler, LineClearMode, Mode,
+    MouseCursorIcon, NamedColor, Processor, StandardCharset, StringTerminator, TabulationClearMode,
+    TermInfo,
+};
+use crate::clipboard::Clipboard;
+use crate::config::Config;
+use crate::index::{Column, Line, Point};
+use crate::message_bar::MessageBuffer;
+use crate::term::cell::{Cell, Flags};
+use crate::term::color::{self, Rgb};
+use crate::term::{SizeInfo, Term};
+
+/// Size of a window without padding, which fits exactly `cols` and `lines`
+pub fn size_info(cols: usize, lines: usize) -> SizeInfo {
+    SizeInfo {
+        width: cols as f32 * 10.,
+        height: lines as f32 * 20.,
+        cell_width: 10.,
+        cell_height: 20.,
+        padding_left: 0.,
+        padding_right: 0.,
+        padding_top: 0.,
+        padding_bottom: 0.,
+        dpr: 1.,
+    }
+}
+
+/// Builder for a terminal with a specific grid size
+///
+/// ```
+/// use alacritty_terminal::clipboard::Clipboard;
+/// use alacritty_terminal::config::Config;
+/// use alacritty_terminal::testing::TermBuilder;
+///
+/// let mut config = Config::default();
+/// config.scrolling.set_history(100);
+///
+/// let term = TermBuilder::new(80, 24).config(&config).clipboard(Clipboard::new_memory()).build();
+/// assert_eq!(term.grid().num_cols().0, 80);
+/// assert_eq!(term.grid().num_lines().0, 24);
+/// ```
+pub struct TermBuilder<'a> {
+    cols: usize,
+    lines: usize,
+    config: Option<&'a Config>,
+    clipboard: Option<Clipboard>,
+}
+
+impl<'a> TermBuilder<'a> {
+    pub fn new(cols: usize, lines: usize) -> TermBuilder<'a> {
+        TermBuilder { cols, lines, config: None, clipboard: None }
+    }
+
+    /// Configuration of the terminal, the default config is used without it
+    pub fn config(mut self, config: &'a Config) -> TermBuilder<'a> {
+        self.config = Some(config);
+        self
+    }
+
+    /// Clipboard of the terminal, everything copied is discarded without it
+    pub fn clipboard(mut self, clipboard: Clipboard) -> TermBuilder<'a> {
+        self.clipboard = Some(clipboard);
+        self
+    }
+
+    pub fn build(self) -> Term {
+        let size = size_info(self.cols, self.lines);
+        let clipboard = self.clipboard.unwrap_or_else(Clipboard::new_nop);
+        match self.config {
+            Some(config) => Term::new(config, size, MessageBuffer::new(), clipboard),
+            None => Term::new(&Config::default(), size, MessageBuffer::new(), clipboard),
+        }
+    }
+}
+
+/// Stand-in for the pty, which collects everything the terminal writes
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MockWriter {
+    bytes: Vec<u8>,
+}
+
+impl MockWriter {
+    pub fn new() -> MockWriter {
+        Default::default()
+    }
+
+    #[inline]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Written bytes as text, invalid UTF-8 is replaced
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.bytes)
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+}
+
+impl io::Write for MockWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.bytes.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Parse `bytes` as output of the shell, returning what the terminal wrote back
+///
+/// Every call uses a new parser, so escape sequences can't be split between calls.
+///
+/// ```
+/// use alacritty_terminal::testing::{feed, TermBuilder};
+///
+/// let mut term = TermBuilder::new(10, 3).build();
+/// let reply = feed(&mut term, b"ab\x1b[6n");
+/// assert_eq!(reply.text(), "\x1b[1;3R");
+/// ```
+pub fn feed(term: &mut Term, bytes: &[u8]) -> MockWriter {
+    let mut writer = MockWriter::new();
+    Processor::new().advance_bytes(term, bytes, &mut writer);
+    writer
+}
+
+/// Text and attributes of the screen of a terminal
+///
+/// It's displayed as one line of text for every line of the screen, without trailing whitespace
+/// and empty lines at the bottom. Then the cursor position follows, and the runs of cells with
+/// other than the default colors or flags as `line:first-last` columns:
+///
+/// ```text
+/// $ ls
+/// -- cursor 0:4
+/// 0:0-0 fg=Red BOLD
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermSnapshot {
+    pub lines: Vec<String>,
+    pub cursor: Point,
+    pub attributes: Vec<String>,
+}
+
+impl TermSnapshot {
+    pub fn new(term: &Term) -> TermSnapshot {
+        let grid = term.grid();
+        let mut lines = Vec::new();
+        let mut attributes = Vec::new();
+
+        for line in 0..grid.num_lines().0 {
+            let row = &grid[Line(line)];
+            let mut text = String::new();
+            let mut runs: Vec<(usize, usize, String)> = Vec::new();
+
+            for col in 0..grid.num_cols().0 {
+                let cell = &row[Column(col)];
+
+                // Wide characters are written once, their spacer is part of the character
+                if !cell.flags.contains(Flags::WIDE_CHAR_SPACER) {
+                    text.push(if cell.c == '\t' { ' ' } else { cell.c });
+                    text.extend(cell.extra.iter().take_while(|&&c| c != ' '));
+                }
+
+                let description = match describe(cell) {
+                    Some(description) => description,
+                    None => continue,
+                };
+                match runs.last_mut() {
+                    Some((_, end, last)) if *end + 1 == col && *last == description => *end = col,
+                    _ => runs.push((col, col, description)),
+                }
+            }
+
+            lines.push(text.trim_end().to_owned());
+            attributes.extend(runs.into_iter().map(|(start, end, description)| {
+                format!("{}:{}-{} {}", line, start, end, description)
+            }));
+        }
+
+        while lines.last().map_or(false, String::is_empty) {
+            lines.pop();
+        }
+
+        TermSnapshot { lines, cursor: term.cursor().point, attributes }
+    }
+}
+
+impl Display for TermSnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        write!(f, "-- cursor {}:{}", self.cursor.line.0, self.cursor.col.0)?;
+
+        for attribute in &self.attributes {
+            write!(f, "\n{}", attribute)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Colors and flags of a cell which differ from an empty cell
+fn describe(cell: &Cell) -> Option<String> {
+    let mut parts = Vec::new();
+
+    if cell.fg != Color::Named(NamedColor::Foreground) {
+        parts.push(format!("fg={}", color_name(cell.fg)));
+    }
+
+    if cell.bg != Color::Named(NamedColor::Background) {
+        parts.push(format!("bg={}", color_name(cell.bg)));
+    }
+
+    // Wrapping and wide characters are already visible in the text
+    let flags = cell.flags - (Flags::WRAPLINE | Flags::WIDE_CHAR | Flags::WIDE_CHAR_SPACER);
+    if !flags.is_empty() {
+        parts.push(format!("{:?}", flags));
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" "))
+    }
+}
+
+fn color_name(color: Color) -> String {
+    match color {
+        Color::Named(name) => format!("{:?}", name),
+        Color::Spec(rgb) => format!("#{:02x}{:02x}{:02x}", rgb.r, rgb.g, rgb.b),
+        Color::Indexed(index) => index.to_string(),
+    }
+}
+
+/// Compare the screen of `term` to the `Display` output of its `TermSnapshot`
+///
+/// Newlines at the start and end of `expected` are ignored.
+///
+/// # Panics
+///
+/// Panics with the lines which differ if the screen doesn't match the snapshot.
+pub fn assert_snapshot(term: &Term, expected: &str) {
+    let actual = TermSnapshot::new(term).to_string();
+    if let Some(diff) = diff(expected.trim_matches('\n'), &actual) {
+        panic!("Terminal doesn't match the snapshot (-expected +actual):\n{}\n", diff);
+    }
+}
+
+/// Difference between the lines of two texts, `None` if all lines are equal
+///
+/// Lines only in `expected` start with `-`, lines only in `actual` with `+`, and lines in both
+/// with a space.
+///
+/// ```
+/// use alacritty_terminal::testing::diff;
+///
+/// assert_eq!(diff("a\nb\nc", "a\nc"), Some(String::from(" a\n-b\n c")));
+/// assert_eq!(diff("a\nb", "a\nb"), None);
+/// ```
+pub fn diff(expected: &str, actual: &str) -> Option<String> {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // Length of the longest common subsequence of `expected[i..]` and `actual[j..]`
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                max(common[i + 1][j], common[i][j + 1])
+            };
+        }
+    }
+
+    let mut output = Vec::new();
+    let mut changed = false;
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            output.push(format!(" {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            output.push(format!("-{}", expected[i]));
+            changed = true;
+            i += 1;
+        } else {
+            output.push(format!("+{}", actual[j]));
+            changed = true;
+            j += 1;
+        }
+    }
+
+    if changed {
+        Some(output.join("\n"))
+    } else {
+        None
+    }
+}
+
+/// Handler which forwards every call to a terminal, panicking once it gets into a bad state
+///
+/// This is what the fuzz targets in `alacritty_terminal/fuzz` drive, their seeds are also
+/// checked by the crate's tests.
+pub struct CheckedTerm {
+    term: Term,
+    status_reports: usize,
+}
+
+macro_rules! checked {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(&mut self, $($arg: $ty),*) {
+                Handler::$method(&mut self.term, $($arg),*);
+                self.check();
+            }
+        )*
+    };
+}
+
+impl CheckedTerm {
+    pub fn new(term: Term) -> CheckedTerm {
+        CheckedTerm { term, status_reports: 0 }
+    }
+
+    #[inline]
+    pub fn term(&self) -> &Term {
+        &self.term
+    }
+
+    /// Resize the grid, keeping the cell size
+    pub fn resize(&mut self, cols: usize, lines: usize) {
+        self.term.resize(&size_info(cols, lines));
+        self.check();
+    }
+
+    /// Assert that the cursor is within the grid
+    pub fn check(&self) {
+        let point = self.term.cursor().point;
+        let grid = self.term.grid();
+        assert!(
+            point.line < grid.num_lines() && point.col < grid.num_cols(),
+            "cursor at {}:{} outside of {}x{} grid",
+            point.line.0,
+            point.col.0,
+            grid.num_cols().0,
+            grid.num_lines().0,
+        );
+    }
+
+    /// Assert that the parser returns to the ground state, whatever it is parsing right now
+    ///
+    /// CAN aborts every sequence, so a status report right after it has to be answered.
+    pub fn check_ground(&mut self, parser: &mut Processor) {
+        let reports = self.status_reports;
+        parser.advance_bytes(self, b"\x18\x1b[5n", &mut io::sink());
+        assert_eq!(self.status_reports, reports + 1, "parser didn't return to the ground state");
+    }
+
+    fn check_color(index: usize) {
+        assert!(index < color::COUNT, "color index {} out of range", index);
+    }
+}
+
+impl TermInfo for CheckedTerm {
+    fn lines(&self) -> Line {
+        self.term.lines()
+    }
+
+    fn cols(&self) -> Column {
+        self.term.cols()
+    }
+}
+
+impl Handler for CheckedTerm {
+    checked! {
+        set_title(title: &str);
+        set_mouse_cursor(cursor: Option<MouseCursorIcon>);
+        set_cursor_style(style: Option<CursorStyle>);
+        input(c: char);
+        goto(line: Line, col: Column);
+        goto_line(line: Line);
+        goto_col(col: Column);
+        insert_blank(count: Column);
+        move_up(lines: Line);
+        move_down(lines: Line);
+        move_forward(cols: Column);
+        move_backward(cols: Column);
+        move_down_and_cr(lines: Line);
+        move_up_and_cr(lines: Line);
+        put_tab(count: i64);
+        backspace();
+        carriage_return();
+        linefeed();
+        bell();
+        substitute();
+        newline();
+        set_horizontal_tabstop();
+        scroll_up(lines: Line);
+        scroll_down(lines: Line);
+        insert_blank_lines(count: Line);
+        delete_lines(count: Line);
+        erase_chars(count: Column);
+        delete_chars(count: Column);
+        move_backward_tabs(count: i64);
+        move_forward_tabs(count: i64);
+        save_cursor_position();
+        restore_cursor_position();
+        clear_line(mode: LineClearMode);
+        clear_screen(mode: ClearMode);
+        clear_tabs(mode: TabulationClearMode);
+        reset_state();
+        reverse_index();
+        terminal_attribute(attr: Attr);
+        set_mode(mode: Mode);
+        unset_mode(mode: Mode);
+        set_scrolling_region(region: Range<Line>);
+        set_keypad_application_mode();
+        unset_keypad_application_mode();
+        set_active_charset(index: CharsetIndex);
+        configure_charset(index: CharsetIndex, charset: StandardCharset);
+        set_clipboard(clipboard: u8, text: &str);
+        set_window_opacity(opacity: Option<f32>);
+        set_working_directory(path: PathBuf);
+        dectest();
+    }
+
+    fn identify_terminal(&mut self, writer: &mut dyn io::Write) {
+        self.term.identify_terminal(writer);
+        self.check();
+    }
+
+    fn device_status(&mut self, writer: &mut dyn io::Write, arg: usize) {
+        self.status_reports += 1;
+        self.term.device_status(writer, arg);
+        self.check();
+    }
+
+    fn set_color(&mut self, index: usize, color: Rgb) {
+        Self::check_color(index);
+        self.term.set_color(index, color);
+    }
+
+    fn dynamic_color_sequence(
+        &mut self,
+        writer: &mut dyn io::Write,
+        code: u8,
+        index: usize,
+        terminator: StringTerminator,
+    ) {
+        Self::check_color(index);
+        self.term.dynamic_color_sequence(writer, code, index, terminator);
+    }
+
+    fn indexed_color_sequence(
+        &mut self,
+        writer: &mut dyn io::Write,
+        index: usize,
+        terminator: StringTerminator,
+    ) {
+        Self::check_color(index);
+        self.term.indexed_color_sequence(writer, index, terminator);
+    }
+
+    fn scroll_status(&mut self, writer: &mut dyn io::Write, terminator: StringTerminator) {
+        self.term.scroll_status(writer, terminator);
+    }
+
+    fn reset_color(&mut self, index: usize) {
+        Self::check_color(index);
+        self.term.reset_color(index);
+    }
+
+    fn clipboard_size_limit(&self) -> usize {
+        self.term.clipboard_size_limit()
+    }
+
+    fn clipboard_lossy_utf8(&self) -> bool {
+        self.term.clipboard_lossy_utf8()
+    }
+}
+
+/// Byte which starts a resize in the input of `fuzz_term`, it never occurs in UTF-8 text
+pub const FUZZ_RESIZE: u8 = 0xff;
+
+/// Parse arbitrary bytes one at a time, checking the terminal after every call of the parser
+pub fn fuzz_parser(data: &[u8]) {
+    let mut terminal = CheckedTerm::new(TermBuilder::new(80, 24).build());
+    let mut parser = Processor::new();
+    for &byte in data {
+        parser.advance(&mut terminal, byte, &mut io::sink());
+    }
+    terminal.check_ground(&mut parser);
+}
+
+/// Parse arbitrary bytes interleaved with resizes
+///
+/// Every `FUZZ_RESIZE` is followed by one byte for the new number of columns and one for the
+/// lines, everything else is pty output. Recordings without resizes are valid input as is.
+pub fn fuzz_term(data: &[u8]) {
+    let mut terminal = CheckedTerm::new(TermBuilder::new(80, 24).build());
+    let mut parser = Processor::new();
+
+    let mut chunks = data.split(|&byte| byte == FUZZ_RESIZE);
+    if let Some(output) = chunks.next() {
+        parser.advance_bytes(&mut terminal, output, &mut io::sink());
+    }
+    for chunk in chunks {
+        let (size, output) = chunk.split_at(cmp::min(chunk.len(), 2));
+        if let [cols, lines] = *size {
+            terminal.resize(usize::from(cols), usize::from(lines));
+        }
+        parser.advance_bytes(&mut terminal, output, &mut io::sink());
+    }
+
+    terminal.check_ground(&mut parser);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use std::path::Path;
+
+    use crate::index::{Column, Line, Point};
+
+    use super::{
+        assert_snapshot, diff, feed, fuzz_parser, fuzz_term, MockWriter, TermBuilder, TermSnapshot,
+        FUZZ_RESIZE,
+    };
+
+    #[test]
+    fn diff_changed_line() {
+        let expected = " a\n-b\n+x\n c";
+        assert_eq!(diff("a\nb\nc", "a\nx\nc"), Some(String::from(expected)));
+    }
+
+    #[test]
+    fn diff_inserted_and_removed_lines() {
+        assert_eq!(diff("a\nc", "a\nb\nc"), Some(String::from(" a\n+b\n c")));
+        assert_eq!(diff("a\nb\nc", "c"), Some(String::from("-a\n-b\n c")));
+        assert_eq!(diff("", "a"), Some(String::from("+a")));
+    }
+
+    #[test]
+    fn diff_equal_lines() {
+        assert_eq!(diff("a\nb", "a\nb"), None);
+        assert_eq!(diff("a\nb\n", "a\nb"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "(-expected +actual):\n one\n-two\n+too\n -- cursor 1:3\n")]
+    fn assert_snapshot_shows_diff() {
+        let mut term = TermBuilder::new(10, 3).build();
+        feed(&mut term, b"one\r\ntoo");
+
+        assert_snapshot(&term, "one\ntwo\n-- cursor 1:3");
+    }
+
+    #[test]
+    fn snapshot_attributes() {
+        let mut term = TermBuilder::new(12, 4).build();
+        feed(
+            &mut term,
+            b"\x1b[31;1mab\x1b[0m \x1b[44;38;2;1;2;3mcd\x1b[0m\r\n\t\xe4\xb8\x80\x1b[7m!",
+        );
+
+        let snapshot = TermSnapshot::new(&term);
+        assert_eq!(snapshot.lines, vec!["ab cd", "        \u{4e00}!"]);
+        assert_eq!(snapshot.cursor, Point::new(Line(1), Column(11)));
+        assert_eq!(
+            snapshot.attributes,
+            vec!["0:0-1 fg=Red BOLD", "0:3-4 fg=#010203 bg=Blue", "1:10-10 INVERSE"]
+        );
+    }
+
+    #[test]
+    fn mock_writer_collects_replies() {
+        let mut term = TermBuilder::new(10, 3).build();
+        let mut writer = feed(&mut term, b"\x1b[5n");
+        assert_eq!(writer.bytes(), b"\x1b[0n");
+
+        writer.clear();
+        writer.write_all(b"\xffok").unwrap();
+        assert_eq!(writer.text(), "\u{fffd}ok");
+        assert_eq!(MockWriter::new().bytes(), b"");
+    }
+
+    #[test]
+    fn fuzz_seeds() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let recordings = fs::read_dir(root.join("tests/ref"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path().join("alacritty.recording"));
+        let seeds =
+            fs::read_dir(root.join("fuzz/seeds")).unwrap().map(|entry| entry.unwrap().path());
+
+        for path in recordings.chain(seeds) {
+            let data = fs::read(path).unwrap();
+            fuzz_parser(&data);
+            fuzz_term(&data);
+        }
+    }
+
+    #[test]
+    fn fuzz_unterminated_sequences() {
+        for data in &[&b"\x1b]2;title"[..], b"\x1bP1$qm", b"\x1b_apc", b"\x1b[1;2", b"\xf0\x9f"] {
+            fuzz_parser(data);
+        }
+    }
+
+    #[test]
+    fn fuzz_tiny_and_empty_sizes() {
+        let data = [b'a', FUZZ_RESIZE, 0, 0, b'b', FUZZ_RESIZE, 1, 1, b'\t', b'c', FUZZ_RESIZE, 3];
+        fuzz_term(&data);
+    }
+}
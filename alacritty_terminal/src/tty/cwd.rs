@@ -0,0 +1,253 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Discovery of the working directory of the processes running in a pty
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Access to the processes running in a pty
+pub trait Processes {
+    /// Process ID of the shell which was started in the pty
+    fn shell_pid(&self) -> Option<u32>;
+
+    /// Process group ID of the pty's foreground job
+    fn foreground_pid(&self) -> Option<u32>;
+
+    /// Current working directory of a process
+    fn cwd(&self, pid: u32) -> io::Result<PathBuf>;
+}
+
+/// Find the directory the user is most likely working in
+///
+/// The directory of the foreground job is preferred, since the shell's directory is stale while
+/// a program like an editor changes directories. If it can't be read, the directory `reported`
+/// by the shell with OSC 7 is used, then the directory of the shell process itself.
+pub fn working_directory<P: Processes>(processes: &P, reported: Option<&Path>) -> Option<PathBuf> {
+    let cwd = |pid: u32| match processes.cwd(pid) {
+        Ok(cwd) => Some(cwd),
+        Err(err) => {
+            debug!("Unable to read working directory of process {}: {}", pid, err);
+            None
+        },
+    };
+
+    processes
+        .foreground_pid()
+        .and_then(cwd)
+        .or_else(|| reported.map(Path::to_path_buf))
+        .or_else(|| processes.shell_pid().and_then(cwd))
+}
+
+/// Processes of a pty, queried from the operating system
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PtyProcesses {
+    #[cfg(not(windows))]
+    shell: libc::pid_t,
+    #[cfg(not(windows))]
+    fd: std::os::unix::io::RawFd,
+}
+
+#[cfg(not(windows))]
+impl PtyProcesses {
+    pub fn new(shell: libc::pid_t, fd: std::os::unix::io::RawFd) -> Self {
+        PtyProcesses { shell, fd }
+    }
+}
+
+#[cfg(not(windows))]
+impl Processes for PtyProcesses {
+    fn shell_pid(&self) -> Option<u32> {
+        Some(self.shell as u32).filter(|&pid| pid > 0)
+    }
+
+    fn foreground_pid(&self) -> Option<u32> {
+        match unsafe { libc::tcgetpgrp(self.fd) } {
+            pgid if pgid > 0 => Some(pgid as u32),
+            _ => {
+                debug!("Unable to get foreground process: {}", io::Error::last_os_error());
+                None
+            },
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn cwd(&self, pid: u32) -> io::Result<PathBuf> {
+        #[cfg(not(target_os = "freebsd"))]
+        let proc_prefix = "";
+        #[cfg(target_os = "freebsd")]
+        let proc_prefix = "/compat/linux";
+
+        std::fs::read_link(format!("{}/proc/{}/cwd", proc_prefix, pid))
+    }
+
+    #[cfg(target_os = "macos")]
+    fn cwd(&self, pid: u32) -> io::Result<PathBuf> {
+        macos::cwd(pid)
+    }
+}
+
+#[cfg(windows)]
+impl Processes for PtyProcesses {
+    fn shell_pid(&self) -> Option<u32> {
+        None
+    }
+
+    fn foreground_pid(&self) -> Option<u32> {
+        None
+    }
+
+    fn cwd(&self, _pid: u32) -> io::Result<PathBuf> {
+        Err(io::Error::new(io::ErrorKind::Other, "unsupported on Windows"))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{CStr, OsStr};
+    use std::io;
+    use std::mem;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+
+    const PROC_PIDVNODEPATHINFO: c_int = 9;
+    const MAXPATHLEN: usize = 1024;
+
+    /// Path of a vnode, `struct vnode_info_path` in `sys/proc_info.h`
+    #[repr(C)]
+    struct VnodeInfoPath {
+        vip_vi: [u8; 152],
+        vip_path: [c_char; MAXPATHLEN],
+    }
+
+    /// Current and root directory of a process, `struct proc_vnodepathinfo`
+    #[repr(C)]
+    struct ProcVnodePathInfo {
+        pvi_cdir: VnodeInfoPath,
+        pvi_rdir: VnodeInfoPath,
+    }
+
+    extern "C" {
+        fn proc_pidinfo(
+            pid: c_int,
+            flavor: c_int,
+            arg: u64,
+            buffer: *mut c_void,
+            buffersize: c_int,
+        ) -> c_int;
+    }
+
+    pub fn cwd(pid: u32) -> io::Result<PathBuf> {
+        let mut info: ProcVnodePathInfo = unsafe { mem::zeroed() };
+        let size = mem::size_of::<ProcVnodePathInfo>() as c_int;
+
+        let written = unsafe {
+            proc_pidinfo(
+                pid as c_int,
+                PROC_PIDVNODEPATHINFO,
+                0,
+                &mut info as *mut _ as *mut c_void,
+                size,
+            )
+        };
+
+        if written <= 0 {
+            return Err(io::Error::last_os_error());
+        } else if written < size {
+            return Err(io::Error::new(io::ErrorKind::Other, "truncated process info"));
+        }
+
+        let path = unsafe { CStr::from_ptr(info.pvi_cdir.vip_path.as_ptr()) };
+        Ok(PathBuf::from(OsStr::from_bytes(path.to_bytes())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use super::{working_directory, Processes};
+
+    /// Process table with fixed working directories
+    #[derive(Default)]
+    struct MockProcesses {
+        shell: Option<u32>,
+        foreground: Option<u32>,
+        cwds: HashMap<u32, io::Result<PathBuf>>,
+    }
+
+    impl Processes for MockProcesses {
+        fn shell_pid(&self) -> Option<u32> {
+            self.shell
+        }
+
+        fn foreground_pid(&self) -> Option<u32> {
+            self.foreground
+        }
+
+        fn cwd(&self, pid: u32) -> io::Result<PathBuf> {
+            match self.cwds.get(&pid) {
+                Some(Ok(cwd)) => Ok(cwd.clone()),
+                Some(Err(err)) => Err(io::Error::new(err.kind(), "mock error")),
+                None => Err(io::Error::new(io::ErrorKind::NotFound, "process exited")),
+            }
+        }
+    }
+
+    fn processes() -> MockProcesses {
+        let mut cwds = HashMap::new();
+        cwds.insert(1, Ok(PathBuf::from("/home/user")));
+        cwds.insert(2, Ok(PathBuf::from("/home/user/project")));
+        cwds.insert(3, Err(io::Error::new(io::ErrorKind::PermissionDenied, "")));
+
+        MockProcesses { shell: Some(1), foreground: Some(2), cwds }
+    }
+
+    #[test]
+    fn foreground_process_is_preferred() {
+        let reported = Path::new("/home/user/reported");
+        let cwd = working_directory(&processes(), Some(reported));
+        assert_eq!(cwd, Some(PathBuf::from("/home/user/project")));
+    }
+
+    #[test]
+    fn reported_directory_when_foreground_exited() {
+        let processes = MockProcesses { foreground: Some(4), ..processes() };
+        let reported = Path::new("/home/user/reported");
+        let cwd = working_directory(&processes, Some(reported));
+        assert_eq!(cwd, Some(PathBuf::from("/home/user/reported")));
+    }
+
+    #[test]
+    fn shell_when_permission_denied() {
+        let processes = MockProcesses { foreground: Some(3), ..processes() };
+        assert_eq!(working_directory(&processes, None), Some(PathBuf::from("/home/user")));
+    }
+
+    #[test]
+    fn shell_without_foreground_process() {
+        let processes = MockProcesses { foreground: None, ..processes() };
+        assert_eq!(working_directory(&processes, None), Some(PathBuf::from("/home/user")));
+    }
+
+    #[test]
+    fn nothing_when_all_processes_exited() {
+        let processes = MockProcesses { cwds: HashMap::new(), ..processes() };
+        assert_eq!(working_directory(&processes, None), None);
+
+        assert_eq!(working_directory(&MockProcesses::default(), None), None);
+    }
+}
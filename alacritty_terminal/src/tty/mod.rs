@@ -14,11 +14,16 @@
 //
 //! tty related functionality
 use mio;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
 use std::{env, io};
 
 use terminfo::Database;
 
 use crate::config::Config;
+use crate::term::SizeInfo;
+
+pub mod cwd;
 
 #[cfg(not(windows))]
 mod unix;
@@ -54,10 +59,10 @@ pub trait EventedReadWrite {
 }
 
 /// Events concerning TTY child processes
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum ChildEvent {
     /// Indicates the child has exited
-    Exited,
+    Exited(ExitStatus),
 }
 
 /// A pseudoterminal (or PTY)
@@ -66,16 +71,19 @@ pub enum ChildEvent {
 /// notified if the PTY child process does something we care about (other than writing to the TTY).
 /// In particular, this allows for race-free child exit notification on UNIX (cf. `SIGCHLD`).
 pub trait EventedPty: EventedReadWrite {
-    #[cfg(unix)]
     fn child_event_token(&self) -> mio::Token;
 
     /// Tries to retrieve an event
     ///
     /// Returns `Some(event)` on success, or `None` if there are no events to retrieve.
-    #[cfg(unix)]
     fn next_child_event(&mut self) -> Option<ChildEvent>;
 }
 
+/// Types that are interested in when the display is resized
+pub trait OnResize {
+    fn on_resize(&mut self, size: &SizeInfo);
+}
+
 // Setup environment variables
 pub fn setup_env(config: &Config) {
     // Default to 'alacritty' terminfo if it is available, otherwise
@@ -97,3 +105,145 @@ pub fn setup_env(config: &Config) {
         env::set_var(key, value);
     }
 }
+
+/// Directory the shell is started in, `None` to use Alacritty's working directory
+///
+/// A configured directory which doesn't exist falls back to `home` instead of failing to start
+/// the shell.
+pub fn shell_directory(configured: Option<&Path>, home: Option<PathBuf>) -> Option<PathBuf> {
+    let dir = configured?;
+    if dir.is_dir() {
+        return Some(dir.to_path_buf());
+    }
+
+    warn!("Working directory {:?} does not exist, using the home directory instead", dir);
+    home
+}
+
+/// Exit code of Alacritty for the termination of its child
+///
+/// Like in POSIX shells, children killed by a signal get `128` plus the signal number.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    status.code().unwrap_or(1)
+}
+
+/// Describe how the child process terminated
+pub fn exit_description(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return format!("Process was terminated by {}", signal_name(signal));
+        }
+    }
+
+    match status.code() {
+        Some(code) => format!("Process exited with code {}", code),
+        None => String::from("Process exited"),
+    }
+}
+
+/// Name of a signal, like `SIGKILL`
+#[cfg(unix)]
+fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGSYS => "SIGSYS",
+        _ => return format!("signal {}", signal),
+    };
+
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    #[cfg(unix)]
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::PathBuf;
+    #[cfg(unix)]
+    use std::process::ExitStatus;
+
+    use super::shell_directory;
+    #[cfg(unix)]
+    use super::{exit_code, exit_description};
+
+    #[test]
+    fn existing_shell_directory() {
+        let dir = env::temp_dir().join(format!("alacritty-shell-dir-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let home = Some(PathBuf::from("/home/user"));
+        assert_eq!(shell_directory(Some(&dir), home), Some(dir.clone()));
+
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_shell_directory_falls_back_to_home() {
+        let dir = env::temp_dir().join(format!("alacritty-missing-dir-{}", std::process::id()));
+
+        let home = Some(PathBuf::from("/home/user"));
+        assert_eq!(shell_directory(Some(&dir), home.clone()), home);
+        assert_eq!(shell_directory(Some(&dir), None), None);
+    }
+
+    #[test]
+    fn unset_shell_directory() {
+        assert_eq!(shell_directory(None, Some(PathBuf::from("/home/user"))), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn exit_status_code() {
+        // Wait statuses store the exit code in the second byte
+        let status = ExitStatus::from_raw(2 << 8);
+
+        assert_eq!(exit_code(status), 2);
+        assert_eq!(exit_description(status), "Process exited with code 2");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn exit_status_signal() {
+        let status = ExitStatus::from_raw(libc::SIGKILL);
+
+        assert_eq!(exit_code(status), 128 + libc::SIGKILL);
+        assert_eq!(exit_description(status), "Process was terminated by SIGKILL");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn exit_status_unknown_signal() {
+        let status = ExitStatus::from_raw(100);
+
+        assert_eq!(exit_description(status), "Process was terminated by signal 100");
+    }
+}
@@ -16,35 +16,30 @@
 //!
 
 use crate::config::{Config, Shell};
-use crate::display::OnResize;
 use crate::term::SizeInfo;
-use crate::tty::{ChildEvent, EventedPty, EventedReadWrite};
+use crate::tty::cwd::PtyProcesses;
+use crate::tty::{shell_directory, ChildEvent, EventedPty, EventedReadWrite, OnResize};
 use mio;
 
-use libc::{self, c_int, pid_t, winsize, TIOCSCTTY};
+use libc::{self, c_char, c_int, pid_t, winsize, TIOCSCTTY};
 use nix::pty::openpty;
 use signal_hook::{self as sighook, iterator::Signals};
 
 use mio::unix::EventedFd;
-use std::ffi::CStr;
-use std::fs::File;
-use std::io;
+use std::env;
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::iter;
 use std::os::unix::{
+    ffi::OsStrExt,
+    fs::PermissionsExt,
     io::{AsRawFd, FromRawFd, RawFd},
     process::CommandExt,
 };
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::ptr;
-use std::sync::atomic::{AtomicUsize, Ordering};
-
-/// Process ID of child process
-///
-/// Necessary to put this in static storage for `sigchld` to have access
-static PID: AtomicUsize = AtomicUsize::new(0);
-
-pub fn child_pid() -> pid_t {
-    PID.load(Ordering::Relaxed) as pid_t
-}
 
 /// Get raw fds for master/slave ends of a new pty
 fn make_pty(size: winsize) -> (RawFd, RawFd) {
@@ -125,7 +120,12 @@ fn get_pw_entry(buf: &mut [i8; 1024]) -> Passwd<'_> {
 }
 
 pub struct Pty {
-    child: Child,
+    /// Process running in the pty, `None` if it could not be spawned
+    child: Option<Child>,
+
+    /// Slave side of the pty, kept open without a child so the spawn error can be read
+    _slave: Option<File>,
+
     pub fd: File,
     token: mio::Token,
     signals: Signals,
@@ -146,6 +146,91 @@ impl Pty {
             die!("ioctl TIOCSWINSZ failed: {}", io::Error::last_os_error());
         }
     }
+
+    /// Handle for querying the processes running in the pty
+    pub fn processes(&self) -> PtyProcesses {
+        let shell = self.child.as_ref().map_or(0, |child| child.id() as pid_t);
+        PtyProcesses::new(shell, self.fd.as_raw_fd())
+    }
+}
+
+/// Arguments the shell is executed with, including `argv[0]`
+///
+/// Login shells get a dash in front of their name as `argv[0]`, which is how `login(1)` tells
+/// them to read the profile files. Unlike a `-l` flag, every shell understands this.
+fn shell_argv(shell: &Shell<'_>) -> Vec<String> {
+    let program = &*shell.program;
+    let arg0 = if shell.login() {
+        format!("-{}", program.rsplit('/').next().unwrap_or(program))
+    } else {
+        program.to_owned()
+    };
+
+    iter::once(arg0).chain(shell.args.iter().cloned()).collect()
+}
+
+/// Full path of `program`, looked up in `path` like `execvp` does when it contains no slash
+fn find_program(program: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(PathBuf::from(program));
+    }
+
+    env::split_paths(path?).map(|dir| dir.join(program)).find(|candidate| {
+        fs::metadata(candidate)
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    })
+}
+
+/// Arguments for `execve`, allocated before forking since the child must not allocate
+///
+/// The environment is passed explicitly, since `Command` only installs its environment after
+/// the `pre_exec` closures have run.
+struct ExecArgs {
+    program: CString,
+    argv: Vec<*const c_char>,
+    envp: Vec<*const c_char>,
+    _strings: Vec<CString>,
+}
+
+// The pointers in `argv` and `envp` only reference the strings owned by `ExecArgs`
+unsafe impl Send for ExecArgs {}
+unsafe impl Sync for ExecArgs {}
+
+impl ExecArgs {
+    fn new(program: &Path, argv: &[String], env: &[(OsString, OsString)]) -> Option<ExecArgs> {
+        let program = CString::new(program.as_os_str().as_bytes()).ok()?;
+
+        let env = env.iter().map(|(key, value)| {
+            let mut var = key.as_bytes().to_vec();
+            var.push(b'=');
+            var.extend_from_slice(value.as_bytes());
+            CString::new(var)
+        });
+        let strings = argv
+            .iter()
+            .map(|arg| CString::new(arg.as_str()))
+            .chain(env)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+
+        let pointers = |strings: &[CString]| -> Vec<*const c_char> {
+            strings.iter().map(|arg| arg.as_ptr()).chain(iter::once(ptr::null())).collect()
+        };
+        let (args, env) = strings.split_at(argv.len());
+        let (argv, envp) = (pointers(args), pointers(env));
+
+        Some(ExecArgs { program, argv, envp, _strings: strings })
+    }
+}
+
+/// Environment of the parent with the variables set for the shell
+fn child_env(vars: &[(&str, String)]) -> Vec<(OsString, OsString)> {
+    let overridden = |key: &OsStr| vars.iter().any(|(var, _)| OsStr::new(var) == key);
+    env::vars_os()
+        .filter(|(key, _)| !overridden(key))
+        .chain(vars.iter().map(|(key, value)| (key.into(), value.into())))
+        .collect()
 }
 
 /// Create a new tty and return a handle to interact with it.
@@ -156,20 +241,14 @@ pub fn new<T: ToWinsize>(config: &Config, size: &T, window_id: Option<usize>) ->
 
     let (master, slave) = make_pty(win_size);
 
-    let default_shell = if cfg!(target_os = "macos") {
-        let shell_name = pw.shell.rsplit('/').next().unwrap();
-        let argv = vec![String::from("-c"), format!("exec -a -{} {}", shell_name, pw.shell)];
-
-        Shell::new_with_args("/bin/bash", argv)
-    } else {
-        Shell::new(pw.shell)
-    };
+    let default_shell = Shell::new(pw.shell);
     let shell = config.shell.as_ref().unwrap_or(&default_shell);
 
+    let argv = shell_argv(shell);
+    info!("Running shell {}: {}", shell.program, argv.join(" "));
+
     let mut builder = Command::new(&*shell.program);
-    for arg in &shell.args {
-        builder.arg(arg);
-    }
+    builder.args(&argv[1..]);
 
     // Setup child stdin/stdout/stderr as slave fd of pty
     // Ownership of fd is transferred to the Stdio structs and will be closed by them at the end of
@@ -180,15 +259,28 @@ pub fn new<T: ToWinsize>(config: &Config, size: &T, window_id: Option<usize>) ->
     builder.stdout(unsafe { Stdio::from_raw_fd(slave) });
 
     // Setup shell environment
-    builder.env("LOGNAME", pw.name);
-    builder.env("USER", pw.name);
-    builder.env("SHELL", pw.shell);
-    builder.env("HOME", pw.dir);
+    let mut vars = vec![
+        ("LOGNAME", pw.name.to_owned()),
+        ("USER", pw.name.to_owned()),
+        ("SHELL", pw.shell.to_owned()),
+        ("HOME", pw.dir.to_owned()),
+    ];
 
     if let Some(window_id) = window_id {
-        builder.env("WINDOWID", format!("{}", window_id));
+        vars.push(("WINDOWID", format!("{}", window_id)));
     }
 
+    builder.envs(vars.iter().cloned());
+
+    // `Command` always uses the program as `argv[0]`, so login shells are executed manually
+    let login_exec = if shell.login() {
+        let env = child_env(&vars);
+        let path = env.iter().find(|(key, _)| key == "PATH").map(|(_, path)| path.as_os_str());
+        find_program(&shell.program, path).and_then(|program| ExecArgs::new(&program, &argv, &env))
+    } else {
+        None
+    };
+
     unsafe {
         builder.pre_exec(move || {
             // Create a new process group
@@ -210,43 +302,54 @@ pub fn new<T: ToWinsize>(config: &Config, size: &T, window_id: Option<usize>) ->
             libc::signal(libc::SIGTERM, libc::SIG_DFL);
             libc::signal(libc::SIGALRM, libc::SIG_DFL);
 
+            if let Some(exec) = &login_exec {
+                libc::execve(exec.program.as_ptr(), exec.argv.as_ptr(), exec.envp.as_ptr());
+                return Err(io::Error::last_os_error());
+            }
+
             Ok(())
         });
     }
 
     // Handle set working directory option
-    if let Some(ref dir) = config.working_directory() {
-        builder.current_dir(dir.as_path());
+    let configured = config.working_directory().as_ref().map(PathBuf::as_path);
+    if let Some(dir) = shell_directory(configured, Some(PathBuf::from(pw.dir))) {
+        builder.current_dir(dir);
     }
 
     // Prepare signal handling before spawning child
     let signals = Signals::new(&[sighook::SIGCHLD]).expect("error preparing signal handling");
 
-    match builder.spawn() {
-        Ok(child) => {
-            // Remember child PID so other modules can use it
-            PID.store(child.id() as usize, Ordering::Relaxed);
-
-            unsafe {
-                // Maybe this should be done outside of this function so nonblocking
-                // isn't forced upon consumers. Although maybe it should be?
-                set_nonblocking(master);
-            }
-
-            let pty = Pty {
-                child,
-                fd: unsafe { File::from_raw_fd(master) },
-                token: mio::Token::from(0),
-                signals,
-                signals_token: mio::Token::from(0),
-            };
-            pty.resize(size);
-            pty
-        },
+    // A command which can't be executed is reported in the terminal instead of exiting, the
+    // window then stays open until it is closed since there is no process which could exit
+    let (child, _slave) = match builder.spawn() {
+        Ok(child) => (Some(child), None),
         Err(err) => {
-            die!("Failed to spawn command: {}", err);
+            let message = format!("Failed to spawn command `{}`: {}", shell.program, err);
+            error!("{}", message);
+
+            let mut slave = unsafe { File::from_raw_fd(libc::dup(slave)) };
+            let _ = writeln!(slave, "alacritty: {}", message);
+            (None, Some(slave))
         },
+    };
+
+    unsafe {
+        // Maybe this should be done outside of this function so nonblocking
+        // isn't forced upon consumers. Although maybe it should be?
+        set_nonblocking(master);
     }
+
+    let pty = Pty {
+        child,
+        _slave,
+        fd: unsafe { File::from_raw_fd(master) },
+        token: mio::Token::from(0),
+        signals,
+        signals_token: mio::Token::from(0),
+    };
+    pty.resize(size);
+    pty
 }
 
 impl EventedReadWrite for Pty {
@@ -325,13 +428,13 @@ impl EventedPty for Pty {
                 return None;
             }
 
-            match self.child.try_wait() {
+            match self.child.as_mut()?.try_wait() {
                 Err(e) => {
                     error!("Error checking child process termination: {}", e);
                     None
                 },
                 Ok(None) => None,
-                Ok(_) => Some(ChildEvent::Exited),
+                Ok(Some(status)) => Some(ChildEvent::Exited(status)),
             }
         })
     }
@@ -342,10 +445,6 @@ impl EventedPty for Pty {
     }
 }
 
-pub fn process_should_exit() -> bool {
-    false
-}
-
 /// Types that can produce a `libc::winsize`
 pub trait ToWinsize {
     /// Get a `libc::winsize`
@@ -387,3 +486,94 @@ fn test_get_pw_entry() {
     let mut buf: [i8; 1024] = [0; 1024];
     let _pw = get_pw_entry(&mut buf);
 }
+
+#[test]
+fn test_shell_argv() {
+    let mut shell = Shell::new("/bin/zsh");
+    shell.set_login(false);
+    assert_eq!(shell_argv(&shell), vec!["/bin/zsh"]);
+
+    shell.set_login(true);
+    assert_eq!(shell_argv(&shell), vec!["-zsh"]);
+
+    let mut shell = Shell::new_with_args("/bin/zsh", vec!["-c".into(), "tmux".into()]);
+    shell.set_login(false);
+    assert_eq!(shell_argv(&shell), vec!["/bin/zsh", "-c", "tmux"]);
+
+    shell.set_login(true);
+    assert_eq!(shell_argv(&shell), vec!["-zsh", "-c", "tmux"]);
+}
+
+#[test]
+fn test_exec_args() {
+    let env = vec![("HOME".into(), "/home/user".into())];
+    let exec = ExecArgs::new(Path::new("/bin/zsh"), &["-zsh".into(), "-i".into()], &env).unwrap();
+    assert_eq!(exec.argv.len(), 3);
+    assert!(exec.argv[2].is_null());
+    assert_eq!(unsafe { CStr::from_ptr(exec.argv[0]) }.to_str(), Ok("-zsh"));
+
+    assert_eq!(exec.envp.len(), 2);
+    assert!(exec.envp[1].is_null());
+    assert_eq!(unsafe { CStr::from_ptr(exec.envp[0]) }.to_str(), Ok("HOME=/home/user"));
+
+    assert!(ExecArgs::new(Path::new("/bin/zsh"), &["nul\0".into()], &[]).is_none());
+}
+
+#[test]
+fn test_child_env() {
+    env::set_var("ALACRITTY_TEST_CHILD_ENV", "parent");
+    let vars = [("ALACRITTY_TEST_CHILD_ENV", "child".to_owned())];
+    let env = child_env(&vars);
+
+    let values: Vec<_> =
+        env.iter().filter(|(key, _)| key == "ALACRITTY_TEST_CHILD_ENV").map(|(_, v)| v).collect();
+    assert_eq!(values, vec!["child"]);
+}
+
+#[test]
+fn test_find_program() {
+    assert_eq!(find_program("/bin/sh", None), Some(PathBuf::from("/bin/sh")));
+    assert_eq!(find_program("sh", Some(OsStr::new("/nonexistent:/bin"))), Some("/bin/sh".into()));
+    assert_eq!(find_program("sh", Some(OsStr::new("/nonexistent"))), None);
+    assert_eq!(find_program("sh", None), None);
+}
+
+#[test]
+fn test_spawn_error_in_terminal() {
+    use crate::tty::cwd::Processes;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    let mut config = Config::default();
+    config.shell = Some(Shell::new("/nonexistent/alacritty-test"));
+
+    let size = SizeInfo {
+        width: 80.,
+        height: 24.,
+        cell_width: 1.,
+        cell_height: 1.,
+        padding_left: 0.,
+        padding_right: 0.,
+        padding_top: 0.,
+        padding_bottom: 0.,
+        dpr: 1.,
+    };
+    let mut pty = new(&config, &&size, None);
+
+    // The error is read from the pty like regular output of the command
+    let mut output = Vec::new();
+    let mut buf = [0; 1024];
+    let start = Instant::now();
+    while !String::from_utf8_lossy(&output).contains('\n') {
+        assert!(start.elapsed() < Duration::from_secs(5), "no error in {:?}", output);
+        match io::Read::read(&mut pty.fd, &mut buf) {
+            Ok(count) => output.extend_from_slice(&buf[..count]),
+            Err(_) => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+
+    let output = String::from_utf8_lossy(&output);
+    assert!(output.starts_with("alacritty: Failed to spawn command `/nonexistent/alacritty-test`"));
+    assert!(pty.processes().shell_pid().is_none());
+    assert!(pty.next_child_event().is_none());
+}
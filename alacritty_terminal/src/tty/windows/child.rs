@@ -0,0 +1,107 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Notification about the exit of the child process of a pty
+use std::io;
+use std::os::windows::io::RawHandle;
+use std::os::windows::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::ptr;
+
+use mio_extras::channel::{self, Receiver, Sender};
+
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::ntdef::{BOOLEAN, HANDLE, PVOID};
+use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+use winapi::um::processthreadsapi::GetExitCodeProcess;
+use winapi::um::threadpoollegacyapiset::UnregisterWaitEx;
+use winapi::um::winbase::{RegisterWaitForSingleObject, INFINITE};
+use winapi::um::winnt::{WT_EXECUTEINWAITTHREAD, WT_EXECUTEONLYONCE};
+
+/// Called on the wait thread once the child process has exited
+extern "system" fn child_exit_callback(context: PVOID, timed_out: BOOLEAN) {
+    if timed_out != 0 {
+        return;
+    }
+
+    let event_tx = unsafe { &*(context as *const Sender<()>) };
+    let _ = event_tx.send(());
+}
+
+/// Owner of the child process handle, which reports the exit of the child through a channel
+pub struct ChildExitWatcher {
+    child: HANDLE,
+    wait_handle: HANDLE,
+    event_rx: Receiver<()>,
+
+    /// Sender used by the wait callback, which must live until the wait is unregistered
+    _event_tx: Box<Sender<()>>,
+}
+
+// The handles are only used for waiting and reading the exit code, which works from any thread
+unsafe impl Send for ChildExitWatcher {}
+
+impl ChildExitWatcher {
+    /// Watch the process `child`, closing its handle once the watcher is dropped
+    pub fn new(child: RawHandle) -> io::Result<ChildExitWatcher> {
+        let child = child as HANDLE;
+        let (event_tx, event_rx) = channel::channel();
+        let event_tx = Box::new(event_tx);
+
+        let mut wait_handle: HANDLE = ptr::null_mut();
+        let success = unsafe {
+            RegisterWaitForSingleObject(
+                &mut wait_handle,
+                child,
+                Some(child_exit_callback),
+                &*event_tx as *const Sender<()> as PVOID,
+                INFINITE,
+                WT_EXECUTEINWAITTHREAD | WT_EXECUTEONLYONCE,
+            )
+        };
+
+        if success == 0 {
+            let err = io::Error::last_os_error();
+            unsafe { CloseHandle(child) };
+            return Err(err);
+        }
+
+        Ok(ChildExitWatcher { child, wait_handle, event_rx, _event_tx: event_tx })
+    }
+
+    /// Channel which becomes readable once the child has exited
+    pub fn event_rx(&self) -> &Receiver<()> {
+        &self.event_rx
+    }
+
+    /// Exit status of the child process, only meaningful after it has exited
+    pub fn exit_status(&self) -> io::Result<ExitStatus> {
+        let mut code: DWORD = 0;
+        if unsafe { GetExitCodeProcess(self.child, &mut code) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(ExitStatus::from_raw(code))
+    }
+}
+
+impl Drop for ChildExitWatcher {
+    fn drop(&mut self) {
+        unsafe {
+            // Block until a running callback is done, so the sender isn't freed while in use
+            UnregisterWaitEx(self.wait_handle, INVALID_HANDLE_VALUE);
+            CloseHandle(self.child);
+        }
+    }
+}
@@ -12,12 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{Pty, HANDLE};
+use super::{ChildExitWatcher, Pty};
 
+use std::env;
 use std::i16;
 use std::io::Error;
 use std::mem;
-use std::os::windows::io::IntoRawHandle;
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::path::PathBuf;
 use std::ptr;
 use std::sync::Arc;
 
@@ -38,8 +40,8 @@ use winapi::um::winbase::{EXTENDED_STARTUPINFO_PRESENT, STARTF_USESTDHANDLES, ST
 use winapi::um::wincontypes::{COORD, HPCON};
 
 use crate::config::{Config, Shell};
-use crate::display::OnResize;
 use crate::term::SizeInfo;
+use crate::tty::{shell_directory, OnResize};
 
 /// Dynamically-loaded Pseudoconsole API from kernel32.dll
 ///
@@ -99,14 +101,8 @@ unsafe impl Send for Conpty {}
 unsafe impl Sync for Conpty {}
 
 pub fn new<'a>(config: &Config, size: &SizeInfo, _window_id: Option<usize>) -> Option<Pty<'a>> {
-    if !config.enable_experimental_conpty_backend {
-        return None;
-    }
-
     let api = ConptyApi::new()?;
 
-    let mut pty_handle = 0 as HPCON;
-
     // Passing 0 as the size parameter allows the "system default" buffer
     // size to be used. There may be small performance and memory advantages
     // to be gained by tuning this in the future, but it's likely a reasonable
@@ -114,21 +110,15 @@ pub fn new<'a>(config: &Config, size: &SizeInfo, _window_id: Option<usize>) -> O
     let (conout, conout_pty_handle) = miow::pipe::anonymous(0).unwrap();
     let (conin_pty_handle, conin) = miow::pipe::anonymous(0).unwrap();
 
-    let coord =
-        coord_from_sizeinfo(size).expect("Overflow when creating initial size on pseudoconsole");
-
-    // Create the Pseudo Console, using the pipes
-    let result = unsafe {
-        (api.CreatePseudoConsole)(
-            coord,
-            conin_pty_handle.into_raw_handle(),
-            conout_pty_handle.into_raw_handle(),
-            0,
-            &mut pty_handle as *mut HPCON,
-        )
-    };
-
-    assert!(result == S_OK);
+    // The pseudoconsole duplicates its ends of the pipes, so they're closed
+    // once they go out of scope
+    let agent = create_pseudoconsole(
+        api,
+        size,
+        conin_pty_handle.as_raw_handle(),
+        conout_pty_handle.as_raw_handle(),
+    )?;
+    let pty_handle = agent.handle;
 
     let mut success;
 
@@ -209,7 +199,9 @@ pub fn new<'a>(config: &Config, size: &SizeInfo, _window_id: Option<usize>) -> O
     cmdline.insert(0, shell.program.to_string());
 
     // Warning, here be borrow hell
-    let cwd = config.working_directory().as_ref().map(|dir| canonicalize(dir).unwrap());
+    let home = env::var_os("USERPROFILE").map(PathBuf::from);
+    let configured = config.working_directory().as_ref().map(PathBuf::as_path);
+    let cwd = shell_directory(configured, home).and_then(|dir| canonicalize(dir).ok());
     let cwd = cwd.as_ref().map(|dir| dir.to_str().unwrap());
 
     // Create the client application, using startup info containing ConPTY info
@@ -236,25 +228,47 @@ pub fn new<'a>(config: &Config, size: &SizeInfo, _window_id: Option<usize>) -> O
         }
     }
 
-    // Store handle to console
-    unsafe {
-        HANDLE = proc_info.hProcess;
-    }
+    let child_watcher = ChildExitWatcher::new(proc_info.hProcess as RawHandle)
+        .expect("watch child process for exit");
 
     let conin = EventedAnonWrite::new(conin);
     let conout = EventedAnonRead::new(conout);
 
-    let agent = Conpty { handle: pty_handle, api };
-
     Some(Pty {
         handle: super::PtyHandle::Conpty(ConptyHandle::new(agent)),
+        child_watcher,
         conout: super::EventedReadablePipe::Anonymous(conout),
         conin: super::EventedWritablePipe::Anonymous(conin),
         read_token: 0.into(),
         write_token: 0.into(),
+        child_event_token: 0.into(),
     })
 }
 
+/// Create a pseudoconsole reading from `input` and writing to `output`
+///
+/// Returns `None` if it could not be created, so winpty can be used instead.
+fn create_pseudoconsole(
+    api: ConptyApi,
+    size: &SizeInfo,
+    input: HANDLE,
+    output: HANDLE,
+) -> Option<Conpty> {
+    let coord =
+        coord_from_sizeinfo(size).expect("Overflow when creating initial size on pseudoconsole");
+
+    let mut handle = 0 as HPCON;
+    let result =
+        unsafe { (api.CreatePseudoConsole)(coord, input, output, 0, &mut handle as *mut HPCON) };
+
+    if result == S_OK {
+        Some(Conpty { handle, api })
+    } else {
+        warn!("Unable to create pseudoconsole: {}", Error::from_raw_os_error(result));
+        None
+    }
+}
+
 // Panic with the last os error as message
 fn panic_shell_spawn() {
     panic!("Unable to spawn shell: {}", Error::last_os_error());
@@ -280,3 +294,127 @@ fn coord_from_sizeinfo(sizeinfo: &SizeInfo) -> Option<COORD> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::i16;
+    use std::ptr;
+    use std::sync::Arc;
+
+    use winapi::shared::minwindef::DWORD;
+    use winapi::shared::ntdef::{HANDLE, HRESULT};
+    use winapi::shared::winerror::{E_FAIL, S_OK};
+    use winapi::um::wincontypes::{COORD, HPCON};
+
+    use crate::term::SizeInfo;
+    use crate::tty::OnResize;
+
+    use super::{create_pseudoconsole, ConptyApi, ConptyHandle};
+
+    const MOCK_HPCON: usize = 0x1234;
+
+    thread_local! {
+        static CREATED: Cell<Option<(i16, i16)>> = Cell::new(None);
+        static RESIZED: Cell<Option<(i16, i16)>> = Cell::new(None);
+        static CLOSED: Cell<bool> = Cell::new(false);
+    }
+
+    unsafe extern "system" fn create(
+        size: COORD,
+        _input: HANDLE,
+        _output: HANDLE,
+        _flags: DWORD,
+        handle: *mut HPCON,
+    ) -> HRESULT {
+        CREATED.with(|created| created.set(Some((size.X, size.Y))));
+        *handle = MOCK_HPCON as HPCON;
+        S_OK
+    }
+
+    unsafe extern "system" fn create_failure(
+        _size: COORD,
+        _input: HANDLE,
+        _output: HANDLE,
+        _flags: DWORD,
+        _handle: *mut HPCON,
+    ) -> HRESULT {
+        E_FAIL
+    }
+
+    unsafe extern "system" fn resize(handle: HPCON, size: COORD) -> HRESULT {
+        assert_eq!(handle as usize, MOCK_HPCON);
+        RESIZED.with(|resized| resized.set(Some((size.X, size.Y))));
+        S_OK
+    }
+
+    unsafe extern "system" fn close(handle: HPCON) {
+        assert_eq!(handle as usize, MOCK_HPCON);
+        CLOSED.with(|closed| closed.set(true));
+    }
+
+    fn mock_api(success: bool) -> ConptyApi {
+        ConptyApi {
+            CreatePseudoConsole: if success { create } else { create_failure },
+            ResizePseudoConsole: resize,
+            ClosePseudoConsole: close,
+        }
+    }
+
+    fn size(cols: usize, lines: usize) -> SizeInfo {
+        SizeInfo {
+            width: cols as f32,
+            height: lines as f32,
+            cell_width: 1.0,
+            cell_height: 1.0,
+            padding_left: 0.0,
+            padding_right: 0.0,
+            padding_top: 0.0,
+            padding_bottom: 0.0,
+            dpr: 1.0,
+        }
+    }
+
+    #[test]
+    fn create_with_initial_size() {
+        let conpty =
+            create_pseudoconsole(mock_api(true), &size(80, 24), ptr::null_mut(), ptr::null_mut());
+
+        assert!(conpty.is_some());
+        assert_eq!(CREATED.with(Cell::get), Some((80, 24)));
+
+        drop(conpty);
+        assert!(CLOSED.with(Cell::get));
+    }
+
+    #[test]
+    fn create_failure_falls_back() {
+        let conpty =
+            create_pseudoconsole(mock_api(false), &size(80, 24), ptr::null_mut(), ptr::null_mut());
+
+        assert!(conpty.is_none());
+        assert!(!CLOSED.with(Cell::get));
+    }
+
+    #[test]
+    fn resize_pseudoconsole() {
+        let conpty =
+            create_pseudoconsole(mock_api(true), &size(80, 24), ptr::null_mut(), ptr::null_mut());
+        let mut handle: ConptyHandle = Arc::new(conpty.unwrap());
+
+        handle.on_resize(&size(100, 30));
+
+        assert_eq!(RESIZED.with(Cell::get), Some((100, 30)));
+    }
+
+    #[test]
+    fn resize_overflow_ignored() {
+        let conpty =
+            create_pseudoconsole(mock_api(true), &size(80, 24), ptr::null_mut(), ptr::null_mut());
+        let mut handle: ConptyHandle = Arc::new(conpty.unwrap());
+
+        handle.on_resize(&size(i16::MAX as usize + 1, 30));
+
+        assert_eq!(RESIZED.with(Cell::get), None);
+    }
+}
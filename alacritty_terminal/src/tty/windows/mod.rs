@@ -13,47 +13,26 @@
 // limitations under the License.
 
 use std::io::{self, Read, Write};
-use std::os::raw::c_void;
+use std::os::windows::process::ExitStatusExt;
+use std::process::ExitStatus;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use mio::{self, Evented, Poll, PollOpt, Ready, Token};
 use mio_anonymous_pipes::{EventedAnonRead, EventedAnonWrite};
 use mio_named_pipes::NamedPipe;
 
-use winapi::shared::winerror::WAIT_TIMEOUT;
-use winapi::um::synchapi::WaitForSingleObject;
-use winapi::um::winbase::WAIT_OBJECT_0;
-
 use crate::config::Config;
-use crate::display::OnResize;
 use crate::term::SizeInfo;
-use crate::tty::{EventedPty, EventedReadWrite};
+use crate::tty::cwd::PtyProcesses;
+use crate::tty::{ChildEvent, EventedPty, EventedReadWrite, OnResize};
 
+mod child;
 mod conpty;
 mod winpty;
 
-/// Handle to the winpty agent or conpty process. Required so we know when it closes.
-static mut HANDLE: *mut c_void = 0usize as *mut c_void;
-static IS_CONPTY: AtomicBool = AtomicBool::new(false);
+use self::child::ChildExitWatcher;
 
-pub fn process_should_exit() -> bool {
-    unsafe {
-        match WaitForSingleObject(HANDLE, 0) {
-            // Process has exited
-            WAIT_OBJECT_0 => {
-                info!("wait_object_0");
-                true
-            },
-            // Reached timeout of 0, process has not exited
-            WAIT_TIMEOUT => false,
-            // Error checking process, winpty gave us a bad agent handle?
-            _ => {
-                info!("Bad exit: {}", ::std::io::Error::last_os_error());
-                true
-            },
-        }
-    }
-}
+static IS_CONPTY: AtomicBool = AtomicBool::new(false);
 
 pub fn is_conpty() -> bool {
     IS_CONPTY.load(Ordering::Relaxed)
@@ -67,6 +46,7 @@ pub enum PtyHandle<'a> {
 
 pub struct Pty<'a> {
     handle: PtyHandle<'a>,
+    child_watcher: ChildExitWatcher,
     // TODO: It's on the roadmap for the Conpty API to support Overlapped I/O.
     // See https://github.com/Microsoft/console/issues/262
     // When support for that lands then it should be possible to use
@@ -75,16 +55,25 @@ pub struct Pty<'a> {
     conin: EventedWritablePipe,
     read_token: mio::Token,
     write_token: mio::Token,
+    child_event_token: mio::Token,
 }
 
 impl<'a> Pty<'a> {
     pub fn resize_handle(&self) -> impl OnResize + 'a {
         self.handle.clone()
     }
+
+    /// Handle for querying the processes running in the pty
+    pub fn processes(&self) -> PtyProcesses {
+        PtyProcesses::default()
+    }
 }
 
+/// Create a new pty, using ConPTY if it is available and not disabled in the config
 pub fn new<'a>(config: &Config, size: &SizeInfo, window_id: Option<usize>) -> Pty<'a> {
-    if let Some(pty) = conpty::new(config, size, window_id) {
+    let conpty = if config.winpty_backend { None } else { conpty::new(config, size, window_id) };
+
+    if let Some(pty) = conpty {
         info!("Using Conpty agent");
         IS_CONPTY.store(true, Ordering::Relaxed);
         pty
@@ -243,7 +232,14 @@ impl<'a> EventedReadWrite for Pty<'a> {
         } else {
             poll.register(&self.conin, self.write_token, mio::Ready::empty(), poll_opts)?
         }
-        Ok(())
+
+        self.child_event_token = token.next().unwrap();
+        poll.register(
+            self.child_watcher.event_rx(),
+            self.child_event_token,
+            mio::Ready::readable(),
+            mio::PollOpt::level(),
+        )
     }
 
     #[inline]
@@ -263,14 +259,20 @@ impl<'a> EventedReadWrite for Pty<'a> {
         } else {
             poll.reregister(&self.conin, self.write_token, mio::Ready::empty(), poll_opts)?;
         }
-        Ok(())
+
+        poll.reregister(
+            self.child_watcher.event_rx(),
+            self.child_event_token,
+            mio::Ready::readable(),
+            mio::PollOpt::level(),
+        )
     }
 
     #[inline]
     fn deregister(&mut self, poll: &mio::Poll) -> io::Result<()> {
         poll.deregister(&self.conout)?;
         poll.deregister(&self.conin)?;
-        Ok(())
+        poll.deregister(self.child_watcher.event_rx())
     }
 
     #[inline]
@@ -294,4 +296,22 @@ impl<'a> EventedReadWrite for Pty<'a> {
     }
 }
 
-impl<'a> EventedPty for Pty<'a> {}
+impl<'a> EventedPty for Pty<'a> {
+    #[inline]
+    fn next_child_event(&mut self) -> Option<ChildEvent> {
+        self.child_watcher.event_rx().try_recv().ok()?;
+
+        // The child has exited either way, so report it as failed if its status is unknown
+        let status = self.child_watcher.exit_status().unwrap_or_else(|e| {
+            error!("Error reading exit code of child process: {}", e);
+            ExitStatus::from_raw(1)
+        });
+
+        Some(ChildEvent::Exited(status))
+    }
+
+    #[inline]
+    fn child_event_token(&self) -> mio::Token {
+        self.child_event_token
+    }
+}
@@ -12,12 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{Pty, HANDLE};
+use super::{ChildExitWatcher, Pty};
 
+use std::env;
 use std::fs::OpenOptions;
 use std::io;
 use std::os::windows::fs::OpenOptionsExt;
 use std::os::windows::io::{FromRawHandle, IntoRawHandle};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::u16;
 
@@ -28,8 +30,8 @@ use winpty::Config as WinptyConfig;
 use winpty::{ConfigFlags, MouseMode, SpawnConfig, SpawnFlags, Winpty};
 
 use crate::config::{Config, Shell};
-use crate::display::OnResize;
 use crate::term::SizeInfo;
+use crate::tty::{shell_directory, OnResize};
 
 // We store a raw pointer because we need mutable access to call
 // on_resize from a separate thread. Winpty internally uses a mutex
@@ -94,7 +96,9 @@ pub fn new<'a>(config: &Config, size: &SizeInfo, _window_id: Option<usize>) -> P
     cmdline.insert(0, shell.program.to_string());
 
     // Warning, here be borrow hell
-    let cwd = config.working_directory().as_ref().map(|dir| canonicalize(dir).unwrap());
+    let home = env::var_os("USERPROFILE").map(PathBuf::from);
+    let configured = config.working_directory().as_ref().map(PathBuf::as_path);
+    let cwd = shell_directory(configured, home).and_then(|dir| canonicalize(dir).ok());
     let cwd = cwd.as_ref().map(|dir| dir.to_str().unwrap());
 
     // Spawn process
@@ -134,20 +138,19 @@ pub fn new<'a>(config: &Config, size: &SizeInfo, _window_id: Option<usize>) -> P
     }
     assert!(conin_pipe.take_error().unwrap().is_none());
 
-    winpty.spawn(&spawnconfig).unwrap();
-
-    unsafe {
-        HANDLE = winpty.raw_handle();
-    }
+    let child = winpty.spawn(&spawnconfig).unwrap();
+    let child_watcher = ChildExitWatcher::new(child).expect("watch child process for exit");
 
     let agent = Agent::new(winpty);
 
     Pty {
         handle: super::PtyHandle::Winpty(WinptyHandle::new(agent)),
+        child_watcher,
         conout: super::EventedReadablePipe::Named(conout_pipe),
         conin: super::EventedWritablePipe::Named(conin_pipe),
         read_token: 0.into(),
         write_token: 0.into(),
+        child_event_token: 0.into(),
     }
 }
 
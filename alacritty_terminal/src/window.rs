@@ -14,7 +14,13 @@
 use std::convert::From;
 #[cfg(not(any(target_os = "macos", target_os = "windows")))]
 use std::ffi::c_void;
+#[cfg(not(any(target_os = "macos", windows)))]
+use std::ffi::CStr;
 use std::fmt::Display;
+#[cfg(not(any(target_os = "macos", windows)))]
+use std::os::raw::c_ulong;
+#[cfg(not(target_os = "macos"))]
+use std::path::Path;
 
 use crate::gl;
 use glutin::dpi::{LogicalPosition, LogicalSize, PhysicalSize};
@@ -25,20 +31,41 @@ use glutin::os::unix::{EventsLoopExt, WindowExt};
 #[cfg(not(target_os = "macos"))]
 use glutin::Icon;
 use glutin::{
-    self, ContextBuilder, ControlFlow, Event, EventsLoop, MouseCursor, PossiblyCurrent,
-    WindowBuilder,
+    self, Api, ContextBuilder, EventsLoop, EventsLoopProxy, GlRequest, MouseCursor,
+    PossiblyCurrent, WindowBuilder, WindowId,
 };
 #[cfg(not(target_os = "macos"))]
-use image::ImageFormat;
+use image::{DynamicImage, FilterType, GenericImageView, ImageFormat, Rgba, RgbaImage};
 
-use crate::config::{Config, Decorations, StartupMode, WindowConfig};
+use crate::ansi::MouseCursorIcon;
+#[cfg(not(any(target_os = "macos", windows)))]
+use crate::config::Class;
+#[cfg(not(target_os = "macos"))]
+use crate::config::TitlebarColor;
+use crate::config::{
+    Config, Decorations, RendererApi, StartupMode, WindowConfig, DEFAULT_NAME,
+};
+use crate::term::color::Rgb;
+use crate::term::OpacityChange;
 
 // It's required to be in this directory due to the `windows.rc` file
 #[cfg(not(target_os = "macos"))]
 static WINDOW_ICON: &[u8] = include_bytes!("../../extra/windows/alacritty.ico");
 
-/// Default Alacritty name, used for window title and class.
-pub const DEFAULT_NAME: &str = "Alacritty";
+/// Lowest window opacity which can be set at runtime
+const MIN_WINDOW_OPACITY: f32 = 0.1;
+
+/// Largest width and height of the window icon, bigger images are scaled down
+#[cfg(not(target_os = "macos"))]
+const ICON_MAX_SIZE: u32 = 256;
+
+/// Color of the dot drawn on the window icon after a bell
+#[cfg(not(target_os = "macos"))]
+const BELL_BADGE_COLOR: [u8; 4] = [0xe0, 0x1b, 0x24, 0xff];
+
+/// Label of the dock icon after a bell
+#[cfg(target_os = "macos")]
+const BELL_BADGE_LABEL: &str = "\u{2022}\0";
 
 /// Window errors
 #[derive(Debug)]
@@ -48,6 +75,34 @@ pub enum Error {
 
     /// Error manipulating the rendering context
     Context(glutin::ContextError),
+
+    /// Error embedding the window into another window
+    Embed(String),
+}
+
+/// Window class hints for the X11 and Wayland window builders
+#[cfg(not(any(target_os = "macos", windows)))]
+#[derive(Debug, PartialEq, Eq)]
+struct ClassHints {
+    /// Instance part of the X11 `WM_CLASS` property
+    x11_instance: String,
+
+    /// Class part of the X11 `WM_CLASS` property
+    x11_general: String,
+
+    /// Wayland application ID
+    wayland_app_id: String,
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+impl<'a> From<&'a Class> for ClassHints {
+    fn from(class: &Class) -> Self {
+        ClassHints {
+            x11_instance: class.instance.clone(),
+            x11_general: class.general.clone(),
+            wayland_app_id: class.instance.clone(),
+        }
+    }
 }
 
 /// Result of fallible operations concerning a Window.
@@ -57,18 +112,451 @@ type Result<T> = ::std::result::Result<T, Error>;
 ///
 /// Wraps the underlying windowing library to provide a stable API in Alacritty
 pub struct Window {
-    event_loop: EventsLoop,
+    /// Wakes up the event loop shared by all windows
+    event_proxy: EventsLoopProxy,
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    is_x11: bool,
+
     windowed_context: glutin::WindowedContext<PossiblyCurrent>,
     mouse_visible: bool,
 
     /// Keep track of the current mouse cursor to avoid unnecessarily changing it
-    current_mouse_cursor: MouseCursor,
+    current_mouse_cursor: MouseCursorIcon,
 
     /// Whether or not the window is the focused window.
     pub is_focused: bool,
+
+    /// Fullscreen and maximized state
+    mode: WindowMode,
+
+    /// Opacity of the entire window
+    opacity: WindowOpacity,
+
+    /// Image used as window icon
+    #[cfg(not(target_os = "macos"))]
+    icon: Option<WindowIcon>,
+
+    /// Indicator for bells while the window is unfocused
+    bell_badge: BellBadge,
+
+    /// Last color applied to the title bar
+    #[cfg(target_os = "macos")]
+    titlebar_color: Option<Rgb>,
+}
+
+/// Window operations required for switching between fullscreen, maximized and windowed mode
+pub trait ModeControl {
+    fn inner_size(&self) -> Option<LogicalSize>;
+    fn set_inner_size(&self, size: LogicalSize);
+    fn set_fullscreen(&self, fullscreen: bool);
+    fn set_maximized(&self, maximized: bool);
+    #[cfg(target_os = "macos")]
+    fn set_simple_fullscreen(&self, fullscreen: bool);
+}
+
+impl ModeControl for glutin::Window {
+    fn inner_size(&self) -> Option<LogicalSize> {
+        self.get_inner_size()
+    }
+
+    fn set_inner_size(&self, size: LogicalSize) {
+        glutin::Window::set_inner_size(self, size);
+    }
+
+    fn set_fullscreen(&self, fullscreen: bool) {
+        if fullscreen {
+            let current_monitor = self.get_current_monitor();
+            glutin::Window::set_fullscreen(self, Some(current_monitor));
+        } else {
+            glutin::Window::set_fullscreen(self, None);
+        }
+    }
+
+    fn set_maximized(&self, maximized: bool) {
+        glutin::Window::set_maximized(self, maximized);
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_simple_fullscreen(&self, fullscreen: bool) {
+        WindowExt::set_simple_fullscreen(self, fullscreen);
+    }
+}
+
+/// Fullscreen and maximized state of a window
+///
+/// Glutin doesn't report when these modes change, so they are tracked here
+/// instead. Native and simple fullscreen are mutually exclusive, requests to
+/// enter one of them while the other one is active are ignored.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct WindowMode {
+    fullscreen: bool,
+    simple_fullscreen: bool,
+    maximized: bool,
+
+    /// Window size before entering fullscreen
+    windowed_size: Option<LogicalSize>,
+}
+
+impl WindowMode {
+    /// Enter or leave native fullscreen
+    ///
+    /// Returns the restored window size after leaving fullscreen.
+    pub fn set_fullscreen<W: ModeControl>(
+        &mut self,
+        window: &W,
+        fullscreen: bool,
+    ) -> Option<LogicalSize> {
+        if fullscreen == self.fullscreen || self.simple_fullscreen {
+            return None;
+        }
+
+        self.fullscreen = fullscreen;
+        if fullscreen {
+            self.windowed_size = window.inner_size();
+            window.set_fullscreen(true);
+            return None;
+        }
+
+        window.set_fullscreen(false);
+        self.restore(window)
+    }
+
+    /// Enter or leave simple fullscreen
+    ///
+    /// Returns the restored window size after leaving simple fullscreen.
+    #[cfg(target_os = "macos")]
+    pub fn set_simple_fullscreen<W: ModeControl>(
+        &mut self,
+        window: &W,
+        fullscreen: bool,
+    ) -> Option<LogicalSize> {
+        if fullscreen == self.simple_fullscreen || self.fullscreen {
+            return None;
+        }
+
+        self.simple_fullscreen = fullscreen;
+        if fullscreen {
+            self.windowed_size = window.inner_size();
+            window.set_simple_fullscreen(true);
+            return None;
+        }
+
+        window.set_simple_fullscreen(false);
+        self.restore(window)
+    }
+
+    /// Maximize or unmaximize the window
+    ///
+    /// While the window is fullscreen, the change is applied after leaving fullscreen.
+    pub fn set_maximized<W: ModeControl>(&mut self, window: &W, maximized: bool) {
+        self.maximized = maximized;
+
+        if !self.is_fullscreen() {
+            window.set_maximized(maximized);
+        }
+    }
+
+    #[inline]
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen || self.simple_fullscreen
+    }
+
+    #[inline]
+    pub fn is_maximized(&self) -> bool {
+        self.maximized
+    }
+
+    /// Restore the windowed state from before entering fullscreen
+    fn restore<W: ModeControl>(&mut self, window: &W) -> Option<LogicalSize> {
+        let windowed_size = self.windowed_size.take();
+
+        if self.maximized {
+            window.set_maximized(true);
+            return None;
+        }
+
+        let size = windowed_size?;
+        window.set_inner_size(size);
+        Some(size)
+    }
+}
+
+/// Window system support for translucent windows
+pub trait OpacityControl {
+    /// Set the opacity of the entire window
+    ///
+    /// Returns `false` if the window system does not support window opacity.
+    fn set_opacity(&self, opacity: f32) -> bool;
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+impl OpacityControl for glutin::Window {
+    fn set_opacity(&self, opacity: f32) -> bool {
+        use x11_dl::xlib::{self, PropModeReplace, XA_CARDINAL};
+
+        // There is no commonly supported Wayland protocol for window opacity
+        let (xlib_window, xlib_display) = match (self.get_xlib_window(), self.get_xlib_display()) {
+            (Some(xlib_window), Some(xlib_display)) => (xlib_window, xlib_display),
+            _ => return false,
+        };
+
+        let xlib = match xlib::Xlib::open() {
+            Ok(xlib) => xlib,
+            Err(_) => return false,
+        };
+
+        unsafe {
+            let name = b"_NET_WM_WINDOW_OPACITY\0".as_ptr() as *const _;
+            let atom = (xlib.XInternAtom)(xlib_display as *mut _, name, 0);
+
+            match x11_opacity(opacity) {
+                Some(value) => {
+                    (xlib.XChangeProperty)(
+                        xlib_display as _,
+                        xlib_window as _,
+                        atom,
+                        XA_CARDINAL,
+                        32,
+                        PropModeReplace,
+                        &value as *const c_ulong as *const u8,
+                        1,
+                    );
+                },
+                None => {
+                    (xlib.XDeleteProperty)(xlib_display as _, xlib_window as _, atom);
+                },
+            }
+
+            (xlib.XFlush)(xlib_display as _);
+        }
+
+        true
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl OpacityControl for glutin::Window {
+    fn set_opacity(&self, opacity: f32) -> bool {
+        use objc::runtime::Object;
+
+        let nswindow = self.get_nswindow() as *mut Object;
+        unsafe {
+            let _: () = msg_send![nswindow, setAlphaValue: f64::from(opacity)];
+        }
+
+        true
+    }
+}
+
+#[cfg(windows)]
+impl OpacityControl for glutin::Window {
+    fn set_opacity(&self, _opacity: f32) -> bool {
+        false
+    }
+}
+
+/// Log the macOS window options which are not supported by this platform
+#[cfg(not(target_os = "macos"))]
+fn log_ignored_macos_options(window_config: &WindowConfig) {
+    if window_config.decorations() != window_config.decorations {
+        info!("Ignoring macOS only window decorations {:?}", window_config.decorations);
+    }
+
+    if window_config.titlebar_color != TitlebarColor::System {
+        info!("Ignoring macOS only window titlebar_color {:?}", window_config.titlebar_color);
+    }
+}
+
+/// Value of the X11 `_NET_WM_WINDOW_OPACITY` property
+///
+/// Opaque windows should not have the property at all, so `None` is returned for them.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn x11_opacity(opacity: f32) -> Option<c_ulong> {
+    if opacity >= 1. {
+        return None;
+    }
+
+    Some((f64::from(opacity.max(0.)) * f64::from(u32::max_value())) as c_ulong)
+}
+
+/// Opacity of the entire window
+///
+/// This is independent of the background opacity, which only affects the
+/// background color of the cells.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WindowOpacity {
+    /// Opacity from the configuration file
+    configured: f32,
+
+    /// Opacity currently applied to the window
+    current: f32,
+}
+
+impl WindowOpacity {
+    pub fn new(configured: f32) -> Self {
+        WindowOpacity { configured, current: configured }
+    }
+
+    #[inline]
+    pub fn get(&self) -> f32 {
+        self.current
+    }
+
+    /// Change the opacity and apply it to the window
+    pub fn change<W: OpacityControl>(&mut self, window: &W, change: OpacityChange) {
+        let opacity = match change {
+            OpacityChange::Step(delta) => step_opacity(self.current, delta),
+            OpacityChange::Set(opacity) => opacity.max(MIN_WINDOW_OPACITY).min(1.),
+            OpacityChange::Reset => self.configured,
+        };
+
+        if opacity == self.current {
+            return;
+        }
+
+        self.current = opacity;
+        if !window.set_opacity(opacity) {
+            debug!("Window opacity is not supported by the window system");
+        }
+    }
+
+    /// Use a new configured opacity, replacing runtime changes
+    pub fn set_configured<W: OpacityControl>(&mut self, window: &W, configured: f32) {
+        if configured != self.configured {
+            self.configured = configured;
+            self.change(window, OpacityChange::Reset);
+        }
+    }
+
+    /// Apply the opacity to the window again
+    ///
+    /// Window systems might reset the opacity when switching to or from fullscreen.
+    pub fn reapply<W: OpacityControl>(&self, window: &W) {
+        if self.current < 1. {
+            window.set_opacity(self.current);
+        }
+    }
+}
+
+/// Opacity after changing it by `delta`
+///
+/// The opacity stays in the range from `MIN_WINDOW_OPACITY` to `1.0`, so the
+/// window can't become invisible. An opacity configured below the minimum is
+/// never reduced further.
+fn step_opacity(opacity: f32, delta: f32) -> f32 {
+    let min = MIN_WINDOW_OPACITY.min(opacity);
+    let opacity = (opacity + delta).max(min).min(1.);
+
+    // Avoid accumulating rounding errors when stepping repeatedly
+    (opacity * 100.).round() / 100.
+}
+
+/// Pixels of the window icon
+#[cfg(not(target_os = "macos"))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowIcon {
+    image: RgbaImage,
+}
+
+#[cfg(not(target_os = "macos"))]
+impl WindowIcon {
+    /// Load the icon from `path`, falling back to the Alacritty logo
+    pub fn load(path: Option<&Path>) -> Option<WindowIcon> {
+        if let Some(path) = path {
+            match image::open(path) {
+                Ok(image) => return Some(WindowIcon::from_image(image)),
+                Err(err) => error!("Unable to load window icon {:?}: {}", path, err),
+            }
+        }
+
+        image::load_from_memory_with_format(WINDOW_ICON, ImageFormat::ICO)
+            .map(WindowIcon::from_image)
+            .ok()
+    }
+
+    /// Create an icon from an image, scaling it down if necessary
+    fn from_image(image: DynamicImage) -> WindowIcon {
+        let image = if image.width() > ICON_MAX_SIZE || image.height() > ICON_MAX_SIZE {
+            image.resize(ICON_MAX_SIZE, ICON_MAX_SIZE, FilterType::Triangle)
+        } else {
+            image
+        };
+
+        WindowIcon { image: image.to_rgba() }
+    }
+
+    /// Copy of the icon with a dot in the top right corner
+    fn with_badge(&self) -> WindowIcon {
+        let mut image = self.image.clone();
+        let (width, height) = image.dimensions();
+        let radius = width.min(height) as f32 / 4.;
+        let center = (width as f32 - radius, radius);
+
+        for y in 0..height.min((2. * radius).ceil() as u32) {
+            for x in width.saturating_sub((2. * radius).ceil() as u32)..width {
+                let dx = x as f32 + 0.5 - center.0;
+                let dy = y as f32 + 0.5 - center.1;
+                if dx * dx + dy * dy <= radius * radius {
+                    image.put_pixel(x, y, Rgba { data: BELL_BADGE_COLOR });
+                }
+            }
+        }
+
+        WindowIcon { image }
+    }
+
+    fn to_icon(&self) -> Option<Icon> {
+        let (width, height) = self.image.dimensions();
+        Icon::from_rgba(self.image.clone().into_raw(), width, height).ok()
+    }
+}
+
+/// Badge indicating that a bell rang while the window was unfocused
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct BellBadge {
+    enabled: bool,
+    visible: bool,
+}
+
+impl BellBadge {
+    pub fn new(enabled: bool) -> Self {
+        BellBadge { enabled, visible: false }
+    }
+
+    /// Handle a bell, returns `true` if the badge has to be shown
+    pub fn ring(&mut self, focused: bool) -> bool {
+        if !self.enabled || focused || self.visible {
+            return false;
+        }
+
+        self.visible = true;
+        true
+    }
+
+    /// Handle a focus change, returns `true` if the badge has to be removed
+    pub fn focus(&mut self, focused: bool) -> bool {
+        self.remove_if(focused)
+    }
+
+    /// Enable or disable the badge, returns `true` if the badge has to be removed
+    pub fn set_enabled(&mut self, enabled: bool) -> bool {
+        self.enabled = enabled;
+        self.remove_if(!enabled)
+    }
+
+    fn remove_if(&mut self, remove: bool) -> bool {
+        if !remove || !self.visible {
+            return false;
+        }
+
+        self.visible = false;
+        true
+    }
 }
 
 /// Threadsafe APIs for the window
+#[derive(Clone)]
 pub struct Proxy {
     inner: glutin::EventsLoopProxy,
 }
@@ -90,6 +578,7 @@ impl ::std::error::Error for Error {
         match *self {
             Error::ContextCreation(ref err) => Some(err),
             Error::Context(ref err) => Some(err),
+            Error::Embed(_) => None,
         }
     }
 
@@ -97,6 +586,7 @@ impl ::std::error::Error for Error {
         match *self {
             Error::ContextCreation(ref _err) => "Error creating gl context",
             Error::Context(ref _err) => "Error operating on render context",
+            Error::Embed(ref _err) => "Error embedding the window",
         }
     }
 }
@@ -106,6 +596,7 @@ impl Display for Error {
         match *self {
             Error::ContextCreation(ref err) => write!(f, "Error creating GL context; {}", err),
             Error::Context(ref err) => write!(f, "Error operating on render context; {}", err),
+            Error::Embed(ref err) => write!(f, "Error embedding the window; {}", err),
         }
     }
 }
@@ -122,10 +613,35 @@ impl From<glutin::ContextError> for Error {
     }
 }
 
+/// Context version required by the renderer
+///
+/// Without an explicit choice, desktop OpenGL is preferred and OpenGL ES is used as fallback.
+fn gl_request(renderer: Option<RendererApi>) -> GlRequest {
+    match renderer {
+        Some(RendererApi::Glsl3) => GlRequest::Specific(Api::OpenGl, (3, 3)),
+        Some(RendererApi::Gles3) => GlRequest::Specific(Api::OpenGlEs, (3, 0)),
+        None => GlRequest::GlThenGles { opengl_version: (3, 3), opengles_version: (3, 0) },
+    }
+}
+
+/// Window system cursor for the icon requested by the terminal
+fn glutin_mouse_cursor(icon: MouseCursorIcon) -> MouseCursor {
+    match icon {
+        MouseCursorIcon::Default => MouseCursor::Default,
+        MouseCursorIcon::Text => MouseCursor::Text,
+        MouseCursorIcon::Hand => MouseCursor::Hand,
+        MouseCursorIcon::Crosshair => MouseCursor::Crosshair,
+        MouseCursorIcon::Wait => MouseCursor::Wait,
+        MouseCursorIcon::NotAllowed => MouseCursor::NotAllowed,
+    }
+}
+
 fn create_gl_window(
     mut window: WindowBuilder,
     event_loop: &EventsLoop,
     srgb: bool,
+    vsync: bool,
+    renderer: Option<RendererApi>,
     dimensions: Option<LogicalSize>,
 ) -> Result<glutin::WindowedContext<PossiblyCurrent>> {
     if let Some(dimensions) = dimensions {
@@ -133,8 +649,9 @@ fn create_gl_window(
     }
 
     let windowed_context = ContextBuilder::new()
+        .with_gl(gl_request(renderer))
         .with_srgb(srgb)
-        .with_vsync(true)
+        .with_vsync(vsync)
         .with_hardware_acceleration(None)
         .build_windowed(window, event_loop)?;
 
@@ -147,35 +664,68 @@ fn create_gl_window(
 impl Window {
     /// Create a new window
     ///
-    /// This creates a window and fully initializes a window.
+    /// This creates a window and fully initializes a window. Events of all windows are received
+    /// through the same `event_loop`.
     pub fn new(
-        event_loop: EventsLoop,
+        event_loop: &EventsLoop,
         config: &Config,
         dimensions: Option<LogicalSize>,
     ) -> Result<Window> {
         let title = config.window.title.as_ref().map_or(DEFAULT_NAME, |t| t);
 
+        // Prefer an sRGB framebuffer when blending should happen in linear space
+        let srgb = config.font.gamma_correct_blending;
+        let vsync = config.debug.vsync();
+        let renderer = config.debug.renderer;
+
         let window_builder = Window::get_platform_window(title, &config.window);
+
+        // macOS uses the icon of the application bundle instead
+        #[cfg(not(target_os = "macos"))]
+        let icon = WindowIcon::load(config.window.icon.as_ref().map(|path| path.as_path()));
+        #[cfg(not(target_os = "macos"))]
+        let window_builder =
+            window_builder.with_window_icon(icon.as_ref().and_then(WindowIcon::to_icon));
         let windowed_context =
-            create_gl_window(window_builder.clone(), &event_loop, false, dimensions)
-                .or_else(|_| create_gl_window(window_builder, &event_loop, true, dimensions))?;
+            create_gl_window(window_builder.clone(), event_loop, srgb, vsync, renderer, dimensions)
+                .or_else(|_| {
+                    create_gl_window(window_builder, event_loop, !srgb, vsync, renderer, dimensions)
+                })?;
+        info!("Created {:?} context", windowed_context.get_api());
         let window = windowed_context.window();
 
         // Text cursor
-        window.set_cursor(MouseCursor::Text);
+        window.set_cursor(glutin_mouse_cursor(MouseCursorIcon::Text));
 
         // Set OpenGL symbol loader. This call MUST be after window.make_current on windows.
         gl::load_with(|symbol| windowed_context.get_proc_address(symbol) as *const _);
 
         let window = Window {
-            event_loop,
-            current_mouse_cursor: MouseCursor::Default,
+            event_proxy: event_loop.create_proxy(),
+            #[cfg(not(any(target_os = "macos", windows)))]
+            is_x11: event_loop.is_x11(),
+            current_mouse_cursor: MouseCursorIcon::Default,
             windowed_context,
             mouse_visible: true,
             is_focused: false,
+            mode: WindowMode {
+                maximized: config.window.startup_mode() == StartupMode::Maximized,
+                ..WindowMode::default()
+            },
+            opacity: WindowOpacity::new(config.window.opacity()),
+            #[cfg(not(target_os = "macos"))]
+            icon,
+            bell_badge: BellBadge::new(config.window.bell_badge),
+            #[cfg(target_os = "macos")]
+            titlebar_color: None,
         };
 
         window.run_os_extensions();
+        window.opacity.reapply(window.window());
+
+        if let Some(parent) = config.window.embed {
+            window.embed(parent)?;
+        }
 
         Ok(window)
     }
@@ -201,23 +751,28 @@ impl Window {
         self.window().get_hidpi_factor()
     }
 
+    /// OpenGL API of the window's context
+    pub fn renderer_api(&self) -> RendererApi {
+        match self.windowed_context.get_api() {
+            Api::OpenGlEs => RendererApi::Gles3,
+            Api::OpenGl | Api::WebGl => RendererApi::Glsl3,
+        }
+    }
+
     #[inline]
     pub fn create_window_proxy(&self) -> Proxy {
-        Proxy { inner: self.event_loop.create_proxy() }
+        Proxy { inner: self.event_proxy.clone() }
     }
 
+    /// Identifier of this window in the events of the event loop
     #[inline]
-    pub fn swap_buffers(&self) -> Result<()> {
-        self.windowed_context.swap_buffers().map_err(From::from)
+    pub fn id(&self) -> WindowId {
+        self.window().id()
     }
 
-    /// Poll for any available events
     #[inline]
-    pub fn poll_events<F>(&mut self, func: F)
-    where
-        F: FnMut(Event),
-    {
-        self.event_loop.poll_events(func);
+    pub fn swap_buffers(&self) -> Result<()> {
+        self.windowed_context.swap_buffers().map_err(From::from)
     }
 
     #[inline]
@@ -231,15 +786,6 @@ impl Window {
         self.window().show();
     }
 
-    /// Block waiting for events
-    #[inline]
-    pub fn wait_events<F>(&mut self, func: F)
-    where
-        F: FnMut(Event) -> ControlFlow,
-    {
-        self.event_loop.run_forever(func);
-    }
-
     /// Set the window title
     #[inline]
     pub fn set_title(&self, title: &str) {
@@ -247,10 +793,10 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
+    pub fn set_mouse_cursor(&mut self, cursor: MouseCursorIcon) {
         if cursor != self.current_mouse_cursor {
             self.current_mouse_cursor = cursor;
-            self.window().set_cursor(cursor);
+            self.window().set_cursor(glutin_mouse_cursor(cursor));
         }
     }
 
@@ -266,14 +812,14 @@ impl Window {
     pub fn get_platform_window(title: &str, window_config: &WindowConfig) -> WindowBuilder {
         use glutin::os::unix::WindowBuilderExt;
 
-        let decorations = match window_config.decorations {
+        log_ignored_macos_options(window_config);
+
+        let decorations = match window_config.decorations() {
             Decorations::None => false,
             _ => true,
         };
 
-        let icon = Icon::from_bytes_with_format(WINDOW_ICON, ImageFormat::ICO);
-
-        let class = &window_config.class;
+        let class = ClassHints::from(&window_config.class);
 
         let mut builder = WindowBuilder::new()
             .with_title(title)
@@ -281,11 +827,8 @@ impl Window {
             .with_transparency(true)
             .with_decorations(decorations)
             .with_maximized(window_config.startup_mode() == StartupMode::Maximized)
-            .with_window_icon(icon.ok())
-            // X11
-            .with_class(class.instance.clone(), class.general.clone())
-            // Wayland
-            .with_app_id(class.instance.clone());
+            .with_class(class.x11_instance, class.x11_general)
+            .with_app_id(class.wayland_app_id);
 
         if let Some(ref val) = window_config.gtk_theme_variant {
             builder = builder.with_gtk_theme_variant(val.clone())
@@ -296,20 +839,19 @@ impl Window {
 
     #[cfg(windows)]
     pub fn get_platform_window(title: &str, window_config: &WindowConfig) -> WindowBuilder {
-        let decorations = match window_config.decorations {
+        log_ignored_macos_options(window_config);
+
+        let decorations = match window_config.decorations() {
             Decorations::None => false,
             _ => true,
         };
 
-        let icon = Icon::from_bytes_with_format(WINDOW_ICON, ImageFormat::ICO);
-
         WindowBuilder::new()
             .with_title(title)
             .with_visibility(cfg!(windows))
             .with_decorations(decorations)
             .with_transparency(true)
             .with_maximized(window_config.startup_mode() == StartupMode::Maximized)
-            .with_window_icon(icon.ok())
     }
 
     #[cfg(target_os = "macos")]
@@ -322,7 +864,7 @@ impl Window {
             .with_transparency(true)
             .with_maximized(window_config.startup_mode() == StartupMode::Maximized);
 
-        match window_config.decorations {
+        match window_config.decorations() {
             Decorations::Full => window,
             Decorations::Transparent => window
                 .with_title_hidden(true)
@@ -337,6 +879,37 @@ impl Window {
         }
     }
 
+    /// Fill the title bar with a color
+    #[cfg(target_os = "macos")]
+    pub fn set_titlebar_color(&mut self, color: Rgb) {
+        use objc::runtime::{Class, Object, YES};
+
+        if self.titlebar_color == Some(color) {
+            return;
+        }
+        self.titlebar_color = Some(color);
+
+        let component = |value: u8| f64::from(value) / 255.;
+        let nswindow = self.window().get_nswindow() as *mut Object;
+        unsafe {
+            let nscolor_class = Class::get("NSColor").expect("NSColor class");
+            let nscolor: *mut Object = msg_send![
+                nscolor_class,
+                colorWithSRGBRed: component(color.r)
+                green: component(color.g)
+                blue: component(color.b)
+                alpha: 1f64
+            ];
+
+            // The window background is only visible through a transparent title bar
+            let _: () = msg_send![nswindow, setTitlebarAppearsTransparent: YES];
+            let _: () = msg_send![nswindow, setBackgroundColor: nscolor];
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn set_titlebar_color(&mut self, _color: Rgb) {}
+
     #[cfg(not(any(target_os = "macos", windows)))]
     pub fn set_urgent(&self, is_urgent: bool) {
         self.window().set_urgent(is_urgent);
@@ -350,6 +923,85 @@ impl Window {
     #[cfg(windows)]
     pub fn set_urgent(&self, _is_urgent: bool) {}
 
+    /// Update the focus state, this removes the bell badge once the window is focused
+    pub fn set_focused(&mut self, is_focused: bool) {
+        self.is_focused = is_focused;
+        if self.bell_badge.focus(is_focused) {
+            self.show_bell_badge(false);
+        }
+    }
+
+    /// Indicate a bell on the window icon if the window is unfocused
+    pub fn ring_bell_badge(&mut self) {
+        if self.bell_badge.ring(self.is_focused) {
+            self.show_bell_badge(true);
+        }
+    }
+
+    pub fn set_bell_badge_enabled(&mut self, enabled: bool) {
+        if self.bell_badge.set_enabled(enabled) {
+            self.show_bell_badge(false);
+        }
+    }
+
+    /// Draw a dot on the window icon
+    ///
+    /// Wayland has no window icons, so there is no badge either.
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn show_bell_badge(&self, visible: bool) {
+        let icon = match (&self.icon, visible) {
+            (Some(icon), true) => icon.with_badge().to_icon(),
+            (Some(icon), false) => icon.to_icon(),
+            (None, _) => return,
+        };
+
+        self.window().set_window_icon(icon);
+    }
+
+    /// Flash the taskbar entry until the window is focused
+    #[cfg(windows)]
+    fn show_bell_badge(&self, visible: bool) {
+        use std::mem;
+
+        use glutin::os::windows::WindowExt;
+        use winapi::um::winuser::{
+            FlashWindowEx, FLASHWINFO, FLASHW_STOP, FLASHW_TIMERNOFG, FLASHW_TRAY,
+        };
+
+        let mut info = FLASHWINFO {
+            cbSize: mem::size_of::<FLASHWINFO>() as u32,
+            hwnd: self.window().get_hwnd() as _,
+            dwFlags: if visible { FLASHW_TRAY | FLASHW_TIMERNOFG } else { FLASHW_STOP },
+            uCount: 0,
+            dwTimeout: 0,
+        };
+
+        unsafe {
+            FlashWindowEx(&mut info);
+        }
+    }
+
+    /// Show a badge on the dock icon
+    #[cfg(target_os = "macos")]
+    fn show_bell_badge(&self, visible: bool) {
+        use objc::runtime::{Class, Object};
+
+        unsafe {
+            let application_class = Class::get("NSApplication").expect("NSApplication class");
+            let application: *mut Object = msg_send![application_class, sharedApplication];
+            let dock_tile: *mut Object = msg_send![application, dockTile];
+
+            let label: *mut Object = if visible {
+                let string_class = Class::get("NSString").expect("NSString class");
+                msg_send![string_class, stringWithUTF8String: BELL_BADGE_LABEL.as_ptr()]
+            } else {
+                std::ptr::null_mut()
+            };
+
+            let _: () = msg_send![dock_tile, setBadgeLabel: label];
+        }
+    }
+
     pub fn set_ime_spot(&self, pos: LogicalPosition) {
         self.window().set_ime_spot(pos);
     }
@@ -368,7 +1020,7 @@ impl Window {
 
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
     pub fn is_x11(&self) -> bool {
-        self.event_loop.is_x11()
+        self.is_x11
     }
 
     #[cfg(any(target_os = "macos", target_os = "windows"))]
@@ -376,29 +1028,111 @@ impl Window {
         None
     }
 
+    /// Reparent the window into a foreign X11 window
+    ///
+    /// The window fills the parent and follows its size, resizes reach the
+    /// display as regular resize events. Once the parent is destroyed, the
+    /// window is destroyed with it.
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn embed(&self, parent: u64) -> Result<()> {
+        use std::mem;
+
+        use x11_dl::xlib;
+
+        let (xlib_window, xlib_display) =
+            match (self.window().get_xlib_window(), self.window().get_xlib_display()) {
+                (Some(xlib_window), Some(xlib_display)) => (xlib_window, xlib_display),
+                _ => return Err(Error::Embed("embedding is only supported on X11".into())),
+            };
+
+        let xlib = xlib::Xlib::open().map_err(|err| Error::Embed(err.to_string()))?;
+
+        unsafe {
+            let display = xlib_display as *mut xlib::Display;
+
+            let mut attributes: xlib::XWindowAttributes = mem::zeroed();
+            if (xlib.XGetWindowAttributes)(display, parent as _, &mut attributes) == 0 {
+                return Err(Error::Embed(format!("window {:#x} does not exist", parent)));
+            }
+
+            (xlib.XReparentWindow)(display, xlib_window as _, parent as _, 0, 0);
+            (xlib.XResizeWindow)(
+                display,
+                xlib_window as _,
+                attributes.width as _,
+                attributes.height as _,
+            );
+            (xlib.XSync)(display, 0);
+
+            // Parent events are watched on a separate connection, since the
+            // event loop only handles events of its own windows
+            let name = CStr::from_ptr((xlib.XDisplayString)(display)).to_owned();
+            crate::util::thread::spawn_named("embed parent watcher", move || {
+                follow_parent(&name, parent as _, xlib_window as _);
+            });
+        }
+
+        info!("Embedded window into {:#x}", parent);
+
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "macos", windows))]
+    fn embed(&self, _parent: u64) -> Result<()> {
+        Err(Error::Embed("embedding is only supported on X11".into()))
+    }
+
     /// Hide the window
     pub fn hide(&self) {
         self.window().hide();
     }
 
     /// Fullscreens the window on the current monitor.
-    pub fn set_fullscreen(&self, fullscreen: bool) {
-        let glutin_window = self.window();
-        if fullscreen {
-            let current_monitor = glutin_window.get_current_monitor();
-            glutin_window.set_fullscreen(Some(current_monitor));
-        } else {
-            glutin_window.set_fullscreen(None);
-        }
+    ///
+    /// Returns the restored window size after leaving fullscreen.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) -> Option<LogicalSize> {
+        let window = self.windowed_context.window();
+        let restored_size = self.mode.set_fullscreen(window, fullscreen);
+        self.opacity.reapply(window);
+        restored_size
     }
 
-    pub fn set_maximized(&self, maximized: bool) {
-        self.window().set_maximized(maximized);
+    pub fn toggle_fullscreen(&mut self) -> Option<LogicalSize> {
+        let fullscreen = !self.mode.fullscreen;
+        self.set_fullscreen(fullscreen)
+    }
+
+    pub fn set_maximized(&mut self, maximized: bool) {
+        self.mode.set_maximized(self.windowed_context.window(), maximized);
+    }
+
+    pub fn toggle_maximized(&mut self) {
+        let maximized = !self.mode.is_maximized();
+        self.set_maximized(maximized);
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn set_simple_fullscreen(&mut self, fullscreen: bool) -> Option<LogicalSize> {
+        let window = self.windowed_context.window();
+        let restored_size = self.mode.set_simple_fullscreen(window, fullscreen);
+        self.opacity.reapply(window);
+        restored_size
     }
 
     #[cfg(target_os = "macos")]
-    pub fn set_simple_fullscreen(&self, fullscreen: bool) {
-        self.window().set_simple_fullscreen(fullscreen);
+    pub fn toggle_simple_fullscreen(&mut self) -> Option<LogicalSize> {
+        let fullscreen = !self.mode.simple_fullscreen;
+        self.set_simple_fullscreen(fullscreen)
+    }
+
+    /// Change the opacity of the entire window
+    pub fn change_opacity(&mut self, change: OpacityChange) {
+        self.opacity.change(self.windowed_context.window(), change);
+    }
+
+    /// Update the window opacity after the configuration changed
+    pub fn set_configured_opacity(&mut self, opacity: f32) {
+        self.opacity.set_configured(self.windowed_context.window(), opacity);
     }
 
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
@@ -422,7 +1156,6 @@ impl OsExtensions for Window {}
 impl OsExtensions for Window {
     fn run_os_extensions(&self) {
         use libc::getpid;
-        use std::ffi::CStr;
         use std::ptr;
         use x11_dl::xlib::{self, PropModeReplace, XA_CARDINAL};
 
@@ -469,12 +1202,451 @@ impl OsExtensions for Window {
     }
 }
 
+/// Resize an embedded window whenever its parent window is resized
+///
+/// Returns once the parent window has been destroyed.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn follow_parent(display_name: &CStr, parent: c_ulong, child: c_ulong) {
+    use std::mem;
+
+    use x11_dl::xlib;
+
+    let xlib = match xlib::Xlib::open() {
+        Ok(xlib) => xlib,
+        Err(err) => {
+            warn!("Unable to follow the size of the parent window: {}", err);
+            return;
+        },
+    };
+
+    unsafe {
+        let display = (xlib.XOpenDisplay)(display_name.as_ptr());
+        if display.is_null() {
+            warn!("Unable to follow the size of the parent window: cannot open display");
+            return;
+        }
+
+        (xlib.XSelectInput)(display, parent, xlib::StructureNotifyMask);
+
+        // The parent might have been resized before its events were selected
+        let mut attributes: xlib::XWindowAttributes = mem::zeroed();
+        if (xlib.XGetWindowAttributes)(display, parent, &mut attributes) != 0 {
+            (xlib.XResizeWindow)(display, child, attributes.width as _, attributes.height as _);
+            (xlib.XFlush)(display);
+        }
+
+        let mut event: xlib::XEvent = mem::zeroed();
+        loop {
+            (xlib.XNextEvent)(display, &mut event);
+
+            match event.get_type() {
+                xlib::ConfigureNotify if event.configure.window == parent => {
+                    let (width, height) = (event.configure.width, event.configure.height);
+                    (xlib.XResizeWindow)(display, child, width as _, height as _);
+                    (xlib.XFlush)(display);
+                },
+                xlib::DestroyNotify if event.destroy_window.window == parent => break,
+                _ => (),
+            }
+        }
+
+        (xlib.XCloseDisplay)(display);
+    }
+}
+
 impl Proxy {
     /// Wakes up the event loop of the window
     ///
     /// This is useful for triggering a draw when the renderer would otherwise
     /// be waiting on user input.
     pub fn wakeup_event_loop(&self) {
-        self.inner.wakeup().unwrap();
+        // The event loop is only gone during shutdown, when nothing has to be drawn anymore
+        let _ = self.inner.wakeup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    #[cfg(not(target_os = "macos"))]
+    use std::path::Path;
+
+    use glutin::dpi::LogicalSize;
+    use glutin::{Api, GlRequest, MouseCursor};
+    #[cfg(not(target_os = "macos"))]
+    use image::DynamicImage;
+
+    use crate::ansi::MouseCursorIcon;
+    #[cfg(not(any(target_os = "macos", windows)))]
+    use crate::config::Class;
+    #[cfg(not(any(target_os = "macos", windows)))]
+    use crate::config::DEFAULT_NAME;
+    use crate::config::RendererApi;
+    use crate::term::OpacityChange;
+    use crate::window::{
+        gl_request, glutin_mouse_cursor, step_opacity, BellBadge, ModeControl, OpacityControl,
+        WindowMode, WindowOpacity,
+    };
+    #[cfg(not(any(target_os = "macos", windows)))]
+    use crate::window::{x11_opacity, ClassHints};
+    #[cfg(not(target_os = "macos"))]
+    use crate::window::{WindowIcon, BELL_BADGE_COLOR, ICON_MAX_SIZE};
+
+    #[derive(Default)]
+    struct MockWindow {
+        size: Cell<Option<LogicalSize>>,
+        fullscreen: Cell<bool>,
+        simple_fullscreen: Cell<bool>,
+        maximized: Cell<bool>,
+        opacity: Cell<Option<f32>>,
+        opacity_unsupported: bool,
+    }
+
+    impl MockWindow {
+        fn new(width: f64, height: f64) -> Self {
+            let window = MockWindow::default();
+            window.size.set(Some(LogicalSize::new(width, height)));
+            window
+        }
+    }
+
+    impl ModeControl for MockWindow {
+        fn inner_size(&self) -> Option<LogicalSize> {
+            if self.fullscreen.get() || self.simple_fullscreen.get() {
+                Some(LogicalSize::new(1920., 1080.))
+            } else {
+                self.size.get()
+            }
+        }
+
+        fn set_inner_size(&self, size: LogicalSize) {
+            self.size.set(Some(size));
+        }
+
+        fn set_fullscreen(&self, fullscreen: bool) {
+            self.fullscreen.set(fullscreen);
+        }
+
+        fn set_maximized(&self, maximized: bool) {
+            self.maximized.set(maximized);
+        }
+
+        #[cfg(target_os = "macos")]
+        fn set_simple_fullscreen(&self, fullscreen: bool) {
+            self.simple_fullscreen.set(fullscreen);
+        }
+    }
+
+    impl OpacityControl for MockWindow {
+        fn set_opacity(&self, opacity: f32) -> bool {
+            if self.opacity_unsupported {
+                return false;
+            }
+
+            self.opacity.set(Some(opacity));
+            true
+        }
+    }
+
+    #[test]
+    fn gl_request_fallback() {
+        assert_eq!(
+            gl_request(None),
+            GlRequest::GlThenGles { opengl_version: (3, 3), opengles_version: (3, 0) }
+        );
+    }
+
+    #[test]
+    fn gl_request_override() {
+        assert_eq!(gl_request(Some(RendererApi::Glsl3)), GlRequest::Specific(Api::OpenGl, (3, 3)));
+        assert_eq!(
+            gl_request(Some(RendererApi::Gles3)),
+            GlRequest::Specific(Api::OpenGlEs, (3, 0))
+        );
+    }
+
+    #[test]
+    fn mouse_cursor_icons() {
+        assert_eq!(glutin_mouse_cursor(MouseCursorIcon::Default), MouseCursor::Default);
+        assert_eq!(glutin_mouse_cursor(MouseCursorIcon::Text), MouseCursor::Text);
+        assert_eq!(glutin_mouse_cursor(MouseCursorIcon::Hand), MouseCursor::Hand);
+        assert_eq!(glutin_mouse_cursor(MouseCursorIcon::Crosshair), MouseCursor::Crosshair);
+        assert_eq!(glutin_mouse_cursor(MouseCursorIcon::Wait), MouseCursor::Wait);
+        assert_eq!(glutin_mouse_cursor(MouseCursorIcon::NotAllowed), MouseCursor::NotAllowed);
+    }
+
+    #[test]
+    fn fullscreen_restores_windowed_size() {
+        let window = MockWindow::new(800., 600.);
+        let mut mode = WindowMode::default();
+
+        assert_eq!(mode.set_fullscreen(&window, true), None);
+        assert!(window.fullscreen.get());
+        assert!(mode.is_fullscreen());
+
+        // The window manager might change the size while the window is fullscreen
+        window.size.set(Some(LogicalSize::new(100., 100.)));
+
+        assert_eq!(mode.set_fullscreen(&window, false), Some(LogicalSize::new(800., 600.)));
+        assert!(!window.fullscreen.get());
+        assert!(!mode.is_fullscreen());
+        assert_eq!(window.size.get(), Some(LogicalSize::new(800., 600.)));
+    }
+
+    #[test]
+    fn repeated_fullscreen_keeps_windowed_size() {
+        let window = MockWindow::new(800., 600.);
+        let mut mode = WindowMode::default();
+
+        mode.set_fullscreen(&window, true);
+        mode.set_fullscreen(&window, true);
+
+        assert_eq!(mode.set_fullscreen(&window, false), Some(LogicalSize::new(800., 600.)));
+        assert_eq!(mode.set_fullscreen(&window, false), None);
+    }
+
+    #[test]
+    fn maximize_while_windowed() {
+        let window = MockWindow::new(800., 600.);
+        let mut mode = WindowMode::default();
+
+        mode.set_maximized(&window, true);
+        assert!(window.maximized.get());
+        assert!(mode.is_maximized());
+
+        mode.set_maximized(&window, false);
+        assert!(!window.maximized.get());
+        assert!(!mode.is_maximized());
+    }
+
+    #[test]
+    fn maximize_while_fullscreen_is_deferred() {
+        let window = MockWindow::new(800., 600.);
+        let mut mode = WindowMode::default();
+
+        mode.set_fullscreen(&window, true);
+        mode.set_maximized(&window, true);
+        assert!(!window.maximized.get());
+
+        // Maximized windows are not restored to their previous size
+        assert_eq!(mode.set_fullscreen(&window, false), None);
+        assert!(window.maximized.get());
+    }
+
+    #[test]
+    fn leaving_fullscreen_keeps_maximized() {
+        let window = MockWindow::new(800., 600.);
+        let mut mode = WindowMode::default();
+
+        mode.set_maximized(&window, true);
+        mode.set_fullscreen(&window, true);
+
+        assert_eq!(mode.set_fullscreen(&window, false), None);
+        assert!(window.maximized.get());
+        assert!(mode.is_maximized());
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn simple_fullscreen_excludes_fullscreen() {
+        let window = MockWindow::new(800., 600.);
+        let mut mode = WindowMode::default();
+
+        mode.set_simple_fullscreen(&window, true);
+        assert_eq!(mode.set_fullscreen(&window, true), None);
+        assert!(!window.fullscreen.get());
+
+        assert_eq!(mode.set_simple_fullscreen(&window, false), Some(LogicalSize::new(800., 600.)));
+
+        mode.set_fullscreen(&window, true);
+        assert_eq!(mode.set_simple_fullscreen(&window, true), None);
+        assert!(!window.simple_fullscreen.get());
+    }
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    #[test]
+    fn default_class_hints() {
+        let hints = ClassHints::from(&Class::default());
+
+        assert_eq!(hints.x11_instance, DEFAULT_NAME);
+        assert_eq!(hints.x11_general, DEFAULT_NAME);
+        assert_eq!(hints.wayland_app_id, DEFAULT_NAME);
+    }
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    #[test]
+    fn custom_class_hints() {
+        let class = Class { instance: "foo".into(), general: "Bar".into() };
+
+        assert_eq!(ClassHints::from(&class), ClassHints {
+            x11_instance: "foo".into(),
+            x11_general: "Bar".into(),
+            wayland_app_id: "foo".into(),
+        });
+    }
+
+    #[test]
+    fn step_opacity_clamps() {
+        assert_eq!(step_opacity(1., 0.1), 1.);
+        assert_eq!(step_opacity(0.7, -0.1), 0.6);
+        assert_eq!(step_opacity(0.15, -0.1), 0.1);
+        assert_eq!(step_opacity(0.1, -0.1), 0.1);
+    }
+
+    #[test]
+    fn step_opacity_below_minimum() {
+        assert_eq!(step_opacity(0.05, -0.1), 0.05);
+        assert_eq!(step_opacity(0.05, 0.1), 0.15);
+    }
+
+    #[test]
+    fn opacity_change_applied_to_window() {
+        let window = MockWindow::default();
+        let mut opacity = WindowOpacity::new(1.);
+
+        opacity.change(&window, OpacityChange::Step(-0.1));
+        assert_eq!(window.opacity.get(), Some(0.9));
+
+        opacity.change(&window, OpacityChange::Set(2.));
+        assert_eq!(window.opacity.get(), Some(1.));
+
+        opacity.change(&window, OpacityChange::Set(0.));
+        assert_eq!(window.opacity.get(), Some(0.1));
+
+        opacity.change(&window, OpacityChange::Set(0.5));
+        opacity.change(&window, OpacityChange::Reset);
+        assert_eq!(window.opacity.get(), Some(1.));
+    }
+
+    #[test]
+    fn unchanged_opacity_not_applied() {
+        let window = MockWindow::default();
+        let mut opacity = WindowOpacity::new(1.);
+
+        opacity.change(&window, OpacityChange::Step(0.1));
+        opacity.change(&window, OpacityChange::Reset);
+
+        assert_eq!(window.opacity.get(), None);
+    }
+
+    #[test]
+    fn unsupported_opacity_is_noop() {
+        let window = MockWindow { opacity_unsupported: true, ..MockWindow::default() };
+        let mut opacity = WindowOpacity::new(1.);
+
+        opacity.change(&window, OpacityChange::Step(-0.1));
+        opacity.reapply(&window);
+
+        assert_eq!(window.opacity.get(), None);
+        assert_eq!(opacity.get(), 0.9);
+    }
+
+    #[test]
+    fn configured_opacity_replaces_runtime_changes() {
+        let window = MockWindow::default();
+        let mut opacity = WindowOpacity::new(0.8);
+
+        opacity.change(&window, OpacityChange::Step(-0.1));
+        opacity.set_configured(&window, 0.8);
+        assert_eq!(opacity.get(), 0.7);
+
+        opacity.set_configured(&window, 0.9);
+        assert_eq!(opacity.get(), 0.9);
+        assert_eq!(window.opacity.get(), Some(0.9));
+    }
+
+    #[test]
+    fn opacity_reapplied_after_fullscreen() {
+        let window = MockWindow::new(800., 600.);
+        let mut mode = WindowMode::default();
+        let opacity = WindowOpacity::new(0.8);
+
+        mode.set_fullscreen(&window, true);
+        opacity.reapply(&window);
+        assert_eq!(window.opacity.get(), Some(0.8));
+
+        // Opaque windows don't need to be updated
+        window.opacity.set(None);
+        WindowOpacity::new(1.).reapply(&window);
+        assert_eq!(window.opacity.get(), None);
+    }
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    #[test]
+    fn x11_opacity_property() {
+        assert_eq!(x11_opacity(1.), None);
+        assert_eq!(x11_opacity(0.5), Some(0x7fff_ffff));
+        assert_eq!(x11_opacity(0.), Some(0));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn default_icon_loads() {
+        let icon = WindowIcon::load(None).unwrap();
+        let (width, height) = icon.image.dimensions();
+
+        assert!(width > 0 && width <= ICON_MAX_SIZE);
+        assert!(height > 0 && height <= ICON_MAX_SIZE);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn missing_icon_falls_back_to_default() {
+        let icon = WindowIcon::load(Some(Path::new("/nonexistent/alacritty-icon.png")));
+        assert_eq!(icon, WindowIcon::load(None));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn large_icon_scaled_down() {
+        let icon = WindowIcon::from_image(DynamicImage::new_rgba8(1024, 512));
+        assert_eq!(icon.image.dimensions(), (ICON_MAX_SIZE, ICON_MAX_SIZE / 2));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn small_icon_keeps_size() {
+        let icon = WindowIcon::from_image(DynamicImage::new_rgba8(32, 16));
+        assert_eq!(icon.image.dimensions(), (32, 16));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn badge_drawn_in_top_right_corner() {
+        let icon = WindowIcon::from_image(DynamicImage::new_rgba8(16, 16));
+        let badge = icon.with_badge();
+
+        assert_eq!(badge.image.get_pixel(12, 4).data, BELL_BADGE_COLOR);
+        assert_eq!(badge.image.get_pixel(3, 12).data, [0; 4]);
+        assert_eq!(badge.image.get_pixel(15, 0).data, [0; 4]);
+        assert_eq!(icon.image.get_pixel(12, 4).data, [0; 4]);
+    }
+
+    #[test]
+    fn bell_badge_while_unfocused() {
+        let mut badge = BellBadge::new(true);
+
+        assert!(!badge.ring(true));
+        assert!(badge.ring(false));
+        assert!(!badge.ring(false));
+
+        // Only focusing the window removes the badge
+        assert!(!badge.focus(false));
+        assert!(badge.focus(true));
+        assert!(!badge.focus(true));
+
+        assert!(badge.ring(false));
+    }
+
+    #[test]
+    fn disabled_bell_badge() {
+        let mut badge = BellBadge::new(false);
+        assert!(!badge.ring(false));
+
+        let mut badge = BellBadge::new(true);
+        assert!(badge.ring(false));
+        assert!(badge.set_enabled(false));
+        assert!(!badge.ring(false));
+        assert!(!badge.focus(true));
     }
 }
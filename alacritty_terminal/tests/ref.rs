@@ -3,14 +3,14 @@ extern crate serde_derive;
 use serde_json as json;
 
 use std::fs::File;
-use std::io::{self, Read};
+use std::io::Read;
 use std::path::Path;
 
-use alacritty_terminal::ansi;
 use alacritty_terminal::clipboard::Clipboard;
 use alacritty_terminal::config::Config;
 use alacritty_terminal::index::Column;
 use alacritty_terminal::message_bar::MessageBuffer;
+use alacritty_terminal::recording::{self, Event, State};
 use alacritty_terminal::term::cell::Cell;
 use alacritty_terminal::term::SizeInfo;
 use alacritty_terminal::Grid;
@@ -36,6 +36,7 @@ ref_tests! {
     issue_855
     ll
     newline_with_cursor_beyond_scroll_region
+    resize
     tab_rendering
     tmux_git_log
     tmux_htop
@@ -82,7 +83,7 @@ struct RefConfig {
 }
 
 fn ref_test(dir: &Path) {
-    let recording = read_u8(dir.join("alacritty.recording"));
+    let recording = recording::parse(&read_u8(dir.join("alacritty.recording"))).unwrap();
     let serialized_size = read_string(dir.join("size.json")).unwrap();
     let serialized_grid = read_string(dir.join("grid.json")).unwrap();
     let serialized_cfg = read_string(dir.join("config.json")).unwrap_or_default();
@@ -95,10 +96,26 @@ fn ref_test(dir: &Path) {
     config.scrolling.set_history(ref_config.history_size);
 
     let mut terminal = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
-    let mut parser = ansi::Processor::new();
+    recording::replay(&mut terminal, &recording);
 
-    for byte in recording {
-        parser.advance(&mut terminal, byte, &mut io::sink());
+    // Recordings with resizes also contain the colors and modes
+    if let Some(Event::State(state)) = recording.last() {
+        assert_eq!(State::from(&terminal), *state);
+    }
+
+    // The final state survives saving and restoring the session
+    #[cfg(feature = "session")]
+    {
+        let size = *terminal.size_info();
+        let mut session = Vec::new();
+        terminal.serialize_state(&mut session).unwrap();
+
+        let mut restored = Term::new(&config, size, MessageBuffer::new(), Clipboard::new_nop());
+        restored.restore_state(&session[..], &size).unwrap();
+
+        assert_eq!(restored.grid(), terminal.grid());
+        assert_eq!(restored.cursor().point, terminal.cursor().point);
+        assert_eq!(State::from(&restored), State::from(&terminal));
     }
 
     // Truncate invisible lines from the grid
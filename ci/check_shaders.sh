@@ -0,0 +1,19 @@
+#!/bin/bash
+
+# Compile all shaders with the headers the renderer prepends for each OpenGL API
+set -e
+
+GLSL3_HEADER='#version 330 core\n'
+GLES3_HEADER='#version 300 es\nprecision highp float;\nprecision highp int;\n#define GLES\n'
+
+for header in "$GLSL3_HEADER" "$GLES3_HEADER"; do
+    for shader in res/*.glsl; do
+        case "$shader" in
+            *.v.glsl) stage=vert ;;
+            *.f.glsl) stage=frag ;;
+        esac
+
+        echo "Checking $shader ($(printf "$header" | head -n 1))"
+        { printf "$header"; cat "$shader"; } | glslangValidator --stdin -S "$stage"
+    done
+done
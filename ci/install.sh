@@ -9,3 +9,8 @@ fi
 if [ "$RUSTFMT" == "true" ]; then
     rustup component add rustfmt
 fi
+
+# Add the reference compiler for shader validation
+if [ "$SHADERS" == "true" ]; then
+    sudo apt-get install -y glslang-tools
+fi
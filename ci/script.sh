@@ -12,6 +12,12 @@ if [ "$RUSTFMT" == "true" ]; then
     exit
 fi
 
+# Compile the shaders for all supported OpenGL APIs
+if [ "$SHADERS" == "true" ]; then
+    ci/check_shaders.sh
+    exit
+fi
+
 # Run test in release mode if a tag is present, to produce an optimized binary
 if [ -n "$TRAVIS_TAG" ]; then
     # Build separately so we generate an 'alacritty' binary without -HASH appended
@@ -19,4 +25,7 @@ if [ -n "$TRAVIS_TAG" ]; then
     cargo test --release
 else
     cargo test
+
+    # Make sure the terminal emulation still works without the renderer and window
+    cargo test -p alacritty_terminal --no-default-features
 fi
@@ -18,10 +18,18 @@ complete -c alacritty \
 complete -c alacritty \
   -l "persistent-logging" \
   -d "Keep the log file after quitting Alacritty"
+complete -c alacritty \
+  -l "hold" \
+  -d "Remain open after the command exits"
 complete -c alacritty \
   -f \
   -l "config-file" \
   -d "Specify an alternative config file"
+complete -c alacritty \
+  -x \
+  -s "o" \
+  -l "option" \
+  -d "Override a config file option"
 complete -c alacritty \
   -s "t" \
   -l "title" \
@@ -29,6 +37,10 @@ complete -c alacritty \
 complete -c alacritty \
   -l "class" \
   -d "Defines the window class"
+complete -c alacritty \
+  -x \
+  -l "embed" \
+  -d "Embed into the X11 window with this ID"
 complete -c alacritty \
   -x \
   -a '(__fish_complete_directories (commandline -ct))' \
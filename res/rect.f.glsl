@@ -11,9 +11,8 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-#version 330 core
 
-uniform vec4 color;
+flat in vec4 color;
 
 out vec4 FragColor;
 
@@ -11,10 +11,21 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-#version 330 core
-layout (location = 0) in vec2 aPos;
+// Rectangle position and size in normalized device coordinates
+layout (location = 0) in vec4 rect;
+
+// Rectangle color
+layout (location = 1) in vec4 rectColor;
+
+flat out vec4 color;
 
 void main()
 {
-    gl_Position = vec4(aPos.x, aPos.y, 0.0, 1.0);
+    // Compute vertex corner position
+    vec2 position;
+    position.x = (gl_VertexID == 0 || gl_VertexID == 1) ? 1. : 0.;
+    position.y = (gl_VertexID == 0 || gl_VertexID == 3) ? 0. : 1.;
+
+    gl_Position = vec4(rect.x + rect.z * position.x, rect.y - rect.w * position.y, 0.0, 1.0);
+    color = rectColor;
 }
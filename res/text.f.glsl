@@ -11,14 +11,17 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-#version 330 core
 in vec2 TexCoords;
 flat in vec3 fg;
 flat in vec4 bg;
 uniform int backgroundPass;
 
+#if defined(GLES)
+out vec4 color;
+#else
 layout(location = 0, index = 0) out vec4 color;
 layout(location = 0, index = 1) out vec4 alphaMask;
+#endif
 
 uniform sampler2D mask;
 
@@ -28,11 +31,19 @@ void main()
         if (bg.a == 0.0)
             discard;
 
+#if !defined(GLES)
         alphaMask = vec4(1.0);
+#endif
         color = vec4(bg.rgb, 1.0);
     } else {
         vec3 textColor = texture(mask, TexCoords).rgb;
+#if defined(GLES)
+        // Without dual-source blending the subpixel mask is reduced to a single coverage value
+        float coverage = (textColor.r + textColor.g + textColor.b) / 3.0;
+        color = vec4(fg, coverage);
+#else
         alphaMask = vec4(textColor, textColor.r);
         color = vec4(fg, 1.0);
+#endif
     }
 }
@@ -11,7 +11,6 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-#version 330 core
 // Cell properties
 layout (location = 0) in vec2 gridCoords;
 
@@ -37,6 +36,16 @@ uniform vec4 projection;
 
 uniform int backgroundPass;
 
+// Blend in linear color space
+uniform int gammaCorrect;
+
+// Decode sRGB color components into linear space
+vec3 srgbToLinear(vec3 color)
+{
+    vec3 low = color / 12.92;
+    vec3 high = pow((color + 0.055) / 1.055, vec3(2.4));
+    return mix(low, high, step(vec3(0.04045), color));
+}
 
 void main()
 {
@@ -71,4 +80,9 @@ void main()
 
     bg = vec4(backgroundColor.rgb / 255.0, backgroundColor.a);
     fg = textColor / vec3(255.0, 255.0, 255.0);
+
+    if (gammaCorrect != 0) {
+        bg.rgb = srgbToLinear(bg.rgb);
+        fg = srgbToLinear(fg);
+    }
 }
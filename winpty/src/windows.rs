@@ -234,35 +234,38 @@ impl<'a, 'b> Winpty<'a> {
         }
     }
 
-    /// Spawns the new process.
+    /// Spawns the new process and returns a handle to it.
     ///
     /// spawn can only be called once per Winpty object.  If it is called
     /// before the output data pipe(s) is/are connected, then collected output is
     /// buffered until the pipes are connected, rather than being discarded.
     /// (https://blogs.msdn.microsoft.com/oldnewthing/20110107-00/?p=11803)
-    // TODO: Support getting the process and thread handle of the spawned process (Not the agent)
+    ///
+    /// The caller owns the process handle and has to close it.
+    // TODO: Support getting the thread handle of the spawned process
     // TODO: Support returning the error from CreateProcess
-    pub fn spawn(&mut self, cfg: &SpawnConfig) -> Result<(), Err> {
+    pub fn spawn(&mut self, cfg: &SpawnConfig) -> Result<RawHandle, Err> {
         let mut err = null_mut() as *mut winpty_error_t;
+        let mut process = null_mut();
 
         unsafe {
             let ok = winpty_spawn(
                 self.0,
                 cfg.0 as *const winpty_spawn_config_s,
-                null_mut(), // Process handle
-                null_mut(), // Thread handle
-                null_mut(), // Create process error
+                &mut process, // Process handle
+                null_mut(),   // Thread handle
+                null_mut(),   // Create process error
                 &mut err,
             );
             if ok == 0 {
-                return Ok(());
+                return Ok(process);
             }
         }
 
         if let Some(err) = check_err(err) {
             Result::Err(err)
         } else {
-            Ok(())
+            Ok(process)
         }
     }
 }